@@ -0,0 +1,66 @@
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use rmemimg::memimg::bank::{AccountId, Bank, BankCommand};
+use rmemimg::memimg::{EventStorage, MemImgProcessor};
+
+const ACCOUNTS: usize = 1000;
+
+// Storage that discards events so the benchmark measures apply and shadow-copy cost only
+struct NullEventStorage;
+
+impl EventStorage for NullEventStorage {
+    type Event = BankCommand;
+
+    fn replay<F>(&mut self, _consumer: &mut F) -> Result<(), Box<dyn std::error::Error + Send + Sync>>
+    where
+        F: FnMut(Self::Event) -> Result<(), Box<dyn std::error::Error + Send + Sync>>,
+    {
+        Ok(())
+    }
+
+    fn append(&mut self, _event: &Self::Event) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        Ok(())
+    }
+}
+
+fn account_pairs() -> Vec<(AccountId, String)> {
+    (0..ACCOUNTS)
+        .map(|i| (format!("acc{}", i).into(), format!("Customer {}", i)))
+        .collect()
+}
+
+fn processor() -> MemImgProcessor<Bank, BankCommand, NullEventStorage> {
+    MemImgProcessor::new_simple(Bank::new(), Box::new(NullEventStorage)).unwrap()
+}
+
+fn bulk_create(c: &mut Criterion) {
+    let mut group = c.benchmark_group("create_1000_accounts");
+
+    group.bench_function("individual_create_account", |b| {
+        b.iter_batched(
+            || (processor(), account_pairs()),
+            |(mut processor, accounts)| {
+                for (id, name) in accounts {
+                    processor.execute_command(BankCommand::CreateAccount { id, name, opening_balance: None }).unwrap();
+                }
+                processor
+            },
+            BatchSize::SmallInput,
+        )
+    });
+
+    group.bench_function("bulk_create_accounts", |b| {
+        b.iter_batched(
+            || (processor(), account_pairs()),
+            |(mut processor, accounts)| {
+                processor.execute_command(BankCommand::BulkCreateAccounts { accounts }).unwrap();
+                processor
+            },
+            BatchSize::SmallInput,
+        )
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, bulk_create);
+criterion_main!(benches);
@@ -0,0 +1,88 @@
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use rmemimg::memimg::bank::{Amount, Bank, BankCommand, LedgerEntry};
+use rmemimg::memimg::{Command, EventStorage, MemImgProcessor};
+use std::collections::HashMap;
+
+const ACCOUNTS: usize = 100_000;
+
+// Storage that discards events so the benchmark measures apply and shadow-copy cost only
+struct NullEventStorage;
+
+impl EventStorage for NullEventStorage {
+    type Event = BankCommand;
+
+    fn replay<F>(&mut self, _consumer: &mut F) -> Result<(), Box<dyn std::error::Error + Send + Sync>>
+    where
+        F: FnMut(Self::Event) -> Result<(), Box<dyn std::error::Error + Send + Sync>>,
+    {
+        Ok(())
+    }
+
+    fn append(&mut self, _event: &Self::Event) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        Ok(())
+    }
+}
+
+// The account layout before ids were shared: every clone reallocates the key and the `id` field
+#[derive(Clone)]
+#[allow(dead_code)]
+struct StringIdAccount {
+    id: String,
+    name: String,
+    total_debits: Amount,
+    total_credits: Amount,
+    owners: Vec<String>,
+}
+
+fn populated_bank() -> Bank {
+    let entries = (0..ACCOUNTS)
+        .map(|i| LedgerEntry { account_id: format!("acc{}", i).into(), name: format!("Customer {}", i), balance: Amount::from(100) })
+        .collect();
+    let mut bank = Bank::new();
+    BankCommand::ImportLedger { entries }.apply_to(&mut bank).unwrap();
+    bank
+}
+
+fn string_id_accounts(bank: &Bank) -> HashMap<String, StringIdAccount> {
+    bank.accounts
+        .values()
+        .map(|account| {
+            let copy = StringIdAccount {
+                id: account.id.to_string(),
+                name: account.name.clone(),
+                total_debits: account.total_debits,
+                total_credits: account.total_credits,
+                owners: account.owners.clone(),
+            };
+            (account.id.to_string(), copy)
+        })
+        .collect()
+}
+
+fn shadow_copy(c: &mut Criterion) {
+    let bank = populated_bank();
+    let string_ids = string_id_accounts(&bank);
+
+    let mut group = c.benchmark_group("shadow_copy_100k_accounts");
+    group.sample_size(10);
+
+    group.bench_function("clone_string_ids", |b| b.iter(|| string_ids.clone()));
+    group.bench_function("clone_shared_ids", |b| b.iter(|| bank.clone()));
+    group.bench_function("execute_deposit", |b| {
+        b.iter_batched(
+            || MemImgProcessor::new_simple(bank.clone(), Box::new(NullEventStorage)).unwrap(),
+            |mut processor| {
+                processor
+                    .execute_command(BankCommand::Deposit { account_id: "acc500".into(), amount: Amount::from(1) })
+                    .unwrap();
+                processor
+            },
+            BatchSize::LargeInput,
+        )
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, shadow_copy);
+criterion_main!(benches);
@@ -0,0 +1,32 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use rmemimg::memimg::bank::{Amount, Bank, BankCommand, GetBalance, LedgerEntry};
+use rmemimg::memimg::bank_sorted::SortedBank;
+use rmemimg::memimg::{Command, Query};
+use std::hint::black_box;
+
+const ACCOUNTS: usize = 100_000;
+
+fn populated_bank() -> Bank {
+    let entries = (0..ACCOUNTS)
+        .map(|i| LedgerEntry { account_id: format!("acc{}", i).into(), name: format!("Customer {}", i), balance: Amount::from(100) })
+        .collect();
+    let mut bank = Bank::new();
+    BankCommand::ImportLedger { entries }.apply_to(&mut bank).unwrap();
+    bank
+}
+
+fn sorted_lookup(c: &mut Criterion) {
+    let bank = populated_bank();
+    let sorted = SortedBank::from(&bank);
+    let query = GetBalance { account_id: "acc50000".into() };
+
+    let mut group = c.benchmark_group("get_balance_100k_accounts");
+
+    group.bench_function("hash_map", |b| b.iter(|| query.extract_from(black_box(&bank)).unwrap()));
+    group.bench_function("btree_map", |b| b.iter(|| black_box(&sorted).balance("acc50000").unwrap()));
+
+    group.finish();
+}
+
+criterion_group!(benches, sorted_lookup);
+criterion_main!(benches);
@@ -0,0 +1,13 @@
+fn main() {
+    // The C client in tests/c exercises the FFI; it is linked into the crate so
+    // tests/ffi_tests.rs can run it
+    #[cfg(feature = "ffi")]
+    {
+        println!("cargo:rerun-if-changed=tests/c/ffi_client.c");
+        println!("cargo:rerun-if-changed=include/rmemimg.h");
+        cc::Build::new()
+            .file("tests/c/ffi_client.c")
+            .include("include")
+            .compile("rmemimg_ffi_client");
+    }
+}
@@ -0,0 +1,35 @@
+use rmemimg::memimg::bank::Bank;
+use rmemimg::memimg::bank_http::serve;
+use rmemimg::memimg::bank_storage::BankJsonConverter;
+use rmemimg::memimg::{MemImgProcessor, TextFileEventStorage};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let storage = Box::new(TextFileEventStorage::new("bank_events.json", BankJsonConverter::new())?);
+    let processor = Arc::new(Mutex::new(MemImgProcessor::new_simple(Bank::new(), storage)?));
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:3000").await?;
+    println!("Listening on http://{}", listener.local_addr()?);
+
+    // With `signals`, SIGTERM stops the server as well as Ctrl-C, and the log is closed on the way out
+    #[cfg(feature = "signals")]
+    {
+        let guard = rmemimg::memimg::ShutdownGuard::install()?;
+        let stop = guard.stop_signal();
+        serve(listener, processor.clone(), async move {
+            let _ = tokio::task::spawn_blocking(move || stop.wait()).await;
+        })
+        .await?;
+        let processor = Arc::into_inner(processor).ok_or("the server still holds the processor")?.into_inner();
+        guard.close(processor)?;
+        Ok(())
+    }
+
+    #[cfg(not(feature = "signals"))]
+    serve(listener, processor, async {
+        let _ = tokio::signal::ctrl_c().await;
+    })
+    .await
+}
@@ -0,0 +1,19 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rmemimg::memimg::bank::{Bank, BankCommand};
+use rmemimg::memimg::bank_invariants::DoubleEntryValidator;
+use rmemimg::memimg::{MemImgProcessor, MemoryEventStorage, SystemValidator};
+use rmemimg_fuzz::FuzzCommand;
+
+// No command panics; a rejected one changes nothing and an accepted one keeps the books balanced
+fuzz_target!(|commands: Vec<FuzzCommand>| {
+    let mut processor = MemImgProcessor::new_simple(Bank::new(), Box::new(MemoryEventStorage::<BankCommand>::new())).unwrap();
+    for command in commands {
+        let before = processor.system().clone();
+        match processor.execute_command(command.into()) {
+            Ok(_) => assert!(DoubleEntryValidator.validate(processor.system()).is_ok(), "{:?}", processor.system()),
+            Err(_) => assert_eq!(processor.system(), &before),
+        }
+    }
+});
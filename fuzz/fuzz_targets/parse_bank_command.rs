@@ -0,0 +1,16 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rmemimg::memimg::bank_storage::BankJsonConverter;
+use rmemimg::memimg::TextConverter;
+
+// Any text either fails to parse or parses into a command that formats and parses back to itself
+fuzz_target!(|data: &[u8]| {
+    let text = String::from_utf8_lossy(data);
+    let converter = BankJsonConverter::new();
+    if let Ok(command) = converter.parse(&text) {
+        let formatted = converter.format(&command).expect("a parsed command must format");
+        let reparsed = converter.parse(&formatted).expect("a formatted command must parse");
+        assert_eq!(reparsed, command);
+    }
+});
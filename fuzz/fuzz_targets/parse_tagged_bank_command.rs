@@ -0,0 +1,16 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rmemimg::memimg::bank_storage::BankJsonConverter;
+use rmemimg::memimg::TextConverter;
+
+// As parse_bank_command, through the internally and adjacently tagged converters
+fuzz_target!(|data: &[u8]| {
+    let text = String::from_utf8_lossy(data);
+    for converter in [BankJsonConverter::internally_tagged("type"), BankJsonConverter::adjacently_tagged("type", "data")] {
+        if let Ok(command) = converter.parse(&text) {
+            let formatted = converter.format(&command).expect("a parsed command must format");
+            assert_eq!(converter.parse(&formatted).expect("a formatted command must parse"), command);
+        }
+    }
+});
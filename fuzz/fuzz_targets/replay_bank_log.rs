@@ -0,0 +1,32 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rmemimg::memimg::bank::BankCommand;
+use rmemimg::memimg::bank_storage::BankJsonConverter;
+use rmemimg::memimg::{EventStorage, ReplayPolicy, TextFileEventStorage};
+
+// Replaying any file, plain or claiming to be gzip, under any policy returns events or an error
+fuzz_target!(|data: &[u8]| {
+    let Some((&mode, contents)) = data.split_first() else {
+        return;
+    };
+    let extension = if mode & 1 == 0 { "json" } else { "json.gz" };
+    let policy = match (mode >> 1) & 3 {
+        0 => ReplayPolicy::Strict,
+        1 => ReplayPolicy::Lenient,
+        _ => ReplayPolicy::Budgeted { error_budget: usize::from(mode >> 3) },
+    };
+    let log = std::env::temp_dir().join(format!("replay_bank_log_{}.{}", std::process::id(), extension));
+    std::fs::write(&log, contents).unwrap();
+
+    let mut storage = TextFileEventStorage::new_unlocked(&log, BankJsonConverter::new()).unwrap().with_replay_policy(policy);
+    let _ = storage.replay(&mut |_event: BankCommand| Ok(()));
+    let _ = storage.version();
+    if let Ok(mut cursor) = storage.open_cursor() {
+        while let Ok(batch) = cursor.next_batch(16) {
+            if batch.is_empty() {
+                break;
+            }
+        }
+    }
+});
@@ -0,0 +1,20 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rmemimg::memimg::bank::BankCommand;
+use rmemimg::memimg::bank_storage::BankJsonConverter;
+use rmemimg::memimg::TextConverter;
+use rmemimg_fuzz::FuzzCommand;
+
+// Every command formats to one line and parses back to itself, in every bank log format
+fuzz_target!(|command: FuzzCommand| {
+    let command = BankCommand::from(command);
+    let converter = BankJsonConverter::new();
+    let text = converter.format(&command).expect("every command formats");
+    assert!(!text.contains('\n'));
+    assert_eq!(converter.parse(&text).expect("a formatted command must parse"), command);
+    for converter in [BankJsonConverter::internally_tagged("type"), BankJsonConverter::adjacently_tagged("type", "data")] {
+        let text = converter.format(&command).expect("every command formats");
+        assert_eq!(converter.parse(&text).expect("a formatted command must parse"), command);
+    }
+});
@@ -0,0 +1,107 @@
+use arbitrary::Arbitrary;
+use rmemimg::memimg::bank::{AccountId, Amount, BankCommand, LedgerEntry, PaymentMethod};
+use rust_decimal::Decimal;
+
+/// An account id: mostly one of a few shared ids, so commands meet the same accounts
+#[derive(Debug, Arbitrary)]
+pub enum FuzzId {
+    Known(u8),
+    Raw(String),
+}
+
+impl From<FuzzId> for AccountId {
+    fn from(id: FuzzId) -> Self {
+        match id {
+            FuzzId::Known(n) => AccountId::from(format!("acc{}", n % 8)),
+            FuzzId::Raw(id) => AccountId::from(id),
+        }
+    }
+}
+
+/// Any amount `Decimal` can hold
+#[derive(Debug, Arbitrary)]
+pub struct FuzzAmount {
+    pub lo: u32,
+    pub mid: u32,
+    pub hi: u32,
+    pub negative: bool,
+    pub scale: u8,
+}
+
+impl From<FuzzAmount> for Amount {
+    fn from(amount: FuzzAmount) -> Self {
+        Decimal::from_parts(amount.lo, amount.mid, amount.hi, amount.negative, u32::from(amount.scale) % 29)
+    }
+}
+
+#[derive(Debug, Arbitrary)]
+pub enum FuzzPaymentMethod {
+    CreditCard,
+    WireTransfer,
+    Ach,
+    Crypto,
+}
+
+/// Mirror of `BankCommand` that `arbitrary` can build from fuzzer bytes
+#[derive(Debug, Arbitrary)]
+pub enum FuzzCommand {
+    CreateAccount { id: FuzzId, name: String, opening_balance: Option<FuzzAmount> },
+    Deposit { account_id: FuzzId, amount: FuzzAmount },
+    Withdrawal { account_id: FuzzId, amount: FuzzAmount },
+    Transfer { from_account_id: FuzzId, to_account_id: FuzzId, amount: FuzzAmount },
+    BulkCreateAccounts { accounts: Vec<(FuzzId, String)> },
+    CloseAccount { id: FuzzId },
+    AddOwner { account_id: FuzzId, owner: String },
+    RemoveOwner { account_id: FuzzId, owner: String },
+    Sweep { from_account_id: FuzzId, to_account_id: FuzzId, amount: Option<FuzzAmount> },
+    ImportLedger { entries: Vec<(FuzzId, String, FuzzAmount)> },
+    RecordExternalPayment { account_id: FuzzId, amount: FuzzAmount, gateway: String, gateway_transaction_id: String, payment_method: FuzzPaymentMethod },
+}
+
+impl From<FuzzCommand> for BankCommand {
+    fn from(command: FuzzCommand) -> Self {
+        match command {
+            FuzzCommand::CreateAccount { id, name, opening_balance } => {
+                BankCommand::CreateAccount { id: id.into(), name, opening_balance: opening_balance.map(Into::into) }
+            }
+            FuzzCommand::Deposit { account_id, amount } => BankCommand::Deposit { account_id: account_id.into(), amount: amount.into() },
+            FuzzCommand::Withdrawal { account_id, amount } => BankCommand::Withdrawal { account_id: account_id.into(), amount: amount.into() },
+            FuzzCommand::Transfer { from_account_id, to_account_id, amount } => BankCommand::Transfer {
+                from_account_id: from_account_id.into(),
+                to_account_id: to_account_id.into(),
+                amount: amount.into(),
+            },
+            FuzzCommand::BulkCreateAccounts { accounts } => {
+                BankCommand::BulkCreateAccounts { accounts: accounts.into_iter().map(|(id, name)| (id.into(), name)).collect() }
+            }
+            FuzzCommand::CloseAccount { id } => BankCommand::CloseAccount { id: id.into() },
+            FuzzCommand::AddOwner { account_id, owner } => BankCommand::AddOwner { account_id: account_id.into(), owner },
+            FuzzCommand::RemoveOwner { account_id, owner } => BankCommand::RemoveOwner { account_id: account_id.into(), owner },
+            FuzzCommand::Sweep { from_account_id, to_account_id, amount } => BankCommand::Sweep {
+                from_account_id: from_account_id.into(),
+                to_account_id: to_account_id.into(),
+                amount: amount.map(Into::into),
+            },
+            FuzzCommand::ImportLedger { entries } => BankCommand::ImportLedger {
+                entries: entries
+                    .into_iter()
+                    .map(|(account_id, name, balance)| LedgerEntry { account_id: account_id.into(), name, balance: balance.into() })
+                    .collect(),
+            },
+            FuzzCommand::RecordExternalPayment { account_id, amount, gateway, gateway_transaction_id, payment_method } => {
+                BankCommand::RecordExternalPayment {
+                    account_id: account_id.into(),
+                    amount: amount.into(),
+                    gateway,
+                    gateway_transaction_id,
+                    payment_method: match payment_method {
+                        FuzzPaymentMethod::CreditCard => PaymentMethod::CreditCard,
+                        FuzzPaymentMethod::WireTransfer => PaymentMethod::WireTransfer,
+                        FuzzPaymentMethod::Ach => PaymentMethod::ACH,
+                        FuzzPaymentMethod::Crypto => PaymentMethod::Crypto,
+                    },
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,124 @@
+#ifndef RMEMIMG_H
+#define RMEMIMG_H
+
+/* Generated by cbindgen from src/ffi.rs; do not edit by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * Opaque handle to a bank processor, with in-memory or file-backed event storage
+ */
+typedef struct BankProcessor BankProcessor;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Create an empty bank; release it with `rmemimg_bank_free`
+ */
+BankProcessor *rmemimg_bank_new(void);
+
+/**
+ * Open the bank whose event log is at `path`, creating the log if missing; release it with `rmemimg_bank_free`
+ *
+ * # Safety
+ *
+ * `path` must be a NUL-terminated string.
+ */
+BankProcessor *rmemimg_bank_open(const char *path);
+
+/**
+ * Execute `command_json`, a `BankCommand` as JSON such as `{"Deposit":{"account_id":"alice","amount":"10"}}`
+ *
+ * Returns 0 on success. On failure returns -1, writes a stable code such as `INSUFFICIENT_FUNDS`
+ * or `MALFORMED_INPUT` to `err_buf` (when not null) and leaves the message in `rmemimg_last_error`.
+ *
+ * # Safety
+ *
+ * `bank` must come from `rmemimg_bank_new` or `rmemimg_bank_open`; `command_json` must be a
+ * NUL-terminated string; `err_buf` must be null or point to `err_len` writable bytes.
+ */
+int rmemimg_bank_execute_json(BankProcessor *bank,
+                              const char *command_json,
+                              char *err_buf,
+                              uintptr_t err_len);
+
+/**
+ * Answer `query_json`, a tagged query such as `{"query":"GetBalance","account_id":"alice"}`, as JSON
+ *
+ * The result must be released with `rmemimg_string_free`. On failure returns null, reporting
+ * through `err_buf` and `rmemimg_last_error` as `rmemimg_bank_execute_json` does.
+ *
+ * # Safety
+ *
+ * `bank` must come from `rmemimg_bank_new` or `rmemimg_bank_open`; `query_json` must be a
+ * NUL-terminated string; `err_buf` must be null or point to `err_len` writable bytes.
+ */
+char *rmemimg_bank_query_json(const BankProcessor *bank,
+                              const char *query_json,
+                              char *err_buf,
+                              uintptr_t err_len);
+
+/**
+ * Open account `id` held by `name`
+ *
+ * # Safety
+ *
+ * `bank` must come from `rmemimg_bank_new`; `id` and `name` must be NUL-terminated strings.
+ */
+int rmemimg_bank_create_account(BankProcessor *bank, const char *id, const char *name);
+
+/**
+ * Credit `amount`, a decimal string such as `"1,000.50"`, to `account_id`
+ *
+ * # Safety
+ *
+ * `bank` must come from `rmemimg_bank_new`; `account_id` and `amount` must be NUL-terminated strings.
+ */
+int rmemimg_bank_deposit(BankProcessor *bank,
+                         const char *account_id,
+                         const char *amount);
+
+/**
+ * Balance of `account_id` as a decimal string; release it with `rmemimg_string_free`
+ *
+ * # Safety
+ *
+ * `bank` must come from `rmemimg_bank_new`; `account_id` must be a NUL-terminated string.
+ */
+char *rmemimg_bank_get_balance(const BankProcessor *bank, const char *account_id);
+
+/**
+ * Release a bank created by `rmemimg_bank_new` or `rmemimg_bank_open`, flushing its log; null is ignored
+ *
+ * A panic while flushing is caught and left in `rmemimg_last_error` rather than unwinding into C.
+ *
+ * # Safety
+ *
+ * `bank` must come from `rmemimg_bank_new` or `rmemimg_bank_open` and must not be used afterwards.
+ */
+void rmemimg_bank_free(BankProcessor *bank);
+
+/**
+ * Release a string returned by this library; null is ignored
+ *
+ * # Safety
+ *
+ * `value` must come from this library and must not be used afterwards.
+ */
+void rmemimg_string_free(char *value);
+
+/**
+ * Message for the last failure on this thread, or null; valid until the next call on this thread
+ */
+const char *rmemimg_last_error(void);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* RMEMIMG_H */
@@ -0,0 +1,201 @@
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::{format_ident, quote, quote_spanned};
+use syn::{parse_macro_input, Attribute, Data, DeriveInput, Error, Fields, Ident, LitStr, Type};
+
+/// Derive `rmemimg::memimg::Command` for an enum by dispatching each variant to a handler method.
+///
+/// ```ignore
+/// #[derive(Command)]
+/// #[command(system = "Inventory")]
+/// enum InventoryCommand {
+///     #[command(handler = "apply_add_item")]
+///     AddItem { sku: String, qty: u32 },
+/// }
+///
+/// impl Inventory {
+///     fn apply_add_item(&mut self, sku: &str, qty: &u32) -> Result<(), InventoryError> { ... }
+/// }
+/// ```
+///
+/// Variant fields are passed to the handler by reference, in declaration order. Handlers return
+/// `Result<(), E>` for any `E: Into<Box<dyn Error + Send + Sync>>`. Optional enum-level
+/// `explain = "method"`, `resolve = "method"` and `check = "method"` forward `Command::explain`,
+/// `Command::resolve` and `Command::check` to `self.method(system)`; a check method returns
+/// `Option<Result<(), E>>`.
+#[proc_macro_derive(Command, attributes(command))]
+pub fn derive_command(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input).unwrap_or_else(Error::into_compile_error).into()
+}
+
+struct EnumOptions {
+    system: Type,
+    explain: Option<Ident>,
+    resolve: Option<Ident>,
+    check: Option<Ident>,
+}
+
+fn enum_options(input: &DeriveInput) -> Result<EnumOptions, Error> {
+    let mut system = None;
+    let mut explain = None;
+    let mut resolve = None;
+    let mut check = None;
+    for attr in command_attrs(&input.attrs) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("system") {
+                let value: LitStr = meta.value()?.parse()?;
+                system = Some(value.parse::<Type>()?);
+                Ok(())
+            } else if meta.path.is_ident("explain") {
+                let value: LitStr = meta.value()?.parse()?;
+                explain = Some(value.parse::<Ident>()?);
+                Ok(())
+            } else if meta.path.is_ident("resolve") {
+                let value: LitStr = meta.value()?.parse()?;
+                resolve = Some(value.parse::<Ident>()?);
+                Ok(())
+            } else if meta.path.is_ident("check") {
+                let value: LitStr = meta.value()?.parse()?;
+                check = Some(value.parse::<Ident>()?);
+                Ok(())
+            } else {
+                Err(meta.error("expected `system = \"...\"`, `explain = \"...\"`, `resolve = \"...\"` or `check = \"...\"`"))
+            }
+        })?;
+    }
+
+    let system = system.ok_or_else(|| {
+        Error::new_spanned(
+            &input.ident,
+            "missing `#[command(system = \"...\")]` naming the system type this command applies to",
+        )
+    })?;
+    Ok(EnumOptions { system, explain, resolve, check })
+}
+
+fn variant_handler(variant: &syn::Variant) -> Result<LitStr, Error> {
+    let mut handler = None;
+    for attr in command_attrs(&variant.attrs) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("handler") {
+                handler = Some(meta.value()?.parse::<LitStr>()?);
+                Ok(())
+            } else {
+                Err(meta.error("expected `handler = \"...\"`"))
+            }
+        })?;
+    }
+
+    handler.ok_or_else(|| {
+        Error::new_spanned(
+            variant,
+            format!(
+                "missing `#[command(handler = \"...\")]` on variant `{}`: every variant needs a handler method on the system",
+                variant.ident
+            ),
+        )
+    })
+}
+
+fn command_attrs(attrs: &[Attribute]) -> impl Iterator<Item = &Attribute> {
+    attrs.iter().filter(|attr| attr.path().is_ident("command"))
+}
+
+fn expand(input: DeriveInput) -> Result<proc_macro2::TokenStream, Error> {
+    let data = match &input.data {
+        Data::Enum(data) => data,
+        _ => return Err(Error::new_spanned(&input.ident, "`#[derive(Command)]` only supports enums")),
+    };
+    let options = enum_options(&input)?;
+
+    let mut arms = Vec::new();
+    let mut errors: Option<Error> = None;
+    for variant in &data.variants {
+        let handler = match variant_handler(variant) {
+            Ok(handler) => handler,
+            Err(e) => {
+                match &mut errors {
+                    Some(errors) => errors.combine(e),
+                    None => errors = Some(e),
+                }
+                continue;
+            }
+        };
+        let method = Ident::new(&handler.value(), handler.span());
+        let variant_ident = &variant.ident;
+
+        let (pattern, args) = match &variant.fields {
+            Fields::Named(fields) => {
+                let names: Vec<_> = fields.named.iter().map(|f| f.ident.clone().unwrap()).collect();
+                (quote! { Self::#variant_ident { #(#names),* } }, names)
+            }
+            Fields::Unnamed(fields) => {
+                let names: Vec<_> = (0..fields.unnamed.len())
+                    .map(|i| format_ident!("field{}", i, span = Span::call_site()))
+                    .collect();
+                (quote! { Self::#variant_ident ( #(#names),* ) }, names)
+            }
+            Fields::Unit => (quote! { Self::#variant_ident }, Vec::new()),
+        };
+
+        // Span the call on the handler name so signature mismatches point at the attribute
+        let call = quote_spanned! {handler.span()=>
+            ::rmemimg::memimg::__command_result(system.#method(#(#args),*))
+        };
+        arms.push(quote! { #pattern => #call, });
+    }
+    if let Some(errors) = errors {
+        return Err(errors);
+    }
+
+    let name = &input.ident;
+    let system = &options.system;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let explain = options.explain.map(|method| {
+        quote! {
+            fn explain(&self, system: &Self::System) -> ::std::string::String {
+                self.#method(system)
+            }
+        }
+    });
+    let resolve = options.resolve.map(|method| {
+        quote! {
+            fn resolve(&self, system: &Self::System) -> ::core::option::Option<Self> {
+                self.#method(system)
+            }
+        }
+    });
+
+    let check = options.check.map(|method| {
+        quote! {
+            fn check(
+                &self,
+                system: &Self::System,
+            ) -> ::core::option::Option<::core::result::Result<(), ::std::boxed::Box<dyn ::std::error::Error + ::core::marker::Send + ::core::marker::Sync>>> {
+                self.#method(system).map(::rmemimg::memimg::__command_result)
+            }
+        }
+    });
+
+    Ok(quote! {
+        impl #impl_generics ::rmemimg::memimg::Command for #name #ty_generics #where_clause {
+            type System = #system;
+
+            fn apply_to(
+                &self,
+                system: &mut Self::System,
+            ) -> ::core::result::Result<(), ::std::boxed::Box<dyn ::std::error::Error + ::core::marker::Send + ::core::marker::Sync>> {
+                match self {
+                    #(#arms)*
+                }
+            }
+
+            #explain
+
+            #resolve
+
+            #check
+        }
+    })
+}
@@ -0,0 +1,90 @@
+use rmemimg::memimg::bank::{Bank, BankCommand, GetBalance, ListAccounts};
+use rmemimg::memimg::bank_repl::{parse_repl_line, touches_account, ReplCommand, REPL_HELP};
+use rmemimg::memimg::bank_storage::BankJsonConverter;
+use rmemimg::memimg::{EventStorage, FailureOutcome, MemImgError, MemImgProcessor, TextFileEventStorage};
+use std::io::{BufRead, Write};
+
+type BankProcessor = MemImgProcessor<Bank, BankCommand, TextFileEventStorage<BankCommand, BankJsonConverter>>;
+
+/// The domain error behind a processor failure, without the processor's framing
+fn describe_error(error: &MemImgError) -> String {
+    match error.outcome() {
+        Some(outcome) => outcome.source.to_string(),
+        None => error.to_string(),
+    }
+}
+
+fn run(processor: &mut BankProcessor, command: ReplCommand) -> Result<(), MemImgError> {
+    match command {
+        ReplCommand::Execute(command) => {
+            processor.execute_command(command)?;
+            println!("ok");
+        }
+        ReplCommand::Balance(account_id) => {
+            let balance = processor.execute_query(&GetBalance { account_id })?;
+            println!("${}", balance);
+        }
+        ReplCommand::Accounts => {
+            for account in processor.execute_query(&ListAccounts)? {
+                println!("{:<12} {:<20} ${}", account.id, account.name, account.balance());
+            }
+        }
+        ReplCommand::History(account_id) => {
+            let mut index = 0u64;
+            processor
+                .event_storage
+                .replay(&mut |command: BankCommand| {
+                    index += 1;
+                    if touches_account(&command, account_id.as_str()) {
+                        println!("#{:<5} {:?}", index, command);
+                    }
+                    Ok(())
+                })
+                .map_err(|e| {
+                    MemImgError::SystemFailure(FailureOutcome::new(e, "reading history from", "EventStorage"))
+                })?;
+        }
+        ReplCommand::Help => println!("{}", REPL_HELP),
+        ReplCommand::Quit => {}
+    }
+    Ok(())
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let path = std::env::args().nth(1).unwrap_or_else(|| "bank_events.json".to_string());
+    let storage = Box::new(TextFileEventStorage::new(&path, BankJsonConverter::new())?);
+    let mut processor = MemImgProcessor::new_simple(Bank::new(), storage)?;
+    #[cfg(feature = "signals")]
+    let guard = rmemimg::memimg::ShutdownGuard::install()?;
+
+    println!("Bank REPL over {} ({} events replayed); type 'help' for commands", path, processor.event_version().as_u64());
+
+    let stdin = std::io::stdin();
+    let mut lines = stdin.lock().lines();
+    loop {
+        #[cfg(feature = "signals")]
+        if guard.should_stop() {
+            break;
+        }
+        print!("bank> ");
+        std::io::stdout().flush()?;
+        let Some(line) = lines.next() else {
+            break;
+        };
+
+        match parse_repl_line(&line?) {
+            Ok(None) => {}
+            Ok(Some(ReplCommand::Quit)) => break,
+            Ok(Some(command)) => {
+                if let Err(e) = run(&mut processor, command) {
+                    println!("error: {}", describe_error(&e));
+                }
+            }
+            Err(e) => println!("error: {}", e),
+        }
+    }
+
+    #[cfg(feature = "signals")]
+    guard.close(processor)?;
+    Ok(())
+}
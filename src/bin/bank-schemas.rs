@@ -0,0 +1,11 @@
+use rmemimg::memimg::bank_schema::write_schemas;
+use std::path::PathBuf;
+
+/// Write the bank's JSON Schemas to the directory given as the only argument (default `schemas`)
+fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let dir = PathBuf::from(std::env::args().nth(1).unwrap_or_else(|| "schemas".to_string()));
+    for path in write_schemas(&dir)? {
+        println!("{}", path.display());
+    }
+    Ok(())
+}
@@ -0,0 +1,196 @@
+use rmemimg::memimg::admin::{compact, migrate_format, record_fingerprint, snapshot, verify, LogStore};
+use rmemimg::memimg::bank::{parse_amount, Amount, Bank, BankCommand};
+use rmemimg::memimg::bank_anonymizer::{anonymize_log, replay_shape, AmountPolicy, BankScrubber};
+use rmemimg::memimg::bank_storage::{BankJsonConverter, TaggedBankJsonConverter};
+use rmemimg::memimg::{SnapshotFormat, TextConverter};
+use serde::Serialize;
+use serde_json::json;
+use std::path::PathBuf;
+
+const USAGE: &str = "usage: memimg-admin [--json] [--from <format>] <command> <bank event log> [options]
+
+--from names the format the log is in: external (the default), internal or adjacent JSON
+
+commands:
+  snapshot <log> <out>                     write a snapshot of the current state to <out>
+  compact <log>                            snapshot the state to <log>.snapshot and empty the log
+  verify <log> [--against <snapshot>]      check the log and snapshot, comparing with <snapshot>
+  migrate-format <log> --to <format>       rewrite the log in another format
+  record-fingerprint <log>                 record the state's fingerprint for `rmemimg --verify`
+  anonymize <log> <out> [--seed <seed>]    write <out> with ids, names and payment ids pseudonymized,
+    [--scale <factor> | --bucket <size>]   amounts optionally scaled or bucketed, then replay both
+                                           and check that they fail alike; --to picks <out>'s format
+
+snapshot and compact record the fingerprint too; anonymize without --seed picks a random one";
+
+/// Version of the bank snapshots this tool reads and writes
+const SNAPSHOT_VERSION: u32 = 1;
+
+/// Bank log line formats, named as `migrate-format` takes them
+enum BankFormat {
+    External,
+    Tagged(TaggedBankJsonConverter),
+}
+
+impl BankFormat {
+    fn parse(name: &str) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        match name {
+            "external" => Ok(BankFormat::External),
+            "internal" => Ok(BankFormat::Tagged(BankJsonConverter::internally_tagged("type"))),
+            "adjacent" => Ok(BankFormat::Tagged(BankJsonConverter::adjacently_tagged("type", "data"))),
+            "bincode" => Err("bincode is not supported: event logs are line-oriented text; use external, internal or adjacent".into()),
+            other => Err(format!("unknown format {:?}; use external, internal or adjacent", other).into()),
+        }
+    }
+}
+
+impl TextConverter<BankCommand> for BankFormat {
+    fn parse(&self, text: &str) -> Result<BankCommand, Box<dyn std::error::Error + Send + Sync>> {
+        match self {
+            BankFormat::External => BankJsonConverter::new().parse(text),
+            BankFormat::Tagged(converter) => converter.parse(text),
+        }
+    }
+
+    fn format(&self, command: &BankCommand) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+        match self {
+            BankFormat::External => BankJsonConverter::new().format(command),
+            BankFormat::Tagged(converter) => converter.format(command),
+        }
+    }
+}
+
+/// Value of `--name` in `args`, removing both from it
+fn take_option(args: &mut Vec<String>, name: &str) -> Result<Option<String>, Box<dyn std::error::Error + Send + Sync>> {
+    match args.iter().position(|arg| arg == name) {
+        Some(at) if at + 1 < args.len() => {
+            let value = args.remove(at + 1);
+            args.remove(at);
+            Ok(Some(value))
+        }
+        Some(_) => Err(format!("{} needs a value", name).into()),
+        None => Ok(None),
+    }
+}
+
+/// `value` of `option`, which must be a positive amount
+fn positive_amount(option: &str, value: &str) -> Result<Amount, Box<dyn std::error::Error + Send + Sync>> {
+    match parse_amount(value) {
+        Ok(amount) if amount > Amount::ZERO => Ok(amount),
+        _ => Err(format!("{} needs a positive number, got {:?}", option, value).into()),
+    }
+}
+
+/// Print `report` as JSON, or as `summary` for people
+fn emit<R: Serialize>(as_json: bool, report: &R, summary: String) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    if as_json {
+        println!("{}", serde_json::to_string(report)?);
+    } else {
+        println!("{}", summary);
+    }
+    Ok(())
+}
+
+/// Run the command in `args`, returning whether the store checked out
+fn run(mut args: Vec<String>) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+    let as_json = args.iter().any(|arg| arg == "--json");
+    args.retain(|arg| arg != "--json");
+    let against = take_option(&mut args, "--against")?;
+    let to = take_option(&mut args, "--to")?;
+    let seed = take_option(&mut args, "--seed")?;
+    let scale = take_option(&mut args, "--scale")?;
+    let bucket = take_option(&mut args, "--bucket")?;
+    let from_name = take_option(&mut args, "--from")?.unwrap_or_else(|| "external".to_string());
+    let from = BankFormat::parse(&from_name)?;
+    let format = SnapshotFormat::new(SNAPSHOT_VERSION);
+
+    match args.iter().map(String::as_str).collect::<Vec<_>>().as_slice() {
+        ["snapshot", log, out] => {
+            let report = snapshot(&LogStore::new(log), Bank::new(), from, &format, &PathBuf::from(out))?;
+            let summary = format!("wrote {} covering {} events, fingerprint {}", report.path.display(), report.event_count, report.fingerprint);
+            emit(as_json, &report, summary)?;
+        }
+        ["compact", log] => {
+            let report = compact(&LogStore::new(log), Bank::new(), from, &format)?;
+            let backups: Vec<_> = report.backups.iter().map(|path| path.display().to_string()).collect();
+            let summary = format!("removed {} events; snapshot covers {}; backups: {}", report.events_removed, report.event_count, backups.join(", "));
+            emit(as_json, &report, summary)?;
+        }
+        ["verify", log] => {
+            let report = verify(&LogStore::new(log), Bank::new(), from, &format, against.as_deref().map(std::path::Path::new))?;
+            let mut summary = format!(
+                "snapshot: {}; log: {} events; fingerprint {}",
+                report.snapshot_event_count.map_or("none".to_string(), |count| format!("{} events", count)),
+                report.log_events,
+                report.fingerprint.as_deref().unwrap_or("-")
+            );
+            for problem in &report.problems {
+                summary.push_str(&format!("\nproblem: {}", problem));
+            }
+            if report.is_ok() {
+                summary.push_str("\nok");
+            }
+            emit(as_json, &report, summary)?;
+            return Ok(report.is_ok());
+        }
+        ["migrate-format", log] => {
+            let to = BankFormat::parse(&to.ok_or("migrate-format needs --to <format>")?)?;
+            let report = migrate_format(&LogStore::new(log), from, to)?;
+            let summary = format!("rewrote {} events; previous log kept at {}", report.events, report.backup.display());
+            emit(as_json, &report, summary)?;
+        }
+        ["record-fingerprint", log] => {
+            let store = LogStore::new(log);
+            let report = record_fingerprint(&store, Bank::new(), from, &format)?;
+            let summary = format!("recorded fingerprint {} of {} events in {}", report.fingerprint, report.event_count, store.fingerprint_path().display());
+            emit(as_json, &report, summary)?;
+        }
+        ["anonymize", log, out] => {
+            let amounts = match (scale, bucket) {
+                (None, None) => AmountPolicy::Keep,
+                (Some(factor), None) => AmountPolicy::Scale(positive_amount("--scale", &factor)?),
+                (None, Some(size)) => AmountPolicy::Bucket(positive_amount("--bucket", &size)?),
+                (Some(_), Some(_)) => return Err("use either --scale or --bucket, not both".into()),
+            };
+            let seed = seed.unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+            let mut scrubber = BankScrubber::new(seed).with_amounts(amounts);
+            let to_name = to.unwrap_or_else(|| from_name.clone());
+            let (log, out) = (std::path::Path::new(log), std::path::Path::new(out));
+            let written = anonymize_log(log, out, from, BankFormat::parse(&to_name)?, &mut scrubber)?;
+            let original = replay_shape(log, BankFormat::parse(&from_name)?)?;
+            let anonymized = replay_shape(out, BankFormat::parse(&to_name)?)?;
+            let alike = original == anonymized;
+            let report = json!({ "events": written.events, "original": original, "anonymized": anonymized, "alike": alike });
+            let mut summary = format!(
+                "wrote {} events to {}; {} of them fail to apply",
+                written.events,
+                out.display(),
+                anonymized.failures.len()
+            );
+            summary.push_str(if alike { "
+ok: replays like the original" } else { "
+problem: does not replay like the original" });
+            emit(as_json, &report, summary)?;
+            return Ok(alike);
+        }
+        _ => return Err(USAGE.into()),
+    }
+    Ok(true)
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let as_json = args.iter().any(|arg| arg == "--json");
+    match run(args) {
+        Ok(true) => {}
+        Ok(false) => std::process::exit(1),
+        Err(e) => {
+            if as_json {
+                println!("{}", json!({ "error": e.to_string() }));
+            } else {
+                eprintln!("{}", e);
+            }
+            std::process::exit(2);
+        }
+    }
+}
@@ -0,0 +1,247 @@
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, List, ListItem, ListState, Paragraph, Wrap};
+use ratatui::{DefaultTerminal, Frame};
+use rmemimg::memimg::bank_browser::{BankStateCache, EventEffect};
+use rmemimg::memimg::bank_storage::BankJsonConverter;
+use rmemimg::memimg::{Command, LogIndex, TextConverter};
+use serde_json::Value;
+
+/// Events between index offsets and between cached bank states
+const STRIDE: u64 = 4096;
+
+const HELP: &str = "↑/↓ PgUp/PgDn Home/End move · / search · n next match · g jump to # · q quit";
+
+enum Mode {
+    Browse,
+    /// Incremental search, started with the cursor at `origin`
+    Search { needle: String, origin: u64 },
+    Jump(String),
+}
+
+struct Browser {
+    log: LogIndex,
+    states: BankStateCache,
+    cursor: u64,
+    /// First event of `window`, the rows currently on screen
+    top: u64,
+    window: Vec<String>,
+    effect: Result<EventEffect, String>,
+    mode: Mode,
+    last_search: String,
+    status: String,
+}
+
+impl Browser {
+    fn open(path: &str) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let log = LogIndex::build(path, STRIDE)?;
+        let status = format!("{}: {} events", path, log.len());
+        let mut browser = Self {
+            log,
+            states: BankStateCache::new(STRIDE),
+            cursor: 0,
+            top: 0,
+            window: Vec::new(),
+            effect: Err("empty log".to_string()),
+            mode: Mode::Browse,
+            last_search: String::new(),
+            status,
+        };
+        browser.select(0, 1)?;
+        Ok(browser)
+    }
+
+    /// Move the cursor to `event`, scrolling the window of `height` rows to keep it in view
+    fn select(&mut self, event: u64, height: u64) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let previous = (self.cursor, self.top);
+        self.cursor = event.min(self.log.len().saturating_sub(1));
+        if self.cursor < self.top {
+            self.top = self.cursor;
+        } else if self.cursor >= self.top + height {
+            self.top = self.cursor + 1 - height;
+        }
+        let rows = height.min(self.log.len() - self.top) as usize;
+        if self.top != previous.1 || self.window.len() != rows {
+            self.window = self.log.read_window(self.top, rows)?;
+        }
+        if self.cursor != previous.0 || self.effect.is_err() {
+            self.effect = if self.log.is_empty() {
+                Err("empty log".to_string())
+            } else {
+                self.states.effect_of(&self.log, self.cursor).map_err(|e| e.to_string())
+            };
+        }
+        Ok(())
+    }
+
+    fn search(&mut self, needle: &str, from: u64, height: u64) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        match self.log.find(from, needle)? {
+            Some(event) => {
+                self.status = format!("found {:?} at #{}", needle, event + 1);
+                self.select(event, height)
+            }
+            None => {
+                self.status = format!("{:?} not found after #{}", needle, from + 1);
+                Ok(())
+            }
+        }
+    }
+
+    /// Handle one key; false means quit
+    fn on_key(&mut self, code: KeyCode, height: u64) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+        match &mut self.mode {
+            Mode::Browse => match code {
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(false),
+                KeyCode::Down | KeyCode::Char('j') => self.select(self.cursor + 1, height)?,
+                KeyCode::Up | KeyCode::Char('k') => self.select(self.cursor.saturating_sub(1), height)?,
+                KeyCode::PageDown => self.select(self.cursor + height, height)?,
+                KeyCode::PageUp => self.select(self.cursor.saturating_sub(height), height)?,
+                KeyCode::Home => self.select(0, height)?,
+                KeyCode::End => self.select(u64::MAX, height)?,
+                KeyCode::Char('/') => self.mode = Mode::Search { needle: String::new(), origin: self.cursor },
+                KeyCode::Char('g') => self.mode = Mode::Jump(String::new()),
+                KeyCode::Char('n') if !self.last_search.is_empty() => {
+                    let needle = self.last_search.clone();
+                    self.search(&needle, self.cursor + 1, height)?;
+                }
+                _ => {}
+            },
+            Mode::Search { needle, origin } => {
+                let origin = *origin;
+                match code {
+                    KeyCode::Esc => {
+                        self.mode = Mode::Browse;
+                        self.select(origin, height)?;
+                    }
+                    KeyCode::Enter => {
+                        self.last_search = needle.clone();
+                        self.mode = Mode::Browse;
+                    }
+                    KeyCode::Backspace | KeyCode::Char(_) => {
+                        match code {
+                            KeyCode::Char(c) => needle.push(c),
+                            _ => {
+                                needle.pop();
+                            }
+                        }
+                        let needle = needle.clone();
+                        if needle.is_empty() {
+                            self.select(origin, height)?;
+                        } else {
+                            self.search(&needle, origin, height)?;
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            Mode::Jump(digits) => match code {
+                KeyCode::Char(c) if c.is_ascii_digit() => digits.push(c),
+                KeyCode::Backspace => {
+                    digits.pop();
+                }
+                KeyCode::Enter => {
+                    let target = digits.parse::<u64>().unwrap_or(1).max(1) - 1;
+                    self.mode = Mode::Browse;
+                    self.select(target, height)?;
+                }
+                KeyCode::Esc => self.mode = Mode::Browse,
+                _ => {}
+            },
+        }
+        Ok(true)
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        let [main, status] = Layout::vertical([Constraint::Min(3), Constraint::Length(1)]).areas(frame.area());
+        let [list_area, right] = Layout::horizontal([Constraint::Percentage(45), Constraint::Percentage(55)]).areas(main);
+        let [detail_area, state_area] = Layout::vertical([Constraint::Percentage(60), Constraint::Percentage(40)]).areas(right);
+
+        let rows: Vec<ListItem> = self
+            .window
+            .iter()
+            .enumerate()
+            .map(|(offset, line)| ListItem::new(summary(self.top + offset as u64, line)))
+            .collect();
+        let mut list_state = ListState::default().with_selected(Some((self.cursor - self.top) as usize));
+        let list = List::new(rows)
+            .block(Block::bordered().title(" events "))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(list, list_area, &mut list_state);
+
+        let detail = match self.window.get((self.cursor - self.top) as usize) {
+            Some(line) => match serde_json::from_str::<Value>(line) {
+                Ok(value) => serde_json::to_string_pretty(&value).unwrap_or_else(|_| line.clone()),
+                Err(e) => format!("{}\n\nnot JSON: {}", line, e),
+            },
+            None => String::new(),
+        };
+        frame.render_widget(
+            Paragraph::new(detail).wrap(Wrap { trim: false }).block(Block::bordered().title(format!(" #{} ", self.cursor + 1))),
+            detail_area,
+        );
+
+        let state = match &self.effect {
+            Ok(effect) => {
+                let mut lines: Vec<Line> = effect
+                    .changes
+                    .iter()
+                    .map(|change| {
+                        let show = |balance: Option<rmemimg::memimg::bank::Amount>| balance.map_or("-".to_string(), |b| b.to_string());
+                        Line::from(format!("{}: {} → {}", change.account_id, show(change.before), show(change.after)))
+                    })
+                    .collect();
+                if let Some(rejection) = &effect.rejection {
+                    lines.push(Line::from(format!("rejected: {}", rejection)));
+                }
+                lines
+            }
+            Err(e) => vec![Line::from(e.clone())],
+        };
+        frame.render_widget(Paragraph::new(state).block(Block::bordered().title(" balances before → after ")), state_area);
+
+        let status_line = match &self.mode {
+            Mode::Browse if self.status.is_empty() => HELP.to_string(),
+            Mode::Browse => format!("{} · {}", self.status, HELP),
+            Mode::Search { needle, .. } => format!("/{}", needle),
+            Mode::Jump(digits) => format!("go to #{}", digits),
+        };
+        frame.render_widget(Paragraph::new(status_line), status);
+    }
+}
+
+/// `#index  timestamp  type` for one list row
+fn summary(event: u64, line: &str) -> String {
+    let command = BankJsonConverter::new().parse(line).ok();
+    let timestamp = command.as_ref().and_then(Command::timestamp).map_or("-".to_string(), |at| at.to_rfc3339());
+    let event_type = match serde_json::from_str::<Value>(line) {
+        Ok(Value::Object(fields)) if fields.len() == 1 => fields.keys().next().cloned().unwrap_or_default(),
+        Ok(Value::Object(fields)) => fields.get("type").and_then(Value::as_str).unwrap_or("?").to_string(),
+        _ => "unparseable".to_string(),
+    };
+    format!("#{:<8} {:<25} {}", event + 1, timestamp, event_type)
+}
+
+fn run(terminal: &mut DefaultTerminal, browser: &mut Browser) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    loop {
+        // Rows inside the list's borders
+        let height = terminal.size()?.height.saturating_sub(3).max(1) as u64;
+        browser.select(browser.cursor, height)?;
+        terminal.draw(|frame| browser.draw(frame))?;
+        if let Event::Key(key) = event::read()? {
+            if key.kind == KeyEventKind::Press && !browser.on_key(key.code, height)? {
+                return Ok(());
+            }
+        }
+    }
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let path = std::env::args().nth(1).ok_or("usage: memimg-tui <bank event log>")?;
+    let mut browser = Browser::open(&path)?;
+    let mut terminal = ratatui::init();
+    let result = run(&mut terminal, &mut browser);
+    ratatui::restore();
+    result
+}
@@ -0,0 +1,44 @@
+use rmemimg::memimg::warehouse::{GetLowStockItems, GetStockLevel, Warehouse, WarehouseCommand};
+use rmemimg::memimg::warehouse_storage::WarehouseJsonConverter;
+use rmemimg::memimg::{MemImgProcessor, TextFileEventStorage};
+
+fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    println!("=== Memory Image Pattern Demo: Warehouse ===\n");
+
+    let storage = Box::new(TextFileEventStorage::new("warehouse_events.json", WarehouseJsonConverter)?);
+    let mut processor = MemImgProcessor::new_simple(Warehouse::new(), storage)?;
+
+    if processor.system().items.is_empty() {
+        println!("Adding items...");
+        processor.execute_command(WarehouseCommand::AddItem { sku: "widget".to_string(), name: "Widget".to_string() })?;
+        processor.execute_command(WarehouseCommand::AddItem { sku: "gadget".to_string(), name: "Gadget".to_string() })?;
+    }
+
+    println!("Receiving 20 widgets and 5 gadgets...");
+    processor.execute_command(WarehouseCommand::ReceiveStock { sku: "widget".to_string(), quantity: 20 })?;
+    processor.execute_command(WarehouseCommand::ReceiveStock { sku: "gadget".to_string(), quantity: 5 })?;
+
+    println!("Reserving and shipping 8 widgets and 3 gadgets...");
+    processor.execute_command(WarehouseCommand::Reserve { sku: "widget".to_string(), quantity: 8 })?;
+    processor.execute_command(WarehouseCommand::Reserve { sku: "gadget".to_string(), quantity: 3 })?;
+    processor.execute_command(WarehouseCommand::Ship {
+        lines: vec![("widget".to_string(), 8), ("gadget".to_string(), 3)],
+    })?;
+
+    println!("Trying to ship a gadget that was never reserved...");
+    if let Err(e) = processor.execute_command(WarehouseCommand::Ship { lines: vec![("gadget".to_string(), 1)] }) {
+        println!("  rejected: {}", e);
+    }
+
+    println!("\n=== Stock Levels ===");
+    for sku in ["widget", "gadget"] {
+        let level = processor.execute_query(&GetStockLevel { sku: sku.to_string() })?;
+        println!("{}: {} on hand, {} reserved", sku, level.on_hand, level.reserved);
+    }
+
+    let low_stock = processor.execute_query(&GetLowStockItems { threshold: 10 })?;
+    println!("\nLow stock (< 10 available): {:?}", low_stock.iter().map(|item| &item.sku).collect::<Vec<_>>());
+
+    println!("\nAll commands saved to warehouse_events.json");
+    Ok(())
+}
@@ -0,0 +1,290 @@
+use crate::memimg::bank::{domain_error_message, parse_amount, Bank, BankCommand, GetBalance};
+use crate::memimg::bank_pipe::{execute_command_json, execute_query_json, PipeFailure};
+use crate::memimg::bank_storage::BankJsonConverter;
+use crate::memimg::{CommandReceipt, MemImgError, MemImgProcessor, MemoryEventStorage, Query, TextFileEventStorage};
+use serde_json::Value;
+use std::cell::RefCell;
+use std::ffi::{c_char, c_int, CStr, CString};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::ptr;
+
+/// Opaque handle to a bank processor, with in-memory or file-backed event storage
+pub struct BankProcessor {
+    processor: Processor,
+}
+
+enum Processor {
+    Memory(MemImgProcessor<Bank, BankCommand, MemoryEventStorage<BankCommand>>),
+    File(MemImgProcessor<Bank, BankCommand, TextFileEventStorage<BankCommand, BankJsonConverter>>),
+}
+
+impl BankProcessor {
+    fn execute_command(&mut self, command: BankCommand) -> Result<CommandReceipt, MemImgError> {
+        match &mut self.processor {
+            Processor::Memory(processor) => processor.execute_command(command),
+            Processor::File(processor) => processor.execute_command(command),
+        }
+    }
+
+    fn execute_query<Q: Query<System = Bank>>(&self, query: &Q) -> Result<Q::Result, MemImgError> {
+        match &self.processor {
+            Processor::Memory(processor) => processor.execute_query(query),
+            Processor::File(processor) => processor.execute_query(query),
+        }
+    }
+
+    fn execute_command_json(&mut self, command: Value) -> Result<u64, PipeFailure> {
+        match &mut self.processor {
+            Processor::Memory(processor) => execute_command_json(processor, command),
+            Processor::File(processor) => execute_command_json(processor, command),
+        }
+    }
+
+    fn execute_query_json(&self, query: Value) -> Result<Value, PipeFailure> {
+        match &self.processor {
+            Processor::Memory(processor) => execute_query_json(processor, query),
+            Processor::File(processor) => execute_query_json(processor, query),
+        }
+    }
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(message: String) {
+    // Interior NULs would truncate the message in C; replace them rather than drop it
+    let message = CString::new(message.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|last_error| *last_error.borrow_mut() = Some(message));
+}
+
+/// A stable error code, as reported by the pipe and HTTP front ends, with its message
+type Failure = (&'static str, String);
+
+fn invalid_argument(message: String) -> Failure {
+    ("INVALID_ARGUMENT", message)
+}
+
+fn pipe_failure(failure: PipeFailure) -> Failure {
+    (failure.code(), failure.message())
+}
+
+/// Run `body` with panics and errors recorded as the last error, returning `on_error` if either occurs
+fn guarded<T>(on_error: T, body: impl FnOnce() -> Result<T, String>) -> T {
+    guarded_coded(on_error, ptr::null_mut(), 0, || body().map_err(|message| ("", message)))
+}
+
+/// As `guarded`, also copying the failure's code into `err_buf`, truncated to fit `err_len` bytes with its NUL
+fn guarded_coded<T>(on_error: T, err_buf: *mut c_char, err_len: usize, body: impl FnOnce() -> Result<T, Failure>) -> T {
+    LAST_ERROR.with(|last_error| *last_error.borrow_mut() = None);
+    let (code, message) = match catch_unwind(AssertUnwindSafe(body)) {
+        Ok(Ok(value)) => return value,
+        Ok(Err(failure)) => failure,
+        Err(_) => ("PANIC", "panic inside rmemimg".to_string()),
+    };
+    if !err_buf.is_null() && err_len > 0 {
+        // Codes are ASCII, so any byte count is a valid cut
+        let len = code.len().min(err_len - 1);
+        unsafe {
+            ptr::copy_nonoverlapping(code.as_ptr().cast::<c_char>(), err_buf, len);
+            *err_buf.add(len) = 0;
+        }
+    }
+    set_last_error(message);
+    on_error
+}
+
+unsafe fn c_str<'a>(value: *const c_char, name: &str) -> Result<&'a str, String> {
+    if value.is_null() {
+        return Err(format!("{} is null", name));
+    }
+    CStr::from_ptr(value).to_str().map_err(|_| format!("{} is not valid UTF-8", name))
+}
+
+unsafe fn processor_mut<'a>(bank: *mut BankProcessor) -> Result<&'a mut BankProcessor, String> {
+    bank.as_mut().ok_or_else(|| "bank is null".to_string())
+}
+
+fn execute(bank: &mut BankProcessor, command: BankCommand) -> Result<c_int, String> {
+    bank.execute_command(command).map_err(|e| domain_error_message(&e))?;
+    Ok(0)
+}
+
+fn parse_json(json: &str) -> Result<Value, Failure> {
+    serde_json::from_str(json).map_err(|e| pipe_failure(PipeFailure::Malformed(e.to_string())))
+}
+
+/// Create an empty bank; release it with `rmemimg_bank_free`
+#[no_mangle]
+pub extern "C" fn rmemimg_bank_new() -> *mut BankProcessor {
+    guarded(ptr::null_mut(), || {
+        let processor = MemImgProcessor::new_simple(Bank::new(), Box::new(MemoryEventStorage::new()))
+            .map_err(|e| e.to_string())?;
+        Ok(Box::into_raw(Box::new(BankProcessor { processor: Processor::Memory(processor) })))
+    })
+}
+
+/// Open the bank whose event log is at `path`, creating the log if missing; release it with `rmemimg_bank_free`
+///
+/// # Safety
+///
+/// `path` must be a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn rmemimg_bank_open(path: *const c_char) -> *mut BankProcessor {
+    guarded(ptr::null_mut(), || {
+        let storage = TextFileEventStorage::new(c_str(path, "path")?, BankJsonConverter::new()).map_err(|e| e.to_string())?;
+        let processor = MemImgProcessor::new_simple(Bank::new(), Box::new(storage)).map_err(|e| e.to_string())?;
+        Ok(Box::into_raw(Box::new(BankProcessor { processor: Processor::File(processor) })))
+    })
+}
+
+/// Execute `command_json`, a `BankCommand` as JSON such as `{"Deposit":{"account_id":"alice","amount":"10"}}`
+///
+/// Returns 0 on success. On failure returns -1, writes a stable code such as `INSUFFICIENT_FUNDS`
+/// or `MALFORMED_INPUT` to `err_buf` (when not null) and leaves the message in `rmemimg_last_error`.
+///
+/// # Safety
+///
+/// `bank` must come from `rmemimg_bank_new` or `rmemimg_bank_open`; `command_json` must be a
+/// NUL-terminated string; `err_buf` must be null or point to `err_len` writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn rmemimg_bank_execute_json(
+    bank: *mut BankProcessor,
+    command_json: *const c_char,
+    err_buf: *mut c_char,
+    err_len: usize,
+) -> c_int {
+    guarded_coded(-1, err_buf, err_len, || {
+        let bank = processor_mut(bank).map_err(invalid_argument)?;
+        let command = parse_json(c_str(command_json, "command_json").map_err(invalid_argument)?)?;
+        bank.execute_command_json(command).map_err(pipe_failure)?;
+        Ok(0)
+    })
+}
+
+/// Answer `query_json`, a tagged query such as `{"query":"GetBalance","account_id":"alice"}`, as JSON
+///
+/// The result must be released with `rmemimg_string_free`. On failure returns null, reporting
+/// through `err_buf` and `rmemimg_last_error` as `rmemimg_bank_execute_json` does.
+///
+/// # Safety
+///
+/// `bank` must come from `rmemimg_bank_new` or `rmemimg_bank_open`; `query_json` must be a
+/// NUL-terminated string; `err_buf` must be null or point to `err_len` writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn rmemimg_bank_query_json(
+    bank: *const BankProcessor,
+    query_json: *const c_char,
+    err_buf: *mut c_char,
+    err_len: usize,
+) -> *mut c_char {
+    guarded_coded(ptr::null_mut(), err_buf, err_len, || {
+        let bank = bank.as_ref().ok_or_else(|| invalid_argument("bank is null".to_string()))?;
+        let query = parse_json(c_str(query_json, "query_json").map_err(invalid_argument)?)?;
+        let result = bank.execute_query_json(query).map_err(pipe_failure)?;
+        // Serialized JSON escapes control characters, so it never holds a NUL
+        Ok(CString::new(result.to_string()).unwrap_or_default().into_raw())
+    })
+}
+
+/// Open account `id` held by `name`
+///
+/// # Safety
+///
+/// `bank` must come from `rmemimg_bank_new`; `id` and `name` must be NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn rmemimg_bank_create_account(
+    bank: *mut BankProcessor,
+    id: *const c_char,
+    name: *const c_char,
+) -> c_int {
+    guarded(-1, || {
+        let bank = processor_mut(bank)?;
+        let command = BankCommand::CreateAccount {
+            id: c_str(id, "id")?.into(),
+            name: c_str(name, "name")?.to_string(),
+            opening_balance: None,
+        };
+        execute(bank, command)
+    })
+}
+
+/// Credit `amount`, a decimal string such as `"1,000.50"`, to `account_id`
+///
+/// # Safety
+///
+/// `bank` must come from `rmemimg_bank_new`; `account_id` and `amount` must be NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn rmemimg_bank_deposit(
+    bank: *mut BankProcessor,
+    account_id: *const c_char,
+    amount: *const c_char,
+) -> c_int {
+    guarded(-1, || {
+        let bank = processor_mut(bank)?;
+        let command = BankCommand::Deposit {
+            account_id: c_str(account_id, "account_id")?.into(),
+            amount: parse_amount(c_str(amount, "amount")?).map_err(|e| e.to_string())?,
+        };
+        execute(bank, command)
+    })
+}
+
+/// Balance of `account_id` as a decimal string; release it with `rmemimg_string_free`
+///
+/// # Safety
+///
+/// `bank` must come from `rmemimg_bank_new`; `account_id` must be a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn rmemimg_bank_get_balance(bank: *const BankProcessor, account_id: *const c_char) -> *mut c_char {
+    guarded(ptr::null_mut(), || {
+        let bank = bank.as_ref().ok_or_else(|| "bank is null".to_string())?;
+        let query = GetBalance { account_id: c_str(account_id, "account_id")?.into() };
+        let balance = bank.execute_query(&query).map_err(|e| domain_error_message(&e))?;
+        let balance = CString::new(balance.to_string()).map_err(|e| e.to_string())?;
+        Ok(balance.into_raw())
+    })
+}
+
+/// Release a bank created by `rmemimg_bank_new` or `rmemimg_bank_open`, flushing its log; null is ignored
+///
+/// A panic while flushing is caught and left in `rmemimg_last_error` rather than unwinding into C.
+///
+/// # Safety
+///
+/// `bank` must come from `rmemimg_bank_new` or `rmemimg_bank_open` and must not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn rmemimg_bank_free(bank: *mut BankProcessor) {
+    guarded((), || {
+        if !bank.is_null() {
+            drop(Box::from_raw(bank));
+        }
+        Ok(())
+    })
+}
+
+/// Release a string returned by this library; null is ignored
+///
+/// # Safety
+///
+/// `value` must come from this library and must not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn rmemimg_string_free(value: *mut c_char) {
+    guarded((), || {
+        if !value.is_null() {
+            drop(CString::from_raw(value));
+        }
+        Ok(())
+    })
+}
+
+/// Message for the last failure on this thread, or null; valid until the next call on this thread
+#[no_mangle]
+pub extern "C" fn rmemimg_last_error() -> *const c_char {
+    LAST_ERROR.with(|last_error| {
+        last_error
+            .borrow()
+            .as_ref()
+            .map_or(ptr::null(), |message| message.as_ptr())
+    })
+}
@@ -1 +1,14 @@
+// Lets `#[derive(Command)]` output refer to `::rmemimg` from inside this crate too
+extern crate self as rmemimg;
+
 pub mod memimg;
+
+#[cfg(feature = "python")]
+mod python;
+
+/// C API over an in-memory bank processor
+///
+/// Functions returning `int` yield 0 on success and -1 on failure; pointer-returning functions
+/// yield null on failure. After a failure, `rmemimg_last_error` describes it.
+#[cfg(feature = "ffi")]
+pub mod ffi;
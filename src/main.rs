@@ -1,53 +1,168 @@
 use rmemimg::memimg::bank::{Bank, BankCommand, GetBalance};
+#[cfg(not(feature = "signals"))]
+use rmemimg::memimg::bank_pipe::run_pipe;
+#[cfg(feature = "signals")]
+use rmemimg::memimg::bank_pipe::run_pipe_until;
 use rmemimg::memimg::bank_storage::BankJsonConverter;
-use rmemimg::memimg::{MemImgProcessor, TextFileEventStorage};
+use rmemimg::memimg::{MemImgConfig, MemImgProcessor, ReplayMetrics, SnapshotFormat, TextFileEventStorage};
 use rust_decimal::Decimal;
 
+const SNAPSHOT_VERSION: u32 = 1;
+
+/// `--verify` exit codes besides 0 for a match; other failures exit with 2
+#[cfg(feature = "admin")]
+const VERIFY_MISMATCH: i32 = 1;
+#[cfg(feature = "admin")]
+const VERIFY_NOT_RECORDED: i32 = 3;
+
+type BankProcessor = MemImgProcessor<Bank, BankCommand, TextFileEventStorage<BankCommand, BankJsonConverter>>;
+
+/// The file named by `--config path`, or the defaults, with `MEMIMG_*` environment overrides either way
+fn load_config() -> Result<MemImgConfig, Box<dyn std::error::Error + Send + Sync>> {
+    let args: Vec<String> = std::env::args().collect();
+    let config = match args.iter().position(|arg| arg == "--config") {
+        Some(index) => MemImgConfig::load(args.get(index + 1).ok_or("--config needs a path")?)?,
+        None => MemImgConfig::default().with_overrides(std::env::vars())?.validated()?,
+    };
+    Ok(config)
+}
+
+fn open_bank(config: &MemImgConfig) -> Result<(BankProcessor, ReplayMetrics), Box<dyn std::error::Error + Send + Sync>> {
+    let storage = Box::new(config.open_storage("bank_events.json", BankJsonConverter::new())?);
+    Ok(config.open_processor(Bank::new(), storage, &SnapshotFormat::new(SNAPSHOT_VERSION))?)
+}
+
+/// Check `bank_events.json` against its recorded fingerprint without locking it, returning the exit code
+#[cfg(feature = "admin")]
+fn verify(config: &MemImgConfig) -> i32 {
+    use rmemimg::memimg::admin::{verify_against_recorded, LogStore};
+
+    let mut store = LogStore::new("bank_events.json");
+    if let Some(snapshot_path) = &config.snapshot_path {
+        store = store.with_snapshot(snapshot_path);
+    }
+    let check = match verify_against_recorded(&store, Bank::new(), BankJsonConverter::new(), &SnapshotFormat::new(SNAPSHOT_VERSION)) {
+        Ok(check) => check,
+        Err(e) => {
+            eprintln!("verify: {}", e);
+            return 2;
+        }
+    };
+    let replayed = format!("{} events, fingerprint {}", check.replayed.event_count, check.replayed.fingerprint);
+    match &check.recorded {
+        None => {
+            eprintln!("verify: no fingerprint recorded at {}; replayed {}", store.fingerprint_path().display(), replayed);
+            VERIFY_NOT_RECORDED
+        }
+        Some(recorded) if !check.matches() => {
+            eprintln!("verify: mismatch");
+            eprintln!("  recorded: {} events, fingerprint {}", recorded.event_count, recorded.fingerprint);
+            eprintln!("  replayed: {}", replayed);
+            eprintln!("  log now holds {} events", check.store_event_count);
+            VERIFY_MISMATCH
+        }
+        Some(_) => {
+            println!("verify: ok; {} ({} in the log now)", replayed, check.store_event_count);
+            0
+        }
+    }
+}
+
 fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let config = load_config()?;
+
+    // --pipe: read NDJSON commands/queries from stdin, write NDJSON results to stdout
+    if std::env::args().any(|arg| arg == "--pipe") {
+        let (mut processor, _) = open_bank(&config)?;
+        #[cfg(feature = "signals")]
+        {
+            let guard = rmemimg::memimg::ShutdownGuard::install()?;
+            run_pipe_until(&mut processor, std::io::stdin().lock(), std::io::stdout().lock(), || guard.should_stop())?;
+            config.checkpoint_if_due(&mut processor, &SnapshotFormat::new(SNAPSHOT_VERSION))?;
+            guard.close(processor)?;
+            return Ok(());
+        }
+        #[cfg(not(feature = "signals"))]
+        {
+            run_pipe(&mut processor, std::io::stdin().lock(), std::io::stdout().lock())?;
+            config.checkpoint_if_due(&mut processor, &SnapshotFormat::new(SNAPSHOT_VERSION))?;
+            return Ok(());
+        }
+    }
+
+    // --verify: compare the replayed state with the fingerprint `memimg-admin record-fingerprint` recorded
+    #[cfg(feature = "admin")]
+    if std::env::args().any(|arg| arg == "--verify") {
+        std::process::exit(verify(&config));
+    }
+
+    // --dump: write the replayed state to stdout as one line of JSON
+    if std::env::args().any(|arg| arg == "--dump") {
+        let (processor, _) = open_bank(&config)?;
+        processor.export_state(std::io::BufWriter::new(std::io::stdout().lock()))?;
+        return Ok(());
+    }
+
     println!("=== Memory Image Pattern Demo ===\n");
 
-    // Create bank and event storage
-    let bank = Bank::new();
-    let storage = Box::new(TextFileEventStorage::new("bank_events.json", BankJsonConverter)?);
-    let mut processor = MemImgProcessor::new(bank, storage).map_err(|e| -> Box<dyn std::error::Error + Send + Sync> { Box::new(std::io::Error::new(std::io::ErrorKind::Other, e.to_string())) })?;
+    // Open the bank, replaying its event storage
+    let (mut processor, metrics) = open_bank(&config)?;
+    println!(
+        "Replayed {} events in {:?} ({} skipped)\n",
+        metrics.events_replayed, metrics.replay_duration, metrics.skip_count
+    );
 
     // Execute commands
     println!("Creating accounts...");
     processor.execute_command(BankCommand::CreateAccount {
-        id: "alice".to_string(),
+        id: "alice".into(),
         name: "Alice".to_string(),
-    }).map_err(|e| -> Box<dyn std::error::Error + Send + Sync> { Box::new(std::io::Error::new(std::io::ErrorKind::Other, e.to_string())) })?;
+        opening_balance: None,
+    }).map_err(|e| -> Box<dyn std::error::Error + Send + Sync> { Box::new(std::io::Error::other(e.to_string())) })?;
 
     processor.execute_command(BankCommand::CreateAccount {
-        id: "bob".to_string(),
+        id: "bob".into(),
         name: "Bob".to_string(),
-    }).map_err(|e| -> Box<dyn std::error::Error + Send + Sync> { Box::new(std::io::Error::new(std::io::ErrorKind::Other, e.to_string())) })?;
+        opening_balance: None,
+    }).map_err(|e| -> Box<dyn std::error::Error + Send + Sync> { Box::new(std::io::Error::other(e.to_string())) })?;
+
+    println!("Opening Carol's account with $250...");
+    processor.execute_command(BankCommand::CreateAccount {
+        id: "carol".into(),
+        name: "Carol".to_string(),
+        opening_balance: Some(Decimal::new(250, 0)),
+    }).map_err(|e| -> Box<dyn std::error::Error + Send + Sync> { Box::new(std::io::Error::other(e.to_string())) })?;
 
     println!("Depositing $1000 to Alice's account...");
     processor.execute_command(BankCommand::Deposit {
-        account_id: "alice".to_string(),
+        account_id: "alice".into(),
         amount: Decimal::new(1000, 0),
-    }).map_err(|e| -> Box<dyn std::error::Error + Send + Sync> { Box::new(std::io::Error::new(std::io::ErrorKind::Other, e.to_string())) })?;
+    }).map_err(|e| -> Box<dyn std::error::Error + Send + Sync> { Box::new(std::io::Error::other(e.to_string())) })?;
 
     println!("Transferring $300 from Alice to Bob...");
     processor.execute_command(BankCommand::Transfer {
-        from_account_id: "alice".to_string(),
-        to_account_id: "bob".to_string(),
+        from_account_id: "alice".into(),
+        to_account_id: "bob".into(),
         amount: Decimal::new(300, 0),
-    }).map_err(|e| -> Box<dyn std::error::Error + Send + Sync> { Box::new(std::io::Error::new(std::io::ErrorKind::Other, e.to_string())) })?;
+    }).map_err(|e| -> Box<dyn std::error::Error + Send + Sync> { Box::new(std::io::Error::other(e.to_string())) })?;
 
     // Query balances
     let alice_balance = processor.execute_query(&GetBalance {
-        account_id: "alice".to_string(),
-    }).map_err(|e| -> Box<dyn std::error::Error + Send + Sync> { Box::new(std::io::Error::new(std::io::ErrorKind::Other, e.to_string())) })?;
+        account_id: "alice".into(),
+    }).map_err(|e| -> Box<dyn std::error::Error + Send + Sync> { Box::new(std::io::Error::other(e.to_string())) })?;
     let bob_balance = processor.execute_query(&GetBalance {
-        account_id: "bob".to_string(),
-    }).map_err(|e| -> Box<dyn std::error::Error + Send + Sync> { Box::new(std::io::Error::new(std::io::ErrorKind::Other, e.to_string())) })?;
+        account_id: "bob".into(),
+    }).map_err(|e| -> Box<dyn std::error::Error + Send + Sync> { Box::new(std::io::Error::other(e.to_string())) })?;
+    let carol_balance = processor.execute_query(&GetBalance {
+        account_id: "carol".into(),
+    }).map_err(|e| -> Box<dyn std::error::Error + Send + Sync> { Box::new(std::io::Error::other(e.to_string())) })?;
 
     println!("\n=== Final Balances ===");
     println!("Alice: ${}", alice_balance);
     println!("Bob: ${}", bob_balance);
+    println!("Carol: ${}", carol_balance);
 
+    config.checkpoint_if_due(&mut processor, &SnapshotFormat::new(SNAPSHOT_VERSION))?;
     println!("\nAll commands saved to bank_events.json");
     println!("Try running again to see state restored from events!");
 
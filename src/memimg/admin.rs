@@ -0,0 +1,400 @@
+use crate::memimg::config::with_suffix;
+use crate::memimg::processor::{Command, MemImgProcessor};
+use crate::memimg::snapshot::{Snapshot, SnapshotFormat};
+use crate::memimg::storage::{EventStorage, ReplayPolicy, TextConverter};
+use crate::memimg::text_file_storage::{LogLock, TextFileEventStorage};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// Processor over a store, with the event count its snapshot covers
+type OpenStore<S, C, T> = (MemImgProcessor<S, C, TextFileEventStorage<C, T>>, u64);
+
+/// A log and the snapshot it continues from, as left by `MemImgProcessor::checkpoint_and_compact`
+///
+/// The state is the snapshot's, or the initial state if there is no snapshot file yet, with every
+/// event in the log replayed on top.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogStore {
+    pub log: PathBuf,
+    pub snapshot: PathBuf,
+}
+
+impl LogStore {
+    /// Store for the log at `log`, with its snapshot at `<log>.snapshot`
+    pub fn new(log: impl Into<PathBuf>) -> Self {
+        let log = log.into();
+        let snapshot = with_suffix(&log, ".snapshot");
+        Self { log, snapshot }
+    }
+
+    pub fn with_snapshot(mut self, snapshot: impl Into<PathBuf>) -> Self {
+        self.snapshot = snapshot.into();
+        self
+    }
+
+    /// Where `record_fingerprint`, `snapshot` and `compact` record the state's fingerprint: `<log>.fingerprint`
+    pub fn fingerprint_path(&self) -> PathBuf {
+        with_suffix(&self.log, ".fingerprint")
+    }
+
+    /// Where compaction and migration keep the previous copy of `file`
+    pub fn backup_path(file: &Path) -> PathBuf {
+        with_suffix(file, ".bak")
+    }
+
+    fn read_snapshot<S: DeserializeOwned>(&self, format: &SnapshotFormat) -> Result<Option<Snapshot<S>>, Box<dyn std::error::Error + Send + Sync>> {
+        match File::open(&self.snapshot) {
+            Ok(file) => Ok(Some(format.read(std::io::BufReader::new(file))?)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(Box::new(e)),
+        }
+    }
+
+    /// Processor over the store's state, with the event count the snapshot covers
+    fn open<S, C, T>(
+        &self,
+        initial: S,
+        converter: T,
+        format: &SnapshotFormat,
+        replay_policy: ReplayPolicy,
+    ) -> Result<OpenStore<S, C, T>, Box<dyn std::error::Error + Send + Sync>>
+    where
+        S: Clone + DeserializeOwned,
+        C: Command<System = S>,
+        T: TextConverter<C>,
+    {
+        let storage = Box::new(TextFileEventStorage::new_unlocked(&self.log, converter)?.with_replay_policy(replay_policy));
+        match self.read_snapshot(format)? {
+            Some(snapshot) => {
+                let base = snapshot.event_count;
+                Ok((MemImgProcessor::from_snapshot(snapshot, storage)?, base))
+            }
+            None => Ok((MemImgProcessor::new_simple(initial, storage)?, 0)),
+        }
+    }
+}
+
+/// Hex SHA-256 of the state's JSON, equal for equal states when `S` serializes deterministically
+pub fn fingerprint<S: Serialize>(state: &S) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    let digest = Sha256::digest(serde_json::to_vec(state)?);
+    Ok(digest.iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+/// Write `contents` to `path` through a temporary file, so readers never see it half-written
+fn replace_file(path: &Path, contents: impl FnOnce(&mut BufWriter<File>) -> Result<(), Box<dyn std::error::Error + Send + Sync>>) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let temp_path = with_suffix(path, ".tmp");
+    let mut writer = BufWriter::new(File::create(&temp_path)?);
+    contents(&mut writer)?;
+    writer.flush()?;
+    writer.get_ref().sync_all()?;
+    drop(writer);
+    std::fs::rename(&temp_path, path)?;
+    Ok(())
+}
+
+/// Copy `file`, if it exists, to its backup path
+fn back_up(file: &Path) -> Result<Option<PathBuf>, Box<dyn std::error::Error + Send + Sync>> {
+    if !file.exists() {
+        return Ok(None);
+    }
+    let backup = LogStore::backup_path(file);
+    std::fs::copy(file, &backup)?;
+    Ok(Some(backup))
+}
+
+/// Outcome of `snapshot`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SnapshotReport {
+    pub path: PathBuf,
+    pub event_count: u64,
+    pub fingerprint: String,
+}
+
+/// Write a snapshot of the store's current state to `out`, leaving the store as it is, and record
+/// the state's fingerprint
+///
+/// `out` must not be the store's own snapshot, which must stay in step with the log; `compact`
+/// replaces that one. Fails if the log is locked.
+pub fn snapshot<S, C, T>(store: &LogStore, initial: S, converter: T, format: &SnapshotFormat, out: &Path) -> Result<SnapshotReport, Box<dyn std::error::Error + Send + Sync>>
+where
+    S: Clone + Serialize + DeserializeOwned,
+    C: Command<System = S>,
+    T: TextConverter<C>,
+{
+    if out == store.snapshot {
+        return Err(format!("{} is the store's own snapshot; compact the store to replace it", out.display()).into());
+    }
+    let _lock = LogLock::acquire(&store.log)?;
+    let (processor, _) = store.open(initial, converter, format, ReplayPolicy::Strict)?;
+    let event_count = processor.event_version().as_u64();
+    replace_file(out, |writer| format.write(writer, processor.system(), event_count))?;
+    let recorded = write_recorded_fingerprint(store, processor.system(), event_count)?;
+    Ok(SnapshotReport {
+        path: out.to_path_buf(),
+        event_count,
+        fingerprint: recorded.fingerprint,
+    })
+}
+
+/// Outcome of `compact`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CompactReport {
+    pub event_count: u64,
+    pub events_removed: u64,
+    pub snapshot_size_bytes: u64,
+    /// Copies of the log and of the previous snapshot, if there was one
+    pub backups: Vec<PathBuf>,
+}
+
+/// Replace the store's snapshot with one of its current state, empty the log and record the
+/// state's fingerprint
+///
+/// The log and snapshot are first copied to their backup paths, which restore the store if
+/// compaction is interrupted. Fails if the log is locked.
+pub fn compact<S, C, T>(store: &LogStore, initial: S, converter: T, format: &SnapshotFormat) -> Result<CompactReport, Box<dyn std::error::Error + Send + Sync>>
+where
+    S: Clone + Serialize + DeserializeOwned,
+    C: Command<System = S>,
+    T: TextConverter<C>,
+{
+    let _lock = LogLock::acquire(&store.log)?;
+    let backups = [&store.log, &store.snapshot]
+        .into_iter()
+        .filter_map(|file| back_up(file).transpose())
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let (mut processor, _) = store.open(initial, converter, format, ReplayPolicy::Strict)?;
+    let temp_path = with_suffix(&store.snapshot, ".tmp");
+    let mut temp = File::create(&temp_path)?;
+    let compaction = processor.checkpoint_and_compact(format, &mut temp)?;
+    temp.sync_all()?;
+    drop(temp);
+    std::fs::rename(&temp_path, &store.snapshot)?;
+    write_recorded_fingerprint(store, processor.system(), processor.event_version().as_u64())?;
+
+    Ok(CompactReport {
+        event_count: processor.event_version().as_u64(),
+        events_removed: compaction.events_removed,
+        snapshot_size_bytes: compaction.snapshot_size_bytes,
+        backups,
+    })
+}
+
+/// A snapshot `verify` compared the store's state with
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ReferenceCheck {
+    pub path: PathBuf,
+    pub event_count: u64,
+    pub fingerprint: String,
+}
+
+/// Outcome of `verify`; the store checks out when `problems` is empty
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct VerifyReport {
+    /// Events the store's snapshot covers, if it has one
+    pub snapshot_event_count: Option<u64>,
+    /// Events replayed from the log on top of the snapshot
+    pub log_events: u64,
+    /// Fingerprint of the store's state, when it could be rebuilt
+    pub fingerprint: Option<String>,
+    pub reference: Option<ReferenceCheck>,
+    pub problems: Vec<String>,
+}
+
+impl VerifyReport {
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+/// Rebuild the store's state, reporting unreadable snapshots, log lines and events as problems
+///
+/// Replay is lenient so every bad line is listed, not just the first; a torn last line is
+/// repaired as on any replay and reported. When `reference` names a snapshot, such as one written
+/// by `snapshot`, its state must match the store's if both cover the same events. Fails only if
+/// the log is locked.
+pub fn verify<S, C, T>(
+    store: &LogStore,
+    initial: S,
+    converter: T,
+    format: &SnapshotFormat,
+    reference: Option<&Path>,
+) -> Result<VerifyReport, Box<dyn std::error::Error + Send + Sync>>
+where
+    S: Clone + Serialize + DeserializeOwned,
+    C: Command<System = S>,
+    T: TextConverter<C>,
+{
+    let _lock = LogLock::acquire(&store.log)?;
+    let mut report = VerifyReport {
+        snapshot_event_count: None,
+        log_events: 0,
+        fingerprint: None,
+        reference: None,
+        problems: Vec::new(),
+    };
+
+    match store.open(initial, converter, format, ReplayPolicy::Lenient) {
+        Ok((processor, base)) => {
+            report.snapshot_event_count = store.snapshot.exists().then_some(base);
+            report.log_events = processor.event_version().as_u64() - base;
+            report.fingerprint = Some(fingerprint(processor.system())?);
+            report.problems.extend(processor.warnings().iter().map(|warning| format!("{}: {}", store.log.display(), warning)));
+        }
+        Err(e) => report.problems.push(format!("cannot rebuild the state: {}", e)),
+    }
+
+    if let Some(path) = reference {
+        match File::open(path).map_err(Into::into).and_then(|file| format.read::<S, _>(std::io::BufReader::new(file))) {
+            Ok(snapshot) => {
+                let check = ReferenceCheck {
+                    path: path.to_path_buf(),
+                    event_count: snapshot.event_count,
+                    fingerprint: fingerprint(&snapshot.state)?,
+                };
+                let event_count = report.snapshot_event_count.unwrap_or(0) + report.log_events;
+                if check.event_count != event_count {
+                    report.problems.push(format!("{} covers {} events, the store {}", path.display(), check.event_count, event_count));
+                } else if report.fingerprint.as_ref().is_some_and(|store| *store != check.fingerprint) {
+                    report.problems.push(format!("{} does not match the store's state", path.display()));
+                }
+                report.reference = Some(check);
+            }
+            Err(e) => report.problems.push(format!("cannot read {}: {}", path.display(), e)),
+        }
+    }
+    Ok(report)
+}
+
+/// Fingerprint of a store's state and how many events that state covers, as kept in its
+/// `fingerprint_path`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordedFingerprint {
+    pub event_count: u64,
+    pub fingerprint: String,
+}
+
+fn write_recorded_fingerprint<S: Serialize>(store: &LogStore, state: &S, event_count: u64) -> Result<RecordedFingerprint, Box<dyn std::error::Error + Send + Sync>> {
+    let recorded = RecordedFingerprint { event_count, fingerprint: fingerprint(state)? };
+    replace_file(&store.fingerprint_path(), |writer| Ok(serde_json::to_writer(writer, &recorded)?))?;
+    Ok(recorded)
+}
+
+fn read_recorded_fingerprint(store: &LogStore) -> Result<Option<RecordedFingerprint>, Box<dyn std::error::Error + Send + Sync>> {
+    match File::open(store.fingerprint_path()) {
+        Ok(file) => Ok(Some(serde_json::from_reader(std::io::BufReader::new(file))?)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(Box::new(e)),
+    }
+}
+
+/// Record the fingerprint of the store's current state, for `verify_against_recorded` to check
+/// later. Fails if the log is locked.
+pub fn record_fingerprint<S, C, T>(store: &LogStore, initial: S, converter: T, format: &SnapshotFormat) -> Result<RecordedFingerprint, Box<dyn std::error::Error + Send + Sync>>
+where
+    S: Clone + Serialize + DeserializeOwned,
+    C: Command<System = S>,
+    T: TextConverter<C>,
+{
+    let _lock = LogLock::acquire(&store.log)?;
+    let (processor, _) = store.open(initial, converter, format, ReplayPolicy::Strict)?;
+    write_recorded_fingerprint(store, processor.system(), processor.event_version().as_u64())
+}
+
+/// Outcome of `verify_against_recorded`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FingerprintCheck {
+    /// The recorded fingerprint, if one was ever recorded
+    pub recorded: Option<RecordedFingerprint>,
+    /// The state rebuilt from the events the recording covers, or from all of them without one
+    pub replayed: RecordedFingerprint,
+    /// Events in the store, including any appended since the recording
+    pub store_event_count: u64,
+}
+
+impl FingerprintCheck {
+    pub fn matches(&self) -> bool {
+        self.recorded.as_ref() == Some(&self.replayed)
+    }
+}
+
+/// Rebuild the state the recorded fingerprint describes and compare the two
+///
+/// Only reads the store, without taking its lock, so it can run beside the processor writing the
+/// log: events appended after the recording are counted but not replayed, and a line still being
+/// appended is left alone. Fails if the snapshot or a log line cannot be read or an event does
+/// not apply; a mismatch or missing recording is reported in the check.
+pub fn verify_against_recorded<S, C, T>(store: &LogStore, initial: S, converter: T, format: &SnapshotFormat) -> Result<FingerprintCheck, Box<dyn std::error::Error + Send + Sync>>
+where
+    S: Clone + Serialize + DeserializeOwned,
+    C: Command<System = S>,
+    T: TextConverter<C>,
+{
+    if !store.log.exists() {
+        return Err(format!("{} does not exist", store.log.display()).into());
+    }
+    let recorded = read_recorded_fingerprint(store)?;
+    let (mut state, mut event_count) = match store.read_snapshot(format)? {
+        Some(snapshot) => (snapshot.state, snapshot.event_count),
+        None => (initial, 0),
+    };
+    let replay_limit = recorded.as_ref().map_or(u64::MAX, |recorded| recorded.event_count);
+    let mut replayed_count = event_count;
+
+    let storage = TextFileEventStorage::new_unlocked(&store.log, converter)?;
+    let mut cursor = storage.open_cursor()?;
+    loop {
+        let batch = cursor.next_batch(1024)?;
+        if batch.is_empty() {
+            break;
+        }
+        for event in batch {
+            event_count += 1;
+            if event_count <= replay_limit {
+                event.apply_to(&mut state).map_err(|e| format!("event {} does not apply: {}", event_count, e))?;
+                replayed_count = event_count;
+            }
+        }
+    }
+
+    Ok(FingerprintCheck {
+        recorded,
+        replayed: RecordedFingerprint { event_count: replayed_count, fingerprint: fingerprint(&state)? },
+        store_event_count: event_count,
+    })
+}
+
+/// Outcome of `migrate_format`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MigrateReport {
+    pub events: u64,
+    pub backup: PathBuf,
+}
+
+/// Rewrite every event in the log from the format `from` reads to the one `to` writes
+///
+/// The old log is kept at its backup path; the snapshot is untouched. Fails if the log is locked
+/// or any line does not parse.
+pub fn migrate_format<C, F, T>(store: &LogStore, from: F, to: T) -> Result<MigrateReport, Box<dyn std::error::Error + Send + Sync>>
+where
+    F: TextConverter<C>,
+    T: TextConverter<C>,
+{
+    let _lock = LogLock::acquire(&store.log)?;
+    let backup = back_up(&store.log)?.ok_or_else(|| format!("{} does not exist", store.log.display()))?;
+
+    let mut events = 0u64;
+    let mut source = TextFileEventStorage::new_unlocked(&store.log, from)?;
+    replace_file(&store.log, |writer| {
+        source.replay(&mut |event: C| {
+            writeln!(writer, "{}", to.format(&event)?)?;
+            events += 1;
+            Ok(())
+        })
+    })?;
+    Ok(MigrateReport { events, backup })
+}
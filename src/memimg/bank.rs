@@ -1,19 +1,559 @@
-use crate::memimg::processor::{Command, Query};
-use rust_decimal::Decimal;
+use crate::memimg::json_event::JsonEvent;
+use crate::memimg::processor::Query;
+use crate::memimg::validation::StateDiff;
+use crate::memimg::{Command, EventStorage, MemImgError, MemImgProcessor, QueryRegistry};
+use chrono::{DateTime, Days, Months, Utc};
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::{Decimal, RoundingStrategy};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::borrow::Borrow;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+use thiserror::Error;
 
 pub type Amount = Decimal;
 
-#[derive(Debug, Clone)]
+/// Domain errors raised by bank commands and queries
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum BankError {
+    #[error("Account not found: {0}")]
+    AccountNotFound(String),
+
+    #[error("Account closed: {0}")]
+    AccountClosed(String),
+
+    #[error("Account {account_id} cannot be closed with a balance of {balance}")]
+    NonZeroBalance { account_id: String, balance: Amount },
+
+    #[error("Insufficient funds: {available} < {requested}")]
+    InsufficientFunds { available: Amount, requested: Amount },
+
+    #[error("Duplicate account ID: {0}")]
+    DuplicateAccount(String),
+
+    #[error("Invalid amount: {0:?}")]
+    InvalidAmount(String),
+
+    #[error("Invalid account ID: {0:?}")]
+    InvalidAccountId(String),
+
+    #[error("{owner} already owns account {account_id}")]
+    DuplicateOwner { account_id: String, owner: String },
+
+    #[error("{owner} does not own account {account_id}")]
+    OwnerNotFound { account_id: String, owner: String },
+
+    #[error("Cannot remove {owner}, the last owner of account {account_id}")]
+    LastOwner { account_id: String, owner: String },
+
+    #[error("Duplicate external payment ID: {0}")]
+    DuplicateExternalPayment(String),
+
+    #[error("Account name already in use: {0}")]
+    DuplicateAccountName(String),
+
+    #[error("No interest is due on account {account_id} for the period ending {period_end}")]
+    InterestNotDue { account_id: String, period_end: DateTime<Utc> },
+}
+
+impl BankError {
+    /// Stable machine-readable code for this error
+    pub fn code(&self) -> &'static str {
+        match self {
+            BankError::AccountNotFound(_) => "ACCOUNT_NOT_FOUND",
+            BankError::AccountClosed(_) => "ACCOUNT_CLOSED",
+            BankError::NonZeroBalance { .. } => "NON_ZERO_BALANCE",
+            BankError::InsufficientFunds { .. } => "INSUFFICIENT_FUNDS",
+            BankError::DuplicateAccount(_) => "DUPLICATE_ACCOUNT",
+            BankError::InvalidAmount(_) => "INVALID_AMOUNT",
+            BankError::InvalidAccountId(_) => "INVALID_ACCOUNT_ID",
+            BankError::DuplicateOwner { .. } => "DUPLICATE_OWNER",
+            BankError::OwnerNotFound { .. } => "OWNER_NOT_FOUND",
+            BankError::LastOwner { .. } => "LAST_OWNER",
+            BankError::DuplicateExternalPayment(_) => "DUPLICATE_EXTERNAL_PAYMENT",
+            BankError::DuplicateAccountName(_) => "DUPLICATE_ACCOUNT_NAME",
+            BankError::InterestNotDue { .. } => "INTEREST_NOT_DUE",
+        }
+    }
+
+    /// Render this error through `formatter`, e.g. in the user's locale
+    pub fn render(&self, formatter: &dyn BankErrorFormatter) -> String {
+        formatter.format(self)
+    }
+}
+
+/// Pluggable rendering of bank errors; `Display` on `BankError` always gives the English text
+pub trait BankErrorFormatter {
+    fn format(&self, error: &BankError) -> String;
+}
+
+/// The built-in English messages
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EnglishBankErrors;
+
+impl BankErrorFormatter for EnglishBankErrors {
+    fn format(&self, error: &BankError) -> String {
+        error.to_string()
+    }
+}
+
+fn domain_error(error: &MemImgError) -> Option<&BankError> {
+    error.outcome().and_then(|outcome| outcome.source.downcast_ref::<BankError>())
+}
+
+/// Code of the `BankError` behind a processor error, falling back to the processor's own code
+pub(crate) fn domain_error_code(error: &MemImgError) -> &'static str {
+    domain_error(error).map(BankError::code).unwrap_or_else(|| error.code())
+}
+
+/// Message of the `BankError` behind a processor error, without the processor's framing
+pub(crate) fn domain_error_message(error: &MemImgError) -> String {
+    match domain_error(error) {
+        Some(bank_error) => bank_error.to_string(),
+        None => error.to_string(),
+    }
+}
+
+/// Parse a human-entered amount such as `"$1,000.50"`, stripping currency symbols and thousands separators
+pub fn parse_amount(s: &str) -> Result<Amount, BankError> {
+    let invalid = || BankError::InvalidAmount(s.to_string());
+
+    let trimmed = s.trim();
+    let (negative, unsigned) = match trimmed.strip_prefix('-') {
+        Some(rest) => (true, rest.trim_start()),
+        None => (false, trimmed),
+    };
+    let unsigned = unsigned.trim_start_matches(['$', '€', '£', '¥']).trim_start();
+
+    let (integer, fraction) = match unsigned.split_once('.') {
+        Some((integer, fraction)) => (integer, Some(fraction)),
+        None => (unsigned, None),
+    };
+
+    // Thousands separators must group the integer part in threes
+    let groups: Vec<&str> = integer.split(',').collect();
+    let well_grouped = groups.len() == 1
+        || (!groups[0].is_empty() && groups[0].len() <= 3 && groups[1..].iter().all(|group| group.len() == 3));
+    let all_digits = |part: &str| part.chars().all(|c| c.is_ascii_digit());
+    if integer.is_empty() || !well_grouped || !groups.iter().all(|group| all_digits(group)) {
+        return Err(invalid());
+    }
+    if fraction.is_some_and(|fraction| fraction.is_empty() || !all_digits(fraction)) {
+        return Err(invalid());
+    }
+
+    let digits = match fraction {
+        Some(fraction) => format!("{}.{}", groups.concat(), fraction),
+        None => groups.concat(),
+    };
+    let amount = Decimal::from_str(&digits).map_err(|_| invalid())?;
+    Ok(if negative { -amount } else { amount })
+}
+
+/// Account identifier; serializes as a plain string, so logs written with string ids still parse
+///
+/// Clones share one allocation, so the shadow copy of a bank does not reallocate its ids.
+/// `From` conversions accept any string; ids of new accounts are checked with `AccountId::validate`
+/// when the account is created.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct AccountId(Arc<str>);
+
+impl AccountId {
+    /// Maximum length of an account id, in characters
+    pub const MAX_LEN: usize = 64;
+
+    /// Validated id: non-empty, at most `MAX_LEN` characters, no whitespace or control characters
+    pub fn parse(id: &str) -> Result<Self, BankError> {
+        let id = AccountId::from(id);
+        id.validate()?;
+        Ok(id)
+    }
+
+    pub fn validate(&self) -> Result<(), BankError> {
+        let valid = !self.0.is_empty()
+            && self.0.chars().count() <= Self::MAX_LEN
+            && !self.0.chars().any(|c| c.is_whitespace() || c.is_control());
+        if !valid {
+            return Err(BankError::InvalidAccountId(self.0.to_string()));
+        }
+        Ok(())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Whether both ids share one allocation, as a map key and its account's `id` should
+    pub fn ptr_eq(&self, other: &AccountId) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl From<&str> for AccountId {
+    fn from(id: &str) -> Self {
+        Self(Arc::from(id))
+    }
+}
+
+impl From<String> for AccountId {
+    fn from(id: String) -> Self {
+        Self(Arc::from(id))
+    }
+}
+
+impl From<AccountId> for String {
+    fn from(id: AccountId) -> Self {
+        id.0.to_string()
+    }
+}
+
+impl Serialize for AccountId {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for AccountId {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(AccountId::from)
+    }
+}
+
+#[cfg(feature = "schemars")]
+impl schemars::JsonSchema for AccountId {
+    fn schema_name() -> std::borrow::Cow<'static, str> {
+        "AccountId".into()
+    }
+
+    fn json_schema(_generator: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        // Mirrors `AccountId::validate`, short of rejecting control characters
+        schemars::json_schema!({
+            "type": "string",
+            "minLength": 1,
+            "maxLength": AccountId::MAX_LEN,
+            "pattern": "^\\S+$",
+        })
+    }
+}
+
+#[cfg(feature = "http")]
+impl utoipa::PartialSchema for AccountId {
+    fn schema() -> utoipa::openapi::RefOr<utoipa::openapi::schema::Schema> {
+        utoipa::openapi::ObjectBuilder::new()
+            .schema_type(utoipa::openapi::schema::Type::String)
+            .min_length(Some(1))
+            .max_length(Some(AccountId::MAX_LEN))
+            .pattern(Some("^\\S+$"))
+            .into()
+    }
+}
+
+#[cfg(feature = "http")]
+impl utoipa::ToSchema for AccountId {}
+
+impl fmt::Display for AccountId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+// Lets maps keyed by `AccountId` be looked up with a `&str`
+impl Borrow<str> for AccountId {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
+impl AsRef<str> for AccountId {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl PartialEq<str> for AccountId {
+    fn eq(&self, other: &str) -> bool {
+        *self.0 == *other
+    }
+}
+
+impl PartialEq<&str> for AccountId {
+    fn eq(&self, other: &&str) -> bool {
+        *self.0 == **other
+    }
+}
+
+/// Name given to accounts opened by `Bank::auto_create_on_deposit`
+pub const AUTO_CREATED_ACCOUNT_NAME: &str = "Auto-created account";
+
+/// Bank state; serializes with snake_case field names and amounts as decimal strings.
+///
+/// Accounts and closed ids serialize sorted by id, so equal banks always produce identical
+/// output (as snapshot fingerprints require) regardless of `HashMap` iteration order.
+/// This is also the stable shape `MemImgProcessor::export_state` dumps: `accounts` maps each id
+/// to its `id`, `name`, `total_debits`, `total_credits`, `owners` and `last_modified_seq`;
+/// `closed_accounts` lists ids; `equity_capital` is an amount; fields added later are omitted
+/// while empty, so older dumps stay valid.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Bank {
-    pub accounts: HashMap<String, Account>,
+    #[serde(serialize_with = "serialize_sorted_map", deserialize_with = "deserialize_shared_ids")]
+    pub accounts: HashMap<AccountId, Account>,
+    /// Ids of closed accounts, kept so lookups can tell "closed" from "never existed"
+    #[serde(default, serialize_with = "serialize_sorted_set")]
+    pub closed_accounts: HashSet<AccountId>,
+    /// Net funds brought in from outside the bank: deposits less withdrawals
+    ///
+    /// Serialized without trailing zeros, as the scale of a running total depends on the amounts
+    /// that made it up, and `genesis_commands` reaches the same total through different ones.
+    #[serde(default, serialize_with = "serialize_normalized")]
+    pub equity_capital: Amount,
+    /// Gateway transaction ids of every external payment recorded, so none is credited twice
+    ///
+    /// Omitted from JSON while empty, so banks without external payments serialize as before.
+    #[serde(default, skip_serializing_if = "HashSet::is_empty", serialize_with = "serialize_sorted_set")]
+    pub external_payment_ids: HashSet<String>,
+    /// Open a missing deposit or transfer destination instead of rejecting the command
+    ///
+    /// Part of the state, so snapshots keep it; a log written under the policy must be replayed
+    /// into a bank that has it too. Omitted from JSON while off.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub auto_create_on_deposit: bool,
+    /// Number of commands that have changed the bank, stamped on the accounts each one touched
+    ///
+    /// Omitted from JSON while zero.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub modification_seq: u64,
+    /// Compound interest schedule of each account that has one; omitted from JSON while empty
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub interest_schedules: BTreeMap<AccountId, InterestSchedule>,
+    /// What is known of closed accounts while the bank keeps tombstones; see `with_tombstones`
+    ///
+    /// Omitted from JSON while empty.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub tombstones: BTreeMap<AccountId, Tombstone>,
+    /// Most tombstones kept, or `None` to keep none
+    #[serde(skip)]
+    tombstone_limit: Option<usize>,
+    /// Most balance points each account keeps, or `None` to keep no history; see `with_balance_history`
+    #[serde(skip)]
+    balance_history_limit: Option<usize>,
+    /// Reject accounts named like an open one; see `with_unique_names`
+    #[serde(skip)]
+    pub require_unique_names: bool,
+}
+
+fn serialize_sorted_map<S: serde::Serializer>(map: &HashMap<AccountId, Account>, serializer: S) -> Result<S::Ok, S::Error> {
+    map.iter().collect::<BTreeMap<_, _>>().serialize(serializer)
+}
+
+// Keys each account by its own `id`, so the key and the field share one allocation
+fn deserialize_shared_ids<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<HashMap<AccountId, Account>, D::Error> {
+    let accounts = HashMap::<String, Account>::deserialize(deserializer)?;
+    accounts
+        .into_iter()
+        .map(|(key, account)| {
+            if account.id != *key {
+                return Err(serde::de::Error::custom(format!("account keyed {} has id {}", key, account.id)));
+            }
+            Ok((account.id.clone(), account))
+        })
+        .collect()
+}
+
+fn serialize_sorted_set<T: Ord + Serialize, S: serde::Serializer>(set: &HashSet<T>, serializer: S) -> Result<S::Ok, S::Error> {
+    set.iter().collect::<BTreeSet<_>>().serialize(serializer)
+}
+
+fn serialize_normalized<S: serde::Serializer>(amount: &Amount, serializer: S) -> Result<S::Ok, S::Error> {
+    Serialize::serialize(&amount.normalize(), serializer)
 }
 
 impl Bank {
     pub fn new() -> Self {
         Self {
             accounts: HashMap::new(),
+            closed_accounts: HashSet::new(),
+            equity_capital: Amount::ZERO,
+            external_payment_ids: HashSet::new(),
+            auto_create_on_deposit: false,
+            modification_seq: 0,
+            interest_schedules: BTreeMap::new(),
+            tombstones: BTreeMap::new(),
+            tombstone_limit: None,
+            balance_history_limit: None,
+            require_unique_names: false,
+        }
+    }
+
+    /// Auto-create the destinations of deposits and transfers that do not exist yet, named
+    /// `AUTO_CREATED_ACCOUNT_NAME`; closed accounts still reject them
+    pub fn with_auto_create_on_deposit(mut self) -> Self {
+        self.auto_create_on_deposit = true;
+        self
+    }
+
+    /// Record each account's balance, with the time, whenever it changes, keeping the latest
+    /// `max_points` per account for `GetBalanceHistory`
+    ///
+    /// The history is a view for charting, not state: it is neither serialized nor replayed from
+    /// the log, so points recorded by a replay carry the time of the replay.
+    pub fn with_balance_history(mut self, max_points: usize) -> Self {
+        self.balance_history_limit = Some(max_points);
+        self
+    }
+
+    /// Keep a `Tombstone` for each account closed, up to the latest `max_tombstones`
+    ///
+    /// Tombstones are part of the state, so snapshots keep them and an account's closure is still
+    /// known once compaction has dropped its `CloseAccount` from the log; see
+    /// `GetAuditTrail::execute_with_tombstone`. The limit is not part of the state: replay a log
+    /// into a bank built with the same one.
+    pub fn with_tombstones(mut self, max_tombstones: usize) -> Self {
+        self.tombstone_limit = Some(max_tombstones);
+        self
+    }
+
+    /// Reject creating or importing an account named like an open one
+    ///
+    /// Logs written without the policy may hold duplicate names, so it is not part of the state:
+    /// replay such a log into a bank without it, then set `require_unique_names` on the live bank
+    /// to enforce it on new commands.
+    pub fn with_unique_names(mut self) -> Self {
+        self.require_unique_names = true;
+        self
+    }
+
+    /// Sum of credits across all open accounts
+    pub fn total_credits(&self) -> Amount {
+        self.accounts.values().map(|account| account.total_credits).sum()
+    }
+
+    /// Sum of debits across all open accounts
+    pub fn total_debits(&self) -> Amount {
+        self.accounts.values().map(|account| account.total_debits).sum()
+    }
+
+    /// Interest `account_id` accrues for its next period: the balance times the period's share of
+    /// the annual rate, rounded half away from zero to cents, or `None` without a schedule
+    ///
+    /// Negative when the account is overdrawn and its schedule charges for it.
+    pub fn interest_due(&self, account_id: &AccountId) -> Option<Amount> {
+        let schedule = self.interest_schedules.get(account_id)?;
+        let balance = self.accounts.get(account_id)?.balance();
+        if balance < Amount::ZERO && schedule.negative_balance == NegativeBalancePolicy::Skip {
+            return Some(Amount::ZERO);
+        }
+        let interest = balance * schedule.annual_rate / schedule.period.per_year();
+        Some(interest.round_dp_with_strategy(2, RoundingStrategy::MidpointAwayFromZero))
+    }
+
+    /// Overdraft fee `ApplyOverdraftFee` charges `account_id`: `fee` while it is overdrawn, cut so
+    /// its balance stays at or above `floor`, and nothing while it is not; `None` for an unknown account
+    pub fn overdraft_fee_due(&self, account_id: &AccountId, fee: Amount, floor: Amount) -> Option<Amount> {
+        let balance = self.accounts.get(account_id)?.balance();
+        if balance >= Amount::ZERO {
+            return Some(Amount::ZERO);
+        }
+        Some(fee.min(balance - floor).max(Amount::ZERO))
+    }
+
+    /// Unresolved `AccrueInterest` commands for every period ended by `now`, in order for each
+    /// account and accounts in id order
+    pub fn due_interest(&self, now: DateTime<Utc>) -> Vec<BankCommand> {
+        let mut commands = Vec::new();
+        for (account_id, schedule) in &self.interest_schedules {
+            let mut n = schedule.periods_accrued + 1;
+            while schedule.period.end_of(schedule.starts_at, n) <= now {
+                let period_end = schedule.period.end_of(schedule.starts_at, n);
+                commands.push(BankCommand::AccrueInterest { account_id: account_id.clone(), period_end, amount: None });
+                n += 1;
+            }
+        }
+        commands
+    }
+
+    /// The bank `command` would leave behind, or why it fails; `self` is never changed
+    ///
+    /// Clones the whole bank, like the processor's shadow copy, so command logic can be tested
+    /// functionally without a processor or storage.
+    pub fn apply(&self, command: &BankCommand) -> Result<Bank, BankError> {
+        let mut next = self.clone();
+        command
+            .apply_to(&mut next)
+            .map_err(|e| *e.downcast::<BankError>().expect("bank command handlers only fail with BankError"))?;
+        Ok(next)
+    }
+
+    /// Commands rebuilding this bank from an empty one, for `MemImgProcessor::bootstrap_from_state`
+    ///
+    /// Each account is created, credited with its `total_credits` through a `Deposit`, debited with
+    /// its `total_debits` through a `Withdrawal` and given its other owners; closed accounts are
+    /// created and closed. Accounts go in id order. A bank that has changed ends with a
+    /// `RestoreModificationSeqs` putting back the stamps those commands renumbered. External payment
+    /// ids, interest schedules and the auto-create policy have no such commands, so a bank with any
+    /// of them is not reproduced; nor are tombstones, which closing the accounts again renumbers.
+    pub fn genesis_commands(&self) -> Vec<BankCommand> {
+        let mut commands = Vec::new();
+        let mut accounts: Vec<_> = self.accounts.values().collect();
+        accounts.sort_by(|a, b| a.id.cmp(&b.id));
+        for account in accounts {
+            commands.push(BankCommand::CreateAccount { id: account.id.clone(), name: account.name.clone(), opening_balance: None });
+            if !account.total_credits.is_zero() {
+                commands.push(BankCommand::Deposit { account_id: account.id.clone(), amount: account.total_credits });
+            }
+            if !account.total_debits.is_zero() {
+                commands.push(BankCommand::Withdrawal { account_id: account.id.clone(), amount: account.total_debits });
+            }
+            for owner in account.owners.iter().filter(|owner| **owner != account.name) {
+                commands.push(BankCommand::AddOwner { account_id: account.id.clone(), owner: owner.clone() });
+            }
+            if !account.owners.contains(&account.name) {
+                commands.push(BankCommand::RemoveOwner { account_id: account.id.clone(), owner: account.name.clone() });
+            }
+        }
+        let mut closed: Vec<_> = self.closed_accounts.iter().collect();
+        closed.sort();
+        for id in closed {
+            commands.push(BankCommand::CreateAccount { id: id.clone(), name: id.to_string(), opening_balance: None });
+            commands.push(BankCommand::CloseAccount { id: id.clone() });
+        }
+        if self.modification_seq > 0 {
+            let mut stamps: Vec<_> = self.accounts.values().map(|account| (account.id.clone(), account.last_modified_seq)).collect();
+            stamps.sort();
+            commands.push(BankCommand::RestoreModificationSeqs { modification_seq: self.modification_seq, stamps });
+        }
+        commands
+    }
+
+    /// The account to open for deposit or transfer destination `account_id`, `None` if it exists
+    ///
+    /// A missing destination is an error unless the auto-create policy is on and the id was never closed.
+    fn new_destination(&self, account_id: &AccountId) -> Result<Option<Account>, BankError> {
+        if self.accounts.contains_key(account_id) {
+            return Ok(None);
+        }
+        if !self.auto_create_on_deposit || self.closed_accounts.contains(account_id) {
+            return Err(Bank::account_not_found(&self.closed_accounts, account_id.as_str()));
+        }
+        account_id.validate()?;
+        Ok(Some(Account::new(account_id.clone(), AUTO_CREATED_ACCOUNT_NAME.to_string())))
+    }
+
+    /// Deposits, withdrawals and transfers move a positive amount; anything else is a typo or an attempt
+    /// to run one in reverse
+    fn positive(amount: &Amount) -> Result<(), BankError> {
+        if *amount <= Amount::ZERO {
+            return Err(BankError::InvalidAmount(amount.to_string()));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn account_not_found(closed_accounts: &HashSet<AccountId>, account_id: &str) -> BankError {
+        if closed_accounts.contains(account_id) {
+            BankError::AccountClosed(account_id.to_string())
+        } else {
+            BankError::AccountNotFound(account_id.to_string())
         }
     }
 }
@@ -24,103 +564,874 @@ impl Default for Bank {
     }
 }
 
-#[derive(Debug, Clone)]
+impl StateDiff for Bank {
+    fn entity_count(&self) -> usize {
+        self.accounts.len()
+    }
+
+    fn diff_keys(&self, other: &Self) -> Vec<String> {
+        let ids: HashSet<&AccountId> = self.accounts.keys()
+            .chain(other.accounts.keys())
+            .chain(self.closed_accounts.symmetric_difference(&other.closed_accounts))
+            .collect();
+        let mut keys: Vec<String> = ids
+            .into_iter()
+            .filter(|id| {
+                self.accounts.get(*id) != other.accounts.get(*id)
+                    || self.closed_accounts.contains(*id) != other.closed_accounts.contains(*id)
+            })
+            .map(|id| id.to_string())
+            .collect();
+        keys.sort();
+        keys
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "http", derive(utoipa::ToSchema))]
 pub struct Account {
-    pub id: String,
+    pub id: AccountId,
     pub name: String,
-    pub balance: Amount,
+    #[cfg_attr(feature = "http", schema(value_type = String))]
+    pub total_debits: Amount,
+    #[cfg_attr(feature = "http", schema(value_type = String))]
+    pub total_credits: Amount,
+    /// Parties owning the account, never empty; the holder named at creation is the first
+    #[serde(default)]
+    pub owners: Vec<String>,
+    /// `Bank::modification_seq` of the last command that changed this account; omitted from JSON while zero
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub last_modified_seq: u64,
+    /// Balance after each change, oldest first, while the bank keeps history
+    #[serde(skip)]
+    pub balance_history: VecDeque<BalancePoint>,
 }
 
 impl Account {
-    pub fn new(id: String, name: String) -> Self {
+    pub fn new(id: impl Into<AccountId>, name: String) -> Self {
         Self {
-            id,
+            id: id.into(),
+            owners: vec![name.clone()],
             name,
-            balance: Amount::ZERO,
+            total_debits: Amount::ZERO,
+            total_credits: Amount::ZERO,
+            last_modified_seq: 0,
+            balance_history: VecDeque::new(),
         }
     }
+
+    /// Net balance, always derived from the ledger totals so it cannot drift
+    pub fn balance(&self) -> Amount {
+        self.total_credits - self.total_debits
+    }
+
+    /// Add a point at `ts` if the balance moved since the last one, evicting the oldest past `max_points`
+    fn record_balance(&mut self, ts: DateTime<Utc>, max_points: usize) {
+        let balance = self.balance();
+        if self.balance_history.back().map_or(Amount::ZERO, |point| point.balance) == balance {
+            return;
+        }
+        self.balance_history.push_back(BalancePoint { ts, balance });
+        while self.balance_history.len() > max_points {
+            self.balance_history.pop_front();
+        }
+    }
+}
+
+/// Record of a closed account, kept by a bank built `with_tombstones`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Tombstone {
+    /// Name the account had when closed
+    pub name: String,
+    /// `Bank::modification_seq` of the `CloseAccount` that closed it
+    pub closed_at_seq: u64,
+}
+
+/// An account's balance as of `ts`, one point of its `GetBalanceHistory`
+#[derive(Debug, Clone, PartialEq)]
+pub struct BalancePoint {
+    pub ts: DateTime<Utc>,
+    pub balance: Amount,
 }
 
 // Commands
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Command)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "http", derive(utoipa::ToSchema))]
+#[command(system = "Bank", explain = "describe", resolve = "resolve", check = "check")]
 pub enum BankCommand {
-    CreateAccount { id: String, name: String },
-    Deposit { account_id: String, amount: Amount },
-    Withdrawal { account_id: String, amount: Amount },
-    Transfer { from_account_id: String, to_account_id: String, amount: Amount },
+    /// Open an account, optionally funded in the same event so it never exists empty
+    #[command(handler = "apply_create_account")]
+    CreateAccount {
+        id: AccountId,
+        name: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        #[cfg_attr(feature = "http", schema(value_type = Option<String>))]
+        opening_balance: Option<Amount>,
+    },
+    #[command(handler = "apply_deposit")]
+    Deposit {
+        account_id: AccountId,
+        #[cfg_attr(feature = "http", schema(value_type = String))]
+        amount: Amount,
+    },
+    #[command(handler = "apply_withdrawal")]
+    Withdrawal {
+        account_id: AccountId,
+        #[cfg_attr(feature = "http", schema(value_type = String))]
+        amount: Amount,
+    },
+    #[command(handler = "apply_transfer")]
+    Transfer {
+        from_account_id: AccountId,
+        to_account_id: AccountId,
+        #[cfg_attr(feature = "http", schema(value_type = String))]
+        amount: Amount,
+    },
+    #[command(handler = "apply_bulk_create_accounts")]
+    BulkCreateAccounts { accounts: Vec<(AccountId, String)> },
+    #[command(handler = "apply_close_account")]
+    CloseAccount { id: AccountId },
+    #[command(handler = "apply_add_owner")]
+    AddOwner { account_id: AccountId, owner: String },
+    #[command(handler = "apply_remove_owner")]
+    RemoveOwner { account_id: AccountId, owner: String },
+    /// Move the whole balance of `from_account_id` into `to_account_id`
+    ///
+    /// Submit with `amount: None`; the processor logs the sweep with the balance it read, and
+    /// replay transfers that recorded amount. A zero balance is still logged, as `amount: 0`.
+    #[command(handler = "apply_sweep")]
+    Sweep {
+        from_account_id: AccountId,
+        to_account_id: AccountId,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        #[cfg_attr(feature = "http", schema(value_type = Option<String>))]
+        amount: Option<Amount>,
+    },
+    /// Open accounts with balances carried over from another system; see `LedgerEntry`
+    #[command(handler = "apply_import_ledger")]
+    ImportLedger { entries: Vec<LedgerEntry> },
+    /// Credit a payment received through a third-party gateway; each gateway transaction is credited once
+    #[command(handler = "apply_record_external_payment")]
+    RecordExternalPayment {
+        account_id: AccountId,
+        #[cfg_attr(feature = "http", schema(value_type = String))]
+        amount: Amount,
+        gateway: String,
+        gateway_transaction_id: String,
+        payment_method: PaymentMethod,
+    },
+    /// Set `Bank::modification_seq` and the listed accounts' `last_modified_seq`, ending the
+    /// genesis events of a dumped bank so its stamps survive bootstrapping; counts as no change
+    #[command(handler = "apply_restore_modification_seqs")]
+    RestoreModificationSeqs { modification_seq: u64, stamps: Vec<(AccountId, u64)> },
+    /// Compound interest on `account_id` every `period` from `starts_at`, replacing any schedule it had
+    #[command(handler = "apply_schedule_interest")]
+    ScheduleInterest {
+        account_id: AccountId,
+        #[cfg_attr(feature = "http", schema(value_type = String))]
+        annual_rate: Decimal,
+        period: InterestPeriod,
+        #[serde(default)]
+        negative_balance: NegativeBalancePolicy,
+        starts_at: DateTime<Utc>,
+    },
+    /// Accrue the interest of `account_id`'s period ending at `period_end`, the next one due
+    ///
+    /// Submit with `amount: None`; the processor logs the accrual with the interest it computed
+    /// from the balance it read, and replay credits that recorded amount (or debits it, when
+    /// negative). `MemImgProcessor::tick` submits every accrual due.
+    #[command(handler = "apply_accrue_interest")]
+    AccrueInterest {
+        account_id: AccountId,
+        period_end: DateTime<Utc>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        #[cfg_attr(feature = "http", schema(value_type = Option<String>))]
+        amount: Option<Amount>,
+    },
+    /// Charge `account_id` an overdraft `fee` if it is overdrawn, never taking its balance below
+    /// `floor`
+    ///
+    /// A fee that would cross the floor is cut to what the floor leaves, so an account at the
+    /// floor or in credit is charged nothing. The charge depends only on the balance, so replay
+    /// charges the same.
+    #[command(handler = "apply_overdraft_fee")]
+    ApplyOverdraftFee {
+        account_id: AccountId,
+        #[cfg_attr(feature = "http", schema(value_type = String))]
+        fee: Amount,
+        #[cfg_attr(feature = "http", schema(value_type = String))]
+        floor: Amount,
+    },
 }
 
-impl Command for BankCommand {
-    type System = Bank;
+/// How an external payment reached its gateway
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "http", derive(utoipa::ToSchema))]
+#[allow(clippy::upper_case_acronyms)]
+pub enum PaymentMethod {
+    CreditCard,
+    WireTransfer,
+    ACH,
+    Crypto,
+}
+
+/// How often scheduled interest compounds
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "http", derive(utoipa::ToSchema))]
+pub enum InterestPeriod {
+    Daily,
+    Monthly,
+    Yearly,
+}
+
+impl InterestPeriod {
+    /// Periods in a year, dividing the annual rate; a year is 365 days, leap years included
+    pub fn per_year(self) -> Decimal {
+        match self {
+            InterestPeriod::Daily => Decimal::from(365),
+            InterestPeriod::Monthly => Decimal::from(12),
+            InterestPeriod::Yearly => Decimal::ONE,
+        }
+    }
+
+    /// End of the `n`th period from `start`, counted from `start` so month ends do not drift:
+    /// monthly periods from January 31st end on the last day of February, then on March 31st
+    pub fn end_of(self, start: DateTime<Utc>, n: u32) -> DateTime<Utc> {
+        let end = match self {
+            InterestPeriod::Daily => start.checked_add_days(Days::new(n.into())),
+            InterestPeriod::Monthly => start.checked_add_months(Months::new(n)),
+            InterestPeriod::Yearly => start.checked_add_months(Months::new(n.saturating_mul(12))),
+        };
+        end.unwrap_or(DateTime::<Utc>::MAX_UTC)
+    }
+}
+
+/// What accruing interest does to an overdrawn account
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "http", derive(utoipa::ToSchema))]
+pub enum NegativeBalancePolicy {
+    /// Accrue nothing for the period
+    #[default]
+    Skip,
+    /// Charge interest on the overdrawn balance at the same rate
+    Charge,
+}
+
+/// An account's compound interest, set by `ScheduleInterest` and advanced by `AccrueInterest`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InterestSchedule {
+    pub annual_rate: Decimal,
+    pub period: InterestPeriod,
+    pub negative_balance: NegativeBalancePolicy,
+    pub starts_at: DateTime<Utc>,
+    pub periods_accrued: u32,
+}
+
+impl InterestSchedule {
+    /// End of the next period to accrue
+    pub fn next_accrual_at(&self) -> DateTime<Utc> {
+        self.period.end_of(self.starts_at, self.periods_accrued + 1)
+    }
+}
+
+/// Opening balance of an imported account
+///
+/// Imports initialize state rather than record history: the balance becomes the account's
+/// credits with no individual transactions behind it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "http", derive(utoipa::ToSchema))]
+pub struct LedgerEntry {
+    pub account_id: AccountId,
+    pub name: String,
+    #[cfg_attr(feature = "http", schema(value_type = String))]
+    pub balance: Amount,
+}
+
+impl JsonEvent for BankCommand {}
+
+impl BankCommand {
+    /// Accounts the command reads or writes, in the order it names them
+    pub fn account_ids(&self) -> Vec<&AccountId> {
+        match self {
+            BankCommand::CreateAccount { id, .. } | BankCommand::CloseAccount { id } => vec![id],
+            BankCommand::Deposit { account_id, .. }
+            | BankCommand::Withdrawal { account_id, .. }
+            | BankCommand::AddOwner { account_id, .. }
+            | BankCommand::RemoveOwner { account_id, .. }
+            | BankCommand::RecordExternalPayment { account_id, .. }
+            | BankCommand::ScheduleInterest { account_id, .. }
+            | BankCommand::AccrueInterest { account_id, .. }
+            | BankCommand::ApplyOverdraftFee { account_id, .. } => vec![account_id],
+            BankCommand::Transfer { from_account_id, to_account_id, .. } | BankCommand::Sweep { from_account_id, to_account_id, .. } => {
+                vec![from_account_id, to_account_id]
+            }
+            BankCommand::BulkCreateAccounts { accounts } => accounts.iter().map(|(id, _)| id).collect(),
+            BankCommand::ImportLedger { entries } => entries.iter().map(|entry| &entry.account_id).collect(),
+            BankCommand::RestoreModificationSeqs { stamps, .. } => stamps.iter().map(|(id, _)| id).collect(),
+        }
+    }
+
+    /// Name of the variant, as serde tags it
+    pub fn kind(&self) -> &'static str {
+        match self {
+            BankCommand::CreateAccount { .. } => "CreateAccount",
+            BankCommand::Deposit { .. } => "Deposit",
+            BankCommand::Withdrawal { .. } => "Withdrawal",
+            BankCommand::Transfer { .. } => "Transfer",
+            BankCommand::BulkCreateAccounts { .. } => "BulkCreateAccounts",
+            BankCommand::CloseAccount { .. } => "CloseAccount",
+            BankCommand::AddOwner { .. } => "AddOwner",
+            BankCommand::RemoveOwner { .. } => "RemoveOwner",
+            BankCommand::Sweep { .. } => "Sweep",
+            BankCommand::ImportLedger { .. } => "ImportLedger",
+            BankCommand::RecordExternalPayment { .. } => "RecordExternalPayment",
+            BankCommand::RestoreModificationSeqs { .. } => "RestoreModificationSeqs",
+            BankCommand::ScheduleInterest { .. } => "ScheduleInterest",
+            BankCommand::AccrueInterest { .. } => "AccrueInterest",
+            BankCommand::ApplyOverdraftFee { .. } => "ApplyOverdraftFee",
+        }
+    }
 
-    fn apply_to(&self, bank: &mut Self::System) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    fn resolve(&self, bank: &Bank) -> Option<BankCommand> {
         match self {
-            BankCommand::CreateAccount { id, name } => {
-                bank.accounts.insert(id.clone(), Account::new(id.clone(), name.clone()));
-                Ok(())
+            BankCommand::Sweep { from_account_id, to_account_id, amount: None } => Some(BankCommand::Sweep {
+                from_account_id: from_account_id.clone(),
+                to_account_id: to_account_id.clone(),
+                amount: Some(bank.accounts.get(from_account_id)?.balance()),
+            }),
+            BankCommand::AccrueInterest { account_id, period_end, amount: None } => Some(BankCommand::AccrueInterest {
+                account_id: account_id.clone(),
+                period_end: *period_end,
+                amount: Some(bank.interest_due(account_id)?),
+            }),
+            _ => None,
+        }
+    }
+
+    /// The checks the handlers of deposits, withdrawals and transfers make before changing
+    /// anything, run against `bank` as it is
+    fn check(&self, bank: &Bank) -> Option<Result<(), BankError>> {
+        let funded = |account_id: &AccountId, amount: &Amount| {
+            let account = bank.accounts.get(account_id).ok_or_else(|| Bank::account_not_found(&bank.closed_accounts, account_id.as_str()))?;
+            if account.balance() < *amount {
+                return Err(BankError::InsufficientFunds { available: account.balance(), requested: *amount });
             }
+            Ok(())
+        };
+        match self {
             BankCommand::Deposit { account_id, amount } => {
-                let account = bank.accounts.get_mut(account_id)
-                    .ok_or_else(|| -> Box<dyn std::error::Error + Send + Sync> {
-                        Box::new(std::io::Error::new(std::io::ErrorKind::NotFound, format!("Account not found: {}", account_id)))
-                    })?;
-                account.balance += *amount;
-                Ok(())
-            }
-            BankCommand::Withdrawal { account_id, amount } => {
-                let account = bank.accounts.get_mut(account_id)
-                    .ok_or_else(|| -> Box<dyn std::error::Error + Send + Sync> {
-                        Box::new(std::io::Error::new(std::io::ErrorKind::NotFound, format!("Account not found: {}", account_id)))
-                    })?;
-
-                if account.balance < *amount {
-                    return Err(Box::new(std::io::Error::new(
-                        std::io::ErrorKind::InvalidInput,
-                        format!("Insufficient funds: {} < {}", account.balance, amount)
-                    )));
-                }
+                Some(Bank::positive(amount).and_then(|_| bank.new_destination(account_id)).map(drop))
+            }
+            BankCommand::Withdrawal { account_id, amount } => Some(Bank::positive(amount).and_then(|_| funded(account_id, amount))),
+            BankCommand::Transfer { from_account_id, to_account_id, amount } => Some(
+                Bank::positive(amount)
+                    .and_then(|_| bank.new_destination(to_account_id))
+                    .and_then(|_| funded(from_account_id, amount)),
+            ),
+            _ => None,
+        }
+    }
+
+    fn describe(&self, bank: &Bank) -> String {
+        let describe = |account_id: &AccountId| match bank.accounts.get(account_id) {
+            Some(account) => format!("{} [{}] (balance ${})", account.name, account_id, account.balance()),
+            None => format!("unknown account {}", account_id),
+        };
+
+        match self {
+            BankCommand::CreateAccount { id, name, opening_balance: None } => format!("Create account {} for {}", id, name),
+            BankCommand::CreateAccount { id, name, opening_balance: Some(balance) } => {
+                format!("Create account {} for {} with ${}", id, name, balance)
+            }
+            BankCommand::Deposit { account_id, amount } => format!("Deposit ${} into {}", amount, describe(account_id)),
+            BankCommand::Withdrawal { account_id, amount } => format!("Withdraw ${} from {}", amount, describe(account_id)),
+            BankCommand::Transfer { from_account_id, to_account_id, amount } => format!(
+                "Transfer ${} from {} to {}",
+                amount,
+                describe(from_account_id),
+                describe(to_account_id)
+            ),
+            BankCommand::BulkCreateAccounts { accounts } => format!("Create {} accounts", accounts.len()),
+            BankCommand::CloseAccount { id } => format!("Close {}", describe(id)),
+            BankCommand::AddOwner { account_id, owner } => format!("Add {} as owner of {}", owner, describe(account_id)),
+            BankCommand::RemoveOwner { account_id, owner } => format!("Remove {} as owner of {}", owner, describe(account_id)),
+            BankCommand::Sweep { from_account_id, to_account_id, .. } => {
+                format!("Sweep all funds from {} to {}", describe(from_account_id), describe(to_account_id))
+            }
+            BankCommand::ImportLedger { entries } => format!("Import {} accounts from a ledger", entries.len()),
+            BankCommand::RecordExternalPayment { account_id, amount, gateway, gateway_transaction_id, payment_method } => format!(
+                "Credit ${} paid by {:?} through {} ({}) to {}",
+                amount,
+                payment_method,
+                gateway,
+                gateway_transaction_id,
+                describe(account_id)
+            ),
+            BankCommand::RestoreModificationSeqs { modification_seq, stamps } => {
+                format!("Restore modification sequence {} and {} account stamps", modification_seq, stamps.len())
+            }
+            BankCommand::ScheduleInterest { account_id, annual_rate, period, starts_at, .. } => format!(
+                "Compound {}% a year {:?} on {} from {}",
+                annual_rate * Decimal::ONE_HUNDRED,
+                period,
+                describe(account_id),
+                starts_at
+            ),
+            BankCommand::AccrueInterest { account_id, period_end, .. } => {
+                format!("Accrue interest for the period ending {} on {}", period_end, describe(account_id))
+            }
+            BankCommand::ApplyOverdraftFee { account_id, fee, floor } => {
+                format!("Charge an overdraft fee of ${} down to ${} on {}", fee, floor, describe(account_id))
+            }
+        }
+    }
+}
+
+/// Dollar amount rounded to cents, e.g. `$100.00`
+fn dollars(amount: &Amount) -> String {
+    format!("${:.2}", amount.round_dp(2))
+}
 
-                account.balance -= *amount;
-                Ok(())
+/// One-line form for user-facing logs, e.g. `Transfer(from=alice, to=bob, amount=$30.00)`
+impl fmt::Display for BankCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BankCommand::CreateAccount { id, name, opening_balance: None } => write!(f, "CreateAccount(id={}, name={})", id, name),
+            BankCommand::CreateAccount { id, name, opening_balance: Some(balance) } => {
+                write!(f, "CreateAccount(id={}, name={}, opening_balance={})", id, name, dollars(balance))
             }
+            BankCommand::Deposit { account_id, amount } => write!(f, "Deposit(account={}, amount={})", account_id, dollars(amount)),
+            BankCommand::Withdrawal { account_id, amount } => write!(f, "Withdrawal(account={}, amount={})", account_id, dollars(amount)),
             BankCommand::Transfer { from_account_id, to_account_id, amount } => {
-                // Operation order deliberately set to exercise rollback (deposit first)
-                {
-                    let to_account = bank.accounts.get_mut(to_account_id)
-                        .ok_or_else(|| -> Box<dyn std::error::Error + Send + Sync> {
-                            Box::new(std::io::Error::new(std::io::ErrorKind::NotFound, format!("Account not found: {}", to_account_id)))
-                        })?;
-                    to_account.balance += *amount;
+                write!(f, "Transfer(from={}, to={}, amount={})", from_account_id, to_account_id, dollars(amount))
+            }
+            BankCommand::BulkCreateAccounts { accounts } => write!(f, "BulkCreateAccounts(count={})", accounts.len()),
+            BankCommand::CloseAccount { id } => write!(f, "CloseAccount(id={})", id),
+            BankCommand::AddOwner { account_id, owner } => write!(f, "AddOwner(account={}, owner={})", account_id, owner),
+            BankCommand::RemoveOwner { account_id, owner } => write!(f, "RemoveOwner(account={}, owner={})", account_id, owner),
+            BankCommand::Sweep { from_account_id, to_account_id, amount: None } => {
+                write!(f, "Sweep(from={}, to={})", from_account_id, to_account_id)
+            }
+            BankCommand::Sweep { from_account_id, to_account_id, amount: Some(amount) } => {
+                write!(f, "Sweep(from={}, to={}, amount={})", from_account_id, to_account_id, dollars(amount))
+            }
+            BankCommand::ImportLedger { entries } => write!(f, "ImportLedger(count={})", entries.len()),
+            BankCommand::RecordExternalPayment { account_id, amount, gateway, gateway_transaction_id, .. } => write!(
+                f,
+                "RecordExternalPayment(account={}, amount={}, gateway={}, transaction={})",
+                account_id,
+                dollars(amount),
+                gateway,
+                gateway_transaction_id
+            ),
+            BankCommand::RestoreModificationSeqs { modification_seq, stamps } => {
+                write!(f, "RestoreModificationSeqs(seq={}, count={})", modification_seq, stamps.len())
+            }
+            BankCommand::ScheduleInterest { account_id, annual_rate, period, starts_at, .. } => {
+                write!(f, "ScheduleInterest(account={}, annual_rate={}, period={:?}, starts_at={})", account_id, annual_rate, period, starts_at)
+            }
+            BankCommand::AccrueInterest { account_id, period_end, amount: None } => {
+                write!(f, "AccrueInterest(account={}, period_end={})", account_id, period_end)
+            }
+            BankCommand::AccrueInterest { account_id, period_end, amount: Some(amount) } => {
+                write!(f, "AccrueInterest(account={}, period_end={}, amount={})", account_id, period_end, dollars(amount))
+            }
+            BankCommand::ApplyOverdraftFee { account_id, fee, floor } => {
+                write!(f, "ApplyOverdraftFee(account={}, fee={}, floor={})", account_id, dollars(fee), dollars(floor))
+            }
+        }
+    }
+}
+
+fn is_zero(n: &u64) -> bool {
+    *n == 0
+}
+
+// Command handlers
+
+impl Bank {
+    /// Count one more change to the bank and stamp it on the open accounts among `ids`, recording
+    /// their balances if the bank keeps history
+    pub(crate) fn touch<'a>(&mut self, ids: impl IntoIterator<Item = &'a AccountId>) {
+        self.modification_seq += 1;
+        let now = self.balance_history_limit.map(|max_points| (Utc::now(), max_points));
+        for id in ids {
+            if let Some(account) = self.accounts.get_mut(id) {
+                account.last_modified_seq = self.modification_seq;
+                if let Some((ts, max_points)) = now {
+                    account.record_balance(ts, max_points);
                 }
+            }
+        }
+    }
+
+    /// Under `require_unique_names`, reject names used by an open account or twice among `names`
+    fn check_names<'a>(&self, names: impl IntoIterator<Item = &'a str>) -> Result<(), BankError> {
+        if !self.require_unique_names {
+            return Ok(());
+        }
+        let mut in_use: HashSet<&str> = self.accounts.values().map(|account| account.name.as_str()).collect();
+        for name in names {
+            if !in_use.insert(name) {
+                return Err(BankError::DuplicateAccountName(name.to_string()));
+            }
+        }
+        Ok(())
+    }
+
+    fn apply_create_account(&mut self, id: &AccountId, name: &str, opening_balance: &Option<Amount>) -> Result<(), BankError> {
+        id.validate()?;
+        let opening_balance = opening_balance.unwrap_or(Amount::ZERO);
+        if opening_balance < Amount::ZERO {
+            return Err(BankError::InvalidAmount(opening_balance.to_string()));
+        }
+        // Closed ids are never reused, so history for an id always refers to one account
+        if self.closed_accounts.contains(id) {
+            return Err(BankError::AccountClosed(id.to_string()));
+        }
+        // Replacing an existing account would silently destroy its balance
+        if self.accounts.contains_key(id) {
+            return Err(BankError::DuplicateAccount(id.to_string()));
+        }
+        self.check_names([name])?;
+        let mut account = Account::new(id.clone(), name.to_string());
+        // Opening funds enter from outside the bank, like deposits
+        account.total_credits = opening_balance;
+        self.equity_capital += opening_balance;
+        self.accounts.insert(id.clone(), account);
+        self.touch([id]);
+        Ok(())
+    }
+
+    fn apply_deposit(&mut self, account_id: &AccountId, amount: &Amount) -> Result<(), BankError> {
+        Bank::positive(amount)?;
+        if let Some(account) = self.new_destination(account_id)? {
+            self.accounts.insert(account_id.clone(), account);
+        }
+        let account = self.accounts.get_mut(account_id)
+            .ok_or_else(|| Bank::account_not_found(&self.closed_accounts, account_id.as_str()))?;
+        account.total_credits += *amount;
+        self.equity_capital += *amount;
+        self.touch([account_id]);
+        Ok(())
+    }
+
+    fn apply_withdrawal(&mut self, account_id: &AccountId, amount: &Amount) -> Result<(), BankError> {
+        Bank::positive(amount)?;
+        let account = self.accounts.get_mut(account_id)
+            .ok_or_else(|| Bank::account_not_found(&self.closed_accounts, account_id.as_str()))?;
 
-                {
-                    let from_account = bank.accounts.get_mut(from_account_id)
-                        .ok_or_else(|| -> Box<dyn std::error::Error + Send + Sync> {
-                            Box::new(std::io::Error::new(std::io::ErrorKind::NotFound, format!("Account not found: {}", from_account_id)))
-                        })?;
+        if account.balance() < *amount {
+            return Err(BankError::InsufficientFunds {
+                available: account.balance(),
+                requested: *amount,
+            });
+        }
 
-                    if from_account.balance < *amount {
-                        return Err(Box::new(std::io::Error::new(
-                            std::io::ErrorKind::InvalidInput,
-                            format!("Insufficient funds: {} < {}", from_account.balance, amount)
-                        )));
-                    }
+        account.total_debits += *amount;
+        self.equity_capital -= *amount;
+        self.touch([account_id]);
+        Ok(())
+    }
 
-                    from_account.balance -= *amount;
+    fn apply_close_account(&mut self, id: &AccountId) -> Result<(), BankError> {
+        let account = self.accounts.get(id)
+            .ok_or_else(|| Bank::account_not_found(&self.closed_accounts, id.as_str()))?;
+        if !account.balance().is_zero() {
+            return Err(BankError::NonZeroBalance {
+                account_id: id.to_string(),
+                balance: account.balance(),
+            });
+        }
+
+        let account = self.accounts.remove(id);
+        self.interest_schedules.remove(id);
+        self.closed_accounts.insert(id.clone());
+        self.touch([]);
+        if let (Some(max_tombstones), Some(account)) = (self.tombstone_limit, account) {
+            let tombstone = Tombstone { name: account.name, closed_at_seq: self.modification_seq };
+            self.tombstones.insert(id.clone(), tombstone);
+            while self.tombstones.len() > max_tombstones {
+                let oldest = self.tombstones.iter().min_by_key(|(_, tombstone)| tombstone.closed_at_seq).map(|(id, _)| id.clone());
+                if let Some(oldest) = oldest {
+                    self.tombstones.remove(&oldest);
                 }
+            }
+        }
+        Ok(())
+    }
+
+    fn apply_add_owner(&mut self, account_id: &AccountId, owner: &str) -> Result<(), BankError> {
+        let account = self.accounts.get_mut(account_id)
+            .ok_or_else(|| Bank::account_not_found(&self.closed_accounts, account_id.as_str()))?;
+        if account.owners.iter().any(|existing| existing == owner) {
+            return Err(BankError::DuplicateOwner {
+                account_id: account_id.to_string(),
+                owner: owner.to_string(),
+            });
+        }
+
+        account.owners.push(owner.to_string());
+        self.touch([account_id]);
+        Ok(())
+    }
 
-                Ok(())
+    fn apply_remove_owner(&mut self, account_id: &AccountId, owner: &str) -> Result<(), BankError> {
+        let account = self.accounts.get_mut(account_id)
+            .ok_or_else(|| Bank::account_not_found(&self.closed_accounts, account_id.as_str()))?;
+        let position = account.owners.iter().position(|existing| existing == owner).ok_or_else(|| BankError::OwnerNotFound {
+            account_id: account_id.to_string(),
+            owner: owner.to_string(),
+        })?;
+        if account.owners.len() == 1 {
+            return Err(BankError::LastOwner {
+                account_id: account_id.to_string(),
+                owner: owner.to_string(),
+            });
+        }
+
+        account.owners.remove(position);
+        self.touch([account_id]);
+        Ok(())
+    }
+
+    fn apply_bulk_create_accounts(&mut self, accounts: &[(AccountId, String)]) -> Result<(), BankError> {
+        let mut batch_ids = HashSet::with_capacity(accounts.len());
+        for (id, _) in accounts {
+            id.validate()?;
+            if !batch_ids.insert(id) || self.accounts.contains_key(id) {
+                return Err(BankError::DuplicateAccount(id.to_string()));
+            }
+            if self.closed_accounts.contains(id) {
+                return Err(BankError::AccountClosed(id.to_string()));
+            }
+        }
+        self.check_names(accounts.iter().map(|(_, name)| name.as_str()))?;
+
+        self.accounts.reserve(accounts.len());
+        for (id, name) in accounts {
+            self.accounts.insert(id.clone(), Account::new(id.clone(), name.clone()));
+        }
+        self.touch(accounts.iter().map(|(id, _)| id));
+        Ok(())
+    }
+
+    fn apply_import_ledger(&mut self, entries: &[LedgerEntry]) -> Result<(), BankError> {
+        let mut batch_ids = HashSet::with_capacity(entries.len());
+        for entry in entries {
+            let id = &entry.account_id;
+            id.validate()?;
+            if !batch_ids.insert(id) || self.accounts.contains_key(id) {
+                return Err(BankError::DuplicateAccount(id.to_string()));
+            }
+            if self.closed_accounts.contains(id) {
+                return Err(BankError::AccountClosed(id.to_string()));
+            }
+            if entry.balance < Amount::ZERO {
+                return Err(BankError::InvalidAmount(entry.balance.to_string()));
             }
         }
+        self.check_names(entries.iter().map(|entry| entry.name.as_str()))?;
+
+        self.accounts.reserve(entries.len());
+        for entry in entries {
+            let mut account = Account::new(entry.account_id.clone(), entry.name.clone());
+            // Imported funds enter from outside the bank, like deposits
+            account.total_credits = entry.balance;
+            self.equity_capital += entry.balance;
+            self.accounts.insert(entry.account_id.clone(), account);
+        }
+        self.touch(entries.iter().map(|entry| &entry.account_id));
+        Ok(())
+    }
+
+    fn apply_record_external_payment(
+        &mut self,
+        account_id: &AccountId,
+        amount: &Amount,
+        _gateway: &str,
+        gateway_transaction_id: &str,
+        _payment_method: &PaymentMethod,
+    ) -> Result<(), BankError> {
+        Bank::positive(amount)?;
+        if self.external_payment_ids.contains(gateway_transaction_id) {
+            return Err(BankError::DuplicateExternalPayment(gateway_transaction_id.to_string()));
+        }
+        self.apply_deposit(account_id, amount)?;
+        self.external_payment_ids.insert(gateway_transaction_id.to_string());
+        Ok(())
+    }
+
+    fn apply_restore_modification_seqs(&mut self, modification_seq: &u64, stamps: &[(AccountId, u64)]) -> Result<(), BankError> {
+        for (id, _) in stamps {
+            if !self.accounts.contains_key(id) {
+                return Err(Bank::account_not_found(&self.closed_accounts, id.as_str()));
+            }
+        }
+
+        for (id, stamp) in stamps {
+            if let Some(account) = self.accounts.get_mut(id) {
+                account.last_modified_seq = *stamp;
+            }
+        }
+        self.modification_seq = *modification_seq;
+        Ok(())
+    }
+
+    fn apply_transfer(
+        &mut self,
+        from_account_id: &AccountId,
+        to_account_id: &AccountId,
+        amount: &Amount,
+    ) -> Result<(), BankError> {
+        Bank::positive(amount)?;
+        self.move_funds(from_account_id, to_account_id, amount)
+    }
+
+    /// Move `amount` between accounts; sweeps come here directly, since they log an empty balance as zero
+    fn move_funds(&mut self, from_account_id: &AccountId, to_account_id: &AccountId, amount: &Amount) -> Result<(), BankError> {
+        // Validate both accounts and funds before any mutation so direct callers get atomic semantics
+        let new_destination = self.new_destination(to_account_id)?;
+        let from_account = self.accounts.get_mut(from_account_id)
+            .ok_or_else(|| Bank::account_not_found(&self.closed_accounts, from_account_id.as_str()))?;
+
+        if from_account.balance() < *amount {
+            return Err(BankError::InsufficientFunds {
+                available: from_account.balance(),
+                requested: *amount,
+            });
+        }
+
+        from_account.total_debits += *amount;
+        if let Some(account) = new_destination {
+            self.accounts.insert(to_account_id.clone(), account);
+        }
+        if let Some(to_account) = self.accounts.get_mut(to_account_id) {
+            to_account.total_credits += *amount;
+        }
+        self.touch([from_account_id, to_account_id]);
+
+        Ok(())
+    }
+
+    fn apply_sweep(&mut self, from_account_id: &AccountId, to_account_id: &AccountId, amount: &Option<Amount>) -> Result<(), BankError> {
+        // Applied directly, an unresolved sweep reads the balance itself
+        let amount = match amount {
+            Some(amount) => *amount,
+            None => self.accounts.get(from_account_id)
+                .ok_or_else(|| Bank::account_not_found(&self.closed_accounts, from_account_id.as_str()))?
+                .balance(),
+        };
+        self.move_funds(from_account_id, to_account_id, &amount)
+    }
+
+    fn apply_schedule_interest(
+        &mut self,
+        account_id: &AccountId,
+        annual_rate: &Decimal,
+        period: &InterestPeriod,
+        negative_balance: &NegativeBalancePolicy,
+        starts_at: &DateTime<Utc>,
+    ) -> Result<(), BankError> {
+        if !self.accounts.contains_key(account_id) {
+            return Err(Bank::account_not_found(&self.closed_accounts, account_id.as_str()));
+        }
+        if *annual_rate < Decimal::ZERO {
+            return Err(BankError::InvalidAmount(annual_rate.to_string()));
+        }
+        let schedule = InterestSchedule {
+            annual_rate: *annual_rate,
+            period: *period,
+            negative_balance: *negative_balance,
+            starts_at: *starts_at,
+            periods_accrued: 0,
+        };
+        self.interest_schedules.insert(account_id.clone(), schedule);
+        self.touch([account_id]);
+        Ok(())
+    }
+
+    fn apply_accrue_interest(&mut self, account_id: &AccountId, period_end: &DateTime<Utc>, amount: &Option<Amount>) -> Result<(), BankError> {
+        // Periods accrue one at a time and in order, so replay compounds on the same balances
+        if self.interest_schedules.get(account_id).map(InterestSchedule::next_accrual_at) != Some(*period_end) {
+            return Err(BankError::InterestNotDue { account_id: account_id.to_string(), period_end: *period_end });
+        }
+        // Applied directly, an unresolved accrual computes the interest itself
+        let amount = match amount {
+            Some(amount) => *amount,
+            None => self.interest_due(account_id)
+                .ok_or_else(|| Bank::account_not_found(&self.closed_accounts, account_id.as_str()))?,
+        };
+        let account = self.accounts.get_mut(account_id)
+            .ok_or_else(|| Bank::account_not_found(&self.closed_accounts, account_id.as_str()))?;
+        if amount < Amount::ZERO {
+            account.total_debits -= amount;
+        } else {
+            account.total_credits += amount;
+        }
+        // Interest is paid from outside the accounts, like deposits
+        self.equity_capital += amount;
+        if let Some(schedule) = self.interest_schedules.get_mut(account_id) {
+            schedule.periods_accrued += 1;
+        }
+        self.touch([account_id]);
+        Ok(())
+    }
+
+    fn apply_overdraft_fee(&mut self, account_id: &AccountId, fee: &Amount, floor: &Amount) -> Result<(), BankError> {
+        if *fee < Amount::ZERO {
+            return Err(BankError::InvalidAmount(fee.to_string()));
+        }
+        if *floor > Amount::ZERO {
+            return Err(BankError::InvalidAmount(floor.to_string()));
+        }
+        let charged = self.overdraft_fee_due(account_id, *fee, *floor)
+            .ok_or_else(|| Bank::account_not_found(&self.closed_accounts, account_id.as_str()))?;
+        if charged.is_zero() {
+            return Ok(());
+        }
+        if let Some(account) = self.accounts.get_mut(account_id) {
+            account.total_debits += charged;
+        }
+        // The fee leaves the accounts for the bank, like a withdrawal
+        self.equity_capital -= charged;
+        self.touch([account_id]);
+        Ok(())
+    }
+}
+
+impl<E> MemImgProcessor<Bank, BankCommand, E>
+where
+    E: EventStorage<Event = BankCommand>,
+{
+    /// Accrue every interest period ended by `now`, each as its own `AccrueInterest` command,
+    /// returning how many were accrued
+    ///
+    /// The periods of an account accrue oldest first, so each compounds on the interest before
+    /// it. Call it on a timer, or with the time a batch job runs for; a failure stops the tick,
+    /// leaving the periods before it accrued.
+    pub fn tick(&mut self, now: DateTime<Utc>) -> Result<u64, MemImgError> {
+        let mut accrued = 0;
+        for command in self.system().due_interest(now) {
+            self.execute_command(command)?;
+            accrued += 1;
+        }
+        Ok(accrued)
     }
 }
 
 // Queries
 
-#[derive(Debug)]
+#[derive(Debug, Deserialize)]
 pub struct GetAccount {
-    pub account_id: String,
+    pub account_id: AccountId,
 }
 
 impl Query for GetAccount {
@@ -132,9 +1443,9 @@ impl Query for GetAccount {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Deserialize)]
 pub struct GetBalance {
-    pub account_id: String,
+    pub account_id: AccountId,
 }
 
 impl Query for GetBalance {
@@ -144,17 +1455,100 @@ impl Query for GetBalance {
     fn extract_from(&self, bank: &Self::System) -> Result<Self::Result, Box<dyn std::error::Error + Send + Sync>> {
         bank.accounts
             .get(&self.account_id)
-            .map(|acc| acc.balance)
-            .ok_or_else(|| -> Box<dyn std::error::Error + Send + Sync> {
-                Box::new(std::io::Error::new(
-                    std::io::ErrorKind::NotFound,
-                    format!("Account not found: {}", self.account_id)
-                ))
-            })
+            .map(|acc| acc.balance())
+            .ok_or_else(|| Bank::account_not_found(&bank.closed_accounts, self.account_id.as_str()).into())
     }
 }
 
+/// Balance points of an account, oldest first; empty unless the bank keeps history
+#[derive(Debug, Deserialize)]
+pub struct GetBalanceHistory {
+    pub account_id: AccountId,
+}
+
+impl Query for GetBalanceHistory {
+    type System = Bank;
+    type Result = Vec<BalancePoint>;
+
+    fn extract_from(&self, bank: &Self::System) -> Result<Self::Result, Box<dyn std::error::Error + Send + Sync>> {
+        bank.accounts
+            .get(&self.account_id)
+            .map(|account| account.balance_history.iter().cloned().collect())
+            .ok_or_else(|| Bank::account_not_found(&bank.closed_accounts, self.account_id.as_str()).into())
+    }
+}
+
+/// Balance rounded half away from zero to `decimal_places` and written with exactly that many,
+/// e.g. `"1.0050"` for 4 places
+#[derive(Debug, Deserialize)]
+pub struct GetBalanceFormatted {
+    pub account_id: AccountId,
+    pub decimal_places: u32,
+}
+
+impl Query for GetBalanceFormatted {
+    type System = Bank;
+    type Result = String;
+
+    fn extract_from(&self, bank: &Self::System) -> Result<Self::Result, Box<dyn std::error::Error + Send + Sync>> {
+        let balance = GetBalance { account_id: self.account_id.clone() }.extract_from(bank)?;
+        let rounded = balance.round_dp_with_strategy(self.decimal_places, RoundingStrategy::MidpointAwayFromZero);
+        Ok(format!("{:.prec$}", rounded, prec = self.decimal_places as usize))
+    }
+}
+
+/// Balance in whole cents, rounded half away from zero
+#[derive(Debug, Deserialize)]
+pub struct GetBalanceCents {
+    pub account_id: AccountId,
+}
+
+impl Query for GetBalanceCents {
+    type System = Bank;
+    type Result = i64;
+
+    fn extract_from(&self, bank: &Self::System) -> Result<Self::Result, Box<dyn std::error::Error + Send + Sync>> {
+        let balance = GetBalance { account_id: self.account_id.clone() }.extract_from(bank)?;
+        let cents = (balance * Decimal::ONE_HUNDRED).round_dp_with_strategy(0, RoundingStrategy::MidpointAwayFromZero);
+        cents.to_i64().ok_or_else(|| format!("balance of {} does not fit in i64 cents", self.account_id).into())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "http", derive(utoipa::ToSchema))]
+pub struct LedgerSummary {
+    #[cfg_attr(feature = "http", schema(value_type = String))]
+    pub total_debits: Amount,
+    #[cfg_attr(feature = "http", schema(value_type = String))]
+    pub total_credits: Amount,
+    #[cfg_attr(feature = "http", schema(value_type = String))]
+    pub net_balance: Amount,
+}
+
 #[derive(Debug)]
+pub struct GetLedgerSummary {
+    pub account_id: AccountId,
+}
+
+impl Query for GetLedgerSummary {
+    type System = Bank;
+    type Result = LedgerSummary;
+
+    fn extract_from(&self, bank: &Self::System) -> Result<Self::Result, Box<dyn std::error::Error + Send + Sync>> {
+        bank.accounts
+            .get(&self.account_id)
+            .map(|acc| LedgerSummary {
+                total_debits: acc.total_debits,
+                total_credits: acc.total_credits,
+                net_balance: acc.balance(),
+            })
+            .ok_or_else(|| Bank::account_not_found(&bank.closed_accounts, self.account_id.as_str()).into())
+    }
+}
+
+/// Every open account, sorted by id
+#[derive(Debug, Deserialize)]
 pub struct ListAccounts;
 
 impl Query for ListAccounts {
@@ -162,6 +1556,144 @@ impl Query for ListAccounts {
     type Result = Vec<Account>;
 
     fn extract_from(&self, bank: &Self::System) -> Result<Self::Result, Box<dyn std::error::Error + Send + Sync>> {
-        Ok(bank.accounts.values().cloned().collect())
+        let mut accounts: Vec<Account> = bank.accounts.values().cloned().collect();
+        accounts.sort_by(|a, b| a.id.cmp(&b.id));
+        Ok(accounts)
+    }
+}
+
+/// The ids of every open account, sorted, without cloning the accounts
+#[derive(Debug, Deserialize)]
+pub struct ListAccountIds;
+
+impl Query for ListAccountIds {
+    type System = Bank;
+    type Result = Vec<AccountId>;
+
+    fn extract_from(&self, bank: &Self::System) -> Result<Self::Result, Box<dyn std::error::Error + Send + Sync>> {
+        let mut ids: Vec<AccountId> = bank.accounts.keys().cloned().collect();
+        ids.sort();
+        Ok(ids)
+    }
+}
+
+/// A slice of a sorted result, with the size of the whole for paging controls
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// Items in the whole result, before paging
+    pub total: usize,
+    pub offset: usize,
+    pub limit: usize,
+}
+
+impl<T> Page<T> {
+    /// Pages of `limit` items it takes to show all `total`; none when `limit` is zero
+    pub fn page_count(&self) -> usize {
+        if self.limit == 0 {
+            return 0;
+        }
+        self.total.div_ceil(self.limit)
+    }
+}
+
+/// Up to `limit` open accounts from position `offset` in id order, with the count of all of them
+#[derive(Debug, Deserialize)]
+pub struct ListAccountsPage {
+    pub offset: usize,
+    pub limit: usize,
+}
+
+impl Query for ListAccountsPage {
+    type System = Bank;
+    type Result = Page<Account>;
+
+    fn extract_from(&self, bank: &Self::System) -> Result<Self::Result, Box<dyn std::error::Error + Send + Sync>> {
+        let mut accounts: Vec<&Account> = bank.accounts.values().collect();
+        accounts.sort_unstable_by(|a, b| a.id.cmp(&b.id));
+        let items = accounts.into_iter().skip(self.offset).take(self.limit).cloned().collect();
+        Ok(Page { items, total: bank.accounts.len(), offset: self.offset, limit: self.limit })
+    }
+}
+
+/// Tombstone of a closed account, or `None` if it is open, never existed, or has none kept
+#[derive(Debug, Deserialize)]
+pub struct GetTombstone {
+    pub account_id: AccountId,
+}
+
+impl Query for GetTombstone {
+    type System = Bank;
+    type Result = Option<Tombstone>;
+
+    fn extract_from(&self, bank: &Self::System) -> Result<Self::Result, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(bank.tombstones.get(&self.account_id).cloned())
+    }
+}
+
+/// Open accounts changed by a command after `Bank::modification_seq` was `seq`, sorted by id
+///
+/// For incremental sync: remember the bank's `modification_seq` at each sync and ask for what
+/// changed since. Closed accounts are not listed; compare `closed_accounts` for those.
+#[derive(Debug, Deserialize)]
+pub struct GetAccountsModifiedSince {
+    pub seq: u64,
+}
+
+impl Query for GetAccountsModifiedSince {
+    type System = Bank;
+    type Result = Vec<Account>;
+
+    fn extract_from(&self, bank: &Self::System) -> Result<Self::Result, Box<dyn std::error::Error + Send + Sync>> {
+        let mut accounts: Vec<Account> =
+            bank.accounts.values().filter(|account| account.last_modified_seq > self.seq).cloned().collect();
+        accounts.sort_by(|a, b| a.id.cmp(&b.id));
+        Ok(accounts)
+    }
+}
+
+/// Registry of `GetAccount`, `GetBalance`, `ListAccountIds`, `ListAccounts`, `ListAccountsPage`
+/// and `GetAccountsModifiedSince` under their type names, for JSON callers
+pub fn query_registry() -> QueryRegistry<Bank> {
+    QueryRegistry::new()
+        .register::<GetAccount>("GetAccount")
+        .register::<GetBalance>("GetBalance")
+        .register::<ListAccountIds>("ListAccountIds")
+        .register::<ListAccounts>("ListAccounts")
+        .register::<ListAccountsPage>("ListAccountsPage")
+        .register::<GetAccountsModifiedSince>("GetAccountsModifiedSince")
+}
+
+/// Sum of the balances of all open accounts
+#[derive(Debug)]
+pub struct GetTotalBalance;
+
+impl Query for GetTotalBalance {
+    type System = Bank;
+    type Result = Amount;
+
+    fn extract_from(&self, bank: &Self::System) -> Result<Self::Result, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(bank.accounts.values().map(Account::balance).sum())
+    }
+}
+
+/// Open accounts owned (solely or jointly) by `owner`, sorted by id
+#[derive(Debug)]
+pub struct GetAccountsByOwner {
+    pub owner: String,
+}
+
+impl Query for GetAccountsByOwner {
+    type System = Bank;
+    type Result = Vec<Account>;
+
+    fn extract_from(&self, bank: &Self::System) -> Result<Self::Result, Box<dyn std::error::Error + Send + Sync>> {
+        let mut accounts: Vec<Account> = bank.accounts
+            .values()
+            .filter(|account| account.owners.contains(&self.owner))
+            .cloned()
+            .collect();
+        accounts.sort_by(|a, b| a.id.cmp(&b.id));
+        Ok(accounts)
     }
 }
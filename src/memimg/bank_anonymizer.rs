@@ -0,0 +1,251 @@
+use crate::memimg::bank::{Amount, AccountId, Bank, BankCommand, BankError, LedgerEntry};
+use crate::memimg::processor::Command;
+use crate::memimg::storage::{EventStorage, TextConverter};
+use crate::memimg::text_file_storage::TextFileEventStorage;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// Rewrites one bank event before it leaves the machine, e.g. for a bug report
+pub trait EventScrubber {
+    fn scrub(&mut self, event: BankCommand) -> BankCommand;
+}
+
+impl<F: FnMut(BankCommand) -> BankCommand> EventScrubber for F {
+    fn scrub(&mut self, event: BankCommand) -> BankCommand {
+        self(event)
+    }
+}
+
+/// What `BankScrubber` does to amounts
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum AmountPolicy {
+    #[default]
+    Keep,
+    /// Multiply every amount by a positive factor, which keeps every comparison and sum the
+    /// commands depend on, so the log fails exactly where the original does
+    Scale(Amount),
+    /// Round every amount to the nearest multiple of a positive size, never down to zero
+    ///
+    /// Rounding changes sums, so a withdrawal that fit the balance may no longer; check the
+    /// result with `replay_shape`.
+    Bucket(Amount),
+}
+
+/// Built-in scrubber: account ids, people and gateway transaction ids become pseudonyms derived
+/// from a keyed hash of the original, so one input always maps to one pseudonym under the same seed
+///
+/// Names and owners share one mapping, as a new account's name is also its first owner. Ids the
+/// bank would reject stay invalid, so rejected events are rejected for the same reason.
+#[derive(Debug, Clone)]
+pub struct BankScrubber {
+    seed: String,
+    amounts: AmountPolicy,
+}
+
+impl BankScrubber {
+    pub fn new(seed: impl Into<String>) -> Self {
+        Self { seed: seed.into(), amounts: AmountPolicy::Keep }
+    }
+
+    pub fn with_amounts(mut self, amounts: AmountPolicy) -> Self {
+        self.amounts = amounts;
+        self
+    }
+
+    /// Hex digest of `value` keyed by the seed, separated by `domain` so an account id and a
+    /// name that happen to be equal get unrelated pseudonyms
+    fn digest(&self, domain: &str, value: &str) -> String {
+        let mut hasher = Sha256::new();
+        for part in [self.seed.as_str(), domain, value] {
+            hasher.update(part.as_bytes());
+            hasher.update([0]);
+        }
+        hasher.finalize().iter().take(8).map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    /// Pseudonym of the account id `id`
+    pub fn account_id(&self, id: &AccountId) -> AccountId {
+        match id.validate() {
+            Ok(()) => format!("acct-{}", self.digest("account", id.as_str())).into(),
+            Err(_) if id.as_str().is_empty() => id.clone(),
+            Err(_) => format!("acct {}", self.digest("account", id.as_str())).into(),
+        }
+    }
+
+    /// Generated name standing in for the person `name`
+    pub fn person(&self, name: &str) -> String {
+        format!("Person {}", self.digest("person", name))
+    }
+
+    pub fn amount(&self, amount: Amount) -> Amount {
+        match self.amounts {
+            AmountPolicy::Keep => amount,
+            AmountPolicy::Scale(factor) => amount * factor,
+            AmountPolicy::Bucket(size) => {
+                let rounded = (amount / size).round() * size;
+                if amount > Amount::ZERO && rounded.is_zero() { size } else { rounded }
+            }
+        }
+    }
+}
+
+impl EventScrubber for BankScrubber {
+    fn scrub(&mut self, event: BankCommand) -> BankCommand {
+        match event {
+            BankCommand::CreateAccount { id, name, opening_balance } => BankCommand::CreateAccount {
+                id: self.account_id(&id),
+                name: self.person(&name),
+                opening_balance: opening_balance.map(|amount| self.amount(amount)),
+            },
+            BankCommand::Deposit { account_id, amount } => {
+                BankCommand::Deposit { account_id: self.account_id(&account_id), amount: self.amount(amount) }
+            }
+            BankCommand::Withdrawal { account_id, amount } => {
+                BankCommand::Withdrawal { account_id: self.account_id(&account_id), amount: self.amount(amount) }
+            }
+            BankCommand::Transfer { from_account_id, to_account_id, amount } => BankCommand::Transfer {
+                from_account_id: self.account_id(&from_account_id),
+                to_account_id: self.account_id(&to_account_id),
+                amount: self.amount(amount),
+            },
+            BankCommand::BulkCreateAccounts { accounts } => BankCommand::BulkCreateAccounts {
+                accounts: accounts.iter().map(|(id, name)| (self.account_id(id), self.person(name))).collect(),
+            },
+            BankCommand::CloseAccount { id } => BankCommand::CloseAccount { id: self.account_id(&id) },
+            BankCommand::AddOwner { account_id, owner } => {
+                BankCommand::AddOwner { account_id: self.account_id(&account_id), owner: self.person(&owner) }
+            }
+            BankCommand::RemoveOwner { account_id, owner } => {
+                BankCommand::RemoveOwner { account_id: self.account_id(&account_id), owner: self.person(&owner) }
+            }
+            BankCommand::Sweep { from_account_id, to_account_id, amount } => BankCommand::Sweep {
+                from_account_id: self.account_id(&from_account_id),
+                to_account_id: self.account_id(&to_account_id),
+                amount: amount.map(|amount| self.amount(amount)),
+            },
+            BankCommand::ImportLedger { entries } => BankCommand::ImportLedger {
+                entries: entries
+                    .iter()
+                    .map(|entry| LedgerEntry {
+                        account_id: self.account_id(&entry.account_id),
+                        name: self.person(&entry.name),
+                        balance: self.amount(entry.balance),
+                    })
+                    .collect(),
+            },
+            BankCommand::RecordExternalPayment { account_id, amount, gateway, gateway_transaction_id, payment_method } => {
+                BankCommand::RecordExternalPayment {
+                    account_id: self.account_id(&account_id),
+                    amount: self.amount(amount),
+                    gateway,
+                    gateway_transaction_id: format!("txn-{}", self.digest("transaction", &gateway_transaction_id)),
+                    payment_method,
+                }
+            }
+            BankCommand::RestoreModificationSeqs { modification_seq, stamps } => BankCommand::RestoreModificationSeqs {
+                modification_seq,
+                stamps: stamps.iter().map(|(id, stamp)| (self.account_id(id), *stamp)).collect(),
+            },
+            BankCommand::ScheduleInterest { account_id, annual_rate, period, negative_balance, starts_at } => BankCommand::ScheduleInterest {
+                account_id: self.account_id(&account_id),
+                annual_rate,
+                period,
+                negative_balance,
+                starts_at,
+            },
+            BankCommand::AccrueInterest { account_id, period_end, amount } => BankCommand::AccrueInterest {
+                account_id: self.account_id(&account_id),
+                period_end,
+                amount: amount.map(|amount| self.amount(amount)),
+            },
+            BankCommand::ApplyOverdraftFee { account_id, fee, floor } => BankCommand::ApplyOverdraftFee {
+                account_id: self.account_id(&account_id),
+                fee: self.amount(fee),
+                floor: self.amount(floor),
+            },
+        }
+    }
+}
+
+/// Outcome of `anonymize_log`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AnonymizeReport {
+    pub events: u64,
+}
+
+/// Stream the bank log at `source` through `scrubber` into a new log at `target`
+///
+/// `from` reads the source and `to` writes the target. The source is only read, without taking
+/// its lock, so a copy can be made of a log a running processor owns.
+pub fn anonymize_log<F, T>(
+    source: &Path,
+    target: &Path,
+    from: F,
+    to: T,
+    scrubber: &mut impl EventScrubber,
+) -> Result<AnonymizeReport, Box<dyn std::error::Error + Send + Sync>>
+where
+    F: TextConverter<BankCommand>,
+    T: TextConverter<BankCommand>,
+{
+    if !source.exists() {
+        return Err(format!("{} does not exist", source.display()).into());
+    }
+    let mut events = 0u64;
+    let mut writer = BufWriter::new(File::create(target)?);
+    TextFileEventStorage::new_unlocked(source, from)?.replay(&mut |event: BankCommand| {
+        writeln!(writer, "{}", to.format(&scrubber.scrub(event))?)?;
+        events += 1;
+        Ok(())
+    })?;
+    writer.flush()?;
+    Ok(AnonymizeReport { events })
+}
+
+/// Which events of a bank log fail to apply, and why, with nothing identifying left in it
+///
+/// Two logs with the same shape fail at the same positions with the same `BankError` codes, so
+/// an anonymized log that keeps the shape of its original still reproduces its failures.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ReplayShape {
+    pub events: u64,
+    /// 1-based index and error code of every event that failed to apply
+    pub failures: Vec<(u64, String)>,
+    pub open_accounts: usize,
+}
+
+impl ReplayShape {
+    /// Whether every event applied
+    pub fn replays_cleanly(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+/// Replay the bank log at `log` into an empty bank, skipping and recording events that fail to apply
+pub fn replay_shape<F>(log: &Path, converter: F) -> Result<ReplayShape, Box<dyn std::error::Error + Send + Sync>>
+where
+    F: TextConverter<BankCommand>,
+{
+    if !log.exists() {
+        return Err(format!("{} does not exist", log.display()).into());
+    }
+    let mut bank = Bank::new();
+    let mut shape = ReplayShape { events: 0, failures: Vec::new(), open_accounts: 0 };
+    TextFileEventStorage::new_unlocked(log, converter)?.replay(&mut |event: BankCommand| {
+        shape.events += 1;
+        let mut shadow = bank.clone();
+        match event.apply_to(&mut shadow) {
+            Ok(()) => bank = shadow,
+            Err(e) => {
+                let code = e.downcast_ref::<BankError>().map_or("OTHER", BankError::code);
+                shape.failures.push((shape.events, code.to_string()));
+            }
+        }
+        Ok(())
+    })?;
+    shape.open_accounts = bank.accounts.len();
+    Ok(shape)
+}
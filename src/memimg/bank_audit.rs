@@ -0,0 +1,71 @@
+use crate::memimg::bank::{AccountId, Bank, BankCommand};
+use crate::memimg::storage::TextConverter;
+use crate::memimg::text_file_storage::TextFileEventStorage;
+
+/// Events read per batch when the log has no index and must be scanned
+const SCAN_BATCH: usize = 1024;
+
+/// Index key of the events naming `account_id`
+pub fn account_key(account_id: &AccountId) -> String {
+    format!("account:{}", account_id)
+}
+
+/// Index key of the events of one `BankCommand::kind`, such as `"Deposit"`
+pub fn kind_key(kind: &str) -> String {
+    format!("kind:{}", kind)
+}
+
+/// Keys to pass to `TextFileEventStorage::with_event_index` for a bank log: one per account the
+/// command names, and one for its kind
+pub fn bank_index_keys(command: &BankCommand) -> Vec<String> {
+    let mut keys: Vec<_> = command.account_ids().into_iter().map(account_key).collect();
+    keys.push(kind_key(command.kind()));
+    keys
+}
+
+/// Every logged command naming `account_id`, oldest first, read from a bank's event log rather
+/// than its state
+///
+/// A log opened with `with_event_index(bank_index_keys)` answers by reading only those lines;
+/// any other log is scanned whole. Commands compacted into a snapshot are no longer in the log.
+#[derive(Debug, Clone)]
+pub struct GetAuditTrail {
+    pub account_id: AccountId,
+}
+
+impl GetAuditTrail {
+    pub fn execute<C>(&self, storage: &TextFileEventStorage<BankCommand, C>) -> Result<Vec<BankCommand>, Box<dyn std::error::Error + Send + Sync>>
+    where
+        C: TextConverter<BankCommand>,
+    {
+        if let Some(events) = storage.indexed_events(&account_key(&self.account_id))? {
+            return Ok(events);
+        }
+        let mut cursor = storage.open_cursor()?;
+        let mut trail = Vec::new();
+        loop {
+            let batch = cursor.next_batch(SCAN_BATCH)?;
+            let done = batch.len() < SCAN_BATCH;
+            trail.extend(batch.into_iter().filter(|command| command.account_ids().contains(&&self.account_id)));
+            if done {
+                return Ok(trail);
+            }
+        }
+    }
+
+    /// Like `execute`, but ending with the account's `CloseAccount` when compaction dropped it
+    /// from the log and `bank` kept its tombstone
+    ///
+    /// An id is never reused, so the closure is always the last command of a trail.
+    pub fn execute_with_tombstone<C>(&self, storage: &TextFileEventStorage<BankCommand, C>, bank: &Bank) -> Result<Vec<BankCommand>, Box<dyn std::error::Error + Send + Sync>>
+    where
+        C: TextConverter<BankCommand>,
+    {
+        let mut trail = self.execute(storage)?;
+        let closed = matches!(trail.last(), Some(BankCommand::CloseAccount { .. }));
+        if !closed && bank.tombstones.contains_key(&self.account_id) {
+            trail.push(BankCommand::CloseAccount { id: self.account_id.clone() });
+        }
+        Ok(trail)
+    }
+}
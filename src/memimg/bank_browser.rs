@@ -0,0 +1,98 @@
+use crate::memimg::bank::{AccountId, Amount, Bank, BankCommand};
+use crate::memimg::bank_storage::BankJsonConverter;
+use crate::memimg::log_index::LogIndex;
+use crate::memimg::processor::Command;
+use crate::memimg::storage::TextConverter;
+use std::collections::BTreeMap;
+
+/// Events replayed per window while walking the log to a checkpoint
+const REPLAY_WINDOW: usize = 1024;
+
+/// Balance of one account just before and just after an event; `None` where the account is not open
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BalanceChange {
+    pub account_id: AccountId,
+    pub before: Option<Amount>,
+    pub after: Option<Amount>,
+}
+
+/// What one logged event does to the bank it is replayed into
+#[derive(Debug, Clone, PartialEq)]
+pub struct EventEffect {
+    pub command: BankCommand,
+    /// The accounts the command names, in its order
+    pub changes: Vec<BalanceChange>,
+    /// Why the event failed to apply, in which case it changed nothing
+    pub rejection: Option<String>,
+}
+
+/// Balances of the accounts `command` names before and after applying it to `before`, and why it failed if it did
+pub fn balance_changes(before: &Bank, command: &BankCommand) -> (Vec<BalanceChange>, Option<String>) {
+    let balance = |bank: &Bank, account_id: &AccountId| bank.accounts.get(account_id).map(|account| account.balance());
+    let mut after = before.clone();
+    let rejection = command.apply_to(&mut after).err().map(|e| e.to_string());
+    let changes = command
+        .account_ids()
+        .into_iter()
+        .map(|account_id| BalanceChange {
+            account_id: account_id.clone(),
+            before: balance(before, account_id),
+            after: balance(&after, account_id),
+        })
+        .collect();
+    (changes, rejection)
+}
+
+/// Bank states at points in a log, rebuilt on demand from cached checkpoints
+///
+/// The state before every `stride`-th event is kept once replay has passed it, so moving to any
+/// event replays at most `stride` events. As in a lenient replay, lines that do not parse and
+/// events that fail to apply are passed over.
+pub struct BankStateCache {
+    stride: u64,
+    /// State before event `n * stride`, for each checkpoint reached so far
+    checkpoints: BTreeMap<u64, Bank>,
+}
+
+impl BankStateCache {
+    pub fn new(stride: u64) -> Self {
+        Self {
+            stride: stride.max(1),
+            checkpoints: BTreeMap::from([(0, Bank::new())]),
+        }
+    }
+
+    /// The bank as it was just before event `event` (0-based) of `log`
+    pub fn state_before(&mut self, log: &LogIndex, event: u64) -> Result<Bank, Box<dyn std::error::Error + Send + Sync>> {
+        let event = event.min(log.len());
+        let (&start, state) = self.checkpoints.range(..=event).next_back().ok_or("missing initial checkpoint")?;
+        let mut bank = state.clone();
+        let mut next = start;
+        while next < event {
+            let count = (event - next).min(REPLAY_WINDOW as u64) as usize;
+            let window = log.read_window(next, count)?;
+            if window.is_empty() {
+                break;
+            }
+            for line in window {
+                if let Ok(command) = BankJsonConverter::new().parse(&line) {
+                    let _ = command.apply_to(&mut bank);
+                }
+                next += 1;
+                if next.is_multiple_of(self.stride) {
+                    self.checkpoints.entry(next).or_insert_with(|| bank.clone());
+                }
+            }
+        }
+        Ok(bank)
+    }
+
+    /// Parse event `event` of `log` and report how it changes the balances it touches
+    pub fn effect_of(&mut self, log: &LogIndex, event: u64) -> Result<EventEffect, Box<dyn std::error::Error + Send + Sync>> {
+        let line = log.read_window(event, 1)?.pop().ok_or_else(|| format!("event {} is past the end of the log", event))?;
+        let command = BankJsonConverter::new().parse(&line)?;
+        let before = self.state_before(log, event)?;
+        let (changes, rejection) = balance_changes(&before, &command);
+        Ok(EventEffect { command, changes, rejection })
+    }
+}
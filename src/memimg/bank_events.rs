@@ -0,0 +1,72 @@
+use crate::memimg::bank::{AccountId, Amount, Bank, BankError};
+use crate::memimg::json_event::JsonEvent;
+use crate::memimg::multi_event::MultiEventCommand;
+use serde::{Deserialize, Serialize};
+
+/// Ledger movement on one account, as logged by a `MultiEventProcessor` over a `Bank`
+///
+/// A debit or credit alone unbalances the bank; the command generating it pairs it with the
+/// opposite movement.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum BankEvent {
+    AccountDebited { account_id: AccountId, amount: Amount },
+    AccountCredited { account_id: AccountId, amount: Amount },
+}
+
+impl JsonEvent for BankEvent {}
+
+/// Transfer recorded as a debit of `from_account_id` and a credit of `to_account_id`
+///
+/// A zero amount, or a transfer to the same account, moves nothing and generates no events.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BankTransfer {
+    pub from_account_id: AccountId,
+    pub to_account_id: AccountId,
+    pub amount: Amount,
+}
+
+impl MultiEventCommand for BankTransfer {
+    type System = Bank;
+    type Event = BankEvent;
+
+    fn generate_events(&self, bank: &Bank) -> Result<Vec<BankEvent>, Box<dyn std::error::Error + Send + Sync>> {
+        if self.amount < Amount::ZERO {
+            return Err(Box::new(BankError::InvalidAmount(self.amount.to_string())));
+        }
+        let from = bank
+            .accounts
+            .get(&self.from_account_id)
+            .ok_or_else(|| Bank::account_not_found(&bank.closed_accounts, self.from_account_id.as_str()))?;
+        if !bank.accounts.contains_key(&self.to_account_id) {
+            return Err(Box::new(Bank::account_not_found(&bank.closed_accounts, self.to_account_id.as_str())));
+        }
+        if self.amount.is_zero() || self.from_account_id == self.to_account_id {
+            return Ok(Vec::new());
+        }
+        if from.balance() < self.amount {
+            return Err(Box::new(BankError::InsufficientFunds { available: from.balance(), requested: self.amount }));
+        }
+        Ok(vec![
+            BankEvent::AccountDebited { account_id: self.from_account_id.clone(), amount: self.amount },
+            BankEvent::AccountCredited { account_id: self.to_account_id.clone(), amount: self.amount },
+        ])
+    }
+
+    fn apply_event(bank: &mut Bank, event: &BankEvent) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let (account_id, amount, debit) = match event {
+            BankEvent::AccountDebited { account_id, amount } => (account_id, *amount, true),
+            BankEvent::AccountCredited { account_id, amount } => (account_id, *amount, false),
+        };
+        let account = bank
+            .accounts
+            .get_mut(account_id)
+            .ok_or_else(|| Bank::account_not_found(&bank.closed_accounts, account_id.as_str()))?;
+        if debit {
+            account.total_debits += amount;
+        } else {
+            account.total_credits += amount;
+        }
+        bank.touch([account_id]);
+        Ok(())
+    }
+}
@@ -0,0 +1,392 @@
+use crate::memimg::bank::{
+    domain_error_code, domain_error_message, Account, AccountId, Amount, Bank, BankCommand, BankError, GetAccount, GetBalance, ListAccounts,
+};
+use crate::memimg::{EventId, EventStorage, MemImgError, MemImgProcessor, TextConverter};
+use crate::memimg::bank_storage::BankJsonConverter;
+use axum::body::Body;
+use axum::extract::{Path, Query, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::sync::Arc;
+use tokio::net::TcpListener;
+use tokio::sync::Mutex;
+use utoipa::openapi::{ContentBuilder, Ref, RefOr, ResponseBuilder};
+use utoipa::{OpenApi, ToSchema};
+
+/// Processor shared between request handlers
+pub type SharedProcessor<E> = Arc<Mutex<MemImgProcessor<Bank, BankCommand, E>>>;
+
+/// HTTP status of each error code; codes not listed, such as `SYSTEM_FAILURE`, are 500s
+///
+/// The OpenAPI document is generated from this table, so it documents exactly these statuses.
+pub const ERROR_STATUSES: &[(&str, StatusCode)] = &[
+    ("ACCOUNT_NOT_FOUND", StatusCode::NOT_FOUND),
+    ("OWNER_NOT_FOUND", StatusCode::NOT_FOUND),
+    ("ACCOUNT_CLOSED", StatusCode::GONE),
+    ("EVENTS_COMPACTED", StatusCode::GONE),
+    ("INSUFFICIENT_FUNDS", StatusCode::CONFLICT),
+    ("DUPLICATE_ACCOUNT", StatusCode::CONFLICT),
+    ("NON_ZERO_BALANCE", StatusCode::CONFLICT),
+    ("DUPLICATE_OWNER", StatusCode::CONFLICT),
+    ("LAST_OWNER", StatusCode::CONFLICT),
+    ("DUPLICATE_EXTERNAL_PAYMENT", StatusCode::CONFLICT),
+    ("DUPLICATE_ACCOUNT_NAME", StatusCode::CONFLICT),
+    ("INTEREST_NOT_DUE", StatusCode::CONFLICT),
+    ("VERSION_CONFLICT", StatusCode::CONFLICT),
+    ("INVALID_AMOUNT", StatusCode::UNPROCESSABLE_ENTITY),
+    ("INVALID_ACCOUNT_ID", StatusCode::UNPROCESSABLE_ENTITY),
+    // Command failures other than bank errors are invariant (validation) rejections
+    ("COMMAND_FAILURE", StatusCode::UNPROCESSABLE_ENTITY),
+    ("RATE_LIMIT_EXCEEDED", StatusCode::TOO_MANY_REQUESTS),
+    ("POISONED", StatusCode::SERVICE_UNAVAILABLE),
+    ("MAINTENANCE_MODE", StatusCode::SERVICE_UNAVAILABLE),
+    ("STORAGE_FULL", StatusCode::INSUFFICIENT_STORAGE),
+];
+
+/// Error response: an HTTP status plus the `{"code", "message"}` body
+#[derive(Debug)]
+pub struct ApiError {
+    pub status: StatusCode,
+    pub code: &'static str,
+    pub message: String,
+}
+
+impl ApiError {
+    /// Error response for an error code, with the status that code maps to
+    pub fn new(code: &'static str, message: String) -> Self {
+        let status = ERROR_STATUSES
+            .iter()
+            .find(|(known, _)| *known == code)
+            .map_or(StatusCode::INTERNAL_SERVER_ERROR, |(_, status)| *status);
+        Self { status, code, message }
+    }
+}
+
+/// Body of every error response
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ErrorBody {
+    /// Stable error code, such as `INSUFFICIENT_FUNDS`
+    pub code: String,
+    pub message: String,
+}
+
+/// Body of a successful `POST /commands`; `seq` is also returned as the `ETag`
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CommandResult {
+    pub seq: u64,
+}
+
+/// An account as the account endpoints return it
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AccountView {
+    pub id: AccountId,
+    pub name: String,
+    #[schema(value_type = String)]
+    pub balance: Amount,
+}
+
+impl From<&Account> for AccountView {
+    fn from(account: &Account) -> Self {
+        Self { id: account.id.clone(), name: account.name.clone(), balance: account.balance() }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BalanceView {
+    pub account_id: String,
+    #[schema(value_type = String)]
+    pub balance: Amount,
+}
+
+impl From<MemImgError> for ApiError {
+    fn from(error: MemImgError) -> Self {
+        ApiError::new(domain_error_code(&error), domain_error_message(&error))
+    }
+}
+
+impl From<BankError> for ApiError {
+    fn from(error: BankError) -> Self {
+        ApiError::new(error.code(), error.to_string())
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.status, Json(ErrorBody { code: self.code.to_string(), message: self.message })).into_response()
+    }
+}
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "rmemimg bank", description = "Commands and account queries over a memory-image bank"),
+    paths(execute_command, list_accounts, get_account, get_balance, export_events),
+    components(schemas(BankCommand, ErrorBody, CommandResult, AccountView, BalanceView))
+)]
+struct ApiDoc;
+
+/// Name of the response component for `status`, such as `Conflict`
+fn error_response_name(status: StatusCode) -> String {
+    status.canonical_reason().unwrap_or("Error").replace([' ', '-'], "")
+}
+
+/// OpenAPI 3 document for `router`, served at `GET /openapi.json`
+///
+/// Each status in `ERROR_STATUSES` becomes a response component listing its codes, referenced
+/// from `POST /commands`; the account queries reference the statuses they can return.
+pub fn openapi() -> utoipa::openapi::OpenApi {
+    let mut doc = ApiDoc::openapi();
+    let mut statuses: Vec<StatusCode> = ERROR_STATUSES.iter().map(|(_, status)| *status).collect();
+    statuses.push(StatusCode::INTERNAL_SERVER_ERROR);
+    statuses.sort_by_key(StatusCode::as_u16);
+    statuses.dedup();
+
+    let components = doc.components.get_or_insert_with(Default::default);
+    for status in &statuses {
+        let mut codes: Vec<&str> = ERROR_STATUSES.iter().filter(|(_, s)| s == status).map(|(code, _)| *code).collect();
+        if codes.is_empty() {
+            codes.push("SYSTEM_FAILURE");
+        }
+        let response = ResponseBuilder::new()
+            .description(format!("Error codes: {}", codes.join(", ")))
+            .content("application/json", ContentBuilder::new().schema(Some(Ref::from_schema_name("ErrorBody"))).build())
+            .build();
+        components.responses.insert(error_response_name(*status), RefOr::T(response));
+    }
+
+    let reference = |status: StatusCode| RefOr::Ref(Ref::new(format!("#/components/responses/{}", error_response_name(status))));
+    for (path, item) in doc.paths.paths.iter_mut() {
+        let (operation, statuses) = match path.as_str() {
+            "/commands" => (item.post.as_mut(), statuses.clone()),
+            "/accounts/{id}" | "/accounts/{id}/balance" => {
+                (item.get.as_mut(), vec![StatusCode::NOT_FOUND, StatusCode::GONE, StatusCode::INTERNAL_SERVER_ERROR])
+            }
+            _ => (item.get.as_mut(), vec![StatusCode::INTERNAL_SERVER_ERROR]),
+        };
+        if let Some(operation) = operation {
+            for status in statuses {
+                operation.responses.responses.insert(status.as_u16().to_string(), reference(status));
+            }
+        }
+    }
+    doc
+}
+
+/// Routes for commands and account queries:
+///
+/// - `POST /commands` executes a `BankCommand`; an `If-Match` header holding an event version
+///   makes it conditional, failing with 409 `VERSION_CONFLICT` if other commands ran since
+/// - `GET /accounts`, `GET /accounts/{id}` and `GET /accounts/{id}/balance` query state
+/// - `GET /events?from_seq=N` streams the events after `N` as JSON lines, for followers to replay
+/// - `GET /openapi.json` describes all of the above
+/// - `GET /metrics`, with the `metrics` feature, returns `metrics::gather` for Prometheus to scrape
+pub fn router<E>(processor: SharedProcessor<E>) -> Router
+where
+    E: EventStorage<Event = BankCommand> + Send + 'static,
+{
+    let router = Router::new()
+        .route("/openapi.json", get(|| async { Json(openapi()) }))
+        .route("/commands", post(execute_command::<E>))
+        .route("/accounts", get(list_accounts::<E>))
+        .route("/accounts/{id}", get(get_account::<E>))
+        .route("/accounts/{id}/balance", get(get_balance::<E>))
+        .route("/events", get(export_events::<E>));
+    #[cfg(feature = "metrics")]
+    let router = router.route(
+        "/metrics",
+        get(|| async { ([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], crate::memimg::metrics::gather()) }),
+    );
+    router.with_state(processor)
+}
+
+/// Serve `router` on `listener` until `shutdown` resolves, then flush the event storage
+pub async fn serve<E>(
+    listener: TcpListener,
+    processor: SharedProcessor<E>,
+    shutdown: impl Future<Output = ()> + Send + 'static,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>>
+where
+    E: EventStorage<Event = BankCommand> + Send + 'static,
+{
+    axum::serve(listener, router(processor.clone()))
+        .with_graceful_shutdown(shutdown)
+        .await?;
+    processor.lock().await.event_storage.flush()?;
+    Ok(())
+}
+
+fn etag(version: EventId) -> (header::HeaderName, String) {
+    (header::ETAG, format!("\"{}\"", version))
+}
+
+/// Execute a bank command
+#[utoipa::path(
+    post,
+    path = "/commands",
+    request_body = BankCommand,
+    params(("If-Match" = Option<String>, Header, description = "Quoted event version the command is conditional on")),
+    responses((status = 200, description = "Command applied and logged", body = CommandResult, headers(("ETag" = String, description = "Quoted event version after the command"))))
+)]
+async fn execute_command<E>(
+    State(processor): State<SharedProcessor<E>>,
+    headers: HeaderMap,
+    Json(command): Json<BankCommand>,
+) -> Result<impl IntoResponse, ApiError>
+where
+    E: EventStorage<Event = BankCommand> + Send + 'static,
+{
+    let mut processor = processor.lock().await;
+
+    if let Some(expected) = headers.get(header::IF_MATCH) {
+        let current = processor.event_version();
+        let expected = expected.to_str().unwrap_or_default().trim_matches('"');
+        if expected != current.to_string() {
+            return Err(ApiError::new(
+                "VERSION_CONFLICT",
+                format!("State is at version {}, not {}", current, expected),
+            ));
+        }
+    }
+
+    processor.execute_command(command)?;
+    let version = processor.event_version();
+    Ok(([etag(version)], Json(CommandResult { seq: version.as_u64() })))
+}
+
+/// All open accounts, sorted by id
+#[utoipa::path(get, path = "/accounts", responses((status = 200, body = Vec<AccountView>)))]
+async fn list_accounts<E>(State(processor): State<SharedProcessor<E>>) -> Result<Json<Vec<AccountView>>, ApiError>
+where
+    E: EventStorage<Event = BankCommand> + Send + 'static,
+{
+    let accounts = processor.lock().await.execute_query(&ListAccounts)?;
+    Ok(Json(accounts.iter().map(AccountView::from).collect()))
+}
+
+/// One account
+#[utoipa::path(get, path = "/accounts/{id}", params(("id" = String, Path)), responses((status = 200, body = AccountView)))]
+async fn get_account<E>(
+    State(processor): State<SharedProcessor<E>>,
+    Path(account_id): Path<String>,
+) -> Result<Json<AccountView>, ApiError>
+where
+    E: EventStorage<Event = BankCommand> + Send + 'static,
+{
+    let processor = processor.lock().await;
+    match processor.execute_query(&GetAccount { account_id: account_id.as_str().into() })? {
+        Some(account) => Ok(Json(AccountView::from(&account))),
+        None => {
+            let error = if processor.system().closed_accounts.contains(account_id.as_str()) {
+                BankError::AccountClosed(account_id)
+            } else {
+                BankError::AccountNotFound(account_id)
+            };
+            Err(error.into())
+        }
+    }
+}
+
+/// One account's balance
+#[utoipa::path(get, path = "/accounts/{id}/balance", params(("id" = String, Path)), responses((status = 200, body = BalanceView)))]
+async fn get_balance<E>(
+    State(processor): State<SharedProcessor<E>>,
+    Path(account_id): Path<String>,
+) -> Result<Json<BalanceView>, ApiError>
+where
+    E: EventStorage<Event = BankCommand> + Send + 'static,
+{
+    let balance = processor.lock().await.execute_query(&GetBalance { account_id: account_id.as_str().into() })?;
+    Ok(Json(BalanceView { account_id, balance }))
+}
+
+/// Events read from storage per chunk of an `/events` response
+const EXPORT_CHUNK: u64 = 1024;
+
+#[derive(Debug, Deserialize)]
+struct ExportParams {
+    #[serde(default)]
+    from_seq: u64,
+}
+
+/// The event log after `from_seq`, one `BankCommand` per line as `POST /commands` takes them
+///
+/// The body is sent in chunks, each resuming where the last stopped and locking the processor
+/// only while it is read, so commands keep running during a long export; events committed
+/// meanwhile are included. A follower replays the lines in order and asks again from the last seq
+/// it applied. Seqs folded into a snapshot by compaction are gone: asking from before them is a
+/// 410 `EVENTS_COMPACTED`, and a compaction during the export ends the body early.
+#[utoipa::path(
+    get,
+    path = "/events",
+    params(("from_seq" = Option<u64>, Query, description = "Seq of the last event already held; 0 or absent for the whole log")),
+    responses(
+        (status = 200, description = "Events after `from_seq`, one per line", content_type = "application/x-ndjson", body = String),
+        (status = 410, description = "EVENTS_COMPACTED", body = ErrorBody)
+    )
+)]
+async fn export_events<E>(State(processor): State<SharedProcessor<E>>, Query(params): Query<ExportParams>) -> Result<Response, ApiError>
+where
+    E: EventStorage<Event = BankCommand> + Send + 'static,
+{
+    let base = log_base(&*processor.lock().await);
+    if params.from_seq < base {
+        let message = format!("events up to seq {base} were compacted into a snapshot; from_seq {} is before them", params.from_seq);
+        return Err(ApiError::new("EVENTS_COMPACTED", message));
+    }
+    // The log holds seqs from `log_base + 1`, so the first `from_seq - log_base` events are skipped
+    let start = ExportCursor { position: 0, skip: params.from_seq - base };
+    let chunks = futures_util::stream::unfold(Some(start), move |cursor| {
+        let processor = processor.clone();
+        async move {
+            let cursor = cursor?;
+            let mut processor = processor.lock().await;
+            if log_base(&processor) != base {
+                return Some((Err("the log was compacted during the export".into()), None));
+            }
+            match read_chunk(&mut *processor.event_storage, cursor) {
+                Ok((_, 0, _)) => None,
+                Ok((lines, read, next)) => Some((Ok(lines), (read == EXPORT_CHUNK).then_some(next))),
+                Err(e) => Some((Err::<String, Box<dyn std::error::Error + Send + Sync>>(e), None)),
+            }
+        }
+    });
+    Ok(([(header::CONTENT_TYPE, "application/x-ndjson")], Body::from_stream(chunks)).into_response())
+}
+
+/// Where the next chunk of an export resumes: a storage position, and how many events read from
+/// it still come before `from_seq`
+#[derive(Debug, Clone, Copy)]
+struct ExportCursor {
+    position: u64,
+    skip: u64,
+}
+
+/// Seq of the last event folded into the processor's snapshot, so its log starts after it
+fn log_base<E>(processor: &MemImgProcessor<Bank, BankCommand, E>) -> u64
+where
+    E: EventStorage<Event = BankCommand>,
+{
+    processor.event_version().as_u64() - processor.events_since_snapshot()
+}
+
+/// Up to `EXPORT_CHUNK` events from `cursor` as JSON lines, minus those it still skips; how many
+/// events were read; and where the next chunk resumes
+fn read_chunk<E>(storage: &mut E, cursor: ExportCursor) -> Result<(String, u64, ExportCursor), Box<dyn std::error::Error + Send + Sync>>
+where
+    E: EventStorage<Event = BankCommand>,
+{
+    let mut lines = String::new();
+    let mut index = 0;
+    let (read, position) = storage.replay_from(cursor.position, EXPORT_CHUNK, &mut |event| {
+        index += 1;
+        if index > cursor.skip {
+            lines.push_str(&BankJsonConverter::new().format(&event)?);
+            lines.push('\n');
+        }
+        Ok(())
+    })?;
+    Ok((lines, read, ExportCursor { position, skip: cursor.skip.saturating_sub(read) }))
+}
@@ -0,0 +1,122 @@
+use crate::memimg::bank::{Account, AccountId, Amount, Bank};
+use crate::memimg::processor::Query;
+use crate::memimg::validation::SystemValidator;
+use thiserror::Error;
+
+/// Bank-wide invariant broken by a command
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum SystemInvariantViolation {
+    #[error("Double-entry mismatch: expected total credits {expected_credits}, found {actual_credits}")]
+    DoubleEntryMismatch { expected_credits: Amount, actual_credits: Amount },
+}
+
+/// Checks that every credit is matched by a debit or by capital deposited from outside the bank:
+/// `total_credits == total_debits + equity_capital`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DoubleEntryValidator;
+
+impl SystemValidator<Bank> for DoubleEntryValidator {
+    fn validate(&self, bank: &Bank) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let expected_credits = bank.total_debits() + bank.equity_capital;
+        let actual_credits = bank.total_credits();
+        if actual_credits != expected_credits {
+            return Err(Box::new(SystemInvariantViolation::DoubleEntryMismatch {
+                expected_credits,
+                actual_credits,
+            }));
+        }
+        Ok(())
+    }
+}
+
+/// One inconsistency found by `VerifyIntegrity`
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum IntegrityViolation {
+    #[error("Account keyed {key} has id {account_id}")]
+    MisKeyedAccount { key: AccountId, account_id: AccountId },
+
+    #[error("Account {0} is both open and closed")]
+    ClosedAccountStillOpen(AccountId),
+
+    #[error("Account {0} has no owners")]
+    NoOwners(AccountId),
+
+    #[error("Account {account_id} has negative ledger totals: debits {total_debits}, credits {total_credits}")]
+    NegativeLedgerTotal { account_id: AccountId, total_debits: Amount, total_credits: Amount },
+
+    #[error("Account {account_id} is overdrawn: balance {balance}")]
+    NegativeBalance { account_id: AccountId, balance: Amount },
+
+    #[error("Total assets are negative: {0}")]
+    NegativeTotalAssets(Amount),
+
+    #[error("Double-entry mismatch: expected total credits {expected_credits}, found {actual_credits}")]
+    DoubleEntryMismatch { expected_credits: Amount, actual_credits: Amount },
+}
+
+/// Every violation found in one pass over the bank, per-account ones sorted by account id
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IntegrityReport {
+    pub accounts_checked: usize,
+    pub violations: Vec<IntegrityViolation>,
+}
+
+impl IntegrityReport {
+    pub fn is_consistent(&self) -> bool {
+        self.violations.is_empty()
+    }
+}
+
+/// Administrative check of every bank invariant, for use after a migration or a restore
+///
+/// Unlike `DoubleEntryValidator` it does not stop at the first problem: the report lists all
+/// of them. Commands cannot produce any of these states, so a violation means the state was
+/// loaded from a damaged or hand-edited snapshot.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct VerifyIntegrity;
+
+impl Query for VerifyIntegrity {
+    type System = Bank;
+    type Result = IntegrityReport;
+
+    fn extract_from(&self, bank: &Self::System) -> Result<Self::Result, Box<dyn std::error::Error + Send + Sync>> {
+        let mut accounts: Vec<(&AccountId, &Account)> = bank.accounts.iter().collect();
+        accounts.sort_by(|a, b| a.0.cmp(b.0));
+
+        let mut violations = Vec::new();
+        for (key, account) in &accounts {
+            if account.id != **key {
+                violations.push(IntegrityViolation::MisKeyedAccount { key: (*key).clone(), account_id: account.id.clone() });
+            }
+            if bank.closed_accounts.contains(*key) {
+                violations.push(IntegrityViolation::ClosedAccountStillOpen((*key).clone()));
+            }
+            if account.owners.is_empty() {
+                violations.push(IntegrityViolation::NoOwners((*key).clone()));
+            }
+            if account.total_debits < Amount::ZERO || account.total_credits < Amount::ZERO {
+                violations.push(IntegrityViolation::NegativeLedgerTotal {
+                    account_id: (*key).clone(),
+                    total_debits: account.total_debits,
+                    total_credits: account.total_credits,
+                });
+            }
+            // The bank offers no overdraft, so any negative balance is a violation
+            if account.balance() < Amount::ZERO {
+                violations.push(IntegrityViolation::NegativeBalance { account_id: (*key).clone(), balance: account.balance() });
+            }
+        }
+
+        let total_assets: Amount = bank.accounts.values().map(Account::balance).sum();
+        if total_assets < Amount::ZERO {
+            violations.push(IntegrityViolation::NegativeTotalAssets(total_assets));
+        }
+        let expected_credits = bank.total_debits() + bank.equity_capital;
+        let actual_credits = bank.total_credits();
+        if actual_credits != expected_credits {
+            violations.push(IntegrityViolation::DoubleEntryMismatch { expected_credits, actual_credits });
+        }
+
+        Ok(IntegrityReport { accounts_checked: accounts.len(), violations })
+    }
+}
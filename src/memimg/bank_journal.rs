@@ -0,0 +1,164 @@
+use crate::memimg::bank::{Bank, BankCommand};
+use crate::memimg::error::{FailureOutcome, MemImgError};
+use crate::memimg::processor::MemImgProcessor;
+use crate::memimg::storage::EventStorage;
+use std::io::Write;
+
+/// Columns of the journal CSV, in order
+pub const JOURNAL_CSV_HEADER: [&str; 8] = [
+    "sequence",
+    "event_type",
+    "account_id",
+    "from_account_id",
+    "to_account_id",
+    "amount",
+    "name",
+    "occurred_at",
+];
+
+/// One journal row; columns a command does not use stay empty
+#[derive(Default)]
+struct JournalRow<'a> {
+    event_type: &'a str,
+    account_id: &'a str,
+    from_account_id: &'a str,
+    to_account_id: &'a str,
+    amount: String,
+    name: &'a str,
+}
+
+fn journal_rows(command: &BankCommand) -> Vec<JournalRow<'_>> {
+    match command {
+        BankCommand::CreateAccount { id, name, opening_balance } => vec![JournalRow {
+            event_type: "CreateAccount",
+            account_id: id.as_str(),
+            amount: opening_balance.map(|balance| balance.to_string()).unwrap_or_default(),
+            name,
+            ..Default::default()
+        }],
+        BankCommand::Deposit { account_id, amount } => vec![JournalRow {
+            event_type: "Deposit",
+            account_id: account_id.as_str(),
+            amount: amount.to_string(),
+            ..Default::default()
+        }],
+        BankCommand::Withdrawal { account_id, amount } => vec![JournalRow {
+            event_type: "Withdrawal",
+            account_id: account_id.as_str(),
+            amount: amount.to_string(),
+            ..Default::default()
+        }],
+        BankCommand::Transfer { from_account_id, to_account_id, amount } => vec![JournalRow {
+            event_type: "Transfer",
+            from_account_id: from_account_id.as_str(),
+            to_account_id: to_account_id.as_str(),
+            amount: amount.to_string(),
+            ..Default::default()
+        }],
+        BankCommand::Sweep { from_account_id, to_account_id, amount } => vec![JournalRow {
+            event_type: "Sweep",
+            from_account_id: from_account_id.as_str(),
+            to_account_id: to_account_id.as_str(),
+            amount: amount.map(|amount| amount.to_string()).unwrap_or_default(),
+            ..Default::default()
+        }],
+        // One row per account created, all sharing the event's sequence
+        BankCommand::BulkCreateAccounts { accounts } => accounts
+            .iter()
+            .map(|(id, name)| JournalRow { event_type: "BulkCreateAccounts", account_id: id.as_str(), name, ..Default::default() })
+            .collect(),
+        // One row per imported account, with its opening balance as the amount
+        BankCommand::ImportLedger { entries } => entries
+            .iter()
+            .map(|entry| JournalRow {
+                event_type: "ImportLedger",
+                account_id: entry.account_id.as_str(),
+                amount: entry.balance.to_string(),
+                name: &entry.name,
+                ..Default::default()
+            })
+            .collect(),
+        BankCommand::RecordExternalPayment { account_id, amount, .. } => vec![JournalRow {
+            event_type: "RecordExternalPayment",
+            account_id: account_id.as_str(),
+            amount: amount.to_string(),
+            ..Default::default()
+        }],
+        BankCommand::CloseAccount { id } => {
+            vec![JournalRow { event_type: "CloseAccount", account_id: id.as_str(), ..Default::default() }]
+        }
+        // The owner goes in the name column
+        BankCommand::AddOwner { account_id, owner } => {
+            vec![JournalRow { event_type: "AddOwner", account_id: account_id.as_str(), name: owner, ..Default::default() }]
+        }
+        BankCommand::RemoveOwner { account_id, owner } => {
+            vec![JournalRow { event_type: "RemoveOwner", account_id: account_id.as_str(), name: owner, ..Default::default() }]
+        }
+        // Bookkeeping for bootstrapped images, with no funds or owners to report
+        BankCommand::RestoreModificationSeqs { .. } => Vec::new(),
+        BankCommand::ScheduleInterest { account_id, .. } => {
+            vec![JournalRow { event_type: "ScheduleInterest", account_id: account_id.as_str(), ..Default::default() }]
+        }
+        BankCommand::AccrueInterest { account_id, amount, .. } => vec![JournalRow {
+            event_type: "AccrueInterest",
+            account_id: account_id.as_str(),
+            amount: amount.map(|amount| amount.to_string()).unwrap_or_default(),
+            ..Default::default()
+        }],
+        // The fee asked for; an account not overdrawn is charged less, or nothing
+        BankCommand::ApplyOverdraftFee { account_id, fee, .. } => vec![JournalRow {
+            event_type: "ApplyOverdraftFee",
+            account_id: account_id.as_str(),
+            amount: fee.to_string(),
+            ..Default::default()
+        }],
+    }
+}
+
+impl<E> MemImgProcessor<Bank, BankCommand, E>
+where
+    E: EventStorage<Event = BankCommand>,
+{
+    /// Write the event log as CSV for spreadsheet analysis, returning the number of data rows
+    ///
+    /// Columns are `JOURNAL_CSV_HEADER`. `sequence` is the event's version; `occurred_at` stays
+    /// empty because events carry no timestamp.
+    pub fn export_journal_csv<W: Write>(&mut self, writer: &mut W) -> Result<u64, MemImgError> {
+        let failure = |e, context: &str| MemImgError::SystemFailure(FailureOutcome::new(e, context, "EventStorage"));
+
+        let mut events = Vec::new();
+        self.event_storage
+            .replay(&mut |event| {
+                events.push(event);
+                Ok(())
+            })
+            .map_err(|e| failure(e, "reading events for"))?;
+        // After a compaction the log starts past the snapshot, so sequences continue from there
+        let first_sequence = self.event_version().as_u64() + 1 - events.len() as u64;
+
+        let mut csv = csv::Writer::from_writer(writer);
+        let write_failure = |e: csv::Error| failure(Box::new(e), "exporting journal from");
+        csv.write_record(JOURNAL_CSV_HEADER).map_err(write_failure)?;
+
+        let mut rows = 0u64;
+        for (index, event) in events.iter().enumerate() {
+            let sequence = (first_sequence + index as u64).to_string();
+            for row in journal_rows(event) {
+                csv.write_record([
+                    sequence.as_str(),
+                    row.event_type,
+                    row.account_id,
+                    row.from_account_id,
+                    row.to_account_id,
+                    &row.amount,
+                    row.name,
+                    "",
+                ])
+                .map_err(write_failure)?;
+                rows += 1;
+            }
+        }
+        csv.flush().map_err(|e| failure(Box::new(e), "exporting journal from"))?;
+        Ok(rows)
+    }
+}
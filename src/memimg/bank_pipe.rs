@@ -0,0 +1,158 @@
+use crate::memimg::bank::{domain_error_code, AccountId, domain_error_message, Bank, BankCommand, GetAccount, GetBalance, GetLedgerSummary, ListAccounts};
+use crate::memimg::{EventStorage, MemImgError, MemImgProcessor};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::io::{BufRead, Write};
+
+/// Query accepted on a pipe input line, tagged by its `query` field
+#[derive(Debug, Deserialize)]
+#[serde(tag = "query")]
+pub enum PipeQuery {
+    GetAccount { account_id: AccountId },
+    GetBalance { account_id: AccountId },
+    GetLedgerSummary { account_id: AccountId },
+    ListAccounts,
+}
+
+/// Run NDJSON requests from `input` through `processor`, writing one NDJSON result per request to `output`
+///
+/// Each line is either a `BankCommand` or a `PipeQuery`. Domain and malformed-input failures are
+/// reported as `{"ok":false,...}` results and processing continues; a system failure (or any I/O
+/// error on `input`/`output`) is reported and then returned, stopping the loop.
+pub fn run_pipe<E, R, W>(
+    processor: &mut MemImgProcessor<Bank, BankCommand, E>,
+    input: R,
+    output: W,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>>
+where
+    E: EventStorage<Event = BankCommand>,
+    R: BufRead,
+    W: Write,
+{
+    run_pipe_until(processor, input, output, || false)
+}
+
+/// `run_pipe`, also stopping cleanly, before reading another line, once `should_stop` returns `true`
+pub fn run_pipe_until<E, R, W>(
+    processor: &mut MemImgProcessor<Bank, BankCommand, E>,
+    input: R,
+    mut output: W,
+    should_stop: impl Fn() -> bool,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>>
+where
+    E: EventStorage<Event = BankCommand>,
+    R: BufRead,
+    W: Write,
+{
+    let mut lines = input.lines();
+    while !should_stop() {
+        let Some(line) = lines.next() else {
+            break;
+        };
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let (result, fatal) = match handle_line(processor, &line) {
+            Ok(result) => (result, None),
+            Err(failure) => {
+                let result = json!({"ok": false, "code": failure.code(), "message": failure.message()});
+                let fatal = match failure {
+                    PipeFailure::Processor(
+                        error @ (MemImgError::SystemFailure(_) | MemImgError::StorageFull(_) | MemImgError::Poisoned),
+                    ) => Some(error),
+                    _ => None,
+                };
+                (result, fatal)
+            }
+        };
+        writeln!(output, "{}", result)?;
+
+        if let Some(error) = fatal {
+            output.flush()?;
+            return Err(Box::new(error));
+        }
+    }
+    output.flush()?;
+    Ok(())
+}
+
+/// Why a request failed: unparseable input, or rejection by the processor
+pub(crate) enum PipeFailure {
+    Malformed(String),
+    Processor(MemImgError),
+}
+
+impl PipeFailure {
+    /// Stable error code: `MALFORMED_INPUT`, a `BankError` code or a processor code
+    pub(crate) fn code(&self) -> &'static str {
+        match self {
+            PipeFailure::Malformed(_) => "MALFORMED_INPUT",
+            PipeFailure::Processor(error) => domain_error_code(error),
+        }
+    }
+
+    pub(crate) fn message(&self) -> String {
+        match self {
+            PipeFailure::Malformed(message) => message.clone(),
+            PipeFailure::Processor(error) => domain_error_message(error),
+        }
+    }
+}
+
+impl From<MemImgError> for PipeFailure {
+    fn from(error: MemImgError) -> Self {
+        PipeFailure::Processor(error)
+    }
+}
+
+fn handle_line<E>(processor: &mut MemImgProcessor<Bank, BankCommand, E>, line: &str) -> Result<Value, PipeFailure>
+where
+    E: EventStorage<Event = BankCommand>,
+{
+    let value: Value = serde_json::from_str(line).map_err(malformed)?;
+
+    if value.get("query").is_none() {
+        let seq = execute_command_json(processor, value)?;
+        return Ok(json!({"ok": true, "seq": seq}));
+    }
+    let result = execute_query_json(processor, value)?;
+    Ok(json!({"ok": true, "result": result}))
+}
+
+fn malformed(error: serde_json::Error) -> PipeFailure {
+    PipeFailure::Malformed(error.to_string())
+}
+
+/// Execute a `BankCommand` in its JSON form, returning the sequence number of its event
+pub(crate) fn execute_command_json<E>(processor: &mut MemImgProcessor<Bank, BankCommand, E>, command: Value) -> Result<u64, PipeFailure>
+where
+    E: EventStorage<Event = BankCommand>,
+{
+    let command: BankCommand = serde_json::from_value(command).map_err(malformed)?;
+    Ok(processor.execute_command(command)?.seq)
+}
+
+/// Answer a `PipeQuery` in its JSON form
+pub(crate) fn execute_query_json<E>(processor: &MemImgProcessor<Bank, BankCommand, E>, query: Value) -> Result<Value, PipeFailure>
+where
+    E: EventStorage<Event = BankCommand>,
+{
+    let result = match serde_json::from_value(query).map_err(malformed)? {
+        PipeQuery::GetAccount { account_id } => match processor.execute_query(&GetAccount { account_id })? {
+            Some(account) => json!({"id": account.id, "name": account.name, "balance": account.balance()}),
+            None => Value::Null,
+        },
+        PipeQuery::GetBalance { account_id } => json!(processor.execute_query(&GetBalance { account_id })?),
+        PipeQuery::GetLedgerSummary { account_id } => json!(processor.execute_query(&GetLedgerSummary { account_id })?),
+        PipeQuery::ListAccounts => {
+            processor
+                .execute_query(&ListAccounts)?
+                .into_iter()
+                .map(|account| json!({"id": account.id, "name": account.name, "balance": account.balance()}))
+                .collect()
+        }
+    };
+    Ok(result)
+}
@@ -0,0 +1,90 @@
+use crate::memimg::bank::{parse_amount, AccountId, BankCommand, BankError};
+use thiserror::Error;
+
+/// Help text listing every REPL command
+pub const REPL_HELP: &str = "\
+create <id> <name>              open an account
+deposit <id> <amount>           credit an account
+withdraw <id> <amount>          debit an account
+transfer <from> <to> <amount>   move funds between accounts
+close <id>                      close an account with a zero balance
+balance <id>                    show an account balance
+accounts                        list all accounts
+history <id>                    list logged commands touching an account
+help                            show this help
+quit                            leave the REPL";
+
+/// A line entered at the bank REPL
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReplCommand {
+    Execute(BankCommand),
+    Balance(AccountId),
+    Accounts,
+    History(AccountId),
+    Help,
+    Quit,
+}
+
+/// Why a REPL line could not be parsed
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum ReplParseError {
+    #[error("Unknown command {0:?}; type 'help' for a list of commands")]
+    UnknownCommand(String),
+
+    #[error("Usage: {usage}")]
+    Usage { usage: &'static str },
+
+    #[error("{0}")]
+    Amount(#[from] BankError),
+}
+
+/// Parse one REPL line; blank lines yield `None`
+pub fn parse_repl_line(line: &str) -> Result<Option<ReplCommand>, ReplParseError> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let Some((&keyword, args)) = words.split_first() else {
+        return Ok(None);
+    };
+
+    let usage = |usage: &'static str| ReplParseError::Usage { usage };
+    let command = match (keyword.to_lowercase().as_str(), args) {
+        ("create", [id, name @ ..]) if !name.is_empty() => ReplCommand::Execute(BankCommand::CreateAccount {
+            id: AccountId::from(*id),
+            name: name.join(" "),
+            opening_balance: None,
+        }),
+        ("create", _) => return Err(usage("create <id> <name>")),
+        ("deposit", [account_id, amount]) => ReplCommand::Execute(BankCommand::Deposit {
+            account_id: AccountId::from(*account_id),
+            amount: parse_amount(amount)?,
+        }),
+        ("deposit", _) => return Err(usage("deposit <id> <amount>")),
+        ("withdraw", [account_id, amount]) => ReplCommand::Execute(BankCommand::Withdrawal {
+            account_id: AccountId::from(*account_id),
+            amount: parse_amount(amount)?,
+        }),
+        ("withdraw", _) => return Err(usage("withdraw <id> <amount>")),
+        ("transfer", [from_account_id, to_account_id, amount]) => ReplCommand::Execute(BankCommand::Transfer {
+            from_account_id: AccountId::from(*from_account_id),
+            to_account_id: AccountId::from(*to_account_id),
+            amount: parse_amount(amount)?,
+        }),
+        ("transfer", _) => return Err(usage("transfer <from> <to> <amount>")),
+        ("close", [id]) => ReplCommand::Execute(BankCommand::CloseAccount { id: AccountId::from(*id) }),
+        ("close", _) => return Err(usage("close <id>")),
+        ("balance", [account_id]) => ReplCommand::Balance(AccountId::from(*account_id)),
+        ("balance", _) => return Err(usage("balance <id>")),
+        ("accounts", []) => ReplCommand::Accounts,
+        ("accounts", _) => return Err(usage("accounts")),
+        ("history", [account_id]) => ReplCommand::History(AccountId::from(*account_id)),
+        ("history", _) => return Err(usage("history <id>")),
+        ("help", _) => ReplCommand::Help,
+        ("quit" | "exit", _) => ReplCommand::Quit,
+        _ => return Err(ReplParseError::UnknownCommand(keyword.to_string())),
+    };
+    Ok(Some(command))
+}
+
+/// Whether `command` reads or writes `account_id`
+pub fn touches_account(command: &BankCommand, account_id: &str) -> bool {
+    command.account_ids().into_iter().any(|id| id == account_id)
+}
@@ -0,0 +1,43 @@
+use crate::memimg::bank::{Account, Amount, BankCommand, LedgerSummary};
+use schemars::{schema_for, Schema};
+use std::path::{Path, PathBuf};
+
+/// Prefix of every schema's `$id`; the file name completes it
+pub const SCHEMA_ID_BASE: &str = "urn:rmemimg:schema:";
+
+/// JSON Schemas of the bank's command payloads and query results, keyed by file name
+///
+/// `BankCommand` is externally tagged (`{"Deposit": {...}}`) and amounts are decimal strings,
+/// exactly as the event log and the pipe and HTTP front ends read and write them.
+pub fn schemas() -> Vec<(&'static str, Schema)> {
+    let mut schemas = vec![
+        ("BankCommand.schema.json", schema_for!(BankCommand)),
+        ("Account.schema.json", schema_for!(Account)),
+        ("AccountList.schema.json", schema_for!(Vec<Account>)),
+        ("Amount.schema.json", schema_for!(Amount)),
+        ("LedgerSummary.schema.json", schema_for!(LedgerSummary)),
+    ];
+    for (file_name, schema) in schemas.iter_mut() {
+        schema.insert("$id".to_string(), format!("{}{}", SCHEMA_ID_BASE, file_name).into());
+    }
+    schemas
+}
+
+/// JSON Schema of `BankCommand`, as in `BankCommand.schema.json`, for clients and gateways to
+/// check payloads against before sending them
+pub fn command_schema() -> serde_json::Value {
+    let (_, schema) = schemas().swap_remove(0);
+    schema.to_value()
+}
+
+/// Write every schema in `schemas()` to `dir`, creating it if needed, and return the paths written
+pub fn write_schemas(dir: &Path) -> Result<Vec<PathBuf>, Box<dyn std::error::Error + Send + Sync>> {
+    std::fs::create_dir_all(dir)?;
+    let mut written = Vec::new();
+    for (file_name, schema) in schemas() {
+        let path = dir.join(file_name);
+        std::fs::write(&path, serde_json::to_string_pretty(&schema)? + "\n")?;
+        written.push(path);
+    }
+    Ok(written)
+}
@@ -0,0 +1,43 @@
+use crate::memimg::bank::{Account, AccountId, Amount, Bank};
+use std::collections::BTreeMap;
+
+/// Read-only copy of a bank's open accounts keyed in id order, for callers that page or
+/// list accounts often enough that re-sorting `Bank::accounts` on every call matters
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SortedBank {
+    accounts: BTreeMap<AccountId, Account>,
+}
+
+impl SortedBank {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Open accounts in lexicographic id order
+    pub fn accounts(&self) -> impl Iterator<Item = &Account> {
+        self.accounts.values()
+    }
+
+    pub fn get(&self, id: &str) -> Option<&Account> {
+        self.accounts.get(id)
+    }
+
+    /// Balance of `id`, or `None` if no such open account exists
+    pub fn balance(&self, id: &str) -> Option<Amount> {
+        self.get(id).map(Account::balance)
+    }
+
+    pub fn len(&self) -> usize {
+        self.accounts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.accounts.is_empty()
+    }
+}
+
+impl From<&Bank> for SortedBank {
+    fn from(bank: &Bank) -> Self {
+        Self { accounts: bank.accounts.iter().map(|(id, account)| (id.clone(), account.clone())).collect() }
+    }
+}
@@ -1,17 +1,221 @@
-use crate::memimg::bank::BankCommand;
+use crate::memimg::bank::{Amount, BankCommand};
+use crate::memimg::command_deserializer::CommandDeserializer;
+use crate::memimg::json_event::JsonConverter;
 use crate::memimg::storage::TextConverter;
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
 
 /// JSON converter for BankCommand
-pub struct BankJsonConverter;
+pub type BankJsonConverter = JsonConverter<BankCommand>;
 
-impl TextConverter<BankCommand> for BankJsonConverter {
+impl JsonConverter<BankCommand> {
+    /// Converter writing the variant name into a `tag` field beside the command's fields,
+    /// as in `{"type":"Deposit","account_id":"alice","amount":"10"}`
+    pub fn internally_tagged(tag: &str) -> TaggedBankJsonConverter {
+        TaggedBankJsonConverter { tag: tag.to_string(), content: None }
+    }
+
+    /// Converter writing the variant name into `tag` and the command's fields into `content`,
+    /// as in `{"type":"Deposit","data":{"account_id":"alice","amount":"10"}}`
+    pub fn adjacently_tagged(tag: &str, content: &str) -> TaggedBankJsonConverter {
+        TaggedBankJsonConverter { tag: tag.to_string(), content: Some(content.to_string()) }
+    }
+
+    /// Converter writing amounts as integer minor units with their scale, as in
+    /// `{"Deposit":{"account_id":"alice","amount":{"minor_units":10050,"scale":2}}}` for `100.50`
+    pub fn minor_units() -> MinorUnitsBankJsonConverter {
+        MinorUnitsBankJsonConverter
+    }
+}
+
+/// JSON converter for BankCommand with the variant name in a field; see `BankJsonConverter::internally_tagged`
+///
+/// Lines without the tag field are read as `BankJsonConverter` writes them, so a log can switch
+/// tagging without being migrated.
+pub struct TaggedBankJsonConverter {
+    tag: String,
+    content: Option<String>,
+}
+
+impl TextConverter<BankCommand> for TaggedBankJsonConverter {
+    fn parse(&self, text: &str) -> Result<BankCommand, Box<dyn std::error::Error + Send + Sync>> {
+        let mut value: Value = serde_json::from_str(text)?;
+        let tagged = value.as_object_mut().and_then(|fields| Some((fields.remove(&self.tag)?, fields)));
+        let Some((variant, fields)) = tagged else {
+            return Ok(serde_json::from_value(value)?);
+        };
+        let Value::String(variant) = variant else {
+            return Err(format!("tag field {:?} is not a variant name: {}", self.tag, variant).into());
+        };
+        let payload = match &self.content {
+            Some(content) => fields.remove(content).unwrap_or(Value::Null),
+            None => Value::Object(std::mem::take(fields)),
+        };
+        // A unit variant has no payload, and serde expects it as a bare string
+        let external = if payload.is_null() { Value::String(variant) } else { Value::Object(Map::from_iter([(variant, payload)])) };
+        Ok(serde_json::from_value(external)?)
+    }
+
+    fn format(&self, command: &BankCommand) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+        let (variant, payload) = match serde_json::to_value(command)? {
+            Value::String(variant) => (variant, None),
+            Value::Object(external) => external.into_iter().next().map(|(variant, payload)| (variant, Some(payload))).ok_or("empty command")?,
+            other => return Err(format!("command serialized as {}, not a tagged variant", other).into()),
+        };
+        let mut tagged = Map::new();
+        tagged.insert(self.tag.clone(), Value::String(variant.clone()));
+        match (&self.content, payload) {
+            (_, None) => {}
+            (Some(content), Some(payload)) => {
+                tagged.insert(content.clone(), payload);
+            }
+            (None, Some(Value::Object(fields))) => {
+                if fields.contains_key(&self.tag) {
+                    return Err(format!("{} has a field named like the tag {:?}", variant, self.tag).into());
+                }
+                tagged.extend(fields);
+            }
+            (None, Some(payload)) => return Err(format!("{} holds {}, which internal tagging cannot carry", variant, payload).into()),
+        }
+        Ok(Value::Object(tagged).to_string())
+    }
+}
+
+/// JSON converter for BankCommand with amounts as integer minor units; see `BankJsonConverter::minor_units`
+///
+/// The minor units are the amount times ten to its scale, so parsing restores the exact value,
+/// trailing zeros included. Amounts written as decimal strings are read as well, so an existing
+/// log can switch to this converter without being migrated. Formatting fails for an amount whose
+/// minor units do not fit an `i64`.
+pub struct MinorUnitsBankJsonConverter;
+
+/// Fields of `BankCommand` and its `LedgerEntry`s that hold an `Amount`
+const AMOUNT_FIELDS: [&str; 5] = ["amount", "opening_balance", "balance", "fee", "floor"];
+
+impl MinorUnitsBankJsonConverter {
+    fn to_minor_units(value: &mut Value) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        match value {
+            Value::Object(fields) => {
+                for (name, field) in fields.iter_mut() {
+                    match field {
+                        Value::String(text) if AMOUNT_FIELDS.contains(&name.as_str()) => {
+                            let amount: Amount = text.parse()?;
+                            let minor_units = i64::try_from(amount.mantissa()).map_err(|_| format!("{} has too many minor units", amount))?;
+                            *field = serde_json::json!({"minor_units": minor_units, "scale": amount.scale()});
+                        }
+                        _ => Self::to_minor_units(field)?,
+                    }
+                }
+            }
+            Value::Array(items) => items.iter_mut().try_for_each(Self::to_minor_units)?,
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn from_minor_units(value: &mut Value) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        match value {
+            Value::Object(fields) => {
+                for (name, field) in fields.iter_mut() {
+                    let minor = field.get("minor_units").and_then(Value::as_i64).zip(field.get("scale").and_then(Value::as_u64));
+                    match minor {
+                        Some((minor_units, scale)) if AMOUNT_FIELDS.contains(&name.as_str()) => {
+                            let scale = u32::try_from(scale).map_err(|_| format!("scale {} is out of range", scale))?;
+                            *field = Value::String(Amount::try_new(minor_units, scale)?.to_string());
+                        }
+                        _ => Self::from_minor_units(field)?,
+                    }
+                }
+            }
+            Value::Array(items) => items.iter_mut().try_for_each(Self::from_minor_units)?,
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+impl TextConverter<BankCommand> for MinorUnitsBankJsonConverter {
     fn parse(&self, text: &str) -> Result<BankCommand, Box<dyn std::error::Error + Send + Sync>> {
-        serde_json::from_str(text).map_err(|e| -> Box<dyn std::error::Error + Send + Sync> { Box::new(e) })
+        let mut value: Value = serde_json::from_str(text)?;
+        Self::from_minor_units(&mut value)?;
+        Ok(serde_json::from_value(value)?)
     }
 
     fn format(&self, command: &BankCommand) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
-        // Ensure JSON is on a single line
-        let json = serde_json::to_string(command).map_err(|e| -> Box<dyn std::error::Error + Send + Sync> { Box::new(e) })?;
-        Ok(json.replace('\n', " "))
+        let mut value = serde_json::to_value(command)?;
+        Self::to_minor_units(&mut value)?;
+        Ok(value.to_string())
+    }
+}
+
+/// `field` of a command's logged `fields`; a missing field reads as `null`, which an `Option` accepts
+fn field<T: DeserializeOwned>(fields: &Value, field: &str) -> Result<T, Box<dyn std::error::Error + Send + Sync>> {
+    let value = fields.get(field).cloned().unwrap_or(Value::Null);
+    serde_json::from_value(value).map_err(|e| format!("field {:?}: {}", field, e).into())
+}
+
+/// `CommandDeserializer` with a parser registered for each `BankCommand` variant
+///
+/// It reads what `BankJsonConverter` and `BankJsonConverter::internally_tagged("type")` write.
+pub fn bank_command_deserializer() -> CommandDeserializer<BankCommand> {
+    let mut deserializer = CommandDeserializer::new();
+    type Parser = fn(&Value) -> Result<BankCommand, Box<dyn std::error::Error + Send + Sync>>;
+    let parsers: [(&str, Parser); 15] = [
+        ("CreateAccount", |f| {
+            Ok(BankCommand::CreateAccount { id: field(f, "id")?, name: field(f, "name")?, opening_balance: field(f, "opening_balance")? })
+        }),
+        ("Deposit", |f| Ok(BankCommand::Deposit { account_id: field(f, "account_id")?, amount: field(f, "amount")? })),
+        ("Withdrawal", |f| Ok(BankCommand::Withdrawal { account_id: field(f, "account_id")?, amount: field(f, "amount")? })),
+        ("Transfer", |f| {
+            Ok(BankCommand::Transfer {
+                from_account_id: field(f, "from_account_id")?,
+                to_account_id: field(f, "to_account_id")?,
+                amount: field(f, "amount")?,
+            })
+        }),
+        ("BulkCreateAccounts", |f| Ok(BankCommand::BulkCreateAccounts { accounts: field(f, "accounts")? })),
+        ("CloseAccount", |f| Ok(BankCommand::CloseAccount { id: field(f, "id")? })),
+        ("AddOwner", |f| Ok(BankCommand::AddOwner { account_id: field(f, "account_id")?, owner: field(f, "owner")? })),
+        ("RemoveOwner", |f| Ok(BankCommand::RemoveOwner { account_id: field(f, "account_id")?, owner: field(f, "owner")? })),
+        ("Sweep", |f| {
+            Ok(BankCommand::Sweep {
+                from_account_id: field(f, "from_account_id")?,
+                to_account_id: field(f, "to_account_id")?,
+                amount: field(f, "amount")?,
+            })
+        }),
+        ("ImportLedger", |f| Ok(BankCommand::ImportLedger { entries: field(f, "entries")? })),
+        ("RecordExternalPayment", |f| {
+            Ok(BankCommand::RecordExternalPayment {
+                account_id: field(f, "account_id")?,
+                amount: field(f, "amount")?,
+                gateway: field(f, "gateway")?,
+                gateway_transaction_id: field(f, "gateway_transaction_id")?,
+                payment_method: field(f, "payment_method")?,
+            })
+        }),
+        ("RestoreModificationSeqs", |f| {
+            Ok(BankCommand::RestoreModificationSeqs { modification_seq: field(f, "modification_seq")?, stamps: field(f, "stamps")? })
+        }),
+        ("ScheduleInterest", |f| {
+            Ok(BankCommand::ScheduleInterest {
+                account_id: field(f, "account_id")?,
+                annual_rate: field(f, "annual_rate")?,
+                period: field(f, "period")?,
+                // Optional, as the serde form defaults it
+                negative_balance: field::<Option<_>>(f, "negative_balance")?.unwrap_or_default(),
+                starts_at: field(f, "starts_at")?,
+            })
+        }),
+        ("AccrueInterest", |f| {
+            Ok(BankCommand::AccrueInterest { account_id: field(f, "account_id")?, period_end: field(f, "period_end")?, amount: field(f, "amount")? })
+        }),
+        ("ApplyOverdraftFee", |f| {
+            Ok(BankCommand::ApplyOverdraftFee { account_id: field(f, "account_id")?, fee: field(f, "fee")?, floor: field(f, "floor")? })
+        }),
+    ];
+    for (type_tag, parser) in parsers {
+        deserializer.register(type_tag, parser).expect("each variant is registered once");
     }
+    deserializer
 }
@@ -0,0 +1,74 @@
+use crate::memimg::bank::BankCommand;
+use crate::memimg::clock::Instant;
+use crate::memimg::error::MemImgError;
+use crate::memimg::middleware::CommandMiddleware;
+use std::collections::{HashMap, VecDeque};
+use std::time::Duration;
+
+const WINDOW: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimit {
+    pub max_per_second: u32,
+}
+
+/// Per-account rate limiter using a one-second sliding window of accepted commands
+#[derive(Debug, Default)]
+pub struct AccountCommandThrottler {
+    limits: HashMap<String, RateLimit>,
+    windows: HashMap<String, VecDeque<Instant>>,
+}
+
+impl AccountCommandThrottler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_limit(&mut self, account_id: &str, limit: RateLimit) {
+        self.limits.insert(account_id.to_string(), limit);
+    }
+
+    /// The account a command is charged against: the one initiating it, if any
+    fn account_of(command: &BankCommand) -> Option<&str> {
+        match command {
+            BankCommand::CreateAccount { id, .. } => Some(id.as_str()),
+            BankCommand::Deposit { account_id, .. } => Some(account_id.as_str()),
+            BankCommand::Withdrawal { account_id, .. } => Some(account_id.as_str()),
+            // Gateways, not account holders, originate external payments
+            BankCommand::RecordExternalPayment { .. } => None,
+            // The bank sets and pays interest, and charges fees
+            BankCommand::ScheduleInterest { .. } | BankCommand::AccrueInterest { .. } | BankCommand::ApplyOverdraftFee { .. } => None,
+            BankCommand::Transfer { from_account_id, .. } | BankCommand::Sweep { from_account_id, .. } => Some(from_account_id.as_str()),
+            BankCommand::BulkCreateAccounts { .. } | BankCommand::ImportLedger { .. } | BankCommand::RestoreModificationSeqs { .. } => None,
+            BankCommand::CloseAccount { id } => Some(id.as_str()),
+            BankCommand::AddOwner { account_id, .. } | BankCommand::RemoveOwner { account_id, .. } => Some(account_id.as_str()),
+        }
+    }
+}
+
+impl CommandMiddleware<BankCommand> for AccountCommandThrottler {
+    fn before(&mut self, command: &BankCommand) -> Result<(), MemImgError> {
+        let Some((account_id, limit)) = Self::account_of(command)
+            .and_then(|account_id| self.limits.get(account_id).map(|limit| (account_id, limit)))
+        else {
+            return Ok(());
+        };
+
+        let now = Instant::now();
+        let window = self.windows.entry(account_id.to_string()).or_default();
+        while window.front().is_some_and(|accepted| now.duration_since(*accepted) >= WINDOW) {
+            window.pop_front();
+        }
+
+        if window.len() >= limit.max_per_second as usize {
+            let oldest = window.front().copied().unwrap_or(now);
+            return Err(MemImgError::RateLimitExceeded {
+                account_id: account_id.to_string(),
+                retry_after: WINDOW.saturating_sub(now.duration_since(oldest)),
+            });
+        }
+
+        window.push_back(now);
+        Ok(())
+    }
+}
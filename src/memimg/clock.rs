@@ -0,0 +1,5 @@
+// `std::time::Instant::now` panics on wasm32-unknown-unknown; `web_time` reads `performance.now()` there
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+pub(crate) use std::time::Instant;
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+pub(crate) use web_time::Instant;
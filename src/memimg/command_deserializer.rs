@@ -0,0 +1,83 @@
+use crate::memimg::command_registry::DuplicateCommandName;
+use crate::memimg::error::MemImgError;
+use crate::memimg::storage::TextConverter;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Builds one command from the fields logged under its type tag
+pub type DeserializeCommand<C> = dyn Fn(&Value) -> Result<C, Box<dyn std::error::Error + Send + Sync>> + Send + Sync;
+
+/// Parses logged commands by dispatching on their serde tag to parsers registered one type at a
+/// time, so replay code does not have to match every variant of a command enum
+///
+/// An event is read either internally tagged, `{"type":"Deposit","account_id":"alice",...}`, or
+/// as serde writes an enum, `{"Deposit":{"account_id":"alice",...}}`; its parser is given the
+/// fields without the tag. Commands are formatted the standard serde way. A tag with no parser
+/// fails to parse with `MemImgError::UnknownCommand`, so replay treats it as the storage's
+/// `ReplayPolicy` says.
+pub struct CommandDeserializer<C> {
+    parsers: HashMap<String, Box<DeserializeCommand<C>>>,
+}
+
+impl<C> Default for CommandDeserializer<C> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<C> CommandDeserializer<C> {
+    pub fn new() -> Self {
+        Self { parsers: HashMap::new() }
+    }
+
+    /// Parse events tagged `type_tag` with `parser`; the tag must not be taken yet
+    pub fn register(
+        &mut self,
+        type_tag: &str,
+        parser: impl Fn(&Value) -> Result<C, Box<dyn std::error::Error + Send + Sync>> + Send + Sync + 'static,
+    ) -> Result<(), DuplicateCommandName> {
+        if self.parsers.contains_key(type_tag) {
+            return Err(DuplicateCommandName(type_tag.to_string()));
+        }
+        self.parsers.insert(type_tag.to_string(), Box::new(parser));
+        Ok(())
+    }
+
+    /// Registered type tags, sorted
+    pub fn type_tags(&self) -> Vec<&str> {
+        let mut tags: Vec<_> = self.parsers.keys().map(String::as_str).collect();
+        tags.sort_unstable();
+        tags
+    }
+
+    /// Build the command tagged `type_tag` from its `fields`
+    ///
+    /// Fails with `UnknownCommand` for an unregistered tag and `InvalidCommandPayload` when the
+    /// parser rejects `fields`.
+    pub fn deserialize(&self, type_tag: &str, fields: &Value) -> Result<C, MemImgError> {
+        let parser = self.parsers.get(type_tag).ok_or_else(|| MemImgError::UnknownCommand(type_tag.to_string()))?;
+        parser(fields).map_err(|e| MemImgError::InvalidCommandPayload { command: type_tag.to_string(), message: e.to_string() })
+    }
+}
+
+impl<C: Serialize> TextConverter<C> for CommandDeserializer<C> {
+    fn parse(&self, text: &str) -> Result<C, Box<dyn std::error::Error + Send + Sync>> {
+        let (type_tag, fields) = match serde_json::from_str(text)? {
+            Value::Object(mut fields) => match fields.remove("type") {
+                Some(Value::String(type_tag)) => (type_tag, Value::Object(fields)),
+                Some(tag) => return Err(format!("\"type\" is not a command tag: {}", tag).into()),
+                None if fields.len() == 1 => fields.into_iter().next().unwrap(),
+                None => return Err("event has no \"type\" field and is not a single tagged command".into()),
+            },
+            Value::String(type_tag) => (type_tag, Value::Null),
+            other => return Err(format!("event is not a tagged command: {}", other).into()),
+        };
+        Ok(self.deserialize(&type_tag, &fields)?)
+    }
+
+    fn format(&self, command: &C) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+        // serde_json::to_string never emits newlines, so each command stays on one line
+        Ok(serde_json::to_string(command)?)
+    }
+}
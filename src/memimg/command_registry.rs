@@ -0,0 +1,198 @@
+use crate::memimg::error::MemImgError;
+use crate::memimg::processor::{Command, CommandReceipt, MemImgProcessor};
+use crate::memimg::storage::{EventStorage, TextConverter};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use std::any::Any;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Arc;
+use thiserror::Error;
+
+/// `CommandRegistry::register` was given a name already taken by another command type
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("A command is already registered as {0:?}")]
+pub struct DuplicateCommandName(pub String);
+
+/// Type-erased registered command
+trait ErasedCommand<S>: fmt::Debug + Send + Sync {
+    fn apply_to(&self, system: &mut S) -> Result<(), Box<dyn std::error::Error + Send + Sync>>;
+
+    fn explain(&self, system: &S) -> String;
+
+    fn payload(&self) -> Result<Value, serde_json::Error>;
+
+    fn as_any(&self) -> &dyn Any;
+}
+
+impl<S, T> ErasedCommand<S> for T
+where
+    S: Clone,
+    T: Command<System = S> + Serialize + Send + Sync + 'static,
+{
+    fn apply_to(&self, system: &mut S) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        Command::apply_to(self, system)
+    }
+
+    fn explain(&self, system: &S) -> String {
+        Command::explain(self, system)
+    }
+
+    fn payload(&self) -> Result<Value, serde_json::Error> {
+        serde_json::to_value(self)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// A command of any type registered in a `CommandRegistry`, tagged with its registered name
+///
+/// Registered commands are applied and logged as they are: `Command::resolve` is not consulted.
+pub struct DynCommand<S> {
+    tag: Arc<str>,
+    command: Arc<dyn ErasedCommand<S>>,
+}
+
+impl<S> DynCommand<S> {
+    /// Name the command's type was registered under
+    pub fn tag(&self) -> &str {
+        &self.tag
+    }
+
+    /// The command itself, if it is a `T`
+    pub fn downcast_ref<T: 'static>(&self) -> Option<&T> {
+        self.command.as_any().downcast_ref()
+    }
+}
+
+impl<S> Clone for DynCommand<S> {
+    fn clone(&self) -> Self {
+        Self { tag: self.tag.clone(), command: self.command.clone() }
+    }
+}
+
+impl<S> fmt::Debug for DynCommand<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {:?}", self.tag, self.command)
+    }
+}
+
+impl<S: Clone> Command for DynCommand<S> {
+    type System = S;
+
+    fn apply_to(&self, system: &mut S) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        self.command.apply_to(system)
+    }
+
+    fn explain(&self, system: &S) -> String {
+        self.command.explain(system)
+    }
+}
+
+type ParseCommand<S> = dyn Fn(Value) -> Result<Arc<dyn ErasedCommand<S>>, serde_json::Error> + Send + Sync;
+
+/// Independent command types over `S` executed by name from JSON, without a central enum
+///
+/// The registry is also the `TextConverter` for its `DynCommand`s: each event is stored as
+/// `{"type": name, "payload": command}` and parsed back through the registry on replay. An
+/// unregistered name fails to parse, so replay treats it as the storage's `ReplayPolicy` says.
+/// Clones share the registrations made so far.
+pub struct CommandRegistry<S> {
+    commands: Arc<BTreeMap<String, Arc<ParseCommand<S>>>>,
+}
+
+impl<S> Clone for CommandRegistry<S> {
+    fn clone(&self) -> Self {
+        Self { commands: self.commands.clone() }
+    }
+}
+
+impl<S: Clone> Default for CommandRegistry<S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S: Clone> CommandRegistry<S> {
+    pub fn new() -> Self {
+        Self { commands: Arc::new(BTreeMap::new()) }
+    }
+
+    /// Register `T` under `name`, which must not be taken yet
+    pub fn register<T>(mut self, name: &str) -> Result<Self, DuplicateCommandName>
+    where
+        T: Command<System = S> + Serialize + DeserializeOwned + Send + Sync + 'static,
+    {
+        if self.commands.contains_key(name) {
+            return Err(DuplicateCommandName(name.to_string()));
+        }
+        let parse = |payload: Value| -> Result<Arc<dyn ErasedCommand<S>>, serde_json::Error> {
+            Ok(Arc::new(serde_json::from_value::<T>(payload)?))
+        };
+        Arc::make_mut(&mut self.commands).insert(name.to_string(), Arc::new(parse));
+        Ok(self)
+    }
+
+    /// Registered command names, sorted
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.commands.keys().map(String::as_str)
+    }
+
+    /// Build the command registered as `name` from its JSON `payload`
+    ///
+    /// Fails with `UnknownCommand` for an unregistered name and `InvalidCommandPayload` when
+    /// `payload` does not deserialize into the command.
+    pub fn command(&self, name: &str, payload: Value) -> Result<DynCommand<S>, MemImgError> {
+        let (tag, parse) = self
+            .commands
+            .get_key_value(name)
+            .ok_or_else(|| MemImgError::UnknownCommand(name.to_string()))?;
+        let command = parse(payload).map_err(|e| MemImgError::InvalidCommandPayload {
+            command: name.to_string(),
+            message: e.to_string(),
+        })?;
+        Ok(DynCommand { tag: Arc::from(tag.as_str()), command })
+    }
+
+    /// Build the command registered as `name` from `payload` and execute it on `processor`
+    pub fn execute_json<E>(
+        &self,
+        processor: &mut MemImgProcessor<S, DynCommand<S>, E>,
+        name: &str,
+        payload: Value,
+    ) -> Result<CommandReceipt, MemImgError>
+    where
+        E: EventStorage<Event = DynCommand<S>>,
+    {
+        processor.execute_command(self.command(name, payload)?)
+    }
+}
+
+/// Stored form of a `DynCommand`, with the name first so logs read naturally
+#[derive(Serialize)]
+struct StoredCommand<'a> {
+    #[serde(rename = "type")]
+    command_type: &'a str,
+    payload: Value,
+}
+
+impl<S: Clone> TextConverter<DynCommand<S>> for CommandRegistry<S> {
+    fn parse(&self, text: &str) -> Result<DynCommand<S>, Box<dyn std::error::Error + Send + Sync>> {
+        let mut event: serde_json::Map<String, Value> = serde_json::from_str(text)?;
+        let tag = match event.remove("type") {
+            Some(Value::String(tag)) => tag,
+            _ => return Err("event has no \"type\" naming its command".into()),
+        };
+        let payload = event.remove("payload").ok_or("event has no \"payload\"")?;
+        Ok(self.command(&tag, payload)?)
+    }
+
+    fn format(&self, command: &DynCommand<S>) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+        // serde_json::to_string never emits newlines, so each command stays on one line
+        let event = StoredCommand { command_type: command.tag(), payload: command.command.payload()? };
+        Ok(serde_json::to_string(&event)?)
+    }
+}
@@ -0,0 +1,359 @@
+use crate::memimg::error::{FailureOutcome, MemImgError};
+use crate::memimg::event_id::EventId;
+use crate::memimg::processor::{Command, CommitStrategy, MemImgProcessor, ReplayMetrics};
+use crate::memimg::snapshot::{CompactionResult, SnapshotFormat};
+use crate::memimg::storage::{EventStorage, ReplayPolicy, TextConverter};
+use crate::memimg::text_file_storage::{Durability, TextFileEventStorage};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use thiserror::Error;
+
+/// Prefix of the environment variables that override config keys, as in `MEMIMG_FLUSH_POLICY`
+pub const ENV_PREFIX: &str = "MEMIMG_";
+
+/// When buffered events are flushed and synced besides explicit `flush` calls
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FlushPolicy {
+    /// Only on `flush`, when the buffer fills, or when the storage drops: `manual`
+    #[default]
+    Manual,
+    /// From a background thread every interval: `interval_ms:250`
+    Interval(Duration),
+    /// After every so many appends: `every_n:100`
+    EveryN(u64),
+}
+
+/// Storage and processor options, read from a TOML or JSON file and `MEMIMG_*` environment variables
+///
+/// Every key is optional and defaults to the matching builder's default. Enumerated options are
+/// strings: `durability` is `every_event` or `buffered`; `flush_policy` is `manual`,
+/// `interval_ms:N` or `every_n:N`; `replay_policy` is `strict`, `lenient` or `budgeted:N`; and
+/// `commit_strategy` is `apply_then_append` or `append_then_apply`.
+#[derive(Debug, Clone, PartialEq, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MemImgConfig {
+    #[serde(deserialize_with = "config_value")]
+    pub durability: Durability,
+    #[serde(deserialize_with = "config_value")]
+    pub flush_policy: FlushPolicy,
+    #[serde(deserialize_with = "config_value")]
+    pub replay_policy: ReplayPolicy,
+    #[serde(deserialize_with = "config_value")]
+    pub commit_strategy: CommitStrategy,
+    /// How long to wait for another writer to release the log; 0 fails at once
+    pub lock_timeout_ms: u64,
+    /// Where `checkpoint_if_due` writes snapshots
+    pub snapshot_path: Option<PathBuf>,
+    /// Events in the log that make a checkpoint due; `None` never checkpoints
+    pub checkpoint_interval: Option<u64>,
+}
+
+/// Why a config could not be loaded
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("Cannot read config {path}: {source}")]
+    Read { path: String, source: std::io::Error },
+
+    #[error("Invalid config {path}: {message}")]
+    Parse { path: String, message: String },
+
+    #[error("Invalid {key} {value:?}: {reason}")]
+    InvalidValue { key: String, value: String, reason: String },
+
+    #[error("Inconsistent {}: {message}", keys.join(" and "))]
+    Inconsistent { keys: Vec<&'static str>, message: String },
+}
+
+/// An option spelled as a string in config files and environment variables
+trait ConfigValue: Sized {
+    fn parse(text: &str) -> Result<Self, String>;
+}
+
+/// `N` in `name:N`, for the parameterized options
+fn parse_count(text: &str, name: &str) -> Result<u64, String> {
+    text.parse().map_err(|_| format!("expected {}:N with N a whole number", name))
+}
+
+impl ConfigValue for Durability {
+    fn parse(text: &str) -> Result<Self, String> {
+        match text {
+            "every_event" => Ok(Durability::EveryEvent),
+            "buffered" => Ok(Durability::Buffered),
+            _ => Err("expected every_event or buffered".to_string()),
+        }
+    }
+}
+
+impl ConfigValue for FlushPolicy {
+    fn parse(text: &str) -> Result<Self, String> {
+        match text.split_once(':') {
+            None if text == "manual" => Ok(FlushPolicy::Manual),
+            Some(("interval_ms", millis)) => match parse_count(millis, "interval_ms")? {
+                0 => Err("the flush interval must be positive".to_string()),
+                millis => Ok(FlushPolicy::Interval(Duration::from_millis(millis))),
+            },
+            Some(("every_n", events)) => match parse_count(events, "every_n")? {
+                0 => Err("the flush batch must hold at least one event".to_string()),
+                events => Ok(FlushPolicy::EveryN(events)),
+            },
+            _ => Err("expected manual, interval_ms:N or every_n:N".to_string()),
+        }
+    }
+}
+
+impl ConfigValue for ReplayPolicy {
+    fn parse(text: &str) -> Result<Self, String> {
+        match text.split_once(':') {
+            None if text == "strict" => Ok(ReplayPolicy::Strict),
+            None if text == "lenient" => Ok(ReplayPolicy::Lenient),
+            Some(("budgeted", budget)) => Ok(ReplayPolicy::Budgeted {
+                error_budget: parse_count(budget, "budgeted")? as usize,
+            }),
+            _ => Err("expected strict, lenient or budgeted:N".to_string()),
+        }
+    }
+}
+
+impl ConfigValue for CommitStrategy {
+    fn parse(text: &str) -> Result<Self, String> {
+        match text {
+            "apply_then_append" => Ok(CommitStrategy::ApplyThenAppend),
+            "append_then_apply" => Ok(CommitStrategy::AppendThenApply),
+            _ => Err("expected apply_then_append or append_then_apply".to_string()),
+        }
+    }
+}
+
+impl ConfigValue for u64 {
+    fn parse(text: &str) -> Result<Self, String> {
+        text.parse().map_err(|_| "expected a whole number".to_string())
+    }
+}
+
+fn config_value<'de, T: ConfigValue, D: Deserializer<'de>>(deserializer: D) -> Result<T, D::Error> {
+    let text = String::deserialize(deserializer)?;
+    T::parse(&text).map_err(|reason| serde::de::Error::custom(format!("{:?}: {}", text, reason)))
+}
+
+fn invalid(key: &str, value: &str) -> impl FnOnce(String) -> ConfigError {
+    let (key, value) = (key.to_string(), value.to_string());
+    move |reason| ConfigError::InvalidValue { key, value, reason }
+}
+
+impl MemImgConfig {
+    /// Read `path`, as TOML if it ends in `.toml` and JSON otherwise, apply the process's
+    /// `MEMIMG_*` environment variables over it, and validate the result
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, ConfigError> {
+        Self::from_file(path)?.with_overrides(std::env::vars())?.validated()
+    }
+
+    /// Read `path` without environment overrides or validation
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, ConfigError> {
+        let path = path.as_ref();
+        let display = path.to_string_lossy().to_string();
+        let text = std::fs::read_to_string(path).map_err(|source| ConfigError::Read { path: display.clone(), source })?;
+        let parsed = if path.extension().is_some_and(|extension| extension == "toml") {
+            toml::from_str(&text).map_err(|e| e.to_string())
+        } else {
+            serde_json::from_str(&text).map_err(|e| e.to_string())
+        };
+        parsed.map_err(|message| ConfigError::Parse { path: display, message: message.trim_end().to_string() })
+    }
+
+    /// Override keys from `vars` named `MEMIMG_` and the key in upper case; other variables are ignored
+    ///
+    /// An empty `MEMIMG_SNAPSHOT_PATH` or `MEMIMG_CHECKPOINT_INTERVAL` unsets the key.
+    pub fn with_overrides<I, K, V>(mut self, vars: I) -> Result<Self, ConfigError>
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: AsRef<str>,
+        V: AsRef<str>,
+    {
+        for (name, value) in vars {
+            let Some(key) = name.as_ref().strip_prefix(ENV_PREFIX) else {
+                continue;
+            };
+            let key = key.to_ascii_lowercase();
+            let value = value.as_ref();
+            let invalid = invalid(name.as_ref(), value);
+            match key.as_str() {
+                "durability" => self.durability = ConfigValue::parse(value).map_err(invalid)?,
+                "flush_policy" => self.flush_policy = ConfigValue::parse(value).map_err(invalid)?,
+                "replay_policy" => self.replay_policy = ConfigValue::parse(value).map_err(invalid)?,
+                "commit_strategy" => self.commit_strategy = ConfigValue::parse(value).map_err(invalid)?,
+                "lock_timeout_ms" => self.lock_timeout_ms = ConfigValue::parse(value).map_err(invalid)?,
+                "snapshot_path" => self.snapshot_path = Some(PathBuf::from(value)).filter(|_| !value.is_empty()),
+                "checkpoint_interval" if value.is_empty() => self.checkpoint_interval = None,
+                "checkpoint_interval" => self.checkpoint_interval = Some(ConfigValue::parse(value).map_err(invalid)?),
+                _ => {}
+            }
+        }
+        Ok(self)
+    }
+
+    /// This config, if no two of its options contradict each other
+    pub fn validated(self) -> Result<Self, ConfigError> {
+        if self.durability == Durability::EveryEvent && self.flush_policy != FlushPolicy::Manual {
+            return Err(ConfigError::Inconsistent {
+                keys: vec!["durability", "flush_policy"],
+                message: "every_event durability writes each event through, so a flush policy needs durability = buffered".to_string(),
+            });
+        }
+        if self.checkpoint_interval == Some(0) {
+            return Err(ConfigError::InvalidValue {
+                key: "checkpoint_interval".to_string(),
+                value: "0".to_string(),
+                reason: "a checkpoint needs at least one event".to_string(),
+            });
+        }
+        if self.checkpoint_interval.is_some() && self.snapshot_path.is_none() {
+            return Err(ConfigError::Inconsistent {
+                keys: vec!["checkpoint_interval", "snapshot_path"],
+                message: "checkpointing is enabled but there is no snapshot_path to write to".to_string(),
+            });
+        }
+        Ok(self)
+    }
+
+    /// Open the log at `path` with this config's lock timeout, then `apply` the rest
+    pub fn open_storage<E, C, P>(&self, path: P, converter: C) -> Result<TextFileEventStorage<E, C>, Box<dyn std::error::Error + Send + Sync>>
+    where
+        C: TextConverter<E>,
+        P: AsRef<Path>,
+    {
+        let storage = TextFileEventStorage::new_with_lock_timeout(path, converter, Duration::from_millis(self.lock_timeout_ms))?;
+        Ok(self.apply(storage))
+    }
+
+    /// Set `storage`'s durability, flush policy and replay policy from this config
+    pub fn apply<E, C>(&self, storage: TextFileEventStorage<E, C>) -> TextFileEventStorage<E, C>
+    where
+        C: TextConverter<E>,
+    {
+        let storage = storage.with_durability(self.durability).with_replay_policy(self.replay_policy);
+        match self.flush_policy {
+            FlushPolicy::Manual => storage,
+            FlushPolicy::Interval(interval) => storage.with_auto_flush(interval),
+            FlushPolicy::EveryN(events) => storage.with_flush_every(events),
+        }
+    }
+
+    /// Open a processor over `event_storage` with this config's commit strategy, resuming from the
+    /// snapshot at `snapshot_path` if there is one and starting from `system` otherwise, along with
+    /// figures on the replay
+    ///
+    /// A checkpoint interrupted after its snapshot was renamed into place but before the log was
+    /// compacted is finished here, so the events the snapshot covers are not replayed twice.
+    pub fn open_processor<S, C, E>(&self, system: S, mut event_storage: Box<E>, format: &SnapshotFormat) -> Result<(MemImgProcessor<S, C, E>, ReplayMetrics), MemImgError>
+    where
+        S: Clone + DeserializeOwned,
+        C: Command<System = S>,
+        E: EventStorage<Event = C>,
+    {
+        let failure = |e, context: &str| MemImgError::SystemFailure(FailureOutcome::new(e, context, "EventStorage"));
+        let (system, log_base) = match self.snapshot_path.as_ref().filter(|path| path.exists()) {
+            Some(path) => {
+                let file = std::fs::File::open(path).map_err(|e| failure(Box::new(e), "opening snapshot for"))?;
+                let snapshot = format.read(std::io::BufReader::new(file)).map_err(|e| failure(e, "reading snapshot for"))?;
+                finish_compaction(path, snapshot.event_count, event_storage.as_mut())?;
+                (snapshot.state, snapshot.event_count)
+            }
+            None => (system, 0),
+        };
+        MemImgProcessor::open(system, log_base, event_storage, self.commit_strategy)
+    }
+
+    /// Checkpoint `processor` to `snapshot_path` once its log holds `checkpoint_interval` events
+    ///
+    /// The snapshot is written to `<snapshot>.tmp`, synced, and renamed over the old one before
+    /// the log is compacted, so a crash or failed write leaves the old snapshot and the full log.
+    /// Until the log is compacted, a `<snapshot>.compacting` file records how many of its events
+    /// the new snapshot covers, for `open_processor` to drop them if compaction never happened.
+    pub fn checkpoint_if_due<S, C, E>(&self, processor: &mut MemImgProcessor<S, C, E>, format: &SnapshotFormat) -> Result<Option<CompactionResult>, MemImgError>
+    where
+        S: Clone + Serialize,
+        C: Command<System = S>,
+        E: EventStorage<Event = C>,
+    {
+        let (Some(interval), Some(snapshot_path)) = (self.checkpoint_interval, &self.snapshot_path) else {
+            return Ok(None);
+        };
+        if processor.events_since_snapshot() < interval {
+            return Ok(None);
+        }
+        let pending = PendingCompaction { event_count: processor.event_version().as_u64(), covered: processor.events_since_snapshot() };
+        let marker = with_suffix(snapshot_path, ".compacting");
+        let result = processor.checkpoint_and_compact_with(format, |snapshot| replace_snapshot(snapshot_path, &marker, pending, snapshot))?;
+        std::fs::remove_file(&marker)
+            .map_err(|e| MemImgError::SystemFailure(FailureOutcome::new(Box::new(e), "clearing compaction marker for", "EventStorage")))?;
+        Ok(Some(result))
+    }
+}
+
+/// `path` with `suffix` appended to its file name, as in `bank.snapshot.tmp`
+pub(crate) fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+/// Contents of a `.compacting` marker: the snapshot's event count, and how many leading events of
+/// the log it covers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct PendingCompaction {
+    event_count: u64,
+    covered: u64,
+}
+
+/// Write `snapshot` over the file at `path` through a synced temporary file and a rename, with
+/// `marker` recording `pending` before the rename
+fn replace_snapshot(path: &Path, marker: &Path, pending: PendingCompaction, snapshot: &[u8]) -> std::io::Result<()> {
+    let temp_path = with_suffix(path, ".tmp");
+    let written = File::create(&temp_path).and_then(|mut temp| temp.write_all(snapshot).and_then(|_| temp.sync_all()));
+    if let Err(e) = written {
+        let _ = std::fs::remove_file(&temp_path);
+        return Err(e);
+    }
+    let mut marker_file = File::create(marker)?;
+    write!(marker_file, "{} {}", pending.event_count, pending.covered)?;
+    marker_file.sync_all()?;
+    sync_parent(path)?;
+    std::fs::rename(&temp_path, path)?;
+    sync_parent(path)
+}
+
+/// Sync the directory holding `path`, making renames and new files in it durable
+fn sync_parent(path: &Path) -> std::io::Result<()> {
+    #[cfg(unix)]
+    if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        File::open(parent)?.sync_all()?;
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+    Ok(())
+}
+
+/// Drop the log events a snapshot of `event_count` events covers if a checkpoint's marker says
+/// they were never compacted away, then remove the marker
+///
+/// A marker left by a snapshot that never replaced the one at `snapshot_path` is just removed.
+fn finish_compaction<E: EventStorage + ?Sized>(snapshot_path: &Path, event_count: u64, event_storage: &mut E) -> Result<(), MemImgError> {
+    let failure = |e, context: &str| MemImgError::SystemFailure(FailureOutcome::new(e, context, "EventStorage"));
+    let marker = with_suffix(snapshot_path, ".compacting");
+    let Ok(text) = std::fs::read_to_string(&marker) else {
+        return Ok(());
+    };
+    let mut fields = text.split_whitespace().map(str::parse::<u64>);
+    if let (Some(Ok(marked)), Some(Ok(covered))) = (fields.next(), fields.next()) {
+        if marked == event_count {
+            // Compaction may have finished before the crash, leaving fewer events than marked
+            let logged = event_storage.version().map_err(|e| failure(e, "counting events for"))?;
+            event_storage.truncate_before(EventId(covered.min(logged) + 1)).map_err(|e| failure(e, "compacting"))?;
+        }
+    }
+    std::fs::remove_file(&marker).map_err(|e| failure(Box::new(e), "clearing compaction marker for"))
+}
@@ -0,0 +1,121 @@
+use crate::memimg::error::StorageOp;
+use crate::memimg::storage::{ReplayPolicy, TextConverter};
+use crate::memimg::stream_storage::{is_event_line, storage_error, IntentFraming};
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader, Read};
+use std::marker::PhantomData;
+
+/// Most events a batch reserves room for up front, however many are asked for
+const PREALLOCATED_BATCH: usize = 1024;
+
+/// Incremental reader over an event log, for consumers that process it in bounded batches
+///
+/// `position` is a byte offset into the (decompressed) log; persist it and pass it to
+/// `TextFileEventStorage::open_cursor_at` to resume after a restart. Under write-ahead intents,
+/// events are returned only once committed, so the cursor never hands out an event a rollback
+/// later cuts off the log.
+pub struct LogCursor<'a, E, C>
+where
+    C: TextConverter<E>,
+{
+    file_path: String,
+    reader: BufReader<Box<dyn Read>>,
+    converter: &'a C,
+    replay_policy: ReplayPolicy,
+    position: u64,
+    /// Unparseable lines skipped so far, charged against a `Budgeted` policy
+    skipped: usize,
+    /// Bytes of a line whose terminating newline has not been written yet
+    pending: Vec<u8>,
+    /// Offset just past the last complete line read
+    read: u64,
+    /// Events read but not committed yet, each with the offset just past its line
+    framing: IntentFraming<(E, u64)>,
+    /// Committed events not returned yet
+    ready: VecDeque<(E, u64)>,
+    _phantom: PhantomData<fn() -> E>,
+}
+
+impl<'a, E, C> LogCursor<'a, E, C>
+where
+    C: TextConverter<E>,
+{
+    /// Cursor reading from `position`; `intents_declared` tells whether the log opens its
+    /// write-ahead intents before it
+    pub(crate) fn new(file_path: &str, reader: Box<dyn Read>, converter: &'a C, replay_policy: ReplayPolicy, position: u64, intents_declared: bool) -> Self {
+        Self {
+            file_path: file_path.to_string(),
+            reader: BufReader::new(reader),
+            converter,
+            replay_policy,
+            position,
+            skipped: 0,
+            pending: Vec::new(),
+            read: position,
+            framing: IntentFraming::new(intents_declared),
+            ready: VecDeque::new(),
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Read up to `n` events; fewer means the cursor reached the current end of the log
+    ///
+    /// An unterminated last line may be an append in progress, so it is left for a later call, as
+    /// are events still waiting for their commit record.
+    pub fn next_batch(&mut self, n: usize) -> Result<Vec<E>, Box<dyn std::error::Error + Send + Sync>> {
+        let mut batch = Vec::with_capacity(n.min(PREALLOCATED_BATCH));
+        loop {
+            while batch.len() < n {
+                let Some((event, end)) = self.ready.pop_front() else {
+                    break;
+                };
+                batch.push(event);
+                self.position = end;
+            }
+            // Nothing read is left to return, so resuming starts after all of it
+            if self.framing.held().is_empty() && self.ready.is_empty() {
+                self.position = self.read;
+            }
+            if batch.len() == n {
+                break;
+            }
+
+            self.reader
+                .read_until(b'\n', &mut self.pending)
+                .map_err(storage_error(&self.file_path, StorageOp::Read))?;
+            if self.pending.last() != Some(&b'\n') {
+                break;
+            }
+            let line = std::mem::take(&mut self.pending);
+            let end = self.read + line.len() as u64;
+
+            let text = String::from_utf8_lossy(&line);
+            let text = text.trim_end_matches(['\n', '\r']);
+            if let Some(committed) = self.framing.record(text) {
+                self.ready.extend(committed);
+            } else if is_event_line(text) {
+                match self.converter.parse(text) {
+                    Ok(event) => self.ready.extend(self.framing.hold((event, end))),
+                    Err(_) if self.replay_policy == ReplayPolicy::Lenient => {}
+                    Err(_) if matches!(self.replay_policy, ReplayPolicy::Budgeted { error_budget } if self.skipped < error_budget) => {
+                        self.skipped += 1;
+                    }
+                    // The position stays before the bad line, so resuming reports it again
+                    Err(e) => return Err(e),
+                }
+            }
+            self.read = end;
+        }
+        Ok(batch)
+    }
+
+    /// Byte offset just past the last event returned
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Whether the lines read so far opened write-ahead intents
+    pub(crate) fn intents_declared(&self) -> bool {
+        self.framing.declared()
+    }
+}
@@ -0,0 +1,108 @@
+use serde_json::{json, Value};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Upper bound on the rendered command payload included in a dump
+pub const MAX_DUMP_PAYLOAD_CHARS: usize = 4096;
+
+type Redactor = Box<dyn Fn(&str) -> String + Send>;
+type Fingerprinter<S> = Box<dyn Fn(&S) -> String + Send>;
+type Snapshotter<S> = Box<dyn Fn(&S) -> Value + Send>;
+
+/// Processor facts captured when a dump is written
+#[derive(Debug, Clone)]
+pub struct DumpContext<'a> {
+    pub command_type: &'a str,
+    pub command_debug: String,
+    pub error_chain: Vec<String>,
+    pub event_count: u64,
+    pub commands_executed: u64,
+    pub commands_failed: u64,
+}
+
+/// Writes a forensic summary when a system failure poisons the processor.
+///
+/// Dumping is best-effort: I/O errors and panics inside user-supplied hooks are swallowed.
+pub struct FailureDumper<S> {
+    path: PathBuf,
+    redactor: Option<Redactor>,
+    fingerprint: Option<Fingerprinter<S>>,
+    snapshot: Option<Snapshotter<S>>,
+}
+
+impl<S> FailureDumper<S> {
+    /// Dump to `path`; the command payload is redacted unless a redactor is configured
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            redactor: None,
+            fingerprint: None,
+            snapshot: None,
+        }
+    }
+
+    /// Render the failing command's `Debug` payload through `redactor` instead of omitting it
+    pub fn with_redactor(mut self, redactor: impl Fn(&str) -> String + Send + 'static) -> Self {
+        self.redactor = Some(Box::new(redactor));
+        self
+    }
+
+    /// Include a state fingerprint computed by `fingerprint`
+    pub fn with_fingerprint(mut self, fingerprint: impl Fn(&S) -> String + Send + 'static) -> Self {
+        self.fingerprint = Some(Box::new(fingerprint));
+        self
+    }
+
+    /// Opt in to a full serialized snapshot of the system
+    pub fn with_full_snapshot(mut self, snapshot: impl Fn(&S) -> Value + Send + 'static) -> Self {
+        self.snapshot = Some(Box::new(snapshot));
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Write the dump, ignoring any failure along the way
+    pub fn dump(&self, system: &S, context: &DumpContext<'_>) {
+        let _ = catch_unwind(AssertUnwindSafe(|| {
+            let document = self.render(system, context);
+            if let Ok(text) = serde_json::to_string_pretty(&document) {
+                let _ = std::fs::write(&self.path, text);
+            }
+        }));
+    }
+
+    fn render(&self, system: &S, context: &DumpContext<'_>) -> Value {
+        let failed_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let command = match &self.redactor {
+            Some(redactor) => {
+                let mut payload = redactor(&context.command_debug);
+                if payload.len() > MAX_DUMP_PAYLOAD_CHARS {
+                    let end = (0..=MAX_DUMP_PAYLOAD_CHARS).rev().find(|i| payload.is_char_boundary(*i)).unwrap_or(0);
+                    payload.truncate(end);
+                }
+                payload
+            }
+            None => "<redacted>".to_string(),
+        };
+
+        json!({
+            "failed_at": failed_at,
+            "command_type": context.command_type,
+            "command": command,
+            "error_chain": context.error_chain,
+            "event_count": context.event_count,
+            "stats": {
+                "commands_executed": context.commands_executed,
+                "commands_failed": context.commands_failed,
+            },
+            "fingerprint": self.fingerprint.as_ref().map(|f| f(system)),
+            "snapshot": self.snapshot.as_ref().map(|f| f(system)),
+        })
+    }
+}
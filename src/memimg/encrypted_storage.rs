@@ -0,0 +1,186 @@
+use crate::memimg::error::StorageOp;
+use crate::memimg::storage::{EventStorage, TextConverter};
+use crate::memimg::stream_storage::storage_error;
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use hkdf::Hkdf;
+use sha2::Sha256;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::marker::PhantomData;
+use std::path::Path;
+
+const KEY_INFO: &[u8] = b"rmemimg event key";
+
+/// File-based event storage encrypting each line with a key derived from the event's sequence number.
+///
+/// Per-event key = HKDF-SHA256(master, salt = big-endian sequence number), so learning one event key
+/// reveals nothing about the others. Lines are `<base64(nonce)>.<base64(ciphertext+tag)>`.
+pub struct HkdfEncryptedStorage<E, C>
+where
+    C: TextConverter<E>,
+{
+    file_path: String,
+    converter: C,
+    master_key: [u8; 32],
+    next_sequence: u64,
+    writer: Option<File>,
+    _phantom: PhantomData<fn() -> E>,
+}
+
+impl<E, C> HkdfEncryptedStorage<E, C>
+where
+    C: TextConverter<E>,
+{
+    pub fn new<P: AsRef<Path>>(path: P, converter: C, master_key: [u8; 32]) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let file_path = path.as_ref().to_string_lossy().to_string();
+
+        if let Some(parent) = path.as_ref().parent() {
+            let parent_path = parent.to_string_lossy();
+            std::fs::create_dir_all(parent).map_err(storage_error(&parent_path, StorageOp::CreateDir))?;
+        }
+
+        if !path.as_ref().exists() {
+            File::create(&path).map_err(storage_error(&file_path, StorageOp::Create))?;
+        }
+
+        // Continue the sequence after any existing events so keys are never reused
+        let file = File::open(&file_path).map_err(storage_error(&file_path, StorageOp::OpenForReplay))?;
+        let mut existing = 0u64;
+        for line in BufReader::new(file).lines() {
+            if !line.map_err(storage_error(&file_path, StorageOp::Read))?.trim().is_empty() {
+                existing += 1;
+            }
+        }
+
+        Ok(Self {
+            file_path,
+            converter,
+            master_key,
+            next_sequence: existing + 1,
+            writer: None,
+            _phantom: PhantomData,
+        })
+    }
+
+    fn cipher_for(&self, sequence: u64) -> Result<Aes256Gcm, Box<dyn std::error::Error + Send + Sync>> {
+        let hkdf = Hkdf::<Sha256>::new(Some(&sequence.to_be_bytes()), &self.master_key);
+        let mut key = [0u8; 32];
+        hkdf.expand(KEY_INFO, &mut key)
+            .map_err(|e| -> Box<dyn std::error::Error + Send + Sync> { Box::new(std::io::Error::other(e.to_string())) })?;
+        Ok(Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key)))
+    }
+
+    /// Write already-encrypted lines in one `write_all`, opening the file on the first append
+    fn write_lines(&mut self, text: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        if self.writer.is_none() {
+            self.writer = Some(
+                OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&self.file_path)
+                    .map_err(storage_error(&self.file_path, StorageOp::OpenForAppend))?,
+            );
+        }
+        if let Some(writer) = &mut self.writer {
+            writer.write_all(text.as_bytes()).map_err(storage_error(&self.file_path, StorageOp::Append))?;
+            writer.flush().map_err(storage_error(&self.file_path, StorageOp::Flush))?;
+        }
+        Ok(())
+    }
+
+    fn encrypt(&self, sequence: u64, plaintext: &str) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+        let cipher = self.cipher_for(sequence)?;
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = cipher.encrypt(&nonce, plaintext.as_bytes())
+            .map_err(|_| -> Box<dyn std::error::Error + Send + Sync> { Box::new(std::io::Error::other(format!("Encryption failed for event {}", sequence))) })?;
+        Ok(format!("{}.{}", STANDARD.encode(nonce), STANDARD.encode(ciphertext)))
+    }
+
+    fn decrypt(&self, sequence: u64, line: &str) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+        let malformed = || -> Box<dyn std::error::Error + Send + Sync> {
+            Box::new(std::io::Error::new(std::io::ErrorKind::InvalidData, format!("Malformed encrypted event {}", sequence)))
+        };
+
+        let (nonce, ciphertext) = line.split_once('.').ok_or_else(malformed)?;
+        let nonce = STANDARD.decode(nonce).map_err(|_| malformed())?;
+        let ciphertext = STANDARD.decode(ciphertext).map_err(|_| malformed())?;
+        if nonce.len() != 12 {
+            return Err(malformed());
+        }
+
+        let cipher = self.cipher_for(sequence)?;
+        let plaintext = cipher.decrypt(Nonce::from_slice(&nonce), ciphertext.as_slice())
+            .map_err(|_| -> Box<dyn std::error::Error + Send + Sync> {
+                Box::new(std::io::Error::new(std::io::ErrorKind::InvalidData, format!("Decryption failed for event {}", sequence)))
+            })?;
+        String::from_utf8(plaintext).map_err(|_| malformed())
+    }
+}
+
+impl<E, C> EventStorage for HkdfEncryptedStorage<E, C>
+where
+    C: TextConverter<E>,
+{
+    type Event = E;
+
+    fn replay<F>(&mut self, consumer: &mut F) -> Result<(), Box<dyn std::error::Error + Send + Sync>>
+    where
+        F: FnMut(Self::Event) -> Result<(), Box<dyn std::error::Error + Send + Sync>>,
+    {
+        let file = File::open(&self.file_path).map_err(storage_error(&self.file_path, StorageOp::OpenForReplay))?;
+        let reader = BufReader::new(file);
+
+        let mut sequence = 0u64;
+        for line in reader.lines() {
+            let line = line.map_err(storage_error(&self.file_path, StorageOp::Read))?;
+            if !line.trim().is_empty() {
+                sequence += 1;
+                let text = self.decrypt(sequence, line.trim())?;
+                consumer(self.converter.parse(&text)?)?;
+            }
+        }
+        self.next_sequence = sequence + 1;
+
+        Ok(())
+    }
+
+    fn append(&mut self, event: &Self::Event) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let line = self.encrypt(self.next_sequence, &self.converter.format(event)?)?;
+        self.write_lines(&format!("{}\n", line))?;
+        self.next_sequence += 1;
+
+        Ok(())
+    }
+
+    /// Encrypt every event before writing any, then write the group in one `write_all`
+    fn append_atomic(&mut self, events: &[Self::Event]) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let mut text = String::new();
+        for (sequence, event) in (self.next_sequence..).zip(events) {
+            text.push_str(&self.encrypt(sequence, &self.converter.format(event)?)?);
+            text.push('\n');
+        }
+        self.write_lines(&text)?;
+        self.next_sequence += events.len() as u64;
+
+        Ok(())
+    }
+
+    /// Every append is flushed to the file
+    fn writes_through(&self) -> bool {
+        true
+    }
+}
+
+impl<E, C> Drop for HkdfEncryptedStorage<E, C>
+where
+    C: TextConverter<E>,
+{
+    fn drop(&mut self) {
+        if let Some(mut writer) = self.writer.take() {
+            let _ = writer.flush();
+        }
+    }
+}
@@ -0,0 +1,83 @@
+use crate::memimg::error::{FailureOutcome, MemImgError};
+use crate::memimg::processor::{Command, MemImgProcessor};
+use crate::memimg::storage::TextConverter;
+use crate::memimg::text_file_storage::TextFileEventStorage;
+use std::mem::ManuallyDrop;
+use std::ops::{Deref, DerefMut};
+use std::path::{Path, PathBuf};
+
+/// Processor over a throwaway event log that is deleted when the processor is dropped
+///
+/// Meant for tests and short-lived in-process state machines; it derefs to the wrapped processor.
+pub struct EphemeralMemImgProcessor<S, C, V>
+where
+    S: Clone,
+    C: Command<System = S>,
+    V: TextConverter<C>,
+{
+    processor: ManuallyDrop<MemImgProcessor<S, C, TextFileEventStorage<C, V>>>,
+    file_path: PathBuf,
+}
+
+impl<S, C, V> EphemeralMemImgProcessor<S, C, V>
+where
+    S: Clone,
+    C: Command<System = S>,
+    V: TextConverter<C>,
+{
+    /// Start from `system` with a new, uniquely named log in `std::env::temp_dir()`
+    pub fn in_temp_dir(system: S, converter: V) -> Result<Self, MemImgError> {
+        let file_path = std::env::temp_dir().join(format!("rmemimg-{}.events", uuid::Uuid::new_v4()));
+        let storage = TextFileEventStorage::new_unlocked(&file_path, converter).map_err(|e| {
+            MemImgError::SystemFailure(FailureOutcome::new(e, "creating ephemeral log", std::any::type_name::<C>()))
+        })?;
+        let processor = MemImgProcessor::new_simple(system, Box::new(storage))?;
+        Ok(Self {
+            processor: ManuallyDrop::new(processor),
+            file_path,
+        })
+    }
+
+    /// The backing log, which exists until the processor is dropped
+    pub fn path(&self) -> &Path {
+        &self.file_path
+    }
+}
+
+impl<S, C, V> Deref for EphemeralMemImgProcessor<S, C, V>
+where
+    S: Clone,
+    C: Command<System = S>,
+    V: TextConverter<C>,
+{
+    type Target = MemImgProcessor<S, C, TextFileEventStorage<C, V>>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.processor
+    }
+}
+
+impl<S, C, V> DerefMut for EphemeralMemImgProcessor<S, C, V>
+where
+    S: Clone,
+    C: Command<System = S>,
+    V: TextConverter<C>,
+{
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.processor
+    }
+}
+
+impl<S, C, V> Drop for EphemeralMemImgProcessor<S, C, V>
+where
+    S: Clone,
+    C: Command<System = S>,
+    V: TextConverter<C>,
+{
+    fn drop(&mut self) {
+        // Close the log before deleting it, which some platforms require
+        // SAFETY: the processor is never used again after this point
+        unsafe { ManuallyDrop::drop(&mut self.processor) };
+        let _ = std::fs::remove_file(&self.file_path);
+    }
+}
@@ -1,13 +1,90 @@
+use crate::memimg::warning::Warning;
 use std::fmt;
+use std::time::Duration;
 use thiserror::Error;
 
 #[derive(Debug, Error)]
 pub enum MemImgError {
     #[error("Command failure: {0}")]
-    CommandFailure(FailureOutcome),
+    CommandFailure(#[source] FailureOutcome),
 
     #[error("System failure: {0}")]
-    SystemFailure(FailureOutcome),
+    SystemFailure(#[source] FailureOutcome),
+
+    /// The event log could not be appended to because its device is out of space
+    ///
+    /// Like a system failure it poisons the processor: free space, then restart it.
+    #[error("Storage full: {0}")]
+    StorageFull(#[source] FailureOutcome),
+
+    #[error("Processor is poisoned by an earlier system failure; restart it to recover")]
+    Poisoned,
+
+    /// Commands are refused while the processor is read-only; see `MemImgProcessor::set_read_only`
+    #[error("Processor is in maintenance mode; commands are refused until it leaves read-only mode")]
+    MaintenanceMode,
+
+    /// Commands are refused while `MemImgProcessor::live_reload` swaps in a new event log
+    #[error("Processor is reloading its event log; retry once the reload completes")]
+    ReloadInProgress,
+
+    /// `CommandRegistry` was asked for a command that was never registered
+    #[error("Unknown command {0}")]
+    UnknownCommand(String),
+
+    /// The payload given to `CommandRegistry` does not deserialize into the command
+    #[error("Invalid payload for command {command}: {message}")]
+    InvalidCommandPayload { command: String, message: String },
+
+    /// `QueryRegistry::execute_json` was asked for a query that was never registered
+    #[error("Unknown query {0}")]
+    UnknownQuery(String),
+
+    /// The parameters given to `QueryRegistry::execute_json` do not deserialize into the query
+    #[error("Invalid parameters for query {query}: {message}")]
+    InvalidQueryParameters { query: String, message: String },
+
+    #[error("Rate limit exceeded for account {account_id}; retry after {retry_after:?}")]
+    RateLimitExceeded { account_id: String, retry_after: Duration },
+
+    /// `MultiTenantProcessor` was given a tenant it has not opened
+    #[error("Unknown tenant {0}")]
+    UnknownTenant(String),
+
+    /// A tenant id must be non-empty ASCII letters, digits, `-` and `_`, so it can name files
+    #[error("Invalid tenant id {0:?}")]
+    InvalidTenantId(String),
+}
+
+impl MemImgError {
+    /// Stable machine-readable code for this error
+    pub fn code(&self) -> &'static str {
+        match self {
+            MemImgError::CommandFailure(_) => "COMMAND_FAILURE",
+            MemImgError::SystemFailure(_) => "SYSTEM_FAILURE",
+            MemImgError::StorageFull(_) => "STORAGE_FULL",
+            MemImgError::Poisoned => "POISONED",
+            MemImgError::MaintenanceMode => "MAINTENANCE_MODE",
+            MemImgError::ReloadInProgress => "RELOAD_IN_PROGRESS",
+            MemImgError::UnknownCommand(_) => "UNKNOWN_COMMAND",
+            MemImgError::InvalidCommandPayload { .. } => "INVALID_COMMAND_PAYLOAD",
+            MemImgError::UnknownQuery(_) => "UNKNOWN_QUERY",
+            MemImgError::InvalidQueryParameters { .. } => "INVALID_QUERY_PARAMETERS",
+            MemImgError::RateLimitExceeded { .. } => "RATE_LIMIT_EXCEEDED",
+            MemImgError::UnknownTenant(_) => "UNKNOWN_TENANT",
+            MemImgError::InvalidTenantId(_) => "INVALID_TENANT_ID",
+        }
+    }
+
+    /// The failure outcome carried by command and system failures
+    pub fn outcome(&self) -> Option<&FailureOutcome> {
+        match self {
+            MemImgError::CommandFailure(outcome) | MemImgError::SystemFailure(outcome) | MemImgError::StorageFull(outcome) => {
+                Some(outcome)
+            }
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -15,6 +92,18 @@ pub struct FailureOutcome {
     pub source: Box<dyn std::error::Error + Send + Sync>,
     pub context: String,
     pub command_type: String,
+    /// Events successfully replayed before a failure during startup replay
+    pub events_replayed: Option<u64>,
+    /// The event that failed to apply during replay, if one did
+    pub failed_event: Option<FailedEvent>,
+}
+
+/// Position and debug rendering of an event that failed to apply during replay
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FailedEvent {
+    /// 1-based position among the events replayed, as in `RejectedEvent` warnings
+    pub index: u64,
+    pub event: String,
 }
 
 impl FailureOutcome {
@@ -27,16 +116,132 @@ impl FailureOutcome {
             source,
             context: context.to_string(),
             command_type: command_type.to_string(),
+            events_replayed: None,
+            failed_event: None,
         }
     }
+
+    pub fn with_events_replayed(mut self, events_replayed: u64) -> Self {
+        self.events_replayed = Some(events_replayed);
+        self
+    }
+
+    pub fn with_failed_event(mut self, failed_event: Option<FailedEvent>) -> Self {
+        self.failed_event = failed_event;
+        self
+    }
 }
 
 impl fmt::Display for FailureOutcome {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(
-            f,
-            "Error while {} {}: {}",
-            self.context, self.command_type, self.source
-        )
+        match &self.failed_event {
+            Some(failed) => write!(
+                f,
+                "Error while {} {} at event {} ({}): {}",
+                self.context, self.command_type, failed.index, failed.event, self.source
+            ),
+            None => write!(
+                f,
+                "Error while {} {}: {}",
+                self.context, self.command_type, self.source
+            ),
+        }
+    }
+}
+
+impl std::error::Error for FailureOutcome {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(self.source.as_ref())
+    }
+}
+
+/// Storage operation being performed when an I/O error occurred
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StorageOp {
+    CreateDir,
+    Create,
+    OpenForReplay,
+    Read,
+    OpenForAppend,
+    Append,
+    Flush,
+    Truncate,
+    Copy,
+    Lock,
+    Remove,
+}
+
+impl fmt::Display for StorageOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            StorageOp::CreateDir => "create directory",
+            StorageOp::Create => "create",
+            StorageOp::OpenForReplay => "open for replay",
+            StorageOp::Read => "read",
+            StorageOp::OpenForAppend => "open for append",
+            StorageOp::Append => "append",
+            StorageOp::Flush => "flush",
+            StorageOp::Truncate => "truncate",
+            StorageOp::Copy => "copy",
+            StorageOp::Lock => "lock",
+            StorageOp::Remove => "remove",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// I/O failure in a storage backend, with the file and operation involved
+#[derive(Debug, Error)]
+#[error("{op} failed on {path}: {source}")]
+pub struct StorageError {
+    pub path: String,
+    pub op: StorageOp,
+    pub source: std::io::Error,
+}
+
+impl StorageError {
+    pub fn new(path: &str, op: StorageOp, source: std::io::Error) -> Self {
+        Self {
+            path: path.to_string(),
+            op,
+            source,
+        }
+    }
+}
+
+/// Replay found more unparseable records than `ReplayPolicy::Budgeted` tolerates
+#[derive(Debug, Error)]
+#[error("{} unparseable records exceed the replay error budget of {error_budget}", errors.len())]
+pub struct ReplayBudgetExceeded {
+    pub error_budget: usize,
+    /// Every unparseable record found, the one that exhausted the budget last
+    pub errors: Vec<Warning>,
+}
+
+/// Snapshot that cannot be loaded by this build, or that fails `Snapshot::verify_against`
+#[derive(Debug, Error)]
+pub enum SnapshotError {
+    #[error("Snapshot version {found} is newer than the supported version {supported}")]
+    UnsupportedVersion { found: u32, supported: u32 },
+
+    #[error("No migration registered from snapshot version {from_version}")]
+    MissingMigration { from_version: u32 },
+
+    #[error("Snapshot covers {event_count} events but the log holds only {available}")]
+    LogTooShort { event_count: u64, available: u64 },
+
+    #[error("Snapshot state differs from replaying the first {event_count} events of the log")]
+    StateMismatch { event_count: u64 },
+}
+
+/// Render an error and its chain of sources, outermost first
+#[cfg(feature = "fs")]
+pub fn error_chain(error: &(dyn std::error::Error + 'static)) -> Vec<String> {
+    let mut chain = vec![error.to_string()];
+    let mut source = error.source();
+    while let Some(cause) = source {
+        chain.push(cause.to_string());
+        source = cause.source();
     }
+    chain
 }
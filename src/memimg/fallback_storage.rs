@@ -0,0 +1,159 @@
+use crate::memimg::error::MemImgError;
+use crate::memimg::event_id::EventId;
+use crate::memimg::processor::{Command, MemImgProcessor};
+use crate::memimg::storage::EventStorage;
+use crate::memimg::warning::{Warning, WarningKind};
+
+/// Which of its two backends a `FallbackEventStorage` is using
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StorageMode {
+    Primary,
+    Fallback,
+}
+
+/// Event storage that is either the preferred backend `P` or its stand-in `F`
+///
+/// Built by `MemImgProcessor::new_with_fallback`, which settles on one of them at startup.
+pub enum FallbackEventStorage<P, F> {
+    Primary(Box<P>),
+    Fallback(Box<F>),
+}
+
+impl<P, F> FallbackEventStorage<P, F> {
+    pub fn mode(&self) -> StorageMode {
+        match self {
+            FallbackEventStorage::Primary(_) => StorageMode::Primary,
+            FallbackEventStorage::Fallback(_) => StorageMode::Fallback,
+        }
+    }
+}
+
+impl<P, F, E> EventStorage for FallbackEventStorage<P, F>
+where
+    P: EventStorage<Event = E>,
+    F: EventStorage<Event = E>,
+{
+    type Event = E;
+
+    fn replay<G>(&mut self, consumer: &mut G) -> Result<(), Box<dyn std::error::Error + Send + Sync>>
+    where
+        G: FnMut(Self::Event) -> Result<(), Box<dyn std::error::Error + Send + Sync>>,
+    {
+        match self {
+            FallbackEventStorage::Primary(storage) => storage.replay(consumer),
+            FallbackEventStorage::Fallback(storage) => storage.replay(consumer),
+        }
+    }
+
+    fn replay_n<G>(&mut self, n: u64, consumer: &mut G) -> Result<u64, Box<dyn std::error::Error + Send + Sync>>
+    where
+        G: FnMut(Self::Event) -> Result<(), Box<dyn std::error::Error + Send + Sync>>,
+    {
+        match self {
+            FallbackEventStorage::Primary(storage) => storage.replay_n(n, consumer),
+            FallbackEventStorage::Fallback(storage) => storage.replay_n(n, consumer),
+        }
+    }
+
+    fn replay_from<G>(&mut self, position: u64, n: u64, consumer: &mut G) -> Result<(u64, u64), Box<dyn std::error::Error + Send + Sync>>
+    where
+        G: FnMut(Self::Event) -> Result<(), Box<dyn std::error::Error + Send + Sync>>,
+    {
+        match self {
+            FallbackEventStorage::Primary(storage) => storage.replay_from(position, n, consumer),
+            FallbackEventStorage::Fallback(storage) => storage.replay_from(position, n, consumer),
+        }
+    }
+
+    fn append(&mut self, event: &Self::Event) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        match self {
+            FallbackEventStorage::Primary(storage) => storage.append(event),
+            FallbackEventStorage::Fallback(storage) => storage.append(event),
+        }
+    }
+
+    fn append_atomic(&mut self, events: &[Self::Event]) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        match self {
+            FallbackEventStorage::Primary(storage) => storage.append_atomic(events),
+            FallbackEventStorage::Fallback(storage) => storage.append_atomic(events),
+        }
+    }
+
+    fn version(&mut self) -> Result<u64, Box<dyn std::error::Error + Send + Sync>> {
+        match self {
+            FallbackEventStorage::Primary(storage) => storage.version(),
+            FallbackEventStorage::Fallback(storage) => storage.version(),
+        }
+    }
+
+    fn truncate_before(&mut self, first_kept: EventId) -> Result<u64, Box<dyn std::error::Error + Send + Sync>> {
+        match self {
+            FallbackEventStorage::Primary(storage) => storage.truncate_before(first_kept),
+            FallbackEventStorage::Fallback(storage) => storage.truncate_before(first_kept),
+        }
+    }
+
+    fn flush(&mut self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        match self {
+            FallbackEventStorage::Primary(storage) => storage.flush(),
+            FallbackEventStorage::Fallback(storage) => storage.flush(),
+        }
+    }
+
+    fn drain_warnings(&mut self) -> Vec<Warning> {
+        match self {
+            FallbackEventStorage::Primary(storage) => storage.drain_warnings(),
+            FallbackEventStorage::Fallback(storage) => storage.drain_warnings(),
+        }
+    }
+
+    fn writes_through(&self) -> bool {
+        match self {
+            FallbackEventStorage::Primary(storage) => storage.writes_through(),
+            FallbackEventStorage::Fallback(storage) => storage.writes_through(),
+        }
+    }
+
+    fn append_marker(&mut self, text: &str) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+        match self {
+            FallbackEventStorage::Primary(storage) => storage.append_marker(text),
+            FallbackEventStorage::Fallback(storage) => storage.append_marker(text),
+        }
+    }
+
+    fn settle_intent(&mut self, applied: bool) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+        match self {
+            FallbackEventStorage::Primary(storage) => storage.settle_intent(applied),
+            FallbackEventStorage::Fallback(storage) => storage.settle_intent(applied),
+        }
+    }
+}
+
+impl<S, C, P, F> MemImgProcessor<S, C, FallbackEventStorage<P, F>>
+where
+    S: Clone,
+    C: Command<System = S>,
+    P: EventStorage<Event = C>,
+    F: EventStorage<Event = C>,
+{
+    /// Replay from `primary`, or if that fails, start over from `fallback` instead of failing
+    ///
+    /// The primary's error is kept as a `StorageFallback` warning. Events replayed from the
+    /// primary before it failed are discarded, as replay restarts from `system`.
+    pub fn new_with_fallback(system: S, primary: Box<P>, fallback: Box<F>) -> Result<Self, MemImgError> {
+        match Self::new_simple(system.clone(), Box::new(FallbackEventStorage::Primary(primary))) {
+            Ok(processor) => Ok(processor),
+            Err(e) => {
+                let mut processor = Self::new_simple(system, Box::new(FallbackEventStorage::Fallback(fallback)))?;
+                let message = format!("primary storage failed, using the fallback: {}", e);
+                processor.buffer_warnings(vec![Warning::new(WarningKind::StorageFallback, 0, 0, &message)]);
+                Ok(processor)
+            }
+        }
+    }
+
+    /// Whether events go to the primary storage or to the fallback
+    pub fn storage_mode(&self) -> StorageMode {
+        self.event_storage.mode()
+    }
+}
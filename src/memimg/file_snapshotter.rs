@@ -0,0 +1,143 @@
+use crate::memimg::error::{FailureOutcome, MemImgError, StorageError, StorageOp};
+use crate::memimg::processor::{Command, MemImgProcessor};
+use crate::memimg::snapshot::{CompactionResult, Snapshot, SnapshotFormat};
+use crate::memimg::storage::EventStorage;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+const SNAPSHOT_PREFIX: &str = "snapshot-";
+const SNAPSHOT_SUFFIX: &str = ".json";
+
+/// Which older snapshots a `FileSnapshotter` keeps after taking a new one
+///
+/// The newest snapshot is always kept, whatever the policy says.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RetentionPolicy {
+    #[default]
+    KeepAll,
+    /// The most recent `n` snapshots, counting the new one
+    KeepLast(usize),
+    /// Snapshots whose files were written less than this long ago
+    KeepNewerThan(Duration),
+}
+
+/// Takes snapshots into numbered files in one directory, pruning old ones per a `RetentionPolicy`
+///
+/// Each snapshot goes through `MemImgProcessor::checkpoint_and_compact` into
+/// `snapshot-<event count>.json`, zero-padded so names sort in log order. The file is written
+/// under a temporary name and renamed into place, so a listed snapshot is always complete.
+pub struct FileSnapshotter {
+    directory: PathBuf,
+    format: SnapshotFormat,
+    retention: RetentionPolicy,
+}
+
+impl FileSnapshotter {
+    pub fn new<P: AsRef<Path>>(directory: P, format: SnapshotFormat) -> Self {
+        Self {
+            directory: directory.as_ref().to_path_buf(),
+            format,
+            retention: RetentionPolicy::default(),
+        }
+    }
+
+    pub fn with_retention(mut self, retention: RetentionPolicy) -> Self {
+        self.retention = retention;
+        self
+    }
+
+    /// Snapshot and compact `processor`, then prune older snapshots, returning the new file's path
+    ///
+    /// Nothing is pruned unless the new snapshot was written and the log compacted.
+    pub fn take<S, C, E>(&self, processor: &mut MemImgProcessor<S, C, E>) -> Result<(PathBuf, CompactionResult), MemImgError>
+    where
+        S: Clone + Serialize,
+        C: Command<System = S>,
+        E: EventStorage<Event = C>,
+    {
+        let failure = |op, path: &Path, e| {
+            let error = StorageError::new(&path.to_string_lossy(), op, e);
+            MemImgError::SystemFailure(FailureOutcome::new(Box::new(error), "taking snapshot for", "FileSnapshotter"))
+        };
+        std::fs::create_dir_all(&self.directory).map_err(|e| failure(StorageOp::CreateDir, &self.directory, e))?;
+
+        let path = self.directory.join(format!("{}{:020}{}", SNAPSHOT_PREFIX, processor.event_version().0, SNAPSHOT_SUFFIX));
+        let partial = path.with_extension("json.tmp");
+        let file = File::create(&partial).map_err(|e| failure(StorageOp::Create, &partial, e))?;
+        let result = match processor.checkpoint_and_compact(&self.format, file) {
+            Ok(result) => result,
+            Err(e) => {
+                let _ = std::fs::remove_file(&partial);
+                return Err(e);
+            }
+        };
+        std::fs::rename(&partial, &path).map_err(|e| failure(StorageOp::Create, &path, e))?;
+
+        self.prune().map_err(|e| MemImgError::SystemFailure(FailureOutcome::new(e, "pruning snapshots for", "FileSnapshotter")))?;
+        Ok((path, result))
+    }
+
+    /// Snapshot files in the directory, oldest first
+    pub fn snapshots(&self) -> Result<Vec<PathBuf>, Box<dyn std::error::Error + Send + Sync>> {
+        let directory = self.directory.to_string_lossy();
+        let entries = match std::fs::read_dir(&self.directory) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(Box::new(StorageError::new(&directory, StorageOp::Read, e))),
+        };
+        let mut snapshots = Vec::new();
+        for entry in entries {
+            let entry = entry.map_err(|e| StorageError::new(&directory, StorageOp::Read, e))?;
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            if name.starts_with(SNAPSHOT_PREFIX) && name.ends_with(SNAPSHOT_SUFFIX) {
+                snapshots.push(entry.path());
+            }
+        }
+        snapshots.sort();
+        Ok(snapshots)
+    }
+
+    /// Read the newest snapshot, if there is one
+    pub fn load_latest<S: DeserializeOwned>(&self) -> Result<Option<Snapshot<S>>, Box<dyn std::error::Error + Send + Sync>> {
+        let Some(path) = self.snapshots()?.pop() else {
+            return Ok(None);
+        };
+        let file = File::open(&path).map_err(|e| StorageError::new(&path.to_string_lossy(), StorageOp::Read, e))?;
+        self.format.read(BufReader::new(file)).map(Some)
+    }
+
+    /// Delete the snapshots `retention` no longer keeps, returning their paths
+    pub fn prune(&self) -> Result<Vec<PathBuf>, Box<dyn std::error::Error + Send + Sync>> {
+        let mut older = self.snapshots()?;
+        // The newest snapshot is never a candidate
+        older.pop();
+        let doomed: Vec<PathBuf> = match self.retention {
+            RetentionPolicy::KeepAll => Vec::new(),
+            RetentionPolicy::KeepLast(n) => {
+                let excess = older.len().saturating_sub(n.saturating_sub(1));
+                older.into_iter().take(excess).collect()
+            }
+            RetentionPolicy::KeepNewerThan(age) => {
+                let now = SystemTime::now();
+                older
+                    .into_iter()
+                    .filter(|path| {
+                        // A file whose age cannot be told is kept
+                        std::fs::metadata(path)
+                            .and_then(|metadata| metadata.modified())
+                            .is_ok_and(|modified| now.duration_since(modified).unwrap_or_default() >= age)
+                    })
+                    .collect()
+            }
+        };
+        for path in &doomed {
+            std::fs::remove_file(path).map_err(|e| StorageError::new(&path.to_string_lossy(), StorageOp::Remove, e))?;
+        }
+        Ok(doomed)
+    }
+}
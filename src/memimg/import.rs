@@ -0,0 +1,93 @@
+use crate::memimg::error::{FailureOutcome, MemImgError};
+use crate::memimg::processor::{Command, MemImgProcessor};
+use crate::memimg::storage::EventStorage;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+/// Outcome of `MemImgProcessor::import_commands`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ImportReport {
+    pub accepted: u64,
+    pub rejected: u64,
+    pub rejections: Vec<ImportRejection>,
+}
+
+/// A command `MemImgProcessor::import_commands` skipped
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ImportRejection {
+    /// 1-based position of the command in the imported array
+    pub index: u64,
+    pub reason: String,
+}
+
+impl<S, C, E> MemImgProcessor<S, C, E>
+where
+    S: Clone,
+    C: Command<System = S> + Clone + DeserializeOwned,
+    E: EventStorage<Event = C>,
+{
+    /// Execute every command in the JSON array in the file at `path`, in order, skipping those
+    /// that do not parse or that the processor rejects
+    ///
+    /// Each command is executed as by `execute_command`, so a rejected one leaves nothing behind
+    /// and the commands after it still run. A file that cannot be read or is not a JSON array
+    /// fails the whole import, as does any error other than a `CommandFailure`, which leaves the
+    /// commands before it imported.
+    pub fn import_commands(&mut self, path: impl AsRef<Path>) -> Result<ImportReport, MemImgError> {
+        let path = path.as_ref();
+        let mut report = ImportReport { accepted: 0, rejected: 0, rejections: Vec::new() };
+        for (index, command) in (1..).zip(read_commands(path)?) {
+            let reason = match serde_json::from_value::<C>(command) {
+                Ok(command) => match self.execute_command(command) {
+                    Ok(_) => None,
+                    Err(e @ MemImgError::CommandFailure(_)) => Some(e.to_string()),
+                    Err(e) => return Err(e),
+                },
+                Err(e) => Some(e.to_string()),
+            };
+            match reason {
+                None => report.accepted += 1,
+                Some(reason) => {
+                    report.rejected += 1;
+                    report.rejections.push(ImportRejection { index, reason });
+                }
+            }
+        }
+        Ok(report)
+    }
+
+    /// Like `import_commands`, but all or nothing: the first command that does not parse or is
+    /// rejected fails the import, and nothing is imported
+    ///
+    /// The commands run as one `execute_transaction`, so they are logged together and middlewares
+    /// do not see them.
+    pub fn import_commands_strict(&mut self, path: impl AsRef<Path>) -> Result<ImportReport, MemImgError> {
+        let path = path.as_ref();
+        let commands = read_commands(path)?;
+        let accepted = commands.len() as u64;
+        let rejection = |index: usize, e: Box<dyn std::error::Error + Send + Sync>| {
+            MemImgError::CommandFailure(FailureOutcome::new(e, "importing", &format!("command {} of {}", index + 1, path.display())))
+        };
+        self.execute_transaction(|transaction| {
+            for (index, command) in commands.into_iter().enumerate() {
+                let command: C = serde_json::from_value(command).map_err(|e| rejection(index, Box::new(e)))?;
+                transaction.execute_command(&command).map_err(|e| rejection(index, Box::new(e)))?;
+            }
+            Ok(())
+        })?;
+        Ok(ImportReport { accepted, rejected: 0, rejections: Vec::new() })
+    }
+}
+
+/// The elements of the JSON array in the file at `path`
+fn read_commands(path: &Path) -> Result<Vec<Value>, MemImgError> {
+    let failure = |e: Box<dyn std::error::Error + Send + Sync>| {
+        MemImgError::CommandFailure(FailureOutcome::new(e, "importing commands from", &path.display().to_string()))
+    };
+    let file = File::open(path).map_err(|e| failure(Box::new(e)))?;
+    serde_json::from_reader(BufReader::new(file)).map_err(|e| failure(Box::new(e)))
+}
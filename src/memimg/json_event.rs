@@ -0,0 +1,74 @@
+use crate::memimg::storage::TextConverter;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fmt;
+use std::marker::PhantomData;
+
+/// Marker for event types stored as one JSON document per line
+///
+/// Opting in is explicit on purpose. A blanket `impl<T: Serialize + DeserializeOwned> TextConverter<T>`
+/// would cover every serde type, so coherence would then forbid any hand-written converter for
+/// those types (such as `BankJsonConverter`). Blanket-implementing on a dedicated converter for
+/// marked types instead leaves custom converters free to coexist.
+pub trait JsonEvent: Serialize + DeserializeOwned {}
+
+/// Untyped events, as stored by `SchemaFreeProcessor`
+impl JsonEvent for serde_json::Value {}
+
+/// Converter for any `JsonEvent`, used by `TextFileEventStorage::json`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JsonEventConverter;
+
+impl<T: JsonEvent> TextConverter<T> for JsonEventConverter {
+    fn parse(&self, text: &str) -> Result<T, Box<dyn std::error::Error + Send + Sync>> {
+        serde_json::from_str(text).map_err(|e| -> Box<dyn std::error::Error + Send + Sync> { Box::new(e) })
+    }
+
+    fn format(&self, value: &T) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+        serde_json::to_string(value).map_err(|e| -> Box<dyn std::error::Error + Send + Sync> { Box::new(e) })
+    }
+}
+
+/// Converter storing any serde type `T` as one JSON document per line, without opting in
+/// through `JsonEvent`; `BankJsonConverter` is `JsonConverter<BankCommand>`
+///
+/// Each `T` gets its own converter type, so hand-written converters for other types still coexist.
+pub struct JsonConverter<T>(PhantomData<fn() -> T>);
+
+impl<T> JsonConverter<T> {
+    pub const fn new() -> Self {
+        JsonConverter(PhantomData)
+    }
+}
+
+impl<T> Default for JsonConverter<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Clone for JsonConverter<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for JsonConverter<T> {}
+
+impl<T> fmt::Debug for JsonConverter<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "JsonConverter<{}>", std::any::type_name::<T>())
+    }
+}
+
+impl<T: Serialize + DeserializeOwned> TextConverter<T> for JsonConverter<T> {
+    fn parse(&self, text: &str) -> Result<T, Box<dyn std::error::Error + Send + Sync>> {
+        serde_json::from_str(text).map_err(|e| -> Box<dyn std::error::Error + Send + Sync> { Box::new(e) })
+    }
+
+    fn format(&self, value: &T) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+        // Ensure JSON is on a single line
+        let json = serde_json::to_string(value).map_err(|e| -> Box<dyn std::error::Error + Send + Sync> { Box::new(e) })?;
+        Ok(json.replace('\n', " "))
+    }
+}
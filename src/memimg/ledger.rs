@@ -0,0 +1,219 @@
+use crate::memimg::json_event::JsonEvent;
+use crate::memimg::processor::Query;
+use crate::memimg::Command;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use thiserror::Error;
+
+pub type Amount = Decimal;
+
+/// Domain errors raised by ledger commands and queries
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum LedgerError {
+    #[error("Unknown ledger account: {0}")]
+    UnknownAccount(String),
+
+    #[error("Duplicate ledger account: {0}")]
+    DuplicateAccount(String),
+
+    #[error("Journal entry {description:?} has no lines")]
+    EmptyEntry { description: String },
+
+    #[error("Line amounts must be positive, got {amount} for {account}")]
+    NonPositiveAmount { account: String, amount: Amount },
+
+    #[error("Journal entry {description:?} does not balance: debits {debits} != credits {credits}")]
+    Unbalanced { description: String, debits: Amount, credits: Amount },
+}
+
+/// Ledger state: accounts keyed by code, so serialization is sorted and stable
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Ledger {
+    pub accounts: BTreeMap<String, LedgerAccount>,
+    /// Journal entries posted so far
+    pub entries_posted: u64,
+}
+
+impl Ledger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn account_mut(&mut self, code: &str) -> Result<&mut LedgerAccount, LedgerError> {
+        self.accounts.get_mut(code).ok_or_else(|| LedgerError::UnknownAccount(code.to_string()))
+    }
+}
+
+/// Classification deciding on which side an account's balance grows
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AccountKind {
+    Asset,
+    Liability,
+    Equity,
+    Revenue,
+    Expense,
+}
+
+impl AccountKind {
+    /// Assets and expenses grow with debits; the rest grow with credits
+    pub fn is_debit_normal(self) -> bool {
+        matches!(self, AccountKind::Asset | AccountKind::Expense)
+    }
+}
+
+/// Ledger account holding the totals of every line posted to it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LedgerAccount {
+    pub code: String,
+    pub name: String,
+    pub kind: AccountKind,
+    pub total_debits: Amount,
+    pub total_credits: Amount,
+}
+
+impl LedgerAccount {
+    pub fn new(code: String, name: String, kind: AccountKind) -> Self {
+        Self {
+            code,
+            name,
+            kind,
+            total_debits: Amount::ZERO,
+            total_credits: Amount::ZERO,
+        }
+    }
+
+    /// Balance on the account's normal side, derived from the posted totals
+    pub fn balance(&self) -> Amount {
+        if self.kind.is_debit_normal() {
+            self.total_debits - self.total_credits
+        } else {
+            self.total_credits - self.total_debits
+        }
+    }
+}
+
+/// Side of a journal line
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Side {
+    Debit,
+    Credit,
+}
+
+/// One line of a journal entry: `amount` debited or credited to `account`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JournalLine {
+    pub account: String,
+    pub side: Side,
+    pub amount: Amount,
+}
+
+impl JournalLine {
+    pub fn debit(account: &str, amount: Amount) -> Self {
+        Self { account: account.to_string(), side: Side::Debit, amount }
+    }
+
+    pub fn credit(account: &str, amount: Amount) -> Self {
+        Self { account: account.to_string(), side: Side::Credit, amount }
+    }
+}
+
+// Commands
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Command)]
+#[command(system = "Ledger")]
+pub enum LedgerCommand {
+    #[command(handler = "apply_open_account")]
+    OpenAccount { code: String, name: String, kind: AccountKind },
+    /// Post lines whose debits and credits must total the same; an unbalanced entry is rejected whole
+    #[command(handler = "apply_journal_entry")]
+    JournalEntry { description: String, lines: Vec<JournalLine> },
+}
+
+impl JsonEvent for LedgerCommand {}
+
+// Command handlers
+
+impl Ledger {
+    fn apply_open_account(&mut self, code: &str, name: &str, kind: &AccountKind) -> Result<(), LedgerError> {
+        if self.accounts.contains_key(code) {
+            return Err(LedgerError::DuplicateAccount(code.to_string()));
+        }
+        self.accounts.insert(code.to_string(), LedgerAccount::new(code.to_string(), name.to_string(), *kind));
+        Ok(())
+    }
+
+    fn apply_journal_entry(&mut self, description: &str, lines: &[JournalLine]) -> Result<(), LedgerError> {
+        if lines.is_empty() {
+            return Err(LedgerError::EmptyEntry { description: description.to_string() });
+        }
+
+        // Lines are posted before the totals are compared, so an unbalanced entry leaves them
+        // applied and the processor's shadow copy discards the whole entry
+        let mut debits = Amount::ZERO;
+        let mut credits = Amount::ZERO;
+        for line in lines {
+            if line.amount <= Amount::ZERO {
+                return Err(LedgerError::NonPositiveAmount { account: line.account.clone(), amount: line.amount });
+            }
+            let account = self.account_mut(&line.account)?;
+            match line.side {
+                Side::Debit => {
+                    account.total_debits += line.amount;
+                    debits += line.amount;
+                }
+                Side::Credit => {
+                    account.total_credits += line.amount;
+                    credits += line.amount;
+                }
+            }
+        }
+        if debits != credits {
+            return Err(LedgerError::Unbalanced { description: description.to_string(), debits, credits });
+        }
+
+        self.entries_posted += 1;
+        Ok(())
+    }
+}
+
+// Queries
+
+#[derive(Debug)]
+pub struct GetAccountBalance {
+    pub code: String,
+}
+
+impl Query for GetAccountBalance {
+    type System = Ledger;
+    type Result = Amount;
+
+    fn extract_from(&self, ledger: &Self::System) -> Result<Self::Result, Box<dyn std::error::Error + Send + Sync>> {
+        ledger.accounts
+            .get(&self.code)
+            .map(LedgerAccount::balance)
+            .ok_or_else(|| LedgerError::UnknownAccount(self.code.clone()).into())
+    }
+}
+
+/// Debit and credit totals across all accounts, equal whenever every entry balanced
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TrialBalance {
+    pub total_debits: Amount,
+    pub total_credits: Amount,
+}
+
+#[derive(Debug)]
+pub struct GetTrialBalance;
+
+impl Query for GetTrialBalance {
+    type System = Ledger;
+    type Result = TrialBalance;
+
+    fn extract_from(&self, ledger: &Self::System) -> Result<Self::Result, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(TrialBalance {
+            total_debits: ledger.accounts.values().map(|account| account.total_debits).sum(),
+            total_credits: ledger.accounts.values().map(|account| account.total_credits).sum(),
+        })
+    }
+}
@@ -0,0 +1,16 @@
+use crate::memimg::ledger::LedgerCommand;
+use crate::memimg::storage::TextConverter;
+
+/// JSON converter for LedgerCommand
+pub struct LedgerJsonConverter;
+
+impl TextConverter<LedgerCommand> for LedgerJsonConverter {
+    fn parse(&self, text: &str) -> Result<LedgerCommand, Box<dyn std::error::Error + Send + Sync>> {
+        serde_json::from_str(text).map_err(|e| -> Box<dyn std::error::Error + Send + Sync> { Box::new(e) })
+    }
+
+    fn format(&self, command: &LedgerCommand) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+        // serde_json::to_string never emits newlines, so each command stays on one line
+        serde_json::to_string(command).map_err(|e| -> Box<dyn std::error::Error + Send + Sync> { Box::new(e) })
+    }
+}
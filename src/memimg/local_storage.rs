@@ -0,0 +1,94 @@
+use crate::memimg::storage::{EventStorage, TextConverter};
+use std::marker::PhantomData;
+use web_sys::Storage;
+
+/// Event storage in the browser's `localStorage`, one entry per event
+///
+/// Event `n` is stored under `{prefix}/{n}` and the event count under `{prefix}/count`, so
+/// several logs can share one origin. `localStorage` writes are synchronous: an append is
+/// persisted by the time it returns.
+pub struct LocalStorageEventStorage<E, C>
+where
+    C: TextConverter<E>,
+{
+    storage: Storage,
+    prefix: String,
+    converter: C,
+    count: u64,
+    _phantom: PhantomData<fn() -> E>,
+}
+
+// `JsValue` errors are not `std::error::Error`s, so only their debug form survives
+fn js_error<T: std::fmt::Debug>(action: &str) -> impl FnOnce(T) -> Box<dyn std::error::Error + Send + Sync> + '_ {
+    move |e| format!("localStorage {} failed: {:?}", action, e).into()
+}
+
+impl<E, C> LocalStorageEventStorage<E, C>
+where
+    C: TextConverter<E>,
+{
+    /// Open the log stored under `prefix` in the current window's `localStorage`
+    pub fn new(prefix: &str, converter: C) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let window = web_sys::window().ok_or("localStorage is only available in a browser window")?;
+        let storage = window.local_storage().map_err(js_error("access"))?.ok_or("localStorage is disabled")?;
+        Self::with_storage(storage, prefix, converter)
+    }
+
+    /// Use `storage` instead of the window's `localStorage`, for instance `sessionStorage`
+    pub fn with_storage(storage: Storage, prefix: &str, converter: C) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let count_key = format!("{}/count", prefix);
+        let count = match storage.get_item(&count_key).map_err(js_error("read"))? {
+            Some(count) => count.parse().map_err(|_| format!("{} holds {:?}, not an event count", count_key, count))?,
+            None => 0,
+        };
+        Ok(Self {
+            storage,
+            prefix: prefix.to_string(),
+            converter,
+            count,
+            _phantom: PhantomData,
+        })
+    }
+
+    fn event_key(&self, n: u64) -> String {
+        format!("{}/{}", self.prefix, n)
+    }
+}
+
+impl<E, C> EventStorage for LocalStorageEventStorage<E, C>
+where
+    C: TextConverter<E>,
+{
+    type Event = E;
+
+    fn replay<F>(&mut self, consumer: &mut F) -> Result<(), Box<dyn std::error::Error + Send + Sync>>
+    where
+        F: FnMut(Self::Event) -> Result<(), Box<dyn std::error::Error + Send + Sync>>,
+    {
+        for n in 1..=self.count {
+            let key = self.event_key(n);
+            let text = self.storage.get_item(&key).map_err(js_error("read"))?.ok_or_else(|| format!("event {} is missing", key))?;
+            consumer(self.converter.parse(&text)?)?;
+        }
+        Ok(())
+    }
+
+    fn append(&mut self, event: &Self::Event) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let text = self.converter.format(event)?;
+        // The event is written before the count, so a failure in between leaves an unreferenced entry, not a gap
+        self.storage.set_item(&self.event_key(self.count + 1), &text).map_err(js_error("write"))?;
+        self.storage
+            .set_item(&format!("{}/count", self.prefix), &(self.count + 1).to_string())
+            .map_err(js_error("write"))?;
+        self.count += 1;
+        Ok(())
+    }
+
+    fn version(&mut self) -> Result<u64, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(self.count)
+    }
+
+    fn writes_through(&self) -> bool {
+        true
+    }
+}
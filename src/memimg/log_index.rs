@@ -0,0 +1,144 @@
+use crate::memimg::error::StorageOp;
+use crate::memimg::stream_storage::{is_event_line, storage_error, IntentFraming};
+use std::fs::File;
+use std::io::{BufRead, BufReader, Seek, SeekFrom};
+use std::path::Path;
+
+/// Sparse index of the event lines in a plain-text log, for reading windows of it without loading it whole
+///
+/// Building the index streams the file once and keeps the byte offset of every `stride`-th event,
+/// so a window anywhere in the log is reached with one seek and at most `stride - 1` skipped lines.
+/// Events are numbered from 0 in log order; blank and marker lines are not events, and an
+/// unterminated last line is left out as an append in progress, as are events still waiting for
+/// the commit record of a write-ahead intent.
+pub struct LogIndex {
+    file_path: String,
+    stride: u64,
+    /// Offset of every `stride`-th event, starting with event 0
+    offsets: Vec<u64>,
+    len: u64,
+    /// Offset just past the last complete line indexed
+    end: u64,
+    /// Whether the log opens write-ahead intents before `end`
+    intents: bool,
+}
+
+impl LogIndex {
+    /// Index the log at `path`, recording an offset every `stride` events
+    pub fn build(path: impl AsRef<Path>, stride: u64) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        if stride == 0 {
+            return Err("log index stride must be positive".into());
+        }
+        let mut index = Self {
+            file_path: path.as_ref().to_string_lossy().to_string(),
+            stride,
+            offsets: Vec::new(),
+            len: 0,
+            end: 0,
+            intents: false,
+        };
+        index.refresh()?;
+        Ok(index)
+    }
+
+    /// Index the lines appended since the last build or refresh, returning how many events they hold
+    pub fn refresh(&mut self) -> Result<u64, Box<dyn std::error::Error + Send + Sync>> {
+        let mut reader = self.reader_at(self.end)?;
+        let before = self.len;
+        let mut framing = IntentFraming::new(self.intents);
+        let mut at = self.end;
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            let read = reader.read_until(b'\n', &mut line).map_err(storage_error(&self.file_path, StorageOp::Read))?;
+            if read == 0 || line.last() != Some(&b'\n') {
+                break;
+            }
+            let text = String::from_utf8_lossy(&line);
+            let committed = match framing.record(&text) {
+                Some(committed) => committed,
+                None if is_event_line(&text) => framing.hold(at).into_iter().collect(),
+                None => Vec::new(),
+            };
+            for offset in committed {
+                if self.len.is_multiple_of(self.stride) {
+                    self.offsets.push(offset);
+                }
+                self.len += 1;
+            }
+            at += read as u64;
+            // Events still held may yet be cut off the log, so the next refresh reads them again
+            if framing.held().is_empty() {
+                self.end = at;
+            }
+        }
+        self.intents = framing.declared();
+        Ok(self.len - before)
+    }
+
+    /// Number of events indexed
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The text of up to `count` events starting at event `start`, fewer at the end of the log
+    pub fn read_window(&self, start: u64, count: usize) -> Result<Vec<String>, Box<dyn std::error::Error + Send + Sync>> {
+        let mut window = Vec::with_capacity(count.min(self.len.saturating_sub(start) as usize));
+        if start < self.len {
+            self.scan_from(start, |_, text| {
+                window.push(text.to_string());
+                window.len() < count
+            })?;
+        }
+        Ok(window)
+    }
+
+    /// First event at or after `from` whose text contains `needle`
+    pub fn find(&self, from: u64, needle: &str) -> Result<Option<u64>, Box<dyn std::error::Error + Send + Sync>> {
+        let mut found = None;
+        if from < self.len {
+            self.scan_from(from, |event, text| {
+                if text.contains(needle) {
+                    found = Some(event);
+                }
+                found.is_none()
+            })?;
+        }
+        Ok(found)
+    }
+
+    /// Feed `visit` each indexed event from `start` on, with its text, until it returns false
+    fn scan_from(&self, start: u64, mut visit: impl FnMut(u64, &str) -> bool) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let checkpoint = start / self.stride;
+        let mut reader = self.reader_at(self.offsets[checkpoint as usize])?;
+        let mut event = checkpoint * self.stride;
+        let mut line = Vec::new();
+        while event < self.len {
+            line.clear();
+            // A log truncated since it was indexed simply ends early
+            if reader.read_until(b'\n', &mut line).map_err(storage_error(&self.file_path, StorageOp::Read))? == 0 {
+                break;
+            }
+            let text = String::from_utf8_lossy(&line);
+            let text = text.trim_end_matches(['\n', '\r']);
+            if !is_event_line(text) {
+                continue;
+            }
+            if event >= start && !visit(event, text) {
+                break;
+            }
+            event += 1;
+        }
+        Ok(())
+    }
+
+    fn reader_at(&self, offset: u64) -> Result<BufReader<File>, Box<dyn std::error::Error + Send + Sync>> {
+        let mut file = File::open(&self.file_path).map_err(storage_error(&self.file_path, StorageOp::OpenForReplay))?;
+        file.seek(SeekFrom::Start(offset)).map_err(storage_error(&self.file_path, StorageOp::Read))?;
+        Ok(BufReader::new(file))
+    }
+}
@@ -0,0 +1,88 @@
+use crate::memimg::event_id::EventId;
+use crate::memimg::storage::EventStorage;
+
+/// Event storage that keeps events in a `Vec`, for tests, prototypes and embedding
+#[derive(Debug, Clone)]
+pub struct MemoryEventStorage<E> {
+    events: Vec<E>,
+}
+
+impl<E> MemoryEventStorage<E> {
+    pub fn new() -> Self {
+        Self { events: Vec::new() }
+    }
+
+    /// Storage pre-loaded with `events`, as if they had been appended in order
+    pub fn with_events(events: Vec<E>) -> Self {
+        Self { events }
+    }
+
+    /// Events stored so far, oldest first
+    pub fn events(&self) -> &[E] {
+        &self.events
+    }
+}
+
+impl<E> Default for MemoryEventStorage<E> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<E: Clone> EventStorage for MemoryEventStorage<E> {
+    type Event = E;
+
+    fn replay<F>(&mut self, consumer: &mut F) -> Result<(), Box<dyn std::error::Error + Send + Sync>>
+    where
+        F: FnMut(Self::Event) -> Result<(), Box<dyn std::error::Error + Send + Sync>>,
+    {
+        for event in &self.events {
+            consumer(event.clone())?;
+        }
+        Ok(())
+    }
+
+    fn replay_n<F>(&mut self, n: u64, consumer: &mut F) -> Result<u64, Box<dyn std::error::Error + Send + Sync>>
+    where
+        F: FnMut(Self::Event) -> Result<(), Box<dyn std::error::Error + Send + Sync>>,
+    {
+        let events = &self.events[..self.events.len().min(usize::try_from(n).unwrap_or(usize::MAX))];
+        for event in events {
+            consumer(event.clone())?;
+        }
+        Ok(events.len() as u64)
+    }
+
+    /// Positions are indexes into the stored events
+    fn replay_from<F>(&mut self, position: u64, n: u64, consumer: &mut F) -> Result<(u64, u64), Box<dyn std::error::Error + Send + Sync>>
+    where
+        F: FnMut(Self::Event) -> Result<(), Box<dyn std::error::Error + Send + Sync>>,
+    {
+        let start = self.events.len().min(usize::try_from(position).unwrap_or(usize::MAX));
+        let end = self.events.len().min(start.saturating_add(usize::try_from(n).unwrap_or(usize::MAX)));
+        for event in &self.events[start..end] {
+            consumer(event.clone())?;
+        }
+        Ok(((end - start) as u64, end as u64))
+    }
+
+    fn append(&mut self, event: &Self::Event) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        self.events.push(event.clone());
+        Ok(())
+    }
+
+    fn append_atomic(&mut self, events: &[Self::Event]) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        self.events.extend_from_slice(events);
+        Ok(())
+    }
+
+    fn truncate_before(&mut self, first_kept: EventId) -> Result<u64, Box<dyn std::error::Error + Send + Sync>> {
+        let removed = (first_kept.as_u64().saturating_sub(1) as usize).min(self.events.len());
+        self.events.drain(..removed);
+        Ok(removed as u64)
+    }
+
+    fn version(&mut self) -> Result<u64, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(self.events.len() as u64)
+    }
+}
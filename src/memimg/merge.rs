@@ -0,0 +1,33 @@
+use crate::memimg::processor::Command;
+use crate::memimg::storage::EventStorage;
+use chrono::{DateTime, Utc};
+
+/// Where an event falls in a merged timeline: timestamp, then source ordinal, then position in the source
+type MergeKey = (Option<DateTime<Utc>>, usize, u64);
+
+/// The events of `sources` as one timeline ordered by `Command::timestamp`, ready to replay
+///
+/// Events with identical timestamps apply in a fixed order: by source ordinal (position in
+/// `sources`), then by position within their source, so a merge always produces the same
+/// timeline and the same state. An event without a timestamp takes the one of the event before
+/// it in its source; those leading a source sort before every timestamped event.
+pub fn merge_by_timestamp<C, E>(sources: &mut [E]) -> Result<Vec<C>, Box<dyn std::error::Error + Send + Sync>>
+where
+    C: Command,
+    E: EventStorage<Event = C>,
+{
+    let mut keyed: Vec<(MergeKey, C)> = Vec::new();
+    for (ordinal, source) in sources.iter_mut().enumerate() {
+        let mut sequence = 0u64;
+        let mut last_timestamp = None;
+        source.replay(&mut |event: C| {
+            last_timestamp = event.timestamp().or(last_timestamp);
+            keyed.push(((last_timestamp, ordinal, sequence), event));
+            sequence += 1;
+            Ok(())
+        })?;
+    }
+    // Keys are unique, so the order never depends on the sort algorithm
+    keyed.sort_unstable_by_key(|(key, _)| *key);
+    Ok(keyed.into_iter().map(|(_, event)| event).collect())
+}
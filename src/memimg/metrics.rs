@@ -0,0 +1,134 @@
+use std::fmt::Write;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Upper bounds in seconds of the duration histograms' buckets, Prometheus' defaults
+const BUCKETS: [f64; 11] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+/// Durations counted into `BUCKETS`, summed in nanoseconds so they can be updated atomically
+struct Histogram {
+    buckets: [AtomicU64; BUCKETS.len()],
+    sum_nanos: AtomicU64,
+    count: AtomicU64,
+}
+
+impl Histogram {
+    const fn new() -> Self {
+        Self {
+            buckets: [const { AtomicU64::new(0) }; BUCKETS.len()],
+            sum_nanos: AtomicU64::new(0),
+            count: AtomicU64::new(0),
+        }
+    }
+
+    fn observe(&self, duration: Duration) {
+        let seconds = duration.as_secs_f64();
+        if let Some(bucket) = BUCKETS.iter().position(|bound| seconds <= *bound) {
+            self.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        }
+        self.sum_nanos.fetch_add(duration.as_nanos() as u64, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn render(&self, out: &mut String, name: &str, help: &str) {
+        header(out, name, help, "histogram");
+        let mut cumulative = 0;
+        for (bound, bucket) in BUCKETS.iter().zip(&self.buckets) {
+            cumulative += bucket.load(Ordering::Relaxed);
+            let _ = writeln!(out, "{}_bucket{{le=\"{}\"}} {}", name, bound, cumulative);
+        }
+        let count = self.count.load(Ordering::Relaxed);
+        let _ = writeln!(out, "{}_bucket{{le=\"+Inf\"}} {}", name, count);
+        let _ = writeln!(out, "{}_sum {}", name, self.sum_nanos.load(Ordering::Relaxed) as f64 / 1e9);
+        let _ = writeln!(out, "{}_count {}", name, count);
+    }
+}
+
+/// Process-wide figures of every processor and file storage, as `gather` renders them
+struct Metrics {
+    commands_succeeded: AtomicU64,
+    commands_failed: AtomicU64,
+    command_duration: Histogram,
+    replay_events: AtomicU64,
+    replay_duration: Histogram,
+    append_bytes: AtomicU64,
+    flushes: AtomicU64,
+    snapshot_duration: Histogram,
+    event_log_size: AtomicU64,
+    poisoned: AtomicBool,
+}
+
+static METRICS: Metrics = Metrics {
+    commands_succeeded: AtomicU64::new(0),
+    commands_failed: AtomicU64::new(0),
+    command_duration: Histogram::new(),
+    replay_events: AtomicU64::new(0),
+    replay_duration: Histogram::new(),
+    append_bytes: AtomicU64::new(0),
+    flushes: AtomicU64::new(0),
+    snapshot_duration: Histogram::new(),
+    event_log_size: AtomicU64::new(0),
+    poisoned: AtomicBool::new(false),
+};
+
+pub(crate) fn record_command(succeeded: bool, duration: Duration) {
+    let counter = if succeeded { &METRICS.commands_succeeded } else { &METRICS.commands_failed };
+    counter.fetch_add(1, Ordering::Relaxed);
+    METRICS.command_duration.observe(duration);
+}
+
+pub(crate) fn record_replay(events: u64, duration: Duration) {
+    METRICS.replay_events.fetch_add(events, Ordering::Relaxed);
+    METRICS.replay_duration.observe(duration);
+}
+
+#[cfg(feature = "fs")]
+pub(crate) fn record_append(bytes: usize) {
+    METRICS.append_bytes.fetch_add(bytes as u64, Ordering::Relaxed);
+}
+
+#[cfg(feature = "fs")]
+pub(crate) fn record_flush() {
+    METRICS.flushes.fetch_add(1, Ordering::Relaxed);
+}
+
+pub(crate) fn record_snapshot(duration: Duration) {
+    METRICS.snapshot_duration.observe(duration);
+}
+
+/// Publish the event count and poisoned state of the processor that last changed
+pub(crate) fn record_processor_state(event_count: u64, poisoned: bool) {
+    METRICS.event_log_size.store(event_count, Ordering::Relaxed);
+    METRICS.poisoned.store(poisoned, Ordering::Relaxed);
+}
+
+fn header(out: &mut String, name: &str, help: &str, kind: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+fn scalar(out: &mut String, name: &str, help: &str, kind: &str, value: u64) {
+    header(out, name, help, kind);
+    let _ = writeln!(out, "{} {}", name, value);
+}
+
+/// Every metric in the Prometheus text exposition format, for a `/metrics` endpoint to return
+///
+/// Counters and histograms add up all processors and file storages in the process; the gauges
+/// describe whichever processor last executed a command or was opened, which is the one
+/// processor a service normally runs.
+pub fn gather() -> String {
+    let mut out = String::new();
+    header(&mut out, "memimg_commands_total", "Commands executed, by outcome", "counter");
+    let _ = writeln!(out, "memimg_commands_total{{outcome=\"success\"}} {}", METRICS.commands_succeeded.load(Ordering::Relaxed));
+    let _ = writeln!(out, "memimg_commands_total{{outcome=\"failure\"}} {}", METRICS.commands_failed.load(Ordering::Relaxed));
+    METRICS.command_duration.render(&mut out, "memimg_command_duration_seconds", "Time to execute a command");
+    scalar(&mut out, "memimg_replay_events_total", "Events replayed when opening processors", "counter", METRICS.replay_events.load(Ordering::Relaxed));
+    METRICS.replay_duration.render(&mut out, "memimg_replay_duration_seconds", "Time to replay the log when opening a processor");
+    scalar(&mut out, "memimg_storage_append_bytes_total", "Bytes appended to event log files", "counter", METRICS.append_bytes.load(Ordering::Relaxed));
+    scalar(&mut out, "memimg_storage_flushes_total", "Flushes of event log files to disk", "counter", METRICS.flushes.load(Ordering::Relaxed));
+    METRICS.snapshot_duration.render(&mut out, "memimg_snapshot_duration_seconds", "Time to snapshot and compact");
+    scalar(&mut out, "memimg_event_log_size", "Events behind the processor's state", "gauge", METRICS.event_log_size.load(Ordering::Relaxed));
+    scalar(&mut out, "memimg_poisoned", "1 while the processor refuses commands after a system failure", "gauge", METRICS.poisoned.load(Ordering::Relaxed) as u64);
+    out
+}
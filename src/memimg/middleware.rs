@@ -0,0 +1,37 @@
+use crate::memimg::error::MemImgError;
+use std::fmt::Display;
+
+/// Hook around command execution; `before` may veto a command before it touches state or storage
+pub trait CommandMiddleware<C> {
+    fn before(&mut self, command: &C) -> Result<(), MemImgError>;
+
+    fn after(&mut self, _command: &C, _result: &Result<(), MemImgError>) {}
+}
+
+/// Middleware that reports each command through its `Display` form, after it runs
+///
+/// Lines look like `ok: Deposit(account=alice, amount=$100.00)` or
+/// `failed: Withdrawal(account=alice, amount=$50.00): <error>`.
+pub struct LoggingMiddleware<F> {
+    sink: F,
+}
+
+impl<F: FnMut(&str)> LoggingMiddleware<F> {
+    pub fn new(sink: F) -> Self {
+        Self { sink }
+    }
+}
+
+impl<C: Display, F: FnMut(&str)> CommandMiddleware<C> for LoggingMiddleware<F> {
+    fn before(&mut self, _command: &C) -> Result<(), MemImgError> {
+        Ok(())
+    }
+
+    fn after(&mut self, command: &C, result: &Result<(), MemImgError>) {
+        let line = match result {
+            Ok(()) => format!("ok: {}", command),
+            Err(e) => format!("failed: {}: {}", command, e),
+        };
+        (self.sink)(&line);
+    }
+}
@@ -1,10 +1,150 @@
 mod processor;
 mod storage;
+#[cfg(feature = "fs")]
+mod text_file_storage;
+mod memory_storage;
+mod stream_storage;
+mod fallback_storage;
+mod multi_version_storage;
+mod merge;
+#[cfg(feature = "fs")]
+mod cursor;
+#[cfg(feature = "fs")]
+mod log_index;
+#[cfg(feature = "fs")]
+mod ephemeral;
+mod json_event;
 mod error;
+mod event_id;
+#[cfg(feature = "fs")]
+mod dump;
+mod warning;
+mod snapshot;
+mod middleware;
+mod rollback;
+mod report;
+mod standing_query;
+mod query_registry;
+mod query_router;
+mod command_registry;
+mod command_deserializer;
+mod schema_free;
+mod multi_event;
+mod replica;
+mod replication;
+mod validation;
+mod view;
+mod projection;
+mod clock;
+mod tenancy;
+#[cfg(feature = "fs")]
+mod config;
+#[cfg(feature = "fs")]
+mod file_snapshotter;
+#[cfg(feature = "fs")]
+mod import;
+#[cfg(feature = "signals")]
+mod shutdown;
+#[cfg(feature = "encryption")]
+mod encrypted_storage;
+#[cfg(feature = "wasm")]
+mod local_storage;
 
+#[cfg(feature = "admin")]
+pub mod admin;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+#[cfg(feature = "bank-example")]
 pub mod bank;
+#[cfg(all(feature = "admin", feature = "bank-example"))]
+pub mod bank_anonymizer;
+#[cfg(all(feature = "fs", feature = "bank-example"))]
+pub mod bank_audit;
+#[cfg(all(feature = "fs", feature = "bank-example"))]
+pub mod bank_browser;
+#[cfg(feature = "http")]
+pub mod bank_http;
+#[cfg(feature = "bank-example")]
+pub mod bank_events;
+#[cfg(feature = "bank-example")]
+pub mod bank_invariants;
+#[cfg(feature = "bank-example")]
+pub mod bank_journal;
+#[cfg(feature = "bank-example")]
+pub mod bank_pipe;
+#[cfg(feature = "bank-example")]
+pub mod bank_repl;
+#[cfg(feature = "schemars")]
+pub mod bank_schema;
+#[cfg(feature = "bank-example")]
+pub mod bank_sorted;
+#[cfg(feature = "bank-example")]
 pub mod bank_storage;
+#[cfg(feature = "bank-example")]
+pub mod bank_throttler;
+#[cfg(feature = "ledger-example")]
+pub mod ledger;
+#[cfg(feature = "ledger-example")]
+pub mod ledger_storage;
+#[cfg(feature = "test-util")]
+pub mod testing;
+#[cfg(feature = "inventory-example")]
+pub mod warehouse;
+#[cfg(feature = "inventory-example")]
+pub mod warehouse_storage;
 
-pub use processor::{Command, Query, MemImgProcessor};
-pub use storage::{EventStorage, TextConverter, TextFileEventStorage};
-pub use error::MemImgError;
+pub use processor::{ClockRegressionPolicy, Command, CommandReceipt, CommitNotificationOrder, CommitStrategy, ProcessorStatistics, Query, QueryAudit, MemImgProcessor, ReloadStats, ReplayMetrics, RotationMarker, SlowCommand, SnapshotMismatchPolicy, TransactionContext};
+pub use rmemimg_derive::Command;
+#[doc(hidden)]
+pub use processor::__command_result;
+pub use storage::{BoxedEventStorage, DynConsumer, DynEventStorage, EventStorage, ReplayPolicy, TextConverter};
+#[cfg(feature = "fs")]
+pub use text_file_storage::{Durability, EventIndexKeys, LogLock, LogMarker, TextFileEventStorage, TextFileEventStorageBuilder};
+pub use memory_storage::MemoryEventStorage;
+pub use stream_storage::{StreamEventStorage, MARKER_PREFIX};
+pub use fallback_storage::{FallbackEventStorage, StorageMode};
+pub use multi_version_storage::{ComparisonReport, MultiVersionEventStorage};
+pub use merge::merge_by_timestamp;
+#[cfg(feature = "fs")]
+pub use config::{ConfigError, FlushPolicy, MemImgConfig, ENV_PREFIX};
+#[cfg(feature = "fs")]
+pub use file_snapshotter::{FileSnapshotter, RetentionPolicy};
+#[cfg(feature = "fs")]
+pub use import::{ImportRejection, ImportReport};
+#[cfg(feature = "signals")]
+pub use shutdown::{ShutdownGuard, StopSignal};
+#[cfg(feature = "fs")]
+pub use cursor::LogCursor;
+#[cfg(feature = "fs")]
+pub use log_index::LogIndex;
+#[cfg(feature = "fs")]
+pub use ephemeral::EphemeralMemImgProcessor;
+pub use json_event::{JsonConverter, JsonEvent, JsonEventConverter};
+#[cfg(feature = "encryption")]
+pub use encrypted_storage::HkdfEncryptedStorage;
+#[cfg(feature = "wasm")]
+pub use local_storage::LocalStorageEventStorage;
+#[cfg(feature = "fs")]
+pub use dump::{DumpContext, FailureDumper, MAX_DUMP_PAYLOAD_CHARS};
+pub use event_id::EventId;
+pub use error::{FailedEvent, FailureOutcome, MemImgError, ReplayBudgetExceeded, SnapshotError, StorageError, StorageOp};
+pub use middleware::{CommandMiddleware, LoggingMiddleware};
+pub use rollback::{RollbackStrategy, SerializedRollback, ShadowCopy};
+pub use report::{FailureReport, ReplayReport, ReportFrame};
+pub use projection::{build_projection, Projection};
+#[cfg(feature = "fs")]
+pub use projection::PersistentProjection;
+pub use replica::ReadReplica;
+pub use replication::{ReplicaEndpoint, ReplicaProcessor, ReplicaRequest, ReplicatedEvent, ReplicationSource};
+pub use snapshot::{CompactionResult, Snapshot, SnapshotFormat};
+pub use standing_query::{QueryId, StandingQueryProcessor};
+pub use query_registry::QueryRegistry;
+pub use query_router::QueryRouter;
+pub use command_registry::{CommandRegistry, DuplicateCommandName, DynCommand};
+pub use command_deserializer::{CommandDeserializer, DeserializeCommand};
+pub use schema_free::{EventHandler, SchemaFreeProcessor};
+pub use multi_event::{MultiEventCommand, MultiEventProcessor};
+pub use tenancy::MultiTenantProcessor;
+pub use validation::{InvariantMonitor, MonitorMode, ReplayValidationResult, StateDiff, SystemValidator};
+pub use view::SystemView;
+pub use warning::{Warning, WarningKind, MAX_BUFFERED_WARNINGS};
@@ -0,0 +1,103 @@
+use crate::memimg::error::{FailedEvent, FailureOutcome, MemImgError};
+use crate::memimg::storage::EventStorage;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fmt::Debug;
+
+/// A command that decides, against the current state, which events record it: none, one or many
+///
+/// Unlike a `Command`, which is logged as it is, only the events are logged, and replay applies
+/// them one by one without the commands that produced them.
+pub trait MultiEventCommand: Debug {
+    type System: Clone;
+    type Event: Clone + Debug + Serialize + DeserializeOwned;
+
+    /// The events recording this command, or why it is rejected; the state is left untouched
+    fn generate_events(&self, system: &Self::System) -> Result<Vec<Self::Event>, Box<dyn std::error::Error + Send + Sync>>;
+
+    /// Apply one event, whether just generated or replayed
+    fn apply_event(system: &mut Self::System, event: &Self::Event) -> Result<(), Box<dyn std::error::Error + Send + Sync>>;
+}
+
+/// Processor for `MultiEventCommand`s, logging and replaying their events rather than the commands
+///
+/// A command's events are applied to a shadow copy and appended with
+/// `EventStorage::append_atomic`, so they are committed together or not at all.
+pub struct MultiEventProcessor<C, E>
+where
+    C: MultiEventCommand,
+    E: EventStorage<Event = C::Event>,
+{
+    system: C::System,
+    event_storage: Box<E>,
+    event_count: u64,
+    poisoned: bool,
+}
+
+impl<C, E> MultiEventProcessor<C, E>
+where
+    C: MultiEventCommand,
+    E: EventStorage<Event = C::Event>,
+{
+    /// Processor starting from `system` with every event in `event_storage` applied
+    pub fn new(mut system: C::System, mut event_storage: Box<E>) -> Result<Self, MemImgError> {
+        let mut event_count = 0u64;
+        let mut failed_event = None;
+        event_storage
+            .replay(&mut |event: C::Event| {
+                C::apply_event(&mut system, &event).inspect_err(|_| {
+                    failed_event = Some(FailedEvent { index: event_count + 1, event: format!("{:?}", event) });
+                })?;
+                event_count += 1;
+                Ok(())
+            })
+            .map_err(|e| {
+                MemImgError::SystemFailure(
+                    FailureOutcome::new(e, "replaying events into", std::any::type_name::<C::System>())
+                        .with_events_replayed(event_count)
+                        .with_failed_event(failed_event),
+                )
+            })?;
+        Ok(Self { system, event_storage, event_count, poisoned: false })
+    }
+
+    /// Generate `command`'s events, apply them to a shadow copy and log them, returning them
+    ///
+    /// A command that generates no events changes nothing and logs nothing.
+    pub fn execute(&mut self, command: &C) -> Result<Vec<C::Event>, MemImgError> {
+        if self.poisoned {
+            return Err(MemImgError::Poisoned);
+        }
+        let command_type = std::any::type_name::<C>();
+        let command_failure = |e| MemImgError::CommandFailure(FailureOutcome::new(e, "executing", command_type));
+        let events = command.generate_events(&self.system).map_err(command_failure)?;
+        if events.is_empty() {
+            return Ok(events);
+        }
+        let mut shadow = self.system.clone();
+        for event in &events {
+            C::apply_event(&mut shadow, event).map_err(command_failure)?;
+        }
+        if let Err(e) = self.event_storage.append_atomic(&events) {
+            // The storage may hold some of the events now: refuse further writes
+            self.poisoned = true;
+            return Err(MemImgError::SystemFailure(FailureOutcome::new(e, "serializing events of", command_type)));
+        }
+        self.system = shadow;
+        self.event_count += events.len() as u64;
+        Ok(events)
+    }
+
+    pub fn system(&self) -> &C::System {
+        &self.system
+    }
+
+    /// Events behind the current state, replayed ones included
+    pub fn event_count(&self) -> u64 {
+        self.event_count
+    }
+
+    pub fn is_poisoned(&self) -> bool {
+        self.poisoned
+    }
+}
@@ -0,0 +1,188 @@
+use crate::memimg::event_id::EventId;
+use crate::memimg::storage::EventStorage;
+use crate::memimg::warning::Warning;
+
+/// Event storage writing every event to a `current` log and a `shadow` one, for trying a new
+/// event schema or backend alongside the one in use
+///
+/// Replay and the other reads use `current` only. A failed append to the shadow does not fail
+/// the command; it is counted, and shows up in `compare_replay` as the shadow falling behind.
+pub struct MultiVersionEventStorage<C, S> {
+    current: Box<C>,
+    shadow: Box<S>,
+    shadow_append_failures: u64,
+}
+
+/// Outcome of `MultiVersionEventStorage::compare_replay`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ComparisonReport {
+    pub current_events: u64,
+    pub shadow_events: u64,
+    /// 1-based index of the first event the two logs disagree on, if any
+    pub first_divergence: Option<u64>,
+    /// The two versions of that event, `None` where a log ends before it
+    pub divergent_events: Option<(Option<String>, Option<String>)>,
+    pub shadow_append_failures: u64,
+}
+
+impl ComparisonReport {
+    pub fn diverged(&self) -> bool {
+        self.first_divergence.is_some()
+    }
+
+    /// Events in `current` beyond those in `shadow`; negative if the shadow holds more
+    pub fn event_count_difference(&self) -> i64 {
+        self.current_events as i64 - self.shadow_events as i64
+    }
+}
+
+impl<C, S> MultiVersionEventStorage<C, S> {
+    pub fn new(current: Box<C>, shadow: Box<S>) -> Self {
+        Self { current, shadow, shadow_append_failures: 0 }
+    }
+
+    pub fn current(&self) -> &C {
+        &self.current
+    }
+
+    pub fn shadow(&self) -> &S {
+        &self.shadow
+    }
+
+    pub fn shadow_mut(&mut self) -> &mut S {
+        &mut self.shadow
+    }
+
+    /// Appends the shadow refused since this storage was created
+    pub fn shadow_append_failures(&self) -> u64 {
+        self.shadow_append_failures
+    }
+}
+
+impl<C, S, E> MultiVersionEventStorage<C, S>
+where
+    C: EventStorage<Event = E>,
+    S: EventStorage<Event = E>,
+    E: PartialEq + std::fmt::Debug,
+{
+    /// Replay both logs and report where, if anywhere, they part ways
+    ///
+    /// The current log is held in memory while the shadow is streamed against it.
+    pub fn compare_replay(&mut self) -> Result<ComparisonReport, Box<dyn std::error::Error + Send + Sync>> {
+        let mut current_events = Vec::new();
+        self.current.replay(&mut |event: E| {
+            current_events.push(event);
+            Ok(())
+        })?;
+
+        let mut shadow_events = 0u64;
+        let mut first_divergence = None;
+        let mut divergent_events = None;
+        self.shadow.replay(&mut |event: E| {
+            shadow_events += 1;
+            if first_divergence.is_none() {
+                let current = current_events.get(shadow_events as usize - 1);
+                if current != Some(&event) {
+                    first_divergence = Some(shadow_events);
+                    divergent_events = Some((current.map(|event| format!("{:?}", event)), Some(format!("{:?}", event))));
+                }
+            }
+            Ok(())
+        })?;
+
+        let current_count = current_events.len() as u64;
+        if first_divergence.is_none() && current_count > shadow_events {
+            first_divergence = Some(shadow_events + 1);
+            divergent_events = Some((Some(format!("{:?}", current_events[shadow_events as usize])), None));
+        }
+        Ok(ComparisonReport {
+            current_events: current_count,
+            shadow_events,
+            first_divergence,
+            divergent_events,
+            shadow_append_failures: self.shadow_append_failures,
+        })
+    }
+}
+
+impl<C, S, E> EventStorage for MultiVersionEventStorage<C, S>
+where
+    C: EventStorage<Event = E>,
+    S: EventStorage<Event = E>,
+{
+    type Event = E;
+
+    fn replay<F>(&mut self, consumer: &mut F) -> Result<(), Box<dyn std::error::Error + Send + Sync>>
+    where
+        F: FnMut(Self::Event) -> Result<(), Box<dyn std::error::Error + Send + Sync>>,
+    {
+        self.current.replay(consumer)
+    }
+
+    fn replay_n<F>(&mut self, n: u64, consumer: &mut F) -> Result<u64, Box<dyn std::error::Error + Send + Sync>>
+    where
+        F: FnMut(Self::Event) -> Result<(), Box<dyn std::error::Error + Send + Sync>>,
+    {
+        self.current.replay_n(n, consumer)
+    }
+
+    fn replay_from<F>(&mut self, position: u64, n: u64, consumer: &mut F) -> Result<(u64, u64), Box<dyn std::error::Error + Send + Sync>>
+    where
+        F: FnMut(Self::Event) -> Result<(), Box<dyn std::error::Error + Send + Sync>>,
+    {
+        self.current.replay_from(position, n, consumer)
+    }
+
+    fn append(&mut self, event: &Self::Event) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        self.current.append(event)?;
+        if self.shadow.append(event).is_err() {
+            self.shadow_append_failures += 1;
+        }
+        Ok(())
+    }
+
+    fn append_atomic(&mut self, events: &[Self::Event]) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        self.current.append_atomic(events)?;
+        if self.shadow.append_atomic(events).is_err() {
+            self.shadow_append_failures += 1;
+        }
+        Ok(())
+    }
+
+    fn version(&mut self) -> Result<u64, Box<dyn std::error::Error + Send + Sync>> {
+        self.current.version()
+    }
+
+    /// Truncates both logs, so they stay comparable after `checkpoint_and_compact`
+    fn truncate_before(&mut self, first_kept: EventId) -> Result<u64, Box<dyn std::error::Error + Send + Sync>> {
+        let removed = self.current.truncate_before(first_kept)?;
+        let _ = self.shadow.truncate_before(first_kept);
+        Ok(removed)
+    }
+
+    fn flush(&mut self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        self.current.flush()?;
+        let _ = self.shadow.flush();
+        Ok(())
+    }
+
+    fn drain_warnings(&mut self) -> Vec<Warning> {
+        self.current.drain_warnings()
+    }
+
+    fn writes_through(&self) -> bool {
+        self.current.writes_through()
+    }
+
+    fn append_marker(&mut self, text: &str) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+        let kept = self.current.append_marker(text)?;
+        let _ = self.shadow.append_marker(text);
+        Ok(kept)
+    }
+
+    fn settle_intent(&mut self, applied: bool) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+        let kept = self.current.settle_intent(applied)?;
+        let _ = self.shadow.settle_intent(applied);
+        Ok(kept)
+    }
+}
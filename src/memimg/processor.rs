@@ -1,12 +1,94 @@
-use crate::memimg::error::{FailureOutcome, MemImgError};
+use crate::memimg::clock::Instant;
+#[cfg(feature = "fs")]
+use crate::memimg::dump::{DumpContext, FailureDumper};
+use crate::memimg::event_id::EventId;
+#[cfg(feature = "fs")]
+use crate::memimg::error::error_chain;
+use crate::memimg::error::{FailedEvent, FailureOutcome, MemImgError, SnapshotError};
+use crate::memimg::middleware::CommandMiddleware;
+use crate::memimg::rollback::{RollbackStrategy, SerializedRollback, ShadowCopy};
+use crate::memimg::snapshot::{CompactionResult, Snapshot, SnapshotFormat};
 use crate::memimg::storage::EventStorage;
-use std::fmt::Debug;
+use crate::memimg::view::SystemView;
+use crate::memimg::validation::{InvariantMonitor, ReplayValidationResult, StateDiff, SystemValidator};
+use crate::memimg::warning::{Warning, WarningKind, MAX_BUFFERED_WARNINGS};
+use chrono::{DateTime, Utc};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fmt::{self, Debug};
+use std::io::Write;
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
 
 /// Trait for commands that mutate system state
 pub trait Command: Debug {
-    type System: Clone;
+    type System;
 
     fn apply_to(&self, system: &mut Self::System) -> Result<(), Box<dyn std::error::Error + Send + Sync>>;
+
+    /// Human-readable description of what the command would do against `system`, without mutating it
+    fn explain(&self, _system: &Self::System) -> String {
+        format!("{:?}", self)
+    }
+
+    /// The event to apply and log in place of this command, with whatever it reads from `system`
+    /// (such as an amount) made explicit so replay never re-derives it; `None` keeps the command as is
+    fn resolve(&self, _system: &Self::System) -> Option<Self>
+    where
+        Self: Sized,
+    {
+        None
+    }
+
+    /// Whether this command would apply to `system`, checked without changing or cloning it;
+    /// `None` for commands with no such check, which `MemImgProcessor::can_apply` dry-runs instead
+    fn check(&self, _system: &Self::System) -> Option<Result<(), Box<dyn std::error::Error + Send + Sync>>> {
+        None
+    }
+
+    /// When the event happened, for events that record it; reported in `ReplayMetrics`
+    fn timestamp(&self) -> Option<DateTime<Utc>> {
+        None
+    }
+
+    /// This event stamped at `at` instead, for `ClockRegressionPolicy::Clamp`; `None` for events
+    /// that cannot be restamped
+    fn with_timestamp(&self, _at: DateTime<Utc>) -> Option<Self>
+    where
+        Self: Sized,
+    {
+        None
+    }
+}
+
+#[doc(hidden)]
+pub fn __command_result<E>(result: Result<(), E>) -> Result<(), Box<dyn std::error::Error + Send + Sync>>
+where
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    result.map_err(Into::into)
+}
+
+/// Whether `error` or any error behind it is an I/O error for a device out of space
+fn is_storage_full(error: &(dyn std::error::Error + 'static)) -> bool {
+    let mut current = Some(error);
+    while let Some(error) = current {
+        let kind = error.downcast_ref::<std::io::Error>().map(std::io::Error::kind);
+        if matches!(kind, Some(std::io::ErrorKind::StorageFull | std::io::ErrorKind::WriteZero)) {
+            return true;
+        }
+        current = error.source();
+    }
+    false
+}
+
+/// Leading identifier of a command's `Debug` form: the variant name for derived enums
+fn variant_name<C: Debug>(command: &C) -> String {
+    let debug = format!("{:?}", command);
+    debug.split(|c: char| !(c.is_alphanumeric() || c == '_')).next().unwrap_or_default().to_string()
 }
 
 /// Trait for queries that extract data from system state
@@ -17,15 +99,268 @@ pub trait Query: Debug {
     fn extract_from(&self, system: &Self::System) -> Result<Self::Result, Box<dyn std::error::Error + Send + Sync>>;
 }
 
-/// Memory Image Processor - manages in-memory system state with event sourcing
-pub struct MemImgProcessor<S, C, E>
+/// Order in which a command is applied to the shadow copy and appended to the log
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CommitStrategy {
+    /// Apply to the shadow copy first and append only commands that succeed
+    #[default]
+    ApplyThenAppend,
+    /// Append first for at-least-once durability; commands that then fail to apply stay in the log,
+    /// unless it keeps write-ahead intents (see `EventStorage::settle_intent`)
+    AppendThenApply,
+}
+
+/// What `execute_command` does with a command stamped earlier than the latest logged event, as
+/// when the system clock is set back
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ClockRegressionPolicy {
+    /// Log the command as stamped and buffer a `ClockRegression` warning
+    #[default]
+    Warn,
+    /// Restamp the command with the latest logged timestamp through `Command::with_timestamp`;
+    /// commands that cannot be restamped are warned about instead
+    Clamp,
+}
+
+/// What `new_with_snapshot` does when a snapshot covers more events than its log holds, as when
+/// the log was restored from an older backup than the snapshot
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SnapshotMismatchPolicy {
+    /// Fail with `SnapshotError::LogTooShort`
+    Fail,
+    /// Discard the snapshot and rebuild by replaying the whole log onto the initial state
+    #[default]
+    FullReplay,
+    /// Keep the snapshot's state and number new events after the log's
+    ///
+    /// The state then reflects events the log no longer holds, and a later restart from the log
+    /// alone silently loses them; only use it when the snapshot is known to be right and the log
+    /// will be rebuilt from it, as with `checkpoint_and_compact`.
+    TrustSnapshot,
+}
+
+/// Whether `on_commit` observers or channel subscribers hear of a commit first
+///
+/// Either way both run after the state changed and the event was logged, and before middlewares'
+/// `CommandMiddleware::after` hooks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CommitNotificationOrder {
+    /// Observers in registration order, then the `subscribe_commits` and `broadcast_commits` channels
+    #[default]
+    ObserversFirst,
+    /// The channels, then the observers
+    PublishFirst,
+}
+
+/// Outcome of a successful `execute_command`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CommandReceipt {
+    /// Position of the command's event in the log, equal to `event_version` right after it
+    pub seq: u64,
+    /// Whether the event already reached the storage's backing file; if not, it becomes
+    /// durable once the storage is flushed
+    pub durable: bool,
+}
+
+/// A command that took longer than the threshold set with `MemImgProcessor::with_slow_command_threshold`
+#[derive(Debug, Clone, PartialEq)]
+pub struct SlowCommand {
+    /// The command's variant name, such as `Transfer`
+    pub command_type: String,
+    pub elapsed: Duration,
+    pub threshold: Duration,
+}
+
+type SlowCommandSink = Box<dyn FnMut(&SlowCommand) + Send>;
+
+/// A query as reported to the hook set with `MemImgProcessor::with_query_audit`
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryAudit {
+    /// The query's type name, as in `FailureOutcome::command_type`
+    pub query_type: String,
+    /// Who ran the query, as given to `execute_query_as`
+    pub actor: Option<String>,
+    pub succeeded: bool,
+}
+
+type QueryAuditSink = Box<dyn Fn(&QueryAudit) + Send>;
+
+/// Marker `MemImgProcessor::rotate_storage` appends to the new storage after the copied events
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RotationMarker {
+    /// Type name of the storage the events were copied from
+    pub from_storage: String,
+    pub events_copied: u64,
+}
+
+impl fmt::Display for RotationMarker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "storage rotated: {} events copied from {}", self.events_copied, self.from_storage)
+    }
+}
+
+/// Outcome of `MemImgProcessor::live_reload`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReloadStats {
+    /// Event version before the reload
+    pub events_before: u64,
+    /// Event version after it: the number of events in the new log
+    pub events_after: u64,
+    /// Events the new log holds beyond the old version, as when it extends the old log
+    pub new_events_applied: u64,
+}
+
+/// Forwards a committed event to one subscriber; `false` once the subscriber is gone
+type CommitSubscriber<C> = Box<dyn FnMut(&C) -> bool + Send>;
+
+type CommitObserver<C> = Box<dyn FnMut(EventId, &C) + Send>;
+
+/// Builds the state replay starts from, for systems whose fresh state is not `S::default()`
+type SystemFactory<S> = Arc<dyn Fn() -> S + Send + Sync>;
+
+/// Operational counters of a processor, as reported by `MemImgProcessor::statistics`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProcessorStatistics {
+    /// Commands applied and logged since the processor was opened
+    pub total_commands_executed: u64,
+    /// Commands rejected by a middleware, the domain, a validator or the storage
+    pub total_commands_failed: u64,
+    /// Queries answered since the processor was opened
+    pub total_queries_executed: u64,
+    pub total_queries_failed: u64,
+    /// When the last successful command was applied; `None` until one is
+    pub last_command_at: Option<DateTime<Utc>>,
+    pub uptime: Duration,
+    /// Events behind the current state, including replayed ones; same as `event_version`
+    pub system_event_count: u64,
+}
+
+/// Startup replay figures returned by `MemImgProcessor::new`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ReplayMetrics {
+    /// Events read from the log, including any rejected ones
+    pub events_replayed: u64,
+    pub replay_duration: Duration,
+    /// Earliest and latest `Command::timestamp` among the replayed events
+    pub first_event_at: Option<DateTime<Utc>>,
+    pub last_event_at: Option<DateTime<Utc>>,
+    /// Records replay passed over: unparseable lines and events rejected under `AppendThenApply`
+    pub skip_count: u64,
+}
+
+/// The shadow state a `MemImgProcessor::execute_transaction` closure works on
+pub struct TransactionContext<'a, S, C> {
+    shadow: S,
+    /// Commands applied so far, as they will be logged
+    events: Vec<C>,
+    failed: u64,
+    validators: &'a [Box<dyn SystemValidator<S> + Send>],
+    monitor: Option<&'a Arc<Mutex<InvariantMonitor<S>>>>,
+}
+
+impl<S, C> TransactionContext<'_, S, C>
 where
     S: Clone,
+    C: Command<System = S> + Clone,
+{
+    /// Apply `command` to the shadow; a rejected command leaves the shadow as it was
+    pub fn execute_command(&mut self, command: &C) -> Result<(), MemImgError> {
+        let resolved = command.resolve(&self.shadow);
+        let command = resolved.as_ref().unwrap_or(command);
+        let mut next = self.shadow.clone();
+        let checked = command
+            .apply_to(&mut next)
+            .map_err(|e| (e, "executing command"))
+            .and_then(|()| {
+                self.validators
+                    .iter()
+                    .try_for_each(|validator| validator.validate(&next))
+                    .map_err(|e| (e, "validating invariants after"))
+            })
+            .and_then(|()| match self.monitor {
+                Some(monitor) => monitor
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .check(&next)
+                    .map_err(|e| (e, "monitoring invariants after")),
+                None => Ok(()),
+            });
+        if let Err((e, context)) = checked {
+            self.failed += 1;
+            return Err(MemImgError::CommandFailure(FailureOutcome::new(e, context, std::any::type_name::<C>())));
+        }
+        self.shadow = next;
+        self.events.push(command.clone());
+        Ok(())
+    }
+
+    /// Run `query` against the shadow, seeing the commands executed so far
+    pub fn query<Q>(&self, query: &Q) -> Result<Q::Result, MemImgError>
+    where
+        Q: Query<System = S>,
+    {
+        query
+            .extract_from(&self.shadow)
+            .map_err(|e| MemImgError::CommandFailure(FailureOutcome::new(e, "executing query", std::any::type_name::<Q>())))
+    }
+
+    /// The shadow state, with the commands executed so far applied
+    pub fn system(&self) -> &S {
+        &self.shadow
+    }
+}
+
+/// Memory Image Processor - manages in-memory system state with event sourcing
+///
+/// `R` is how a failed command is undone: `ShadowCopy` for states that are `Clone`, or
+/// `SerializedRollback` for those that only serialize.
+///
+/// The processor is `Send`, and can move to another thread, whenever `S` and `E` are; `C` and `R`
+/// need not be. A custom storage is `Send` when its fields are, which for one built on a
+/// `TextConverter` means the converter must be `Send`; the event type it parses need not be.
+/// Validators, middlewares and callbacks are required to be `Send` when they are registered.
+pub struct MemImgProcessor<S, C, E, R = ShadowCopy>
+where
     C: Command<System = S>,
     E: EventStorage<Event = C>,
 {
     pub system: S,
     pub event_storage: Box<E>,
+    warnings: Vec<Warning>,
+    dropped_warnings: u64,
+    event_count: u64,
+    /// Events covered by the last snapshot and no longer in the log
+    log_base: u64,
+    commands_executed: u64,
+    commands_failed: u64,
+    /// Events appended since the processor was opened, reported by `close`
+    events_appended: u64,
+    /// Atomic because queries only borrow the processor
+    queries_executed: AtomicU64,
+    queries_failed: AtomicU64,
+    last_command_at: Option<DateTime<Utc>>,
+    /// Latest `Command::timestamp` in the log, checked by `clock_regression`
+    last_event_at: Option<DateTime<Utc>>,
+    clock_regression: ClockRegressionPolicy,
+    created_at: Instant,
+    poisoned: bool,
+    /// Set by `set_read_only`; never persisted
+    read_only: bool,
+    /// Set while `live_reload` swaps the state and log
+    reloading: bool,
+    commit_strategy: CommitStrategy,
+    #[cfg(feature = "fs")]
+    failure_dumper: Option<FailureDumper<S>>,
+    validators: Vec<Box<dyn SystemValidator<S> + Send>>,
+    monitor: Option<Arc<Mutex<InvariantMonitor<S>>>>,
+    middlewares: Vec<Box<dyn CommandMiddleware<C> + Send>>,
+    slow_command: Option<(Duration, SlowCommandSink)>,
+    query_audit: Option<QueryAuditSink>,
+    commit_subscribers: Vec<CommitSubscriber<C>>,
+    commit_observers: Vec<CommitObserver<C>>,
+    notification_order: CommitNotificationOrder,
+    system_factory: Option<SystemFactory<S>>,
+    rollback: PhantomData<fn() -> R>,
 }
 
 impl<S, C, E> MemImgProcessor<S, C, E>
@@ -34,22 +369,348 @@ where
     C: Command<System = S>,
     E: EventStorage<Event = C>,
 {
+    /// Create a new processor, replaying all events from storage, along with figures on that replay
+    ///
+    /// `new_with_snapshot` skips the events a snapshot covers, checking it against the log first.
+    pub fn new(system: S, event_storage: Box<E>) -> Result<(Self, ReplayMetrics), MemImgError> {
+        Self::open(system, 0, event_storage, CommitStrategy::default())
+    }
+
     /// Create a new processor, replaying all events from storage
-    pub fn new(mut system: S, mut event_storage: Box<E>) -> Result<Self, MemImgError> {
-        event_storage.replay(&mut |command: C| {
-            command.apply_to(&mut system)
-        }).map_err(|e| {
-            MemImgError::SystemFailure(FailureOutcome::new(
-                e,
-                "replaying events",
-                "EventStorage",
-            ))
-        })?;
+    pub fn new_simple(system: S, event_storage: Box<E>) -> Result<Self, MemImgError> {
+        Self::new(system, event_storage).map(|(processor, _)| processor)
+    }
+
+    /// Create a new processor that orders apply and append per `commit_strategy`
+    ///
+    /// The strategy also governs replay: under `AppendThenApply` the log may hold events that were
+    /// rejected when first applied, so replay skips any event that fails to apply (leaving the state
+    /// untouched) and reports it as a `RejectedEvent` warning instead of failing.
+    pub fn new_with_commit_strategy(
+        system: S,
+        event_storage: Box<E>,
+        commit_strategy: CommitStrategy,
+    ) -> Result<Self, MemImgError> {
+        Self::open(system, 0, event_storage, commit_strategy).map(|(processor, _)| processor)
+    }
+
+    /// Create a new processor, calling `on_replay_complete` with the event count once replay finishes
+    ///
+    /// The hook runs exactly once, before the processor is returned and can accept commands, so
+    /// projections fed during replay can switch from batch to live mode. It is not called if replay fails.
+    pub fn new_with_replay_hook(
+        system: S,
+        event_storage: Box<E>,
+        on_replay_complete: impl FnOnce(u64),
+    ) -> Result<Self, MemImgError> {
+        let (processor, _) = Self::new(system, event_storage)?;
+        on_replay_complete(processor.event_count);
+        Ok(processor)
+    }
+
+    /// Create a new processor whose state starts as `system_factory()`, replaying all events from storage
+    ///
+    /// The factory is kept, and `replay_from_scratch` and `validate_replay` start from a fresh
+    /// `system_factory()` instead of `S::default()`, so systems seeded at construction (say, a
+    /// bank with a fee account) rebuild to the same state.
+    pub fn new_with_system_factory(
+        system_factory: impl Fn() -> S + Send + Sync + 'static,
+        event_storage: Box<E>,
+    ) -> Result<Self, MemImgError> {
+        let (mut processor, _) = Self::new(system_factory(), event_storage)?;
+        processor.system_factory = Some(Arc::new(system_factory));
+        Ok(processor)
+    }
+
+    /// Create a new processor resuming from `snapshot` over a log that still holds every event,
+    /// skipping the events the snapshot covers
+    ///
+    /// If the log holds fewer events than `snapshot.event_count`, `policy` decides, and unless it
+    /// fails a `SnapshotMismatch` warning is buffered. `initial` is the state a `FullReplay` starts
+    /// from.
+    pub fn new_with_snapshot(
+        initial: S,
+        snapshot: Snapshot<S>,
+        mut event_storage: Box<E>,
+        policy: SnapshotMismatchPolicy,
+    ) -> Result<Self, MemImgError> {
+        let failure = |e: Box<dyn std::error::Error + Send + Sync>| MemImgError::SystemFailure(FailureOutcome::new(e, "reconciling snapshot", std::any::type_name::<S>()));
+        let logged = event_storage.version().map_err(failure)?;
+        if logged >= snapshot.event_count {
+            return Self::open_skipping(snapshot.state, 0, snapshot.event_count, event_storage, CommitStrategy::default()).map(|(processor, _)| processor);
+        }
+
+        let mismatch = SnapshotError::LogTooShort { event_count: snapshot.event_count, available: logged };
+        let (mut processor, _) = match policy {
+            SnapshotMismatchPolicy::Fail => return Err(failure(Box::new(mismatch))),
+            SnapshotMismatchPolicy::FullReplay => Self::open(initial, 0, event_storage, CommitStrategy::default())?,
+            SnapshotMismatchPolicy::TrustSnapshot => Self::open_skipping(snapshot.state, 0, logged, event_storage, CommitStrategy::default())?,
+        };
+        let message = format!("{}; resolved by {:?}", mismatch, policy);
+        processor.buffer_warnings(vec![Warning::new(WarningKind::SnapshotMismatch, logged, 0, &message)]);
+        Ok(processor)
+    }
+
+    /// Resume from `snapshot`, replaying the events logged after it
+    ///
+    /// `event_storage` must hold exactly the events that follow the snapshot, as left by
+    /// `checkpoint_and_compact`.
+    pub fn from_snapshot(snapshot: Snapshot<S>, event_storage: Box<E>) -> Result<Self, MemImgError> {
+        Self::open(snapshot.state, snapshot.event_count, event_storage, CommitStrategy::default()).map(|(processor, _)| processor)
+    }
+
+    /// The state replaying `event_storage` into `system` would produce, without creating a processor
+    ///
+    /// Replay follows the default commit strategy. Nothing is appended, and the storage is dropped
+    /// before this returns, so no writer is left open on its log.
+    pub fn preview_replay(mut system: S, mut event_storage: Box<E>) -> Result<S, MemImgError> {
+        replay_into::<_, _, _, ShadowCopy>(event_storage.as_mut(), &mut system, 0, CommitStrategy::default(), &mut Vec::new())?;
+        Ok(system)
+    }
+
+    /// New image whose log starts with genesis events reproducing `state`, such as a dump from `export_state`
+    ///
+    /// `to_commands` turns the state into commands that rebuild it from `S::default()`. They are
+    /// applied first, and unless the result serializes to the same JSON as `state` (the same
+    /// `admin::fingerprint`) nothing is written; otherwise they are appended with
+    /// `EventStorage::append_atomic` and the processor is opened by replaying them. Refuses a
+    /// storage that already holds events.
+    pub fn bootstrap_from_state(state: S, mut event_storage: Box<E>, to_commands: impl Fn(&S) -> Vec<C>) -> Result<Self, MemImgError>
+    where
+        S: Default + Serialize,
+    {
+        let failure = |e: Box<dyn std::error::Error + Send + Sync>| MemImgError::SystemFailure(FailureOutcome::new(e, "bootstrapping", std::any::type_name::<S>()));
+        let existing = event_storage.version().map_err(failure)?;
+        if existing > 0 {
+            return Err(failure(format!("the event log already holds {} events", existing).into()));
+        }
+
+        let commands = genesis_commands(&state, to_commands)?;
+        event_storage.append_atomic(&commands).map_err(failure)?;
+        Self::new_simple(S::default(), event_storage)
+    }
+}
+
+impl<S, C, E> MemImgProcessor<S, C, E, SerializedRollback>
+where
+    S: Serialize + DeserializeOwned,
+    C: Command<System = S>,
+    E: EventStorage<Event = C>,
+{
+    /// Create a processor for a state that is not `Clone`, replaying all events from storage
+    ///
+    /// Commands are applied to the live state, which is serialized before each one and restored
+    /// from those bytes if it fails. `snapshot_view` and `execute_transaction` need `Clone` and
+    /// are not available.
+    pub fn new_with_serialized_rollback(system: S, event_storage: Box<E>) -> Result<Self, MemImgError> {
+        Self::open(system, 0, event_storage, CommitStrategy::default()).map(|(processor, _)| processor)
+    }
+}
 
-        Ok(Self {
+impl<S, C, E, R> MemImgProcessor<S, C, E, R>
+where
+    C: Command<System = S>,
+    E: EventStorage<Event = C>,
+    R: RollbackStrategy<S>,
+{
+    pub(crate) fn open(
+        system: S,
+        log_base: u64,
+        event_storage: Box<E>,
+        commit_strategy: CommitStrategy,
+    ) -> Result<(Self, ReplayMetrics), MemImgError> {
+        Self::open_skipping(system, log_base, 0, event_storage, commit_strategy)
+    }
+
+    /// `open`, leaving the first `skip` logged events unapplied because `system` already reflects them
+    fn open_skipping(
+        mut system: S,
+        log_base: u64,
+        skip: u64,
+        mut event_storage: Box<E>,
+        commit_strategy: CommitStrategy,
+    ) -> Result<(Self, ReplayMetrics), MemImgError> {
+        let started = Instant::now();
+        let mut rejected = Vec::new();
+        let mut metrics = replay_into::<_, _, _, R>(event_storage.as_mut(), &mut system, skip, commit_strategy, &mut rejected)?;
+        metrics.replay_duration = started.elapsed();
+        let event_count = log_base + skip + metrics.events_replayed;
+
+        let mut warnings = event_storage.drain_warnings();
+        warnings.append(&mut rejected);
+        metrics.skip_count = warnings
+            .iter()
+            .filter(|warning| matches!(warning.kind, WarningKind::SkippedLine | WarningKind::RejectedEvent))
+            .count() as u64;
+        let mut processor = Self {
             system,
             event_storage,
-        })
+            warnings: Vec::new(),
+            dropped_warnings: 0,
+            event_count,
+            log_base,
+            commands_executed: 0,
+            commands_failed: 0,
+            events_appended: 0,
+            queries_executed: AtomicU64::new(0),
+            queries_failed: AtomicU64::new(0),
+            last_command_at: None,
+            last_event_at: metrics.last_event_at,
+            clock_regression: ClockRegressionPolicy::default(),
+            created_at: Instant::now(),
+            poisoned: false,
+            read_only: false,
+            reloading: false,
+            commit_strategy,
+            #[cfg(feature = "fs")]
+            failure_dumper: None,
+            validators: Vec::new(),
+            monitor: None,
+            middlewares: Vec::new(),
+            slow_command: None,
+            query_audit: None,
+            commit_subscribers: Vec::new(),
+            commit_observers: Vec::new(),
+            notification_order: CommitNotificationOrder::default(),
+            system_factory: None,
+            rollback: PhantomData,
+        };
+        processor.buffer_warnings(warnings);
+        #[cfg(feature = "metrics")]
+        {
+            crate::memimg::metrics::record_replay(metrics.events_replayed, metrics.replay_duration);
+            crate::memimg::metrics::record_processor_state(event_count, false);
+        }
+        Ok((processor, metrics))
+    }
+
+    pub(crate) fn buffer_warnings(&mut self, warnings: Vec<Warning>) {
+        for warning in warnings {
+            if self.warnings.len() < MAX_BUFFERED_WARNINGS {
+                self.warnings.push(warning);
+            } else {
+                self.dropped_warnings += 1;
+            }
+        }
+    }
+
+    /// Handle commands stamped earlier than the latest logged event per `policy`, instead of warning
+    ///
+    /// Only `execute_command` checks; commands in a transaction are logged as stamped.
+    pub fn with_clock_regression_policy(mut self, policy: ClockRegressionPolicy) -> Self {
+        self.clock_regression = policy;
+        self
+    }
+
+    /// Run `middleware` around every command, in registration order
+    pub fn with_middleware(mut self, middleware: impl CommandMiddleware<C> + Send + 'static) -> Self {
+        self.middlewares.push(Box::new(middleware));
+        self
+    }
+
+    /// Reject any command whose resulting state fails `validator`
+    ///
+    /// Validators run on live commands only, not during replay. Under `AppendThenApply` a command
+    /// rejected by a validator has already been logged, and replay will apply it.
+    pub fn with_validator(mut self, validator: impl SystemValidator<S> + Send + 'static) -> Self {
+        self.validators.push(Box::new(validator));
+        self
+    }
+
+    /// Check every command's resulting state against `monitor`'s validators, after the processor's own
+    ///
+    /// The monitor is shared so its violations can be read and cleared while the processor runs.
+    /// Like validators, it sees live commands only, including those in transactions, not replay.
+    pub fn with_monitor(mut self, monitor: Arc<Mutex<InvariantMonitor<S>>>) -> Self {
+        self.monitor = Some(monitor);
+        self
+    }
+
+    /// Report every command that takes longer than `threshold` to `sink`, whether it succeeds or not
+    ///
+    /// The time includes middlewares and the shadow copy, so a growing state shows up here first.
+    pub fn with_slow_command_threshold(mut self, threshold: Duration, sink: impl FnMut(&SlowCommand) + Send + 'static) -> Self {
+        self.slow_command = Some((threshold, Box::new(sink)));
+        self
+    }
+
+    /// Report every query run through `execute_query`, `execute_query_as` or a `QueryRegistry` to
+    /// `sink`, for a read audit kept apart from the event log
+    ///
+    /// Nothing about queries is logged as an event; without a sink, queries pay nothing for this.
+    pub fn with_query_audit(mut self, sink: impl Fn(&QueryAudit) + Send + 'static) -> Self {
+        self.query_audit = Some(Box::new(sink));
+        self
+    }
+
+    /// Receive every event from now on as it is committed, in log order
+    ///
+    /// Events arrive as logged, after `Command::resolve`, so applying them to a copy of the state
+    /// reproduces this processor's state. Rejected commands are not sent. See
+    /// `CommitNotificationOrder` for when this channel hears of a commit.
+    pub fn subscribe_commits(&mut self) -> Receiver<C>
+    where
+        C: Clone + Send + 'static,
+    {
+        let (sender, receiver) = mpsc::channel();
+        self.commit_subscribers.push(Box::new(move |event: &C| sender.send(event.clone()).is_ok()));
+        receiver
+    }
+
+    /// Like `subscribe_commits`, but over a tokio broadcast channel holding up to `capacity`
+    /// events, for async tasks to await commits without blocking command processing
+    ///
+    /// `resubscribe` the receiver for each further consumer; all see every event committed after
+    /// they subscribe. A consumer more than `capacity` events behind gets
+    /// `RecvError::Lagged(missed)` and resumes at the oldest event still held, instead of missing
+    /// events silently. The channel is dropped once every receiver is.
+    #[cfg(feature = "broadcast")]
+    pub fn broadcast_commits(&mut self, capacity: usize) -> tokio::sync::broadcast::Receiver<C>
+    where
+        C: Clone + Send + 'static,
+    {
+        let (sender, receiver) = tokio::sync::broadcast::channel(capacity);
+        self.commit_subscribers.push(Box::new(move |event: &C| sender.send(event.clone()).is_ok()));
+        receiver
+    }
+
+    /// Call `observer` with each event's id as it is committed, in log order, as for `subscribe_commits`
+    ///
+    /// Observers run synchronously in registration order, after the state changed and the event was
+    /// logged; whether before or after the channels is set by `with_commit_notification_order`.
+    pub fn on_commit(&mut self, observer: impl FnMut(EventId, &C) + Send + 'static) {
+        self.commit_observers.push(Box::new(observer));
+    }
+
+    /// Notify the commit channels before the `on_commit` observers, instead of after
+    pub fn with_commit_notification_order(mut self, order: CommitNotificationOrder) -> Self {
+        self.notification_order = order;
+        self
+    }
+
+    /// Tell observers and subscribers that `command` was committed as event `seq`
+    fn notify_commit(&mut self, seq: EventId, command: &C) {
+        if self.notification_order == CommitNotificationOrder::PublishFirst {
+            self.publish_commit(command);
+        }
+        for observer in self.commit_observers.iter_mut() {
+            observer(seq, command);
+        }
+        if self.notification_order == CommitNotificationOrder::ObserversFirst {
+            self.publish_commit(command);
+        }
+    }
+
+    fn publish_commit(&mut self, command: &C) {
+        // Subscribers that dropped their receiver are forgotten
+        self.commit_subscribers.retain_mut(|subscriber| subscriber(command));
+    }
+
+    /// Write a forensic dump through `dumper` if a system failure poisons the processor
+    #[cfg(feature = "fs")]
+    pub fn with_failure_dumper(mut self, dumper: FailureDumper<S>) -> Self {
+        self.failure_dumper = Some(dumper);
+        self
     }
 
     /// Execute a query against the current system state
@@ -57,57 +718,867 @@ where
     where
         Q: Query<System = S>,
     {
-        query.extract_from(&self.system).map_err(|e| {
+        self.run_query(std::any::type_name::<Q>(), None, |system| query.extract_from(system))
+    }
+
+    /// Execute a query on behalf of `actor`, who is named to the query audit hook
+    pub fn execute_query_as<Q>(&self, actor: &str, query: &Q) -> Result<Q::Result, MemImgError>
+    where
+        Q: Query<System = S>,
+    {
+        self.run_query(std::any::type_name::<Q>(), Some(actor), |system| query.extract_from(system))
+    }
+
+    /// Run `extract` as the query named `query_type`, counting it in the statistics and reporting
+    /// it to the query audit hook
+    pub(crate) fn run_query<T>(
+        &self,
+        query_type: &str,
+        actor: Option<&str>,
+        extract: impl FnOnce(&S) -> Result<T, Box<dyn std::error::Error + Send + Sync>>,
+    ) -> Result<T, MemImgError> {
+        let result = extract(&self.system).map_err(|e| {
             MemImgError::CommandFailure(FailureOutcome::new(
                 e,
                 "executing query",
-                std::any::type_name::<Q>(),
+                query_type,
             ))
-        })
+        });
+        let counter = if result.is_ok() { &self.queries_executed } else { &self.queries_failed };
+        counter.fetch_add(1, Ordering::Relaxed);
+        if let Some(sink) = &self.query_audit {
+            sink(&QueryAudit { query_type: query_type.to_string(), actor: actor.map(str::to_string), succeeded: result.is_ok() });
+        }
+        result
     }
 
     /// Execute a command with shadow-copy transaction semantics
-    pub fn execute_command(&mut self, command: C) -> Result<(), MemImgError> {
-        // Shadow copy: clone the entire system state
-        let mut shadow = self.system.clone();
+    pub fn execute_command(&mut self, command: C) -> Result<CommandReceipt, MemImgError> {
+        // A failed append may have left storage in an unknown state: refuse further writes
+        if self.poisoned {
+            return Err(MemImgError::Poisoned);
+        }
+        if self.read_only {
+            return Err(MemImgError::MaintenanceMode);
+        }
+        if self.reloading {
+            return Err(MemImgError::ReloadInProgress);
+        }
 
-        // Apply command to shadow copy
-        command.apply_to(&mut shadow).map_err(|e| {
-            MemImgError::CommandFailure(FailureOutcome::new(
-                e,
-                "executing command",
-                std::any::type_name::<C>(),
-            ))
+        let started = Instant::now();
+        let result = self.run_command(&command);
+        let elapsed = started.elapsed();
+        if let Some((threshold, sink)) = &mut self.slow_command {
+            if elapsed > *threshold {
+                sink(&SlowCommand { command_type: variant_name(&command), elapsed, threshold: *threshold });
+            }
+        }
+        #[cfg(feature = "metrics")]
+        {
+            crate::memimg::metrics::record_command(result.is_ok(), elapsed);
+            crate::memimg::metrics::record_processor_state(self.event_count, self.poisoned);
+        }
+        result
+    }
+
+    /// Execute each of `commands` in order as by `execute_command`, returning every outcome
+    ///
+    /// Unlike `execute_transaction`, a rejected command leaves the others applied and logged.
+    /// Once one poisons the processor, the rest fail with `Poisoned` without running.
+    pub fn execute_independent(&mut self, commands: Vec<C>) -> Vec<Result<(), MemImgError>> {
+        commands.into_iter().map(|command| self.execute_command(command).map(drop)).collect()
+    }
+
+    fn run_command(&mut self, command: &C) -> Result<CommandReceipt, MemImgError> {
+        for middleware in self.middlewares.iter_mut() {
+            if let Err(e) = middleware.before(command) {
+                self.commands_failed += 1;
+                return Err(e);
+            }
+        }
+
+        let result = self.apply_and_append(command);
+        for middleware in self.middlewares.iter_mut() {
+            middleware.after(command, &result);
+        }
+        result?;
+        Ok(CommandReceipt {
+            seq: self.event_count,
+            durable: self.event_storage.writes_through(),
+        })
+    }
+
+    fn apply_and_append(&mut self, command: &C) -> Result<(), MemImgError> {
+        let resolved = command.resolve(&self.system);
+        let command = resolved.as_ref().unwrap_or(command);
+        let restamped = self.check_clock(command);
+        let command = restamped.as_ref().unwrap_or(command);
+        // Shadow copy (or its serialized form) of the entire system state, put back on failure
+        let checkpoint = R::checkpoint(&self.system).map_err(|e| {
+            self.commands_failed += 1;
+            MemImgError::CommandFailure(FailureOutcome::new(e, "checkpointing state before", std::any::type_name::<C>()))
         })?;
+        match self.commit_strategy {
+            CommitStrategy::ApplyThenAppend => {
+                if let Err(e) = self.apply_checked(command) {
+                    self.roll_back(checkpoint)?;
+                    return Err(e);
+                }
+                if let Err(e) = self.event_storage.append(command).and_then(|()| self.event_storage.settle_intent(true).map(drop)) {
+                    self.commands_failed += 1;
+                    self.roll_back(checkpoint)?;
+                    return Err(self.append_failed(e, command));
+                }
+                self.events_appended += 1;
+                self.commit();
+            }
+            CommitStrategy::AppendThenApply => {
+                self.append(command)?;
+                if let Err(e) = self.apply_checked(command) {
+                    self.roll_back(checkpoint)?;
+                    // A storage keeping write-ahead intents takes the rejected event back; in any
+                    // other it stays in the log and still occupies a position
+                    match self.event_storage.settle_intent(false) {
+                        Ok(true) => self.events_appended -= 1,
+                        Ok(false) => self.event_count += 1,
+                        Err(settle) => return Err(self.append_failed(settle, command)),
+                    }
+                    return Err(e);
+                }
+                if let Err(e) = self.event_storage.settle_intent(true) {
+                    self.commands_failed += 1;
+                    self.roll_back(checkpoint)?;
+                    return Err(self.append_failed(e, command));
+                }
+                self.commit();
+            }
+        }
+        self.last_event_at = self.last_event_at.max(command.timestamp());
+        self.notify_commit(EventId(self.event_count), command);
+        Ok(())
+    }
+
+    /// `command` restamped if it is earlier than the latest logged event and the policy clamps it;
+    /// otherwise such a command is warned about
+    fn check_clock(&mut self, command: &C) -> Option<C> {
+        let (at, latest) = (command.timestamp()?, self.last_event_at?);
+        if at >= latest {
+            return None;
+        }
+        if self.clock_regression == ClockRegressionPolicy::Clamp {
+            if let Some(restamped) = command.with_timestamp(latest) {
+                return Some(restamped);
+            }
+        }
+        let message = format!("{} is stamped {}, before the latest logged event at {}", variant_name(command), at, latest);
+        self.buffer_warnings(vec![Warning::new(WarningKind::ClockRegression, self.event_count + 1, 0, &message)]);
+        None
+    }
+
+    /// Put the state taken before a command back, poisoning the processor if that fails
+    fn roll_back(&mut self, checkpoint: R::Checkpoint) -> Result<(), MemImgError> {
+        R::restore(&mut self.system, checkpoint).map_err(|e| {
+            self.poisoned = true;
+            MemImgError::SystemFailure(FailureOutcome::new(e, "rolling back", std::any::type_name::<S>()))
+        })
+    }
+
+    /// Apply `command` to the live state, then check the validators and monitor against it
+    fn apply_checked(&mut self, command: &C) -> Result<(), MemImgError> {
+        let shadow = &mut self.system;
 
-        // Serialize command before committing
-        self.event_storage.append(&command).map_err(|e| {
-            MemImgError::SystemFailure(FailureOutcome::new(
+        if let Err(e) = command.apply_to(shadow) {
+            self.commands_failed += 1;
+            return Err(MemImgError::CommandFailure(FailureOutcome::new(
                 e,
-                "serializing command",
+                "executing command",
                 std::any::type_name::<C>(),
-            ))
-        })?;
+            )));
+        }
 
-        // Commit: swap shadow copy into main system
-        self.system = shadow;
+        for validator in &self.validators {
+            if let Err(e) = validator.validate(shadow) {
+                self.commands_failed += 1;
+                return Err(MemImgError::CommandFailure(FailureOutcome::new(
+                    e,
+                    "validating invariants after",
+                    std::any::type_name::<C>(),
+                )));
+            }
+        }
+
+        if let Some(monitor) = &self.monitor {
+            if let Err(e) = monitor.lock().unwrap_or_else(PoisonError::into_inner).check(shadow) {
+                self.commands_failed += 1;
+                return Err(MemImgError::CommandFailure(FailureOutcome::new(
+                    e,
+                    "monitoring invariants after",
+                    std::any::type_name::<C>(),
+                )));
+            }
+        }
+        Ok(())
+    }
 
+    fn append(&mut self, command: &C) -> Result<(), MemImgError> {
+        if let Err(e) = self.event_storage.append(command) {
+            self.commands_failed += 1;
+            return Err(self.append_failed(e, command));
+        }
+        self.events_appended += 1;
         Ok(())
     }
 
+    /// Poison the processor after `command` could not be logged
+    #[cfg_attr(not(feature = "fs"), allow(unused_variables))]
+    fn append_failed(&mut self, e: Box<dyn std::error::Error + Send + Sync>, command: &C) -> MemImgError {
+        self.poisoned = true;
+        let storage_full = is_storage_full(e.as_ref());
+        let outcome = FailureOutcome::new(e, "serializing command", std::any::type_name::<C>());
+        let error = if storage_full { MemImgError::StorageFull(outcome) } else { MemImgError::SystemFailure(outcome) };
+        #[cfg(feature = "fs")]
+        self.dump_failure(command, &error);
+        error
+    }
+
+    /// Run `f` against a shadow copy, logging the commands it executes together if it succeeds
+    ///
+    /// Commands apply in turn to the shadow, each checked by the validators. If `f` fails nothing
+    /// is logged and the state is unchanged; otherwise its commands are appended with
+    /// `EventStorage::append_atomic` before the shadow replaces the state. Commands are always
+    /// applied before they are appended, whatever the commit strategy, and middlewares do not see them.
+    pub fn execute_transaction<F, T>(&mut self, f: F) -> Result<T, MemImgError>
+    where
+        S: Clone,
+        F: FnOnce(&mut TransactionContext<'_, S, C>) -> Result<T, MemImgError>,
+    {
+        if self.poisoned {
+            return Err(MemImgError::Poisoned);
+        }
+        if self.read_only {
+            return Err(MemImgError::MaintenanceMode);
+        }
+        if self.reloading {
+            return Err(MemImgError::ReloadInProgress);
+        }
+
+        let mut context = TransactionContext {
+            shadow: self.system.clone(),
+            events: Vec::new(),
+            failed: 0,
+            validators: &self.validators,
+            monitor: self.monitor.as_ref(),
+        };
+        let result = f(&mut context);
+        let TransactionContext { shadow, events, failed, .. } = context;
+        self.commands_failed += failed;
+        let value = result?;
+
+        if let Err(e) = self.event_storage.append_atomic(&events) {
+            self.commands_failed += events.len() as u64;
+            let error = match events.last() {
+                Some(command) => self.append_failed(e, command),
+                None => {
+                    self.poisoned = true;
+                    MemImgError::SystemFailure(FailureOutcome::new(e, "serializing command", std::any::type_name::<C>()))
+                }
+            };
+            #[cfg(feature = "metrics")]
+            crate::memimg::metrics::record_processor_state(self.event_count, true);
+            return Err(error);
+        }
+        self.system = shadow;
+        self.events_appended += events.len() as u64;
+        if !events.is_empty() {
+            self.event_count += events.len() as u64;
+            self.commands_executed += events.len() as u64;
+            self.last_command_at = Some(Utc::now());
+        }
+        let first_seq = self.event_count - events.len() as u64;
+        for (seq, command) in (first_seq + 1..).zip(&events) {
+            self.notify_commit(EventId(seq), command);
+        }
+        #[cfg(feature = "metrics")]
+        crate::memimg::metrics::record_processor_state(self.event_count, false);
+        Ok(value)
+    }
+
+    /// The state `commands` would leave, applied in turn to a copy as in `execute_transaction`
+    ///
+    /// Nothing is logged and the live state, counters and monitor are untouched, so this also
+    /// works on a read-only processor. The first command rejected, or failing a validator, fails
+    /// the whole sandbox.
+    pub fn sandbox(&self, commands: &[C]) -> Result<S, MemImgError>
+    where
+        S: Clone,
+        C: Clone,
+    {
+        let mut context = TransactionContext {
+            shadow: self.system.clone(),
+            events: Vec::new(),
+            failed: 0,
+            validators: &self.validators,
+            monitor: None,
+        };
+        for command in commands {
+            context.execute_command(command)?;
+        }
+        Ok(context.shadow)
+    }
+
+    /// Whether `command` would be accepted now, without changing anything
+    ///
+    /// A command whose `Command::check` answers is checked against the live state as it is, which
+    /// is cheap however large the state; validators are not run. Any other command is dry-run
+    /// through `sandbox`, on a copy of the state.
+    pub fn can_apply(&self, command: &C) -> Result<(), MemImgError>
+    where
+        S: Clone,
+        C: Clone,
+    {
+        match command.check(&self.system) {
+            Some(result) => result.map_err(|e| MemImgError::CommandFailure(FailureOutcome::new(e, "checking", std::any::type_name::<C>()))),
+            None => self.sandbox(std::slice::from_ref(command)).map(drop),
+        }
+    }
+
+    fn commit(&mut self) {
+        self.event_count += 1;
+        self.commands_executed += 1;
+        self.last_command_at = Some(Utc::now());
+    }
+
+    /// Command, query and event counters accumulated since the processor was opened
+    pub fn statistics(&self) -> ProcessorStatistics {
+        ProcessorStatistics {
+            total_commands_executed: self.commands_executed,
+            total_commands_failed: self.commands_failed,
+            total_queries_executed: self.queries_executed.load(Ordering::Relaxed),
+            total_queries_failed: self.queries_failed.load(Ordering::Relaxed),
+            last_command_at: self.last_command_at,
+            uptime: self.created_at.elapsed(),
+            system_event_count: self.event_count,
+        }
+    }
+
+    /// Events in the log since the last `checkpoint_and_compact`, or since the log began
+    pub fn events_since_snapshot(&self) -> u64 {
+        self.event_count - self.log_base
+    }
+
+    /// Whether a system failure has stopped this processor from accepting commands
+    pub fn is_poisoned(&self) -> bool {
+        self.poisoned
+    }
+
+    /// Flush and sync the log, then drop the storage and any lock it holds
+    ///
+    /// Returns how many events were appended since the processor was opened, which this made
+    /// durable if the storage buffered them.
+    pub fn close(mut self) -> Result<u64, MemImgError> {
+        self.event_storage
+            .flush()
+            .map_err(|e| MemImgError::SystemFailure(FailureOutcome::new(e, "closing", "EventStorage")))?;
+        Ok(self.events_appended)
+    }
+
+    /// Move the log to `new_storage`, which must be empty, and carry on over it
+    ///
+    /// Every event in the log is copied in order, then a `RotationMarker` is appended where the
+    /// new storage keeps markers. The old storage is flushed and dropped with any lock it holds.
+    /// The state, counters and hooks carry over; nothing is replayed.
+    pub fn rotate_storage<NewE>(mut self, mut new_storage: Box<NewE>) -> Result<MemImgProcessor<S, C, NewE, R>, MemImgError>
+    where
+        NewE: EventStorage<Event = C>,
+    {
+        if self.poisoned {
+            return Err(MemImgError::Poisoned);
+        }
+        let rotation_failure = |e| MemImgError::SystemFailure(FailureOutcome::new(e, "rotating storage to", std::any::type_name::<NewE>()));
+        if new_storage.version().map_err(rotation_failure)? > 0 {
+            return Err(rotation_failure("the new storage already holds events".into()));
+        }
+        let mut events_copied = 0u64;
+        self.event_storage
+            .replay(&mut |event: C| {
+                new_storage.append(&event)?;
+                events_copied += 1;
+                Ok(())
+            })
+            .map_err(|e| {
+                MemImgError::SystemFailure(
+                    FailureOutcome::new(e, "rotating storage to", std::any::type_name::<NewE>()).with_events_replayed(events_copied),
+                )
+            })?;
+        new_storage.settle_intent(true).map_err(rotation_failure)?;
+        let marker = RotationMarker { from_storage: std::any::type_name::<E>().to_string(), events_copied };
+        new_storage.append_marker(&marker.to_string()).map_err(rotation_failure)?;
+        new_storage.flush().map_err(rotation_failure)?;
+        self.event_storage.flush().map_err(|e| MemImgError::SystemFailure(FailureOutcome::new(e, "closing", "EventStorage")))?;
+        Ok(self.swap_storage(new_storage))
+    }
+
+    /// Move to `new_storage`, which must be empty, logging only genesis events that rebuild the
+    /// current state instead of the whole history, and carry on over it
+    ///
+    /// `to_commands` turns the state into those commands, as for `bootstrap_from_state`, and they
+    /// must reproduce it exactly. The old storage is flushed and dropped but left as it was, so it
+    /// still holds the full history. The state and hooks carry over, and the event count restarts
+    /// at the number of genesis events.
+    pub fn migrate_storage<NewE>(mut self, mut new_storage: Box<NewE>, to_commands: impl Fn(&S) -> Vec<C>) -> Result<MemImgProcessor<S, C, NewE, R>, MemImgError>
+    where
+        S: Default + Serialize,
+        NewE: EventStorage<Event = C>,
+    {
+        if self.poisoned {
+            return Err(MemImgError::Poisoned);
+        }
+        let migration_failure = |e| MemImgError::SystemFailure(FailureOutcome::new(e, "migrating storage to", std::any::type_name::<NewE>()));
+        if new_storage.version().map_err(migration_failure)? > 0 {
+            return Err(migration_failure("the new storage already holds events".into()));
+        }
+        let commands = genesis_commands(&self.system, to_commands)?;
+        new_storage.append_atomic(&commands).map_err(migration_failure)?;
+        new_storage.flush().map_err(migration_failure)?;
+        self.event_storage.flush().map_err(|e| MemImgError::SystemFailure(FailureOutcome::new(e, "closing", "EventStorage")))?;
+
+        let mut processor = self.swap_storage(new_storage);
+        processor.event_count = commands.len() as u64;
+        processor.log_base = 0;
+        Ok(processor)
+    }
+
+    /// This processor over `new_storage`, dropping the current storage
+    fn swap_storage<NewE>(self, new_storage: Box<NewE>) -> MemImgProcessor<S, C, NewE, R>
+    where
+        NewE: EventStorage<Event = C>,
+    {
+        let MemImgProcessor {
+            system,
+            event_storage: _,
+            warnings,
+            dropped_warnings,
+            event_count,
+            log_base,
+            commands_executed,
+            commands_failed,
+            events_appended,
+            queries_executed,
+            queries_failed,
+            last_command_at,
+            last_event_at,
+            clock_regression,
+            created_at,
+            poisoned,
+            read_only,
+            reloading,
+            commit_strategy,
+            #[cfg(feature = "fs")]
+            failure_dumper,
+            validators,
+            monitor,
+            middlewares,
+            slow_command,
+            query_audit,
+            commit_subscribers,
+            commit_observers,
+            notification_order,
+            system_factory,
+            rollback,
+        } = self;
+        MemImgProcessor {
+            system,
+            event_storage: new_storage,
+            warnings,
+            dropped_warnings,
+            event_count,
+            log_base,
+            commands_executed,
+            commands_failed,
+            events_appended,
+            queries_executed,
+            queries_failed,
+            last_command_at,
+            last_event_at,
+            clock_regression,
+            created_at,
+            poisoned,
+            read_only,
+            reloading,
+            commit_strategy,
+            #[cfg(feature = "fs")]
+            failure_dumper,
+            validators,
+            monitor,
+            middlewares,
+            slow_command,
+            query_audit,
+            commit_subscribers,
+            commit_observers,
+            notification_order,
+            system_factory,
+            rollback,
+        }
+    }
+
+    /// Refuse commands with `MemImgError::MaintenanceMode` until cleared, still answering queries
+    pub fn set_read_only(&mut self, read_only: bool) {
+        self.read_only = read_only;
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    #[cfg(feature = "fs")]
+    fn dump_failure(&self, command: &C, error: &MemImgError) {
+        if let Some(dumper) = &self.failure_dumper {
+            let context = DumpContext {
+                command_type: std::any::type_name::<C>(),
+                command_debug: format!("{:?}", command),
+                error_chain: error_chain(error),
+                event_count: self.event_count,
+                commands_executed: self.commands_executed,
+                commands_failed: self.commands_failed,
+            };
+            dumper.dump(&self.system, &context);
+        }
+    }
+
     /// Get immutable reference to system state
     pub fn system(&self) -> &S {
         &self.system
     }
+
+    /// Id of the last event applied to the state, `EventId(0)` for an empty log
+    ///
+    /// Monotonic, so suitable as an ETag, an optimistic-locking token or a cache key.
+    pub fn event_version(&self) -> EventId {
+        EventId(self.event_count)
+    }
+
+    /// Point-in-time copy of the state that later commands do not affect
+    ///
+    /// Takes a full clone of the system, the same cost as one command's shadow copy.
+    pub fn snapshot_view(&self) -> SystemView<S>
+    where
+        S: Clone,
+    {
+        SystemView::new(self.system.clone(), self.event_version())
+    }
+
+    /// Stream the whole state to `writer` as one line of JSON, for ETL and other bulk readers
+    ///
+    /// The state is serialized straight into `writer`, never whole into memory, so buffer
+    /// `writer` when it is a file or socket. Its shape is whatever `S`'s `Serialize` produces.
+    pub fn export_state<W: Write>(&self, mut writer: W) -> Result<(), MemImgError>
+    where
+        S: Serialize,
+    {
+        let failure = |e| MemImgError::SystemFailure(FailureOutcome::new(e, "exporting", std::any::type_name::<S>()));
+        serde_json::to_writer(&mut writer, &self.system).map_err(|e| failure(Box::new(e)))?;
+        writer.write_all(b"\n").and_then(|_| writer.flush()).map_err(|e| failure(Box::new(e)))
+    }
+
+    /// Get mutable reference to system state
+    ///
+    /// Changes made here bypass the event log and will not survive a restart.
+    pub fn system_mut(&mut self) -> &mut S {
+        &mut self.system
+    }
+
+    /// Snapshot the state, then drop the events it covers from the log
+    ///
+    /// The snapshot is serialized in full before anything is written, then written to
+    /// `snapshot_writer` in one call and flushed; the log is only truncated after that succeeds.
+    /// If truncation fails, snapshot and full log both remain and either can restore the state.
+    /// Restart from the snapshot with `from_snapshot`. Whatever the state serializes survives,
+    /// including fields no command could rebuild, which `migrate_storage` would refuse.
+    pub fn checkpoint_and_compact<W: Write>(&mut self, format: &SnapshotFormat, mut snapshot_writer: W) -> Result<CompactionResult, MemImgError>
+    where
+        S: Serialize,
+    {
+        self.checkpoint_and_compact_with(format, |snapshot| snapshot_writer.write_all(snapshot).and_then(|_| snapshot_writer.flush()))
+    }
+
+    /// Like `checkpoint_and_compact`, but hand the serialized snapshot to `persist`, which must
+    /// make it durable before returning; the log is only truncated after it succeeds
+    pub fn checkpoint_and_compact_with<F>(&mut self, format: &SnapshotFormat, persist: F) -> Result<CompactionResult, MemImgError>
+    where
+        S: Serialize,
+        F: FnOnce(&[u8]) -> std::io::Result<()>,
+    {
+        let failure = |e, context: &str| MemImgError::SystemFailure(FailureOutcome::new(e, context, "EventStorage"));
+        #[cfg(feature = "metrics")]
+        let started = Instant::now();
+
+        let mut snapshot = Vec::new();
+        format
+            .write(&mut snapshot, &self.system, self.event_count)
+            .map_err(|e| failure(e, "serializing snapshot for"))?;
+        persist(&snapshot).map_err(|e| failure(Box::new(e), "writing snapshot for"))?;
+
+        let first_kept = EventId(self.event_count - self.log_base + 1);
+        let events_removed = self
+            .event_storage
+            .truncate_before(first_kept)
+            .map_err(|e| failure(e, "compacting"))?;
+        self.log_base = self.event_count;
+        #[cfg(feature = "metrics")]
+        crate::memimg::metrics::record_snapshot(started.elapsed());
+
+        Ok(CompactionResult {
+            snapshot_size_bytes: snapshot.len() as u64,
+            events_removed,
+        })
+    }
+
+    /// Replay the whole event log into a fresh state and compare the result with the live state
+    ///
+    /// Only meaningful while the log holds every event, i.e. before any `checkpoint_and_compact`.
+    pub fn validate_replay(&mut self) -> Result<ReplayValidationResult, MemImgError>
+    where
+        S: Default + PartialEq + StateDiff,
+    {
+        let replayed_system = self.replay_from_scratch()?;
+        let diverged = replayed_system != self.system;
+        Ok(ReplayValidationResult {
+            diverged,
+            live_accounts: self.system.entity_count(),
+            replayed_accounts: replayed_system.entity_count(),
+            divergent_keys: if diverged { self.system.diff_keys(&replayed_system) } else { Vec::new() },
+        })
+    }
+
+    /// Replay the whole event log into a fresh state, leaving the live state untouched
+    ///
+    /// The fresh state comes from the factory given to `new_with_system_factory`, or is
+    /// `S::default()`. Like `validate_replay`, this only rebuilds the live state while the log
+    /// holds every event.
+    pub fn replay_from_scratch(&mut self) -> Result<S, MemImgError>
+    where
+        S: Default,
+    {
+        let mut replayed_system = self.system_factory.as_ref().map_or_else(S::default, |factory| factory());
+        let mut rejected = Vec::new();
+        replay_into::<_, _, _, R>(self.event_storage.as_mut(), &mut replayed_system, 0, self.commit_strategy, &mut rejected)?;
+        let mut warnings = self.event_storage.drain_warnings();
+        warnings.append(&mut rejected);
+        self.buffer_warnings(warnings);
+        Ok(replayed_system)
+    }
+
+    /// Switch to the log in `new_storage`, replacing the state with one rebuilt from it alone, as
+    /// when a new deployment writes to another log
+    ///
+    /// The new log is replayed into a fresh state, from the `new_with_system_factory` factory or
+    /// `S::default()`, before anything is swapped, so a failed replay leaves the processor as it
+    /// was. The old storage is flushed and dropped with any lock it holds. Commands are refused
+    /// with `ReloadInProgress` until the swap completes, and from then on if the reload panics.
+    pub fn live_reload(&mut self, new_storage: Box<E>) -> Result<ReloadStats, MemImgError>
+    where
+        S: Default,
+    {
+        if self.poisoned {
+            return Err(MemImgError::Poisoned);
+        }
+        self.reloading = true;
+        let reloaded = self.reload_from(new_storage);
+        self.reloading = false;
+        reloaded
+    }
+
+    fn reload_from(&mut self, mut new_storage: Box<E>) -> Result<ReloadStats, MemImgError>
+    where
+        S: Default,
+    {
+        let mut system = self.system_factory.as_ref().map_or_else(S::default, |factory| factory());
+        let mut rejected = Vec::new();
+        let metrics = replay_into::<_, _, _, R>(new_storage.as_mut(), &mut system, 0, self.commit_strategy, &mut rejected)?;
+        self.event_storage.flush().map_err(|e| MemImgError::SystemFailure(FailureOutcome::new(e, "closing", "EventStorage")))?;
+
+        let events_before = self.event_count;
+        let mut warnings = new_storage.drain_warnings();
+        warnings.append(&mut rejected);
+        self.system = system;
+        self.event_storage = new_storage;
+        self.event_count = metrics.events_replayed;
+        self.last_event_at = metrics.last_event_at;
+        self.log_base = 0;
+        self.buffer_warnings(warnings);
+        Ok(ReloadStats {
+            events_before,
+            events_after: self.event_count,
+            new_events_applied: self.event_count.saturating_sub(events_before),
+        })
+    }
+
+    /// Replay the event log into `target`, a state of another type, leaving the live state untouched
+    ///
+    /// `adapter` maps each logged event to a command on the target, or to `None` to skip it; this
+    /// bootstraps read models from the log without a second storage. Under `AppendThenApply`,
+    /// adapted commands that fail are skipped, as their source events are on replay.
+    pub fn replay_into_new<S2, C2>(&mut self, mut target: S2, adapter: impl Fn(C) -> Option<C2>) -> Result<S2, MemImgError>
+    where
+        S2: Clone,
+        C2: Command<System = S2>,
+    {
+        let commit_strategy = self.commit_strategy;
+        let mut event_count = 0u64;
+        let mut failed_event = None;
+        let replayed = self.event_storage.replay(&mut |command: C| {
+            if let Some(command) = adapter(command) {
+                match commit_strategy {
+                    CommitStrategy::ApplyThenAppend => command.apply_to(&mut target).inspect_err(|_| {
+                        failed_event = Some(FailedEvent { index: event_count + 1, event: format!("{:?}", command) });
+                    })?,
+                    CommitStrategy::AppendThenApply => {
+                        let mut shadow = target.clone();
+                        if command.apply_to(&mut shadow).is_ok() {
+                            target = shadow;
+                        }
+                    }
+                }
+            }
+            event_count += 1;
+            Ok(())
+        });
+        replayed.map_err(|e| {
+            MemImgError::SystemFailure(
+                FailureOutcome::new(e, "replaying events into", std::any::type_name::<C2>())
+                    .with_events_replayed(event_count)
+                    .with_failed_event(failed_event),
+            )
+        })?;
+        Ok(target)
+    }
+
+    /// Call `visitor` with every event in the log, in order, without touching the state
+    ///
+    /// Returns how many events were visited. Events compacted into a snapshot are gone from the
+    /// log and are not visited.
+    pub fn for_each_event<F>(&mut self, mut visitor: F) -> Result<u64, MemImgError>
+    where
+        F: FnMut(&C),
+    {
+        self.for_each_event_with_index(|_, event| visitor(event))
+    }
+
+    /// Like `for_each_event`, also passing each event's sequence number, as in `CommandReceipt::seq`
+    pub fn for_each_event_with_index<F>(&mut self, mut visitor: F) -> Result<u64, MemImgError>
+    where
+        F: FnMut(u64, &C),
+    {
+        let mut event_count = 0u64;
+        let log_base = self.log_base;
+        let visited = self.event_storage.replay(&mut |event: C| {
+            event_count += 1;
+            visitor(log_base + event_count, &event);
+            Ok(())
+        });
+        let warnings = self.event_storage.drain_warnings();
+        self.buffer_warnings(warnings);
+        visited.map_err(|e| {
+            MemImgError::SystemFailure(FailureOutcome::new(e, "visiting events of", std::any::type_name::<C>()).with_events_replayed(event_count))
+        })?;
+        Ok(event_count)
+    }
+
+    /// Non-fatal anomalies found while replaying events in `new`, `validate_replay`,
+    /// `replay_from_scratch` or `for_each_event`, and clock regressions in live commands
+    pub fn warnings(&self) -> &[Warning] {
+        &self.warnings
+    }
+
+    /// Number of warnings beyond `MAX_BUFFERED_WARNINGS` that were counted but not buffered
+    pub fn dropped_warnings(&self) -> u64 {
+        self.dropped_warnings
+    }
 }
 
-impl<S, C, E> Drop for MemImgProcessor<S, C, E>
+/// The commands `to_commands` gives for `state`, checked to rebuild it from `S::default()` to the
+/// same JSON
+fn genesis_commands<S, C>(state: &S, to_commands: impl Fn(&S) -> Vec<C>) -> Result<Vec<C>, MemImgError>
 where
-    S: Clone,
+    S: Default + Serialize,
     C: Command<System = S>,
-    E: EventStorage<Event = C>,
 {
-    fn drop(&mut self) {
-        // EventStorage cleanup handled by its own Drop implementation
+    let failure = |e: Box<dyn std::error::Error + Send + Sync>| MemImgError::SystemFailure(FailureOutcome::new(e, "bootstrapping", std::any::type_name::<S>()));
+    let commands = to_commands(state);
+    let mut rebuilt = S::default();
+    for (index, command) in commands.iter().enumerate() {
+        command.apply_to(&mut rebuilt).map_err(|e| {
+            MemImgError::SystemFailure(
+                FailureOutcome::new(e, "bootstrapping", std::any::type_name::<C>())
+                    .with_failed_event(Some(FailedEvent { index: index as u64 + 1, event: format!("{:?}", command) })),
+            )
+        })?;
     }
+    let json = |system: &S| serde_json::to_vec(system).map_err(|e| failure(Box::new(e)));
+    if json(&rebuilt)? != json(state)? {
+        return Err(failure("the genesis events do not reproduce the state".into()));
+    }
+    Ok(commands)
 }
+
+/// Replay every stored event into `system`, returning how many were read and their time span
+///
+/// Under `AppendThenApply`, events that fail to apply are skipped and recorded in `rejected`.
+fn replay_into<S, C, E, R>(
+    event_storage: &mut E,
+    system: &mut S,
+    skip: u64,
+    commit_strategy: CommitStrategy,
+    rejected: &mut Vec<Warning>,
+) -> Result<ReplayMetrics, MemImgError>
+where
+    C: Command<System = S>,
+    E: EventStorage<Event = C>,
+    R: RollbackStrategy<S>,
+{
+    let mut event_count = 0u64;
+    let mut failed_event = None;
+    let mut span: Option<(DateTime<Utc>, DateTime<Utc>)> = None;
+    let replayed = event_storage.replay(&mut |command: C| {
+        if let Some(at) = command.timestamp() {
+            span = Some(span.map_or((at, at), |(first, last)| (first.min(at), last.max(at))));
+        }
+        if event_count < skip {
+            event_count += 1;
+            return Ok(());
+        }
+        match commit_strategy {
+            CommitStrategy::ApplyThenAppend => command.apply_to(system).inspect_err(|_| {
+                failed_event = Some(FailedEvent { index: event_count + 1, event: format!("{:?}", command) });
+            })?,
+            CommitStrategy::AppendThenApply => {
+                let checkpoint = R::checkpoint(system)?;
+                if let Err(e) = command.apply_to(system) {
+                    R::restore(system, checkpoint)?;
+                    rejected.push(Warning::new(
+                        WarningKind::RejectedEvent,
+                        event_count + 1,
+                        0,
+                        &e.to_string(),
+                    ));
+                }
+            }
+        }
+        event_count += 1;
+        Ok(())
+    });
+    replayed.map_err(|e| {
+        MemImgError::SystemFailure(FailureOutcome::new(
+            e,
+            "replaying events",
+            "EventStorage",
+        ).with_events_replayed(event_count).with_failed_event(failed_event))
+    })?;
+    Ok(ReplayMetrics {
+        events_replayed: event_count - skip,
+        first_event_at: span.map(|(first, _)| first),
+        last_event_at: span.map(|(_, last)| last),
+        ..ReplayMetrics::default()
+    })
+}
+
@@ -0,0 +1,129 @@
+#[cfg(feature = "fs")]
+use crate::memimg::event_id::EventId;
+use crate::memimg::storage::EventStorage;
+#[cfg(feature = "fs")]
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+#[cfg(feature = "fs")]
+use std::path::{Path, PathBuf};
+
+/// Read model folded from a log's events, independently of the processor's state
+pub trait Projection<C> {
+    fn apply(&mut self, event: &C);
+}
+
+/// Build `P` by replaying every event in `storage` from the start
+pub fn build_projection<P, C, E>(storage: &mut E) -> Result<P, Box<dyn std::error::Error + Send + Sync>>
+where
+    P: Projection<C> + Default,
+    E: EventStorage<Event = C>,
+{
+    let mut projection = P::default();
+    storage.replay(&mut |event| {
+        projection.apply(&event);
+        Ok(())
+    })?;
+    Ok(projection)
+}
+
+/// Projection that saves its state and watermark to `state_file`, so reopening it only
+/// replays the events appended since
+#[cfg(feature = "fs")]
+pub struct PersistentProjection<P, C>
+where
+    P: Projection<C> + Serialize + DeserializeOwned,
+{
+    projection: P,
+    state_file: PathBuf,
+    watermark: EventId,
+    _phantom: std::marker::PhantomData<fn(&C)>,
+}
+
+#[cfg(feature = "fs")]
+#[derive(Serialize, Deserialize)]
+struct Saved<P> {
+    watermark: EventId,
+    state: P,
+}
+
+#[cfg(feature = "fs")]
+impl<P, C> PersistentProjection<P, C>
+where
+    P: Projection<C> + Serialize + DeserializeOwned + Default,
+{
+    /// Load the projection saved in `state_file`, or start empty if there is none, then catch up with `storage`
+    pub fn open<E>(state_file: impl AsRef<Path>, storage: &mut E) -> Result<Self, Box<dyn std::error::Error + Send + Sync>>
+    where
+        E: EventStorage<Event = C>,
+    {
+        let state_file = state_file.as_ref().to_path_buf();
+        let saved = match std::fs::read_to_string(&state_file) {
+            Ok(text) => serde_json::from_str(&text)
+                .map_err(|e| format!("{} is not a saved projection: {}", state_file.display(), e))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Saved { watermark: EventId(0), state: P::default() },
+            Err(e) => return Err(format!("cannot read {}: {}", state_file.display(), e).into()),
+        };
+        let mut projection = Self {
+            projection: saved.state,
+            state_file,
+            watermark: saved.watermark,
+            _phantom: std::marker::PhantomData,
+        };
+        projection.replay_from_watermark(storage)?;
+        Ok(projection)
+    }
+}
+
+#[cfg(feature = "fs")]
+impl<P, C> PersistentProjection<P, C>
+where
+    P: Projection<C> + Serialize + DeserializeOwned,
+{
+    /// Apply the events of `storage` past the watermark, saving once at the end
+    pub fn replay_from_watermark<E>(&mut self, storage: &mut E) -> Result<u64, Box<dyn std::error::Error + Send + Sync>>
+    where
+        E: EventStorage<Event = C>,
+    {
+        let mut seen = 0;
+        let mut applied = 0;
+        storage.replay(&mut |event| {
+            seen += 1;
+            if seen > self.watermark.as_u64() {
+                self.projection.apply(&event);
+                applied += 1;
+            }
+            Ok(())
+        })?;
+        if applied > 0 {
+            self.watermark = EventId(self.watermark.as_u64() + applied);
+            self.save()?;
+        }
+        Ok(applied)
+    }
+
+    /// Apply the event following the watermark and save the result
+    pub fn apply(&mut self, event: &C) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        self.projection.apply(event);
+        self.watermark = self.watermark.next();
+        self.save()
+    }
+
+    pub fn projection(&self) -> &P {
+        &self.projection
+    }
+
+    /// Id of the last event applied
+    pub fn watermark(&self) -> EventId {
+        self.watermark
+    }
+
+    /// Write state and watermark together through a temporary file and rename, so a crash keeps the previous save
+    fn save(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let saved = Saved { watermark: self.watermark, state: &self.projection };
+        let temp_path = self.state_file.with_extension("tmp");
+        std::fs::write(&temp_path, serde_json::to_vec(&saved)?)
+            .map_err(|e| format!("cannot write {}: {}", temp_path.display(), e))?;
+        std::fs::rename(&temp_path, &self.state_file)
+            .map_err(|e| format!("cannot replace {}: {}", self.state_file.display(), e))?;
+        Ok(())
+    }
+}
@@ -0,0 +1,75 @@
+use crate::memimg::error::MemImgError;
+use crate::memimg::processor::{Command, MemImgProcessor, Query};
+use crate::memimg::storage::EventStorage;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// A query deserialized from its parameters, waiting for the state to run against
+type ParsedQuery<S> = Box<dyn FnOnce(&S) -> Result<Value, Box<dyn std::error::Error + Send + Sync>>>;
+
+struct Registration<S> {
+    query_type: &'static str,
+    parse: Box<dyn Fn(Value) -> Result<ParsedQuery<S>, serde_json::Error> + Send + Sync>,
+}
+
+/// Queries over `S` invoked by name, with JSON parameters and JSON results
+///
+/// Lets remote callers run queries generically even though each query has its own result type.
+pub struct QueryRegistry<S> {
+    queries: BTreeMap<String, Registration<S>>,
+}
+
+impl<S> Default for QueryRegistry<S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S> QueryRegistry<S> {
+    pub fn new() -> Self {
+        Self { queries: BTreeMap::new() }
+    }
+
+    /// Register `Q` under `name`, replacing any query registered under it before
+    ///
+    /// Its parameters are the JSON form of `Q` itself, so a unit struct query takes `null`.
+    pub fn register<Q>(mut self, name: &str) -> Self
+    where
+        Q: Query<System = S> + DeserializeOwned + 'static,
+        Q::Result: Serialize,
+    {
+        let parse = |params: Value| -> Result<ParsedQuery<S>, serde_json::Error> {
+            let query: Q = serde_json::from_value(params)?;
+            Ok(Box::new(move |system: &S| Ok(serde_json::to_value(query.extract_from(system)?)?)))
+        };
+        let registration = Registration { query_type: std::any::type_name::<Q>(), parse: Box::new(parse) };
+        self.queries.insert(name.to_string(), registration);
+        self
+    }
+
+    /// Registered query names, sorted
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.queries.keys().map(String::as_str)
+    }
+
+    /// Run the query registered as `name` with `params` against `processor`'s state
+    ///
+    /// Fails with `UnknownQuery` for an unregistered name and `InvalidQueryParameters` when
+    /// `params` do not fit the query; the query's own failures are command failures, as with
+    /// `MemImgProcessor::execute_query`.
+    pub fn execute_json<C, E>(&self, processor: &MemImgProcessor<S, C, E>, name: &str, params: Value) -> Result<Value, MemImgError>
+    where
+        S: Clone,
+        C: Command<System = S>,
+        E: EventStorage<Event = C>,
+    {
+        let registration = self.queries.get(name).ok_or_else(|| MemImgError::UnknownQuery(name.to_string()))?;
+        let query = (registration.parse)(params).map_err(|e| MemImgError::InvalidQueryParameters {
+            query: name.to_string(),
+            message: e.to_string(),
+        })?;
+        processor.run_query(registration.query_type, None, query)
+    }
+}
@@ -0,0 +1,47 @@
+use crate::memimg::processor::Query;
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+
+/// `Fn(&Q, &S) -> Result<Q::Result, _>` for the query type `Q` it is keyed by
+type Handler<Q, S> = Box<dyn Fn(&Q, &S) -> Result<<Q as Query>::Result, Box<dyn std::error::Error + Send + Sync>>>;
+
+/// Answers queries over `S` with handlers registered per query type
+///
+/// Lets a read model override how particular queries are answered; queries without a handler
+/// fall through to their own `extract_from`.
+pub struct QueryRouter<S> {
+    handlers: HashMap<TypeId, Box<dyn Any>>,
+    _system: std::marker::PhantomData<fn(&S)>,
+}
+
+impl<S: 'static> Default for QueryRouter<S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S: 'static> QueryRouter<S> {
+    pub fn new() -> Self {
+        Self { handlers: HashMap::new(), _system: std::marker::PhantomData }
+    }
+
+    /// Answer `Q` with `handler` from now on, replacing any handler registered for it before
+    pub fn register<Q>(&mut self, handler: impl Fn(&Q, &S) -> Result<Q::Result, Box<dyn std::error::Error + Send + Sync>> + 'static)
+    where
+        Q: Query<System = S> + 'static,
+    {
+        let handler: Handler<Q, S> = Box::new(handler);
+        self.handlers.insert(TypeId::of::<Q>(), Box::new(handler));
+    }
+
+    /// Answer `query` against `system` with its registered handler, or with `extract_from` if it has none
+    pub fn route<Q>(&self, query: &Q, system: &S) -> Result<Q::Result, Box<dyn std::error::Error + Send + Sync>>
+    where
+        Q: Query<System = S> + 'static,
+    {
+        match self.handlers.get(&TypeId::of::<Q>()).and_then(|handler| handler.downcast_ref::<Handler<Q, S>>()) {
+            Some(handler) => handler(query, system),
+            None => query.extract_from(system),
+        }
+    }
+}
@@ -0,0 +1,78 @@
+use crate::memimg::error::{FailureOutcome, MemImgError};
+use crate::memimg::processor::{Command, MemImgProcessor, Query};
+use crate::memimg::storage::EventStorage;
+use std::sync::mpsc::{Receiver, TryRecvError};
+
+/// Query-only copy of a leader processor's state, kept current from its commit channel
+///
+/// The replica has no log of its own: it replays the leader's log once when created, then
+/// applies each event the leader commits. Queries first apply whatever has arrived, so they see
+/// every commit made before the query started. It can be moved to another thread.
+pub struct ReadReplica<S, C> {
+    system: S,
+    commits: Receiver<C>,
+    events_applied: u64,
+}
+
+impl<S, C> ReadReplica<S, C>
+where
+    S: Clone + Default,
+    C: Command<System = S> + Clone + Send + 'static,
+{
+    /// Subscribe to `leader`'s commits and rebuild its state from its log
+    ///
+    /// Needs the log to hold every event, as `MemImgProcessor::replay_from_scratch` does.
+    pub fn follow<E>(leader: &mut MemImgProcessor<S, C, E>) -> Result<Self, MemImgError>
+    where
+        E: EventStorage<Event = C>,
+    {
+        // Borrowing the leader mutably keeps commits out between the subscription and the replay
+        let commits = leader.subscribe_commits();
+        let system = leader.replay_from_scratch()?;
+        Ok(Self {
+            system,
+            commits,
+            events_applied: leader.event_version().as_u64(),
+        })
+    }
+
+    /// Apply every commit received so far, returning how many there were
+    ///
+    /// Once the leader is dropped the replica keeps answering queries from its last state.
+    pub fn catch_up(&mut self) -> Result<u64, MemImgError> {
+        let mut applied = 0;
+        loop {
+            let event = match self.commits.try_recv() {
+                Ok(event) => event,
+                Err(TryRecvError::Empty | TryRecvError::Disconnected) => return Ok(applied),
+            };
+            // The leader only sends events that applied to the same state, so this failing is a bug
+            event.apply_to(&mut self.system).map_err(|e| {
+                MemImgError::SystemFailure(FailureOutcome::new(e, "applying replicated event", std::any::type_name::<C>()))
+            })?;
+            self.events_applied += 1;
+            applied += 1;
+        }
+    }
+
+    /// Catch up with the leader, then run `query` against the replicated state
+    pub fn execute_query<Q>(&mut self, query: &Q) -> Result<Q::Result, MemImgError>
+    where
+        Q: Query<System = S>,
+    {
+        self.catch_up()?;
+        query.extract_from(&self.system).map_err(|e| {
+            MemImgError::CommandFailure(FailureOutcome::new(e, "executing query", std::any::type_name::<Q>()))
+        })
+    }
+
+    /// The replicated state as of the last catch-up
+    pub fn system(&self) -> &S {
+        &self.system
+    }
+
+    /// Events behind the replicated state, matching the leader's `event_version` once caught up
+    pub fn events_applied(&self) -> u64 {
+        self.events_applied
+    }
+}
@@ -0,0 +1,222 @@
+use crate::memimg::error::{FailureOutcome, MemImgError};
+use crate::memimg::processor::{Command, MemImgProcessor, Query};
+use crate::memimg::rollback::RollbackStrategy;
+use crate::memimg::storage::EventStorage;
+use serde::{Deserialize, Serialize};
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+
+/// A committed event as streamed to standbys, with its sequence number as in `CommandReceipt::seq`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReplicatedEvent<C> {
+    pub seq: u64,
+    pub event: C,
+}
+
+/// What a standby asks of its `ReplicationSource`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ReplicaRequest {
+    /// Resend the events numbered `from_seq` through `through_seq`, read back from the primary's log
+    CatchUp { from_seq: u64, through_seq: u64 },
+}
+
+/// The standby's end of a replication link: events in, requests out
+///
+/// Both messages serialize, so a link can be carried over a socket by forwarding them.
+pub struct ReplicaEndpoint<C> {
+    pub events: Receiver<ReplicatedEvent<C>>,
+    pub requests: Sender<ReplicaRequest>,
+}
+
+/// The primary's end of a replication link
+struct ReplicaLink<C> {
+    events: Sender<ReplicatedEvent<C>>,
+    requests: Receiver<ReplicaRequest>,
+}
+
+/// Streams a primary processor's committed events, numbered, to hot standbys
+///
+/// The source subscribes to the primary's commits, so it sees every event in commit order.
+/// `pump` forwards what was committed since the last call and answers standbys' catch-up requests
+/// from the primary's log; call it after commands, or on a timer, to keep standbys close behind.
+/// Events compacted out of the log cannot be resent, so a standby further behind than the last
+/// compaction must start again from a snapshot.
+pub struct ReplicationSource<C> {
+    commits: Receiver<C>,
+    /// Sequence number of the last commit forwarded
+    head: u64,
+    links: Vec<ReplicaLink<C>>,
+}
+
+impl<C> ReplicationSource<C>
+where
+    C: Command + Clone + Send + 'static,
+{
+    /// Stream the commits `primary` makes from now on
+    pub fn attach<E, R>(primary: &mut MemImgProcessor<C::System, C, E, R>) -> Self
+    where
+        E: EventStorage<Event = C>,
+        R: RollbackStrategy<C::System>,
+    {
+        Self { commits: primary.subscribe_commits(), head: primary.event_version().as_u64(), links: Vec::new() }
+    }
+
+    /// Link a standby whose state ends at event `last_applied_seq`; the events after it are sent
+    /// from the log on the next `pump`
+    pub fn connect(&mut self, last_applied_seq: u64) -> ReplicaEndpoint<C> {
+        let (event_sender, events) = mpsc::channel();
+        let (requests, request_receiver) = mpsc::channel();
+        // Queued as the standby's own request, so a new link is served like any other gap
+        let _ = requests.send(ReplicaRequest::CatchUp { from_seq: last_applied_seq + 1, through_seq: u64::MAX });
+        self.links.push(ReplicaLink { events: event_sender, requests: request_receiver });
+        ReplicaEndpoint { events, requests }
+    }
+
+    /// Forward the commits made since the last call to every standby, after resending the ranges
+    /// they asked for, returning how many events were sent in all
+    ///
+    /// A standby that went away is unlinked. Ranges end at the last commit forwarded.
+    pub fn pump<E, R>(&mut self, primary: &mut MemImgProcessor<C::System, C, E, R>) -> Result<u64, MemImgError>
+    where
+        E: EventStorage<Event = C>,
+        R: RollbackStrategy<C::System>,
+    {
+        let mut committed = Vec::new();
+        while let Ok(event) = self.commits.try_recv() {
+            self.head += 1;
+            committed.push(ReplicatedEvent { seq: self.head, event });
+        }
+
+        let mut sent = 0u64;
+        let mut links = Vec::with_capacity(self.links.len());
+        for link in std::mem::take(&mut self.links) {
+            let mut connected = true;
+            loop {
+                match link.requests.try_recv() {
+                    Ok(ReplicaRequest::CatchUp { from_seq, through_seq }) => {
+                        let through_seq = through_seq.min(self.head);
+                        let mut resent = Vec::new();
+                        primary.for_each_event_with_index(|seq, event| {
+                            if (from_seq..=through_seq).contains(&seq) {
+                                resent.push(ReplicatedEvent { seq, event: event.clone() });
+                            }
+                        })?;
+                        for event in resent {
+                            connected &= link.events.send(event).is_ok();
+                            sent += 1;
+                        }
+                    }
+                    Err(TryRecvError::Empty) => break,
+                    Err(TryRecvError::Disconnected) => {
+                        connected = false;
+                        break;
+                    }
+                }
+            }
+            for event in &committed {
+                connected &= link.events.send(event.clone()).is_ok();
+                sent += 1;
+            }
+            if connected {
+                links.push(link);
+            }
+        }
+        self.links = links;
+        Ok(sent)
+    }
+
+    /// Standbys linked as of the last `pump`
+    pub fn replicas(&self) -> usize {
+        self.links.len()
+    }
+}
+
+/// Hot standby applying a primary's events to its own processor, over its own log
+///
+/// The standby's processor stays read-only, answering queries, until it is promoted. Each event
+/// must be the one after the last applied: older ones are dropped as duplicates, and a later one
+/// reveals a gap, so it is dropped too and the range up to it requested from the source again.
+pub struct ReplicaProcessor<S, C, E>
+where
+    C: Command<System = S>,
+    E: EventStorage<Event = C>,
+{
+    processor: MemImgProcessor<S, C, E>,
+    endpoint: ReplicaEndpoint<C>,
+    /// Highest sequence number asked for in a catch-up not yet fully received
+    requested_through: u64,
+}
+
+impl<S, C, E> ReplicaProcessor<S, C, E>
+where
+    S: Clone,
+    C: Command<System = S>,
+    E: EventStorage<Event = C>,
+{
+    /// Standby over `processor`, already replayed from its own log, receiving through `endpoint`
+    ///
+    /// Connect the endpoint with `processor.event_version()`, so the source resends what the
+    /// standby's log is missing.
+    pub fn new(mut processor: MemImgProcessor<S, C, E>, endpoint: ReplicaEndpoint<C>) -> Self {
+        processor.set_read_only(true);
+        let requested_through = processor.event_version().as_u64();
+        Self { processor, endpoint, requested_through }
+    }
+
+    /// Apply every event received so far, in sequence, returning how many were applied
+    pub fn sync(&mut self) -> Result<u64, MemImgError> {
+        let mut applied = 0;
+        while let Ok(ReplicatedEvent { seq, event }) = self.endpoint.events.try_recv() {
+            let last_applied = self.last_applied_seq();
+            if seq <= last_applied {
+                continue;
+            }
+            if seq > last_applied + 1 {
+                if seq > self.requested_through {
+                    let from_seq = last_applied.max(self.requested_through) + 1;
+                    // A source that is gone cannot answer; the standby stays where it is
+                    let _ = self.endpoint.requests.send(ReplicaRequest::CatchUp { from_seq, through_seq: seq });
+                    self.requested_through = seq;
+                }
+                continue;
+            }
+            // The primary committed this event on the same state, so this failing means divergence
+            self.processor.set_read_only(false);
+            let outcome = self.processor.execute_command(event);
+            self.processor.set_read_only(true);
+            outcome.map_err(|e| {
+                MemImgError::SystemFailure(FailureOutcome::new(Box::new(e), "applying replicated event", std::any::type_name::<C>()))
+            })?;
+            applied += 1;
+        }
+        Ok(applied)
+    }
+
+    /// Sequence number of the last event applied, that of the standby's `event_version`
+    pub fn last_applied_seq(&self) -> u64 {
+        self.processor.event_version().as_u64()
+    }
+
+    /// Sync, then run `query` against the standby's state
+    pub fn execute_query<Q>(&mut self, query: &Q) -> Result<Q::Result, MemImgError>
+    where
+        Q: Query<System = S>,
+    {
+        self.sync()?;
+        self.processor.execute_query(query)
+    }
+
+    /// The standby's processor, for queries and inspection
+    pub fn processor(&self) -> &MemImgProcessor<S, C, E> {
+        &self.processor
+    }
+
+    /// Apply what has arrived and hand over the processor to take writes, as the new primary
+    ///
+    /// The standby's log becomes the primary log; a file log keeps the lock taken when the standby
+    /// opened it, so no other writer can open it meanwhile. The old primary must be stopped first.
+    pub fn promote(mut self) -> Result<MemImgProcessor<S, C, E>, MemImgError> {
+        self.sync()?;
+        self.processor.set_read_only(false);
+        Ok(self.processor)
+    }
+}
@@ -0,0 +1,127 @@
+use crate::memimg::error::{FailureOutcome, MemImgError, SnapshotError, StorageError};
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "fs")]
+use std::path::Path;
+
+/// One error in a flattened failure chain, outermost first
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReportFrame {
+    pub code: String,
+    pub message: String,
+    pub context: Option<String>,
+}
+
+/// Replay progress at the time of a failure during `MemImgProcessor::new`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReplayReport {
+    pub events_replayed: u64,
+    /// 1-based position of the event that failed to apply, if one did
+    #[serde(default)]
+    pub failed_event_index: Option<u64>,
+    /// Debug rendering of that event
+    #[serde(default)]
+    pub failed_event: Option<String>,
+}
+
+/// Operator-facing failure report, stable enough to diff between occurrences
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FailureReport {
+    pub crate_version: String,
+    pub code: String,
+    pub frames: Vec<ReportFrame>,
+    pub replay: Option<ReplayReport>,
+    pub storage_path: Option<String>,
+}
+
+impl FailureReport {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("failure report is always serializable")
+    }
+}
+
+impl MemImgError {
+    /// Flatten this error and its whole source chain into a `FailureReport`
+    pub fn to_report(&self) -> FailureReport {
+        let mut frames = vec![ReportFrame {
+            code: self.code().to_string(),
+            message: match self.outcome() {
+                Some(_) => match self {
+                    MemImgError::CommandFailure(_) => "Command failure".to_string(),
+                    _ => "System failure".to_string(),
+                },
+                None => self.to_string(),
+            },
+            context: None,
+        }];
+        let mut storage_path = None;
+
+        let mut source = std::error::Error::source(self);
+        while let Some(error) = source {
+            frames.push(frame_for(error));
+            if let Some(storage_error) = error.downcast_ref::<StorageError>() {
+                storage_path.get_or_insert_with(|| storage_error.path.clone());
+            }
+            source = error.source();
+        }
+
+        FailureReport {
+            crate_version: env!("CARGO_PKG_VERSION").to_string(),
+            code: self.code().to_string(),
+            frames,
+            replay: self.outcome().and_then(|outcome| {
+                outcome.events_replayed.map(|events_replayed| ReplayReport {
+                    events_replayed,
+                    failed_event_index: outcome.failed_event.as_ref().map(|failed| failed.index),
+                    failed_event: outcome.failed_event.as_ref().map(|failed| failed.event.clone()),
+                })
+            }),
+            storage_path,
+        }
+    }
+
+    /// Write `to_report()` as pretty JSON to `path`
+    #[cfg(feature = "fs")]
+    pub fn write_report<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        std::fs::write(path, self.to_report().to_json())
+    }
+}
+
+fn frame_for(error: &(dyn std::error::Error + 'static)) -> ReportFrame {
+    if let Some(outcome) = error.downcast_ref::<FailureOutcome>() {
+        ReportFrame {
+            code: "FAILURE_OUTCOME".to_string(),
+            message: format!("Error while {} {}", outcome.context, outcome.command_type),
+            context: Some(outcome.context.clone()),
+        }
+    } else if let Some(storage_error) = error.downcast_ref::<StorageError>() {
+        ReportFrame {
+            code: "STORAGE_IO".to_string(),
+            message: format!("{} failed on {}", storage_error.op, storage_error.path),
+            context: Some(storage_error.op.to_string()),
+        }
+    } else if let Some(snapshot_error) = error.downcast_ref::<SnapshotError>() {
+        ReportFrame {
+            code: "SNAPSHOT".to_string(),
+            message: snapshot_error.to_string(),
+            context: None,
+        }
+    } else if let Some(io_error) = error.downcast_ref::<std::io::Error>() {
+        ReportFrame {
+            code: "IO".to_string(),
+            message: io_error.to_string(),
+            context: Some(format!("{:?}", io_error.kind())),
+        }
+    } else if error.downcast_ref::<serde_json::Error>().is_some() {
+        ReportFrame {
+            code: "JSON".to_string(),
+            message: error.to_string(),
+            context: None,
+        }
+    } else {
+        ReportFrame {
+            code: "ERROR".to_string(),
+            message: error.to_string(),
+            context: None,
+        }
+    }
+}
@@ -0,0 +1,52 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// How a `MemImgProcessor` undoes a command that fails to apply, or whose event cannot be logged
+///
+/// Commands are applied to the live state after a checkpoint is taken; a failure restores the
+/// checkpoint, a success drops it.
+pub trait RollbackStrategy<S> {
+    type Checkpoint;
+
+    fn checkpoint(system: &S) -> Result<Self::Checkpoint, Box<dyn std::error::Error + Send + Sync>>;
+
+    fn restore(system: &mut S, checkpoint: Self::Checkpoint) -> Result<(), Box<dyn std::error::Error + Send + Sync>>;
+}
+
+/// The default strategy: a full clone of the state, put back on failure
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ShadowCopy;
+
+impl<S: Clone> RollbackStrategy<S> for ShadowCopy {
+    type Checkpoint = S;
+
+    fn checkpoint(system: &S) -> Result<S, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(system.clone())
+    }
+
+    fn restore(system: &mut S, checkpoint: S) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        *system = checkpoint;
+        Ok(())
+    }
+}
+
+/// For states that cannot be cloned: the state is serialized to JSON before each command and
+/// deserialized back on failure
+///
+/// Every command pays for a serialization, and a failed one for a deserialization too, so this is
+/// slower than `ShadowCopy`. Selected with `MemImgProcessor::new_with_serialized_rollback`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SerializedRollback;
+
+impl<S: Serialize + DeserializeOwned> RollbackStrategy<S> for SerializedRollback {
+    type Checkpoint = Vec<u8>;
+
+    fn checkpoint(system: &S) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(serde_json::to_vec(system)?)
+    }
+
+    fn restore(system: &mut S, checkpoint: Vec<u8>) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        *system = serde_json::from_slice(&checkpoint)?;
+        Ok(())
+    }
+}
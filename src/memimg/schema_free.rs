@@ -0,0 +1,122 @@
+use crate::memimg::command_registry::DuplicateCommandName;
+use crate::memimg::error::{FailedEvent, FailureOutcome, MemImgError};
+use crate::memimg::storage::{BoxedEventStorage, EventStorage};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+/// Applies one schema-free event of a registered type to the system
+pub type EventHandler<S> = dyn Fn(&mut S, &Value) -> Result<(), Box<dyn std::error::Error + Send + Sync>> + Send + Sync;
+
+/// Processor whose events are plain JSON values, dispatched by their `"type"` field to handlers
+/// registered at runtime, for plugins that bring event types the application was not compiled with
+///
+/// Unlike `CommandRegistry`, no Rust type stands behind an event: the handler reads the `Value`
+/// as it was logged. Register every handler the log needs, then `replay` it.
+pub struct SchemaFreeProcessor<S: Clone> {
+    initial: S,
+    system: S,
+    storage: BoxedEventStorage<Value>,
+    handlers: BTreeMap<String, Arc<EventHandler<S>>>,
+    event_count: u64,
+}
+
+impl<S: Clone> SchemaFreeProcessor<S> {
+    /// Processor starting from `system`; the events already in `storage` apply on `replay`
+    pub fn new(system: S, storage: BoxedEventStorage<Value>) -> Self {
+        Self { initial: system.clone(), system, storage, handlers: BTreeMap::new(), event_count: 0 }
+    }
+
+    /// Dispatch events whose `"type"` is `event_type` to `handler`
+    pub fn register_handler(
+        &mut self,
+        event_type: &str,
+        handler: impl Fn(&mut S, &Value) -> Result<(), Box<dyn std::error::Error + Send + Sync>> + Send + Sync + 'static,
+    ) -> Result<(), DuplicateCommandName> {
+        if self.handlers.contains_key(event_type) {
+            return Err(DuplicateCommandName(event_type.to_string()));
+        }
+        self.handlers.insert(event_type.to_string(), Arc::new(handler));
+        Ok(())
+    }
+
+    /// Whether a handler is registered for `event_type`
+    pub fn handles(&self, event_type: &str) -> bool {
+        self.handlers.contains_key(event_type)
+    }
+
+    /// Rebuild the state from the initial system and every logged event, returning the event count
+    ///
+    /// An event with no handler, or whose handler fails, fails the whole replay and leaves the
+    /// state as it was.
+    pub fn replay(&mut self) -> Result<u64, MemImgError> {
+        let mut system = self.initial.clone();
+        let mut replayed = 0u64;
+        let mut failed = None;
+        let handlers = &self.handlers;
+        self.storage
+            .replay(&mut |event: Value| {
+                let result = Self::handler_for(handlers, &event)
+                    .map_err(|e| -> Box<dyn std::error::Error + Send + Sync> { Box::new(e) })
+                    .and_then(|handler| handler(&mut system, &event));
+                match result {
+                    Ok(()) => {
+                        replayed += 1;
+                        Ok(())
+                    }
+                    Err(e) => {
+                        failed = Some(FailedEvent { index: replayed + 1, event: event.to_string() });
+                        Err(e)
+                    }
+                }
+            })
+            .map_err(|e| {
+                MemImgError::SystemFailure(
+                    FailureOutcome::new(e, "replaying events into", std::any::type_name::<S>())
+                        .with_events_replayed(replayed)
+                        .with_failed_event(failed),
+                )
+            })?;
+        self.system = system;
+        self.event_count = replayed;
+        Ok(replayed)
+    }
+
+    /// Apply `event` to a copy of the state and, once it applies and is logged, keep the copy
+    pub fn execute(&mut self, event: Value) -> Result<(), MemImgError> {
+        let handler = Self::handler_for(&self.handlers, &event)?;
+        let mut shadow = self.system.clone();
+        let event_type = event["type"].as_str().unwrap_or_default().to_string();
+        handler(&mut shadow, &event)
+            .map_err(|e| MemImgError::CommandFailure(FailureOutcome::new(e, "executing", &event_type)))?;
+        // A storage keeping write-ahead intents holds the event pending until it is settled
+        if let Err(e) = self.storage.append(&event).and_then(|()| self.storage.settle_intent(true).map(drop)) {
+            // Take back whatever the failed append left pending; the append's error is reported
+            let _ = self.storage.settle_intent(false);
+            return Err(MemImgError::SystemFailure(FailureOutcome::new(e, "serializing command", &event_type)));
+        }
+        self.system = shadow;
+        self.event_count += 1;
+        Ok(())
+    }
+
+    pub fn system(&self) -> &S {
+        &self.system
+    }
+
+    /// Events applied since the last `replay`, including those it replayed
+    pub fn event_count(&self) -> u64 {
+        self.event_count
+    }
+
+    fn handler_for(
+        handlers: &BTreeMap<String, Arc<EventHandler<S>>>,
+        event: &Value,
+    ) -> Result<Arc<EventHandler<S>>, MemImgError> {
+        let event_type = event.get("type").and_then(Value::as_str).ok_or_else(|| MemImgError::InvalidCommandPayload {
+            command: String::new(),
+            message: "event has no string \"type\" field".to_string(),
+        })?;
+        handlers.get(event_type).cloned().ok_or_else(|| MemImgError::UnknownCommand(event_type.to_string()))
+    }
+}
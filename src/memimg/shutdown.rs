@@ -0,0 +1,114 @@
+use crate::memimg::error::MemImgError;
+use crate::memimg::processor::{Command, MemImgProcessor};
+use crate::memimg::storage::EventStorage;
+use signal_hook::consts::{SIGINT, SIGTERM};
+use signal_hook::iterator::{Handle, Signals};
+use std::sync::{Arc, Condvar, Mutex, PoisonError};
+use std::thread::JoinHandle;
+
+type ShutdownLog = Box<dyn Fn(&str) + Send + Sync>;
+
+/// Whether shutdown was requested, shared by a `ShutdownGuard` and its signal thread
+#[derive(Clone, Default)]
+pub struct StopSignal {
+    stopping: Arc<(Mutex<bool>, Condvar)>,
+}
+
+impl StopSignal {
+    pub fn should_stop(&self) -> bool {
+        *self.stopping.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Block until shutdown is requested
+    pub fn wait(&self) {
+        let (stopping, changed) = &*self.stopping;
+        let mut stopping = stopping.lock().unwrap_or_else(PoisonError::into_inner);
+        while !*stopping {
+            stopping = changed.wait(stopping).unwrap_or_else(PoisonError::into_inner);
+        }
+    }
+
+    /// Request shutdown, as SIGINT or SIGTERM does
+    pub fn trigger(&self) {
+        let (stopping, changed) = &*self.stopping;
+        *stopping.lock().unwrap_or_else(PoisonError::into_inner) = true;
+        changed.notify_all();
+    }
+}
+
+/// Turns SIGINT and SIGTERM into a request to stop, so loops can finish and the log be closed
+///
+/// The first signal sets `should_stop`, which command loops check between inputs; a loop blocked
+/// reading input notices on the next line or end of input. A second signal ends the process at
+/// once, as if no handler were installed. Handlers are removed when the guard drops.
+pub struct ShutdownGuard {
+    stop: StopSignal,
+    signals: Handle,
+    thread: Option<JoinHandle<()>>,
+    log: ShutdownLog,
+}
+
+impl ShutdownGuard {
+    /// Install the handlers, logging to stderr
+    pub fn install() -> Result<Self, std::io::Error> {
+        let stop = StopSignal::default();
+        let mut signals = Signals::new([SIGINT, SIGTERM])?;
+        let handle = signals.handle();
+        let thread_stop = stop.clone();
+        let thread = std::thread::spawn(move || {
+            for signal in signals.forever() {
+                if thread_stop.should_stop() {
+                    let _ = signal_hook::low_level::emulate_default_handler(signal);
+                }
+                thread_stop.trigger();
+            }
+        });
+        Ok(Self {
+            stop,
+            signals: handle,
+            thread: Some(thread),
+            log: Box::new(|line| eprintln!("{}", line)),
+        })
+    }
+
+    /// Send the line `close` logs to `log` instead of stderr
+    pub fn with_log(mut self, log: impl Fn(&str) + Send + Sync + 'static) -> Self {
+        self.log = Box::new(log);
+        self
+    }
+
+    pub fn should_stop(&self) -> bool {
+        self.stop.should_stop()
+    }
+
+    /// What the signal handlers run; lets tests and other shutdown paths request a stop
+    pub fn handle_signal(&self) {
+        self.stop.trigger();
+    }
+
+    /// Handle on the stop request for another thread or task, such as a server's shutdown future
+    pub fn stop_signal(&self) -> StopSignal {
+        self.stop.clone()
+    }
+
+    /// Close `processor`, logging how many events it flushed
+    pub fn close<S, C, E>(&self, processor: MemImgProcessor<S, C, E>) -> Result<u64, MemImgError>
+    where
+        S: Clone,
+        C: Command<System = S>,
+        E: EventStorage<Event = C>,
+    {
+        let flushed = processor.close()?;
+        (self.log)(&format!("shutdown: flushed and closed the event log; the {} events appended since it opened are on disk", flushed));
+        Ok(flushed)
+    }
+}
+
+impl Drop for ShutdownGuard {
+    fn drop(&mut self) {
+        self.signals.close();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
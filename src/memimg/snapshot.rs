@@ -0,0 +1,128 @@
+use crate::memimg::error::SnapshotError;
+use crate::memimg::processor::Command;
+use crate::memimg::storage::EventStorage;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+
+type Migration = Box<dyn Fn(Value) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> + Send + Sync>;
+
+/// Point-in-time system state covering the first `event_count` events of the log
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot<S> {
+    pub version: u32,
+    pub event_count: u64,
+    pub state: S,
+}
+
+impl<S: PartialEq> Snapshot<S> {
+    /// Replay the first `event_count` events of `storage` onto `initial` and check that they
+    /// rebuild `state`, failing with a `SnapshotError` if the log is too short or they differ
+    ///
+    /// A mismatch means the snapshot is corrupt or a command is nondeterministic. The log must
+    /// still hold every event, so a snapshot cannot be verified after `checkpoint_and_compact`.
+    pub fn verify_against<C, E>(&self, initial: S, storage: &mut E) -> Result<(), Box<dyn std::error::Error + Send + Sync>>
+    where
+        C: Command<System = S>,
+        E: EventStorage<Event = C>,
+    {
+        let mut state = initial;
+        let mut index = 0u64;
+        let replayed = storage.replay_n(self.event_count, &mut |event| {
+            index += 1;
+            event.apply_to(&mut state).map_err(|e| format!("event {} does not apply: {}", index, e).into())
+        })?;
+        if replayed < self.event_count {
+            return Err(Box::new(SnapshotError::LogTooShort { event_count: self.event_count, available: replayed }));
+        }
+        if state != self.state {
+            return Err(Box::new(SnapshotError::StateMismatch { event_count: self.event_count }));
+        }
+        Ok(())
+    }
+}
+
+/// Outcome of `MemImgProcessor::checkpoint_and_compact`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompactionResult {
+    pub snapshot_size_bytes: u64,
+    pub events_removed: u64,
+}
+
+/// Versioned snapshot format: writes the current version and upgrades older ones on load
+pub struct SnapshotFormat {
+    current_version: u32,
+    migrations: BTreeMap<u32, Migration>,
+}
+
+impl SnapshotFormat {
+    pub fn new(current_version: u32) -> Self {
+        Self {
+            current_version,
+            migrations: BTreeMap::new(),
+        }
+    }
+
+    /// Register the upgrade of a raw state from `from_version` to `from_version + 1`
+    pub fn with_migration<F>(mut self, from_version: u32, migration: F) -> Self
+    where
+        F: Fn(Value) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> + Send + Sync + 'static,
+    {
+        self.migrations.insert(from_version, Box::new(migration));
+        self
+    }
+
+    pub fn current_version(&self) -> u32 {
+        self.current_version
+    }
+
+    /// Write `state` as a single JSON document tagged with the current version
+    pub fn write<S, W>(&self, writer: &mut W, state: &S, event_count: u64) -> Result<(), Box<dyn std::error::Error + Send + Sync>>
+    where
+        S: Serialize,
+        W: Write,
+    {
+        let snapshot = Snapshot {
+            version: self.current_version,
+            event_count,
+            state,
+        };
+        serde_json::to_writer(&mut *writer, &snapshot).map_err(|e| -> Box<dyn std::error::Error + Send + Sync> { Box::new(e) })?;
+        writer.flush().map_err(|e| -> Box<dyn std::error::Error + Send + Sync> { Box::new(e) })?;
+        Ok(())
+    }
+
+    /// Read a snapshot, migrating older versions up to the current one
+    pub fn read<S, R>(&self, reader: R) -> Result<Snapshot<S>, Box<dyn std::error::Error + Send + Sync>>
+    where
+        S: DeserializeOwned,
+        R: Read,
+    {
+        let raw: Snapshot<Value> = serde_json::from_reader(reader).map_err(|e| -> Box<dyn std::error::Error + Send + Sync> { Box::new(e) })?;
+
+        if raw.version > self.current_version {
+            return Err(Box::new(SnapshotError::UnsupportedVersion {
+                found: raw.version,
+                supported: self.current_version,
+            }));
+        }
+
+        let mut state = raw.state;
+        for version in raw.version..self.current_version {
+            let migration = self
+                .migrations
+                .get(&version)
+                .ok_or(SnapshotError::MissingMigration { from_version: version })?;
+            state = migration(state)?;
+        }
+
+        let state = serde_json::from_value(state).map_err(|e| -> Box<dyn std::error::Error + Send + Sync> { Box::new(e) })?;
+        Ok(Snapshot {
+            version: self.current_version,
+            event_count: raw.event_count,
+            state,
+        })
+    }
+}
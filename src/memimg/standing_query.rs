@@ -0,0 +1,150 @@
+use crate::memimg::error::MemImgError;
+use crate::memimg::processor::{Command, CommandReceipt, MemImgProcessor, Query};
+use crate::memimg::storage::EventStorage;
+use std::any::Any;
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::sync::mpsc::{self, Receiver, Sender};
+
+/// Handle to a registered standing query whose results are of type `R`
+#[derive(Debug)]
+pub struct QueryId<R> {
+    id: u64,
+    _result: PhantomData<fn() -> R>,
+}
+
+impl<R> Clone for QueryId<R> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<R> Copy for QueryId<R> {}
+
+/// Type-erased standing query: re-evaluates against the state and pushes changed results
+trait StandingQuery<S>: Send {
+    fn refresh(&mut self, system: &S);
+
+    /// The `Vec<Sender<Result>>` of subscribers
+    fn subscribers(&mut self) -> &mut dyn Any;
+}
+
+struct Standing<Q: Query> {
+    query: Q,
+    last: Option<Q::Result>,
+    subscribers: Vec<Sender<Q::Result>>,
+}
+
+impl<Q> StandingQuery<Q::System> for Standing<Q>
+where
+    Q: Query + Send,
+    Q::Result: Clone + PartialEq + Send + 'static,
+{
+    fn refresh(&mut self, system: &Q::System) {
+        // A query that fails against this state has no new result to report
+        let Ok(result) = self.query.extract_from(system) else {
+            return;
+        };
+        if self.last.as_ref() != Some(&result) {
+            // Subscribers that dropped their receiver are forgotten
+            self.subscribers.retain(|subscriber| subscriber.send(result.clone()).is_ok());
+            self.last = Some(result);
+        }
+    }
+
+    fn subscribers(&mut self) -> &mut dyn Any {
+        &mut self.subscribers
+    }
+}
+
+/// Processor that pushes query results to subscribers as commands change them
+///
+/// After each successful command every registered query is re-evaluated, and subscribers
+/// receive the new result only when it differs from the previous one.
+pub struct StandingQueryProcessor<S, C, E>
+where
+    S: Clone,
+    C: Command<System = S>,
+    E: EventStorage<Event = C>,
+{
+    processor: MemImgProcessor<S, C, E>,
+    queries: HashMap<u64, Box<dyn StandingQuery<S>>>,
+    next_id: u64,
+}
+
+impl<S, C, E> StandingQueryProcessor<S, C, E>
+where
+    S: Clone,
+    C: Command<System = S>,
+    E: EventStorage<Event = C>,
+{
+    pub fn new(processor: MemImgProcessor<S, C, E>) -> Self {
+        Self {
+            processor,
+            queries: HashMap::new(),
+            next_id: 0,
+        }
+    }
+
+    /// Register `query`, evaluating it once now so later notifications only report changes
+    pub fn register_query<Q>(&mut self, query: Q) -> QueryId<Q::Result>
+    where
+        Q: Query<System = S> + Send + 'static,
+        Q::Result: Clone + PartialEq + Send + 'static,
+    {
+        let mut standing = Standing {
+            query,
+            last: None,
+            subscribers: Vec::new(),
+        };
+        standing.refresh(&self.processor.system);
+
+        self.next_id += 1;
+        self.queries.insert(self.next_id, Box::new(standing));
+        QueryId {
+            id: self.next_id,
+            _result: PhantomData,
+        }
+    }
+
+    /// Receive every changed result of the query registered as `id`
+    pub fn subscribe<R: 'static>(&mut self, id: QueryId<R>) -> Receiver<R> {
+        let (sender, receiver) = mpsc::channel();
+        let subscribers = self
+            .queries
+            .get_mut(&id.id)
+            .and_then(|query| query.subscribers().downcast_mut::<Vec<Sender<R>>>());
+        // Ids are only minted by `register_query`, with the matching result type
+        if let Some(subscribers) = subscribers {
+            subscribers.push(sender);
+        }
+        receiver
+    }
+
+    /// Execute a command, then push the results of standing queries it changed
+    pub fn execute_command(&mut self, command: C) -> Result<CommandReceipt, MemImgError> {
+        let receipt = self.processor.execute_command(command)?;
+        for query in self.queries.values_mut() {
+            query.refresh(&self.processor.system);
+        }
+        Ok(receipt)
+    }
+
+    /// Execute a one-off query against the current state
+    pub fn execute_query<Q>(&self, query: &Q) -> Result<Q::Result, MemImgError>
+    where
+        Q: Query<System = S>,
+    {
+        self.processor.execute_query(query)
+    }
+
+    /// The wrapped processor
+    pub fn processor(&self) -> &MemImgProcessor<S, C, E> {
+        &self.processor
+    }
+
+    /// Unwrap the processor, dropping every standing query and subscription
+    pub fn into_inner(self) -> MemImgProcessor<S, C, E> {
+        self.processor
+    }
+}
@@ -1,7 +1,7 @@
-use std::fs::{File, OpenOptions};
-use std::io::{BufRead, BufReader, Write};
-use std::marker::PhantomData;
-use std::path::Path;
+use crate::memimg::event_id::EventId;
+use crate::memimg::warning::Warning;
+use serde::Serialize;
+use std::io::Write;
 
 /// Trait for event storage backends
 pub trait EventStorage {
@@ -11,105 +11,293 @@ pub trait EventStorage {
     where
         F: FnMut(Self::Event) -> Result<(), Box<dyn std::error::Error + Send + Sync>>;
 
+    /// Replay only the first `n` events, returning how many were replayed (fewer if the log is shorter)
+    ///
+    /// The default stops the full replay by failing the consumer once `n` events went through.
+    fn replay_n<F>(&mut self, n: u64, consumer: &mut F) -> Result<u64, Box<dyn std::error::Error + Send + Sync>>
+    where
+        F: FnMut(Self::Event) -> Result<(), Box<dyn std::error::Error + Send + Sync>>,
+    {
+        let mut replayed = 0u64;
+        let result = self.replay(&mut |event| {
+            if replayed == n {
+                return Err(Box::new(ReplayLimitReached));
+            }
+            consumer(event)?;
+            replayed += 1;
+            Ok(())
+        });
+        match result {
+            Err(e) if !e.is::<ReplayLimitReached>() => Err(e),
+            _ => Ok(replayed),
+        }
+    }
+
+    /// Replay up to `n` events from `position`, returning how many were replayed and the position
+    /// to resume from, so a long read can go in chunks; position 0 is the first event
+    ///
+    /// Positions are opaque: pass back only those this storage returned. The default counts events,
+    /// so each call reads the log again from the start; storages that can resume in place do.
+    fn replay_from<F>(&mut self, position: u64, n: u64, consumer: &mut F) -> Result<(u64, u64), Box<dyn std::error::Error + Send + Sync>>
+    where
+        F: FnMut(Self::Event) -> Result<(), Box<dyn std::error::Error + Send + Sync>>,
+    {
+        let mut seen = 0u64;
+        let read = self.replay_n(position.saturating_add(n), &mut |event| {
+            seen += 1;
+            if seen > position {
+                consumer(event)?;
+            }
+            Ok(())
+        })?;
+        let replayed = read.saturating_sub(position);
+        Ok((replayed, position + replayed))
+    }
+
     fn append(&mut self, event: &Self::Event) -> Result<(), Box<dyn std::error::Error + Send + Sync>>;
-}
 
-/// Trait for converting events to/from text format
-pub trait TextConverter<T> {
-    fn parse(&self, text: &str) -> Result<T, Box<dyn std::error::Error + Send + Sync>>;
-    fn format(&self, value: &T) -> Result<String, Box<dyn std::error::Error + Send + Sync>>;
+    /// Append `events` as one group that replay sees whole or not at all, as far as the backend
+    /// allows. The default appends them one by one and flushes once, which is not atomic.
+    fn append_atomic(&mut self, events: &[Self::Event]) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        for event in events {
+            self.append(event)?;
+        }
+        self.flush()
+    }
+
+    /// Write all events to `writer` as NDJSON (one JSON value per line), returning the bytes written
+    fn copy_to<W: Write>(&mut self, writer: &mut W) -> Result<u64, Box<dyn std::error::Error + Send + Sync>>
+    where
+        Self::Event: Serialize,
+    {
+        let mut written = 0u64;
+        self.replay(&mut |event: Self::Event| {
+            let json = serde_json::to_string(&event).map_err(|e| -> Box<dyn std::error::Error + Send + Sync> { Box::new(e) })?;
+            writeln!(writer, "{}", json).map_err(|e| -> Box<dyn std::error::Error + Send + Sync> { Box::new(e) })?;
+            written += json.len() as u64 + 1;
+            Ok(())
+        })?;
+        writer.flush().map_err(|e| -> Box<dyn std::error::Error + Send + Sync> { Box::new(e) })?;
+        Ok(written)
+    }
+
+    /// Number of events currently stored; the default counts them by replaying the log
+    fn version(&mut self) -> Result<u64, Box<dyn std::error::Error + Send + Sync>> {
+        let mut count = 0u64;
+        self.replay(&mut |_event: Self::Event| {
+            count += 1;
+            Ok(())
+        })?;
+        Ok(count)
+    }
+
+    /// Remove every stored event before `first_kept` (1-based, counting from the first stored
+    /// event), returning how many were removed. Used by `checkpoint_and_compact`.
+    fn truncate_before(&mut self, _first_kept: EventId) -> Result<u64, Box<dyn std::error::Error + Send + Sync>> {
+        Err("this event storage does not support truncation".into())
+    }
+
+    /// Make every appended event durable; called before shutting down
+    fn flush(&mut self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        Ok(())
+    }
+
+    /// Take the non-fatal anomalies accumulated by the last replay
+    fn drain_warnings(&mut self) -> Vec<Warning> {
+        Vec::new()
+    }
+
+    /// Whether a successful `append` has already written the event to the backing file
+    fn writes_through(&self) -> bool {
+        false
+    }
+
+    /// Append an operator's note that replay skips, returning whether this storage keeps such
+    /// notes; the default keeps none
+    fn append_marker(&mut self, _text: &str) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(false)
+    }
+
+    /// Settle the events appended since the last call, keeping them if `applied` or taking them
+    /// back out of the log, and return whether this storage keeps write-ahead intents; the
+    /// default keeps none
+    ///
+    /// A storage that keeps them holds appended events as pending until they are settled, and
+    /// replay drops any a crash left pending. `append_atomic` settles its own group.
+    fn settle_intent(&mut self, _applied: bool) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(false)
+    }
 }
 
-/// File-based event storage using line-oriented text format
-pub struct TextFileEventStorage<E, C>
-where
-    C: TextConverter<E>,
-{
-    file_path: String,
-    converter: C,
-    writer: Option<File>,
-    _phantom: PhantomData<E>,
+/// Replay consumer taken by `DynEventStorage`
+pub type DynConsumer<'a, E> = dyn FnMut(E) -> Result<(), Box<dyn std::error::Error + Send + Sync>> + 'a;
+
+/// Object-safe face of `EventStorage`, implemented for every storage, so a backend chosen at
+/// runtime can be held as a `BoxedEventStorage`
+///
+/// The methods are prefixed with `dyn_` so that they never shadow `EventStorage`'s when both
+/// traits are in scope.
+pub trait DynEventStorage {
+    type Event;
+
+    fn dyn_replay(&mut self, consumer: &mut DynConsumer<'_, Self::Event>) -> Result<(), Box<dyn std::error::Error + Send + Sync>>;
+    fn dyn_replay_n(&mut self, n: u64, consumer: &mut DynConsumer<'_, Self::Event>) -> Result<u64, Box<dyn std::error::Error + Send + Sync>>;
+    fn dyn_replay_from(&mut self, position: u64, n: u64, consumer: &mut DynConsumer<'_, Self::Event>) -> Result<(u64, u64), Box<dyn std::error::Error + Send + Sync>>;
+    fn dyn_append(&mut self, event: &Self::Event) -> Result<(), Box<dyn std::error::Error + Send + Sync>>;
+    fn dyn_append_atomic(&mut self, events: &[Self::Event]) -> Result<(), Box<dyn std::error::Error + Send + Sync>>;
+    fn dyn_version(&mut self) -> Result<u64, Box<dyn std::error::Error + Send + Sync>>;
+    fn dyn_truncate_before(&mut self, first_kept: EventId) -> Result<u64, Box<dyn std::error::Error + Send + Sync>>;
+    fn dyn_flush(&mut self) -> Result<(), Box<dyn std::error::Error + Send + Sync>>;
+    fn dyn_drain_warnings(&mut self) -> Vec<Warning>;
+    fn dyn_writes_through(&self) -> bool;
+    fn dyn_append_marker(&mut self, text: &str) -> Result<bool, Box<dyn std::error::Error + Send + Sync>>;
+    fn dyn_settle_intent(&mut self, applied: bool) -> Result<bool, Box<dyn std::error::Error + Send + Sync>>;
 }
 
-impl<E, C> TextFileEventStorage<E, C>
-where
-    C: TextConverter<E>,
-{
-    pub fn new<P: AsRef<Path>>(path: P, converter: C) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
-        let file_path = path.as_ref().to_string_lossy().to_string();
+impl<T: EventStorage> DynEventStorage for T {
+    type Event = T::Event;
 
-        // Ensure parent directory exists
-        if let Some(parent) = path.as_ref().parent() {
-            std::fs::create_dir_all(parent).map_err(|e| -> Box<dyn std::error::Error + Send + Sync> { Box::new(e) })?;
-        }
+    fn dyn_replay(&mut self, consumer: &mut DynConsumer<'_, Self::Event>) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        self.replay(&mut |event| consumer(event))
+    }
 
-        // Create file if it doesn't exist
-        if !path.as_ref().exists() {
-            File::create(&path).map_err(|e| -> Box<dyn std::error::Error + Send + Sync> { Box::new(e) })?;
-        }
+    fn dyn_replay_n(&mut self, n: u64, consumer: &mut DynConsumer<'_, Self::Event>) -> Result<u64, Box<dyn std::error::Error + Send + Sync>> {
+        self.replay_n(n, &mut |event| consumer(event))
+    }
+
+    fn dyn_replay_from(&mut self, position: u64, n: u64, consumer: &mut DynConsumer<'_, Self::Event>) -> Result<(u64, u64), Box<dyn std::error::Error + Send + Sync>> {
+        self.replay_from(position, n, &mut |event| consumer(event))
+    }
+
+    fn dyn_append(&mut self, event: &Self::Event) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        self.append(event)
+    }
+
+    fn dyn_append_atomic(&mut self, events: &[Self::Event]) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        self.append_atomic(events)
+    }
 
-        Ok(Self {
-            file_path,
-            converter,
-            writer: None,
-            _phantom: PhantomData,
-        })
+    fn dyn_version(&mut self) -> Result<u64, Box<dyn std::error::Error + Send + Sync>> {
+        self.version()
+    }
+
+    fn dyn_truncate_before(&mut self, first_kept: EventId) -> Result<u64, Box<dyn std::error::Error + Send + Sync>> {
+        self.truncate_before(first_kept)
+    }
+
+    fn dyn_flush(&mut self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        self.flush()
+    }
+
+    fn dyn_drain_warnings(&mut self) -> Vec<Warning> {
+        self.drain_warnings()
+    }
+
+    fn dyn_writes_through(&self) -> bool {
+        self.writes_through()
+    }
+
+    fn dyn_append_marker(&mut self, text: &str) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+        self.append_marker(text)
+    }
+
+    fn dyn_settle_intent(&mut self, applied: bool) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+        self.settle_intent(applied)
     }
 }
 
-impl<E, C> EventStorage for TextFileEventStorage<E, C>
-where
-    C: TextConverter<E>,
-{
+/// A storage backend picked at runtime, usable wherever an `EventStorage` is, including as a
+/// processor's storage: `MemImgProcessor::new(system, Box::new(boxed))`
+pub type BoxedEventStorage<E> = Box<dyn DynEventStorage<Event = E> + Send>;
+
+impl<E> EventStorage for BoxedEventStorage<E> {
     type Event = E;
 
     fn replay<F>(&mut self, consumer: &mut F) -> Result<(), Box<dyn std::error::Error + Send + Sync>>
     where
         F: FnMut(Self::Event) -> Result<(), Box<dyn std::error::Error + Send + Sync>>,
     {
-        let file = File::open(&self.file_path).map_err(|e| -> Box<dyn std::error::Error + Send + Sync> { Box::new(e) })?;
-        let reader = BufReader::new(file);
+        (**self).dyn_replay(consumer)
+    }
 
-        for line in reader.lines() {
-            let line = line.map_err(|e| -> Box<dyn std::error::Error + Send + Sync> { Box::new(e) })?;
-            if !line.trim().is_empty() {
-                let event = self.converter.parse(&line)?;
-                consumer(event)?;
-            }
-        }
+    fn replay_n<F>(&mut self, n: u64, consumer: &mut F) -> Result<u64, Box<dyn std::error::Error + Send + Sync>>
+    where
+        F: FnMut(Self::Event) -> Result<(), Box<dyn std::error::Error + Send + Sync>>,
+    {
+        (**self).dyn_replay_n(n, consumer)
+    }
 
-        Ok(())
+    fn replay_from<F>(&mut self, position: u64, n: u64, consumer: &mut F) -> Result<(u64, u64), Box<dyn std::error::Error + Send + Sync>>
+    where
+        F: FnMut(Self::Event) -> Result<(), Box<dyn std::error::Error + Send + Sync>>,
+    {
+        (**self).dyn_replay_from(position, n, consumer)
     }
 
     fn append(&mut self, event: &Self::Event) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        // Lazy-open writer after replay
-        if self.writer.is_none() {
-            self.writer = Some(
-                OpenOptions::new()
-                    .create(true)
-                    .append(true)
-                    .open(&self.file_path)
-                    .map_err(|e| -> Box<dyn std::error::Error + Send + Sync> { Box::new(e) })?,
-            );
-        }
+        (**self).dyn_append(event)
+    }
 
-        let text = self.converter.format(event)?;
-        if let Some(writer) = &mut self.writer {
-            writeln!(writer, "{}", text).map_err(|e| -> Box<dyn std::error::Error + Send + Sync> { Box::new(e) })?;
-            writer.flush().map_err(|e| -> Box<dyn std::error::Error + Send + Sync> { Box::new(e) })?;
-        }
+    fn append_atomic(&mut self, events: &[Self::Event]) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        (**self).dyn_append_atomic(events)
+    }
 
-        Ok(())
+    fn version(&mut self) -> Result<u64, Box<dyn std::error::Error + Send + Sync>> {
+        (**self).dyn_version()
+    }
+
+    fn truncate_before(&mut self, first_kept: EventId) -> Result<u64, Box<dyn std::error::Error + Send + Sync>> {
+        (**self).dyn_truncate_before(first_kept)
+    }
+
+    fn flush(&mut self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        (**self).dyn_flush()
+    }
+
+    fn drain_warnings(&mut self) -> Vec<Warning> {
+        (**self).dyn_drain_warnings()
+    }
+
+    fn writes_through(&self) -> bool {
+        (**self).dyn_writes_through()
+    }
+
+    fn append_marker(&mut self, text: &str) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+        (**self).dyn_append_marker(text)
+    }
+
+    fn settle_intent(&mut self, applied: bool) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+        (**self).dyn_settle_intent(applied)
     }
 }
 
-impl<E, C> Drop for TextFileEventStorage<E, C>
-where
-    C: TextConverter<E>,
-{
-    fn drop(&mut self) {
-        if let Some(mut writer) = self.writer.take() {
-            let _ = writer.flush();
-        }
+/// Raised by the default `replay_n` to stop a replay early; never escapes it
+#[derive(Debug)]
+struct ReplayLimitReached;
+
+impl std::fmt::Display for ReplayLimitReached {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("replay limit reached")
     }
 }
+
+impl std::error::Error for ReplayLimitReached {}
+
+/// How replay treats records that cannot be parsed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ReplayPolicy {
+    /// Fail replay on the first unparseable record
+    #[default]
+    Strict,
+    /// Skip unparseable records, reporting each as a warning
+    Lenient,
+    /// Skip up to `error_budget` unparseable records like `Lenient`; one more fails replay
+    /// with a `ReplayBudgetExceeded` listing all of them
+    Budgeted { error_budget: usize },
+}
+
+/// Trait for converting events to/from text format
+pub trait TextConverter<T> {
+    fn parse(&self, text: &str) -> Result<T, Box<dyn std::error::Error + Send + Sync>>;
+    fn format(&self, value: &T) -> Result<String, Box<dyn std::error::Error + Send + Sync>>;
+}
@@ -0,0 +1,465 @@
+use crate::memimg::error::{MemImgError, ReplayBudgetExceeded, StorageError, StorageOp};
+use crate::memimg::processor::{Command, MemImgProcessor};
+use crate::memimg::storage::{EventStorage, ReplayPolicy, TextConverter};
+use crate::memimg::warning::{Warning, WarningKind};
+use std::io::{BufRead, BufReader, Read, Sink, Write};
+use std::marker::PhantomData;
+
+/// Start of a marker line: an operator's note in the log (a deploy, a manual fix) that replay
+/// skips. No converter's output starts with it, as event lines are JSON.
+pub const MARKER_PREFIX: &str = "#";
+
+/// Record opening a log's write-ahead intents: each event after it counts only once a
+/// `COMMIT_RECORD` follows it. Unlike a marker's, its text has no space after the prefix.
+pub(crate) const INTENTS_RECORD: &str = "#intents";
+
+/// Record committing the events appended since the previous one
+pub(crate) const COMMIT_RECORD: &str = "#commit";
+
+/// Whether a log line is one of the records write-ahead intents keep, rather than a marker
+#[cfg(feature = "fs")]
+pub(crate) fn is_intent_record(text: &str) -> bool {
+    matches!(text.trim(), INTENTS_RECORD | COMMIT_RECORD)
+}
+
+/// Whether a log line holds an event rather than nothing or a marker
+pub(crate) fn is_event_line(text: &str) -> bool {
+    let text = text.trim();
+    !text.is_empty() && !text.starts_with(MARKER_PREFIX)
+}
+
+/// Write-ahead intent framing of a line-oriented log, shared by replay and the readers that walk
+/// a log line by line: once an `INTENTS_RECORD` is read, events are held until a `COMMIT_RECORD`
+/// releases them, and those still held at the end of the log were never committed
+pub(crate) struct IntentFraming<T> {
+    declared: bool,
+    held: Vec<T>,
+}
+
+impl<T> IntentFraming<T> {
+    /// Framing for a log read from a point where intents are already `declared`, or not
+    pub fn new(declared: bool) -> Self {
+        Self { declared, held: Vec::new() }
+    }
+
+    /// Take in a complete line that may be an intent record, returning the events a commit
+    /// record releases, or `None` for any other line
+    ///
+    /// A torn record was never written, so only terminated lines count.
+    pub fn record(&mut self, text: &str) -> Option<Vec<T>> {
+        match text.trim() {
+            INTENTS_RECORD => {
+                self.declared = true;
+                Some(Vec::new())
+            }
+            COMMIT_RECORD => Some(std::mem::take(&mut self.held)),
+            _ => None,
+        }
+    }
+
+    /// Hold `event` until the next commit record if intents are declared, or hand it back
+    pub fn hold(&mut self, event: T) -> Option<T> {
+        if self.declared {
+            self.held.push(event);
+            None
+        } else {
+            Some(event)
+        }
+    }
+
+    pub fn declared(&self) -> bool {
+        self.declared
+    }
+
+    /// Events read since the last commit record
+    pub fn held(&self) -> &[T] {
+        &self.held
+    }
+}
+
+/// Appended text of `events`, one line each, after an `INTENTS_RECORD` if `declare` and before a
+/// `COMMIT_RECORD` if `commit`, with the offset of each event's line in it
+///
+/// Formats every event before returning any text, so an unformattable event writes nothing.
+pub(crate) fn event_lines<E, C>(converter: &C, events: &[E], declare: bool, commit: bool) -> Result<(String, Vec<u64>), Box<dyn std::error::Error + Send + Sync>>
+where
+    C: TextConverter<E>,
+{
+    let mut text = String::new();
+    if declare {
+        text.push_str(INTENTS_RECORD);
+        text.push('\n');
+    }
+    let mut line_starts = Vec::with_capacity(events.len());
+    for event in events {
+        line_starts.push(text.len() as u64);
+        text.push_str(&converter.format(event)?);
+        text.push('\n');
+    }
+    if commit {
+        text.push_str(COMMIT_RECORD);
+        text.push('\n');
+    }
+    Ok((text, line_starts))
+}
+
+/// Marker line holding `text`, its line breaks made spaces
+pub(crate) fn marker_line(text: &str) -> String {
+    format!("{} {}\n", MARKER_PREFIX, text.replace(['\n', '\r'], " "))
+}
+
+/// Wrap an I/O error with the file path (or stream name) and operation it happened on
+pub(crate) fn storage_error(path: &str, op: StorageOp) -> impl FnOnce(std::io::Error) -> Box<dyn std::error::Error + Send + Sync> + '_ {
+    move |e| Box::new(StorageError::new(path, op, e))
+}
+
+/// Cuts a torn last line off a log at the given byte offset, where the log allows it
+pub(crate) type RepairTail<'a> = dyn FnMut(u64) -> Result<(), Box<dyn std::error::Error + Send + Sync>> + 'a;
+
+/// Replay of a line-oriented log, one event per line, shared by the file and stream storages
+pub(crate) struct LineReplay<'a, C> {
+    /// Path or name of the log, for errors
+    pub source: &'a str,
+    pub converter: &'a C,
+    pub replay_policy: ReplayPolicy,
+    pub warnings: &'a mut Vec<Warning>,
+    /// Set once an `INTENTS_RECORD` is read
+    pub intents_declared: &'a mut bool,
+}
+
+impl<C> LineReplay<'_, C> {
+    /// Parse lines from `reader` until `limit` events have been consumed, returning how many were
+    ///
+    /// `consumer` is given each event with the byte offset of its line. An unterminated unparseable
+    /// last line is a torn append: it is handed to `repair_tail` when given, and otherwise treated
+    /// as any other unparseable line.
+    ///
+    /// After an `INTENTS_RECORD`, events are held until a `COMMIT_RECORD` commits them; those
+    /// still held at the end of the log are dropped, and cut off it through `repair_tail`.
+    pub fn run<E, F>(self, reader: &mut dyn BufRead, limit: u64, mut repair_tail: Option<&mut RepairTail<'_>>, consumer: &mut F) -> Result<u64, Box<dyn std::error::Error + Send + Sync>>
+    where
+        C: TextConverter<E>,
+        F: FnMut(u64, E) -> Result<(), Box<dyn std::error::Error + Send + Sync>>,
+    {
+        let mut line = String::new();
+        let mut index = 0u64;
+        let mut offset = 0u64;
+        let mut skipped = Vec::new();
+        let mut replayed = 0u64;
+        let mut framing = IntentFraming::new(false);
+        // Line number and offset of the first event held since the last commit record
+        let mut pending_from = None;
+        let mut at_end = false;
+        while replayed < limit {
+            line.clear();
+            let read = reader.read_line(&mut line).map_err(storage_error(self.source, StorageOp::Read))?;
+            if read == 0 {
+                at_end = true;
+                break;
+            }
+            index += 1;
+
+            let terminated = line.ends_with('\n');
+            let text = line.trim_end_matches(['\n', '\r']);
+            if let Some(committed) = terminated.then(|| framing.record(text)).flatten() {
+                *self.intents_declared |= framing.declared();
+                for (offset, event) in committed.into_iter().take((limit - replayed) as usize) {
+                    consumer(offset, event)?;
+                    replayed += 1;
+                }
+                pending_from = None;
+            }
+            if is_event_line(text) {
+                match self.converter.parse(text) {
+                    Ok(event) => {
+                        if let Some((offset, event)) = framing.hold((offset, event)) {
+                            consumer(offset, event)?;
+                            replayed += 1;
+                        } else {
+                            pending_from.get_or_insert((index, offset));
+                        }
+                    }
+                    // A crash mid-append leaves an unterminated last line: drop it so appends start clean
+                    Err(e) if !terminated && repair_tail.is_some() => {
+                        if let Some(repair_tail) = repair_tail.as_mut() {
+                            repair_tail(offset)?;
+                        }
+                        self.warnings.push(Warning::new(
+                            WarningKind::RepairedTail,
+                            index,
+                            offset,
+                            &format!("truncated torn last line: {}", e),
+                        ));
+                    }
+                    Err(e) => {
+                        let warning = Warning::new(WarningKind::SkippedLine, index, offset, &format!("skipped unparseable line: {}", e));
+                        match self.replay_policy {
+                            ReplayPolicy::Strict => return Err(e),
+                            ReplayPolicy::Lenient => {}
+                            ReplayPolicy::Budgeted { error_budget } if skipped.len() < error_budget => {}
+                            ReplayPolicy::Budgeted { error_budget } => {
+                                skipped.push(warning);
+                                return Err(Box::new(ReplayBudgetExceeded { error_budget, errors: skipped }));
+                            }
+                        }
+                        skipped.push(warning.clone());
+                        self.warnings.push(warning);
+                    }
+                }
+            }
+            offset += read as u64;
+        }
+
+        if let (true, Some((index, offset))) = (at_end, pending_from) {
+            if let Some(repair_tail) = repair_tail.as_mut() {
+                repair_tail(offset)?;
+            }
+            let message = format!("dropped {} uncommitted event(s) at the end of the log", framing.held().len());
+            self.warnings.push(Warning::new(WarningKind::DiscardedIntent, index, offset, &message));
+        }
+        Ok(replayed)
+    }
+}
+
+/// Line-oriented event storage over any reader and writer: an in-memory `Cursor`, a socket, a
+/// compressing wrapper
+///
+/// Events are replayed from `source` and appended to `sink`, one per line in the
+/// `TextFileEventStorage` format, so either can be a log file. The source is read once, by the
+/// first replay; later replays find nothing more, and `version` counts the events read plus those
+/// appended since. Appends are buffered by the sink itself and reach it in full on `flush`.
+///
+/// A sink cannot be cut back, so under `with_write_ahead_intents` pending events are held in
+/// memory and written with their commit record when `settle_intent` commits them.
+pub struct StreamEventStorage<R, W, E, C> {
+    source: BufReader<R>,
+    sink: W,
+    name: String,
+    converter: C,
+    replay_policy: ReplayPolicy,
+    warnings: Vec<Warning>,
+    events_read: u64,
+    events_appended: u64,
+    /// Set by `read_only`
+    read_only: bool,
+    /// Set by `with_write_ahead_intents`
+    intents: bool,
+    /// Whether the log holds an `INTENTS_RECORD`, read from the source or written to the sink
+    intents_declared: bool,
+    /// Lines of the events appended since the last `settle_intent`, and how many there are
+    pending: (String, u64),
+    _phantom: PhantomData<fn() -> E>,
+}
+
+impl<R, W, E, C> StreamEventStorage<R, W, E, C>
+where
+    R: Read,
+    W: Write,
+    C: TextConverter<E>,
+{
+    /// Storage replaying the log in `source` and appending to `sink`
+    pub fn new(source: R, sink: W, converter: C) -> Self {
+        Self {
+            source: BufReader::new(source),
+            sink,
+            name: "stream".to_string(),
+            converter,
+            replay_policy: ReplayPolicy::default(),
+            warnings: Vec::new(),
+            events_read: 0,
+            events_appended: 0,
+            read_only: false,
+            intents: false,
+            intents_declared: false,
+            pending: (String::new(), 0),
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Name the log in storage errors in place of "stream"
+    pub fn with_name(mut self, name: &str) -> Self {
+        self.name = name.to_string();
+        self
+    }
+
+    /// Set the policy applied to unparseable lines during replay
+    pub fn with_replay_policy(mut self, replay_policy: ReplayPolicy) -> Self {
+        self.replay_policy = replay_policy;
+        self
+    }
+
+    /// Keep a write-ahead intent for each append, as `TextFileEventStorage::with_write_ahead_intents`
+    /// does: events reach the sink only when `settle_intent` commits them, followed by a commit
+    /// record, and are dropped if it does not
+    pub fn with_write_ahead_intents(mut self) -> Self {
+        self.intents = true;
+        self
+    }
+
+    pub fn sink(&self) -> &W {
+        &self.sink
+    }
+
+    /// The source and sink, the sink holding everything appended but not what the source held
+    pub fn into_parts(self) -> (R, W) {
+        (self.source.into_inner(), self.sink)
+    }
+
+    fn replay_up_to<F>(&mut self, limit: u64, consumer: &mut F) -> Result<u64, Box<dyn std::error::Error + Send + Sync>>
+    where
+        F: FnMut(E) -> Result<(), Box<dyn std::error::Error + Send + Sync>>,
+    {
+        let replay = LineReplay {
+            source: &self.name,
+            converter: &self.converter,
+            replay_policy: self.replay_policy,
+            warnings: &mut self.warnings,
+            intents_declared: &mut self.intents_declared,
+        };
+        // A stream cannot be cut short, so a torn last line is just unparseable
+        let replayed = replay.run(&mut self.source, limit, None, &mut |_, event| consumer(event))?;
+        self.events_read += replayed;
+        Ok(replayed)
+    }
+
+    /// Fail appends to a stream opened with `read_only`
+    fn ensure_writable(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        if self.read_only {
+            let error = std::io::Error::new(std::io::ErrorKind::PermissionDenied, "the stream was opened read-only");
+            return Err(storage_error(&self.name, StorageOp::Append)(error));
+        }
+        Ok(())
+    }
+
+    fn write_lines(&mut self, text: &str, events: u64) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        self.ensure_writable()?;
+        self.sink.write_all(text.as_bytes()).map_err(storage_error(&self.name, StorageOp::Append))?;
+        self.events_appended += events;
+        Ok(())
+    }
+}
+
+impl<R, E, C> StreamEventStorage<R, Sink, E, C>
+where
+    R: Read,
+    C: TextConverter<E>,
+{
+    /// Storage replaying the log in `source`, such as `stdin().lock()`, that refuses appends and markers
+    pub fn read_only(source: R, converter: C) -> Self {
+        let mut storage = Self::new(source, std::io::sink(), converter);
+        storage.read_only = true;
+        storage
+    }
+}
+
+impl<S, C, R, T> MemImgProcessor<S, C, StreamEventStorage<R, Sink, C, T>>
+where
+    S: Clone,
+    C: Command<System = S>,
+    R: Read,
+    T: TextConverter<C>,
+{
+    /// Read-only processor over the log read from `source`, for tools a log is piped into
+    ///
+    /// The processor refuses commands, and its storage refuses appends even if the processor is
+    /// made writable, so it only answers queries on the state the log replays to.
+    pub fn from_reader(system: S, source: R, converter: T) -> Result<Self, MemImgError> {
+        let mut processor = Self::new_simple(system, Box::new(StreamEventStorage::read_only(source, converter)))?;
+        processor.set_read_only(true);
+        Ok(processor)
+    }
+}
+
+impl<R, W, E, C> EventStorage for StreamEventStorage<R, W, E, C>
+where
+    R: Read,
+    W: Write,
+    C: TextConverter<E>,
+{
+    type Event = E;
+
+    fn replay<F>(&mut self, consumer: &mut F) -> Result<(), Box<dyn std::error::Error + Send + Sync>>
+    where
+        F: FnMut(Self::Event) -> Result<(), Box<dyn std::error::Error + Send + Sync>>,
+    {
+        self.replay_up_to(u64::MAX, consumer)?;
+        Ok(())
+    }
+
+    fn replay_n<F>(&mut self, n: u64, consumer: &mut F) -> Result<u64, Box<dyn std::error::Error + Send + Sync>>
+    where
+        F: FnMut(Self::Event) -> Result<(), Box<dyn std::error::Error + Send + Sync>>,
+    {
+        self.replay_up_to(n, consumer)
+    }
+
+    /// Under write-ahead intents the line is held until `settle_intent`
+    fn append(&mut self, event: &Self::Event) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let (text, _) = event_lines(&self.converter, std::slice::from_ref(event), false, false)?;
+        if self.intents {
+            self.ensure_writable()?;
+            self.pending.0.push_str(&text);
+            self.pending.1 += 1;
+            return Ok(());
+        }
+        self.write_lines(&text, 1)
+    }
+
+    /// Format every event before writing any, then write the group in one `write_all`; under
+    /// write-ahead intents it commits itself, with any events still pending before it
+    fn append_atomic(&mut self, events: &[Self::Event]) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let intents = self.intents && !events.is_empty();
+        let declare = intents && !self.intents_declared;
+        let (group, _) = event_lines(&self.converter, events, false, intents)?;
+        let (mut text, mut count) = if intents { std::mem::take(&mut self.pending) } else { (String::new(), 0) };
+        if declare {
+            text.insert_str(0, &format!("{}\n", INTENTS_RECORD));
+        }
+        text.push_str(&group);
+        count += events.len() as u64;
+        self.write_lines(&text, count)?;
+        self.intents_declared |= declare;
+        self.flush()
+    }
+
+    /// Reads the rest of the source, if not replayed yet, counting its events
+    fn version(&mut self) -> Result<u64, Box<dyn std::error::Error + Send + Sync>> {
+        self.replay_up_to(u64::MAX, &mut |_event| Ok(()))?;
+        Ok(self.events_read + self.events_appended)
+    }
+
+    fn flush(&mut self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        self.sink.flush().map_err(storage_error(&self.name, StorageOp::Flush))?;
+        Ok(())
+    }
+
+    fn drain_warnings(&mut self) -> Vec<Warning> {
+        std::mem::take(&mut self.warnings)
+    }
+
+    fn append_marker(&mut self, text: &str) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+        self.write_lines(&marker_line(text), 0)?;
+        Ok(true)
+    }
+
+    /// Write the pending events and their commit record to the sink, or drop them, under
+    /// `with_write_ahead_intents`
+    fn settle_intent(&mut self, applied: bool) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+        if !self.intents {
+            return Ok(false);
+        }
+        let (mut text, count) = std::mem::take(&mut self.pending);
+        if !applied || count == 0 {
+            return Ok(true);
+        }
+        let declare = !self.intents_declared;
+        if declare {
+            text.insert_str(0, &format!("{}\n", INTENTS_RECORD));
+        }
+        text.push_str(COMMIT_RECORD);
+        text.push('\n');
+        self.write_lines(&text, count)?;
+        self.intents_declared |= declare;
+        Ok(true)
+    }
+}
@@ -0,0 +1,82 @@
+use crate::memimg::error::MemImgError;
+use crate::memimg::processor::{Command, CommandReceipt, MemImgProcessor, Query};
+use crate::memimg::storage::EventStorage;
+use std::collections::btree_map::Entry;
+use std::collections::BTreeMap;
+
+type TenantOpener<S, C, E> = Box<dyn FnMut(&str) -> Result<MemImgProcessor<S, C, E>, MemImgError> + Send>;
+
+/// Whether `tenant` can be used as a tenant id
+fn valid_tenant_id(tenant: &str) -> bool {
+    !tenant.is_empty() && tenant.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// One processor per tenant, each with its own state and event log, routed by tenant id
+///
+/// Tenants share nothing: a command only ever sees its own tenant's state, so it cannot name
+/// another tenant's entities; a bank transfer to another tenant's account fails as an unknown
+/// account. `open` builds a tenant's processor, typically over a log named after the tenant.
+pub struct MultiTenantProcessor<S, C, E>
+where
+    S: Clone,
+    C: Command<System = S>,
+    E: EventStorage<Event = C>,
+{
+    tenants: BTreeMap<String, MemImgProcessor<S, C, E>>,
+    open: TenantOpener<S, C, E>,
+}
+
+impl<S, C, E> MultiTenantProcessor<S, C, E>
+where
+    S: Clone,
+    C: Command<System = S>,
+    E: EventStorage<Event = C>,
+{
+    pub fn new(open: impl FnMut(&str) -> Result<MemImgProcessor<S, C, E>, MemImgError> + Send + 'static) -> Self {
+        Self {
+            tenants: BTreeMap::new(),
+            open: Box::new(open),
+        }
+    }
+
+    /// Open `tenant`, replaying its log; a tenant already open is left as it is
+    pub fn open_tenant(&mut self, tenant: &str) -> Result<&mut MemImgProcessor<S, C, E>, MemImgError> {
+        if !valid_tenant_id(tenant) {
+            return Err(MemImgError::InvalidTenantId(tenant.to_string()));
+        }
+        match self.tenants.entry(tenant.to_string()) {
+            Entry::Occupied(open) => Ok(open.into_mut()),
+            Entry::Vacant(closed) => Ok(closed.insert((self.open)(tenant)?)),
+        }
+    }
+
+    /// Ids of the open tenants, sorted
+    pub fn tenants(&self) -> impl Iterator<Item = &str> {
+        self.tenants.keys().map(String::as_str)
+    }
+
+    pub fn tenant(&self, tenant: &str) -> Option<&MemImgProcessor<S, C, E>> {
+        self.tenants.get(tenant)
+    }
+
+    pub fn tenant_mut(&mut self, tenant: &str) -> Option<&mut MemImgProcessor<S, C, E>> {
+        self.tenants.get_mut(tenant)
+    }
+
+    /// Execute `command` against `tenant`'s state and log
+    pub fn execute_command(&mut self, tenant: &str, command: C) -> Result<CommandReceipt, MemImgError> {
+        self.tenant_mut(tenant)
+            .ok_or_else(|| MemImgError::UnknownTenant(tenant.to_string()))?
+            .execute_command(command)
+    }
+
+    /// Answer `query` from `tenant`'s state
+    pub fn execute_query<Q>(&self, tenant: &str, query: &Q) -> Result<Q::Result, MemImgError>
+    where
+        Q: Query<System = S>,
+    {
+        self.tenant(tenant)
+            .ok_or_else(|| MemImgError::UnknownTenant(tenant.to_string()))?
+            .execute_query(query)
+    }
+}
@@ -0,0 +1,597 @@
+use crate::memimg::bank::{AccountId, Amount, BankCommand};
+use crate::memimg::bank_storage::BankJsonConverter;
+use crate::memimg::error::{MemImgError, StorageError, StorageOp};
+use crate::memimg::event_id::EventId;
+use crate::memimg::processor::{Command, MemImgProcessor, Query};
+use crate::memimg::rollback::RollbackStrategy;
+use crate::memimg::storage::EventStorage;
+use crate::memimg::text_file_storage::TextFileEventStorage;
+use crate::memimg::validation::StateDiff;
+use crate::memimg::warning::Warning;
+use proptest::arbitrary::Arbitrary;
+use proptest::collection::vec;
+use proptest::prelude::*;
+use proptest::strategy::BoxedStrategy;
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::fmt;
+use std::io::ErrorKind;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+fn sample_events() -> Vec<BankCommand> {
+    vec![
+        BankCommand::CreateAccount {
+            id: "acc1".into(),
+            name: "Alice".to_string(),
+            opening_balance: None,
+        },
+        BankCommand::CreateAccount {
+            id: "acc2".into(),
+            name: "Bob".to_string(),
+            opening_balance: None,
+        },
+        BankCommand::Deposit {
+            account_id: "acc1".into(),
+            amount: Decimal::new(10050, 2),
+        },
+        BankCommand::Transfer {
+            from_account_id: "acc1".into(),
+            to_account_id: "acc2".into(),
+            amount: Decimal::new(25, 0),
+        },
+        BankCommand::Withdrawal {
+            account_id: "acc2".into(),
+            amount: Decimal::new(5, 0),
+        },
+    ]
+}
+
+fn replay_all<S: EventStorage<Event = BankCommand>>(storage: &mut S) -> Vec<BankCommand> {
+    let mut replayed = Vec::new();
+    storage
+        .replay(&mut |event| {
+            replayed.push(event);
+            Ok(())
+        })
+        .expect("replay failed");
+    replayed
+}
+
+/// Assert that a storage backend honors the `EventStorage` contract.
+///
+/// `make` must return a fresh, empty storage on every call. Covers empty replay,
+/// round-trip, ordering, and appending after a replay.
+pub fn assert_storage_conformance<S, M>(make: M)
+where
+    S: EventStorage<Event = BankCommand>,
+    M: Fn() -> S,
+{
+    // Empty replay
+    let mut storage = make();
+    assert!(replay_all(&mut storage).is_empty(), "fresh storage must replay no events");
+
+    // Round-trip and ordering
+    let mut storage = make();
+    let events = sample_events();
+    for event in &events {
+        storage.append(event).expect("append failed");
+    }
+    assert_eq!(replay_all(&mut storage), events, "replay must return appended events in order");
+    assert_eq!(replay_all(&mut storage), events, "replay must be repeatable");
+
+    // Append after replay
+    let mut storage = make();
+    let (first, rest) = events.split_at(2);
+    for event in first {
+        storage.append(event).expect("append failed");
+    }
+    assert_eq!(replay_all(&mut storage), first);
+    for event in rest {
+        storage.append(event).expect("append after replay failed");
+    }
+    assert_eq!(replay_all(&mut storage), events, "appends after replay must follow earlier events");
+}
+
+/// How a fresh replay of a processor's log differs from its live state
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DivergenceReport {
+    /// Keys of the entities that differ, sorted; empty when compared by fingerprint
+    pub divergent_keys: Vec<String>,
+    /// Live and replayed fingerprints, when compared by fingerprint
+    pub fingerprints: Option<(String, String)>,
+}
+
+impl DivergenceReport {
+    /// The first entity, in key order, whose replayed state differs from the live one
+    pub fn first_divergent_key(&self) -> Option<&str> {
+        self.divergent_keys.first().map(String::as_str)
+    }
+}
+
+impl fmt::Display for DivergenceReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.fingerprints, self.first_divergent_key()) {
+            (Some((live, replayed)), _) => {
+                write!(f, "replayed fingerprint {:?} differs from live fingerprint {:?}", replayed, live)
+            }
+            (None, Some(key)) => write!(
+                f,
+                "replayed state first diverges at key {:?} ({} divergent: {})",
+                key,
+                self.divergent_keys.len(),
+                self.divergent_keys.join(", ")
+            ),
+            (None, None) => write!(f, "replayed state differs from live state"),
+        }
+    }
+}
+
+/// Replay `processor`'s own log into a fresh system and compare it with the live state,
+/// naming the divergent entities through `StateDiff`
+///
+/// Only meaningful while the log holds every event, i.e. before any `checkpoint_and_compact`.
+pub fn check_replay_deterministic<S, C, E>(processor: &mut MemImgProcessor<S, C, E>) -> Result<Option<DivergenceReport>, MemImgError>
+where
+    S: Clone + Default + PartialEq + StateDiff,
+    C: Command<System = S>,
+    E: EventStorage<Event = C>,
+{
+    let replayed = processor.replay_from_scratch()?;
+    if &replayed == processor.system() {
+        return Ok(None);
+    }
+    Ok(Some(DivergenceReport {
+        divergent_keys: processor.system().diff_keys(&replayed),
+        fingerprints: None,
+    }))
+}
+
+/// Like `check_replay_deterministic`, for systems without `PartialEq` or `StateDiff`:
+/// the states are compared by `fingerprint`
+pub fn check_replay_deterministic_by<S, C, E, F>(
+    processor: &mut MemImgProcessor<S, C, E>,
+    fingerprint: F,
+) -> Result<Option<DivergenceReport>, MemImgError>
+where
+    S: Clone + Default,
+    C: Command<System = S>,
+    E: EventStorage<Event = C>,
+    F: Fn(&S) -> String,
+{
+    let replayed = fingerprint(&processor.replay_from_scratch()?);
+    let live = fingerprint(processor.system());
+    if replayed == live {
+        return Ok(None);
+    }
+    Ok(Some(DivergenceReport {
+        divergent_keys: Vec::new(),
+        fingerprints: Some((live, replayed)),
+    }))
+}
+
+/// Assert that replaying `processor`'s log rebuilds its live state; see `check_replay_deterministic`
+pub fn assert_replay_deterministic<S, C, E>(processor: &mut MemImgProcessor<S, C, E>)
+where
+    S: Clone + Default + PartialEq + StateDiff,
+    C: Command<System = S>,
+    E: EventStorage<Event = C>,
+{
+    match check_replay_deterministic(processor) {
+        Ok(None) => {}
+        Ok(Some(report)) => panic!("replay is not deterministic: {}", report),
+        Err(e) => panic!("replay failed: {}", e),
+    }
+}
+
+/// Assert that replaying `processor`'s log rebuilds a state with the live `fingerprint`
+pub fn assert_replay_deterministic_by<S, C, E, F>(processor: &mut MemImgProcessor<S, C, E>, fingerprint: F)
+where
+    S: Clone + Default,
+    C: Command<System = S>,
+    E: EventStorage<Event = C>,
+    F: Fn(&S) -> String,
+{
+    match check_replay_deterministic_by(processor, fingerprint) {
+        Ok(None) => {}
+        Ok(Some(report)) => panic!("replay is not deterministic: {}", report),
+        Err(e) => panic!("replay failed: {}", e),
+    }
+}
+
+/// Environment variable that makes `assert_state_matches_json` rewrite goldens instead of comparing
+pub const BLESS_ENV_VAR: &str = "RMEMIMG_BLESS";
+
+static FIXTURE_COPIES: AtomicU64 = AtomicU64::new(0);
+
+/// Resolve `path` against this crate's `tests/fixtures/`; absolute paths are used as given
+pub fn fixture_path(path: impl AsRef<Path>) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(path)
+}
+
+/// Open a bank event log fixture through a private copy, so tests can append without
+/// dirtying the checked-in file
+///
+/// Copies live under the system temp dir and are not removed.
+pub fn fixture_storage(path: impl AsRef<Path>) -> TextFileEventStorage<BankCommand, BankJsonConverter> {
+    let source = fixture_path(path);
+    let dir = std::env::temp_dir().join(format!(
+        "rmemimg-fixture-{}-{}",
+        std::process::id(),
+        FIXTURE_COPIES.fetch_add(1, Ordering::Relaxed)
+    ));
+    std::fs::create_dir_all(&dir).expect("creating fixture dir failed");
+    let copy = dir.join(source.file_name().expect("fixture path has no file name"));
+    std::fs::copy(&source, &copy).unwrap_or_else(|e| panic!("copying fixture {} failed: {}", source.display(), e));
+    TextFileEventStorage::new(&copy, BankJsonConverter::new()).expect("opening fixture copy failed")
+}
+
+/// Assert that `processor`'s state serializes to the pretty JSON in the golden file at
+/// `expected_json_path` (resolved like `fixture_path`), showing the differing lines on mismatch
+///
+/// With `RMEMIMG_BLESS=1` the golden is rewritten from the current state instead.
+pub fn assert_state_matches_json<S, C, E>(processor: &MemImgProcessor<S, C, E>, expected_json_path: impl AsRef<Path>)
+where
+    S: Clone + Serialize,
+    C: Command<System = S>,
+    E: EventStorage<Event = C>,
+{
+    let golden = fixture_path(expected_json_path);
+    let actual = serde_json::to_string_pretty(processor.system()).expect("serializing state failed") + "\n";
+    if std::env::var(BLESS_ENV_VAR).is_ok_and(|value| value == "1") {
+        std::fs::write(&golden, &actual).unwrap_or_else(|e| panic!("blessing {} failed: {}", golden.display(), e));
+        return;
+    }
+
+    let expected = std::fs::read_to_string(&golden)
+        .unwrap_or_else(|e| panic!("reading golden {} failed: {} (run with {}=1 to create it)", golden.display(), e, BLESS_ENV_VAR));
+    if expected != actual {
+        panic!(
+            "state does not match golden {} (run with {}=1 to regenerate it):\n{}",
+            golden.display(),
+            BLESS_ENV_VAR,
+            line_diff(&expected, &actual)
+        );
+    }
+}
+
+impl<S, C, E, R> MemImgProcessor<S, C, E, R>
+where
+    C: Command<System = S>,
+    E: EventStorage<Event = C>,
+    R: RollbackStrategy<S>,
+{
+    /// Run `assertions` against the current state, naming the state type and event version when
+    /// one of them fails
+    pub fn assert_state<F: FnOnce(&S)>(&self, assertions: F) {
+        let context = format!("state assertion failed on {} at event {}", std::any::type_name::<S>(), self.event_version());
+        check(&context, || assertions(self.system()));
+    }
+
+    /// Execute `query` and run `assertions` against its result; a failing query is returned, not asserted
+    pub fn assert_query<Q, F>(&self, query: &Q, assertions: F) -> Result<(), MemImgError>
+    where
+        Q: Query<System = S>,
+        F: FnOnce(Q::Result),
+    {
+        let result = self.execute_query(query)?;
+        let context = format!("query assertion failed on {} at event {}", std::any::type_name::<Q>(), self.event_version());
+        check(&context, || assertions(result));
+        Ok(())
+    }
+}
+
+// Re-panic a failed assertion with `context` in front of its message
+fn check(context: &str, assertions: impl FnOnce()) {
+    if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(assertions)) {
+        let message = payload
+            .downcast_ref::<String>()
+            .map(String::as_str)
+            .or_else(|| payload.downcast_ref::<&str>().copied())
+            .unwrap_or("non-string panic payload");
+        panic!("{}: {}", context, message);
+    }
+}
+
+const MAX_DIFF_LINES: usize = 20;
+
+// Positional line diff: good enough to spot the changed fields of a pretty-printed state
+fn line_diff(expected: &str, actual: &str) -> String {
+    let expected: Vec<&str> = expected.lines().collect();
+    let actual: Vec<&str> = actual.lines().collect();
+    let mut diff = Vec::new();
+    for line in 0..expected.len().max(actual.len()) {
+        let (before, after) = (expected.get(line), actual.get(line));
+        if before == after {
+            continue;
+        }
+        if diff.len() == MAX_DIFF_LINES {
+            diff.push("...".to_string());
+            break;
+        }
+        let mut hunk = format!("line {}:", line + 1);
+        if let Some(before) = before {
+            hunk.push_str(&format!("\n- {}", before));
+        }
+        if let Some(after) = after {
+            hunk.push_str(&format!("\n+ {}", after));
+        }
+        diff.push(hunk);
+    }
+    diff.join("\n")
+}
+
+/// Account ids the strategies draw from; a small pool makes commands collide often
+pub const STRATEGY_ACCOUNT_IDS: [&str; 4] = ["alice", "bob", "carol", "dave"];
+
+/// Amounts up to 1,000.00 either way, in cents so they print exactly; zero and negative ones are
+/// rejected as invalid, so they are kept rare
+pub fn amount_strategy() -> impl Strategy<Value = Amount> {
+    prop_oneof![
+        18 => (1i64..=100_000).prop_map(|cents| Decimal::new(cents, 2)),
+        1 => Just(Decimal::ZERO),
+        1 => (-100_000i64..=-1).prop_map(|cents| Decimal::new(cents, 2)),
+    ]
+}
+
+fn account_id_strategy() -> impl Strategy<Value = AccountId> {
+    // Mostly known ids, sometimes one that never exists
+    prop_oneof![
+        9 => proptest::sample::select(STRATEGY_ACCOUNT_IDS.as_slice()).prop_map(AccountId::from),
+        1 => Just(AccountId::from("nobody")),
+    ]
+}
+
+/// Bank commands weighted toward ones that usually succeed against accounts from
+/// `STRATEGY_ACCOUNT_IDS`, but including overdrafts, unknown accounts and duplicates
+pub fn bank_command_strategy() -> impl Strategy<Value = BankCommand> {
+    prop_oneof![
+        2 => account_id_strategy().prop_map(|id| BankCommand::CreateAccount { name: id.as_str().to_uppercase(), id, opening_balance: None }),
+        4 => (account_id_strategy(), amount_strategy()).prop_map(|(account_id, amount)| BankCommand::Deposit { account_id, amount }),
+        2 => (account_id_strategy(), amount_strategy()).prop_map(|(account_id, amount)| BankCommand::Withdrawal { account_id, amount }),
+        3 => (account_id_strategy(), account_id_strategy(), amount_strategy()).prop_map(|(from_account_id, to_account_id, amount)| {
+            BankCommand::Transfer { from_account_id, to_account_id, amount }
+        }),
+        1 => account_id_strategy().prop_map(|id| BankCommand::CloseAccount { id }),
+    ]
+}
+
+/// Sequences of up to `max_len` commands from `bank_command_strategy`
+pub fn bank_commands_strategy(max_len: usize) -> impl Strategy<Value = Vec<BankCommand>> {
+    vec(bank_command_strategy(), 0..=max_len)
+}
+
+impl Arbitrary for BankCommand {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_args: Self::Parameters) -> Self::Strategy {
+        bank_command_strategy().boxed()
+    }
+}
+
+/// Operation counts of a `FaultyEventStorage`; attempts include injected failures
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FaultStats {
+    pub append_attempts: u64,
+    pub appends_succeeded: u64,
+    pub replay_attempts: u64,
+    pub replays_succeeded: u64,
+}
+
+/// Counters shared between a `FaultyEventStorage` and observers on other threads
+#[derive(Debug, Default)]
+pub struct FaultCounters {
+    append_attempts: AtomicU64,
+    appends_succeeded: AtomicU64,
+    replay_attempts: AtomicU64,
+    replays_succeeded: AtomicU64,
+}
+
+impl FaultCounters {
+    pub fn stats(&self) -> FaultStats {
+        FaultStats {
+            append_attempts: self.append_attempts.load(Ordering::SeqCst),
+            appends_succeeded: self.appends_succeeded.load(Ordering::SeqCst),
+            replay_attempts: self.replay_attempts.load(Ordering::SeqCst),
+            replays_succeeded: self.replays_succeeded.load(Ordering::SeqCst),
+        }
+    }
+}
+
+fn injected(op: StorageOp, kind: ErrorKind, message: String) -> Box<dyn std::error::Error + Send + Sync> {
+    Box::new(StorageError::new("<fault injection>", op, std::io::Error::new(kind, message)))
+}
+
+/// Storage wrapper that fails on a deterministic, programmed script, for testing how
+/// the processor handles storage failures
+///
+/// Append numbers count attempts from 1, including attempts that were made to fail.
+pub struct FaultyEventStorage<S: EventStorage> {
+    inner: S,
+    fail_append_at: Option<(u64, ErrorKind)>,
+    failing_appends: u64,
+    failing_append_kind: ErrorKind,
+    fail_every_append: Option<(u64, ErrorKind)>,
+    /// Bytes the appends may take before failing
+    byte_budget: Option<u64>,
+    event_size: fn(&S::Event) -> u64,
+    bytes_appended: u64,
+    fail_replay_at: Option<u64>,
+    latency: Duration,
+    counters: Arc<FaultCounters>,
+}
+
+impl<S: EventStorage> FaultyEventStorage<S> {
+    pub fn new(inner: S) -> Self {
+        Self {
+            inner,
+            fail_append_at: None,
+            failing_appends: 0,
+            failing_append_kind: ErrorKind::Other,
+            fail_every_append: None,
+            byte_budget: None,
+            event_size: |_| 0,
+            bytes_appended: 0,
+            fail_replay_at: None,
+            latency: Duration::ZERO,
+            counters: Arc::new(FaultCounters::default()),
+        }
+    }
+
+    /// Fail the `n`th append attempt with an I/O error of `kind`
+    pub fn with_failing_append(mut self, n: u64, kind: ErrorKind) -> Self {
+        self.fail_append_at = Some((n, kind));
+        self
+    }
+
+    /// Fail the next `m` append attempts with `kind`, then succeed; `u64::MAX` fails them all
+    pub fn with_failing_appends(mut self, m: u64, kind: ErrorKind) -> Self {
+        self.failing_appends = m;
+        self.failing_append_kind = kind;
+        self
+    }
+
+    /// Fail every `n`th append attempt (the `n`th, the `2n`th and so on) with `kind`
+    pub fn with_failing_every_append(mut self, n: u64, kind: ErrorKind) -> Self {
+        self.fail_every_append = Some((n, kind));
+        self
+    }
+
+    /// Fail with `StorageFull` every append that would take the events appended past `bytes`,
+    /// counting each event as its JSON line, as a disk of that size would
+    pub fn with_failing_after_bytes(mut self, bytes: u64) -> Self
+    where
+        S::Event: Serialize,
+    {
+        self.byte_budget = Some(bytes);
+        self.event_size = |event| serde_json::to_string(event).map_or(0, |json| json.len() as u64 + 1);
+        self
+    }
+
+    /// Fail every replay when it reaches event `k` (1-based), after delivering the ones before it
+    pub fn with_failing_replay_at(mut self, k: u64) -> Self {
+        self.fail_replay_at = Some(k);
+        self
+    }
+
+    /// Sleep for `latency` before every append and replay
+    pub fn with_latency(mut self, latency: Duration) -> Self {
+        self.latency = latency;
+        self
+    }
+
+    /// Counters that stay readable while a processor owns this storage
+    pub fn counters(&self) -> Arc<FaultCounters> {
+        Arc::clone(&self.counters)
+    }
+
+    pub fn stats(&self) -> FaultStats {
+        self.counters.stats()
+    }
+
+    pub fn inner(&self) -> &S {
+        &self.inner
+    }
+
+    fn delay(&self) {
+        if !self.latency.is_zero() {
+            std::thread::sleep(self.latency);
+        }
+    }
+
+    /// Count an append attempt of `events` and fail it if a configured fault says so
+    fn inject_append_failure(&mut self, events: &[S::Event]) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let attempt = self.counters.append_attempts.fetch_add(1, Ordering::SeqCst) + 1;
+        if self.failing_appends > 0 {
+            self.failing_appends -= 1;
+            return Err(injected(StorageOp::Append, self.failing_append_kind, format!("injected append failure on attempt {}", attempt)));
+        }
+        if let Some((n, kind)) = self.fail_append_at {
+            if n == attempt {
+                return Err(injected(StorageOp::Append, kind, format!("injected append failure on attempt {}", attempt)));
+            }
+        }
+        if let Some((n, kind)) = self.fail_every_append {
+            if attempt.is_multiple_of(n) {
+                return Err(injected(StorageOp::Append, kind, format!("injected append failure on attempt {}", attempt)));
+            }
+        }
+        if let Some(budget) = self.byte_budget {
+            let bytes = self.bytes_appended + events.iter().map(self.event_size).sum::<u64>();
+            if bytes > budget {
+                let message = format!("injected append failure on attempt {}: {} bytes exceed {}", attempt, bytes, budget);
+                return Err(injected(StorageOp::Append, ErrorKind::StorageFull, message));
+            }
+            self.bytes_appended = bytes;
+        }
+        Ok(())
+    }
+}
+
+impl<S: EventStorage> EventStorage for FaultyEventStorage<S> {
+    type Event = S::Event;
+
+    fn replay<F>(&mut self, consumer: &mut F) -> Result<(), Box<dyn std::error::Error + Send + Sync>>
+    where
+        F: FnMut(Self::Event) -> Result<(), Box<dyn std::error::Error + Send + Sync>>,
+    {
+        self.delay();
+        self.counters.replay_attempts.fetch_add(1, Ordering::SeqCst);
+        let fail_at = self.fail_replay_at;
+        let mut index = 0u64;
+        self.inner.replay(&mut |event| {
+            index += 1;
+            if fail_at == Some(index) {
+                return Err(injected(StorageOp::Read, ErrorKind::InvalidData, format!("injected replay failure at event {}", index)));
+            }
+            consumer(event)
+        })?;
+        self.counters.replays_succeeded.fetch_add(1, Ordering::SeqCst);
+        Ok(())
+    }
+
+    fn append(&mut self, event: &Self::Event) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        self.delay();
+        self.inject_append_failure(std::slice::from_ref(event))?;
+        self.inner.append(event)?;
+        self.counters.appends_succeeded.fetch_add(1, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Counts as a single append attempt, failing or succeeding as a whole
+    fn append_atomic(&mut self, events: &[Self::Event]) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        self.delay();
+        self.inject_append_failure(events)?;
+        self.inner.append_atomic(events)?;
+        self.counters.appends_succeeded.fetch_add(1, Ordering::SeqCst);
+        Ok(())
+    }
+
+    fn truncate_before(&mut self, first_kept: EventId) -> Result<u64, Box<dyn std::error::Error + Send + Sync>> {
+        self.inner.truncate_before(first_kept)
+    }
+
+    fn flush(&mut self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        self.inner.flush()
+    }
+
+    fn drain_warnings(&mut self) -> Vec<Warning> {
+        self.inner.drain_warnings()
+    }
+
+    fn writes_through(&self) -> bool {
+        self.inner.writes_through()
+    }
+
+    fn append_marker(&mut self, text: &str) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+        self.inner.append_marker(text)
+    }
+
+    fn settle_intent(&mut self, applied: bool) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+        self.inner.settle_intent(applied)
+    }
+}
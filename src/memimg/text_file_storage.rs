@@ -0,0 +1,929 @@
+use crate::memimg::cursor::LogCursor;
+use crate::memimg::error::StorageOp;
+use crate::memimg::event_id::EventId;
+use crate::memimg::json_event::{JsonEvent, JsonEventConverter};
+use crate::memimg::storage::{EventStorage, ReplayPolicy, TextConverter};
+use crate::memimg::stream_storage::{is_event_line, is_intent_record, storage_error, COMMIT_RECORD, INTENTS_RECORD, MARKER_PREFIX};
+use crate::memimg::stream_storage::{event_lines, marker_line, IntentFraming, LineReplay, RepairTail};
+use crate::memimg::warning::Warning;
+use flate2::read::MultiGzDecoder;
+use serde::Serialize;
+use std::fs::{File, OpenOptions, TryLockError};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// When appended events reach the file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Durability {
+    /// Write each event through to the file as it is appended
+    #[default]
+    EveryEvent,
+    /// Buffer appends in memory until the buffer fills, `flush` is called, an auto-flush fires, or the storage drops
+    Buffered,
+}
+
+/// An operator's note found in a log by `TextFileEventStorage::markers`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogMarker {
+    /// Events before the marker in the file
+    pub after_event: u64,
+    pub text: String,
+}
+
+/// How often `LogLock::acquire_within` retries a held lock
+const LOCK_RETRY_INTERVAL: Duration = Duration::from_millis(10);
+
+type SharedWriter = Arc<Mutex<Option<BufWriter<File>>>>;
+
+/// The lines starting at `offsets`, in ascending order, moving `reader` forward with `skip`
+fn read_lines_at<R: BufRead>(mut reader: R, offsets: &[u64], mut skip: impl FnMut(&mut R, u64) -> std::io::Result<()>) -> std::io::Result<Vec<String>> {
+    let mut position = 0u64;
+    let mut lines = Vec::with_capacity(offsets.len());
+    for &offset in offsets {
+        skip(&mut reader, offset - position)?;
+        let mut line = String::new();
+        position = offset + reader.read_line(&mut line)? as u64;
+        lines.push(line.trim_end_matches(['\n', '\r']).to_string());
+    }
+    Ok(lines)
+}
+
+/// Byte offset in the file at which the next line written through `writer` will start
+fn append_offset(writer: &BufWriter<File>, file_path: &str) -> Result<u64, Box<dyn std::error::Error + Send + Sync>> {
+    let written = writer.get_ref().metadata().map_err(storage_error(file_path, StorageOp::Append))?.len();
+    Ok(written + writer.buffer().len() as u64)
+}
+
+fn lock_writer(writer: &SharedWriter) -> MutexGuard<'_, Option<BufWriter<File>>> {
+    writer.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Exclusive advisory lock on a log, held by the process using it until dropped
+///
+/// Taken on a `<log>.lock` file beside the log rather than on the log itself, since compaction
+/// replaces the log file. The operating system releases it if the holder dies.
+#[derive(Debug)]
+pub struct LogLock {
+    _file: File,
+}
+
+impl LogLock {
+    /// Lock the log at `log_path`, failing with a `WouldBlock` `StorageError` if another holder has it
+    pub fn acquire<P: AsRef<Path>>(log_path: P) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        Self::acquire_within(log_path, Duration::ZERO)
+    }
+
+    /// Lock the log at `log_path`, retrying until `timeout` has passed while another holder has it
+    pub fn acquire_within<P: AsRef<Path>>(log_path: P, timeout: Duration) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let lock_path = format!("{}.lock", log_path.as_ref().to_string_lossy());
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&lock_path)
+            .map_err(storage_error(&lock_path, StorageOp::Lock))?;
+        let deadline = Instant::now() + timeout;
+        loop {
+            match file.try_lock() {
+                Ok(()) => return Ok(Self { _file: file }),
+                Err(TryLockError::WouldBlock) if Instant::now() < deadline => std::thread::sleep(LOCK_RETRY_INTERVAL),
+                Err(TryLockError::WouldBlock) => {
+                    let message = if timeout.is_zero() {
+                        "the log is locked by another writer".to_string()
+                    } else {
+                        format!("the log is still locked by another writer after {:?}", timeout)
+                    };
+                    let error = std::io::Error::new(std::io::ErrorKind::WouldBlock, message);
+                    return Err(storage_error(&lock_path, StorageOp::Lock)(error));
+                }
+                Err(TryLockError::Error(e)) => return Err(storage_error(&lock_path, StorageOp::Lock)(e)),
+            }
+        }
+    }
+}
+
+/// Background thread that flushes and syncs a shared writer every interval
+struct AutoFlush {
+    stop: Option<mpsc::Sender<()>>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl AutoFlush {
+    fn start(writer: SharedWriter, interval: Duration) -> Self {
+        let (stop, stopped) = mpsc::channel::<()>();
+        let handle = std::thread::spawn(move || {
+            while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                // Best effort: an explicit `flush` reports errors to the caller
+                if let Some(writer) = lock_writer(&writer).as_mut() {
+                    let _ = writer.flush().and_then(|_| writer.get_ref().sync_data());
+                }
+            }
+        });
+        Self {
+            stop: Some(stop),
+            handle: Some(handle),
+        }
+    }
+}
+
+impl Drop for AutoFlush {
+    fn drop(&mut self) {
+        // Disconnecting the channel wakes the thread and ends its loop
+        self.stop.take();
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+/// Keys `TextFileEventStorage::with_event_index` files an event under, such as the accounts it names
+pub type EventIndexKeys<E> = dyn Fn(&E) -> Vec<String> + Send + Sync;
+
+/// Byte offsets of the event lines filed under each key, in log order
+struct EventIndex<E> {
+    keys: Box<EventIndexKeys<E>>,
+    offsets: HashMap<String, Vec<u64>>,
+}
+
+impl<E> EventIndex<E> {
+    fn insert(&mut self, offset: u64, event: &E) {
+        for key in (self.keys)(event) {
+            self.offsets.entry(key).or_default().push(offset);
+        }
+    }
+
+    /// Drop the offsets of lines cut off the file at `offset`
+    fn truncate(&mut self, offset: u64) {
+        for offsets in self.offsets.values_mut() {
+            offsets.retain(|&start| start < offset);
+        }
+    }
+}
+
+/// File-based event storage using line-oriented text format
+///
+/// A path ending in `.gz` is a read-only compressed archive segment: replay gunzips it
+/// transparently, while appends and truncation are refused. Keep appending to a plain segment.
+pub struct TextFileEventStorage<E, C>
+where
+    C: TextConverter<E>,
+{
+    file_path: String,
+    converter: C,
+    compressed: bool,
+    /// Set by `TextFileEventStorageBuilder::read_only`
+    read_only: bool,
+    writer: SharedWriter,
+    durability: Durability,
+    auto_flush: Option<AutoFlush>,
+    /// Set by `with_flush_every`: the batch size, and buffered appends since the last batch was synced
+    flush_every: Option<(u64, u64)>,
+    replay_policy: ReplayPolicy,
+    warnings: Vec<Warning>,
+    lock: Option<LogLock>,
+    event_index: Option<EventIndex<E>>,
+    /// Set by `with_write_ahead_intents`
+    intents: bool,
+    /// Whether the file holds an `INTENTS_RECORD`, as far as this storage has seen
+    intents_declared: bool,
+    /// Offset of the first event appended since the last `settle_intent`
+    pending_offset: Option<u64>,
+    _phantom: PhantomData<fn() -> E>,
+}
+
+impl<E, C> TextFileEventStorage<E, C>
+where
+    C: TextConverter<E>,
+{
+    /// Open the log at `path`, holding its `LogLock` for as long as this storage lives
+    ///
+    /// Fails at once if another storage or maintenance tool holds the lock, so two processes never
+    /// append to one log; `new_with_lock_timeout` waits for it instead. Compressed archive segments
+    /// are never written, so they are not locked.
+    pub fn new<P: AsRef<Path>>(path: P, converter: C) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        Self::new_with_lock_timeout(path, converter, Duration::ZERO)
+    }
+
+    /// Like `new`, but wait up to `lock_timeout` for another holder to release the log
+    pub fn new_with_lock_timeout<P: AsRef<Path>>(path: P, converter: C, lock_timeout: Duration) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let mut storage = Self::new_unlocked(path, converter)?;
+        if !storage.compressed {
+            storage.lock = Some(LogLock::acquire_within(&storage.file_path, lock_timeout)?);
+        }
+        Ok(storage)
+    }
+
+    /// Open the log at `path` without locking it, for callers that hold its `LogLock` themselves
+    /// or otherwise ensure a single writer
+    pub fn new_unlocked<P: AsRef<Path>>(path: P, converter: C) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let file_path = path.as_ref().to_string_lossy().to_string();
+        let compressed = path.as_ref().extension().is_some_and(|extension| extension == "gz");
+
+        // Ensure parent directory exists
+        if let Some(parent) = path.as_ref().parent() {
+            let parent_path = parent.to_string_lossy();
+            std::fs::create_dir_all(parent).map_err(storage_error(&parent_path, StorageOp::CreateDir))?;
+        }
+
+        // Create file if it doesn't exist; an archive must already exist, as an empty file is not valid gzip
+        if !compressed && !path.as_ref().exists() {
+            File::create(&path).map_err(storage_error(&file_path, StorageOp::Create))?;
+        }
+
+        Ok(Self {
+            file_path,
+            converter,
+            compressed,
+            read_only: false,
+            writer: Arc::new(Mutex::new(None)),
+            durability: Durability::default(),
+            auto_flush: None,
+            flush_every: None,
+            replay_policy: ReplayPolicy::default(),
+            warnings: Vec::new(),
+            lock: None,
+            event_index: None,
+            intents: false,
+            intents_declared: false,
+            pending_offset: None,
+            _phantom: PhantomData,
+        })
+    }
+
+    /// Builder for opening the log at `path` with options that `new` leaves at their defaults
+    pub fn builder<P: AsRef<Path>>(path: P, converter: C) -> TextFileEventStorageBuilder<E, C> {
+        TextFileEventStorageBuilder::new(path, converter)
+    }
+
+    /// Set the policy applied to unparseable lines during replay
+    pub fn with_replay_policy(mut self, replay_policy: ReplayPolicy) -> Self {
+        self.replay_policy = replay_policy;
+        self
+    }
+
+    /// Keep the offsets of the events filed under each of `keys(event)`, so `indexed_events` reads
+    /// just those lines instead of scanning the log
+    ///
+    /// The index lives in memory: each replay rebuilds it, appends extend it, and `split_at` and
+    /// truncation re-read the shortened file.
+    pub fn with_event_index(mut self, keys: impl Fn(&E) -> Vec<String> + Send + Sync + 'static) -> Self {
+        self.event_index = Some(EventIndex { keys: Box::new(keys), offsets: HashMap::new() });
+        self
+    }
+
+    /// Events filed under `key`, oldest first, or `None` if this storage keeps no index
+    pub fn indexed_events(&self, key: &str) -> Result<Option<Vec<E>>, Box<dyn std::error::Error + Send + Sync>> {
+        let Some(index) = &self.event_index else {
+            return Ok(None);
+        };
+        let Some(offsets) = index.offsets.get(key) else {
+            return Ok(Some(Vec::new()));
+        };
+        self.write_through()?;
+        // An archive cannot seek, so its reader skips forward to each offset instead
+        let lines = if self.compressed {
+            let reader = BufReader::new(self.open_reader()?);
+            read_lines_at(reader, offsets, |reader, gap| std::io::copy(&mut reader.by_ref().take(gap), &mut std::io::sink()).map(drop))
+        } else {
+            let file = File::open(&self.file_path).map_err(storage_error(&self.file_path, StorageOp::OpenForReplay))?;
+            read_lines_at(BufReader::new(file), offsets, |reader, gap| reader.seek_relative(gap as i64))
+        };
+        let events = lines
+            .map_err(storage_error(&self.file_path, StorageOp::Read))?
+            .iter()
+            .map(|line| self.converter.parse(line))
+            .collect::<Result<_, _>>()?;
+        Ok(Some(events))
+    }
+
+    /// Rebuild the event index, if kept, from the file as it is now
+    fn reindex(&mut self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        if self.event_index.is_none() {
+            return Ok(());
+        }
+        let mut reader = BufReader::new(self.open_reader()?);
+        let Some(index) = self.event_index.as_mut() else {
+            return Ok(());
+        };
+        index.offsets.clear();
+        let mut offset = 0u64;
+        let mut line = String::new();
+        loop {
+            line.clear();
+            let read = reader.read_line(&mut line).map_err(storage_error(&self.file_path, StorageOp::Read))?;
+            if read == 0 {
+                break;
+            }
+            let text = line.trim_end_matches(['\n', '\r']);
+            // Lines that do not parse were reported by replay; they are simply not indexed
+            if let Some(event) = is_event_line(text).then(|| self.converter.parse(text).ok()).flatten() {
+                index.insert(offset, &event);
+            }
+            offset += read as u64;
+        }
+        Ok(())
+    }
+
+    /// Keep a write-ahead intent for each append: the event stays pending until `settle_intent`
+    /// commits it with a commit record or cuts it back off the file, and replay drops and cuts off
+    /// any events a crash left pending at the end of the log
+    ///
+    /// Under `CommitStrategy::AppendThenApply` this makes application exactly-once: a command that
+    /// fails to apply leaves nothing in the log, and one interrupted between append and apply is
+    /// never replayed. The first append writes a record opening the intents, so events already
+    /// in the log count as committed; readers that skip markers skip the records too.
+    pub fn with_write_ahead_intents(mut self) -> Self {
+        self.intents = true;
+        self
+    }
+
+    /// Set when appended events are written through to the file
+    pub fn with_durability(mut self, durability: Durability) -> Self {
+        self.durability = durability;
+        self
+    }
+
+    /// Flush and fsync buffered events every `interval` from a background thread, bounding how
+    /// much a crash can lose when commands stop arriving. The thread stops on `shutdown` or drop.
+    pub fn with_auto_flush(mut self, interval: Duration) -> Self {
+        self.auto_flush = Some(AutoFlush::start(Arc::clone(&self.writer), interval));
+        self
+    }
+
+    /// Flush and fsync buffered events after every `events` appends, bounding how many a crash
+    /// can lose however slowly they arrive. Only meaningful with `Durability::Buffered`.
+    pub fn with_flush_every(mut self, events: u64) -> Self {
+        self.flush_every = Some((events.max(1), 0));
+        self
+    }
+
+    /// Stop any auto-flush thread, then flush and sync every appended event
+    pub fn shutdown(&mut self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        self.auto_flush.take();
+        self.flush()
+    }
+
+    /// Write buffered events to the file so readers of the file see them
+    fn write_through(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        if let Some(writer) = lock_writer(&self.writer).as_mut() {
+            writer.flush().map_err(storage_error(&self.file_path, StorageOp::Flush))?;
+        }
+        Ok(())
+    }
+
+    /// Open the file for reading, gunzipping a compressed archive
+    fn open_reader(&self) -> Result<Box<dyn Read>, Box<dyn std::error::Error + Send + Sync>> {
+        let file = File::open(&self.file_path).map_err(storage_error(&self.file_path, StorageOp::OpenForReplay))?;
+        if self.compressed {
+            Ok(Box::new(MultiGzDecoder::new(file)))
+        } else {
+            Ok(Box::new(file))
+        }
+    }
+
+    /// Cursor over the log from its first event
+    pub fn open_cursor(&self) -> Result<LogCursor<'_, E, C>, Box<dyn std::error::Error + Send + Sync>> {
+        self.open_cursor_at(0)
+    }
+
+    /// Cursor resuming at `position`, as previously reported by `LogCursor::position`
+    pub fn open_cursor_at(&self, position: u64) -> Result<LogCursor<'_, E, C>, Box<dyn std::error::Error + Send + Sync>> {
+        self.write_through()?;
+        let mut reader = self.open_reader()?;
+        // Compressed archives cannot seek, so skip to the position by reading, looking out for
+        // the record that opens write-ahead intents
+        let mut skipped = 0u64;
+        let mut intents_declared = self.intents_declared;
+        let mut prefix = BufReader::new(reader.by_ref().take(position));
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            let read = prefix.read_until(b'\n', &mut line).map_err(storage_error(&self.file_path, StorageOp::Read))?;
+            if read == 0 {
+                break;
+            }
+            skipped += read as u64;
+            intents_declared |= line.trim_ascii() == INTENTS_RECORD.as_bytes();
+        }
+        if skipped < position {
+            let error = std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "cursor position is past the end of the log");
+            return Err(storage_error(&self.file_path, StorageOp::Read)(error));
+        }
+        Ok(LogCursor::new(&self.file_path, reader, &self.converter, self.replay_policy, position, intents_declared))
+    }
+
+    /// Move events 1..=`sequence` to a new log at `archive_path`, keeping the rest here
+    ///
+    /// Both files are written in full under temporary names and then renamed into place, the
+    /// archive first, so a crash leaves either the original log or both halves. Refuses to
+    /// overwrite an existing archive. Returns the number of events archived; a processor resumes
+    /// the shortened log from a snapshot of the state at `sequence`.
+    pub fn split_at(&mut self, sequence: EventId, archive_path: &Path) -> Result<u64, Box<dyn std::error::Error + Send + Sync>> {
+        if archive_path.exists() {
+            let error = std::io::Error::new(std::io::ErrorKind::AlreadyExists, "archive already exists");
+            return Err(storage_error(&archive_path.to_string_lossy(), StorageOp::Create)(error));
+        }
+        self.rewrite_from(EventId(sequence.as_u64() + 1), Some(archive_path))
+    }
+
+    /// Rewrite the file from `first_kept` on, moving the lines before it to `archive_path` or dropping them
+    fn rewrite_from(&mut self, first_kept: EventId, archive_path: Option<&Path>) -> Result<u64, Box<dyn std::error::Error + Send + Sync>> {
+        self.ensure_writable(StorageOp::Truncate)?;
+        let mut writer = lock_writer(&self.writer);
+        if let Some(writer) = writer.as_mut() {
+            writer.flush().map_err(storage_error(&self.file_path, StorageOp::Flush))?;
+        }
+
+        let create = |path: &str| File::create(path).map(BufWriter::new).map_err(storage_error(path, StorageOp::Truncate));
+        let finish = |mut file: BufWriter<File>, path: &str| -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+            file.flush().map_err(storage_error(path, StorageOp::Truncate))?;
+            file.get_ref().sync_all().map_err(storage_error(path, StorageOp::Truncate))?;
+            Ok(())
+        };
+        let temp_path = format!("{}.compact", self.file_path);
+        let archive_temp_path = archive_path.map(|path| format!("{}.tmp", path.to_string_lossy()));
+        let file = File::open(&self.file_path).map_err(storage_error(&self.file_path, StorageOp::Truncate))?;
+        let mut temp = create(&temp_path)?;
+        let mut archive = archive_temp_path.as_deref().map(create).transpose()?;
+
+        // Markers and blank lines go with the event that follows them, commit records with the
+        // events before them, and an intents record to both files
+        let mut removed = 0u64;
+        let mut last_event_removed = false;
+        for line in BufReader::new(file).lines() {
+            let line = line.map_err(storage_error(&self.file_path, StorageOp::Read))?;
+            let removing = if line.trim() == COMMIT_RECORD { last_event_removed } else { removed + 1 < first_kept.as_u64() };
+            if removing && line.trim() == INTENTS_RECORD {
+                writeln!(temp, "{}", line).map_err(storage_error(&temp_path, StorageOp::Truncate))?;
+            }
+            if is_event_line(&line) {
+                last_event_removed = removing;
+            }
+            if removing {
+                if is_event_line(&line) {
+                    removed += 1;
+                }
+                if let (Some(archive), Some(path)) = (archive.as_mut(), archive_temp_path.as_deref()) {
+                    writeln!(archive, "{}", line).map_err(storage_error(path, StorageOp::Truncate))?;
+                }
+            } else {
+                writeln!(temp, "{}", line).map_err(storage_error(&temp_path, StorageOp::Truncate))?;
+            }
+        }
+        finish(temp, &temp_path)?;
+        if let (Some(archive), Some(archive_temp_path), Some(archive_path)) = (archive, archive_temp_path.as_deref(), archive_path) {
+            finish(archive, archive_temp_path)?;
+            std::fs::rename(archive_temp_path, archive_path).map_err(storage_error(archive_temp_path, StorageOp::Truncate))?;
+        }
+
+        std::fs::rename(&temp_path, &self.file_path).map_err(storage_error(&self.file_path, StorageOp::Truncate))?;
+        // The append handle still points at the replaced file; reopen lazily on the next append
+        *writer = None;
+        drop(writer);
+        self.reindex()?;
+        Ok(removed)
+    }
+
+    /// Every marker in the log, in order, with the number of events before it
+    pub fn markers(&self) -> Result<Vec<LogMarker>, Box<dyn std::error::Error + Send + Sync>> {
+        self.write_through()?;
+        let mut markers = Vec::new();
+        let mut events = 0u64;
+        for line in BufReader::new(self.open_reader()?).lines() {
+            let line = line.map_err(storage_error(&self.file_path, StorageOp::Read))?;
+            if is_intent_record(&line) {
+                continue;
+            }
+            if let Some(text) = line.trim().strip_prefix(MARKER_PREFIX) {
+                markers.push(LogMarker { after_event: events, text: text.trim().to_string() });
+            } else if is_event_line(&line) {
+                events += 1;
+            }
+        }
+        Ok(markers)
+    }
+
+    /// Lock the append writer, opening the file on the first append after replay
+    fn lock_append_writer(&self) -> Result<MutexGuard<'_, Option<BufWriter<File>>>, Box<dyn std::error::Error + Send + Sync>> {
+        let mut writer = lock_writer(&self.writer);
+        if writer.is_none() {
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.file_path)
+                .map_err(storage_error(&self.file_path, StorageOp::OpenForAppend))?;
+            *writer = Some(BufWriter::new(file));
+        }
+        Ok(writer)
+    }
+
+    /// Fail `op` if this storage is a compressed archive or was opened read-only
+    fn ensure_writable(&self, op: StorageOp) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let message = if self.compressed {
+            "compressed archive segments are read-only"
+        } else if self.read_only {
+            "the log was opened read-only"
+        } else {
+            return Ok(());
+        };
+        let error = std::io::Error::new(std::io::ErrorKind::PermissionDenied, message);
+        Err(storage_error(&self.file_path, op)(error))
+    }
+
+    /// Replay events until `limit` have been consumed, returning how many were
+    fn replay_up_to<F>(&mut self, limit: u64, consumer: &mut F) -> Result<u64, Box<dyn std::error::Error + Send + Sync>>
+    where
+        F: FnMut(E) -> Result<(), Box<dyn std::error::Error + Send + Sync>>,
+    {
+        self.write_through()?;
+        let mut reader = BufReader::new(self.open_reader()?);
+        let file_path = &self.file_path;
+        // Cut a torn (unterminated, unparseable) last line off the file; a read-only log is left as it is
+        let mut repair_tail = |offset: u64| -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+            let file = OpenOptions::new().write(true).open(file_path).map_err(storage_error(file_path, StorageOp::Truncate))?;
+            file.set_len(offset).map_err(storage_error(file_path, StorageOp::Truncate))?;
+            Ok(())
+        };
+        let repair_tail: Option<&mut RepairTail<'_>> = if self.compressed || self.read_only { None } else { Some(&mut repair_tail) };
+        let mut index = self.event_index.as_mut();
+        if let Some(index) = index.as_mut() {
+            index.offsets.clear();
+        }
+        let replay = LineReplay {
+            source: file_path,
+            converter: &self.converter,
+            replay_policy: self.replay_policy,
+            warnings: &mut self.warnings,
+            intents_declared: &mut self.intents_declared,
+        };
+        replay.run(&mut reader, limit, repair_tail, &mut |offset, event| {
+            if let Some(index) = index.as_mut() {
+                index.insert(offset, &event);
+            }
+            consumer(event)
+        })
+    }
+}
+
+impl<E, C> EventStorage for TextFileEventStorage<E, C>
+where
+    C: TextConverter<E>,
+{
+    type Event = E;
+
+    fn replay<F>(&mut self, consumer: &mut F) -> Result<(), Box<dyn std::error::Error + Send + Sync>>
+    where
+        F: FnMut(Self::Event) -> Result<(), Box<dyn std::error::Error + Send + Sync>>,
+    {
+        self.replay_up_to(u64::MAX, consumer)?;
+        Ok(())
+    }
+
+    /// Stops reading at the `n`th event, so lines past it are neither parsed nor repaired
+    fn replay_n<F>(&mut self, n: u64, consumer: &mut F) -> Result<u64, Box<dyn std::error::Error + Send + Sync>>
+    where
+        F: FnMut(Self::Event) -> Result<(), Box<dyn std::error::Error + Send + Sync>>,
+    {
+        self.replay_up_to(n, consumer)
+    }
+
+    /// Positions are byte offsets as `LogCursor::position` reports them; a plain file seeks to
+    /// one, so each call reads only the lines it returns
+    fn replay_from<F>(&mut self, position: u64, n: u64, consumer: &mut F) -> Result<(u64, u64), Box<dyn std::error::Error + Send + Sync>>
+    where
+        F: FnMut(Self::Event) -> Result<(), Box<dyn std::error::Error + Send + Sync>>,
+    {
+        let mut cursor = if self.compressed || position == 0 {
+            self.open_cursor_at(position)?
+        } else {
+            self.write_through()?;
+            let mut file = File::open(&self.file_path).map_err(storage_error(&self.file_path, StorageOp::OpenForReplay))?;
+            file.seek(SeekFrom::Start(position)).map_err(storage_error(&self.file_path, StorageOp::Read))?;
+            LogCursor::new(&self.file_path, Box::new(file), &self.converter, self.replay_policy, position, self.intents_declared)
+        };
+        let events = cursor.next_batch(usize::try_from(n).unwrap_or(usize::MAX))?;
+        let (next, declared) = (cursor.position(), cursor.intents_declared());
+        self.intents_declared |= declared;
+        let replayed = events.len() as u64;
+        for event in events {
+            consumer(event)?;
+        }
+        Ok((replayed, next))
+    }
+
+    fn append(&mut self, event: &Self::Event) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        self.ensure_writable(StorageOp::Append)?;
+        let declare = self.intents && !self.intents_declared;
+        let (text, line_starts) = event_lines(&self.converter, std::slice::from_ref(event), declare, false)?;
+
+        let mut writer = self.lock_append_writer()?;
+        let mut offset = None;
+        if let Some(writer) = writer.as_mut() {
+            if self.event_index.is_some() || self.intents {
+                offset = Some(append_offset(writer, &self.file_path)? + line_starts[0]);
+            }
+            writer.write_all(text.as_bytes()).map_err(storage_error(&self.file_path, StorageOp::Append))?;
+            #[cfg(feature = "metrics")]
+            crate::memimg::metrics::record_append(text.len());
+            let batch_full = matches!(self.flush_every, Some((batch, pending)) if pending + 1 >= batch);
+            if self.durability == Durability::EveryEvent {
+                writer.flush().map_err(storage_error(&self.file_path, StorageOp::Flush))?;
+            } else if batch_full {
+                writer
+                    .flush()
+                    .and_then(|_| writer.get_ref().sync_data())
+                    .map_err(storage_error(&self.file_path, StorageOp::Flush))?;
+            }
+        }
+        drop(writer);
+        self.intents_declared |= declare;
+        if self.intents && self.pending_offset.is_none() {
+            self.pending_offset = offset;
+        }
+        if let (Some(index), Some(offset)) = (self.event_index.as_mut(), offset) {
+            index.insert(offset, event);
+        }
+        if let Some((batch, pending)) = &mut self.flush_every {
+            *pending = (*pending + 1) % *batch;
+        }
+
+        Ok(())
+    }
+
+    /// Format every event before writing any, then write the group straight to the file in one
+    /// `write_all`, so an unformattable event writes nothing. The OS may still tear a single write
+    /// on a crash; replay then repairs only the torn last line. With write-ahead intents the
+    /// group ends with its commit record, so a torn group is dropped whole.
+    fn append_atomic(&mut self, events: &[Self::Event]) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        self.ensure_writable(StorageOp::Append)?;
+        let intents = self.intents && !events.is_empty();
+        let declare = intents && !self.intents_declared;
+        let (text, line_starts) = event_lines(&self.converter, events, declare, intents)?;
+
+        let mut writer = self.lock_append_writer()?;
+        let mut offset = None;
+        if let Some(writer) = writer.as_mut() {
+            // Earlier buffered appends go first so the group stays in order
+            writer.flush().map_err(storage_error(&self.file_path, StorageOp::Flush))?;
+            if self.event_index.is_some() {
+                offset = Some(append_offset(writer, &self.file_path)?);
+            }
+            writer.get_mut().write_all(text.as_bytes()).map_err(storage_error(&self.file_path, StorageOp::Append))?;
+            #[cfg(feature = "metrics")]
+            crate::memimg::metrics::record_append(text.len());
+        }
+        drop(writer);
+        self.intents_declared |= declare;
+        if intents {
+            self.pending_offset = None;
+        }
+        if let (Some(index), Some(offset)) = (self.event_index.as_mut(), offset) {
+            for (event, start) in events.iter().zip(line_starts) {
+                index.insert(offset + start, event);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Rewrite the file without the removed lines, atomically via a temporary file and rename
+    fn truncate_before(&mut self, first_kept: EventId) -> Result<u64, Box<dyn std::error::Error + Send + Sync>> {
+        self.rewrite_from(first_kept, None)
+    }
+
+    fn flush(&mut self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        if let Some(writer) = lock_writer(&self.writer).as_mut() {
+            writer.flush().map_err(storage_error(&self.file_path, StorageOp::Flush))?;
+            writer.get_ref().sync_all().map_err(storage_error(&self.file_path, StorageOp::Flush))?;
+            #[cfg(feature = "metrics")]
+            crate::memimg::metrics::record_flush();
+        }
+        Ok(())
+    }
+
+    /// Copy of the committed event lines as the converter wrote them, decompressed; markers,
+    /// intent records and events still pending are left out
+    fn copy_to<W: Write>(&mut self, writer: &mut W) -> Result<u64, Box<dyn std::error::Error + Send + Sync>>
+    where
+        E: Serialize,
+    {
+        self.write_through()?;
+        let mut reader = BufReader::new(self.open_reader()?);
+        let mut framing = IntentFraming::new(false);
+        let mut written = 0u64;
+        let mut line = String::new();
+        while reader.read_line(&mut line).map_err(storage_error(&self.file_path, StorageOp::Copy))? > 0 {
+            let terminated = line.ends_with('\n');
+            let text = line.trim_end_matches(['\n', '\r']);
+            let committed = match terminated.then(|| framing.record(text)).flatten() {
+                Some(committed) => committed,
+                // An unterminated last line is a torn append unless it parses, as in replay
+                None if is_event_line(text) && (terminated || self.converter.parse(text).is_ok()) => framing.hold(text.to_string()).into_iter().collect(),
+                None => Vec::new(),
+            };
+            for text in committed {
+                writeln!(writer, "{}", text).map_err(storage_error(&self.file_path, StorageOp::Copy))?;
+                written += text.len() as u64 + 1;
+            }
+            line.clear();
+        }
+        writer.flush().map_err(storage_error(&self.file_path, StorageOp::Copy))?;
+        Ok(written)
+    }
+
+    fn drain_warnings(&mut self) -> Vec<Warning> {
+        std::mem::take(&mut self.warnings)
+    }
+
+    fn writes_through(&self) -> bool {
+        self.durability == Durability::EveryEvent
+    }
+
+    /// Append a marker line holding `text`, which replay skips and `markers` reports
+    ///
+    /// Line breaks in `text` become spaces. Rewrites that go through replay, such as
+    /// `admin::migrate_format`, leave markers out.
+    fn append_marker(&mut self, text: &str) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+        self.ensure_writable(StorageOp::Append)?;
+        let line = marker_line(text);
+        let mut writer = self.lock_append_writer()?;
+        if let Some(writer) = writer.as_mut() {
+            writer.write_all(line.as_bytes()).map_err(storage_error(&self.file_path, StorageOp::Append))?;
+            writer.flush().map_err(storage_error(&self.file_path, StorageOp::Flush))?;
+        }
+        Ok(true)
+    }
+
+    /// Write a commit record after the pending events, or cut them back off the file, under
+    /// `with_write_ahead_intents`
+    fn settle_intent(&mut self, applied: bool) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+        if !self.intents {
+            return Ok(false);
+        }
+        let Some(start) = self.pending_offset.take() else {
+            return Ok(true);
+        };
+        let mut writer = self.lock_append_writer()?;
+        if let Some(writer) = writer.as_mut() {
+            if applied {
+                writeln!(writer, "{}", COMMIT_RECORD).map_err(storage_error(&self.file_path, StorageOp::Append))?;
+                if self.durability == Durability::EveryEvent {
+                    writer.flush().map_err(storage_error(&self.file_path, StorageOp::Flush))?;
+                }
+            } else {
+                // Appends go to the end of the file wherever it is, so the next one starts at `start`
+                writer.flush().map_err(storage_error(&self.file_path, StorageOp::Flush))?;
+                writer.get_ref().set_len(start).map_err(storage_error(&self.file_path, StorageOp::Truncate))?;
+            }
+        }
+        drop(writer);
+        if let (false, Some(index)) = (applied, self.event_index.as_mut()) {
+            index.truncate(start);
+        }
+        Ok(true)
+    }
+}
+
+impl<E, C> Drop for TextFileEventStorage<E, C>
+where
+    C: TextConverter<E>,
+{
+    fn drop(&mut self) {
+        self.auto_flush.take();
+        if let Some(mut writer) = lock_writer(&self.writer).take() {
+            let _ = writer.flush();
+        }
+    }
+}
+
+/// Options for opening a `TextFileEventStorage`, set one at a time and applied by `open`
+///
+/// `TextFileEventStorage::new` is `builder(path, converter).open()`. Options that only matter at
+/// open time, such as locking and read-only access, are set here; the rest mirror the storage's
+/// own `with_*` methods.
+pub struct TextFileEventStorageBuilder<E, C> {
+    path: PathBuf,
+    converter: C,
+    /// How long to wait for the `LogLock`, or `None` to open unlocked
+    lock_timeout: Option<Duration>,
+    read_only: bool,
+    replay_policy: ReplayPolicy,
+    durability: Durability,
+    auto_flush: Option<Duration>,
+    flush_every: Option<u64>,
+    event_index: Option<Box<EventIndexKeys<E>>>,
+    write_ahead_intents: bool,
+}
+
+impl<E, C> TextFileEventStorageBuilder<E, C>
+where
+    C: TextConverter<E>,
+{
+    pub fn new<P: AsRef<Path>>(path: P, converter: C) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            converter,
+            lock_timeout: Some(Duration::ZERO),
+            read_only: false,
+            replay_policy: ReplayPolicy::default(),
+            durability: Durability::default(),
+            auto_flush: None,
+            flush_every: None,
+            event_index: None,
+            write_ahead_intents: false,
+        }
+    }
+
+    /// Wait up to `lock_timeout` for another holder to release the log, as `new_with_lock_timeout`
+    pub fn lock_timeout(mut self, lock_timeout: Duration) -> Self {
+        self.lock_timeout = Some(lock_timeout);
+        self
+    }
+
+    /// Open without the `LogLock`, as `new_unlocked`
+    pub fn unlocked(mut self) -> Self {
+        self.lock_timeout = None;
+        self
+    }
+
+    /// Open for replay and reading only: the log must exist, it is not locked, so a writer may
+    /// hold it meanwhile, and appends, truncation and torn-tail repair are refused
+    pub fn read_only(mut self) -> Self {
+        self.read_only = true;
+        self
+    }
+
+    pub fn replay_policy(mut self, replay_policy: ReplayPolicy) -> Self {
+        self.replay_policy = replay_policy;
+        self
+    }
+
+    pub fn durability(mut self, durability: Durability) -> Self {
+        self.durability = durability;
+        self
+    }
+
+    /// See `TextFileEventStorage::with_auto_flush`
+    pub fn auto_flush(mut self, interval: Duration) -> Self {
+        self.auto_flush = Some(interval);
+        self
+    }
+
+    /// See `TextFileEventStorage::with_flush_every`
+    pub fn flush_every(mut self, events: u64) -> Self {
+        self.flush_every = Some(events);
+        self
+    }
+
+    /// See `TextFileEventStorage::with_event_index`
+    pub fn event_index(mut self, keys: impl Fn(&E) -> Vec<String> + Send + Sync + 'static) -> Self {
+        self.event_index = Some(Box::new(keys));
+        self
+    }
+
+    /// See `TextFileEventStorage::with_write_ahead_intents`
+    pub fn write_ahead_intents(mut self) -> Self {
+        self.write_ahead_intents = true;
+        self
+    }
+
+    /// Open the log with the options set
+    pub fn open(self) -> Result<TextFileEventStorage<E, C>, Box<dyn std::error::Error + Send + Sync>> {
+        let mut storage = if self.read_only {
+            let file_path = self.path.to_string_lossy().to_string();
+            File::open(&self.path).map_err(storage_error(&file_path, StorageOp::OpenForReplay))?;
+            let mut storage = TextFileEventStorage::new_unlocked(&self.path, self.converter)?;
+            storage.read_only = true;
+            storage
+        } else {
+            match self.lock_timeout {
+                Some(lock_timeout) => TextFileEventStorage::new_with_lock_timeout(&self.path, self.converter, lock_timeout)?,
+                None => TextFileEventStorage::new_unlocked(&self.path, self.converter)?,
+            }
+        };
+        storage.replay_policy = self.replay_policy;
+        storage.durability = self.durability;
+        storage.intents = self.write_ahead_intents;
+        storage.event_index = self.event_index.map(|keys| EventIndex { keys, offsets: HashMap::new() });
+        if let Some(events) = self.flush_every {
+            storage = storage.with_flush_every(events);
+        }
+        if let Some(interval) = self.auto_flush {
+            storage = storage.with_auto_flush(interval);
+        }
+        Ok(storage)
+    }
+}
+
+impl<E: JsonEvent> TextFileEventStorage<E, JsonEventConverter> {
+    /// Open a JSON-lines event file without naming a converter
+    pub fn json<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        Self::new(path, JsonEventConverter)
+    }
+}
@@ -0,0 +1,89 @@
+use chrono::{DateTime, Utc};
+
+/// Invariant checked against the shadow copy after each command, before it is committed
+pub trait SystemValidator<S> {
+    fn validate(&self, system: &S) -> Result<(), Box<dyn std::error::Error + Send + Sync>>;
+}
+
+/// What an `InvariantMonitor` does with a command whose resulting state breaks an invariant
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MonitorMode {
+    /// Record the violation and commit the command anyway
+    #[default]
+    Record,
+    /// Record the violation and reject the command, as a validator would
+    Enforce,
+}
+
+/// Validators run after every command, keeping a history of the violations they found
+///
+/// Unlike `MemImgProcessor::with_validator`, which only rejects, a monitor in `Record` mode lets a
+/// broken state through and remembers when it happened, for alerting on drift in production.
+pub struct InvariantMonitor<S> {
+    mode: MonitorMode,
+    violations: Vec<(DateTime<Utc>, String)>,
+    validators: Vec<Box<dyn SystemValidator<S> + Send>>,
+}
+
+impl<S> InvariantMonitor<S> {
+    pub fn new(mode: MonitorMode) -> Self {
+        Self { mode, violations: Vec::new(), validators: Vec::new() }
+    }
+
+    pub fn with_validator(mut self, validator: impl SystemValidator<S> + Send + 'static) -> Self {
+        self.validators.push(Box::new(validator));
+        self
+    }
+
+    pub fn mode(&self) -> MonitorMode {
+        self.mode
+    }
+
+    /// When each violation was found, and the validator's message, oldest first
+    pub fn violations(&self) -> &[(DateTime<Utc>, String)] {
+        &self.violations
+    }
+
+    pub fn violation_count(&self) -> usize {
+        self.violations.len()
+    }
+
+    pub fn clear_violations(&mut self) {
+        self.violations.clear();
+    }
+
+    /// Run every validator against `system`, recording each failure; under `Enforce`, the first
+    /// failure is returned so the command is rejected
+    pub(crate) fn check(&mut self, system: &S) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let mut first = None;
+        for validator in &self.validators {
+            if let Err(e) = validator.validate(system) {
+                self.violations.push((Utc::now(), e.to_string()));
+                first.get_or_insert(e);
+            }
+        }
+        match first {
+            Some(e) if self.mode == MonitorMode::Enforce => Err(e),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Entity-level comparison used to explain a replay divergence
+pub trait StateDiff {
+    /// Number of top-level entities in this state (accounts, for `Bank`)
+    fn entity_count(&self) -> usize;
+
+    /// Keys of entities that are missing from, or differ in, `other`, sorted
+    fn diff_keys(&self, other: &Self) -> Vec<String>;
+}
+
+/// Outcome of replaying the event log into a fresh system and comparing it with the live one
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplayValidationResult {
+    pub diverged: bool,
+    pub live_accounts: usize,
+    pub replayed_accounts: usize,
+    /// Keys of the entities that differ between the live and replayed states
+    pub divergent_keys: Vec<String>,
+}
@@ -0,0 +1,55 @@
+use crate::memimg::error::{FailureOutcome, MemImgError};
+use crate::memimg::event_id::EventId;
+use crate::memimg::processor::Query;
+use std::sync::Arc;
+
+/// Frozen, read-only copy of a processor's state for queries that must see one point in time
+///
+/// Cloning a view is cheap: clones share the same state.
+#[derive(Debug)]
+pub struct SystemView<S> {
+    system: Arc<S>,
+    version: EventId,
+}
+
+impl<S> Clone for SystemView<S> {
+    fn clone(&self) -> Self {
+        Self {
+            system: Arc::clone(&self.system),
+            version: self.version,
+        }
+    }
+}
+
+impl<S> SystemView<S> {
+    pub(crate) fn new(system: S, version: EventId) -> Self {
+        Self {
+            system: Arc::new(system),
+            version,
+        }
+    }
+
+    /// Execute a query against the frozen state
+    pub fn execute_query<Q>(&self, query: &Q) -> Result<Q::Result, MemImgError>
+    where
+        Q: Query<System = S>,
+    {
+        query.extract_from(&self.system).map_err(|e| {
+            MemImgError::CommandFailure(FailureOutcome::new(
+                e,
+                "executing query",
+                std::any::type_name::<Q>(),
+            ))
+        })
+    }
+
+    /// The frozen state
+    pub fn system(&self) -> &S {
+        &self.system
+    }
+
+    /// Event version the view was taken at
+    pub fn version(&self) -> EventId {
+        self.version
+    }
+}
@@ -0,0 +1,208 @@
+use crate::memimg::json_event::JsonEvent;
+use crate::memimg::processor::Query;
+use crate::memimg::Command;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use thiserror::Error;
+
+pub type Quantity = u32;
+
+/// Domain errors raised by warehouse commands and queries
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum WarehouseError {
+    #[error("Unknown SKU: {0}")]
+    UnknownSku(String),
+
+    #[error("Duplicate SKU: {0}")]
+    DuplicateSku(String),
+
+    #[error("Quantity must be positive for {0}")]
+    ZeroQuantity(String),
+
+    #[error("Insufficient stock of {sku}: {available} available < {requested}")]
+    InsufficientStock { sku: String, available: Quantity, requested: Quantity },
+
+    #[error("Only {reserved} of {sku} reserved, cannot release or ship {requested}")]
+    NotReserved { sku: String, reserved: Quantity, requested: Quantity },
+}
+
+/// Warehouse state: items keyed by SKU, so serialization is sorted and stable
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Warehouse {
+    pub items: BTreeMap<String, Item>,
+}
+
+impl Warehouse {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn item_mut(&mut self, sku: &str) -> Result<&mut Item, WarehouseError> {
+        self.items.get_mut(sku).ok_or_else(|| WarehouseError::UnknownSku(sku.to_string()))
+    }
+}
+
+/// Stock of one SKU; `reserved` never exceeds `on_hand`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Item {
+    pub sku: String,
+    pub name: String,
+    pub on_hand: Quantity,
+    pub reserved: Quantity,
+}
+
+impl Item {
+    pub fn new(sku: String, name: String) -> Self {
+        Self {
+            sku,
+            name,
+            on_hand: 0,
+            reserved: 0,
+        }
+    }
+
+    /// Units on hand that are not reserved
+    pub fn available(&self) -> Quantity {
+        self.on_hand - self.reserved
+    }
+}
+
+// Commands
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Command)]
+#[command(system = "Warehouse")]
+pub enum WarehouseCommand {
+    #[command(handler = "apply_add_item")]
+    AddItem { sku: String, name: String },
+    #[command(handler = "apply_receive_stock")]
+    ReceiveStock { sku: String, quantity: Quantity },
+    #[command(handler = "apply_reserve")]
+    Reserve { sku: String, quantity: Quantity },
+    #[command(handler = "apply_release_reservation")]
+    ReleaseReservation { sku: String, quantity: Quantity },
+    /// Ship reserved units of several SKUs as one shipment; any failing line fails the shipment
+    #[command(handler = "apply_ship")]
+    Ship { lines: Vec<(String, Quantity)> },
+}
+
+impl JsonEvent for WarehouseCommand {}
+
+// Command handlers
+
+fn positive(sku: &str, quantity: Quantity) -> Result<Quantity, WarehouseError> {
+    if quantity == 0 {
+        return Err(WarehouseError::ZeroQuantity(sku.to_string()));
+    }
+    Ok(quantity)
+}
+
+impl Warehouse {
+    fn apply_add_item(&mut self, sku: &str, name: &str) -> Result<(), WarehouseError> {
+        if self.items.contains_key(sku) {
+            return Err(WarehouseError::DuplicateSku(sku.to_string()));
+        }
+        self.items.insert(sku.to_string(), Item::new(sku.to_string(), name.to_string()));
+        Ok(())
+    }
+
+    fn apply_receive_stock(&mut self, sku: &str, quantity: &Quantity) -> Result<(), WarehouseError> {
+        let quantity = positive(sku, *quantity)?;
+        self.item_mut(sku)?.on_hand += quantity;
+        Ok(())
+    }
+
+    fn apply_reserve(&mut self, sku: &str, quantity: &Quantity) -> Result<(), WarehouseError> {
+        let quantity = positive(sku, *quantity)?;
+        let item = self.item_mut(sku)?;
+        if item.available() < quantity {
+            return Err(WarehouseError::InsufficientStock {
+                sku: sku.to_string(),
+                available: item.available(),
+                requested: quantity,
+            });
+        }
+
+        item.reserved += quantity;
+        Ok(())
+    }
+
+    fn apply_release_reservation(&mut self, sku: &str, quantity: &Quantity) -> Result<(), WarehouseError> {
+        let quantity = positive(sku, *quantity)?;
+        let item = self.item_mut(sku)?;
+        if item.reserved < quantity {
+            return Err(WarehouseError::NotReserved {
+                sku: sku.to_string(),
+                reserved: item.reserved,
+                requested: quantity,
+            });
+        }
+
+        item.reserved -= quantity;
+        Ok(())
+    }
+
+    fn apply_ship(&mut self, lines: &[(String, Quantity)]) -> Result<(), WarehouseError> {
+        // Lines are shipped one by one: a failing line leaves earlier ones applied, and the
+        // processor's shadow copy discards the whole partial shipment
+        for (sku, quantity) in lines {
+            let quantity = positive(sku, *quantity)?;
+            let item = self.item_mut(sku)?;
+            if item.reserved < quantity {
+                return Err(WarehouseError::NotReserved {
+                    sku: sku.clone(),
+                    reserved: item.reserved,
+                    requested: quantity,
+                });
+            }
+
+            item.reserved -= quantity;
+            item.on_hand -= quantity;
+        }
+        Ok(())
+    }
+}
+
+// Queries
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StockLevel {
+    pub on_hand: Quantity,
+    pub reserved: Quantity,
+    pub available: Quantity,
+}
+
+#[derive(Debug)]
+pub struct GetStockLevel {
+    pub sku: String,
+}
+
+impl Query for GetStockLevel {
+    type System = Warehouse;
+    type Result = StockLevel;
+
+    fn extract_from(&self, warehouse: &Self::System) -> Result<Self::Result, Box<dyn std::error::Error + Send + Sync>> {
+        warehouse.items
+            .get(&self.sku)
+            .map(|item| StockLevel {
+                on_hand: item.on_hand,
+                reserved: item.reserved,
+                available: item.available(),
+            })
+            .ok_or_else(|| WarehouseError::UnknownSku(self.sku.clone()).into())
+    }
+}
+
+/// Items whose available stock is below `threshold`, sorted by SKU
+#[derive(Debug)]
+pub struct GetLowStockItems {
+    pub threshold: Quantity,
+}
+
+impl Query for GetLowStockItems {
+    type System = Warehouse;
+    type Result = Vec<Item>;
+
+    fn extract_from(&self, warehouse: &Self::System) -> Result<Self::Result, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(warehouse.items.values().filter(|item| item.available() < self.threshold).cloned().collect())
+    }
+}
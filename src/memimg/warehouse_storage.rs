@@ -0,0 +1,16 @@
+use crate::memimg::storage::TextConverter;
+use crate::memimg::warehouse::WarehouseCommand;
+
+/// JSON converter for WarehouseCommand
+pub struct WarehouseJsonConverter;
+
+impl TextConverter<WarehouseCommand> for WarehouseJsonConverter {
+    fn parse(&self, text: &str) -> Result<WarehouseCommand, Box<dyn std::error::Error + Send + Sync>> {
+        serde_json::from_str(text).map_err(|e| -> Box<dyn std::error::Error + Send + Sync> { Box::new(e) })
+    }
+
+    fn format(&self, command: &WarehouseCommand) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+        // serde_json::to_string never emits newlines, so each command stays on one line
+        serde_json::to_string(command).map_err(|e| -> Box<dyn std::error::Error + Send + Sync> { Box::new(e) })
+    }
+}
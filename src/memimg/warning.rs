@@ -0,0 +1,56 @@
+use std::fmt;
+
+/// Maximum number of warnings a processor buffers before counting the rest as dropped
+pub const MAX_BUFFERED_WARNINGS: usize = 1024;
+
+/// Kind of non-fatal anomaly found while replaying events
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WarningKind {
+    /// An unparseable line was skipped under a lenient replay policy
+    SkippedLine,
+    /// A truncated, unterminated last line was cut off the log
+    RepairedTail,
+    /// A stored event failed to apply and was skipped (see `CommitStrategy::AppendThenApply`)
+    RejectedEvent,
+    /// The primary storage failed at startup and the processor uses its fallback instead
+    StorageFallback,
+    /// A live command was stamped earlier than the latest logged event (see `ClockRegressionPolicy`)
+    ClockRegression,
+    /// Events a crash left appended but never committed were cut off the end of a log kept with
+    /// write-ahead intents
+    DiscardedIntent,
+    /// A snapshot covered more events than its log holds (see `SnapshotMismatchPolicy`)
+    SnapshotMismatch,
+}
+
+/// Non-fatal replay anomaly an operator should know about
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Warning {
+    pub kind: WarningKind,
+    /// 1-based index of the offending record (line number for text storages)
+    pub index: u64,
+    /// Byte offset of the offending record, 0 when the reporter does not know it
+    pub offset: u64,
+    pub message: String,
+}
+
+impl Warning {
+    pub fn new(kind: WarningKind, index: u64, offset: u64, message: &str) -> Self {
+        Self {
+            kind,
+            index,
+            offset,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:?} at record {} (offset {}): {}",
+            self.kind, self.index, self.offset, self.message
+        )
+    }
+}
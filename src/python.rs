@@ -0,0 +1,258 @@
+use crate::memimg::bank::{self, parse_amount, Amount, Bank, BankCommand, GetBalance};
+use crate::memimg::bank_repl::touches_account;
+use crate::memimg::bank_storage::BankJsonConverter;
+use crate::memimg::{EventStorage, MemImgError, MemImgProcessor, MemoryEventStorage, SnapshotFormat, TextFileEventStorage};
+use pyo3::create_exception;
+use pyo3::exceptions::PyException;
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList};
+use serde_json::Value;
+
+create_exception!(rmemimg, BankError, PyException, "Command or query rejected by the bank; args are (code, message)");
+create_exception!(rmemimg, ProcessorError, PyException, "Failure of the processor itself or malformed input; args are (code, message)");
+
+fn bank_error(error: bank::BankError) -> PyErr {
+    BankError::new_err((error.code(), error.to_string()))
+}
+
+fn processor_error(error: MemImgError) -> PyErr {
+    let domain = error.outcome().and_then(|outcome| outcome.source.downcast_ref::<bank::BankError>());
+    match domain {
+        Some(domain) => bank_error(domain.clone()),
+        None => ProcessorError::new_err((error.code(), error.to_string())),
+    }
+}
+
+fn system_failure(error: Box<dyn std::error::Error + Send + Sync>) -> PyErr {
+    ProcessorError::new_err(("SYSTEM_FAILURE", error.to_string()))
+}
+
+fn decimal<'py>(py: Python<'py>, amount: &str) -> PyResult<Bound<'py, PyAny>> {
+    py.import_bound("decimal")?.getattr("Decimal")?.call1((amount,))
+}
+
+/// JSON fields that hold amounts, handed to Python as `decimal.Decimal` rather than strings
+const AMOUNT_FIELDS: [&str; 3] = ["amount", "opening_balance", "balance"];
+
+/// Convert serialized bank data to Python objects, with amounts as `decimal.Decimal`
+fn to_python(py: Python<'_>, value: &Value) -> PyResult<PyObject> {
+    Ok(match value {
+        Value::Null => py.None(),
+        Value::Bool(flag) => flag.into_py(py),
+        Value::Number(number) => match number.as_i64() {
+            Some(integer) => integer.into_py(py),
+            None => decimal(py, &number.to_string())?.into_py(py),
+        },
+        Value::String(text) => text.into_py(py),
+        Value::Array(items) => PyList::new_bound(py, items.iter().map(|item| to_python(py, item)).collect::<PyResult<Vec<_>>>()?).into_py(py),
+        Value::Object(fields) => {
+            let dict = PyDict::new_bound(py);
+            for (key, field) in fields {
+                match field {
+                    Value::String(amount) if AMOUNT_FIELDS.contains(&key.as_str()) => dict.set_item(key, decimal(py, amount)?)?,
+                    field => dict.set_item(key, to_python(py, field)?)?,
+                }
+            }
+            dict.into_py(py)
+        }
+    })
+}
+
+/// Accept amounts as strings (`"$1,000.50"`) or as any number whose `str()` is a plain decimal
+fn amount(value: &Bound<'_, PyAny>) -> PyResult<Amount> {
+    let text = match value.extract::<String>() {
+        Ok(text) => text,
+        Err(_) => value.str()?.to_string(),
+    };
+    parse_amount(&text).map_err(bank_error)
+}
+
+/// Bank state handed to a processor, or copied out of one for inspection
+#[pyclass(name = "Bank")]
+#[derive(Clone, Default)]
+pub struct PyBank {
+    bank: Bank,
+}
+
+#[pymethods]
+impl PyBank {
+    #[new]
+    fn new() -> Self {
+        Self::default()
+    }
+
+    /// Ids of all open accounts, sorted
+    fn account_ids(&self) -> Vec<String> {
+        let mut ids: Vec<String> = self.bank.accounts.keys().map(ToString::to_string).collect();
+        ids.sort();
+        ids
+    }
+
+    fn __len__(&self) -> usize {
+        self.bank.accounts.len()
+    }
+}
+
+/// Memory image processor over a bank, with events kept in memory
+#[pyclass(name = "MemImgProcessor")]
+pub struct PyMemImgProcessor {
+    processor: MemImgProcessor<Bank, BankCommand, MemoryEventStorage<BankCommand>>,
+}
+
+impl PyMemImgProcessor {
+    fn execute(&mut self, command: BankCommand) -> PyResult<u64> {
+        self.processor.execute_command(command).map_err(processor_error)?;
+        Ok(self.processor.event_version().as_u64())
+    }
+}
+
+#[pymethods]
+impl PyMemImgProcessor {
+    #[new]
+    #[pyo3(signature = (bank = None))]
+    fn new(bank: Option<PyBank>) -> PyResult<Self> {
+        let bank = bank.unwrap_or_default().bank;
+        let processor = MemImgProcessor::new_simple(bank, Box::new(MemoryEventStorage::new())).map_err(processor_error)?;
+        Ok(Self { processor })
+    }
+
+    /// Each command method returns the event version after the command
+    fn create_account(&mut self, id: String, name: String) -> PyResult<u64> {
+        self.execute(BankCommand::CreateAccount { id: id.into(), name, opening_balance: None })
+    }
+
+    fn deposit(&mut self, account_id: String, amount: &Bound<'_, PyAny>) -> PyResult<u64> {
+        let amount = self::amount(amount)?;
+        self.execute(BankCommand::Deposit { account_id: account_id.into(), amount })
+    }
+
+    fn withdraw(&mut self, account_id: String, amount: &Bound<'_, PyAny>) -> PyResult<u64> {
+        let amount = self::amount(amount)?;
+        self.execute(BankCommand::Withdrawal { account_id: account_id.into(), amount })
+    }
+
+    fn transfer(&mut self, from_account_id: String, to_account_id: String, amount: &Bound<'_, PyAny>) -> PyResult<u64> {
+        let amount = self::amount(amount)?;
+        self.execute(BankCommand::Transfer {
+            from_account_id: from_account_id.into(),
+            to_account_id: to_account_id.into(),
+            amount,
+        })
+    }
+
+    /// Balance as a `decimal.Decimal`
+    fn get_balance<'py>(&self, py: Python<'py>, account_id: String) -> PyResult<Bound<'py, PyAny>> {
+        let balance = self.processor.execute_query(&GetBalance { account_id: account_id.into() }).map_err(processor_error)?;
+        py.import_bound("decimal")?.getattr("Decimal")?.call1((balance.to_string(),))
+    }
+
+    /// Copy of the current bank state
+    fn bank(&self) -> PyBank {
+        PyBank { bank: self.processor.system().clone() }
+    }
+
+    #[getter]
+    fn event_version(&self) -> u64 {
+        self.processor.event_version().as_u64()
+    }
+}
+
+/// Memory image processor over a bank whose events persist in a JSON-lines file
+///
+/// Opening an existing log replays it, so the processor can also serve to inspect a bank log.
+#[pyclass(name = "BankProcessor")]
+pub struct PyBankProcessor {
+    processor: MemImgProcessor<Bank, BankCommand, TextFileEventStorage<BankCommand, BankJsonConverter>>,
+}
+
+#[pymethods]
+impl PyBankProcessor {
+    #[new]
+    fn new(path: &str) -> PyResult<Self> {
+        let storage = TextFileEventStorage::new(path, BankJsonConverter::new()).map_err(system_failure)?;
+        let processor = MemImgProcessor::new_simple(Bank::new(), Box::new(storage)).map_err(processor_error)?;
+        Ok(Self { processor })
+    }
+
+    /// Execute a command given in its JSON form, e.g. `{"Deposit": {"account_id": "alice", "amount": Decimal("10")}}`,
+    /// returning the event version after it
+    fn execute(&mut self, py: Python<'_>, command: &Bound<'_, PyDict>) -> PyResult<u64> {
+        // Decimals become JSON strings, which is how amounts are stored anyway
+        let options = PyDict::new_bound(py);
+        options.set_item("default", py.import_bound("builtins")?.getattr("str")?)?;
+        let json = py.import_bound("json")?.getattr("dumps")?.call((command,), Some(&options))?;
+        let command: BankCommand = serde_json::from_str(&json.extract::<String>()?)
+            .map_err(|e| ProcessorError::new_err(("MALFORMED_INPUT", e.to_string())))?;
+        let receipt = self.processor.execute_command(command).map_err(processor_error)?;
+        Ok(receipt.seq)
+    }
+
+    /// Balance as a `decimal.Decimal`
+    fn balance<'py>(&self, py: Python<'py>, account_id: String) -> PyResult<Bound<'py, PyAny>> {
+        let balance = self.processor.execute_query(&GetBalance { account_id: account_id.into() }).map_err(processor_error)?;
+        decimal(py, &balance.to_string())
+    }
+
+    /// Open accounts sorted by id, as dicts of `id`, `name`, `owners` and `balance`
+    fn accounts(&self, py: Python<'_>) -> PyResult<Vec<PyObject>> {
+        let mut accounts: Vec<&bank::Account> = self.processor.system().accounts.values().collect();
+        accounts.sort_by(|a, b| a.id.cmp(&b.id));
+        accounts
+            .into_iter()
+            .map(|account| {
+                let json = serde_json::json!({
+                    "id": account.id,
+                    "name": account.name,
+                    "owners": account.owners,
+                    "balance": account.balance(),
+                });
+                to_python(py, &json)
+            })
+            .collect()
+    }
+
+    /// `(event version, command)` for every logged command that reads or writes `account_id`
+    fn history(&mut self, py: Python<'_>, account_id: &str) -> PyResult<Vec<(u64, PyObject)>> {
+        let mut commands = Vec::new();
+        self.processor
+            .event_storage
+            .replay(&mut |command: BankCommand| {
+                commands.push(command);
+                Ok(())
+            })
+            .map_err(system_failure)?;
+
+        let mut history = Vec::new();
+        for (index, command) in commands.iter().enumerate() {
+            if touches_account(command, account_id) {
+                let json = serde_json::to_value(command).map_err(|e| system_failure(Box::new(e)))?;
+                history.push((index as u64 + 1, to_python(py, &json)?));
+            }
+        }
+        Ok(history)
+    }
+
+    /// Write the current state as a version 1 snapshot, restorable with `MemImgProcessor::from_snapshot`
+    fn snapshot(&self, path: &str) -> PyResult<()> {
+        let mut file = std::fs::File::create(path).map_err(|e| system_failure(Box::new(e)))?;
+        SnapshotFormat::new(1)
+            .write(&mut file, self.processor.system(), self.processor.event_version().as_u64())
+            .map_err(system_failure)
+    }
+
+    #[getter]
+    fn event_version(&self) -> u64 {
+        self.processor.event_version().as_u64()
+    }
+}
+
+#[pymodule]
+#[pyo3(name = "rmemimg")]
+fn python_module(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyBank>()?;
+    m.add_class::<PyMemImgProcessor>()?;
+    m.add_class::<PyBankProcessor>()?;
+    m.add("BankError", m.py().get_type_bound::<BankError>())?;
+    m.add("ProcessorError", m.py().get_type_bound::<ProcessorError>())?;
+    Ok(())
+}
@@ -0,0 +1,254 @@
+#![cfg(all(feature = "admin", feature = "bank-example"))]
+
+use rmemimg::memimg::admin::{compact, fingerprint, migrate_format, record_fingerprint, snapshot, verify, verify_against_recorded, LogStore, RecordedFingerprint};
+use rmemimg::memimg::bank::{Bank, BankCommand};
+use rmemimg::memimg::bank_anonymizer::{anonymize_log, replay_shape, AmountPolicy, BankScrubber};
+use rmemimg::memimg::bank_storage::BankJsonConverter;
+use rmemimg::memimg::{MemImgProcessor, SnapshotFormat, StorageError, TextFileEventStorage};
+use rust_decimal::Decimal;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+/// Fresh directory under the temp dir for one test's store
+fn store_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("memimg_admin_{}", name));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Log three events to `log`: Alice opens with $100 and moves $40 to Bob
+fn populate(log: &Path) -> Bank {
+    let storage = Box::new(TextFileEventStorage::new(log, BankJsonConverter::new()).unwrap());
+    let mut processor = MemImgProcessor::new_simple(Bank::new(), storage).unwrap();
+    for command in [
+        BankCommand::CreateAccount { id: "alice".into(), name: "Alice".to_string(), opening_balance: Some(Decimal::from(100)) },
+        BankCommand::CreateAccount { id: "bob".into(), name: "Bob".to_string(), opening_balance: None },
+        BankCommand::Transfer { from_account_id: "alice".into(), to_account_id: "bob".into(), amount: Decimal::from(40) },
+    ] {
+        processor.execute_command(command).unwrap();
+    }
+    processor.system().clone()
+}
+
+#[test]
+fn populate_snapshot_compact_verify() {
+    let dir = store_dir("lifecycle");
+    let store = LogStore::new(dir.join("bank.json"));
+    let format = SnapshotFormat::new(1);
+    let bank = populate(&store.log);
+
+    let exported = dir.join("export.snapshot");
+    let snapshotted = snapshot(&store, Bank::new(), BankJsonConverter::new(), &format, &exported).unwrap();
+    assert_eq!(snapshotted.event_count, 3);
+    assert_eq!(snapshotted.fingerprint, fingerprint(&bank).unwrap());
+    assert!(!store.snapshot.exists());
+
+    let compacted = compact(&store, Bank::new(), BankJsonConverter::new(), &format).unwrap();
+    assert_eq!((compacted.event_count, compacted.events_removed), (3, 3));
+    assert_eq!(compacted.backups, vec![LogStore::backup_path(&store.log)]);
+    assert_eq!(std::fs::read_to_string(&store.log).unwrap(), "");
+    assert_eq!(std::fs::read_to_string(&compacted.backups[0]).unwrap().lines().count(), 3);
+
+    let verified = verify(&store, Bank::new(), BankJsonConverter::new(), &format, Some(&exported)).unwrap();
+    assert!(verified.is_ok(), "{:?}", verified.problems);
+    assert_eq!((verified.snapshot_event_count, verified.log_events), (Some(3), 0));
+    assert_eq!(verified.fingerprint, Some(snapshotted.fingerprint.clone()));
+
+    // A processor resumes from the compacted store where the log left off
+    let snapshot = format.read::<Bank, _>(std::fs::File::open(&store.snapshot).unwrap()).unwrap();
+    let storage = Box::new(TextFileEventStorage::new(&store.log, BankJsonConverter::new()).unwrap());
+    let mut processor = MemImgProcessor::from_snapshot(snapshot, storage).unwrap();
+    assert_eq!(processor.system(), &bank);
+    processor.execute_command(BankCommand::Deposit { account_id: "bob".into(), amount: Decimal::from(5) }).unwrap();
+    drop(processor);
+
+    // The exported snapshot now lags the store by one event
+    let verified = verify(&store, Bank::new(), BankJsonConverter::new(), &format, Some(&exported)).unwrap();
+    assert_eq!(verified.log_events, 1);
+    assert_eq!(verified.problems.len(), 1);
+    assert!(verified.problems[0].contains("covers 3 events, the store 4"), "{}", verified.problems[0]);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn verify_reports_unparseable_lines_and_mismatched_snapshots() {
+    let dir = store_dir("verify");
+    let store = LogStore::new(dir.join("bank.json"));
+    let format = SnapshotFormat::new(1);
+    populate(&store.log);
+
+    let stale = dir.join("stale.snapshot");
+    format.write(&mut std::fs::File::create(&stale).unwrap(), &Bank::new(), 3).unwrap();
+    let mut log = std::fs::read_to_string(&store.log).unwrap();
+    log.push_str("not an event\n");
+    std::fs::write(&store.log, log).unwrap();
+
+    let report = verify(&store, Bank::new(), BankJsonConverter::new(), &format, Some(&stale)).unwrap();
+    assert!(!report.is_ok());
+    assert_eq!(report.log_events, 3);
+    assert_eq!(report.problems.len(), 2, "{:?}", report.problems);
+    assert!(report.problems[0].contains("skipped unparseable line"));
+    assert!(report.problems[1].contains("does not match the store's state"));
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn operations_refuse_a_log_held_by_a_running_processor() {
+    let dir = store_dir("locked");
+    let store = LogStore::new(dir.join("bank.json"));
+    let format = SnapshotFormat::new(1);
+    populate(&store.log);
+
+    let storage = Box::new(TextFileEventStorage::new(&store.log, BankJsonConverter::new()).unwrap());
+    let processor = MemImgProcessor::new_simple(Bank::new(), storage).unwrap();
+
+    let error = compact(&store, Bank::new(), BankJsonConverter::new(), &format).unwrap_err();
+    let storage_error = error.downcast_ref::<StorageError>().unwrap();
+    assert_eq!(storage_error.source.kind(), ErrorKind::WouldBlock);
+    assert!(verify(&store, Bank::new(), BankJsonConverter::new(), &format, None).is_err());
+    assert!(!LogStore::backup_path(&store.log).exists());
+    assert_eq!(std::fs::read_to_string(&store.log).unwrap().lines().count(), 3);
+
+    drop(processor);
+    assert!(compact(&store, Bank::new(), BankJsonConverter::new(), &format).is_ok());
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn migrate_format_rewrites_every_event_and_keeps_the_old_log() {
+    let dir = store_dir("migrate");
+    let store = LogStore::new(dir.join("bank.json"));
+    let bank = populate(&store.log);
+    let original = std::fs::read_to_string(&store.log).unwrap();
+
+    let report = migrate_format(&store, BankJsonConverter::new(), BankJsonConverter::internally_tagged("type")).unwrap();
+    assert_eq!(report.events, 3);
+    assert_eq!(std::fs::read_to_string(&report.backup).unwrap(), original);
+    let migrated = std::fs::read_to_string(&store.log).unwrap();
+    assert!(migrated.lines().all(|line| line.contains(r#""type":"#)), "{}", migrated);
+
+    let storage = Box::new(TextFileEventStorage::new(&store.log, BankJsonConverter::internally_tagged("type")).unwrap());
+    assert_eq!(MemImgProcessor::preview_replay(Bank::new(), storage).unwrap(), bank);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn verify_against_recorded_matches_until_the_log_is_tampered_with() {
+    let dir = store_dir("recorded");
+    let store = LogStore::new(dir.join("bank.json"));
+    let format = SnapshotFormat::new(1);
+    let bank = populate(&store.log);
+
+    let recorded = record_fingerprint(&store, Bank::new(), BankJsonConverter::new(), &format).unwrap();
+    assert_eq!(recorded, RecordedFingerprint { event_count: 3, fingerprint: fingerprint(&bank).unwrap() });
+    let check = verify_against_recorded(&store, Bank::new(), BankJsonConverter::new(), &format).unwrap();
+    assert!(check.matches());
+
+    // Events appended after the recording are counted but not checked
+    let storage = Box::new(TextFileEventStorage::new(&store.log, BankJsonConverter::new()).unwrap());
+    let mut processor = MemImgProcessor::new_simple(Bank::new(), storage).unwrap();
+    processor.execute_command(BankCommand::Deposit { account_id: "bob".into(), amount: Decimal::from(5) }).unwrap();
+    let check = verify_against_recorded(&store, Bank::new(), BankJsonConverter::new(), &format).unwrap();
+    drop(processor);
+    assert!(check.matches());
+    assert_eq!(check.store_event_count, 4);
+
+    // Rewriting the transfer's amount leaves a valid log with a different state
+    let log = std::fs::read_to_string(&store.log).unwrap();
+    std::fs::write(&store.log, log.replacen(r#""amount":"40""#, r#""amount":"4""#, 1)).unwrap();
+    let check = verify_against_recorded(&store, Bank::new(), BankJsonConverter::new(), &format).unwrap();
+    assert!(!check.matches());
+    assert_eq!(check.recorded, Some(recorded.clone()));
+    assert_eq!(check.replayed.event_count, 3);
+    assert_ne!(check.replayed.fingerprint, recorded.fingerprint);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn verify_against_recorded_reports_a_missing_recording() {
+    let dir = store_dir("unrecorded");
+    let store = LogStore::new(dir.join("bank.json"));
+    let format = SnapshotFormat::new(1);
+    let bank = populate(&store.log);
+
+    let check = verify_against_recorded(&store, Bank::new(), BankJsonConverter::new(), &format).unwrap();
+    assert!(!check.matches());
+    assert_eq!(check.recorded, None);
+    assert_eq!(check.replayed, RecordedFingerprint { event_count: 3, fingerprint: fingerprint(&bank).unwrap() });
+
+    // Compaction records the fingerprint as well
+    compact(&store, Bank::new(), BankJsonConverter::new(), &format).unwrap();
+    assert!(verify_against_recorded(&store, Bank::new(), BankJsonConverter::new(), &format).unwrap().matches());
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+/// Log lines with customer data, ending in a withdrawal that overdraws Bob
+const CUSTOMER_LOG: &str = r#"{"CreateAccount":{"id":"alice","name":"Alice Smith","opening_balance":"100"}}
+{"CreateAccount":{"id":"bob","name":"Bob Jones"}}
+{"AddOwner":{"account_id":"bob","owner":"Alice Smith"}}
+{"Transfer":{"from_account_id":"alice","to_account_id":"bob","amount":"30"}}
+{"RecordExternalPayment":{"account_id":"bob","amount":"5","gateway":"stripe","gateway_transaction_id":"ch_123","payment_method":"CreditCard"}}
+{"Withdrawal":{"account_id":"bob","amount":"50"}}
+"#;
+
+#[test]
+fn anonymized_pseudonyms_depend_only_on_the_seed() {
+    let dir = store_dir("anonymize_seed");
+    let log = dir.join("bank.json");
+    std::fs::write(&log, CUSTOMER_LOG).unwrap();
+
+    let anonymize = |seed: &str, out: &str| {
+        anonymize_log(&log, &dir.join(out), BankJsonConverter::new(), BankJsonConverter::new(), &mut BankScrubber::new(seed)).unwrap();
+        std::fs::read_to_string(dir.join(out)).unwrap()
+    };
+    let first = anonymize("s3cret", "first.json");
+    assert_eq!(anonymize("s3cret", "second.json"), first);
+    assert_ne!(anonymize("other", "third.json"), first);
+    for identifying in ["alice", "bob", "Alice", "Smith", "Jones", "ch_123"] {
+        assert!(!first.contains(identifying), "{} leaked into {}", identifying, first);
+    }
+
+    let scrubber = BankScrubber::new("s3cret");
+    assert_eq!(scrubber.account_id(&"alice".into()), scrubber.account_id(&"alice".into()));
+    assert_ne!(scrubber.account_id(&"alice".into()), scrubber.account_id(&"bob".into()));
+    assert!(scrubber.account_id(&"alice".into()).validate().is_ok());
+    assert!(scrubber.account_id(&"not valid".into()).validate().is_err());
+}
+
+#[test]
+fn anonymized_log_replays_with_the_original_failures() {
+    let dir = store_dir("anonymize_replay");
+    let log = dir.join("bank.json");
+    std::fs::write(&log, CUSTOMER_LOG).unwrap();
+    let original = replay_shape(&log, BankJsonConverter::new()).unwrap();
+    assert_eq!(original.failures, vec![(6, "INSUFFICIENT_FUNDS".to_string())]);
+
+    let scaled = dir.join("scaled.json");
+    let mut scrubber = BankScrubber::new("seed").with_amounts(AmountPolicy::Scale(Decimal::new(25, 1)));
+    let report = anonymize_log(&log, &scaled, BankJsonConverter::new(), BankJsonConverter::new(), &mut scrubber).unwrap();
+    assert_eq!(report.events, 6);
+    assert_eq!(replay_shape(&scaled, BankJsonConverter::new()).unwrap(), original);
+
+    // The scrubbed events alone, without the overdraft, replay into a processor without errors
+    let clean = dir.join("clean.json");
+    let scrubbed = std::fs::read_to_string(&scaled).unwrap();
+    let lines: Vec<&str> = scrubbed.lines().take(5).collect();
+    std::fs::write(&clean, lines.join("\n") + "\n").unwrap();
+    let storage = Box::new(TextFileEventStorage::new(&clean, BankJsonConverter::new()).unwrap());
+    let processor = MemImgProcessor::new_simple(Bank::new(), storage).unwrap();
+    assert_eq!(processor.system().accounts.len(), 2);
+    assert_eq!(processor.system().total_credits(), Decimal::from(337) + Decimal::new(5, 1));
+
+    // Bucketing rounds the overdraft away, which the shape shows
+    let bucketed = dir.join("bucketed.json");
+    let mut scrubber = BankScrubber::new("seed").with_amounts(AmountPolicy::Bucket(Decimal::from(100)));
+    anonymize_log(&log, &bucketed, BankJsonConverter::new(), BankJsonConverter::new(), &mut scrubber).unwrap();
+    assert!(replay_shape(&bucketed, BankJsonConverter::new()).unwrap().replays_cleanly());
+}
@@ -0,0 +1,80 @@
+#![cfg(feature = "bank-example")]
+
+use rmemimg::memimg::bank::Bank;
+use rmemimg::memimg::bank_pipe::run_pipe;
+use rmemimg::memimg::testing::FaultyEventStorage;
+use rmemimg::memimg::{MemImgProcessor, MemoryEventStorage};
+use serde_json::{json, Value};
+use std::io::{Cursor, ErrorKind};
+
+fn pipe(input: &str, fail_appends: bool) -> (Vec<Value>, bool) {
+    let failing_appends = if fail_appends { u64::MAX } else { 0 };
+    let storage = Box::new(FaultyEventStorage::new(MemoryEventStorage::new()).with_failing_appends(failing_appends, ErrorKind::Other));
+    let mut processor = MemImgProcessor::new_simple(Bank::new(), storage).unwrap();
+    let mut output = Vec::new();
+    let outcome = run_pipe(&mut processor, Cursor::new(input), &mut output);
+    let results = String::from_utf8(output)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    (results, outcome.is_ok())
+}
+
+#[test]
+fn reports_each_result_and_continues_after_domain_failures() {
+    let input = r#"{"CreateAccount":{"id":"alice","name":"Alice"}}
+{"Deposit":{"account_id":"alice","amount":"100"}}
+{"Withdrawal":{"account_id":"alice","amount":"500"}}
+
+{"query":"GetBalance","account_id":"alice"}
+{"query":"GetBalance","account_id":"bob"}
+{"Withdrawal":{"account_id":"alice","amount":"40"}}
+{"query":"ListAccounts"}
+"#;
+    let (results, ok) = pipe(input, false);
+
+    assert!(ok);
+    assert_eq!(
+        results,
+        vec![
+            json!({"ok": true, "seq": 1}),
+            json!({"ok": true, "seq": 2}),
+            json!({"ok": false, "code": "INSUFFICIENT_FUNDS", "message": "Insufficient funds: 100 < 500"}),
+            json!({"ok": true, "result": "100"}),
+            json!({"ok": false, "code": "ACCOUNT_NOT_FOUND", "message": "Account not found: bob"}),
+            json!({"ok": true, "seq": 3}),
+            json!({"ok": true, "result": [{"id": "alice", "name": "Alice", "balance": "60"}]}),
+        ]
+    );
+}
+
+#[test]
+fn reports_malformed_lines_and_continues() {
+    let input = r#"not json
+{"Teleport":{"account_id":"alice"}}
+{"query":"GetNothing"}
+{"CreateAccount":{"id":"alice","name":"Alice"}}
+"#;
+    let (results, ok) = pipe(input, false);
+
+    assert!(ok);
+    assert_eq!(results.len(), 4);
+    for result in &results[..3] {
+        assert_eq!(result["ok"], json!(false));
+        assert_eq!(result["code"], json!("MALFORMED_INPUT"));
+    }
+    assert_eq!(results[3], json!({"ok": true, "seq": 1}));
+}
+
+#[test]
+fn stops_with_an_error_on_system_failure() {
+    let input = r#"{"CreateAccount":{"id":"alice","name":"Alice"}}
+{"CreateAccount":{"id":"bob","name":"Bob"}}
+"#;
+    let (results, ok) = pipe(input, true);
+
+    assert!(!ok);
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0]["code"], json!("SYSTEM_FAILURE"));
+}
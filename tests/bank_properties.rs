@@ -0,0 +1,96 @@
+#![cfg(feature = "test-util")]
+
+use proptest::prelude::*;
+use rmemimg::memimg::bank::{Amount, Bank, BankCommand};
+use rmemimg::memimg::testing::{bank_commands_strategy, check_replay_deterministic};
+use rmemimg::memimg::{MemImgProcessor, MemoryEventStorage};
+
+type BankProcessor = MemImgProcessor<Bank, BankCommand, MemoryEventStorage<BankCommand>>;
+
+fn run(commands: &[BankCommand]) -> (BankProcessor, Amount) {
+    let mut processor = MemImgProcessor::new_simple(Bank::new(), Box::new(MemoryEventStorage::new())).unwrap();
+    let mut net_deposits = Amount::ZERO;
+    for command in commands {
+        if processor.execute_command(command.clone()).is_ok() {
+            match command {
+                BankCommand::Deposit { amount, .. } => net_deposits += *amount,
+                BankCommand::Withdrawal { amount, .. } => net_deposits -= *amount,
+                _ => {}
+            }
+        }
+    }
+    (processor, net_deposits)
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(300))]
+
+    #[test]
+    fn replaying_the_log_rebuilds_the_live_bank(commands in bank_commands_strategy(40)) {
+        let (mut processor, _) = run(&commands);
+
+        prop_assert_eq!(check_replay_deterministic(&mut processor).unwrap(), None);
+    }
+
+    #[test]
+    fn no_balance_goes_below_zero(commands in bank_commands_strategy(40)) {
+        let mut processor = MemImgProcessor::new_simple(Bank::new(), Box::new(MemoryEventStorage::new())).unwrap();
+        for command in commands {
+            let _ = processor.execute_command(command.clone());
+            for account in processor.system().accounts.values() {
+                prop_assert!(account.balance() >= Amount::ZERO, "{} went to {} after {}", account.id, account.balance(), command);
+            }
+        }
+    }
+
+    #[test]
+    fn balances_sum_to_net_deposits(commands in bank_commands_strategy(40)) {
+        let (processor, net_deposits) = run(&commands);
+
+        let total: Amount = processor.system().accounts.values().map(|account| account.balance()).sum();
+        prop_assert_eq!(total, net_deposits);
+        prop_assert_eq!(processor.system().equity_capital, net_deposits);
+    }
+
+    #[test]
+    fn every_command_displays_on_one_line(command in any::<BankCommand>()) {
+        let text = command.to_string();
+
+        prop_assert!(!text.contains('\n'));
+        prop_assert!(text.ends_with(')'), "{}", text);
+    }
+
+    #[test]
+    fn transfers_conserve_total_assets(
+        balances in proptest::collection::vec(0u32..10_000, 2..6),
+        transfers in proptest::collection::vec((0usize..6, 0usize..6, 1u32..5_000), 0..40),
+    ) {
+        let id = |index: usize| format!("acc{}", index % balances.len());
+        let mut bank = Bank::new();
+        for (index, balance) in balances.iter().enumerate() {
+            let create = BankCommand::CreateAccount { id: id(index).into(), name: id(index), opening_balance: Some(Amount::from(*balance)) };
+            bank = bank.apply(&create).unwrap();
+        }
+        let total = |bank: &Bank| bank.accounts.values().map(|account| account.balance()).sum::<Amount>();
+        let (assets, equity) = (total(&bank), bank.equity_capital);
+
+        for (from, to, amount) in transfers {
+            let transfer = BankCommand::Transfer { from_account_id: id(from).into(), to_account_id: id(to).into(), amount: Amount::from(amount) };
+            // Overdrafts are refused, leaving `bank` as it was
+            if let Ok(next) = bank.apply(&transfer) {
+                bank = next;
+            }
+            prop_assert_eq!(total(&bank), assets);
+            prop_assert_eq!(bank.equity_capital, equity);
+        }
+    }
+
+    #[test]
+    fn account_keys_always_share_their_account_ids(commands in bank_commands_strategy(40)) {
+        let (processor, _) = run(&commands);
+
+        for (key, account) in &processor.system().accounts {
+            prop_assert!(key.ptr_eq(&account.id), "key {} does not share its account's id", key);
+        }
+    }
+}
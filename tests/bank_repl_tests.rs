@@ -0,0 +1,69 @@
+#![cfg(feature = "bank-example")]
+
+use rmemimg::memimg::bank::{BankCommand, BankError};
+use rmemimg::memimg::bank_repl::{parse_repl_line, touches_account, ReplCommand, ReplParseError};
+use rust_decimal::Decimal;
+
+fn parse(line: &str) -> ReplCommand {
+    parse_repl_line(line).unwrap().unwrap()
+}
+
+#[test]
+fn parses_every_command() {
+    assert_eq!(
+        parse("create alice Alice Smith"),
+        ReplCommand::Execute(BankCommand::CreateAccount { id: "alice".into(), name: "Alice Smith".to_string(), opening_balance: None })
+    );
+    assert_eq!(
+        parse("deposit alice $1,000.50"),
+        ReplCommand::Execute(BankCommand::Deposit { account_id: "alice".into(), amount: Decimal::new(100050, 2) })
+    );
+    assert_eq!(
+        parse("withdraw alice 20"),
+        ReplCommand::Execute(BankCommand::Withdrawal { account_id: "alice".into(), amount: Decimal::from(20) })
+    );
+    assert_eq!(
+        parse("  TRANSFER alice bob 5  "),
+        ReplCommand::Execute(BankCommand::Transfer {
+            from_account_id: "alice".into(),
+            to_account_id: "bob".into(),
+            amount: Decimal::from(5),
+        })
+    );
+    assert_eq!(parse("close alice"), ReplCommand::Execute(BankCommand::CloseAccount { id: "alice".into() }));
+    assert_eq!(parse("balance alice"), ReplCommand::Balance("alice".into()));
+    assert_eq!(parse("accounts"), ReplCommand::Accounts);
+    assert_eq!(parse("history bob"), ReplCommand::History("bob".into()));
+    assert_eq!(parse("help"), ReplCommand::Help);
+    assert_eq!(parse("quit"), ReplCommand::Quit);
+    assert_eq!(parse_repl_line("   ").unwrap(), None);
+}
+
+#[test]
+fn rejects_malformed_lines() {
+    assert_eq!(parse_repl_line("frobnicate"), Err(ReplParseError::UnknownCommand("frobnicate".to_string())));
+    assert_eq!(parse_repl_line("create alice"), Err(ReplParseError::Usage { usage: "create <id> <name>" }));
+    assert_eq!(parse_repl_line("deposit alice"), Err(ReplParseError::Usage { usage: "deposit <id> <amount>" }));
+    assert_eq!(parse_repl_line("withdraw"), Err(ReplParseError::Usage { usage: "withdraw <id> <amount>" }));
+    assert_eq!(parse_repl_line("transfer alice bob"), Err(ReplParseError::Usage { usage: "transfer <from> <to> <amount>" }));
+    assert_eq!(parse_repl_line("close"), Err(ReplParseError::Usage { usage: "close <id>" }));
+    assert_eq!(parse_repl_line("balance"), Err(ReplParseError::Usage { usage: "balance <id>" }));
+    assert_eq!(parse_repl_line("accounts extra"), Err(ReplParseError::Usage { usage: "accounts" }));
+    assert_eq!(parse_repl_line("history a b"), Err(ReplParseError::Usage { usage: "history <id>" }));
+    assert_eq!(
+        parse_repl_line("deposit alice 12abc"),
+        Err(ReplParseError::Amount(BankError::InvalidAmount("12abc".to_string())))
+    );
+}
+
+#[test]
+fn history_filter_matches_both_sides_of_transfers() {
+    let transfer = BankCommand::Transfer {
+        from_account_id: "alice".into(),
+        to_account_id: "bob".into(),
+        amount: Decimal::from(1),
+    };
+    assert!(touches_account(&transfer, "alice"));
+    assert!(touches_account(&transfer, "bob"));
+    assert!(!touches_account(&transfer, "carol"));
+}
@@ -0,0 +1,897 @@
+#![cfg(feature = "bank-example")]
+
+use rmemimg::memimg::bank::{parse_amount, query_registry, Account, AccountId, Bank, BankCommand, AUTO_CREATED_ACCOUNT_NAME, BankError, BankErrorFormatter, EnglishBankErrors, GetAccountsModifiedSince, GetBalance, GetBalanceCents, GetBalanceFormatted, GetBalanceHistory, GetTombstone, GetTotalBalance, InterestPeriod, LedgerEntry, ListAccountIds, ListAccounts, ListAccountsPage, NegativeBalancePolicy, PaymentMethod, Tombstone};
+use rmemimg::memimg::bank_audit::{bank_index_keys, kind_key, GetAuditTrail};
+use rmemimg::memimg::bank_events::{BankEvent, BankTransfer};
+use rmemimg::memimg::bank_sorted::SortedBank;
+use rmemimg::memimg::bank_invariants::{IntegrityReport, IntegrityViolation, VerifyIntegrity};
+use rmemimg::memimg::bank_storage::{bank_command_deserializer, BankJsonConverter};
+use rmemimg::memimg::{Command, DuplicateCommandName, EventStorage, MemImgError, MultiEventProcessor, MemImgProcessor, MemoryEventStorage, Query, QueryRouter, SnapshotFormat, TextConverter, TextFileEventStorage};
+use rust_decimal::Decimal;
+use serde_json::json;
+
+#[test]
+fn parses_currency_with_thousands_separators() {
+    assert_eq!(parse_amount("$1,000.50").unwrap(), Decimal::new(100050, 2));
+}
+
+#[test]
+fn parses_plain_decimal() {
+    assert_eq!(parse_amount("1000.5").unwrap(), Decimal::new(10005, 1));
+    assert_eq!(parse_amount(" -$25 ").unwrap(), Decimal::new(-25, 0));
+}
+
+#[test]
+fn rejects_garbage_amounts() {
+    for input in ["abc", "", "$", "1,00", "1.2.3", "12a", "1."] {
+        assert_eq!(
+            parse_amount(input),
+            Err(BankError::InvalidAmount(input.to_string())),
+            "input {:?}",
+            input
+        );
+    }
+}
+
+struct SpanishBankErrors;
+
+impl BankErrorFormatter for SpanishBankErrors {
+    fn format(&self, error: &BankError) -> String {
+        match error {
+            BankError::InsufficientFunds { available, requested } => {
+                format!("Fondos insuficientes: disponible {}, solicitado {}", available, requested)
+            }
+            other => EnglishBankErrors.format(other),
+        }
+    }
+}
+
+#[test]
+fn bank_errors_render_through_a_pluggable_formatter() {
+    let error = BankError::InsufficientFunds { available: Decimal::from(10), requested: Decimal::from(25) };
+
+    assert_eq!(error.render(&EnglishBankErrors), "Insufficient funds: 10 < 25");
+    assert_eq!(error.render(&SpanishBankErrors), "Fondos insuficientes: disponible 10, solicitado 25");
+    assert_eq!(
+        BankError::AccountNotFound("bob".to_string()).render(&SpanishBankErrors),
+        "Account not found: bob"
+    );
+}
+
+fn populated_bank(ids: &[&str]) -> Bank {
+    let mut bank = Bank::new();
+    // Created in one command, so every account carries the same `last_modified_seq` whatever the order
+    let accounts = ids.iter().map(|id| ((*id).into(), id.to_uppercase())).collect();
+    BankCommand::BulkCreateAccounts { accounts }.apply_to(&mut bank).unwrap();
+    BankCommand::Deposit { account_id: "alice".into(), amount: Decimal::new(10050, 2) }.apply_to(&mut bank).unwrap();
+    BankCommand::CloseAccount { id: "dave".into() }.apply_to(&mut bank).unwrap();
+    bank
+}
+
+#[test]
+fn bank_round_trips_through_json() {
+    let bank = populated_bank(&["alice", "bob", "carol", "dave"]);
+
+    let json = serde_json::to_string(&bank).unwrap();
+    let restored: Bank = serde_json::from_str(&json).unwrap();
+
+    assert_eq!(restored, bank);
+    assert_eq!(restored.accounts["alice"].balance(), Decimal::new(10050, 2));
+}
+
+#[test]
+fn bank_serialization_is_sorted_and_stable() {
+    let forwards = serde_json::to_string(&populated_bank(&["alice", "bob", "carol", "dave"])).unwrap();
+    let backwards = serde_json::to_string(&populated_bank(&["dave", "carol", "bob", "alice"])).unwrap();
+
+    assert_eq!(forwards, backwards);
+    assert_eq!(
+        forwards,
+        concat!(
+            r#"{"accounts":{"#,
+            r#""alice":{"id":"alice","name":"ALICE","total_debits":"0","total_credits":"100.50","owners":["ALICE"],"last_modified_seq":2},"#,
+            r#""bob":{"id":"bob","name":"BOB","total_debits":"0","total_credits":"0","owners":["BOB"],"last_modified_seq":1},"#,
+            r#""carol":{"id":"carol","name":"CAROL","total_debits":"0","total_credits":"0","owners":["CAROL"],"last_modified_seq":1}},"#,
+            r#""closed_accounts":["dave"],"equity_capital":"100.5","modification_seq":3}"#,
+        )
+    );
+}
+
+#[test]
+fn creating_an_existing_account_is_rejected() {
+    let mut bank = populated_bank(&["alice", "dave"]);
+
+    let error = BankCommand::CreateAccount { id: "alice".into(), name: "Impostor".to_string(), opening_balance: None }.apply_to(&mut bank).unwrap_err();
+
+    assert_eq!(error.downcast_ref::<BankError>(), Some(&BankError::DuplicateAccount("alice".to_string())));
+    assert_eq!(bank.accounts["alice"].balance(), Decimal::new(10050, 2));
+}
+
+#[test]
+fn account_ids_keep_their_plain_string_json_form() {
+    let line = r#"{"Transfer":{"from_account_id":"acc1","to_account_id":"acc2","amount":"12.50"}}"#;
+
+    let command = BankJsonConverter::new().parse(line).unwrap();
+
+    assert_eq!(
+        command,
+        BankCommand::Transfer { from_account_id: "acc1".into(), to_account_id: "acc2".into(), amount: Decimal::new(1250, 2) }
+    );
+    assert_eq!(BankJsonConverter::new().format(&command).unwrap(), line);
+}
+
+#[test]
+fn rejects_malformed_account_ids() {
+    assert_eq!(AccountId::parse("acc-1").unwrap().as_str(), "acc-1");
+    for id in ["", "has space", "tab\there", &"x".repeat(AccountId::MAX_LEN + 1)] {
+        assert_eq!(AccountId::parse(id), Err(BankError::InvalidAccountId(id.to_string())), "{:?}", id);
+    }
+
+    let mut bank = Bank::new();
+    let error = BankCommand::CreateAccount { id: "bad id".into(), name: "Bad".to_string(), opening_balance: None }.apply_to(&mut bank).unwrap_err();
+    assert_eq!(error.downcast_ref::<BankError>(), Some(&BankError::InvalidAccountId("bad id".to_string())));
+    assert!(bank.accounts.is_empty());
+}
+
+#[test]
+fn commands_display_in_a_readable_one_line_form() {
+    let cases = [
+        (
+            BankCommand::CreateAccount { id: "alice".into(), name: "Alice".to_string(), opening_balance: None },
+            "CreateAccount(id=alice, name=Alice)",
+        ),
+        (
+            BankCommand::CreateAccount { id: "alice".into(), name: "Alice".to_string(), opening_balance: Some(Decimal::from(5)) },
+            "CreateAccount(id=alice, name=Alice, opening_balance=$5.00)",
+        ),
+        (BankCommand::Deposit { account_id: "alice".into(), amount: Decimal::from(100) }, "Deposit(account=alice, amount=$100.00)"),
+        (BankCommand::Withdrawal { account_id: "alice".into(), amount: Decimal::new(5, 1) }, "Withdrawal(account=alice, amount=$0.50)"),
+        (
+            BankCommand::Transfer { from_account_id: "alice".into(), to_account_id: "bob".into(), amount: Decimal::new(30005, 3) },
+            "Transfer(from=alice, to=bob, amount=$30.00)",
+        ),
+        (BankCommand::BulkCreateAccounts { accounts: vec![("bob".into(), "Bob".to_string())] }, "BulkCreateAccounts(count=1)"),
+        (BankCommand::CloseAccount { id: "alice".into() }, "CloseAccount(id=alice)"),
+        (BankCommand::AddOwner { account_id: "alice".into(), owner: "Bob".to_string() }, "AddOwner(account=alice, owner=Bob)"),
+        (BankCommand::RemoveOwner { account_id: "alice".into(), owner: "Bob".to_string() }, "RemoveOwner(account=alice, owner=Bob)"),
+        (BankCommand::ImportLedger { entries: Vec::new() }, "ImportLedger(count=0)"),
+        (
+            BankCommand::RecordExternalPayment {
+                account_id: "alice".into(),
+                amount: Decimal::from(25),
+                gateway: "stripe".to_string(),
+                gateway_transaction_id: "ch_1".to_string(),
+                payment_method: PaymentMethod::CreditCard,
+            },
+            "RecordExternalPayment(account=alice, amount=$25.00, gateway=stripe, transaction=ch_1)",
+        ),
+    ];
+
+    for (command, expected) in cases {
+        assert_eq!(command.to_string(), expected);
+    }
+}
+
+fn assert_keys_share_account_ids(bank: &Bank) {
+    for (key, account) in &bank.accounts {
+        assert_eq!(key, &account.id);
+        assert!(key.ptr_eq(&account.id), "key {} does not share its account's id", key);
+    }
+}
+
+#[test]
+fn account_keys_share_the_id_allocation() {
+    let mut bank = populated_bank(&["alice", "bob", "carol", "dave"]);
+    BankCommand::BulkCreateAccounts { accounts: vec![("erin".into(), "Erin".to_string())] }.apply_to(&mut bank).unwrap();
+    assert_keys_share_account_ids(&bank);
+
+    let shadow = bank.clone();
+    assert!(shadow.accounts["alice"].id.ptr_eq(&bank.accounts["alice"].id));
+
+    let restored: Bank = serde_json::from_str(&serde_json::to_string(&bank).unwrap()).unwrap();
+    assert_eq!(restored, bank);
+    assert_keys_share_account_ids(&restored);
+}
+
+#[test]
+fn rejects_snapshot_whose_account_key_and_id_disagree() {
+    let json = r#"{"accounts":{"alice":{"id":"bob","name":"Bob","total_debits":"0","total_credits":"0"}}}"#;
+
+    let error = serde_json::from_str::<Bank>(json).unwrap_err();
+
+    assert!(error.to_string().contains("account keyed alice has id bob"), "{}", error);
+}
+
+#[test]
+fn integrity_check_reports_every_desynced_index_entry() {
+    let mut bank = populated_bank(&["alice", "bob", "carol", "dave"]);
+    assert_eq!(
+        VerifyIntegrity.extract_from(&bank).unwrap(),
+        IntegrityReport { accounts_checked: 3, violations: vec![] }
+    );
+
+    // Re-key alice under another id and resurrect closed dave, as a broken migration might
+    let alice = bank.accounts.remove("alice").unwrap();
+    bank.accounts.insert("alicia".into(), alice);
+    bank.accounts.insert("dave".into(), Account::new("dave", "DAVE".to_string()));
+
+    let report = VerifyIntegrity.extract_from(&bank).unwrap();
+
+    assert!(!report.is_consistent());
+    assert_eq!(
+        report.violations,
+        vec![
+            IntegrityViolation::MisKeyedAccount { key: "alicia".into(), account_id: "alice".into() },
+            IntegrityViolation::ClosedAccountStillOpen("dave".into()),
+        ]
+    );
+}
+
+fn one_of_each_command() -> Vec<BankCommand> {
+    let amount = Decimal::new(1050, 2);
+    vec![
+        BankCommand::CreateAccount { id: "alice".into(), name: "Alice".to_string(), opening_balance: Some(amount) },
+        BankCommand::CreateAccount { id: "bob".into(), name: "Bob".to_string(), opening_balance: None },
+        BankCommand::Deposit { account_id: "alice".into(), amount },
+        BankCommand::Withdrawal { account_id: "alice".into(), amount },
+        BankCommand::Transfer { from_account_id: "alice".into(), to_account_id: "bob".into(), amount },
+        BankCommand::BulkCreateAccounts { accounts: vec![("carol".into(), "Carol".to_string())] },
+        BankCommand::CloseAccount { id: "carol".into() },
+        BankCommand::AddOwner { account_id: "bob".into(), owner: "Dave".to_string() },
+        BankCommand::RemoveOwner { account_id: "bob".into(), owner: "Dave".to_string() },
+        BankCommand::Sweep { from_account_id: "bob".into(), to_account_id: "alice".into(), amount: Some(amount) },
+        BankCommand::ImportLedger { entries: vec![LedgerEntry { account_id: "erin".into(), name: "Erin".to_string(), balance: amount }] },
+        BankCommand::RecordExternalPayment {
+            account_id: "erin".into(),
+            amount,
+            gateway: "stripe".to_string(),
+            gateway_transaction_id: "ch_1".to_string(),
+            payment_method: PaymentMethod::ACH,
+        },
+        BankCommand::ScheduleInterest {
+            account_id: "erin".into(),
+            annual_rate: Decimal::new(5, 2),
+            period: InterestPeriod::Monthly,
+            negative_balance: NegativeBalancePolicy::Charge,
+            starts_at: chrono::DateTime::UNIX_EPOCH,
+        },
+        BankCommand::AccrueInterest { account_id: "erin".into(), period_end: chrono::DateTime::UNIX_EPOCH, amount: Some(amount) },
+        BankCommand::ApplyOverdraftFee { account_id: "erin".into(), fee: amount, floor: -amount },
+    ]
+}
+
+#[test]
+fn internally_tagged_converter_round_trips_every_command() {
+    let converter = BankJsonConverter::internally_tagged("type");
+
+    for command in one_of_each_command() {
+        let line = converter.format(&command).unwrap();
+        assert!(!line.contains('\n'));
+        assert_eq!(converter.parse(&line).unwrap(), command, "{}", line);
+    }
+    let deposit = converter.format(&BankCommand::Deposit { account_id: "alice".into(), amount: Decimal::from(10) }).unwrap();
+    assert_eq!(serde_json::from_str::<serde_json::Value>(&deposit).unwrap(), serde_json::json!({"type": "Deposit", "account_id": "alice", "amount": "10"}));
+}
+
+#[test]
+fn tagged_converters_still_read_externally_tagged_lines() {
+    let adjacent = BankJsonConverter::adjacently_tagged("type", "data");
+    for command in one_of_each_command() {
+        assert_eq!(adjacent.parse(&adjacent.format(&command).unwrap()).unwrap(), command);
+
+        let legacy = BankJsonConverter::new().format(&command).unwrap();
+        assert_eq!(adjacent.parse(&legacy).unwrap(), command);
+        assert_eq!(BankJsonConverter::internally_tagged("type").parse(&legacy).unwrap(), command);
+    }
+}
+
+#[test]
+fn minor_units_converter_writes_integer_amounts_and_reads_them_back_exactly() {
+    let converter = BankJsonConverter::minor_units();
+    let deposit = BankCommand::Deposit { account_id: "alice".into(), amount: Decimal::new(10050, 2) };
+
+    let line = converter.format(&deposit).unwrap();
+    let value: serde_json::Value = serde_json::from_str(&line).unwrap();
+    assert_eq!(value["Deposit"]["amount"], json!({"minor_units": 10050, "scale": 2}));
+    let parsed = converter.parse(&line).unwrap();
+    assert_eq!(parsed, deposit);
+    let BankCommand::Deposit { amount, .. } = parsed else { unreachable!() };
+    assert_eq!(amount.to_string(), "100.50");
+
+    for command in one_of_each_command() {
+        assert_eq!(converter.parse(&converter.format(&command).unwrap()).unwrap(), command);
+        assert_eq!(converter.parse(&BankJsonConverter::new().format(&command).unwrap()).unwrap(), command);
+    }
+}
+
+#[test]
+fn command_deserializer_parses_each_variant_through_its_own_parser() {
+    let deserializer = bank_command_deserializer();
+    assert_eq!(deserializer.type_tags().len(), 15);
+
+    for command in one_of_each_command() {
+        assert_eq!(deserializer.parse(&deserializer.format(&command).unwrap()).unwrap(), command);
+        let tagged = BankJsonConverter::internally_tagged("type").format(&command).unwrap();
+        assert_eq!(deserializer.parse(&tagged).unwrap(), command, "{}", tagged);
+    }
+}
+
+#[test]
+fn command_deserializer_names_the_event_types_it_cannot_parse() {
+    let mut deserializer = bank_command_deserializer();
+
+    let error = deserializer.parse(r#"{"type":"Freeze","account_id":"alice"}"#).unwrap_err();
+    assert!(matches!(error.downcast_ref::<MemImgError>(), Some(MemImgError::UnknownCommand(tag)) if tag == "Freeze"));
+    assert_eq!(error.to_string(), "Unknown command Freeze");
+    let error = deserializer.parse(r#"{"Freeze":{"account_id":"alice"}}"#).unwrap_err();
+    assert_eq!(error.to_string(), "Unknown command Freeze");
+    let error = deserializer.parse(r#"{"type":"Deposit","account_id":"alice"}"#).unwrap_err();
+    assert!(error.to_string().starts_with("Invalid payload for command Deposit: field \"amount\""), "{}", error);
+    assert!(deserializer.parse(r#"{"account_id":"alice","amount":"1"}"#).is_err());
+
+    // A new event type needs only its own parser
+    deserializer
+        .register("Freeze", |fields| Ok(BankCommand::CloseAccount { id: serde_json::from_value(fields["account_id"].clone())? }))
+        .unwrap();
+    assert_eq!(deserializer.parse(r#"{"type":"Freeze","account_id":"alice"}"#).unwrap(), BankCommand::CloseAccount { id: "alice".into() });
+    assert_eq!(deserializer.register("Deposit", |_| Err("shadowed".into())), Err(DuplicateCommandName("Deposit".to_string())));
+}
+
+#[test]
+fn transfers_log_a_debit_and_a_credit_that_replay_one_by_one() {
+    let log = std::env::temp_dir().join("test_multi_event_transfers.json");
+    let _ = std::fs::remove_file(&log);
+    let genesis = populated_bank(&["alice", "bob", "carol", "dave"]);
+    let transfer = |from: &str, to: &str, amount: i64| BankTransfer { from_account_id: from.into(), to_account_id: to.into(), amount: Decimal::from(amount) };
+    let mut processor = MultiEventProcessor::new(genesis.clone(), Box::new(TextFileEventStorage::json(&log).unwrap())).unwrap();
+
+    let events = processor.execute(&transfer("alice", "bob", 40)).unwrap();
+    assert_eq!(
+        events,
+        vec![
+            BankEvent::AccountDebited { account_id: "alice".into(), amount: Decimal::from(40) },
+            BankEvent::AccountCredited { account_id: "bob".into(), amount: Decimal::from(40) },
+        ]
+    );
+    processor.execute(&transfer("bob", "carol", 15)).unwrap();
+    assert!(processor.execute(&transfer("carol", "carol", 5)).unwrap().is_empty());
+    assert!(processor.execute(&transfer("bob", "alice", 0)).unwrap().is_empty());
+    let before = processor.system().clone();
+    assert!(processor.execute(&transfer("carol", "alice", 16)).unwrap_err().to_string().contains("Insufficient funds"));
+    assert_eq!(processor.system(), &before);
+    assert_eq!(processor.event_count(), 4);
+    let balances = |bank: &Bank| ["alice", "bob", "carol"].map(|id| bank.accounts[id].balance());
+    assert_eq!(balances(processor.system()), [Decimal::new(6050, 2), Decimal::from(25), Decimal::from(15)]);
+    drop(processor);
+
+    // Each event applies on its own: every prefix of the log replays
+    let mut logged = Vec::new();
+    TextFileEventStorage::json(&log)
+        .unwrap()
+        .replay(&mut |event: BankEvent| {
+            logged.push(event);
+            Ok(())
+        })
+        .unwrap();
+    assert_eq!(logged.len(), 4);
+    for n in 0..=logged.len() {
+        let storage = MemoryEventStorage::with_events(logged[..n].to_vec());
+        assert_eq!(MultiEventProcessor::<BankTransfer, _>::new(genesis.clone(), Box::new(storage)).unwrap().event_count(), n as u64);
+    }
+    let reopened = MultiEventProcessor::<BankTransfer, _>::new(genesis, Box::new(TextFileEventStorage::json(&log).unwrap())).unwrap();
+    assert_eq!(reopened.system(), &before);
+    let _ = std::fs::remove_file(&log);
+}
+
+fn processor_with_alice() -> MemImgProcessor<Bank, BankCommand, MemoryEventStorage<BankCommand>> {
+    let mut processor = MemImgProcessor::new_simple(Bank::new(), Box::new(MemoryEventStorage::new())).unwrap();
+    processor
+        .execute_command(BankCommand::CreateAccount { id: "alice".into(), name: "Alice".to_string(), opening_balance: None })
+        .unwrap();
+    processor
+        .execute_command(BankCommand::Deposit { account_id: "alice".into(), amount: Decimal::new(1050, 2) })
+        .unwrap();
+    processor
+}
+
+#[test]
+fn query_registry_runs_bank_queries_from_json() {
+    let processor = processor_with_alice();
+    let registry = query_registry();
+
+    assert_eq!(registry.names().collect::<Vec<_>>(), vec!["GetAccount", "GetAccountsModifiedSince", "GetBalance", "ListAccountIds", "ListAccounts", "ListAccountsPage"]);
+    let balance = registry.execute_json(&processor, "GetBalance", json!({"account_id": "alice"})).unwrap();
+    assert_eq!(balance, json!("10.50"));
+    let account = registry.execute_json(&processor, "GetAccount", json!({"account_id": "alice"})).unwrap();
+    assert_eq!(account["name"], json!("Alice"));
+    let missing = registry.execute_json(&processor, "GetAccount", json!({"account_id": "bob"})).unwrap();
+    assert_eq!(missing, serde_json::Value::Null);
+    let accounts = registry.execute_json(&processor, "ListAccounts", serde_json::Value::Null).unwrap();
+    assert_eq!(accounts.as_array().unwrap().len(), 1);
+}
+
+fn bank_with_shuffled_ids() -> Bank {
+    let entries = ["carol", "Bob", "alice", "acc10", "acc2"]
+        .into_iter()
+        .map(|id| LedgerEntry { account_id: id.into(), name: id.to_string(), balance: Decimal::new(100, 0) })
+        .collect();
+    let mut bank = Bank::new();
+    BankCommand::ImportLedger { entries }.apply_to(&mut bank).unwrap();
+    bank
+}
+
+#[test]
+fn list_accounts_returns_accounts_in_lexicographic_id_order() {
+    let bank = bank_with_shuffled_ids();
+
+    let accounts = ListAccounts.extract_from(&bank).unwrap();
+    let ids: Vec<&str> = accounts.iter().map(|account| account.id.as_str()).collect();
+    assert_eq!(ids, vec!["Bob", "acc10", "acc2", "alice", "carol"]);
+}
+
+#[test]
+fn list_account_ids_returns_only_the_sorted_ids() {
+    let bank = bank_with_shuffled_ids();
+
+    let ids: Vec<AccountId> = ListAccountIds.extract_from(&bank).unwrap();
+    assert_eq!(ids, ["Bob", "acc10", "acc2", "alice", "carol"].map(AccountId::from));
+
+    let processor = MemImgProcessor::new_simple(bank, Box::new(MemoryEventStorage::<BankCommand>::new())).unwrap();
+    let json = query_registry().execute_json(&processor, "ListAccountIds", json!(null)).unwrap();
+    assert_eq!(json, json!(["Bob", "acc10", "acc2", "alice", "carol"]));
+}
+
+#[test]
+fn list_accounts_page_reports_the_full_total_on_every_page() {
+    let bank = bank_with_shuffled_ids();
+
+    let pages: Vec<_> = [0, 2, 4].into_iter().map(|offset| ListAccountsPage { offset, limit: 2 }.extract_from(&bank).unwrap()).collect();
+    let ids: Vec<Vec<&str>> = pages.iter().map(|page| page.items.iter().map(|account| account.id.as_str()).collect()).collect();
+    assert_eq!(ids, vec![vec!["Bob", "acc10"], vec!["acc2", "alice"], vec!["carol"]]);
+    for page in &pages {
+        assert_eq!((page.total, page.limit, page.page_count()), (5, 2, 3));
+    }
+    assert_eq!(pages[2].offset, 4);
+
+    let past_the_end = ListAccountsPage { offset: 6, limit: 2 }.extract_from(&bank).unwrap();
+    assert!(past_the_end.items.is_empty());
+    assert_eq!(past_the_end.total, 5);
+    assert_eq!(ListAccountsPage { offset: 0, limit: 0 }.extract_from(&bank).unwrap().page_count(), 0);
+
+    let json = serde_json::to_value(&pages[2]).unwrap();
+    assert_eq!((&json["total"], &json["offset"], &json["limit"]), (&json!(5), &json!(4), &json!(2)));
+}
+
+#[test]
+fn formatted_balances_round_half_away_from_zero() {
+    let mut bank = Bank::new();
+    BankCommand::CreateAccount { id: "alice".into(), name: "Alice".to_string(), opening_balance: Some(Decimal::new(1005, 3)) }
+        .apply_to(&mut bank)
+        .unwrap();
+    let formatted = |decimal_places| GetBalanceFormatted { account_id: "alice".into(), decimal_places }.extract_from(&bank).unwrap();
+
+    assert_eq!(formatted(2), "1.01");
+    assert_eq!(formatted(4), "1.0050");
+    assert_eq!(formatted(0), "1");
+    assert_eq!(GetBalanceCents { account_id: "alice".into() }.extract_from(&bank).unwrap(), 101);
+    assert!(matches!(
+        GetBalanceCents { account_id: "bob".into() }.extract_from(&bank).unwrap_err().downcast_ref::<BankError>(),
+        Some(BankError::AccountNotFound(_))
+    ));
+}
+
+#[test]
+fn accounts_modified_since_a_sequence_are_the_ones_changed_after_it() {
+    let test_file = std::env::temp_dir().join("test_modified_since_events.json");
+    let _ = std::fs::remove_file(&test_file);
+    let open = || MemImgProcessor::new_simple(Bank::new(), Box::new(TextFileEventStorage::new(&test_file, BankJsonConverter::new()).unwrap())).unwrap();
+
+    let mut processor = open();
+    for id in ["alice", "bob", "carol"] {
+        processor.execute_command(BankCommand::CreateAccount { id: id.into(), name: id.to_string(), opening_balance: None }).unwrap();
+    }
+    let synced = processor.system().modification_seq;
+    processor.execute_command(BankCommand::Deposit { account_id: "bob".into(), amount: Decimal::new(5, 0) }).unwrap();
+
+    let changed = processor.execute_query(&GetAccountsModifiedSince { seq: synced }).unwrap();
+    assert_eq!(changed.iter().map(|account| account.id.as_str()).collect::<Vec<_>>(), vec!["bob"]);
+    assert_eq!(processor.execute_query(&GetAccountsModifiedSince { seq: 0 }).unwrap().len(), 3);
+    let live = processor.system().clone();
+    drop(processor);
+
+    let replayed = open();
+    assert_eq!(replayed.system(), &live);
+    assert_eq!(replayed.execute_query(&GetAccountsModifiedSince { seq: synced }).unwrap(), changed);
+
+    drop(replayed);
+    let _ = std::fs::remove_file(&test_file);
+}
+
+#[test]
+fn sorted_bank_iterates_in_lexicographic_id_order_and_matches_balances() {
+    let bank = bank_with_shuffled_ids();
+    let sorted = SortedBank::from(&bank);
+
+    let ids: Vec<&str> = sorted.accounts().map(|account| account.id.as_str()).collect();
+    assert_eq!(ids, vec!["Bob", "acc10", "acc2", "alice", "carol"]);
+    assert_eq!(sorted.len(), 5);
+    assert_eq!(sorted.balance("alice"), Some(GetBalance { account_id: "alice".into() }.extract_from(&bank).unwrap()));
+    assert_eq!(sorted.balance("dave"), None);
+    assert!(SortedBank::new().is_empty());
+}
+
+#[test]
+fn query_registry_reports_unknown_queries_and_bad_parameters_distinctly() {
+    let processor = processor_with_alice();
+    let registry = query_registry();
+
+    let unknown = registry.execute_json(&processor, "GetEverything", json!({})).unwrap_err();
+    assert_eq!(unknown.code(), "UNKNOWN_QUERY");
+    let missing_field = registry.execute_json(&processor, "GetBalance", json!({})).unwrap_err();
+    assert_eq!(missing_field.code(), "INVALID_QUERY_PARAMETERS");
+    let wrong_type = registry.execute_json(&processor, "GetBalance", json!({"account_id": 7})).unwrap_err();
+    assert_eq!(wrong_type.code(), "INVALID_QUERY_PARAMETERS");
+    let failed = registry.execute_json(&processor, "GetBalance", json!({"account_id": "bob"})).unwrap_err();
+    assert_eq!(failed.code(), "COMMAND_FAILURE");
+    assert!(matches!(
+        failed.outcome().unwrap().source.downcast_ref::<BankError>(),
+        Some(BankError::AccountNotFound(_))
+    ));
+}
+
+#[test]
+fn query_router_uses_registered_handlers_and_falls_back_to_the_query() {
+    let processor = processor_with_alice();
+    let fee = Decimal::new(50, 2);
+    let mut router = QueryRouter::new();
+    router.register(move |query: &GetBalance, bank: &Bank| Ok(query.extract_from(bank)? - fee));
+
+    let balance = router.route(&GetBalance { account_id: "alice".into() }, processor.system()).unwrap();
+    assert_eq!(balance, Decimal::new(1000, 2));
+    // GetTotalBalance has no handler, so it answers itself
+    assert_eq!(router.route(&GetTotalBalance, processor.system()).unwrap(), Decimal::new(1050, 2));
+}
+
+#[test]
+fn auto_create_policy_opens_missing_deposit_and_transfer_destinations() {
+    let events = vec![
+        BankCommand::Deposit { account_id: "alice".into(), amount: Decimal::from(100) },
+        BankCommand::Transfer { from_account_id: "alice".into(), to_account_id: "bob".into(), amount: Decimal::from(30) },
+    ];
+    let mut processor = MemImgProcessor::new_simple(Bank::new().with_auto_create_on_deposit(), Box::new(MemoryEventStorage::new())).unwrap();
+    for event in &events {
+        processor.execute_command(event.clone()).unwrap();
+    }
+
+    let bank = processor.system();
+    assert_eq!(bank.accounts["alice"].name, AUTO_CREATED_ACCOUNT_NAME);
+    assert_eq!(bank.accounts["alice"].balance(), Decimal::from(70));
+    assert_eq!(bank.accounts["bob"].balance(), Decimal::from(30));
+    assert_eq!(bank.equity_capital, Decimal::from(100));
+
+    // Replaying the log into a bank under the same policy rebuilds the auto-created accounts
+    let mut log = MemoryEventStorage::new();
+    for event in &events {
+        log.append(event).unwrap();
+    }
+    let replayed = MemImgProcessor::preview_replay(Bank::new().with_auto_create_on_deposit(), Box::new(log)).unwrap();
+    assert_eq!(&replayed, processor.system());
+}
+
+#[test]
+fn auto_create_policy_leaves_closed_accounts_and_failed_transfers_alone() {
+    let mut bank = Bank::new().with_auto_create_on_deposit();
+    BankCommand::CreateAccount { id: "alice".into(), name: "Alice".to_string(), opening_balance: None }.apply_to(&mut bank).unwrap();
+    BankCommand::CloseAccount { id: "alice".into() }.apply_to(&mut bank).unwrap();
+
+    let deposit = BankCommand::Deposit { account_id: "alice".into(), amount: Decimal::from(5) };
+    assert!(deposit.apply_to(&mut bank).unwrap_err().to_string().contains("closed"));
+
+    BankCommand::Deposit { account_id: "carol".into(), amount: Decimal::from(5) }.apply_to(&mut bank).unwrap();
+    let overdraw = BankCommand::Transfer { from_account_id: "carol".into(), to_account_id: "dave".into(), amount: Decimal::from(10) };
+    assert!(overdraw.apply_to(&mut bank).is_err());
+    assert!(!bank.accounts.contains_key("dave"));
+
+    let strict = BankCommand::Deposit { account_id: "erin".into(), amount: Decimal::from(5) };
+    assert!(strict.apply_to(&mut Bank::new()).is_err());
+}
+
+#[test]
+fn auto_create_policy_is_omitted_from_json_while_off() {
+    assert!(!serde_json::to_string(&Bank::new()).unwrap().contains("auto_create_on_deposit"));
+    let bank = Bank::new().with_auto_create_on_deposit();
+    let restored: Bank = serde_json::from_str(&serde_json::to_string(&bank).unwrap()).unwrap();
+    assert!(restored.auto_create_on_deposit);
+}
+
+#[test]
+fn apply_returns_the_new_bank_and_leaves_the_original() {
+    let bank = Bank::new()
+        .apply(&BankCommand::CreateAccount { id: "alice".into(), name: "Alice".to_string(), opening_balance: Some(Decimal::from(30)) })
+        .unwrap();
+
+    let after = bank.apply(&BankCommand::Withdrawal { account_id: "alice".into(), amount: Decimal::from(10) }).unwrap();
+    assert_eq!(after.accounts["alice"].balance(), Decimal::from(20));
+    assert_eq!(bank.accounts["alice"].balance(), Decimal::from(30));
+
+    let error = bank.apply(&BankCommand::Withdrawal { account_id: "alice".into(), amount: Decimal::from(50) }).unwrap_err();
+    assert_eq!(error, BankError::InsufficientFunds { available: Decimal::from(30), requested: Decimal::from(50) });
+}
+
+#[test]
+fn zero_and_negative_amounts_are_rejected_by_deposits_withdrawals_and_transfers() {
+    let bank = Bank::new()
+        .apply(&BankCommand::CreateAccount { id: "alice".into(), name: "Alice".to_string(), opening_balance: Some(Decimal::from(30)) })
+        .unwrap()
+        .apply(&BankCommand::CreateAccount { id: "bob".into(), name: "Bob".to_string(), opening_balance: None })
+        .unwrap();
+    let processor = MemImgProcessor::new_simple(bank.clone(), Box::new(MemoryEventStorage::new())).unwrap();
+
+    for amount in [Decimal::ZERO, Decimal::from(-5)] {
+        let commands = [
+            BankCommand::Deposit { account_id: "alice".into(), amount },
+            BankCommand::Withdrawal { account_id: "alice".into(), amount },
+            BankCommand::Transfer { from_account_id: "alice".into(), to_account_id: "bob".into(), amount },
+        ];
+        for command in commands {
+            assert_eq!(bank.apply(&command).unwrap_err(), BankError::InvalidAmount(amount.to_string()), "{}", command);
+            assert!(matches!(processor.can_apply(&command), Err(MemImgError::CommandFailure(_))), "{}", command);
+        }
+    }
+    assert!(processor.can_apply(&BankCommand::Transfer { from_account_id: "alice".into(), to_account_id: "bob".into(), amount: Decimal::ONE }).is_ok());
+}
+
+#[test]
+fn balance_history_records_each_deposit_in_order_and_evicts_the_oldest() {
+    let deposit = |amount: i64| BankCommand::Deposit { account_id: "alice".into(), amount: Decimal::from(amount) };
+    let history_of = |max_points: usize| {
+        let mut processor = MemImgProcessor::new_simple(Bank::new().with_balance_history(max_points), Box::new(MemoryEventStorage::new())).unwrap();
+        processor.execute_command(BankCommand::CreateAccount { id: "alice".into(), name: "Alice".to_string(), opening_balance: None }).unwrap();
+        processor.execute_command(BankCommand::AddOwner { account_id: "alice".into(), owner: "Bob".to_string() }).unwrap();
+        for amount in [10, 20, 30] {
+            processor.execute_command(deposit(amount)).unwrap();
+        }
+        processor.execute_query(&GetBalanceHistory { account_id: "alice".into() }).unwrap()
+    };
+
+    // Opening an empty account and adding an owner leave the balance where it was
+    let history = history_of(10);
+    let balances: Vec<_> = history.iter().map(|point| point.balance).collect();
+    assert_eq!(balances, [10, 30, 60].map(Decimal::from));
+    assert!(history.windows(2).all(|points| points[0].ts <= points[1].ts));
+
+    let balances: Vec<_> = history_of(2).iter().map(|point| point.balance).collect();
+    assert_eq!(balances, [30, 60].map(Decimal::from));
+
+    let mut processor = MemImgProcessor::new_simple(Bank::new(), Box::new(MemoryEventStorage::new())).unwrap();
+    processor.execute_command(BankCommand::CreateAccount { id: "alice".into(), name: "Alice".to_string(), opening_balance: Some(Decimal::from(5)) }).unwrap();
+    assert!(processor.execute_query(&GetBalanceHistory { account_id: "alice".into() }).unwrap().is_empty());
+}
+
+#[test]
+fn unique_names_reject_a_second_alice_only_when_required() {
+    let create = |id: &str| BankCommand::CreateAccount { id: id.into(), name: "Alice".to_string(), opening_balance: None };
+
+    let mut lenient = Bank::new();
+    create("alice-1").apply_to(&mut lenient).unwrap();
+    create("alice-2").apply_to(&mut lenient).unwrap();
+
+    let mut strict = MemImgProcessor::new_simple(Bank::new().with_unique_names(), Box::new(MemoryEventStorage::new())).unwrap();
+    strict.execute_command(create("alice-1")).unwrap();
+    let error = strict.execute_command(create("alice-2")).unwrap_err();
+    let domain = error.outcome().and_then(|outcome| outcome.source.downcast_ref::<BankError>());
+    assert_eq!(domain, Some(&BankError::DuplicateAccountName("Alice".to_string())));
+    assert_eq!(domain.unwrap().code(), "DUPLICATE_ACCOUNT_NAME");
+    assert!(!strict.system().accounts.contains_key("alice-2"));
+
+    let batch = BankCommand::BulkCreateAccounts { accounts: vec![("bob-1".into(), "Bob".to_string()), ("bob-2".into(), "Bob".to_string())] };
+    assert!(strict.execute_command(batch).is_err());
+
+    // A historical log with duplicates replays leniently, then the policy holds for new commands
+    let log = MemoryEventStorage::with_events(vec![create("alice-1"), create("alice-2")]);
+    let mut replayed = MemImgProcessor::new_simple(Bank::new(), Box::new(log)).unwrap();
+    assert_eq!(replayed.system().accounts.len(), 2);
+    replayed.system_mut().require_unique_names = true;
+    assert!(replayed.execute_command(create("alice-3")).is_err());
+}
+
+#[test]
+fn tick_compounds_monthly_interest_and_replays_the_recorded_amounts() {
+    let at = |date: &str| chrono::DateTime::parse_from_rfc3339(date).unwrap().to_utc();
+    let mut processor = MemImgProcessor::new_simple(Bank::new(), Box::new(MemoryEventStorage::new())).unwrap();
+    processor.execute_command(BankCommand::CreateAccount { id: "alice".into(), name: "Alice".to_string(), opening_balance: Some(Decimal::from(1000)) }).unwrap();
+    processor
+        .execute_command(BankCommand::ScheduleInterest {
+            account_id: "alice".into(),
+            annual_rate: Decimal::new(5, 2),
+            period: InterestPeriod::Monthly,
+            negative_balance: NegativeBalancePolicy::Skip,
+            starts_at: at("2026-01-31T00:00:00Z"),
+        })
+        .unwrap();
+
+    // Two periods end by March 31st: February 28th, then March 31st
+    assert_eq!(processor.tick(at("2026-02-27T00:00:00Z")).unwrap(), 0);
+    assert_eq!(processor.tick(at("2026-03-31T00:00:00Z")).unwrap(), 2);
+    assert_eq!(processor.tick(at("2026-03-31T00:00:00Z")).unwrap(), 0);
+    // 1000 * 0.05 / 12 = 4.1666... rounds to 4.17; 1004.17 * 0.05 / 12 = 4.1840... rounds to 4.18
+    let balance = processor.system().accounts["alice"].balance();
+    assert_eq!(balance, Decimal::new(100835, 2));
+    let recorded: Vec<_> = processor.event_storage.events()[2..].to_vec();
+    assert_eq!(
+        recorded,
+        vec![
+            BankCommand::AccrueInterest { account_id: "alice".into(), period_end: at("2026-02-28T00:00:00Z"), amount: Some(Decimal::new(417, 2)) },
+            BankCommand::AccrueInterest { account_id: "alice".into(), period_end: at("2026-03-31T00:00:00Z"), amount: Some(Decimal::new(418, 2)) },
+        ]
+    );
+
+    // Replay credits the logged amounts, whatever the balance came to be
+    let log = MemoryEventStorage::with_events(processor.event_storage.events().to_vec());
+    let replayed = MemImgProcessor::new_simple(Bank::new(), Box::new(log)).unwrap();
+    assert_eq!(replayed.system(), processor.system());
+    assert!(replayed.execute_query(&VerifyIntegrity).unwrap().is_consistent());
+
+    // A period accrues once, and only in turn
+    let early = BankCommand::AccrueInterest { account_id: "alice".into(), period_end: at("2026-05-31T00:00:00Z"), amount: None };
+    let error = processor.execute_command(early).unwrap_err();
+    let domain = error.outcome().and_then(|outcome| outcome.source.downcast_ref::<BankError>());
+    assert_eq!(domain.map(BankError::code), Some("INTEREST_NOT_DUE"));
+
+    // An overdrawn account is charged or skipped as its schedule says
+    let mut overdrawn = replayed.system().clone();
+    overdrawn.accounts.get_mut("alice").unwrap().total_debits += Decimal::from(1108) + Decimal::new(35, 2);
+    overdrawn.interest_schedules.get_mut("alice").unwrap().annual_rate = Decimal::new(12, 2);
+    assert_eq!(overdrawn.interest_due(&"alice".into()), Some(Decimal::ZERO));
+    overdrawn.interest_schedules.get_mut("alice").unwrap().negative_balance = NegativeBalancePolicy::Charge;
+    let accrual = overdrawn.due_interest(at("2026-04-30T00:00:00Z")).remove(0);
+    let charged = overdrawn.apply(&accrual).unwrap();
+    assert_eq!(charged.accounts["alice"].balance(), Decimal::from(-101));
+}
+
+#[test]
+fn overdraft_fee_is_charged_to_overdrawn_accounts_down_to_the_floor() {
+    let fee = |account_id: &str, fee: i64| BankCommand::ApplyOverdraftFee { account_id: account_id.into(), fee: Decimal::from(fee), floor: Decimal::from(-100) };
+    let mut bank = populated_bank(&["alice", "bob", "carol", "dave"]);
+    // Overdrawn as if by withdrawals past an overdraft limit, keeping the books balanced
+    for (id, overdraft) in [("bob", 30), ("carol", 95)] {
+        bank.accounts.get_mut(id).unwrap().total_debits += Decimal::from(overdraft);
+        bank.equity_capital -= Decimal::from(overdraft);
+    }
+
+    // Alice is in credit and is charged nothing; bob accrues the fee
+    let charged = bank.apply(&fee("alice", 25)).unwrap().apply(&fee("bob", 25)).unwrap();
+    assert_eq!(charged.accounts["alice"].balance(), Decimal::new(10050, 2));
+    assert_eq!(charged.accounts["bob"].balance(), Decimal::from(-55));
+    // The fee leaves the accounts for the bank's equity, keeping the double entry balanced
+    assert_eq!(charged.total_credits(), charged.total_debits() + charged.equity_capital);
+
+    // The fee may take carol further negative, but only down to the floor, and then no further
+    assert_eq!(bank.overdraft_fee_due(&"carol".into(), Decimal::from(25), Decimal::from(-100)), Some(Decimal::from(5)));
+    let floored = bank.apply(&fee("carol", 25)).unwrap();
+    assert_eq!(floored.accounts["carol"].balance(), Decimal::from(-100));
+    assert_eq!(floored.apply(&fee("carol", 25)).unwrap().accounts["carol"].balance(), Decimal::from(-100));
+
+    assert_eq!(bank.apply(&fee("bob", -1)).unwrap_err().code(), "INVALID_AMOUNT");
+    let above_zero = BankCommand::ApplyOverdraftFee { account_id: "bob".into(), fee: Decimal::from(25), floor: Decimal::from(1) };
+    assert_eq!(bank.apply(&above_zero).unwrap_err().code(), "INVALID_AMOUNT");
+    assert_eq!(bank.apply(&fee("dave", 25)).unwrap_err().code(), "ACCOUNT_CLOSED");
+}
+
+#[test]
+fn audit_trail_reads_only_the_indexed_lines_of_the_log() {
+    let log = std::env::temp_dir().join("test_audit_trail_index.json");
+    let _ = std::fs::remove_file(&log);
+    let create = |id: &str| BankCommand::CreateAccount { id: id.into(), name: id.to_string(), opening_balance: None };
+    let deposit = |id: &str, amount: i64| BankCommand::Deposit { account_id: id.into(), amount: Decimal::from(amount) };
+    let transfer = BankCommand::Transfer { from_account_id: "alice".into(), to_account_id: "bob".into(), amount: Decimal::from(4) };
+    let open = || TextFileEventStorage::new(&log, BankJsonConverter::new()).unwrap().with_event_index(bank_index_keys);
+
+    let mut processor = MemImgProcessor::new_simple(Bank::new(), Box::new(open())).unwrap();
+    for command in [create("alice"), create("bob"), deposit("alice", 10), deposit("bob", 99)] {
+        processor.execute_command(command).unwrap();
+    }
+    drop(processor);
+
+    // Reopening rebuilds the index by replay; later appends extend it
+    let mut processor = MemImgProcessor::new_simple(Bank::new(), Box::new(open())).unwrap();
+    processor.execute_command(transfer.clone()).unwrap();
+    let trail = GetAuditTrail { account_id: "alice".into() };
+    let expected = vec![create("alice"), deposit("alice", 10), transfer];
+    assert_eq!(trail.execute(&processor.event_storage).unwrap(), expected);
+    assert_eq!(processor.event_storage.indexed_events(&kind_key("Deposit")).unwrap().unwrap().len(), 2);
+
+    // Garble bob's deposit in place: the indexed lookup never reads it, while a scan fails on it
+    let text = std::fs::read_to_string(&log).unwrap();
+    let bobs_deposit = text.lines().find(|line| line.contains("Deposit") && line.contains("bob")).unwrap();
+    std::fs::write(&log, text.replace(bobs_deposit, &"x".repeat(bobs_deposit.len()))).unwrap();
+    assert_eq!(trail.execute(&processor.event_storage).unwrap(), expected);
+    let unindexed = TextFileEventStorage::new_unlocked(&log, BankJsonConverter::new()).unwrap();
+    assert!(trail.execute(&unindexed).is_err());
+    drop(processor);
+    let _ = std::fs::remove_file(&log);
+}
+
+#[test]
+fn tombstones_keep_closures_in_the_audit_trail_through_compaction() {
+    let log = std::env::temp_dir().join("test_audit_trail_tombstones.json");
+    let _ = std::fs::remove_file(&log);
+    let create = |id: &str| BankCommand::CreateAccount { id: id.into(), name: id.to_uppercase(), opening_balance: None };
+    let close = |id: &str| BankCommand::CloseAccount { id: id.into() };
+    let format = SnapshotFormat::new(1);
+
+    let storage = TextFileEventStorage::new(&log, BankJsonConverter::new()).unwrap();
+    let mut processor = MemImgProcessor::new_simple(Bank::new().with_tombstones(2), Box::new(storage)).unwrap();
+    for command in [create("alice"), create("bob"), create("carol"), create("dave"), close("bob"), close("carol"), close("dave")] {
+        processor.execute_command(command).unwrap();
+    }
+    let trail = GetAuditTrail { account_id: "dave".into() };
+    assert_eq!(trail.execute_with_tombstone(&processor.event_storage, processor.system()).unwrap(), vec![create("dave"), close("dave")]);
+
+    let mut snapshot = Vec::new();
+    processor.checkpoint_and_compact(&format, &mut snapshot).unwrap();
+    drop(processor);
+    let snapshot = format.read::<Bank, _>(&snapshot[..]).unwrap();
+    let storage = TextFileEventStorage::new(&log, BankJsonConverter::new()).unwrap();
+    let processor = MemImgProcessor::from_snapshot(snapshot, Box::new(storage)).unwrap();
+
+    // The log no longer holds the closure; the snapshot's tombstone does
+    assert!(trail.execute(&processor.event_storage).unwrap().is_empty());
+    assert_eq!(trail.execute_with_tombstone(&processor.event_storage, processor.system()).unwrap(), vec![close("dave")]);
+    assert_eq!(
+        processor.execute_query(&GetTombstone { account_id: "dave".into() }).unwrap(),
+        Some(Tombstone { name: "DAVE".to_string(), closed_at_seq: 7 })
+    );
+    // Only the latest two closures are kept; bob is still known to be closed
+    assert_eq!(processor.execute_query(&GetTombstone { account_id: "bob".into() }).unwrap(), None);
+    assert!(processor.system().closed_accounts.contains("bob"));
+    assert!(GetAuditTrail { account_id: "alice".into() }.execute_with_tombstone(&processor.event_storage, processor.system()).unwrap().is_empty());
+    drop(processor);
+    let _ = std::fs::remove_file(&log);
+}
+
+#[test]
+fn compaction_snapshots_state_that_genesis_commands_cannot_rebuild() {
+    let at = |date: &str| chrono::DateTime::parse_from_rfc3339(date).unwrap().to_utc();
+    let format = SnapshotFormat::new(1);
+    let mut processor = MemImgProcessor::new_simple(Bank::new().with_auto_create_on_deposit().with_tombstones(4), Box::new(MemoryEventStorage::new())).unwrap();
+    for command in [
+        BankCommand::CreateAccount { id: "alice".into(), name: "Alice".to_string(), opening_balance: Some(Decimal::from(100)) },
+        BankCommand::AddOwner { account_id: "alice".into(), owner: "Bob".to_string() },
+        BankCommand::ScheduleInterest {
+            account_id: "alice".into(),
+            annual_rate: Decimal::new(5, 2),
+            period: InterestPeriod::Yearly,
+            negative_balance: NegativeBalancePolicy::Charge,
+            starts_at: at("2026-01-01T00:00:00Z"),
+        },
+        BankCommand::RecordExternalPayment {
+            account_id: "alice".into(),
+            amount: Decimal::from(25),
+            gateway: "stripe".to_string(),
+            gateway_transaction_id: "ch_1".to_string(),
+            payment_method: PaymentMethod::CreditCard,
+        },
+        BankCommand::CreateAccount { id: "carol".into(), name: "Carol".to_string(), opening_balance: None },
+        BankCommand::CloseAccount { id: "carol".into() },
+    ] {
+        processor.execute_command(command).unwrap();
+    }
+    let before = serde_json::to_value(processor.system()).unwrap();
+
+    let mut snapshot = Vec::new();
+    processor.checkpoint_and_compact(&format, &mut snapshot).unwrap();
+    let snapshot = format.read::<Bank, _>(&snapshot[..]).unwrap();
+    let restored = MemImgProcessor::from_snapshot(snapshot, Box::new(MemoryEventStorage::<BankCommand>::new())).unwrap();
+
+    assert_eq!(serde_json::to_value(restored.system()).unwrap(), before);
+    let alice = &restored.system().accounts["alice"];
+    assert_eq!(alice.owners, ["Alice", "Bob"]);
+    assert_eq!(restored.system().interest_schedules["alice"].negative_balance, NegativeBalancePolicy::Charge);
+    assert!(restored.system().auto_create_on_deposit);
+    assert_eq!(restored.execute_query(&GetTombstone { account_id: "carol".into() }).unwrap().map(|tombstone| tombstone.name), Some("Carol".to_string()));
+
+    // Synthetic commands leave out the schedule, the payment id and the policy, so they are refused
+    let migrated = processor.migrate_storage(Box::new(MemoryEventStorage::new()), Bank::genesis_commands);
+    assert!(matches!(migrated, Err(MemImgError::SystemFailure(_))));
+}
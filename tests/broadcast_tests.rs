@@ -0,0 +1,51 @@
+#![cfg(all(feature = "broadcast", feature = "bank-example"))]
+
+use rmemimg::memimg::bank::{Bank, BankCommand};
+use rmemimg::memimg::{MemImgProcessor, MemoryEventStorage};
+use rust_decimal::Decimal;
+use tokio::sync::broadcast::error::RecvError;
+
+fn deposit(amount: i64) -> BankCommand {
+    BankCommand::Deposit { account_id: "alice".into(), amount: Decimal::from(amount) }
+}
+
+#[tokio::test]
+async fn every_receiver_gets_every_commit() {
+    let mut processor = MemImgProcessor::new_simple(Bank::new(), Box::new(MemoryEventStorage::new())).unwrap();
+    processor.execute_command(BankCommand::CreateAccount { id: "alice".into(), name: "Alice".to_string(), opening_balance: None }).unwrap();
+    let mut first = processor.broadcast_commits(16);
+    let mut second = first.resubscribe();
+
+    let pusher = tokio::spawn(async move {
+        let mut received = Vec::new();
+        for _ in 0..3 {
+            received.push(second.recv().await.unwrap());
+        }
+        received
+    });
+    for amount in [10, 20, 30] {
+        processor.execute_command(deposit(amount)).unwrap();
+    }
+
+    let expected = vec![deposit(10), deposit(20), deposit(30)];
+    let mut received = Vec::new();
+    for _ in 0..3 {
+        received.push(first.recv().await.unwrap());
+    }
+    assert_eq!(received, expected);
+    assert_eq!(pusher.await.unwrap(), expected);
+}
+
+#[tokio::test]
+async fn a_lagging_receiver_is_told_how_many_commits_it_missed() {
+    let mut processor = MemImgProcessor::new_simple(Bank::new(), Box::new(MemoryEventStorage::new())).unwrap();
+    let mut receiver = processor.broadcast_commits(2);
+    processor.execute_command(BankCommand::CreateAccount { id: "alice".into(), name: "Alice".to_string(), opening_balance: None }).unwrap();
+    for amount in [10, 20] {
+        processor.execute_command(deposit(amount)).unwrap();
+    }
+
+    assert_eq!(receiver.recv().await, Err(RecvError::Lagged(1)));
+    assert_eq!(receiver.recv().await.unwrap(), deposit(10));
+    assert_eq!(receiver.recv().await.unwrap(), deposit(20));
+}
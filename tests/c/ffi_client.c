@@ -0,0 +1,84 @@
+#include <stdio.h>
+#include <string.h>
+
+#include "rmemimg.h"
+
+#define CHECK(condition)                                              \
+    do {                                                              \
+        if (!(condition)) {                                           \
+            fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__, __LINE__, #condition); \
+            return 1;                                                 \
+        }                                                             \
+    } while (0)
+
+/* Drives the C API the way an embedding service would; returns 0 when every check passes */
+int rmemimg_ffi_client_run(void) {
+    BankProcessor *bank = rmemimg_bank_new();
+    CHECK(bank != NULL);
+
+    CHECK(rmemimg_bank_create_account(bank, "alice", "Alice") == 0);
+    CHECK(rmemimg_last_error() == NULL);
+    CHECK(rmemimg_bank_deposit(bank, "alice", "1,000.50") == 0);
+
+    char *balance = rmemimg_bank_get_balance(bank, "alice");
+    CHECK(balance != NULL);
+    CHECK(strcmp(balance, "1000.50") == 0);
+    rmemimg_string_free(balance);
+
+    /* Failures report through the last error */
+    CHECK(rmemimg_bank_deposit(bank, "bob", "5") == -1);
+    CHECK(strcmp(rmemimg_last_error(), "Account not found: bob") == 0);
+    CHECK(rmemimg_bank_deposit(bank, "alice", "five") == -1);
+    CHECK(strcmp(rmemimg_last_error(), "Invalid amount: \"five\"") == 0);
+    CHECK(rmemimg_bank_get_balance(bank, "bob") == NULL);
+    CHECK(strcmp(rmemimg_last_error(), "Account not found: bob") == 0);
+    CHECK(rmemimg_bank_create_account(NULL, "carol", "Carol") == -1);
+    CHECK(strcmp(rmemimg_last_error(), "bank is null") == 0);
+
+    /* A later success clears the last error */
+    CHECK(rmemimg_bank_create_account(bank, "carol", "Carol") == 0);
+    CHECK(rmemimg_last_error() == NULL);
+
+    rmemimg_bank_free(bank);
+    rmemimg_bank_free(NULL);
+    return 0;
+}
+
+/* Drives the JSON API against a file-backed bank at `path`; returns 0 when every check passes */
+int rmemimg_ffi_json_client_run(const char *path) {
+    char err[32];
+
+    BankProcessor *bank = rmemimg_bank_open(path);
+    CHECK(bank != NULL);
+    CHECK(rmemimg_bank_execute_json(bank, "{\"CreateAccount\":{\"id\":\"alice\",\"name\":\"Alice\"}}", err, sizeof err) == 0);
+    CHECK(rmemimg_bank_execute_json(bank, "{\"Deposit\":{\"account_id\":\"alice\",\"amount\":\"100\"}}", err, sizeof err) == 0);
+
+    /* Failures write the stable code to err_buf and the message to the last error */
+    CHECK(rmemimg_bank_execute_json(bank, "{\"Withdrawal\":{\"account_id\":\"alice\",\"amount\":\"500\"}}", err, sizeof err) == -1);
+    CHECK(strcmp(err, "INSUFFICIENT_FUNDS") == 0);
+    CHECK(strstr(rmemimg_last_error(), "Insufficient funds") != NULL);
+    CHECK(rmemimg_bank_execute_json(bank, "{\"Deposit\":", err, sizeof err) == -1);
+    CHECK(strcmp(err, "MALFORMED_INPUT") == 0);
+    CHECK(rmemimg_bank_execute_json(NULL, "{}", err, sizeof err) == -1);
+    CHECK(strcmp(err, "INVALID_ARGUMENT") == 0);
+
+    /* Codes are truncated to fit, and a null err_buf is allowed */
+    char small[5];
+    CHECK(rmemimg_bank_execute_json(bank, "{\"Deposit\":{\"account_id\":\"bob\",\"amount\":\"1\"}}", small, sizeof small) == -1);
+    CHECK(strcmp(small, "ACCO") == 0);
+    CHECK(rmemimg_bank_execute_json(bank, "{\"Deposit\":{\"account_id\":\"bob\",\"amount\":\"1\"}}", NULL, 0) == -1);
+    rmemimg_bank_free(bank);
+
+    /* Reopening replays the log */
+    bank = rmemimg_bank_open(path);
+    CHECK(bank != NULL);
+    char *balance = rmemimg_bank_query_json(bank, "{\"query\":\"GetBalance\",\"account_id\":\"alice\"}", err, sizeof err);
+    CHECK(balance != NULL);
+    CHECK(strcmp(balance, "\"100\"") == 0);
+    rmemimg_string_free(balance);
+
+    CHECK(rmemimg_bank_query_json(bank, "{\"query\":\"GetBalance\",\"account_id\":\"bob\"}", err, sizeof err) == NULL);
+    CHECK(strcmp(err, "ACCOUNT_NOT_FOUND") == 0);
+    rmemimg_bank_free(bank);
+    return 0;
+}
@@ -0,0 +1,224 @@
+#![cfg(all(feature = "fs", feature = "bank-example"))]
+
+use rmemimg::memimg::bank::{Bank, BankCommand};
+use rmemimg::memimg::bank_storage::BankJsonConverter;
+use rmemimg::memimg::{CommitStrategy, ConfigError, Durability, FlushPolicy, MemImgConfig, ReplayPolicy, SnapshotFormat};
+use rust_decimal::Decimal;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+fn fixture(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/config").join(name)
+}
+
+fn full_config() -> MemImgConfig {
+    MemImgConfig {
+        durability: Durability::Buffered,
+        flush_policy: FlushPolicy::EveryN(100),
+        replay_policy: ReplayPolicy::Budgeted { error_budget: 3 },
+        commit_strategy: CommitStrategy::AppendThenApply,
+        lock_timeout_ms: 500,
+        snapshot_path: Some(PathBuf::from("bank.snapshot")),
+        checkpoint_interval: Some(1000),
+    }
+}
+
+/// The keys named by an `Inconsistent` error
+fn inconsistent_keys(result: Result<MemImgConfig, ConfigError>) -> Vec<&'static str> {
+    match result {
+        Err(ConfigError::Inconsistent { keys, .. }) => keys,
+        other => panic!("expected an inconsistency, got {:?}", other),
+    }
+}
+
+#[test]
+fn loads_every_key_from_toml_and_json() {
+    let toml = MemImgConfig::from_file(fixture("full.toml")).unwrap();
+    assert_eq!(toml, full_config());
+    assert_eq!(toml.clone().validated().unwrap(), toml);
+    assert_eq!(MemImgConfig::from_file(fixture("full.json")).unwrap(), full_config());
+}
+
+#[test]
+fn missing_keys_take_the_defaults() {
+    let path = std::env::temp_dir().join("test_config_defaults.toml");
+    std::fs::write(&path, "lock_timeout_ms = 20\n").unwrap();
+    let config = MemImgConfig::from_file(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(config, MemImgConfig { lock_timeout_ms: 20, ..MemImgConfig::default() });
+}
+
+#[test]
+fn environment_overrides_file_values() {
+    let config = MemImgConfig::from_file(fixture("full.toml"))
+        .unwrap()
+        .with_overrides([
+            ("MEMIMG_FLUSH_POLICY", "interval_ms:250"),
+            ("MEMIMG_REPLAY_POLICY", "lenient"),
+            ("MEMIMG_CHECKPOINT_INTERVAL", ""),
+            ("MEMIMG_SNAPSHOT_PATH", "other.snapshot"),
+            ("PATH", "/usr/bin"),
+        ])
+        .unwrap();
+
+    assert_eq!(config.flush_policy, FlushPolicy::Interval(Duration::from_millis(250)));
+    assert_eq!(config.replay_policy, ReplayPolicy::Lenient);
+    assert_eq!(config.checkpoint_interval, None);
+    assert_eq!(config.snapshot_path, Some(PathBuf::from("other.snapshot")));
+    assert_eq!(config.durability, Durability::Buffered);
+}
+
+#[test]
+fn rejects_unparseable_values_naming_the_key() {
+    let error = MemImgConfig::default().with_overrides([("MEMIMG_FLUSH_POLICY", "every_n:lots")]).unwrap_err();
+    assert_eq!(error.to_string(), r#"Invalid MEMIMG_FLUSH_POLICY "every_n:lots": expected every_n:N with N a whole number"#);
+
+    let path = std::env::temp_dir().join("test_config_invalid.toml");
+    std::fs::write(&path, "durability = \"sometimes\"\n").unwrap();
+    let error = MemImgConfig::from_file(&path).unwrap_err();
+    std::fs::remove_file(&path).unwrap();
+    assert!(matches!(&error, ConfigError::Parse { message, .. } if message.contains("durability") && message.contains("expected every_event or buffered")));
+
+    let path = std::env::temp_dir().join("test_config_unknown.json");
+    std::fs::write(&path, r#"{"segment_size": 10}"#).unwrap();
+    let error = MemImgConfig::from_file(&path).unwrap_err();
+    std::fs::remove_file(&path).unwrap();
+    assert!(matches!(&error, ConfigError::Parse { message, .. } if message.contains("segment_size")));
+}
+
+#[test]
+fn rejects_flush_policy_with_write_through_durability() {
+    let config = MemImgConfig { durability: Durability::EveryEvent, ..full_config() };
+    assert_eq!(inconsistent_keys(config.validated()), vec!["durability", "flush_policy"]);
+
+    let config = MemImgConfig { durability: Durability::EveryEvent, flush_policy: FlushPolicy::Manual, ..full_config() };
+    assert!(config.validated().is_ok());
+}
+
+#[test]
+fn rejects_checkpointing_without_snapshot_path() {
+    let config = MemImgConfig { snapshot_path: None, ..full_config() };
+    let error = config.validated().unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Inconsistent checkpoint_interval and snapshot_path: checkpointing is enabled but there is no snapshot_path to write to"
+    );
+
+    let config = MemImgConfig { checkpoint_interval: Some(0), ..full_config() };
+    assert!(matches!(config.validated(), Err(ConfigError::InvalidValue { key, .. }) if key == "checkpoint_interval"));
+}
+
+#[test]
+fn configures_storage_and_checkpoints_when_due() {
+    let log = std::env::temp_dir().join("test_config_checkpoint.json");
+    let snapshot = std::env::temp_dir().join("test_config_checkpoint.snapshot");
+    let _ = std::fs::remove_file(&log);
+    let _ = std::fs::remove_file(&snapshot);
+    let config = MemImgConfig { snapshot_path: Some(snapshot.clone()), checkpoint_interval: Some(2), ..full_config() };
+    let format = SnapshotFormat::new(1);
+
+    let storage = Box::new(config.open_storage(&log, BankJsonConverter::new()).unwrap());
+    let (mut processor, _) = config.open_processor(Bank::new(), storage, &format).unwrap();
+    processor.execute_command(BankCommand::CreateAccount { id: "alice".into(), name: "Alice".to_string(), opening_balance: Some(Decimal::from(10)) }).unwrap();
+    assert!(config.checkpoint_if_due(&mut processor, &format).unwrap().is_none());
+    processor.execute_command(BankCommand::Deposit { account_id: "alice".into(), amount: Decimal::from(5) }).unwrap();
+    assert_eq!(config.checkpoint_if_due(&mut processor, &format).unwrap().unwrap().events_removed, 2);
+    processor.execute_command(BankCommand::Deposit { account_id: "alice".into(), amount: Decimal::from(1) }).unwrap();
+    drop(processor);
+
+    // Reopening resumes from the snapshot and replays the one event logged after it
+    let storage = Box::new(config.open_storage(&log, BankJsonConverter::new()).unwrap());
+    let (processor, metrics) = config.open_processor(Bank::new(), storage, &format).unwrap();
+    assert_eq!(metrics.events_replayed, 1);
+    assert_eq!(processor.system().accounts["alice"].balance(), Decimal::from(16));
+    assert_eq!(processor.events_since_snapshot(), 1);
+    drop(processor);
+    let _ = std::fs::remove_file(&log);
+    let _ = std::fs::remove_file(&snapshot);
+}
+
+/// Paths for a checkpointing test, cleared of any earlier run's files
+fn checkpoint_paths(name: &str) -> (PathBuf, PathBuf) {
+    let log = std::env::temp_dir().join(format!("{}.json", name));
+    let snapshot = std::env::temp_dir().join(format!("{}.snapshot", name));
+    for suffix in ["", ".tmp", ".compacting"] {
+        let _ = std::fs::remove_file(format!("{}{}", snapshot.display(), suffix));
+    }
+    let _ = std::fs::remove_file(&log);
+    (log, snapshot)
+}
+
+fn deposit(amount: i64) -> BankCommand {
+    BankCommand::Deposit { account_id: "alice".into(), amount: Decimal::from(amount) }
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn failed_snapshot_write_leaves_the_old_snapshot_and_log_to_replay() {
+    let (log, snapshot) = checkpoint_paths("test_config_failed_snapshot");
+    let config = MemImgConfig { snapshot_path: Some(snapshot.clone()), checkpoint_interval: Some(2), ..full_config() };
+    let format = SnapshotFormat::new(1);
+
+    let storage = Box::new(config.open_storage(&log, BankJsonConverter::new()).unwrap());
+    let (mut processor, _) = config.open_processor(Bank::new(), storage, &format).unwrap();
+    processor.execute_command(BankCommand::CreateAccount { id: "alice".into(), name: "Alice".to_string(), opening_balance: None }).unwrap();
+    processor.execute_command(deposit(10)).unwrap();
+    config.checkpoint_if_due(&mut processor, &format).unwrap().unwrap();
+    let old_snapshot = std::fs::read(&snapshot).unwrap();
+    processor.execute_command(deposit(5)).unwrap();
+    processor.execute_command(deposit(1)).unwrap();
+
+    // The temporary snapshot lands on a full device, so writing it fails
+    let temp = PathBuf::from(format!("{}.tmp", snapshot.display()));
+    std::os::unix::fs::symlink("/dev/full", &temp).unwrap();
+    assert!(config.checkpoint_if_due(&mut processor, &format).is_err());
+    assert_eq!(std::fs::read(&snapshot).unwrap(), old_snapshot);
+    assert!(std::fs::symlink_metadata(&temp).is_err());
+    drop(processor);
+    assert_eq!(std::fs::read_to_string(&log).unwrap().lines().count(), 2);
+
+    let storage = Box::new(config.open_storage(&log, BankJsonConverter::new()).unwrap());
+    let (processor, metrics) = config.open_processor(Bank::new(), storage, &format).unwrap();
+    assert_eq!(metrics.events_replayed, 2);
+    assert_eq!(processor.system().accounts["alice"].balance(), Decimal::from(16));
+    drop(processor);
+    let _ = checkpoint_paths("test_config_failed_snapshot");
+}
+
+#[test]
+fn reopening_finishes_a_checkpoint_interrupted_before_compaction() {
+    let (log, snapshot) = checkpoint_paths("test_config_interrupted_checkpoint");
+    let config = MemImgConfig { snapshot_path: Some(snapshot.clone()), checkpoint_interval: Some(2), ..full_config() };
+    let format = SnapshotFormat::new(1);
+
+    let storage = Box::new(config.open_storage(&log, BankJsonConverter::new()).unwrap());
+    let (mut processor, _) = config.open_processor(Bank::new(), storage, &format).unwrap();
+    processor.execute_command(BankCommand::CreateAccount { id: "alice".into(), name: "Alice".to_string(), opening_balance: None }).unwrap();
+    processor.execute_command(deposit(10)).unwrap();
+    drop(processor);
+
+    // A read-only log refuses compaction, stopping the checkpoint after its snapshot is in place
+    let storage = Box::new(config.apply(rmemimg::memimg::TextFileEventStorage::builder(&log, BankJsonConverter::new()).read_only().open().unwrap()));
+    let (mut processor, _) = config.open_processor(Bank::new(), storage, &format).unwrap();
+    assert!(config.checkpoint_if_due(&mut processor, &format).is_err());
+    drop(processor);
+    assert!(snapshot.exists());
+    assert_eq!(std::fs::read_to_string(&log).unwrap().lines().count(), 2);
+
+    // The events the snapshot covers are dropped rather than replayed on top of it
+    let storage = Box::new(config.open_storage(&log, BankJsonConverter::new()).unwrap());
+    let (mut processor, metrics) = config.open_processor(Bank::new(), storage, &format).unwrap();
+    assert_eq!(metrics.events_replayed, 0);
+    assert_eq!(processor.system().accounts["alice"].balance(), Decimal::from(10));
+    assert!(!PathBuf::from(format!("{}.compacting", snapshot.display())).exists());
+    processor.execute_command(deposit(1)).unwrap();
+    drop(processor);
+
+    let storage = Box::new(config.open_storage(&log, BankJsonConverter::new()).unwrap());
+    let (processor, metrics) = config.open_processor(Bank::new(), storage, &format).unwrap();
+    assert_eq!(metrics.events_replayed, 1);
+    assert_eq!(processor.system().accounts["alice"].balance(), Decimal::from(11));
+    drop(processor);
+    let _ = checkpoint_paths("test_config_interrupted_checkpoint");
+}
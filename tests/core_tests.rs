@@ -0,0 +1,181 @@
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use rmemimg::memimg::{
+    Command, EventId, EventStorage, JsonConverter, JsonEvent, MemImgError, MemImgProcessor, MemoryEventStorage, Query, SnapshotFormat, StorageError,
+    StorageOp, TextConverter, TextFileEventStorage,
+};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+
+// Minimal domain with no dependency on the bank example
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+struct Counter {
+    value: i64,
+}
+
+#[derive(Debug)]
+struct Underflow;
+
+impl std::fmt::Display for Underflow {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "counter would go below zero")
+    }
+}
+
+impl std::error::Error for Underflow {}
+
+impl Counter {
+    fn apply_add(&mut self, amount: &i64) -> Result<(), Underflow> {
+        if self.value + amount < 0 {
+            return Err(Underflow);
+        }
+        self.value += amount;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Command)]
+#[command(system = "Counter")]
+enum CounterCommand {
+    #[command(handler = "apply_add")]
+    Add(i64),
+}
+
+impl JsonEvent for CounterCommand {}
+
+#[derive(Debug)]
+struct CurrentValue;
+
+impl Query for CurrentValue {
+    type System = Counter;
+    type Result = i64;
+
+    fn extract_from(&self, system: &Counter) -> Result<i64, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(system.value)
+    }
+}
+
+#[test]
+fn applies_commands_and_answers_queries() {
+    let mut processor = MemImgProcessor::new_simple(Counter::default(), Box::new(MemoryEventStorage::new())).unwrap();
+
+    processor.execute_command(CounterCommand::Add(5)).unwrap();
+    processor.execute_command(CounterCommand::Add(-2)).unwrap();
+
+    assert_eq!(processor.execute_query(&CurrentValue).unwrap(), 3);
+    assert_eq!(processor.event_version(), EventId(2));
+    assert_eq!(processor.event_storage.events(), &[CounterCommand::Add(5), CounterCommand::Add(-2)][..]);
+}
+
+#[test]
+fn rejected_command_leaves_state_and_log_untouched() {
+    let mut processor = MemImgProcessor::new_simple(Counter::default(), Box::new(MemoryEventStorage::new())).unwrap();
+    processor.execute_command(CounterCommand::Add(1)).unwrap();
+
+    let result = processor.execute_command(CounterCommand::Add(-5));
+
+    assert!(matches!(result, Err(MemImgError::CommandFailure(_))));
+    assert_eq!(processor.system().value, 1);
+    assert_eq!(processor.event_storage.events().len(), 1);
+}
+
+#[test]
+fn replays_json_log_on_restart() {
+    let test_file = std::env::temp_dir().join("test_core_counter_events.json");
+    let _ = std::fs::remove_file(&test_file);
+
+    {
+        let mut processor = MemImgProcessor::new_simple(Counter::default(), Box::new(TextFileEventStorage::json(&test_file).unwrap())).unwrap();
+        processor.execute_command(CounterCommand::Add(10)).unwrap();
+        processor.execute_command(CounterCommand::Add(-4)).unwrap();
+    }
+
+    let storage = TextFileEventStorage::<CounterCommand, _>::json(&test_file).unwrap();
+    let processor = MemImgProcessor::new_simple(Counter::default(), Box::new(storage)).unwrap();
+    assert_eq!(processor.system().value, 6);
+    assert_eq!(processor.event_version(), EventId(2));
+
+    let _ = std::fs::remove_file(&test_file);
+}
+
+// Neither a `JsonEvent` nor given a converter of its own
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Note {
+    text: String,
+}
+
+#[test]
+fn json_converter_stores_any_serde_type_one_line_per_record() {
+    let converter = JsonConverter::<Note>::new();
+    // serde_json escapes the line break inside the string, so the record keeps to one line
+    let note = Note { text: "two\nlines".to_string() };
+    let line = converter.format(&note).unwrap();
+    assert_eq!(line, r#"{"text":"two\nlines"}"#);
+    assert_eq!(converter.parse(&line).unwrap(), note);
+
+    let test_file = std::env::temp_dir().join("test_core_json_converter_notes.json");
+    let _ = std::fs::remove_file(&test_file);
+    {
+        let mut storage = TextFileEventStorage::new(&test_file, converter).unwrap();
+        storage.append(&note).unwrap();
+        storage.append(&Note { text: "three".to_string() }).unwrap();
+    }
+    let mut storage = TextFileEventStorage::new(&test_file, JsonConverter::<Note>::new()).unwrap();
+    let mut notes = Vec::new();
+    storage.replay(&mut |note| {
+        notes.push(note);
+        Ok(())
+    }).unwrap();
+    assert_eq!(notes, [note, Note { text: "three".to_string() }]);
+
+    let _ = std::fs::remove_file(&test_file);
+}
+
+#[test]
+fn resumes_from_snapshot_after_compaction() {
+    let format = SnapshotFormat::new(1);
+    let mut processor = MemImgProcessor::new_simple(Counter::default(), Box::new(MemoryEventStorage::new())).unwrap();
+    processor.execute_command(CounterCommand::Add(7)).unwrap();
+
+    let mut snapshot = Vec::new();
+    processor.checkpoint_and_compact(&format, &mut snapshot).unwrap();
+    processor.execute_command(CounterCommand::Add(1)).unwrap();
+
+    let events = processor.event_storage.events().to_vec();
+    let snapshot = format.read::<Counter, _>(&snapshot[..]).unwrap();
+    let restored = MemImgProcessor::from_snapshot(snapshot, Box::new(MemoryEventStorage::with_events(events))).unwrap();
+
+    assert_eq!(restored.system(), processor.system());
+    assert_eq!(restored.event_version(), EventId(2));
+}
+
+#[test]
+fn replays_gzipped_archive_then_plain_segment() {
+    let archive_file = std::env::temp_dir().join("test_core_counter_archive.json.gz");
+    let live_file = std::env::temp_dir().join("test_core_counter_live.json");
+    let _ = std::fs::remove_file(&live_file);
+
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    for event in [CounterCommand::Add(3), CounterCommand::Add(4)] {
+        writeln!(encoder, "{}", serde_json::to_string(&event).unwrap()).unwrap();
+    }
+    std::fs::write(&archive_file, encoder.finish().unwrap()).unwrap();
+    std::fs::write(&live_file, "{\"Add\":-2}\n").unwrap();
+
+    let mut counter = Counter::default();
+    let mut archive = TextFileEventStorage::<CounterCommand, _>::json(&archive_file).unwrap();
+    archive.replay(&mut |event: CounterCommand| event.apply_to(&mut counter)).unwrap();
+
+    let live = TextFileEventStorage::<CounterCommand, _>::json(&live_file).unwrap();
+    let mut processor = MemImgProcessor::new_simple(counter, Box::new(live)).unwrap();
+    assert_eq!(processor.system().value, 5);
+
+    processor.execute_command(CounterCommand::Add(1)).unwrap();
+    assert_eq!(std::fs::read_to_string(&live_file).unwrap().lines().count(), 2);
+
+    let error = archive.append(&CounterCommand::Add(1)).unwrap_err();
+    assert_eq!(error.downcast_ref::<StorageError>().unwrap().op, StorageOp::Append);
+
+    let _ = std::fs::remove_file(&archive_file);
+    let _ = std::fs::remove_file(&live_file);
+}
@@ -0,0 +1,88 @@
+#![cfg(feature = "bank-example")]
+
+use rmemimg::memimg::bank::{Bank, BankCommand};
+use rmemimg::memimg::bank_storage::BankJsonConverter;
+use rmemimg::memimg::{HkdfEncryptedStorage, MemImgProcessor};
+use rust_decimal::Decimal;
+
+const MASTER_KEY: [u8; 32] = [7u8; 32];
+
+fn deposit(amount: i64) -> BankCommand {
+    BankCommand::Deposit {
+        account_id: "acc1".into(),
+        amount: Decimal::new(amount, 0),
+    }
+}
+
+fn populate(path: &std::path::Path) {
+    let storage = Box::new(HkdfEncryptedStorage::new(path, BankJsonConverter::new(), MASTER_KEY).unwrap());
+    let mut processor = MemImgProcessor::new_simple(Bank::new(), storage).unwrap();
+    processor
+        .execute_command(BankCommand::CreateAccount {
+            id: "acc1".into(),
+            name: "Alice".to_string(),
+            opening_balance: None,
+        })
+        .unwrap();
+    processor.execute_command(deposit(100)).unwrap();
+    processor.execute_command(deposit(100)).unwrap();
+}
+
+#[test]
+fn decrypts_with_correct_key() {
+    let test_file = std::env::temp_dir().join("test_hkdf_correct_key.log");
+    let _ = std::fs::remove_file(&test_file);
+    populate(&test_file);
+
+    let raw = std::fs::read_to_string(&test_file).unwrap();
+    assert!(!raw.contains("Alice"));
+    assert!(raw.lines().all(|line| line.split('.').count() == 2));
+
+    let storage = Box::new(HkdfEncryptedStorage::new(&test_file, BankJsonConverter::new(), MASTER_KEY).unwrap());
+    let mut processor = MemImgProcessor::new_simple(Bank::new(), storage).unwrap();
+    assert_eq!(
+        processor.system().accounts.get("acc1").unwrap().balance(),
+        Decimal::new(200, 0)
+    );
+
+    // Appends after reopening continue the sequence and stay decryptable
+    processor.execute_command(deposit(50)).unwrap();
+    drop(processor);
+    let storage = Box::new(HkdfEncryptedStorage::new(&test_file, BankJsonConverter::new(), MASTER_KEY).unwrap());
+    let processor = MemImgProcessor::new_simple(Bank::new(), storage).unwrap();
+    assert_eq!(
+        processor.system().accounts.get("acc1").unwrap().balance(),
+        Decimal::new(250, 0)
+    );
+
+    let _ = std::fs::remove_file(&test_file);
+}
+
+#[test]
+fn fails_with_wrong_key() {
+    let test_file = std::env::temp_dir().join("test_hkdf_wrong_key.log");
+    let _ = std::fs::remove_file(&test_file);
+    populate(&test_file);
+
+    let storage = Box::new(HkdfEncryptedStorage::new(&test_file, BankJsonConverter::new(), [8u8; 32]).unwrap());
+    let error = MemImgProcessor::new_simple(Bank::new(), storage).err().unwrap();
+    assert!(error.to_string().contains("Decryption failed for event 1"));
+
+    let _ = std::fs::remove_file(&test_file);
+}
+
+#[test]
+fn same_plaintext_yields_different_ciphertexts() {
+    let test_file = std::env::temp_dir().join("test_hkdf_distinct_ciphertexts.log");
+    let _ = std::fs::remove_file(&test_file);
+    populate(&test_file);
+
+    // Events 2 and 3 are identical deposits
+    let raw = std::fs::read_to_string(&test_file).unwrap();
+    let lines: Vec<&str> = raw.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert_ne!(lines[1], lines[2]);
+    assert_ne!(lines[1].split('.').nth(1), lines[2].split('.').nth(1));
+
+    let _ = std::fs::remove_file(&test_file);
+}
@@ -0,0 +1,46 @@
+use std::ffi::{c_char, c_int, CString};
+
+// Nothing here names the crate, so link it explicitly for the C client and the FFI symbols
+extern crate rmemimg;
+
+// Compiled from tests/c/ffi_client.c by build.rs
+extern "C" {
+    fn rmemimg_ffi_client_run() -> c_int;
+    fn rmemimg_ffi_json_client_run(path: *const c_char) -> c_int;
+}
+
+#[test]
+fn c_client_drives_the_ffi() {
+    assert_eq!(unsafe { rmemimg_ffi_client_run() }, 0);
+}
+
+#[test]
+fn c_client_drives_the_json_ffi_over_a_file_log() {
+    let test_file = std::env::temp_dir().join("test_ffi_json_events.json");
+    let _ = std::fs::remove_file(&test_file);
+    let path = CString::new(test_file.to_str().unwrap()).unwrap();
+
+    assert_eq!(unsafe { rmemimg_ffi_json_client_run(path.as_ptr()) }, 0);
+
+    let _ = std::fs::remove_file(&test_file);
+}
+
+#[test]
+fn header_matches_cbindgen_output() {
+    let root = std::path::Path::new(env!("CARGO_MANIFEST_DIR"));
+    let config = cbindgen::Config::from_file(root.join("cbindgen.toml")).unwrap();
+    let mut generated = Vec::new();
+    cbindgen::Builder::new()
+        .with_config(config)
+        .with_src(root.join("src/ffi.rs"))
+        .generate()
+        .unwrap()
+        .write(&mut generated);
+
+    let committed = std::fs::read_to_string(root.join("include/rmemimg.h")).unwrap();
+    assert_eq!(
+        committed,
+        String::from_utf8(generated).unwrap(),
+        "include/rmemimg.h is stale; regenerate it with the command in cbindgen.toml"
+    );
+}
@@ -0,0 +1,62 @@
+#![cfg(all(feature = "fs", feature = "bank-example"))]
+
+use rmemimg::memimg::bank::{Bank, BankCommand};
+use rmemimg::memimg::bank_storage::BankJsonConverter;
+use rmemimg::memimg::{FileSnapshotter, MemImgProcessor, RetentionPolicy, SnapshotFormat, TextFileEventStorage};
+use rust_decimal::Decimal;
+use std::path::Path;
+use std::time::Duration;
+
+fn file_names(paths: &[impl AsRef<Path>]) -> Vec<String> {
+    paths.iter().map(|path| path.as_ref().file_name().unwrap().to_string_lossy().to_string()).collect()
+}
+
+/// Take five snapshots through `snapshotter` with a deposit before each, returning the last one's path
+fn take_five_snapshots(name: &str, snapshotter: &FileSnapshotter) -> std::path::PathBuf {
+    let log = std::env::temp_dir().join(format!("{}.json", name));
+    let _ = std::fs::remove_file(&log);
+    let storage = Box::new(TextFileEventStorage::new(&log, BankJsonConverter::new()).unwrap());
+    let mut processor = MemImgProcessor::new_simple(Bank::new(), storage).unwrap();
+    processor.execute_command(BankCommand::CreateAccount { id: "alice".into(), name: "Alice".to_string(), opening_balance: None }).unwrap();
+    let mut last = None;
+    for _ in 0..5 {
+        processor.execute_command(BankCommand::Deposit { account_id: "alice".into(), amount: Decimal::from(10) }).unwrap();
+        last = Some(snapshotter.take(&mut processor).unwrap().0);
+    }
+    drop(processor);
+    let _ = std::fs::remove_file(&log);
+    last.unwrap()
+}
+
+#[test]
+fn retention_keeps_only_the_most_recent_snapshots() {
+    let directory = std::env::temp_dir().join("test_snapshot_retention");
+    let _ = std::fs::remove_dir_all(&directory);
+    let snapshotter = FileSnapshotter::new(&directory, SnapshotFormat::new(1)).with_retention(RetentionPolicy::KeepLast(2));
+
+    take_five_snapshots("test_snapshot_retention", &snapshotter);
+
+    assert_eq!(
+        file_names(&snapshotter.snapshots().unwrap()),
+        vec!["snapshot-00000000000000000005.json", "snapshot-00000000000000000006.json"]
+    );
+    let latest = snapshotter.load_latest::<Bank>().unwrap().unwrap();
+    assert_eq!(latest.event_count, 6);
+    assert_eq!(latest.state.accounts["alice"].balance(), Decimal::from(50));
+    let _ = std::fs::remove_dir_all(&directory);
+}
+
+#[test]
+fn retention_never_prunes_the_newest_snapshot() {
+    let directory = std::env::temp_dir().join("test_snapshot_retention_newest");
+    let _ = std::fs::remove_dir_all(&directory);
+    let snapshotter = FileSnapshotter::new(&directory, SnapshotFormat::new(1)).with_retention(RetentionPolicy::KeepNewerThan(Duration::ZERO));
+
+    let last = take_five_snapshots("test_snapshot_retention_newest", &snapshotter);
+
+    assert_eq!(snapshotter.snapshots().unwrap(), vec![last]);
+    let snapshotter = FileSnapshotter::new(&directory, SnapshotFormat::new(1)).with_retention(RetentionPolicy::KeepLast(0));
+    assert!(snapshotter.prune().unwrap().is_empty());
+    assert_eq!(snapshotter.snapshots().unwrap().len(), 1);
+    let _ = std::fs::remove_dir_all(&directory);
+}
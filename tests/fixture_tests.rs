@@ -0,0 +1,58 @@
+#![cfg(feature = "test-util")]
+
+use rmemimg::memimg::bank::{Bank, BankCommand, GetBalance};
+use rmemimg::memimg::testing::{assert_state_matches_json, fixture_path, fixture_storage};
+use rmemimg::memimg::MemImgProcessor;
+use rust_decimal::Decimal;
+use std::panic::AssertUnwindSafe;
+
+#[test]
+fn current_format_log_replays_to_golden_state() {
+    let processor = MemImgProcessor::new_simple(Bank::new(), Box::new(fixture_storage("bank/current_events.json"))).unwrap();
+
+    assert_eq!(processor.event_version().as_u64(), 9);
+    assert_state_matches_json(&processor, "bank/current_state.json");
+}
+
+#[test]
+fn legacy_format_log_replays_to_golden_state() {
+    let processor = MemImgProcessor::new_simple(Bank::new(), Box::new(fixture_storage("bank/legacy_events.json"))).unwrap();
+
+    assert_eq!(processor.execute_query(&GetBalance { account_id: "acc2".into() }).unwrap(), Decimal::new(47525, 2));
+    assert_state_matches_json(&processor, "bank/legacy_state.json");
+}
+
+#[test]
+fn appending_to_a_fixture_leaves_the_checked_in_log_untouched() {
+    let original = std::fs::read_to_string(fixture_path("bank/legacy_events.json")).unwrap();
+
+    let mut processor = MemImgProcessor::new_simple(Bank::new(), Box::new(fixture_storage("bank/legacy_events.json"))).unwrap();
+    processor.execute_command(BankCommand::Deposit { account_id: "acc1".into(), amount: Decimal::from(1) }).unwrap();
+
+    assert_eq!(std::fs::read_to_string(fixture_path("bank/legacy_events.json")).unwrap(), original);
+    let reopened = MemImgProcessor::new_simple(Bank::new(), Box::new(fixture_storage("bank/legacy_events.json"))).unwrap();
+    assert_eq!(reopened.event_version().as_u64(), 6);
+}
+
+#[test]
+fn golden_mismatch_shows_the_differing_lines() {
+    let mut processor = MemImgProcessor::new_simple(Bank::new(), Box::new(fixture_storage("bank/legacy_events.json"))).unwrap();
+    processor.execute_command(BankCommand::Deposit { account_id: "acc1".into(), amount: Decimal::from(1) }).unwrap();
+
+    let panic = std::panic::catch_unwind(AssertUnwindSafe(|| assert_state_matches_json(&processor, "bank/legacy_state.json"))).unwrap_err();
+    let message = panic.downcast_ref::<String>().unwrap();
+
+    assert!(message.contains("RMEMIMG_BLESS=1"), "{}", message);
+    assert!(message.contains(concat!("line 7:\n", r#"-       "total_credits": "1000.00","#, "\n", r#"+       "total_credits": "1001.00","#)), "{}", message);
+}
+
+#[test]
+fn export_state_matches_golden_dump() {
+    let mut processor = MemImgProcessor::new_simple(Bank::new(), Box::new(fixture_storage("bank/current_events.json"))).unwrap();
+    processor.execute_command(BankCommand::Deposit { account_id: "carol".into(), amount: Decimal::new(1250, 2) }).unwrap();
+
+    let mut dump = Vec::new();
+    processor.export_state(&mut dump).unwrap();
+
+    assert_eq!(String::from_utf8(dump).unwrap(), std::fs::read_to_string(fixture_path("bank/current_export.json")).unwrap());
+}
@@ -0,0 +1,232 @@
+use axum::body::{to_bytes, Body};
+use axum::http::{header, Request, StatusCode};
+use axum::Router;
+use rmemimg::memimg::bank::{Bank, BankCommand, ListAccounts};
+use rmemimg::memimg::bank_http::{router, serve};
+use rmemimg::memimg::bank_invariants::DoubleEntryValidator;
+use rmemimg::memimg::bank_storage::BankJsonConverter;
+use rmemimg::memimg::{MemImgProcessor, MemoryEventStorage, SnapshotFormat, TextConverter, TextFileEventStorage};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tower::ServiceExt;
+
+fn app() -> Router {
+    let storage = Box::new(MemoryEventStorage::new());
+    let processor = MemImgProcessor::new_simple(Bank::new(), storage).unwrap().with_validator(DoubleEntryValidator);
+    router(Arc::new(Mutex::new(processor)))
+}
+
+async fn send(app: &Router, request: Request<Body>) -> (StatusCode, Value) {
+    let response = app.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+}
+
+async fn post_command(app: &Router, command: Value) -> (StatusCode, Value) {
+    let request = Request::post("/commands")
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(command.to_string()))
+        .unwrap();
+    send(app, request).await
+}
+
+async fn get(app: &Router, uri: &str) -> (StatusCode, Value) {
+    send(app, Request::get(uri).body(Body::empty()).unwrap()).await
+}
+
+#[tokio::test]
+async fn executes_commands_and_serves_queries() {
+    let app = app();
+    assert_eq!(
+        post_command(&app, json!({"CreateAccount": {"id": "alice", "name": "Alice"}})).await,
+        (StatusCode::OK, json!({"seq": 1}))
+    );
+    post_command(&app, json!({"Deposit": {"account_id": "alice", "amount": "25"}})).await;
+
+    assert_eq!(
+        get(&app, "/accounts").await,
+        (StatusCode::OK, json!([{"id": "alice", "name": "Alice", "balance": "25"}]))
+    );
+    assert_eq!(
+        get(&app, "/accounts/alice").await,
+        (StatusCode::OK, json!({"id": "alice", "name": "Alice", "balance": "25"}))
+    );
+    assert_eq!(
+        get(&app, "/accounts/alice/balance").await,
+        (StatusCode::OK, json!({"account_id": "alice", "balance": "25"}))
+    );
+}
+
+#[tokio::test]
+async fn maps_errors_to_status_codes() {
+    let app = app();
+    post_command(&app, json!({"CreateAccount": {"id": "alice", "name": "Alice"}})).await;
+    post_command(&app, json!({"CreateAccount": {"id": "gone", "name": "Gone"}})).await;
+    post_command(&app, json!({"CloseAccount": {"id": "gone"}})).await;
+
+    let (status, body) = get(&app, "/accounts/nobody").await;
+    assert_eq!((status, &body["code"]), (StatusCode::NOT_FOUND, &json!("ACCOUNT_NOT_FOUND")));
+
+    let (status, body) = get(&app, "/accounts/nobody/balance").await;
+    assert_eq!((status, &body["code"]), (StatusCode::NOT_FOUND, &json!("ACCOUNT_NOT_FOUND")));
+
+    let (status, body) = get(&app, "/accounts/gone").await;
+    assert_eq!((status, &body["code"]), (StatusCode::GONE, &json!("ACCOUNT_CLOSED")));
+
+    let (status, body) = post_command(&app, json!({"Withdrawal": {"account_id": "alice", "amount": "5"}})).await;
+    assert_eq!((status, &body["code"]), (StatusCode::CONFLICT, &json!("INSUFFICIENT_FUNDS")));
+    assert_eq!(body["message"], json!("Insufficient funds: 0 < 5"));
+
+    let (status, body) = post_command(&app, json!({"BulkCreateAccounts": {"accounts": [["alice", "Again"]]}})).await;
+    assert_eq!((status, &body["code"]), (StatusCode::CONFLICT, &json!("DUPLICATE_ACCOUNT")));
+
+    // Malformed bodies are rejected by the JSON extractor before reaching the processor
+    let (status, _) = post_command(&app, json!({"Teleport": {}})).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+#[tokio::test]
+async fn if_match_rejects_stale_versions() {
+    let app = app();
+    post_command(&app, json!({"CreateAccount": {"id": "alice", "name": "Alice"}})).await;
+
+    let conditional = |version: &str| {
+        Request::post("/commands")
+            .header(header::CONTENT_TYPE, "application/json")
+            .header(header::IF_MATCH, version)
+            .body(Body::from(json!({"Deposit": {"account_id": "alice", "amount": "1"}}).to_string()))
+            .unwrap()
+    };
+
+    let (status, body) = send(&app, conditional("\"0\"")).await;
+    assert_eq!((status, &body["code"]), (StatusCode::CONFLICT, &json!("VERSION_CONFLICT")));
+
+    let (status, body) = send(&app, conditional("\"1\"")).await;
+    assert_eq!((status, body), (StatusCode::OK, json!({"seq": 2})));
+}
+
+#[tokio::test]
+async fn graceful_shutdown_flushes_storage() {
+    let test_file = std::env::temp_dir().join("test_http_shutdown.json");
+    let _ = std::fs::remove_file(&test_file);
+
+    let storage = Box::new(TextFileEventStorage::new(&test_file, BankJsonConverter::new()).unwrap());
+    let processor = Arc::new(Mutex::new(MemImgProcessor::new_simple(Bank::new(), storage).unwrap()));
+    processor
+        .lock()
+        .await
+        .execute_command(BankCommand::CreateAccount { id: "alice".into(), name: "Alice".to_string(), opening_balance: None })
+        .unwrap();
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    serve(listener, processor, async {}).await.unwrap();
+
+    let contents = std::fs::read_to_string(&test_file).unwrap();
+    assert_eq!(contents.lines().count(), 1);
+
+    let _ = std::fs::remove_file(&test_file);
+}
+
+#[tokio::test]
+async fn serves_an_openapi_document_matching_the_error_mapping() {
+    let (status, doc) = get(&app(), "/openapi.json").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(doc["openapi"].as_str().unwrap().starts_with("3."));
+
+    let post = &doc["paths"]["/commands"]["post"];
+    assert_eq!(post["requestBody"]["content"]["application/json"]["schema"]["$ref"], "#/components/schemas/BankCommand");
+    let deposit = doc["components"]["schemas"]["BankCommand"]["oneOf"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find_map(|variant| variant["properties"].get("Deposit"))
+        .unwrap();
+    assert_eq!(deposit["properties"]["amount"]["type"], "string");
+    assert_eq!(deposit["required"], json!(["account_id", "amount"]));
+
+    assert_eq!(post["responses"]["409"]["$ref"], "#/components/responses/Conflict");
+    let conflict = &doc["components"]["responses"]["Conflict"];
+    assert_eq!(conflict["content"]["application/json"]["schema"]["$ref"], "#/components/schemas/ErrorBody");
+    let description = conflict["description"].as_str().unwrap();
+    assert!(description.contains("INSUFFICIENT_FUNDS") && description.contains("VERSION_CONFLICT"));
+}
+
+#[tokio::test]
+async fn exported_events_replay_into_a_fresh_processor() {
+    let app = app();
+    post_command(&app, json!({"CreateAccount": {"id": "alice", "name": "Alice"}})).await;
+    post_command(&app, json!({"CreateAccount": {"id": "bob", "name": "Bob"}})).await;
+    post_command(&app, json!({"Deposit": {"account_id": "alice", "amount": "25"}})).await;
+    post_command(&app, json!({"Transfer": {"from_account_id": "alice", "to_account_id": "bob", "amount": "10"}})).await;
+
+    let export = |from_seq: u64| {
+        let app = app.clone();
+        async move {
+            let request = Request::get(format!("/events?from_seq={}", from_seq)).body(Body::empty()).unwrap();
+            let response = app.oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(response.headers()[header::CONTENT_TYPE], "application/x-ndjson");
+            String::from_utf8(to_bytes(response.into_body(), usize::MAX).await.unwrap().to_vec()).unwrap()
+        }
+    };
+
+    let log = export(0).await;
+    assert_eq!(log.lines().count(), 4);
+    let mut follower = MemImgProcessor::new_simple(Bank::new(), Box::new(MemoryEventStorage::new())).unwrap();
+    for line in log.lines() {
+        follower.execute_command(BankJsonConverter::new().parse(line).unwrap()).unwrap();
+    }
+    let accounts = follower.execute_query(&ListAccounts).unwrap();
+    let balances: Vec<_> = accounts.iter().map(|account| (account.id.to_string(), account.balance().to_string())).collect();
+    assert_eq!(balances, [("alice".to_string(), "15".to_string()), ("bob".to_string(), "10".to_string())]);
+
+    // A follower that already holds the first events only pulls the rest
+    assert_eq!(export(2).await.lines().collect::<Vec<_>>(), log.lines().skip(2).collect::<Vec<_>>());
+    assert_eq!(export(4).await, "");
+}
+
+#[tokio::test]
+async fn exports_seqs_past_a_compaction_in_resumed_chunks() {
+    let test_file = std::env::temp_dir().join("test_http_export_compacted.json");
+    let _ = std::fs::remove_file(&test_file);
+
+    let storage = Box::new(TextFileEventStorage::new(&test_file, BankJsonConverter::new()).unwrap());
+    let mut processor = MemImgProcessor::new_simple(Bank::new(), storage).unwrap();
+    for id in ["alice", "bob"] {
+        processor.execute_command(BankCommand::CreateAccount { id: id.into(), name: id.to_string(), opening_balance: None }).unwrap();
+    }
+    processor.checkpoint_and_compact(&SnapshotFormat::new(1), Vec::new()).unwrap();
+    // More events than one chunk holds, so the export resumes mid-file
+    for amount in 1..=1100 {
+        let deposit = json!({"Deposit": {"account_id": "alice", "amount": amount.to_string()}});
+        processor.execute_command(BankJsonConverter::new().parse(&deposit.to_string()).unwrap()).unwrap();
+    }
+    let app = router(Arc::new(Mutex::new(processor)));
+
+    let export = |from_seq: u64| {
+        let app = app.clone();
+        async move {
+            let response = app.oneshot(Request::get(format!("/events?from_seq={}", from_seq)).body(Body::empty()).unwrap()).await.unwrap();
+            let status = response.status();
+            (status, String::from_utf8(to_bytes(response.into_body(), usize::MAX).await.unwrap().to_vec()).unwrap())
+        }
+    };
+    let amounts = |log: &str| -> Vec<u64> {
+        log.lines().map(|line| serde_json::from_str::<Value>(line).unwrap()["Deposit"]["amount"].as_str().unwrap().parse().unwrap()).collect()
+    };
+
+    // Seqs 1 and 2 went into the snapshot; the log holds seq 3 onwards
+    let (status, log) = export(2).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(amounts(&log), (1..=1100).collect::<Vec<_>>());
+    let (_, log) = export(1052).await;
+    assert_eq!(amounts(&log), (1051..=1100).collect::<Vec<_>>());
+
+    let (status, body) = export(1).await;
+    assert_eq!(status, StatusCode::GONE);
+    assert_eq!(serde_json::from_str::<Value>(&body).unwrap()["code"], "EVENTS_COMPACTED");
+
+    let _ = std::fs::remove_file(&test_file);
+}
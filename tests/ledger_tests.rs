@@ -0,0 +1,161 @@
+#![cfg(feature = "ledger-example")]
+
+use rmemimg::memimg::ledger::{
+    AccountKind, GetAccountBalance, GetTrialBalance, JournalLine, Ledger, LedgerCommand, LedgerError, TrialBalance,
+};
+use rmemimg::memimg::ledger_storage::LedgerJsonConverter;
+use rmemimg::memimg::testing::assert_replay_deterministic_by;
+use rmemimg::memimg::{MemImgError, MemImgProcessor, MemoryEventStorage, TextConverter, TextFileEventStorage};
+use rust_decimal::Decimal;
+
+fn open(code: &str, kind: AccountKind) -> LedgerCommand {
+    LedgerCommand::OpenAccount { code: code.to_string(), name: code.to_uppercase(), kind }
+}
+
+fn entry(description: &str, lines: Vec<JournalLine>) -> LedgerCommand {
+    LedgerCommand::JournalEntry { description: description.to_string(), lines }
+}
+
+fn chart_of_accounts() -> Vec<LedgerCommand> {
+    vec![
+        open("cash", AccountKind::Asset),
+        open("capital", AccountKind::Equity),
+        open("sales", AccountKind::Revenue),
+        open("rent", AccountKind::Expense),
+    ]
+}
+
+fn processor() -> MemImgProcessor<Ledger, LedgerCommand, MemoryEventStorage<LedgerCommand>> {
+    let mut processor = MemImgProcessor::new_simple(Ledger::new(), Box::new(MemoryEventStorage::new())).unwrap();
+    for command in chart_of_accounts() {
+        processor.execute_command(command).unwrap();
+    }
+    processor
+}
+
+fn balance<E>(processor: &MemImgProcessor<Ledger, LedgerCommand, E>, code: &str) -> Decimal
+where
+    E: rmemimg::memimg::EventStorage<Event = LedgerCommand>,
+{
+    processor.execute_query(&GetAccountBalance { code: code.to_string() }).unwrap()
+}
+
+fn domain_error(error: MemImgError) -> LedgerError {
+    error.outcome().unwrap().source.downcast_ref::<LedgerError>().cloned().unwrap()
+}
+
+#[test]
+fn balanced_entries_post_to_normal_side_balances() {
+    let mut processor = processor();
+
+    processor
+        .execute_command(entry("owner investment", vec![
+            JournalLine::debit("cash", Decimal::from(1000)),
+            JournalLine::credit("capital", Decimal::from(1000)),
+        ]))
+        .unwrap();
+    processor
+        .execute_command(entry("rent and sales", vec![
+            JournalLine::debit("rent", Decimal::from(300)),
+            JournalLine::debit("cash", Decimal::from(200)),
+            JournalLine::credit("cash", Decimal::from(300)),
+            JournalLine::credit("sales", Decimal::from(200)),
+        ]))
+        .unwrap();
+
+    assert_eq!(balance(&processor, "cash"), Decimal::from(900));
+    assert_eq!(balance(&processor, "capital"), Decimal::from(1000));
+    assert_eq!(balance(&processor, "sales"), Decimal::from(200));
+    assert_eq!(balance(&processor, "rent"), Decimal::from(300));
+    assert_eq!(
+        processor.execute_query(&GetTrialBalance).unwrap(),
+        TrialBalance { total_debits: Decimal::from(1500), total_credits: Decimal::from(1500) }
+    );
+    assert_eq!(processor.system().entries_posted, 2);
+}
+
+#[test]
+fn unbalanced_entry_rolls_back_lines_already_posted() {
+    let mut processor = processor();
+    let before = processor.system().clone();
+
+    // The debit posts before the totals are compared
+    let error = processor
+        .execute_command(entry("typo", vec![
+            JournalLine::debit("cash", Decimal::from(100)),
+            JournalLine::credit("sales", Decimal::from(10)),
+        ]))
+        .unwrap_err();
+
+    assert_eq!(
+        domain_error(error),
+        LedgerError::Unbalanced { description: "typo".to_string(), debits: Decimal::from(100), credits: Decimal::from(10) }
+    );
+    assert_eq!(processor.system(), &before);
+    assert_eq!(processor.event_storage.events().len(), 4);
+    assert_replay_deterministic_by(&mut processor, |ledger| serde_json::to_string(ledger).unwrap());
+}
+
+#[test]
+fn rejects_empty_entries_unknown_accounts_and_non_positive_lines() {
+    let mut processor = processor();
+
+    let error = processor.execute_command(entry("nothing", Vec::new())).unwrap_err();
+    assert_eq!(domain_error(error), LedgerError::EmptyEntry { description: "nothing".to_string() });
+
+    let error = processor
+        .execute_command(entry("lost", vec![
+            JournalLine::debit("cash", Decimal::from(5)),
+            JournalLine::credit("bank", Decimal::from(5)),
+        ]))
+        .unwrap_err();
+    assert_eq!(domain_error(error), LedgerError::UnknownAccount("bank".to_string()));
+
+    let error = processor
+        .execute_command(entry("negative", vec![
+            JournalLine::debit("cash", Decimal::from(-5)),
+            JournalLine::credit("sales", Decimal::from(-5)),
+        ]))
+        .unwrap_err();
+    assert_eq!(domain_error(error), LedgerError::NonPositiveAmount { account: "cash".to_string(), amount: Decimal::from(-5) });
+
+    let error = processor.execute_command(open("cash", AccountKind::Asset)).unwrap_err();
+    assert_eq!(domain_error(error), LedgerError::DuplicateAccount("cash".to_string()));
+}
+
+#[test]
+fn converter_round_trips_entries_on_one_line() {
+    let command = entry("sale", vec![JournalLine::debit("cash", Decimal::new(1999, 2)), JournalLine::credit("sales", Decimal::new(1999, 2))]);
+
+    let text = LedgerJsonConverter.format(&command).unwrap();
+
+    assert!(!text.contains('\n'));
+    assert_eq!(LedgerJsonConverter.parse(&text).unwrap(), command);
+}
+
+#[test]
+fn restores_ledger_from_text_file_events() {
+    let test_file = std::env::temp_dir().join("test_ledger_events.json");
+    let _ = std::fs::remove_file(&test_file);
+
+    {
+        let storage = Box::new(TextFileEventStorage::new(&test_file, LedgerJsonConverter).unwrap());
+        let mut processor = MemImgProcessor::new_simple(Ledger::new(), storage).unwrap();
+        for command in chart_of_accounts() {
+            processor.execute_command(command).unwrap();
+        }
+        processor
+            .execute_command(entry("investment", vec![
+                JournalLine::debit("cash", Decimal::from(50)),
+                JournalLine::credit("capital", Decimal::from(50)),
+            ]))
+            .unwrap();
+    }
+
+    let storage = Box::new(TextFileEventStorage::new(&test_file, LedgerJsonConverter).unwrap());
+    let processor = MemImgProcessor::new_simple(Ledger::new(), storage).unwrap();
+    assert_eq!(balance(&processor, "cash"), Decimal::from(50));
+    assert_eq!(balance(&processor, "capital"), Decimal::from(50));
+
+    let _ = std::fs::remove_file(&test_file);
+}
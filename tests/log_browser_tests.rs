@@ -0,0 +1,146 @@
+#![cfg(feature = "bank-example")]
+
+use rmemimg::memimg::bank::{Bank, BankCommand};
+use rmemimg::memimg::bank_browser::{balance_changes, BalanceChange, BankStateCache};
+use rmemimg::memimg::bank_storage::BankJsonConverter;
+use rmemimg::memimg::{Command, LogIndex, MemImgProcessor, TextConverter, TextFileEventStorage};
+use rust_decimal::Decimal;
+use std::io::Write;
+use std::path::PathBuf;
+
+fn deposit(account_id: &str, amount: i64) -> BankCommand {
+    BankCommand::Deposit { account_id: account_id.into(), amount: Decimal::from(amount) }
+}
+
+/// A log opening alice and bob, then depositing 1, 2, ... `deposits` into alternating accounts
+fn write_log(name: &str, deposits: i64) -> (PathBuf, Vec<BankCommand>) {
+    let path = std::env::temp_dir().join(name);
+    let mut commands = vec![
+        BankCommand::CreateAccount { id: "alice".into(), name: "Alice".to_string(), opening_balance: None },
+        BankCommand::CreateAccount { id: "bob".into(), name: "Bob".to_string(), opening_balance: None },
+    ];
+    commands.extend((1..=deposits).map(|n| deposit(if n % 2 == 1 { "alice" } else { "bob" }, n)));
+    let text: String = commands.iter().map(|command| BankJsonConverter::new().format(command).unwrap() + "\n").collect();
+    std::fs::write(&path, text).unwrap();
+    (path, commands)
+}
+
+#[test]
+fn log_index_reads_windows_anywhere_in_the_log() {
+    let (path, commands) = write_log("test_log_index_windows.json", 20);
+
+    let index = LogIndex::build(&path, 4).unwrap();
+
+    assert_eq!(index.len(), 22);
+    let window = index.read_window(9, 3).unwrap();
+    let expected: Vec<String> = commands[9..12].iter().map(|command| BankJsonConverter::new().format(command).unwrap()).collect();
+    assert_eq!(window, expected);
+    assert_eq!(index.read_window(20, 10).unwrap().len(), 2);
+    assert!(index.read_window(22, 10).unwrap().is_empty());
+
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn log_index_counts_only_committed_intents() {
+    let (path, _) = write_log("test_log_index_intents.json", 1);
+    let mut file = std::fs::OpenOptions::new().append(true).open(&path).unwrap();
+    let line = |command: &BankCommand| BankJsonConverter::new().format(command).unwrap();
+    write!(file, "#intents\n{}\n#commit\n{}\n", line(&deposit("bob", 2)), line(&deposit("bob", 3))).unwrap();
+
+    let mut index = LogIndex::build(&path, 2).unwrap();
+    assert_eq!(index.len(), 4);
+    assert_eq!(index.read_window(3, 5).unwrap(), vec![line(&deposit("bob", 2))]);
+
+    writeln!(file, "#commit").unwrap();
+    assert_eq!(index.refresh().unwrap(), 1);
+    assert_eq!(index.read_window(4, 1).unwrap(), vec![line(&deposit("bob", 3))]);
+
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn log_index_skips_blank_lines_and_waits_for_unterminated_ones() {
+    let (path, _) = write_log("test_log_index_refresh.json", 3);
+    let mut file = std::fs::OpenOptions::new().append(true).open(&path).unwrap();
+    write!(file, "\n{}", BankJsonConverter::new().format(&deposit("bob", 40)).unwrap()).unwrap();
+
+    let mut index = LogIndex::build(&path, 2).unwrap();
+    assert_eq!(index.len(), 5);
+
+    writeln!(file).unwrap();
+    assert_eq!(index.refresh().unwrap(), 1);
+    assert_eq!(index.read_window(5, 1).unwrap(), vec![BankJsonConverter::new().format(&deposit("bob", 40)).unwrap()]);
+
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn log_index_finds_the_next_matching_event() {
+    let (path, _) = write_log("test_log_index_find.json", 10);
+
+    let index = LogIndex::build(&path, 3).unwrap();
+
+    assert_eq!(index.find(0, "bob").unwrap(), Some(1));
+    assert_eq!(index.find(2, r#""amount":"7""#).unwrap(), Some(8));
+    assert_eq!(index.find(9, r#""amount":"7""#).unwrap(), None);
+
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn state_cache_matches_a_full_replay_at_every_event() {
+    let (path, commands) = write_log("test_bank_state_cache.json", 25);
+    let index = LogIndex::build(&path, 5).unwrap();
+    let mut cache = BankStateCache::new(5);
+
+    // Visit out of order, so later lookups start from checkpoints cached by earlier ones
+    for event in [20, 3, 27, 11, 0, 26] {
+        let mut expected = Bank::new();
+        for command in commands.iter().take(event as usize) {
+            command.apply_to(&mut expected).unwrap();
+        }
+        assert_eq!(cache.state_before(&index, event).unwrap(), expected, "before event {}", event);
+    }
+    let storage = Box::new(TextFileEventStorage::new(&path, BankJsonConverter::new()).unwrap());
+    let replayed = MemImgProcessor::new_simple(Bank::new(), storage).unwrap();
+    assert_eq!(&cache.state_before(&index, index.len()).unwrap(), replayed.system());
+
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn effect_reports_balances_before_and_after_the_event() {
+    let (path, _) = write_log("test_bank_event_effect.json", 4);
+    let index = LogIndex::build(&path, 2).unwrap();
+    let mut cache = BankStateCache::new(2);
+
+    // Event 5 deposits 4 into bob, who already holds 2
+    let effect = cache.effect_of(&index, 5).unwrap();
+
+    assert_eq!(effect.command, deposit("bob", 4));
+    assert_eq!(
+        effect.changes,
+        vec![BalanceChange { account_id: "bob".into(), before: Some(Decimal::from(2)), after: Some(Decimal::from(6)) }]
+    );
+    assert_eq!(effect.rejection, None);
+
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn balance_changes_reports_rejected_commands_as_unchanged() {
+    let bank = Bank::new();
+    let transfer = BankCommand::Transfer { from_account_id: "alice".into(), to_account_id: "bob".into(), amount: Decimal::ONE };
+
+    let (changes, rejection) = balance_changes(&bank, &transfer);
+
+    assert_eq!(
+        changes,
+        vec![
+            BalanceChange { account_id: "alice".into(), before: None, after: None },
+            BalanceChange { account_id: "bob".into(), before: None, after: None },
+        ]
+    );
+    assert_eq!(rejection.unwrap(), "Account not found: bob");
+}
@@ -1,51 +1,46 @@
-use rmemimg::memimg::bank::{Bank, BankCommand, GetAccount, GetBalance};
+#![cfg(feature = "bank-example")]
+
+use rmemimg::memimg::admin::fingerprint;
+use rmemimg::memimg::bank::{Bank, BankCommand, BankError, GetAccount, GetAccountsByOwner, GetBalance, GetLedgerSummary, GetTotalBalance, LedgerEntry, PaymentMethod};
+use rmemimg::memimg::bank_invariants::{DoubleEntryValidator, SystemInvariantViolation};
+use rmemimg::memimg::bank_journal::JOURNAL_CSV_HEADER;
 use rmemimg::memimg::bank_storage::BankJsonConverter;
-use rmemimg::memimg::{EventStorage, MemImgProcessor, TextFileEventStorage};
+use rmemimg::memimg::bank_throttler::{AccountCommandThrottler, RateLimit};
+use rmemimg::memimg::testing::{
+    assert_replay_deterministic, assert_storage_conformance, check_replay_deterministic, FaultStats, FaultyEventStorage,
+};
+use rmemimg::memimg::{
+    merge_by_timestamp, BoxedEventStorage, ClockRegressionPolicy, Command, CommandRegistry, CommitNotificationOrder, CommitStrategy, Durability, DuplicateCommandName, DynCommand, EphemeralMemImgProcessor, EventId, EventStorage, FailureDumper, InvariantMonitor, LoggingMiddleware, LogMarker, MemoryEventStorage, MemImgError, MemImgProcessor, MonitorMode, MultiTenantProcessor, MultiVersionEventStorage, PersistentProjection, Projection, QueryAudit, ReadReplica, ReloadStats, ReplayBudgetExceeded, ReplicaEndpoint, ReplicaProcessor, ReplicaRequest, ReplicatedEvent, ReplicationSource, RotationMarker, ReplayPolicy, SchemaFreeProcessor, SlowCommand, Snapshot, SnapshotFormat, StandingQueryProcessor, StorageMode, StreamEventStorage, SystemValidator,
+    StorageError, StorageOp, TextConverter, TextFileEventStorage, WarningKind, MARKER_PREFIX,
+};
 use rust_decimal::Decimal;
+use std::collections::{BTreeMap, HashMap};
+use std::convert::Infallible;
+use std::io::{Cursor, ErrorKind};
+use std::panic::AssertUnwindSafe;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
 
-// In-memory event storage for testing
-struct MemoryEventStorage {
-    events: Vec<BankCommand>,
-}
-
-impl MemoryEventStorage {
-    fn new() -> Self {
-        Self { events: Vec::new() }
-    }
-}
-
-impl EventStorage for MemoryEventStorage {
-    type Event = BankCommand;
-
-    fn replay<F>(&mut self, consumer: &mut F) -> Result<(), Box<dyn std::error::Error + Send + Sync>>
-    where
-        F: FnMut(Self::Event) -> Result<(), Box<dyn std::error::Error + Send + Sync>>,
-    {
-        for event in &self.events {
-            consumer(event.clone())?;
-        }
-        Ok(())
-    }
-
-    fn append(&mut self, event: &Self::Event) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        self.events.push(event.clone());
-        Ok(())
-    }
+// Event storage whose appends always fail, as with a full or vanished disk
+fn failing_append_storage() -> Box<FaultyEventStorage<MemoryEventStorage<BankCommand>>> {
+    Box::new(FaultyEventStorage::new(MemoryEventStorage::new()).with_failing_appends(u64::MAX, ErrorKind::PermissionDenied))
 }
 
 #[test]
 fn executes_and_serializes_successful_command() {
     let bank = Bank::new();
     let storage = Box::new(MemoryEventStorage::new());
-    let mut processor = MemImgProcessor::new(bank, storage).unwrap();
+    let mut processor = MemImgProcessor::new_simple(bank, storage).unwrap();
 
     let cmd1 = BankCommand::CreateAccount {
-        id: "acc1".to_string(),
+        id: "acc1".into(),
         name: "Alice".to_string(),
+        opening_balance: None,
     };
     let cmd2 = BankCommand::CreateAccount {
-        id: "acc2".to_string(),
+        id: "acc2".into(),
         name: "Bob".to_string(),
+        opening_balance: None,
     };
 
     processor.execute_command(cmd1).unwrap();
@@ -58,18 +53,19 @@ fn executes_and_serializes_successful_command() {
 fn initializes_from_previous_commands() {
     let storage = Box::new(MemoryEventStorage::new());
     let bank1 = Bank::new();
-    let mut processor1 = MemImgProcessor::new(bank1, storage).unwrap();
+    let mut processor1 = MemImgProcessor::new_simple(bank1, storage).unwrap();
 
     processor1
         .execute_command(BankCommand::CreateAccount {
-            id: "acc1".to_string(),
+            id: "acc1".into(),
             name: "Alice".to_string(),
+            opening_balance: None,
         })
         .unwrap();
 
     processor1
         .execute_command(BankCommand::Deposit {
-            account_id: "acc1".to_string(),
+            account_id: "acc1".into(),
             amount: Decimal::new(100, 0),
         })
         .unwrap();
@@ -82,52 +78,73 @@ fn initializes_from_previous_commands() {
 
     // Create new processor with same storage
     let bank2 = Bank::new();
-    let processor2 = MemImgProcessor::new(bank2, storage).unwrap();
+    let processor2 = MemImgProcessor::new_simple(bank2, storage).unwrap();
 
     assert_eq!(processor2.system().accounts.len(), 1);
     assert_eq!(
-        processor2.system().accounts.get("acc1").unwrap().balance,
+        processor2.system().accounts.get("acc1").unwrap().balance(),
         Decimal::new(100, 0)
     );
 }
 
+#[test]
+fn new_reports_replay_metrics() {
+    let events = vec![
+        BankCommand::CreateAccount { id: "alice".into(), name: "Alice".to_string(), opening_balance: None },
+        deposit("alice", 10),
+        deposit("alice", 20),
+        deposit("alice", 30),
+        deposit("alice", 40),
+    ];
+
+    let (processor, metrics) = MemImgProcessor::new(Bank::new(), Box::new(MemoryEventStorage::with_events(events))).unwrap();
+
+    assert_eq!(metrics.events_replayed, 5);
+    assert!(metrics.replay_duration > std::time::Duration::ZERO);
+    assert_eq!(metrics.skip_count, 0);
+    // Bank events carry no timestamps
+    assert_eq!((metrics.first_event_at, metrics.last_event_at), (None, None));
+    assert_eq!(processor.event_version(), EventId(5));
+}
+
 #[test]
 fn executes_query() {
     let bank = Bank::new();
     let storage = Box::new(MemoryEventStorage::new());
-    let mut processor = MemImgProcessor::new(bank, storage).unwrap();
+    let mut processor = MemImgProcessor::new_simple(bank, storage).unwrap();
 
     processor
         .execute_command(BankCommand::CreateAccount {
-            id: "acc1".to_string(),
+            id: "acc1".into(),
             name: "Alice".to_string(),
+            opening_balance: None,
         })
         .unwrap();
 
     processor
         .execute_command(BankCommand::Deposit {
-            account_id: "acc1".to_string(),
+            account_id: "acc1".into(),
             amount: Decimal::new(100, 0),
         })
         .unwrap();
 
     let query = GetAccount {
-        account_id: "acc1".to_string(),
+        account_id: "acc1".into(),
     };
     let result = processor.execute_query(&query).unwrap();
 
     assert!(result.is_some());
-    assert_eq!(result.unwrap().balance, Decimal::new(100, 0));
+    assert_eq!(result.unwrap().balance(), Decimal::new(100, 0));
 }
 
 #[test]
 fn signals_failure_on_failed_query() {
     let bank = Bank::new();
-    let storage = Box::new(MemoryEventStorage::new());
-    let processor = MemImgProcessor::new(bank, storage).unwrap();
+    let storage = Box::new(MemoryEventStorage::<BankCommand>::new());
+    let processor = MemImgProcessor::new_simple(bank, storage).unwrap();
 
     let query = GetBalance {
-        account_id: "nonexistent".to_string(),
+        account_id: "nonexistent".into(),
     };
     let result = processor.execute_query(&query);
 
@@ -138,125 +155,302 @@ fn signals_failure_on_failed_query() {
 fn rolls_back_partial_updates_on_failed_command() {
     let bank = Bank::new();
     let storage = Box::new(MemoryEventStorage::new());
-    let mut processor = MemImgProcessor::new(bank, storage).unwrap();
+    let mut processor = MemImgProcessor::new_simple(bank, storage).unwrap();
 
     processor
         .execute_command(BankCommand::CreateAccount {
-            id: "acc1".to_string(),
+            id: "acc1".into(),
             name: "Alice".to_string(),
+            opening_balance: None,
         })
         .unwrap();
 
     processor
         .execute_command(BankCommand::Deposit {
-            account_id: "acc1".to_string(),
+            account_id: "acc1".into(),
             amount: Decimal::new(100, 0),
         })
         .unwrap();
 
     // Try to withdraw more than balance - should fail
     let result = processor.execute_command(BankCommand::Withdrawal {
-        account_id: "acc1".to_string(),
+        account_id: "acc1".into(),
         amount: Decimal::new(200, 0),
     });
 
     assert!(result.is_err());
     // Balance should remain 100
     assert_eq!(
-        processor.system().accounts.get("acc1").unwrap().balance,
+        processor.system().accounts.get("acc1").unwrap().balance(),
         Decimal::new(100, 0)
     );
 }
 
+/// `BankCommand` applying transfers deposit-first, so a failing one leaves partial state behind
+/// that only the processor's shadow copy can undo
+#[derive(Debug, Clone)]
+struct DepositFirst(BankCommand);
+
+impl Command for DepositFirst {
+    type System = Bank;
+
+    fn apply_to(&self, bank: &mut Bank) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        match &self.0 {
+            BankCommand::Transfer { from_account_id, to_account_id, amount } => {
+                BankCommand::Deposit { account_id: to_account_id.clone(), amount: *amount }.apply_to(bank)?;
+                BankCommand::Withdrawal { account_id: from_account_id.clone(), amount: *amount }.apply_to(bank)
+            }
+            command => command.apply_to(bank),
+        }
+    }
+}
+
 #[test]
 fn transfer_rolls_back_on_insufficient_funds() {
-    let bank = Bank::new();
     let storage = Box::new(MemoryEventStorage::new());
-    let mut processor = MemImgProcessor::new(bank, storage).unwrap();
+    let mut processor = MemImgProcessor::new_simple(Bank::new(), storage).unwrap();
+    for command in [
+        BankCommand::CreateAccount { id: "acc1".into(), name: "Alice".to_string(), opening_balance: None },
+        BankCommand::CreateAccount { id: "acc2".into(), name: "Bob".to_string(), opening_balance: None },
+        BankCommand::Deposit { account_id: "acc1".into(), amount: Decimal::new(50, 0) },
+    ] {
+        processor.execute_command(DepositFirst(command)).unwrap();
+    }
 
-    processor
-        .execute_command(BankCommand::CreateAccount {
-            id: "acc1".to_string(),
-            name: "Alice".to_string(),
+    // Try transfer more than available - should fail and rollback
+    let transfer = DepositFirst(BankCommand::Transfer { from_account_id: "acc1".into(), to_account_id: "acc2".into(), amount: Decimal::new(100, 0) });
+    let mut partial = processor.system().clone();
+    assert!(transfer.apply_to(&mut partial).is_err());
+    assert_eq!(partial.accounts["acc2"].balance(), Decimal::new(100, 0));
+
+    assert!(processor.execute_command(transfer).is_err());
+    // Both balances should remain as before
+    processor.assert_state(|bank| {
+        assert_eq!(bank.accounts["acc1"].balance(), Decimal::new(50, 0));
+        assert_eq!(bank.accounts["acc2"].balance(), Decimal::ZERO);
+    });
+}
+
+#[test]
+fn transaction_logs_its_commands_together() {
+    let storage = Box::new(MemoryEventStorage::new());
+    let mut processor = MemImgProcessor::new_simple(Bank::new(), storage).unwrap();
+
+    let balance = processor
+        .execute_transaction(|tx| {
+            tx.execute_command(&BankCommand::CreateAccount { id: "acc1".into(), name: "Alice".to_string(), opening_balance: None })?;
+            tx.execute_command(&BankCommand::Deposit { account_id: "acc1".into(), amount: Decimal::new(70, 0) })?;
+            tx.query(&GetBalance { account_id: "acc1".into() })
         })
         .unwrap();
 
+    assert_eq!(balance, Decimal::new(70, 0));
+    assert_eq!(processor.system().accounts.get("acc1").unwrap().balance(), Decimal::new(70, 0));
+    assert_eq!(processor.event_storage.events().len(), 2);
+    assert_eq!(processor.event_version().as_u64(), 2);
+    assert_eq!(processor.statistics().total_commands_executed, 2);
+}
+
+#[test]
+fn failed_transaction_logs_nothing_and_leaves_the_state() {
+    let storage = Box::new(MemoryEventStorage::new());
+    let mut processor = MemImgProcessor::new_simple(Bank::new(), storage).unwrap();
     processor
-        .execute_command(BankCommand::CreateAccount {
-            id: "acc2".to_string(),
-            name: "Bob".to_string(),
-        })
+        .execute_command(BankCommand::CreateAccount { id: "acc1".into(), name: "Alice".to_string(), opening_balance: Some(Decimal::new(50, 0)) })
         .unwrap();
+    let before = processor.system().clone();
+
+    let result = processor.execute_transaction(|tx| {
+        tx.execute_command(&BankCommand::CreateAccount { id: "acc2".into(), name: "Bob".to_string(), opening_balance: None })?;
+        // The shadow already holds acc2, but acc1 cannot cover the transfer
+        assert!(tx.system().accounts.contains_key("acc2"));
+        tx.execute_command(&BankCommand::Transfer { from_account_id: "acc1".into(), to_account_id: "acc2".into(), amount: Decimal::new(100, 0) })
+    });
+
+    assert!(matches!(result, Err(MemImgError::CommandFailure(_))));
+    assert_eq!(processor.system(), &before);
+    assert_eq!(processor.event_storage.events().len(), 1);
+    assert_eq!(processor.event_version().as_u64(), 1);
+    assert_eq!(processor.statistics().total_commands_failed, 1);
+}
+
+#[test]
+fn independent_commands_succeed_or_fail_one_by_one() {
+    let storage = Box::new(MemoryEventStorage::new());
+    let mut processor = MemImgProcessor::new_simple(Bank::new(), storage).unwrap();
+    processor.execute_command(BankCommand::CreateAccount { id: "acc1".into(), name: "Alice".to_string(), opening_balance: None }).unwrap();
+
+    let results = processor.execute_independent(vec![
+        deposit("acc1", 30),
+        BankCommand::Withdrawal { account_id: "acc1".into(), amount: Decimal::new(100, 0) },
+        deposit("acc1", 20),
+    ]);
+
+    assert!(results[0].is_ok() && results[2].is_ok());
+    let domain = results[1].as_ref().unwrap_err().outcome().and_then(|outcome| outcome.source.downcast_ref::<BankError>());
+    assert_eq!(domain, Some(&BankError::InsufficientFunds { available: Decimal::new(30, 0), requested: Decimal::new(100, 0) }));
+    assert_eq!(processor.system().accounts["acc1"].balance(), Decimal::new(50, 0));
+    assert_eq!(processor.event_storage.events().len(), 3);
+}
+
+/// Commit notifications as recorded by two observers, a channel and a middleware, for one
+/// command and then a two-command transaction
+fn commit_notifications(order: CommitNotificationOrder) -> Vec<String> {
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let middleware = Arc::clone(&log);
+    let mut processor = MemImgProcessor::new_simple(Bank::new(), Box::new(MemoryEventStorage::new()))
+        .unwrap()
+        .with_middleware(LoggingMiddleware::new(move |_: &str| middleware.lock().unwrap().push("middleware".to_string())))
+        .with_commit_notification_order(order);
+    // The first observer holds the channel, so it sees whether the event was published yet
+    let commits = processor.subscribe_commits();
+    let first = Arc::clone(&log);
+    let mut received = 0;
+    processor.on_commit(move |seq, _| {
+        received += commits.try_iter().count() as u64;
+        first.lock().unwrap().push(format!("first {} published={}", seq, received == seq.as_u64()));
+    });
+    let second = Arc::clone(&log);
+    processor.on_commit(move |seq, _| second.lock().unwrap().push(format!("second {}", seq)));
 
+    processor.execute_command(BankCommand::CreateAccount { id: "acc1".into(), name: "Alice".to_string(), opening_balance: None }).unwrap();
     processor
-        .execute_command(BankCommand::Deposit {
-            account_id: "acc1".to_string(),
-            amount: Decimal::new(50, 0),
+        .execute_transaction(|tx| {
+            tx.execute_command(&deposit("acc1", 1))?;
+            tx.execute_command(&deposit("acc1", 2))
         })
         .unwrap();
+    let notifications = log.lock().unwrap().clone();
+    notifications
+}
 
-    // Try transfer more than available - should fail and rollback
-    let result = processor.execute_command(BankCommand::Transfer {
-        from_account_id: "acc1".to_string(),
-        to_account_id: "acc2".to_string(),
-        amount: Decimal::new(100, 0),
-    });
-
-    assert!(result.is_err());
-    // Both balances should remain as before
+#[test]
+fn commit_notifications_follow_the_documented_order() {
     assert_eq!(
-        processor.system().accounts.get("acc1").unwrap().balance,
-        Decimal::new(50, 0)
+        commit_notifications(CommitNotificationOrder::default()),
+        ["first 1 published=false", "second 1", "middleware", "first 2 published=false", "second 2", "first 3 published=false", "second 3"]
     );
     assert_eq!(
-        processor.system().accounts.get("acc2").unwrap().balance,
-        Decimal::ZERO
+        commit_notifications(CommitNotificationOrder::PublishFirst),
+        ["first 1 published=true", "second 1", "middleware", "first 2 published=true", "second 2", "first 3 published=true", "second 3"]
     );
 }
 
+#[test]
+fn sandbox_projects_a_sequence_without_touching_the_live_state() {
+    let storage = Box::new(MemoryEventStorage::new());
+    let mut processor = MemImgProcessor::new_simple(Bank::new(), storage).unwrap();
+    processor
+        .execute_command(BankCommand::CreateAccount { id: "acc1".into(), name: "Alice".to_string(), opening_balance: Some(Decimal::new(50, 0)) })
+        .unwrap();
+    processor.execute_command(BankCommand::CreateAccount { id: "acc2".into(), name: "Bob".to_string(), opening_balance: None }).unwrap();
+    let before = processor.system().clone();
+
+    let projected = processor
+        .sandbox(&[
+            BankCommand::Deposit { account_id: "acc1".into(), amount: Decimal::new(30, 0) },
+            BankCommand::Transfer { from_account_id: "acc1".into(), to_account_id: "acc2".into(), amount: Decimal::new(60, 0) },
+        ])
+        .unwrap();
+
+    assert_eq!(projected.accounts["acc1"].balance(), Decimal::new(20, 0));
+    assert_eq!(projected.accounts["acc2"].balance(), Decimal::new(60, 0));
+    assert_eq!(processor.system(), &before);
+    assert_eq!(processor.event_storage.events().len(), 2);
+    assert_eq!(processor.statistics().total_commands_executed, 2);
+
+    // The transfer overdraws without the deposit before it
+    let result = processor.sandbox(&[BankCommand::Transfer { from_account_id: "acc1".into(), to_account_id: "acc2".into(), amount: Decimal::new(60, 0) }]);
+    assert!(matches!(result, Err(MemImgError::CommandFailure(_))));
+    assert_eq!(processor.statistics().total_commands_failed, 0);
+}
+
+#[test]
+fn read_only_processor_refuses_commands_but_answers_queries() {
+    let storage = Box::new(MemoryEventStorage::new());
+    let mut processor = MemImgProcessor::new_simple(Bank::new(), storage).unwrap();
+    processor
+        .execute_command(BankCommand::CreateAccount { id: "acc1".into(), name: "Alice".to_string(), opening_balance: Some(Decimal::new(50, 0)) })
+        .unwrap();
+
+    processor.set_read_only(true);
+    assert!(processor.is_read_only());
+    let deposit = BankCommand::Deposit { account_id: "acc1".into(), amount: Decimal::new(10, 0) };
+    let result = processor.execute_command(deposit.clone());
+    assert!(matches!(result, Err(MemImgError::MaintenanceMode)));
+    assert_eq!(result.unwrap_err().code(), "MAINTENANCE_MODE");
+    assert_eq!(processor.execute_query(&GetBalance { account_id: "acc1".into() }).unwrap(), Decimal::new(50, 0));
+    assert_eq!(processor.event_storage.events().len(), 1);
+    assert_eq!(processor.statistics().total_commands_failed, 0);
+
+    processor.set_read_only(false);
+    processor.execute_command(deposit).unwrap();
+    assert_eq!(processor.execute_query(&GetBalance { account_id: "acc1".into() }).unwrap(), Decimal::new(60, 0));
+    assert_eq!(processor.event_storage.events().len(), 2);
+}
+
 #[test]
 fn successful_transfer() {
     let bank = Bank::new();
     let storage = Box::new(MemoryEventStorage::new());
-    let mut processor = MemImgProcessor::new(bank, storage).unwrap();
+    let mut processor = MemImgProcessor::new_simple(bank, storage).unwrap();
 
     processor
         .execute_command(BankCommand::CreateAccount {
-            id: "acc1".to_string(),
+            id: "acc1".into(),
             name: "Alice".to_string(),
+            opening_balance: None,
         })
         .unwrap();
 
     processor
         .execute_command(BankCommand::CreateAccount {
-            id: "acc2".to_string(),
+            id: "acc2".into(),
             name: "Bob".to_string(),
+            opening_balance: None,
         })
         .unwrap();
 
     processor
         .execute_command(BankCommand::Deposit {
-            account_id: "acc1".to_string(),
+            account_id: "acc1".into(),
             amount: Decimal::new(100, 0),
         })
         .unwrap();
 
     processor
         .execute_command(BankCommand::Transfer {
-            from_account_id: "acc1".to_string(),
-            to_account_id: "acc2".to_string(),
+            from_account_id: "acc1".into(),
+            to_account_id: "acc2".into(),
             amount: Decimal::new(30, 0),
         })
         .unwrap();
 
-    assert_eq!(
-        processor.system().accounts.get("acc1").unwrap().balance,
-        Decimal::new(70, 0)
-    );
-    assert_eq!(
-        processor.system().accounts.get("acc2").unwrap().balance,
-        Decimal::new(30, 0)
-    );
+    processor.assert_state(|bank| {
+        assert_eq!(bank.accounts["acc1"].balance(), Decimal::new(70, 0));
+        assert_eq!(bank.accounts["acc2"].balance(), Decimal::new(30, 0));
+    });
+
+    for account_id in ["acc1", "acc2"] {
+        let summary = processor
+            .execute_query(&GetLedgerSummary {
+                account_id: account_id.into(),
+            })
+            .unwrap();
+        assert_eq!(
+            summary.net_balance,
+            processor.system().accounts.get(account_id).unwrap().balance()
+        );
+        assert_eq!(summary.net_balance, summary.total_credits - summary.total_debits);
+    }
+
+    let acc1 = processor
+        .execute_query(&GetLedgerSummary {
+            account_id: "acc1".into(),
+        })
+        .unwrap();
+    assert_eq!(acc1.total_credits, Decimal::new(100, 0));
+    assert_eq!(acc1.total_debits, Decimal::new(30, 0));
 }
 
 #[test]
@@ -270,20 +464,21 @@ fn text_file_storage_round_trip() {
     {
         let bank = Bank::new();
         let storage = Box::new(
-            TextFileEventStorage::new(&test_file, BankJsonConverter).unwrap(),
+            TextFileEventStorage::new(&test_file, BankJsonConverter::new()).unwrap(),
         );
-        let mut processor = MemImgProcessor::new(bank, storage).unwrap();
+        let mut processor = MemImgProcessor::new_simple(bank, storage).unwrap();
 
         processor
             .execute_command(BankCommand::CreateAccount {
-                id: "acc1".to_string(),
+                id: "acc1".into(),
                 name: "Alice".to_string(),
+                opening_balance: None,
             })
             .unwrap();
 
         processor
             .execute_command(BankCommand::Deposit {
-                account_id: "acc1".to_string(),
+                account_id: "acc1".into(),
                 amount: Decimal::new(250, 0),
             })
             .unwrap();
@@ -293,13 +488,13 @@ fn text_file_storage_round_trip() {
     {
         let bank = Bank::new();
         let storage = Box::new(
-            TextFileEventStorage::new(&test_file, BankJsonConverter).unwrap(),
+            TextFileEventStorage::new(&test_file, BankJsonConverter::new()).unwrap(),
         );
-        let processor = MemImgProcessor::new(bank, storage).unwrap();
+        let processor = MemImgProcessor::new_simple(bank, storage).unwrap();
 
         assert_eq!(processor.system().accounts.len(), 1);
         assert_eq!(
-            processor.system().accounts.get("acc1").unwrap().balance,
+            processor.system().accounts.get("acc1").unwrap().balance(),
             Decimal::new(250, 0)
         );
     }
@@ -307,3 +502,3004 @@ fn text_file_storage_round_trip() {
     // Clean up
     let _ = std::fs::remove_file(&test_file);
 }
+
+fn count_ndjson_values(text: &str) -> usize {
+    serde_json::Deserializer::from_str(text)
+        .into_iter::<serde_json::Value>()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap()
+        .len()
+}
+
+#[test]
+fn copies_memory_events_as_ndjson() {
+    let mut storage = MemoryEventStorage::new();
+    storage
+        .append(&BankCommand::CreateAccount {
+            id: "acc1".into(),
+            name: "Alice".to_string(),
+            opening_balance: None,
+        })
+        .unwrap();
+    storage
+        .append(&BankCommand::Deposit {
+            account_id: "acc1".into(),
+            amount: Decimal::new(100, 0),
+        })
+        .unwrap();
+
+    let mut buffer = Vec::new();
+    let written = storage.copy_to(&mut buffer).unwrap();
+
+    assert_eq!(written, buffer.len() as u64);
+    let text = String::from_utf8(buffer).unwrap();
+    assert_eq!(text.lines().count(), 2);
+    assert_eq!(count_ndjson_values(&text), 2);
+}
+
+#[test]
+fn copies_text_file_events_as_ndjson() {
+    let test_file = std::env::temp_dir().join("test_copy_to_events.json");
+    let _ = std::fs::remove_file(&test_file);
+
+    {
+        let bank = Bank::new();
+        let storage = Box::new(TextFileEventStorage::new(&test_file, BankJsonConverter::new()).unwrap());
+        let mut processor = MemImgProcessor::new_simple(bank, storage).unwrap();
+
+        processor
+            .execute_command(BankCommand::CreateAccount {
+                id: "acc1".into(),
+                name: "Alice".to_string(),
+                opening_balance: None,
+            })
+            .unwrap();
+        processor
+            .execute_command(BankCommand::Deposit {
+                account_id: "acc1".into(),
+                amount: Decimal::new(100, 0),
+            })
+            .unwrap();
+
+        let mut buffer = Vec::new();
+        let written = processor.event_storage.copy_to(&mut buffer).unwrap();
+
+        assert_eq!(written, std::fs::metadata(&test_file).unwrap().len());
+        assert_eq!(count_ndjson_values(&String::from_utf8(buffer).unwrap()), 2);
+    }
+
+    let _ = std::fs::remove_file(&test_file);
+}
+
+#[test]
+fn copies_only_committed_text_file_events() {
+    let test_file = std::env::temp_dir().join("test_copy_to_committed_events.json");
+    let _ = std::fs::remove_file(&test_file);
+    let storage = Box::new(TextFileEventStorage::new(&test_file, BankJsonConverter::new()).unwrap().with_write_ahead_intents());
+    let mut processor = MemImgProcessor::new_with_commit_strategy(Bank::new(), storage, CommitStrategy::AppendThenApply).unwrap();
+    processor.execute_command(BankCommand::CreateAccount { id: "acc1".into(), name: "Alice".to_string(), opening_balance: None }).unwrap();
+    processor.event_storage.append_marker("deploy 1.2").unwrap();
+    processor.execute_command(deposit("acc1", 100)).unwrap();
+    // Appended but never settled, so still pending
+    processor.event_storage.append(&deposit("acc1", 5)).unwrap();
+
+    let mut buffer = Vec::new();
+    let written = processor.event_storage.copy_to(&mut buffer).unwrap();
+
+    let text = String::from_utf8(buffer).unwrap();
+    assert_eq!(written, text.len() as u64);
+    assert_eq!(text.lines().count(), 2);
+    assert_eq!(count_ndjson_values(&text), 2);
+    assert!(!text.contains(MARKER_PREFIX));
+
+    drop(processor);
+    let _ = std::fs::remove_file(&test_file);
+}
+
+#[test]
+fn lenient_replay_reports_skipped_line_and_repaired_tail() {
+    let test_file = std::env::temp_dir().join("test_replay_warnings.json");
+    let good = concat!(
+        r#"{"CreateAccount":{"id":"acc1","name":"Alice"}}"#,
+        "\n",
+        "not json at all\n",
+        r#"{"Deposit":{"account_id":"acc1","amount":"100"}}"#,
+        "\n",
+    );
+    std::fs::write(&test_file, format!("{}{}", good, r#"{"Deposit":{"account_"#)).unwrap();
+
+    let bank = Bank::new();
+    let storage = Box::new(
+        TextFileEventStorage::new(&test_file, BankJsonConverter::new())
+            .unwrap()
+            .with_replay_policy(ReplayPolicy::Lenient),
+    );
+    let (processor, metrics) = MemImgProcessor::new(bank, storage).unwrap();
+
+    assert_eq!((metrics.events_replayed, metrics.skip_count), (2, 1));
+    let warnings = processor.warnings();
+    assert_eq!(warnings.len(), 2);
+    assert_eq!(warnings[0].kind, WarningKind::SkippedLine);
+    assert_eq!(warnings[0].index, 2);
+    assert_eq!(warnings[1].kind, WarningKind::RepairedTail);
+    assert_eq!(warnings[1].index, 4);
+    assert_eq!(warnings[1].offset, good.len() as u64);
+    assert_eq!(processor.dropped_warnings(), 0);
+
+    assert_eq!(
+        processor.system().accounts.get("acc1").unwrap().balance(),
+        Decimal::new(100, 0)
+    );
+    assert_eq!(std::fs::read_to_string(&test_file).unwrap(), good);
+
+    drop(processor);
+    let _ = std::fs::remove_file(&test_file);
+}
+
+#[test]
+fn strict_replay_fails_on_unparseable_line() {
+    let test_file = std::env::temp_dir().join("test_strict_replay.json");
+    std::fs::write(&test_file, "not json at all\n").unwrap();
+
+    let bank = Bank::new();
+    let storage = Box::new(TextFileEventStorage::new(&test_file, BankJsonConverter::new()).unwrap());
+
+    assert!(MemImgProcessor::new_simple(bank, storage).is_err());
+
+    let _ = std::fs::remove_file(&test_file);
+}
+
+#[test]
+fn budgeted_replay_tolerates_bad_lines_up_to_its_budget() {
+    let test_file = std::env::temp_dir().join("test_budgeted_replay.json");
+    let log = |bad_lines: usize| {
+        let mut log = format!("{}\n", r#"{"CreateAccount":{"id":"acc1","name":"Alice"}}"#);
+        for _ in 0..bad_lines {
+            log.push_str("not json at all\n");
+            log.push_str(r#"{"Deposit":{"account_id":"acc1","amount":"10"}}"#);
+            log.push('\n');
+        }
+        log
+    };
+    let open = || {
+        let storage = TextFileEventStorage::new(&test_file, BankJsonConverter::new()).unwrap();
+        MemImgProcessor::new_simple(Bank::new(), Box::new(storage.with_replay_policy(ReplayPolicy::Budgeted { error_budget: 3 })))
+    };
+
+    std::fs::write(&test_file, log(2)).unwrap();
+    let processor = open().unwrap();
+    assert_eq!(processor.warnings().len(), 2);
+    assert_eq!(processor.execute_query(&GetBalance { account_id: "acc1".into() }).unwrap(), Decimal::from(20));
+    drop(processor);
+
+    std::fs::write(&test_file, log(4)).unwrap();
+    let error = open().err().unwrap();
+    let exceeded = error.outcome().unwrap().source.downcast_ref::<ReplayBudgetExceeded>().unwrap();
+    assert_eq!(exceeded.error_budget, 3);
+    assert_eq!(exceeded.errors.iter().map(|warning| warning.index).collect::<Vec<_>>(), vec![2, 4, 6, 8]);
+
+    let _ = std::fs::remove_file(&test_file);
+}
+
+#[test]
+fn direct_transfer_apply_never_leaves_partial_state() {
+    let mut bank = Bank::new();
+    BankCommand::CreateAccount {
+        id: "acc1".into(),
+        name: "Alice".to_string(),
+        opening_balance: None,
+    }
+    .apply_to(&mut bank)
+    .unwrap();
+    BankCommand::CreateAccount {
+        id: "acc2".into(),
+        name: "Bob".to_string(),
+        opening_balance: None,
+    }
+    .apply_to(&mut bank)
+    .unwrap();
+    BankCommand::Deposit {
+        account_id: "acc1".into(),
+        amount: Decimal::new(50, 0),
+    }
+    .apply_to(&mut bank)
+    .unwrap();
+
+    let insufficient = BankCommand::Transfer {
+        from_account_id: "acc1".into(),
+        to_account_id: "acc2".into(),
+        amount: Decimal::new(100, 0),
+    };
+    assert!(insufficient.apply_to(&mut bank).is_err());
+
+    let missing_source = BankCommand::Transfer {
+        from_account_id: "nonexistent".into(),
+        to_account_id: "acc2".into(),
+        amount: Decimal::new(10, 0),
+    };
+    assert!(missing_source.apply_to(&mut bank).is_err());
+
+    assert_eq!(bank.accounts.get("acc1").unwrap().balance(), Decimal::new(50, 0));
+    assert_eq!(bank.accounts.get("acc2").unwrap().balance(), Decimal::ZERO);
+    assert_eq!(bank.accounts.get("acc2").unwrap().total_credits, Decimal::ZERO);
+}
+
+fn render_error_chain(error: &dyn std::error::Error) -> String {
+    let mut rendered = error.to_string();
+    let mut source = error.source();
+    while let Some(cause) = source {
+        rendered.push_str(" <- ");
+        rendered.push_str(&cause.to_string());
+        source = cause.source();
+    }
+    rendered
+}
+
+#[test]
+fn storage_errors_carry_path_and_operation() {
+    // Permission bits are not enforced for root, so block directory creation with a regular file instead
+    let blocker = std::env::temp_dir().join("test_storage_error_blocker");
+    let _ = std::fs::remove_dir_all(&blocker);
+    std::fs::write(&blocker, "").unwrap();
+    let test_file = blocker.join("events.json");
+
+    let error = TextFileEventStorage::new(&test_file, BankJsonConverter::new()).err().unwrap();
+    let storage_error = error.downcast_ref::<StorageError>().unwrap();
+    assert_eq!(storage_error.op, StorageOp::CreateDir);
+    assert_eq!(storage_error.path, blocker.to_string_lossy());
+    assert!(render_error_chain(error.as_ref()).contains("create directory failed on"));
+
+    let _ = std::fs::remove_file(&blocker);
+}
+
+#[test]
+fn replay_failure_chain_names_path_and_operation() {
+    // A directory where the log file should be opens fine but cannot be read
+    let test_dir = std::env::temp_dir().join("test_storage_error_dir.json");
+    let _ = std::fs::remove_file(&test_dir);
+    std::fs::create_dir_all(&test_dir).unwrap();
+
+    let bank = Bank::new();
+    let storage = Box::new(TextFileEventStorage::new(&test_dir, BankJsonConverter::new()).unwrap());
+    let error = MemImgProcessor::new_simple(bank, storage).err().unwrap();
+
+    let rendered = render_error_chain(&error);
+    assert!(rendered.contains(&test_dir.to_string_lossy().to_string()));
+    assert!(rendered.contains("read failed on"));
+
+    let _ = std::fs::remove_dir_all(&test_dir);
+}
+
+#[test]
+fn poisoning_failure_writes_crash_dump() {
+    let dump_dir = std::env::temp_dir().join("test_crash_dump");
+    let _ = std::fs::remove_dir_all(&dump_dir);
+    std::fs::create_dir_all(&dump_dir).unwrap();
+    let dump_file = dump_dir.join("failure.json");
+
+    let bank = Bank::new();
+    let mut processor = MemImgProcessor::new_simple(bank, failing_append_storage())
+        .unwrap()
+        .with_failure_dumper(
+            FailureDumper::new(&dump_file)
+                .with_redactor(|payload| payload.replace("Alice", "***"))
+                .with_fingerprint(|bank: &Bank| format!("accounts={}", bank.accounts.len())),
+        );
+
+    let result = processor.execute_command(BankCommand::CreateAccount {
+        id: "acc1".into(),
+        name: "Alice".to_string(),
+        opening_balance: None,
+    });
+    assert!(matches!(result, Err(MemImgError::SystemFailure(_))));
+    assert!(processor.is_poisoned());
+
+    let dump: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&dump_file).unwrap()).unwrap();
+    assert!(dump["command_type"].as_str().unwrap().ends_with("BankCommand"));
+    let command = dump["command"].as_str().unwrap();
+    assert!(command.contains("acc1") && !command.contains("Alice"));
+    let chain = dump["error_chain"].as_array().unwrap();
+    assert!(chain.iter().any(|frame| frame.as_str().unwrap().contains("injected append failure")));
+    assert_eq!(dump["event_count"], 0);
+    assert_eq!(dump["stats"]["commands_failed"], 1);
+    assert_eq!(dump["fingerprint"], "accounts=0");
+    assert!(dump["snapshot"].is_null());
+
+    // Poisoned processors reject further commands without touching storage or dumping again
+    std::fs::remove_file(&dump_file).unwrap();
+    let result = processor.execute_command(BankCommand::CreateAccount {
+        id: "acc2".into(),
+        name: "Bob".to_string(),
+        opening_balance: None,
+    });
+    assert!(matches!(result, Err(MemImgError::Poisoned)));
+    assert!(!dump_file.exists());
+
+    let _ = std::fs::remove_dir_all(&dump_dir);
+}
+
+#[test]
+fn throttler_rejects_commands_beyond_account_rate_limit() {
+    let mut throttler = AccountCommandThrottler::new();
+    throttler.set_limit("acc1", RateLimit { max_per_second: 5 });
+
+    let bank = Bank::new();
+    let storage = Box::new(MemoryEventStorage::new());
+    let mut processor = MemImgProcessor::new_simple(bank, storage)
+        .unwrap()
+        .with_middleware(throttler);
+
+    processor
+        .execute_command(BankCommand::CreateAccount {
+            id: "acc1".into(),
+            name: "Alice".to_string(),
+            opening_balance: None,
+        })
+        .unwrap();
+
+    let mut first_rejected = None;
+    for call in 2..=10 {
+        let result = processor.execute_command(BankCommand::Deposit {
+            account_id: "acc1".into(),
+            amount: Decimal::new(1, 0),
+        });
+        if let Err(MemImgError::RateLimitExceeded { account_id, retry_after }) = result {
+            assert_eq!(account_id, "acc1");
+            assert!(retry_after <= std::time::Duration::from_secs(1));
+            first_rejected.get_or_insert(call);
+        }
+    }
+
+    assert_eq!(first_rejected, Some(6));
+    assert_eq!(
+        processor.system().accounts.get("acc1").unwrap().balance(),
+        Decimal::new(4, 0)
+    );
+}
+
+#[test]
+fn logging_middleware_reports_commands_in_display_form() {
+    let (sender, receiver) = std::sync::mpsc::channel();
+    let logger = LoggingMiddleware::new(move |line: &str| sender.send(line.to_string()).unwrap());
+    let mut processor = MemImgProcessor::new_simple(Bank::new(), Box::new(MemoryEventStorage::new())).unwrap().with_middleware(logger);
+
+    processor.execute_command(BankCommand::CreateAccount { id: "alice".into(), name: "Alice".to_string(), opening_balance: None }).unwrap();
+    processor.execute_command(deposit("alice", 100)).unwrap();
+    let _ = processor.execute_command(BankCommand::Withdrawal { account_id: "alice".into(), amount: Decimal::from(150) });
+
+    let lines: Vec<String> = receiver.try_iter().collect();
+    assert_eq!(lines[0], "ok: CreateAccount(id=alice, name=Alice)");
+    assert_eq!(lines[1], "ok: Deposit(account=alice, amount=$100.00)");
+    assert!(lines[2].starts_with("failed: Withdrawal(account=alice, amount=$150.00): "), "{}", lines[2]);
+    assert_eq!(lines.len(), 3);
+}
+
+#[test]
+fn explains_transfer_with_account_context() {
+    let mut bank = Bank::new();
+    for (id, name) in [("alice", "Alice"), ("bob", "Bob")] {
+        BankCommand::CreateAccount {
+            id: id.into(),
+            name: name.to_string(),
+            opening_balance: None,
+        }
+        .apply_to(&mut bank)
+        .unwrap();
+    }
+    BankCommand::Deposit {
+        account_id: "alice".into(),
+        amount: Decimal::new(100, 0),
+    }
+    .apply_to(&mut bank)
+    .unwrap();
+
+    let transfer = BankCommand::Transfer {
+        from_account_id: "alice".into(),
+        to_account_id: "bob".into(),
+        amount: Decimal::new(30, 0),
+    };
+    let explanation = transfer.explain(&bank);
+
+    assert!(explanation.contains("Alice"));
+    assert!(explanation.contains("Bob"));
+    assert!(explanation.contains("$30"));
+    assert!(explanation.contains("balance $100"));
+    assert_eq!(bank.accounts.get("alice").unwrap().balance(), Decimal::new(100, 0));
+}
+
+#[test]
+fn bulk_creates_accounts_in_one_command() {
+    let bank = Bank::new();
+    let storage = Box::new(MemoryEventStorage::new());
+    let mut processor = MemImgProcessor::new_simple(bank, storage).unwrap();
+
+    let accounts = (0..1000)
+        .map(|i| (format!("acc{}", i).into(), format!("Customer {}", i)))
+        .collect();
+    processor
+        .execute_command(BankCommand::BulkCreateAccounts { accounts })
+        .unwrap();
+
+    processor.assert_state(|bank| {
+        assert_eq!(bank.accounts.len(), 1000);
+        assert_eq!(bank.accounts["acc999"].name, "Customer 999");
+    });
+    assert_eq!(processor.event_storage.events().len(), 1);
+}
+
+#[test]
+fn bulk_create_rejects_duplicates_naming_first_conflict() {
+    let bank = Bank::new();
+    let storage = Box::new(MemoryEventStorage::new());
+    let mut processor = MemImgProcessor::new_simple(bank, storage).unwrap();
+
+    processor
+        .execute_command(BankCommand::CreateAccount {
+            id: "acc1".into(),
+            name: "Alice".to_string(),
+            opening_balance: None,
+        })
+        .unwrap();
+
+    let within_batch = processor.execute_command(BankCommand::BulkCreateAccounts {
+        accounts: vec![
+            ("acc2".into(), "Bob".to_string()),
+            ("acc3".into(), "Carol".to_string()),
+            ("acc2".into(), "Bobby".to_string()),
+        ],
+    });
+    assert!(within_batch.unwrap_err().to_string().contains("Duplicate account ID: acc2"));
+
+    let with_existing = processor.execute_command(BankCommand::BulkCreateAccounts {
+        accounts: vec![
+            ("acc4".into(), "Dave".to_string()),
+            ("acc1".into(), "Alicia".to_string()),
+        ],
+    });
+    assert!(with_existing.unwrap_err().to_string().contains("Duplicate account ID: acc1"));
+
+    processor.assert_state(|bank| {
+        assert_eq!(bank.accounts.len(), 1);
+        assert_eq!(bank.accounts["acc1"].name, "Alice");
+    });
+}
+
+#[test]
+fn memory_storage_conforms_to_event_storage_contract() {
+    assert_storage_conformance(MemoryEventStorage::new);
+}
+
+#[test]
+fn text_file_storage_conforms_to_event_storage_contract() {
+    let run = std::sync::atomic::AtomicUsize::new(0);
+    let dir = std::env::temp_dir().join("test_text_file_conformance");
+    let _ = std::fs::remove_dir_all(&dir);
+
+    assert_storage_conformance(|| {
+        let n = run.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        TextFileEventStorage::new(dir.join(format!("events-{}.json", n)), BankJsonConverter::new()).unwrap()
+    });
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn validate_replay_matches_live_state() {
+    let mut processor = MemImgProcessor::new_simple(Bank::new(), Box::new(MemoryEventStorage::new())).unwrap();
+    processor.execute_command(BankCommand::CreateAccount { id: "alice".into(), name: "Alice".to_string(), opening_balance: None }).unwrap();
+    processor.execute_command(BankCommand::Deposit { account_id: "alice".into(), amount: Decimal::from(100) }).unwrap();
+
+    let result = processor.validate_replay().unwrap();
+    assert!(!result.diverged);
+    assert_replay_deterministic(&mut processor);
+    assert_eq!(result.live_accounts, 1);
+    assert_eq!(result.replayed_accounts, 1);
+    assert!(result.divergent_keys.is_empty());
+}
+
+#[test]
+fn validate_replay_detects_out_of_band_mutation() {
+    let mut processor = MemImgProcessor::new_simple(Bank::new(), Box::new(MemoryEventStorage::new())).unwrap();
+    processor.execute_command(BankCommand::CreateAccount { id: "alice".into(), name: "Alice".to_string(), opening_balance: None }).unwrap();
+
+    processor.system_mut().accounts.get_mut("alice").unwrap().total_credits = Decimal::from(5);
+    processor.system_mut().accounts.insert("ghost".into(), rmemimg::memimg::bank::Account::new("ghost", "Ghost".to_string()));
+
+    let result = processor.validate_replay().unwrap();
+    assert!(result.diverged);
+    assert_eq!(result.live_accounts, 2);
+    assert_eq!(result.replayed_accounts, 1);
+    assert_eq!(result.divergent_keys, vec!["alice".to_string(), "ghost".to_string()]);
+}
+
+// Domain with a command that reads a process-wide counter, so replay cannot reproduce it
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Tickets {
+    issued: BTreeMap<String, u64>,
+}
+
+static NEXT_STAMP: AtomicU64 = AtomicU64::new(1);
+
+impl Tickets {
+    fn apply_issue(&mut self, holder: &str) -> Result<(), Infallible> {
+        self.issued.insert(holder.to_string(), 0);
+        Ok(())
+    }
+
+    fn apply_issue_stamped(&mut self, holder: &str) -> Result<(), Infallible> {
+        self.issued.insert(holder.to_string(), NEXT_STAMP.fetch_add(1, Ordering::SeqCst));
+        Ok(())
+    }
+}
+
+impl rmemimg::memimg::StateDiff for Tickets {
+    fn entity_count(&self) -> usize {
+        self.issued.len()
+    }
+
+    fn diff_keys(&self, other: &Self) -> Vec<String> {
+        self.issued
+            .iter()
+            .filter(|(holder, stamp)| other.issued.get(*holder) != Some(stamp))
+            .map(|(holder, _)| holder.clone())
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Command)]
+#[command(system = "Tickets")]
+enum TicketCommand {
+    #[command(handler = "apply_issue")]
+    Issue(String),
+    #[command(handler = "apply_issue_stamped")]
+    IssueStamped(String),
+}
+
+#[test]
+fn determinism_check_names_the_divergent_key() {
+    let mut processor = MemImgProcessor::new_simple(Tickets::default(), Box::new(MemoryEventStorage::new())).unwrap();
+    processor.execute_command(TicketCommand::Issue("alice".to_string())).unwrap();
+    processor.execute_command(TicketCommand::IssueStamped("bob".to_string())).unwrap();
+    processor.execute_command(TicketCommand::Issue("carol".to_string())).unwrap();
+
+    let report = check_replay_deterministic(&mut processor).unwrap().unwrap();
+
+    assert_eq!(report.first_divergent_key(), Some("bob"));
+    assert_eq!(report.to_string(), r#"replayed state first diverges at key "bob" (1 divergent: bob)"#);
+}
+
+#[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+struct Ping {
+    seq: u32,
+}
+
+impl rmemimg::memimg::JsonEvent for Ping {}
+
+#[test]
+fn json_event_round_trips_without_a_converter() {
+    let test_file = std::env::temp_dir().join("test_json_event_ping.json");
+    let _ = std::fs::remove_file(&test_file);
+
+    let mut storage = TextFileEventStorage::<Ping, _>::json(&test_file).unwrap();
+    storage.append(&Ping { seq: 1 }).unwrap();
+    storage.append(&Ping { seq: 2 }).unwrap();
+    drop(storage);
+
+    let mut storage = TextFileEventStorage::<Ping, _>::json(&test_file).unwrap();
+    let mut replayed = Vec::new();
+    storage.replay(&mut |ping| {
+        replayed.push(ping);
+        Ok(())
+    }).unwrap();
+    assert_eq!(replayed, vec![Ping { seq: 1 }, Ping { seq: 2 }]);
+
+    let _ = std::fs::remove_file(&test_file);
+}
+
+#[test]
+fn event_version_counts_successful_commands() {
+    let mut processor = MemImgProcessor::new_simple(Bank::new(), Box::new(MemoryEventStorage::new())).unwrap();
+    assert_eq!(processor.event_version(), EventId(0));
+
+    processor.execute_command(BankCommand::CreateAccount { id: "alice".into(), name: "Alice".to_string(), opening_balance: None }).unwrap();
+    processor.execute_command(BankCommand::Deposit { account_id: "alice".into(), amount: Decimal::from(10) }).unwrap();
+    processor.execute_command(BankCommand::Withdrawal { account_id: "alice".into(), amount: Decimal::from(50) }).unwrap_err();
+
+    assert_eq!(processor.event_version(), EventId(2));
+    assert_eq!(processor.event_storage.version().unwrap(), 2);
+}
+
+#[test]
+fn event_version_resumes_from_replayed_log() {
+    let test_file = std::env::temp_dir().join("test_event_version.json");
+    let _ = std::fs::remove_file(&test_file);
+
+    let storage = TextFileEventStorage::new(&test_file, BankJsonConverter::new()).unwrap();
+    let mut processor = MemImgProcessor::new_simple(Bank::new(), Box::new(storage)).unwrap();
+    processor.execute_command(BankCommand::CreateAccount { id: "alice".into(), name: "Alice".to_string(), opening_balance: None }).unwrap();
+    processor.execute_command(BankCommand::CreateAccount { id: "bob".into(), name: "Bob".to_string(), opening_balance: None }).unwrap();
+    drop(processor);
+
+    let storage = TextFileEventStorage::new(&test_file, BankJsonConverter::new()).unwrap();
+    let mut processor = MemImgProcessor::new_simple(Bank::new(), Box::new(storage)).unwrap();
+    assert_eq!(processor.event_version(), EventId(2));
+    assert_eq!(processor.event_storage.version().unwrap(), 2);
+
+    let _ = std::fs::remove_file(&test_file);
+}
+
+#[test]
+fn closed_accounts_report_a_distinct_error() {
+    let storage = Box::new(MemoryEventStorage::new());
+    let mut processor = MemImgProcessor::new_simple(Bank::new(), storage).unwrap();
+    processor.execute_command(BankCommand::CreateAccount { id: "alice".into(), name: "Alice".to_string(), opening_balance: None }).unwrap();
+    processor.execute_command(BankCommand::CloseAccount { id: "alice".into() }).unwrap();
+
+    let deposit = |account_id: &str| BankCommand::Deposit { account_id: account_id.into(), amount: Decimal::from(10) };
+    let domain_error = |error: MemImgError| error.outcome().unwrap().source.downcast_ref::<BankError>().cloned().unwrap();
+
+    assert_eq!(
+        domain_error(processor.execute_command(deposit("alice")).unwrap_err()),
+        BankError::AccountClosed("alice".to_string())
+    );
+    assert_eq!(
+        domain_error(processor.execute_command(deposit("nobody")).unwrap_err()),
+        BankError::AccountNotFound("nobody".to_string())
+    );
+
+    // The closed set is rebuilt by replay
+    let storage = Box::new(MemoryEventStorage::with_events(processor.event_storage.events().to_vec()));
+    let mut replayed = MemImgProcessor::new_simple(Bank::new(), storage).unwrap();
+    assert_eq!(
+        domain_error(replayed.execute_command(deposit("alice")).unwrap_err()),
+        BankError::AccountClosed("alice".to_string())
+    );
+}
+
+#[test]
+fn close_account_requires_zero_balance() {
+    let storage = Box::new(MemoryEventStorage::new());
+    let mut processor = MemImgProcessor::new_simple(Bank::new(), storage).unwrap();
+    processor.execute_command(BankCommand::CreateAccount { id: "alice".into(), name: "Alice".to_string(), opening_balance: None }).unwrap();
+    processor.execute_command(BankCommand::Deposit { account_id: "alice".into(), amount: Decimal::from(5) }).unwrap();
+
+    let error = processor.execute_command(BankCommand::CloseAccount { id: "alice".into() }).unwrap_err();
+    assert_eq!(error.outcome().unwrap().source.to_string(), "Account alice cannot be closed with a balance of 5");
+    assert!(processor.system().accounts.contains_key("alice"));
+}
+
+#[test]
+fn joint_owners_survive_replay_and_last_owner_stays() {
+    let storage = Box::new(MemoryEventStorage::new());
+    let mut processor = MemImgProcessor::new_simple(Bank::new(), storage).unwrap();
+    processor.execute_command(BankCommand::CreateAccount { id: "joint".into(), name: "Alice".to_string(), opening_balance: None }).unwrap();
+    processor.execute_command(BankCommand::CreateAccount { id: "solo".into(), name: "Bob".to_string(), opening_balance: None }).unwrap();
+    processor.execute_command(BankCommand::AddOwner { account_id: "joint".into(), owner: "Bob".to_string() }).unwrap();
+    processor.execute_command(BankCommand::AddOwner { account_id: "joint".into(), owner: "Carol".to_string() }).unwrap();
+
+    let ids = |processor: &MemImgProcessor<Bank, BankCommand, MemoryEventStorage<BankCommand>>, owner: &str| -> Vec<String> {
+        let query = GetAccountsByOwner { owner: owner.to_string() };
+        processor.execute_query(&query).unwrap().into_iter().map(|account| account.id.to_string()).collect()
+    };
+    assert_eq!(ids(&processor, "Alice"), vec!["joint"]);
+    assert_eq!(ids(&processor, "Bob"), vec!["joint", "solo"]);
+    assert_eq!(ids(&processor, "Carol"), vec!["joint"]);
+
+    let storage = Box::new(MemoryEventStorage::with_events(processor.event_storage.events().to_vec()));
+    let mut replayed = MemImgProcessor::new_simple(Bank::new(), storage).unwrap();
+    assert_eq!(replayed.system().accounts["joint"].owners, vec!["Alice", "Bob", "Carol"]);
+
+    let error = replayed.execute_command(BankCommand::RemoveOwner { account_id: "solo".into(), owner: "Bob".to_string() }).unwrap_err();
+    assert_eq!(
+        error.outcome().unwrap().source.downcast_ref::<BankError>(),
+        Some(&BankError::LastOwner { account_id: "solo".into(), owner: "Bob".to_string() })
+    );
+    assert_eq!(ids(&replayed, "Bob"), vec!["joint", "solo"]);
+}
+
+#[test]
+fn event_ids_advance_by_one() {
+    assert_eq!(EventId::first(), EventId(1));
+    assert_eq!(EventId(1).next(), EventId(2));
+    assert_eq!(EventId::from(7).as_u64(), 7);
+    assert_eq!(u64::from(EventId(3)), 3);
+    assert!(EventId(2) > EventId::first());
+}
+
+#[test]
+fn failed_append_leaves_state_untouched_under_either_commit_strategy() {
+    for strategy in [CommitStrategy::ApplyThenAppend, CommitStrategy::AppendThenApply] {
+        let mut processor =
+            MemImgProcessor::new_with_commit_strategy(Bank::new(), failing_append_storage(), strategy).unwrap();
+
+        let result = processor.execute_command(BankCommand::CreateAccount { id: "alice".into(), name: "Alice".to_string(), opening_balance: None });
+
+        assert!(matches!(result, Err(MemImgError::SystemFailure(_))), "{:?}", strategy);
+        assert!(processor.system().accounts.is_empty(), "{:?}", strategy);
+        assert!(processor.is_poisoned(), "{:?}", strategy);
+        assert_eq!(processor.event_version(), EventId(0), "{:?}", strategy);
+    }
+}
+
+#[test]
+fn append_then_apply_keeps_rejected_events_and_skips_them_on_replay() {
+    let storage = Box::new(MemoryEventStorage::new());
+    let mut processor =
+        MemImgProcessor::new_with_commit_strategy(Bank::new(), storage, CommitStrategy::AppendThenApply).unwrap();
+    processor.execute_command(BankCommand::CreateAccount { id: "alice".into(), name: "Alice".to_string(), opening_balance: None }).unwrap();
+    let rejected = processor.execute_command(BankCommand::Withdrawal { account_id: "alice".into(), amount: Decimal::from(5) });
+    assert!(matches!(rejected, Err(MemImgError::CommandFailure(_))));
+    processor.execute_command(BankCommand::Deposit { account_id: "alice".into(), amount: Decimal::from(7) }).unwrap();
+
+    // The rejected withdrawal was appended before it failed
+    assert_eq!(processor.event_storage.events().len(), 3);
+    assert_eq!(processor.event_version(), EventId(3));
+
+    assert_replay_deterministic(&mut processor);
+    let storage = Box::new(MemoryEventStorage::with_events(processor.event_storage.events().to_vec()));
+    let replayed =
+        MemImgProcessor::new_with_commit_strategy(Bank::new(), storage, CommitStrategy::AppendThenApply).unwrap();
+    assert_eq!(replayed.warnings().len(), 1);
+    assert_eq!(replayed.warnings()[0].kind, WarningKind::RejectedEvent);
+    assert_eq!(replayed.warnings()[0].index, 2);
+
+    // The default strategy treats the same log as corrupt
+    let storage = Box::new(MemoryEventStorage::with_events(processor.event_storage.events().to_vec()));
+    assert!(matches!(MemImgProcessor::new_simple(Bank::new(), storage), Err(MemImgError::SystemFailure(_))));
+}
+
+#[test]
+fn write_ahead_intents_drop_rejected_and_uncommitted_events() {
+    let log = std::env::temp_dir().join("test_write_ahead_intents.json");
+    let _ = std::fs::remove_file(&log);
+    let open = || {
+        let storage = Box::new(TextFileEventStorage::new(&log, BankJsonConverter::new()).unwrap().with_write_ahead_intents());
+        MemImgProcessor::new_with_commit_strategy(Bank::new(), storage, CommitStrategy::AppendThenApply).unwrap()
+    };
+    let event_lines = || std::fs::read_to_string(&log).unwrap().lines().filter(|line| !line.starts_with(MARKER_PREFIX)).count();
+
+    let mut processor = open();
+    processor.execute_command(BankCommand::CreateAccount { id: "alice".into(), name: "Alice".to_string(), opening_balance: None }).unwrap();
+    let rejected = processor.execute_command(BankCommand::Withdrawal { account_id: "alice".into(), amount: Decimal::from(5) });
+    assert!(matches!(rejected, Err(MemImgError::CommandFailure(_))));
+    processor.execute_command(deposit("alice", 7)).unwrap();
+    // The rejected withdrawal was taken back out of the log
+    assert_eq!(processor.event_version(), EventId(2));
+    assert_eq!(event_lines(), 2);
+    assert!(processor.event_storage.markers().unwrap().is_empty());
+
+    // A crash between append and commit: the deposit reaches the file, its commit record never does
+    processor.event_storage.append(&deposit("alice", 100)).unwrap();
+    drop(processor);
+    assert_eq!(event_lines(), 3);
+
+    let mut processor = open();
+    assert_eq!(processor.system().accounts["alice"].balance(), Decimal::from(7));
+    assert_eq!(processor.event_version(), EventId(2));
+    assert_eq!(processor.warnings().len(), 1);
+    assert_eq!(processor.warnings()[0].kind, WarningKind::DiscardedIntent);
+    assert_eq!(event_lines(), 2);
+
+    processor.execute_command(deposit("alice", 1)).unwrap();
+    drop(processor);
+    let processor = open();
+    assert_eq!(processor.system().accounts["alice"].balance(), Decimal::from(8));
+    assert!(processor.warnings().is_empty());
+    let _ = std::fs::remove_file(&log);
+}
+
+#[test]
+fn double_entry_validator_accepts_every_bank_command() {
+    let storage = Box::new(MemoryEventStorage::new());
+    let mut processor = MemImgProcessor::new_simple(Bank::new(), storage).unwrap().with_validator(DoubleEntryValidator);
+
+    let commands = vec![
+        BankCommand::CreateAccount { id: "alice".into(), name: "Alice".to_string(), opening_balance: None },
+        BankCommand::BulkCreateAccounts { accounts: vec![("bob".into(), "Bob".to_string()), ("carol".into(), "Carol".to_string())] },
+        BankCommand::Deposit { account_id: "alice".into(), amount: Decimal::from(100) },
+        BankCommand::Transfer { from_account_id: "alice".into(), to_account_id: "bob".into(), amount: Decimal::from(30) },
+        BankCommand::Withdrawal { account_id: "bob".into(), amount: Decimal::from(10) },
+        BankCommand::CloseAccount { id: "carol".into() },
+    ];
+    for command in commands {
+        processor.execute_command(command).unwrap();
+    }
+    // Domain failures are still reported as themselves, not as invariant violations
+    let overdraw = processor.execute_command(BankCommand::Transfer {
+        from_account_id: "bob".into(),
+        to_account_id: "alice".into(),
+        amount: Decimal::from(500),
+    });
+    assert!(overdraw.unwrap_err().outcome().unwrap().source.downcast_ref::<BankError>().is_some());
+
+    assert_eq!(processor.system().equity_capital, Decimal::from(90));
+}
+
+#[test]
+fn double_entry_validator_rejects_unbalanced_state() {
+    let storage = Box::new(MemoryEventStorage::new());
+    let mut processor = MemImgProcessor::new_simple(Bank::new(), storage).unwrap().with_validator(DoubleEntryValidator);
+    processor.execute_command(BankCommand::CreateAccount { id: "alice".into(), name: "Alice".to_string(), opening_balance: None }).unwrap();
+
+    // Credits conjured out of band have no matching debit or capital
+    processor.system_mut().accounts.get_mut("alice").unwrap().total_credits = Decimal::from(50);
+
+    let error = processor
+        .execute_command(BankCommand::CreateAccount { id: "bob".into(), name: "Bob".to_string(), opening_balance: None })
+        .unwrap_err();
+    let violation = error.outcome().unwrap().source.downcast_ref::<SystemInvariantViolation>().unwrap();
+    assert_eq!(
+        violation,
+        &SystemInvariantViolation::DoubleEntryMismatch { expected_credits: Decimal::ZERO, actual_credits: Decimal::from(50) }
+    );
+    assert!(!processor.system().accounts.contains_key("bob"));
+    assert_eq!(processor.event_storage.events().len(), 1);
+}
+
+#[test]
+fn invariant_monitor_records_violations_without_rejecting() {
+    let monitor = Arc::new(Mutex::new(InvariantMonitor::new(MonitorMode::Record).with_validator(DoubleEntryValidator)));
+    let storage = Box::new(MemoryEventStorage::new());
+    let mut processor = MemImgProcessor::new_simple(Bank::new(), storage).unwrap().with_monitor(monitor.clone());
+    processor.execute_command(BankCommand::CreateAccount { id: "alice".into(), name: "Alice".to_string(), opening_balance: None }).unwrap();
+    for _ in 0..8 {
+        processor.execute_command(BankCommand::Deposit { account_id: "alice".into(), amount: Decimal::from(10) }).unwrap();
+    }
+    assert_eq!(monitor.lock().unwrap().violation_count(), 0);
+
+    // Credits conjured out of band break double entry for the tenth command, which still commits
+    processor.system_mut().accounts.get_mut("alice").unwrap().total_credits += Decimal::from(50);
+    processor.execute_command(BankCommand::CreateAccount { id: "bob".into(), name: "Bob".to_string(), opening_balance: None }).unwrap();
+    assert!(processor.system().accounts.contains_key("bob"));
+    assert_eq!(processor.event_storage.events().len(), 10);
+
+    let mut monitor = monitor.lock().unwrap();
+    assert_eq!(monitor.violation_count(), 1);
+    assert!(monitor.violations()[0].1.starts_with("Double-entry mismatch"));
+    monitor.clear_violations();
+    assert_eq!(monitor.violation_count(), 0);
+}
+
+#[test]
+fn enforcing_invariant_monitor_rejects_and_records() {
+    let monitor = Arc::new(Mutex::new(InvariantMonitor::new(MonitorMode::Enforce).with_validator(DoubleEntryValidator)));
+    let storage = Box::new(MemoryEventStorage::new());
+    let mut processor = MemImgProcessor::new_simple(Bank::new(), storage).unwrap().with_monitor(monitor.clone());
+    processor.execute_command(BankCommand::CreateAccount { id: "alice".into(), name: "Alice".to_string(), opening_balance: None }).unwrap();
+    processor.system_mut().accounts.get_mut("alice").unwrap().total_credits = Decimal::from(50);
+
+    let error = processor
+        .execute_command(BankCommand::CreateAccount { id: "bob".into(), name: "Bob".to_string(), opening_balance: None })
+        .unwrap_err();
+    assert!(error.outcome().unwrap().source.downcast_ref::<SystemInvariantViolation>().is_some());
+    assert!(!processor.system().accounts.contains_key("bob"));
+    assert_eq!(processor.event_storage.events().len(), 1);
+    assert_eq!(monitor.lock().unwrap().violation_count(), 1);
+}
+
+#[test]
+fn snapshot_view_is_unaffected_by_later_commands() {
+    let storage = Box::new(MemoryEventStorage::new());
+    let mut processor = MemImgProcessor::new_simple(Bank::new(), storage).unwrap();
+    processor.execute_command(BankCommand::CreateAccount { id: "alice".into(), name: "Alice".to_string(), opening_balance: None }).unwrap();
+    processor.execute_command(BankCommand::Deposit { account_id: "alice".into(), amount: Decimal::from(100) }).unwrap();
+
+    let view = processor.snapshot_view();
+
+    processor.execute_command(BankCommand::Withdrawal { account_id: "alice".into(), amount: Decimal::from(60) }).unwrap();
+    processor.execute_command(BankCommand::CreateAccount { id: "bob".into(), name: "Bob".to_string(), opening_balance: None }).unwrap();
+
+    assert_eq!(view.version(), EventId(2));
+    assert_eq!(view.execute_query(&GetBalance { account_id: "alice".into() }).unwrap(), Decimal::from(100));
+    assert!(view.execute_query(&GetBalance { account_id: "bob".into() }).is_err());
+    assert_eq!(view.clone().system().accounts.len(), 1);
+    assert_eq!(processor.execute_query(&GetBalance { account_id: "alice".into() }).unwrap(), Decimal::from(40));
+}
+
+#[test]
+fn buffered_storage_holds_events_until_flushed() {
+    let test_file = std::env::temp_dir().join("test_buffered_durability.json");
+    let _ = std::fs::remove_file(&test_file);
+
+    let mut storage = TextFileEventStorage::new(&test_file, BankJsonConverter::new()).unwrap().with_durability(Durability::Buffered);
+    storage.append(&BankCommand::CreateAccount { id: "alice".into(), name: "Alice".to_string(), opening_balance: None }).unwrap();
+    assert_eq!(std::fs::read_to_string(&test_file).unwrap(), "");
+
+    storage.flush().unwrap();
+    assert_eq!(std::fs::read_to_string(&test_file).unwrap().lines().count(), 1);
+
+    let _ = std::fs::remove_file(&test_file);
+}
+
+#[test]
+fn flush_every_writes_buffered_events_in_batches() {
+    let test_file = std::env::temp_dir().join("test_flush_every.json");
+    let _ = std::fs::remove_file(&test_file);
+
+    let mut storage = TextFileEventStorage::new(&test_file, BankJsonConverter::new()).unwrap().with_durability(Durability::Buffered).with_flush_every(2);
+    for (round, id) in ["alice", "bob", "carol"].into_iter().enumerate() {
+        storage.append(&BankCommand::CreateAccount { id: id.into(), name: id.to_uppercase(), opening_balance: None }).unwrap();
+        let written = std::fs::read_to_string(&test_file).unwrap().lines().count();
+        assert_eq!(written, [0, 2, 2][round]);
+    }
+
+    let _ = std::fs::remove_file(&test_file);
+}
+
+#[test]
+fn successive_deposits_return_increasing_sequence_numbers() {
+    let mut processor = MemImgProcessor::new_simple(Bank::new(), Box::new(MemoryEventStorage::new())).unwrap();
+    processor
+        .execute_command(BankCommand::CreateAccount { id: "alice".into(), name: "Alice".to_string(), opening_balance: None })
+        .unwrap();
+
+    let first = processor.execute_command(deposit("alice", 10)).unwrap();
+    let second = processor.execute_command(deposit("alice", 20)).unwrap();
+    processor.execute_command(deposit("nobody", 5)).unwrap_err();
+    let third = processor.execute_command(deposit("alice", 30)).unwrap();
+
+    assert_eq!((first.seq, second.seq, third.seq), (2, 3, 4));
+    assert_eq!(third.seq, processor.event_storage.version().unwrap());
+    assert_eq!(EventId(third.seq), processor.event_version());
+}
+
+#[test]
+fn receipts_report_whether_the_event_is_already_durable() {
+    let test_file = std::env::temp_dir().join("test_receipt_durability.json");
+    let create = || BankCommand::CreateAccount { id: "alice".into(), name: "Alice".to_string(), opening_balance: None };
+
+    for (durability, durable) in [(Durability::EveryEvent, true), (Durability::Buffered, false)] {
+        let _ = std::fs::remove_file(&test_file);
+        let storage = TextFileEventStorage::new(&test_file, BankJsonConverter::new()).unwrap().with_durability(durability);
+        let mut processor = MemImgProcessor::new_simple(Bank::new(), Box::new(storage)).unwrap();
+
+        let receipt = processor.execute_command(create()).unwrap();
+
+        assert_eq!(receipt.seq, 1);
+        assert_eq!(receipt.durable, durable, "{:?}", durability);
+    }
+
+    let _ = std::fs::remove_file(&test_file);
+}
+
+#[test]
+fn statistics_count_commands_queries_and_events() {
+    let mut processor = MemImgProcessor::new_simple(Bank::new(), Box::new(MemoryEventStorage::new())).unwrap();
+    assert_eq!(processor.statistics().last_command_at, None);
+    let started = chrono::Utc::now();
+
+    processor
+        .execute_command(BankCommand::CreateAccount { id: "alice".into(), name: "Alice".to_string(), opening_balance: None })
+        .unwrap();
+    processor.execute_command(deposit("alice", 10)).unwrap();
+    processor.execute_command(deposit("nobody", 5)).unwrap_err();
+    processor.execute_query(&GetBalance { account_id: "alice".into() }).unwrap();
+    processor.execute_query(&GetBalance { account_id: "nobody".into() }).unwrap_err();
+
+    let statistics = processor.statistics();
+    assert_eq!(statistics.total_commands_executed, 2);
+    assert_eq!(statistics.total_commands_failed, 1);
+    assert_eq!(statistics.total_queries_executed, 1);
+    assert_eq!(statistics.total_queries_failed, 1);
+    assert!(statistics.last_command_at.unwrap() >= started);
+    assert!(statistics.uptime <= processor.statistics().uptime);
+    assert_eq!(statistics.system_event_count, 2);
+}
+
+// Converter that cannot format deposits into one account, to fail partway through a group
+struct RefusingConverter(&'static str);
+
+impl TextConverter<BankCommand> for RefusingConverter {
+    fn parse(&self, text: &str) -> Result<BankCommand, Box<dyn std::error::Error + Send + Sync>> {
+        BankJsonConverter::new().parse(text)
+    }
+
+    fn format(&self, command: &BankCommand) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+        match command {
+            BankCommand::Deposit { account_id, .. } if *account_id == self.0 => Err(format!("refusing deposit into {}", account_id).into()),
+            command => BankJsonConverter::new().format(command),
+        }
+    }
+}
+
+#[test]
+fn atomic_append_writes_a_debit_credit_pair_whole_or_not_at_all() {
+    let test_file = std::env::temp_dir().join("test_atomic_append.json");
+    let _ = std::fs::remove_file(&test_file);
+    let debit_credit = |to: &str| {
+        [
+            BankCommand::Withdrawal { account_id: "alice".into(), amount: Decimal::from(10) },
+            deposit(to, 10),
+        ]
+    };
+
+    let mut storage = TextFileEventStorage::new(&test_file, RefusingConverter("mallory")).unwrap().with_durability(Durability::Buffered);
+    storage.append(&BankCommand::CreateAccount { id: "alice".into(), name: "Alice".to_string(), opening_balance: Some(Decimal::from(100)) }).unwrap();
+    storage.append(&BankCommand::CreateAccount { id: "bob".into(), name: "Bob".to_string(), opening_balance: None }).unwrap();
+
+    storage.append_atomic(&debit_credit("mallory")).unwrap_err();
+    storage.append_atomic(&debit_credit("bob")).unwrap();
+    drop(storage);
+
+    let mut storage = TextFileEventStorage::new(&test_file, RefusingConverter("mallory")).unwrap();
+    let mut events = Vec::new();
+    storage.replay(&mut |event| {
+        events.push(event);
+        Ok(())
+    }).unwrap();
+    assert_eq!(events.len(), 4);
+    assert_eq!(events[2..], debit_credit("bob"));
+
+    let _ = std::fs::remove_file(&test_file);
+}
+
+#[test]
+fn auto_flush_writes_buffered_events_while_idle() {
+    let test_file = std::env::temp_dir().join("test_auto_flush.json");
+    let _ = std::fs::remove_file(&test_file);
+
+    let mut storage = TextFileEventStorage::new(&test_file, BankJsonConverter::new())
+        .unwrap()
+        .with_durability(Durability::Buffered)
+        .with_auto_flush(std::time::Duration::from_millis(20));
+    storage.append(&BankCommand::CreateAccount { id: "alice".into(), name: "Alice".to_string(), opening_balance: None }).unwrap();
+
+    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+    while std::fs::read_to_string(&test_file).unwrap().is_empty() {
+        assert!(std::time::Instant::now() < deadline, "auto-flush never wrote the buffered event");
+        std::thread::sleep(std::time::Duration::from_millis(10));
+    }
+    assert_eq!(std::fs::read_to_string(&test_file).unwrap().lines().count(), 1);
+
+    // Shutdown stops the timer and flushes whatever is still buffered
+    storage.append(&BankCommand::CreateAccount { id: "bob".into(), name: "Bob".to_string(), opening_balance: None }).unwrap();
+    storage.shutdown().unwrap();
+    assert_eq!(std::fs::read_to_string(&test_file).unwrap().lines().count(), 2);
+
+    let _ = std::fs::remove_file(&test_file);
+}
+
+fn deposit(account_id: &str, amount: i64) -> BankCommand {
+    BankCommand::Deposit {
+        account_id: account_id.into(),
+        amount: Decimal::new(amount, 0),
+    }
+}
+
+#[test]
+fn restores_identical_state_after_checkpoint_and_compact() {
+    let test_file = std::env::temp_dir().join("test_checkpoint_compact.json");
+    let _ = std::fs::remove_file(&test_file);
+    let format = SnapshotFormat::new(1);
+
+    let mut snapshot = Vec::new();
+    let (live_bank, live_version) = {
+        let storage = Box::new(TextFileEventStorage::new(&test_file, BankJsonConverter::new()).unwrap());
+        let mut processor = MemImgProcessor::new_simple(Bank::new(), storage).unwrap();
+        processor
+            .execute_command(BankCommand::CreateAccount {
+                id: "acc1".into(),
+                name: "Alice".to_string(),
+                opening_balance: None,
+            })
+            .unwrap();
+        processor.execute_command(deposit("acc1", 100)).unwrap();
+
+        let result = processor.checkpoint_and_compact(&format, &mut snapshot).unwrap();
+        assert_eq!(result.events_removed, 2);
+        assert_eq!(result.snapshot_size_bytes, snapshot.len() as u64);
+        assert_eq!(std::fs::metadata(&test_file).unwrap().len(), 0);
+
+        processor.execute_command(deposit("acc1", 50)).unwrap();
+        (processor.system().clone(), processor.event_version())
+    };
+
+    let snapshot = format.read::<Bank, _>(&snapshot[..]).unwrap();
+    let storage = Box::new(TextFileEventStorage::new(&test_file, BankJsonConverter::new()).unwrap());
+    let processor = MemImgProcessor::from_snapshot(snapshot, storage).unwrap();
+
+    assert_eq!(processor.system(), &live_bank);
+    assert_eq!(processor.event_version(), live_version);
+    assert_eq!(processor.event_version(), EventId(3));
+
+    let _ = std::fs::remove_file(&test_file);
+}
+
+#[test]
+fn failed_state_and_query_assertions_name_what_was_checked() {
+    let mut processor = MemImgProcessor::new_simple(Bank::new(), Box::new(MemoryEventStorage::new())).unwrap();
+    processor.execute_command(BankCommand::CreateAccount { id: "alice".into(), name: "Alice".to_string(), opening_balance: Some(Decimal::from(5)) }).unwrap();
+
+    let panic_message = |outcome: std::thread::Result<()>| *outcome.unwrap_err().downcast::<String>().unwrap();
+    let message = panic_message(std::panic::catch_unwind(AssertUnwindSafe(|| processor.assert_state(|bank| assert!(bank.accounts.is_empty(), "accounts remain")))));
+    assert!(message.starts_with("state assertion failed on rmemimg::memimg::bank::Bank at event 1: accounts remain"), "{}", message);
+
+    let query = GetBalance { account_id: "alice".into() };
+    let message = panic_message(std::panic::catch_unwind(AssertUnwindSafe(|| {
+        processor.assert_query(&query, |balance| assert_eq!(balance, Decimal::ZERO)).unwrap();
+    })));
+    assert!(message.starts_with("query assertion failed on rmemimg::memimg::bank::GetBalance at event 1: assertion `left == right` failed"), "{}", message);
+
+    let missing = processor.assert_query(&GetBalance { account_id: "nobody".into() }, |_| unreachable!());
+    assert!(matches!(missing, Err(MemImgError::CommandFailure(_))), "{:?}", missing);
+}
+
+#[test]
+fn live_reload_rebuilds_the_state_from_the_new_log_only() {
+    let create = |id: &str| BankCommand::CreateAccount { id: id.into(), name: id.to_string(), opening_balance: None };
+    let mut processor = MemImgProcessor::new_simple(Bank::new(), Box::new(MemoryEventStorage::new())).unwrap();
+    for command in [create("alice"), deposit("alice", 100)] {
+        processor.execute_command(command).unwrap();
+    }
+
+    let canary = MemoryEventStorage::with_events(vec![create("bob"), deposit("bob", 7), deposit("bob", 3)]);
+    let stats = processor.live_reload(Box::new(canary)).unwrap();
+    assert_eq!(stats, ReloadStats { events_before: 2, events_after: 3, new_events_applied: 1 });
+    processor.assert_state(|bank| {
+        assert!(!bank.accounts.contains_key("alice"));
+        assert_eq!(bank.accounts["bob"].balance(), Decimal::from(10));
+    });
+    assert_eq!(processor.event_version(), EventId(3));
+    processor.execute_command(deposit("bob", 1)).unwrap();
+    assert_eq!(processor.event_storage.events().len(), 4);
+
+    // A log that does not replay is not swapped in
+    let broken = MemoryEventStorage::with_events(vec![deposit("nobody", 1)]);
+    assert!(matches!(processor.live_reload(Box::new(broken)), Err(MemImgError::SystemFailure(_))));
+    assert_eq!(processor.event_version(), EventId(4));
+    processor.execute_command(deposit("bob", 1)).unwrap();
+    assert_eq!(processor.system().accounts["bob"].balance(), Decimal::from(12));
+}
+
+#[test]
+fn stream_storage_round_trips_a_log_through_an_in_memory_cursor() {
+    let open = |log: Vec<u8>| {
+        let storage = StreamEventStorage::new(Cursor::new(log), Cursor::new(Vec::new()), BankJsonConverter::new());
+        MemImgProcessor::new_simple(Bank::new(), Box::new(storage)).unwrap()
+    };
+    let mut processor = open(Vec::new());
+    processor.execute_command(BankCommand::CreateAccount { id: "alice".into(), name: "Alice".to_string(), opening_balance: None }).unwrap();
+    processor.execute_command(deposit("alice", 10)).unwrap();
+    assert!(processor.event_storage.append_marker("deployed v2").unwrap());
+    processor.execute_command(deposit("alice", 5)).unwrap();
+    let (_, sink) = processor.event_storage.into_parts();
+    let log = sink.into_inner();
+    assert_eq!(String::from_utf8_lossy(&log).lines().filter(|line| line.starts_with(MARKER_PREFIX)).count(), 1);
+
+    // The sink of one run is the source of the next, in the text file format
+    let mut reopened = open(log.clone());
+    assert_eq!(reopened.event_version(), EventId(3));
+    assert_eq!(reopened.system().accounts["alice"].balance(), Decimal::from(15));
+    reopened.execute_command(deposit("alice", 1)).unwrap();
+    assert_eq!(reopened.event_storage.version().unwrap(), 4);
+
+    let path = std::env::temp_dir().join("test_stream_storage_round_trip.json");
+    std::fs::write(&path, &log).unwrap();
+    let from_file = MemImgProcessor::new_simple(Bank::new(), Box::new(TextFileEventStorage::new_unlocked(&path, BankJsonConverter::new()).unwrap())).unwrap();
+    assert_eq!(from_file.system().accounts["alice"].balance(), Decimal::from(15));
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn stream_storage_writes_only_settled_intents_to_its_sink() {
+    let storage = StreamEventStorage::new(Cursor::new(Vec::new()), Cursor::new(Vec::new()), BankJsonConverter::new()).with_write_ahead_intents();
+    let mut processor = MemImgProcessor::new_with_commit_strategy(Bank::new(), Box::new(storage), CommitStrategy::AppendThenApply).unwrap();
+    processor.execute_command(BankCommand::CreateAccount { id: "alice".into(), name: "Alice".to_string(), opening_balance: None }).unwrap();
+    // Fails to apply, so its intent is dropped before it reaches the sink
+    assert!(processor.execute_command(deposit("nobody", 1)).is_err());
+    processor.execute_command(deposit("alice", 10)).unwrap();
+    // Appended but never settled, as if the process died before applying it
+    processor.event_storage.append(&deposit("alice", 5)).unwrap();
+
+    let (_, sink) = processor.event_storage.into_parts();
+    let log = String::from_utf8(sink.into_inner()).unwrap();
+    assert_eq!(log.lines().filter(|line| !line.starts_with(MARKER_PREFIX)).count(), 2);
+    assert_eq!(log.lines().filter(|line| *line == "#commit").count(), 2);
+
+    // The same framing as a text file's, so the file storage replays the sink as it is
+    let path = std::env::temp_dir().join("test_stream_storage_intents.json");
+    std::fs::write(&path, &log).unwrap();
+    let from_file = MemImgProcessor::new_simple(Bank::new(), Box::new(TextFileEventStorage::new_unlocked(&path, BankJsonConverter::new()).unwrap())).unwrap();
+    assert_eq!(from_file.event_version(), EventId(2));
+    assert_eq!(from_file.system().accounts["alice"].balance(), Decimal::from(10));
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn processor_from_a_piped_reader_answers_queries_but_takes_no_commands() {
+    let log = concat!(
+        r#"{"CreateAccount":{"id":"alice","name":"Alice"}}"#, "\n",
+        r#"{"Deposit":{"account_id":"alice","amount":"10"}}"#, "\n",
+        "# deployed v2\n",
+        r#"{"Deposit":{"account_id":"alice","amount":"5"}}"#, "\n",
+    );
+    let stdin = Cursor::new(log.as_bytes().to_vec());
+
+    let mut processor = MemImgProcessor::from_reader(Bank::new(), stdin, BankJsonConverter::new()).unwrap();
+    assert_eq!(processor.event_version(), EventId(3));
+    assert_eq!(processor.execute_query(&GetBalance { account_id: "alice".into() }).unwrap(), Decimal::from(15));
+
+    assert!(matches!(processor.execute_command(deposit("alice", 1)), Err(MemImgError::MaintenanceMode)));
+    // Even made writable, the stream takes no appends
+    processor.set_read_only(false);
+    assert!(processor.execute_command(deposit("alice", 1)).is_err());
+    let error = processor.event_storage.append_marker("note").unwrap_err();
+    assert_eq!(error.downcast_ref::<StorageError>().unwrap().source.kind(), ErrorKind::PermissionDenied);
+    assert_eq!(processor.system().accounts["alice"].balance(), Decimal::from(15));
+}
+
+#[test]
+fn rotated_storage_holds_the_events_from_before_and_after_the_rotation() {
+    let log = std::env::temp_dir().join("test_rotate_storage.json");
+    let _ = std::fs::remove_file(&log);
+    let mut processor = MemImgProcessor::new_simple(Bank::new(), Box::new(MemoryEventStorage::new())).unwrap();
+    let before = [
+        BankCommand::CreateAccount { id: "alice".into(), name: "Alice".to_string(), opening_balance: None },
+        deposit("alice", 10),
+        deposit("alice", 20),
+    ];
+    for command in before.clone() {
+        processor.execute_command(command).unwrap();
+    }
+
+    let file = Box::new(TextFileEventStorage::new(&log, BankJsonConverter::new()).unwrap());
+    let mut processor = processor.rotate_storage(file).unwrap();
+    let after = [deposit("alice", 1), BankCommand::Withdrawal { account_id: "alice".into(), amount: Decimal::from(2) }, deposit("alice", 3)];
+    for command in after.clone() {
+        processor.execute_command(command).unwrap();
+    }
+    assert_eq!(processor.event_version(), EventId(6));
+    assert_eq!(processor.system().accounts["alice"].balance(), Decimal::from(32));
+    let marker = RotationMarker { from_storage: std::any::type_name::<MemoryEventStorage<BankCommand>>().to_string(), events_copied: 3 };
+    assert_eq!(processor.event_storage.markers().unwrap(), vec![LogMarker { after_event: 3, text: marker.to_string() }]);
+    drop(processor);
+
+    let logged: Vec<_> = std::fs::read_to_string(&log)
+        .unwrap()
+        .lines()
+        .filter(|line| !line.starts_with(MARKER_PREFIX))
+        .map(|line| BankJsonConverter::new().parse(line).unwrap())
+        .collect();
+    assert_eq!(logged, before.into_iter().chain(after).collect::<Vec<_>>());
+
+    // A storage that already holds events is not a rotation target
+    let processor = MemImgProcessor::new_simple(Bank::new(), Box::new(MemoryEventStorage::new())).unwrap();
+    let error = processor.rotate_storage(Box::new(TextFileEventStorage::new(&log, BankJsonConverter::new()).unwrap())).err().unwrap();
+    assert!(error.to_string().contains("already holds events"), "{}", error);
+    let _ = std::fs::remove_file(&log);
+}
+
+#[test]
+fn migrated_storage_replays_to_the_same_state_from_genesis_events() {
+    let log = std::env::temp_dir().join("test_migrate_storage.json");
+    let _ = std::fs::remove_file(&log);
+    let mut processor = MemImgProcessor::new_simple(Bank::new(), Box::new(MemoryEventStorage::new())).unwrap();
+    processor.execute_command(BankCommand::CreateAccount { id: "alice".into(), name: "Alice".to_string(), opening_balance: None }).unwrap();
+    for amount in [10, 20, 30, 40, 50, 60] {
+        processor.execute_command(deposit("alice", amount)).unwrap();
+    }
+    processor.execute_command(BankCommand::CreateAccount { id: "bob".into(), name: "Bob".to_string(), opening_balance: Some(Decimal::from(5)) }).unwrap();
+    assert_eq!(processor.event_version().as_u64(), 8);
+
+    let file = Box::new(TextFileEventStorage::new(&log, BankJsonConverter::new()).unwrap());
+    let mut processor = processor.migrate_storage(file, Bank::genesis_commands).unwrap();
+    // Two creates, one deposit each and the restored stamps
+    let genesis_events = processor.event_version().as_u64();
+    assert_eq!(genesis_events, 5);
+    processor.execute_command(deposit("bob", 1)).unwrap();
+    let state = serde_json::to_value(processor.system()).unwrap();
+    drop(processor);
+
+    let reopened = MemImgProcessor::new_simple(Bank::new(), Box::new(TextFileEventStorage::new(&log, BankJsonConverter::new()).unwrap())).unwrap();
+    assert_eq!(serde_json::to_value(reopened.system()).unwrap(), state);
+    assert_eq!(reopened.event_version().as_u64(), genesis_events + 1);
+    drop(reopened);
+
+    // A storage that already holds events is not a migration target
+    let processor = MemImgProcessor::new_simple(Bank::new(), Box::new(MemoryEventStorage::new())).unwrap();
+    let error = processor.migrate_storage(Box::new(TextFileEventStorage::new(&log, BankJsonConverter::new()).unwrap()), Bank::genesis_commands).err().unwrap();
+    assert!(error.to_string().contains("already holds events"), "{}", error);
+    let _ = std::fs::remove_file(&log);
+}
+
+#[test]
+fn import_skips_rejected_commands_and_reports_why() {
+    let file = std::env::temp_dir().join("test_import_commands.json");
+    std::fs::write(
+        &file,
+        r#"[
+            {"CreateAccount": {"id": "alice", "name": "Alice"}},
+            {"Withdrawal": {"account_id": "alice", "amount": "5"}},
+            {"Deposit": {"account_id": "alice", "amount": "10"}}
+        ]"#,
+    )
+    .unwrap();
+
+    let mut processor = MemImgProcessor::new_simple(Bank::new(), Box::new(MemoryEventStorage::<BankCommand>::new())).unwrap();
+    let report = processor.import_commands(&file).unwrap();
+    assert_eq!((report.accepted, report.rejected), (2, 1));
+    assert_eq!(report.rejections.len(), 1);
+    assert_eq!(report.rejections[0].index, 2);
+    assert!(report.rejections[0].reason.contains("Insufficient funds"), "{}", report.rejections[0].reason);
+    assert_eq!(processor.event_version(), EventId(2));
+    assert_eq!(processor.system().accounts["alice"].balance(), Decimal::from(10));
+
+    // Strict, the same file imports nothing
+    let mut processor = MemImgProcessor::new_simple(Bank::new(), Box::new(MemoryEventStorage::<BankCommand>::new())).unwrap();
+    let error = processor.import_commands_strict(&file).unwrap_err();
+    assert!(error.to_string().contains("command 2 of"), "{}", error);
+    assert_eq!(processor.event_version(), EventId(0));
+    assert!(processor.system().accounts.is_empty());
+
+    std::fs::write(&file, r#"{"Deposit": {"account_id": "alice", "amount": "10"}}"#).unwrap();
+    assert!(matches!(processor.import_commands(&file), Err(MemImgError::CommandFailure(_))));
+    let _ = std::fs::remove_file(&file);
+}
+
+#[test]
+fn replay_skips_marker_lines_between_commands() {
+    let log = std::env::temp_dir().join("test_log_markers.json");
+    let _ = std::fs::remove_file(&log);
+    {
+        let storage = Box::new(TextFileEventStorage::new(&log, BankJsonConverter::new()).unwrap());
+        let mut processor = MemImgProcessor::new_simple(Bank::new(), storage).unwrap();
+        processor
+            .execute_command(BankCommand::CreateAccount { id: "alice".into(), name: "Alice".to_string(), opening_balance: None })
+            .unwrap();
+        processor.event_storage.append_marker("deploy v2.1\nby ops").unwrap();
+        processor.execute_command(deposit("alice", 25)).unwrap();
+    }
+    let text = std::fs::read_to_string(&log).unwrap();
+    assert_eq!(text.lines().nth(1), Some("# deploy v2.1 by ops"));
+
+    let storage = TextFileEventStorage::new(&log, BankJsonConverter::new()).unwrap();
+    assert_eq!(storage.markers().unwrap(), vec![LogMarker { after_event: 1, text: "deploy v2.1 by ops".to_string() }]);
+    let mut processor = MemImgProcessor::new_simple(Bank::new(), Box::new(storage)).unwrap();
+    assert!(processor.warnings().is_empty());
+    assert_eq!(processor.event_version(), EventId(2));
+    assert_eq!(processor.system().accounts["alice"].balance(), Decimal::from(25));
+    assert_eq!(processor.event_storage.open_cursor().unwrap().next_batch(10).unwrap().len(), 2);
+
+    // The marker stays with the event after it when the log is split
+    let archive = log.with_extension("archive");
+    let _ = std::fs::remove_file(&archive);
+    assert_eq!(processor.event_storage.split_at(EventId(1), &archive).unwrap(), 1);
+    assert_eq!(std::fs::read_to_string(&archive).unwrap().lines().count(), 1);
+    assert_eq!(processor.event_storage.markers().unwrap(), vec![LogMarker { after_event: 0, text: "deploy v2.1 by ops".to_string() }]);
+
+    let _ = std::fs::remove_file(&log);
+    let _ = std::fs::remove_file(log.with_extension("archive"));
+}
+
+#[test]
+fn split_at_archives_the_head_of_the_log_and_keeps_the_tail_live() {
+    let directory = std::env::temp_dir().join("test_split_at");
+    let _ = std::fs::remove_dir_all(&directory);
+    let (live_log, archive_log) = (directory.join("bank.json"), directory.join("archive.json"));
+
+    let mut storage = TextFileEventStorage::new(&live_log, BankJsonConverter::new()).unwrap();
+    let mut live_bank = Bank::new();
+    let mut bank_at_split = Bank::new();
+    let create = BankCommand::CreateAccount { id: "acc1".into(), name: "Alice".to_string(), opening_balance: None };
+    for (sequence, command) in std::iter::once(create).chain((1..10).map(|amount| deposit("acc1", amount))).enumerate() {
+        command.apply_to(&mut live_bank).unwrap();
+        storage.append(&command).unwrap();
+        if sequence + 1 == 5 {
+            bank_at_split = live_bank.clone();
+        }
+    }
+
+    assert_eq!(storage.split_at(EventId(5), &archive_log).unwrap(), 5);
+    assert_eq!(std::fs::read_to_string(&archive_log).unwrap().lines().count(), 5);
+    assert_eq!(std::fs::read_to_string(&live_log).unwrap().lines().count(), 5);
+    assert!(storage.split_at(EventId(1), &archive_log).is_err());
+    drop(storage);
+
+    let snapshot = Snapshot { version: 1, event_count: 5, state: bank_at_split };
+    let storage = Box::new(TextFileEventStorage::new(&live_log, BankJsonConverter::new()).unwrap());
+    let processor = MemImgProcessor::from_snapshot(snapshot, storage).unwrap();
+    assert_eq!(processor.system(), &live_bank);
+    assert_eq!(processor.event_version(), EventId(10));
+
+    let archived = MemImgProcessor::preview_replay(Bank::new(), Box::new(TextFileEventStorage::new(&archive_log, BankJsonConverter::new()).unwrap())).unwrap();
+    assert_eq!(archived.accounts["acc1"].balance(), Decimal::from(10));
+
+    let _ = std::fs::remove_dir_all(&directory);
+}
+
+#[test]
+fn compacts_only_events_logged_since_last_checkpoint() {
+    let format = SnapshotFormat::new(1);
+    let mut processor = MemImgProcessor::new_simple(Bank::new(), Box::new(MemoryEventStorage::new())).unwrap();
+    processor
+        .execute_command(BankCommand::CreateAccount {
+            id: "acc1".into(),
+            name: "Alice".to_string(),
+            opening_balance: None,
+        })
+        .unwrap();
+    processor.checkpoint_and_compact(&format, Vec::new()).unwrap();
+
+    processor.execute_command(deposit("acc1", 10)).unwrap();
+    processor.execute_command(deposit("acc1", 20)).unwrap();
+    let result = processor.checkpoint_and_compact(&format, Vec::new()).unwrap();
+
+    assert_eq!(result.events_removed, 2);
+    assert!(processor.event_storage.events().is_empty());
+    assert_eq!(processor.event_version(), EventId(3));
+}
+
+#[test]
+fn standing_queries_push_changed_results() {
+    let processor = MemImgProcessor::new_simple(Bank::new(), Box::new(MemoryEventStorage::new())).unwrap();
+    let mut standing = StandingQueryProcessor::new(processor);
+    let total = standing.register_query(GetTotalBalance);
+    let updates = standing.subscribe(total);
+
+    standing.execute_command(BankCommand::CreateAccount { id: "acc1".into(), name: "Alice".to_string(), opening_balance: None }).unwrap();
+    standing.execute_command(deposit("acc1", 100)).unwrap();
+    standing.execute_command(deposit("acc1", 50)).unwrap();
+    assert!(standing.execute_command(deposit("nobody", 10)).is_err());
+
+    // Creating an empty account leaves the total at zero, so it sends nothing
+    assert_eq!(updates.try_iter().collect::<Vec<_>>(), vec![Decimal::from(100), Decimal::from(150)]);
+}
+
+#[test]
+fn exports_journal_as_csv() {
+    let mut processor = MemImgProcessor::new_simple(Bank::new(), Box::new(MemoryEventStorage::new())).unwrap();
+    for command in [
+        BankCommand::CreateAccount { id: "acc1".into(), name: "Alice".to_string(), opening_balance: None },
+        BankCommand::CreateAccount { id: "acc2".into(), name: "Bob".to_string(), opening_balance: None },
+        deposit("acc1", 100),
+        BankCommand::Transfer { from_account_id: "acc1".into(), to_account_id: "acc2".into(), amount: Decimal::from(30) },
+        BankCommand::Withdrawal { account_id: "acc2".into(), amount: Decimal::from(10) },
+    ] {
+        processor.execute_command(command).unwrap();
+    }
+
+    let mut output = Vec::new();
+    let rows = processor.export_journal_csv(&mut output).unwrap();
+    assert_eq!(rows, 5);
+
+    let mut reader = csv::Reader::from_reader(&output[..]);
+    assert_eq!(reader.headers().unwrap(), JOURNAL_CSV_HEADER.as_slice());
+    let records: Vec<csv::StringRecord> = reader.records().map(Result::unwrap).collect();
+    assert_eq!(records.len(), 5);
+    assert_eq!(
+        records.iter().map(|record| &record[1]).collect::<Vec<_>>(),
+        vec!["CreateAccount", "CreateAccount", "Deposit", "Transfer", "Withdrawal"]
+    );
+    assert_eq!(&records[3], vec!["4", "Transfer", "", "acc1", "acc2", "30", "", ""].as_slice());
+}
+
+#[test]
+fn cursor_reads_log_in_batches_and_resumes_from_saved_position() {
+    let test_file = std::env::temp_dir().join("test_log_cursor.json");
+    let _ = std::fs::remove_file(&test_file);
+    let mut storage = TextFileEventStorage::new(&test_file, BankJsonConverter::new()).unwrap();
+    let events: Vec<BankCommand> = (1..=5).map(|amount| deposit("acc1", amount)).collect();
+    for event in &events {
+        storage.append(event).unwrap();
+    }
+
+    let mut cursor = storage.open_cursor().unwrap();
+    assert_eq!(cursor.next_batch(2).unwrap(), events[0..2]);
+    assert_eq!(cursor.next_batch(2).unwrap(), events[2..4]);
+    let saved = cursor.position();
+    drop(cursor);
+    drop(storage);
+
+    // A restarted consumer picks up where it left off
+    let storage = TextFileEventStorage::new(&test_file, BankJsonConverter::new()).unwrap();
+    let mut cursor = storage.open_cursor_at(saved).unwrap();
+    assert_eq!(cursor.next_batch(2).unwrap(), events[4..]);
+    assert!(cursor.next_batch(2).unwrap().is_empty());
+    assert_eq!(cursor.position(), std::fs::metadata(&test_file).unwrap().len());
+
+    let _ = std::fs::remove_file(&test_file);
+}
+
+#[test]
+fn cursor_reads_through_a_rolled_back_intent() {
+    let test_file = std::env::temp_dir().join("test_log_cursor_intents.json");
+    let _ = std::fs::remove_file(&test_file);
+    let mut storage = TextFileEventStorage::new(&test_file, BankJsonConverter::new()).unwrap().with_write_ahead_intents();
+    storage.append(&deposit("acc1", 1)).unwrap();
+    storage.settle_intent(true).unwrap();
+    storage.append(&deposit("acc1", 2)).unwrap();
+
+    // The pending deposit is not handed out, and the saved position stays before it
+    let mut cursor = storage.open_cursor().unwrap();
+    assert_eq!(cursor.next_batch(10).unwrap(), vec![deposit("acc1", 1)]);
+    let saved = cursor.position();
+    drop(cursor);
+
+    storage.settle_intent(false).unwrap();
+    storage.append(&deposit("acc1", 3)).unwrap();
+    storage.settle_intent(true).unwrap();
+
+    let mut cursor = storage.open_cursor_at(saved).unwrap();
+    assert_eq!(cursor.next_batch(10).unwrap(), vec![deposit("acc1", 3)]);
+    assert_eq!(cursor.position(), std::fs::metadata(&test_file).unwrap().len());
+
+    drop(cursor);
+    drop(storage);
+    let _ = std::fs::remove_file(&test_file);
+}
+
+fn ledger_entry(account_id: &str, balance: i64) -> LedgerEntry {
+    LedgerEntry { account_id: account_id.into(), name: account_id.to_uppercase(), balance: Decimal::from(balance) }
+}
+
+#[test]
+fn imports_ledger_balances_that_survive_replay() {
+    let test_file = std::env::temp_dir().join("test_import_ledger.json");
+    let _ = std::fs::remove_file(&test_file);
+    let entries: Vec<LedgerEntry> = [("acc1", 100), ("acc2", 0), ("acc3", 250), ("acc4", 75), ("acc5", 1000)]
+        .into_iter()
+        .map(|(id, balance)| ledger_entry(id, balance))
+        .collect();
+
+    {
+        let storage = Box::new(TextFileEventStorage::new(&test_file, BankJsonConverter::new()).unwrap());
+        let mut processor = MemImgProcessor::new_simple(Bank::new(), storage).unwrap().with_validator(DoubleEntryValidator);
+        processor.execute_command(BankCommand::ImportLedger { entries: entries.clone() }).unwrap();
+        for entry in &entries {
+            assert_eq!(processor.execute_query(&GetBalance { account_id: entry.account_id.clone() }).unwrap(), entry.balance);
+        }
+
+        processor.execute_command(deposit("acc1", 20)).unwrap();
+        assert_eq!(processor.execute_query(&GetBalance { account_id: "acc1".into() }).unwrap(), Decimal::from(120));
+    }
+
+    let storage = Box::new(TextFileEventStorage::new(&test_file, BankJsonConverter::new()).unwrap());
+    let processor = MemImgProcessor::new_simple(Bank::new(), storage).unwrap();
+    processor.assert_state(|bank| assert_eq!(bank.accounts.len(), 5));
+    processor.assert_query(&GetBalance { account_id: "acc1".into() }, |balance| assert_eq!(balance, Decimal::from(120))).unwrap();
+    processor.assert_query(&GetBalance { account_id: "acc5".into() }, |balance| assert_eq!(balance, Decimal::from(1000))).unwrap();
+
+    let _ = std::fs::remove_file(&test_file);
+}
+
+#[test]
+fn rejects_ledger_imports_with_negative_balances_or_duplicate_ids() {
+    let mut processor = MemImgProcessor::new_simple(Bank::new(), Box::new(MemoryEventStorage::new())).unwrap();
+    let domain_error = |error: MemImgError| error.outcome().unwrap().source.downcast_ref::<BankError>().cloned().unwrap();
+
+    let negative = BankCommand::ImportLedger { entries: vec![ledger_entry("acc1", 10), ledger_entry("acc2", -5)] };
+    assert_eq!(domain_error(processor.execute_command(negative).unwrap_err()), BankError::InvalidAmount("-5".to_string()));
+
+    let duplicate = BankCommand::ImportLedger { entries: vec![ledger_entry("acc1", 10), ledger_entry("acc1", 20)] };
+    assert_eq!(domain_error(processor.execute_command(duplicate).unwrap_err()), BankError::DuplicateAccount("acc1".to_string()));
+    assert!(processor.system().accounts.is_empty());
+}
+
+fn external_payment(account_id: &str, amount: i64, gateway_transaction_id: &str, payment_method: PaymentMethod) -> BankCommand {
+    BankCommand::RecordExternalPayment {
+        account_id: account_id.into(),
+        amount: Decimal::from(amount),
+        gateway: "acme-pay".to_string(),
+        gateway_transaction_id: gateway_transaction_id.to_string(),
+        payment_method,
+    }
+}
+
+#[test]
+fn external_payments_are_credited_once_and_survive_replay() {
+    let test_file = std::env::temp_dir().join("test_external_payments.json");
+    let _ = std::fs::remove_file(&test_file);
+
+    {
+        let storage = Box::new(TextFileEventStorage::new(&test_file, BankJsonConverter::new()).unwrap());
+        let mut processor = MemImgProcessor::new_simple(Bank::new(), storage).unwrap().with_validator(DoubleEntryValidator);
+        processor
+            .execute_command(BankCommand::CreateAccount { id: "alice".into(), name: "Alice".to_string(), opening_balance: None })
+            .unwrap();
+        processor.execute_command(external_payment("alice", 40, "txn-1", PaymentMethod::CreditCard)).unwrap();
+        processor.execute_command(external_payment("alice", 60, "txn-2", PaymentMethod::WireTransfer)).unwrap();
+        assert_eq!(processor.execute_query(&GetBalance { account_id: "alice".into() }).unwrap(), Decimal::from(100));
+
+        let error = processor.execute_command(external_payment("alice", 40, "txn-1", PaymentMethod::ACH)).unwrap_err();
+        let domain = error.outcome().unwrap().source.downcast_ref::<BankError>().cloned().unwrap();
+        assert_eq!(domain, BankError::DuplicateExternalPayment("txn-1".to_string()));
+        assert_eq!(domain.to_string(), "Duplicate external payment ID: txn-1");
+        assert_eq!(processor.execute_query(&GetBalance { account_id: "alice".into() }).unwrap(), Decimal::from(100));
+    }
+
+    let storage = Box::new(TextFileEventStorage::new(&test_file, BankJsonConverter::new()).unwrap());
+    let mut processor = MemImgProcessor::new_simple(Bank::new(), storage).unwrap();
+    assert_eq!(processor.execute_query(&GetBalance { account_id: "alice".into() }).unwrap(), Decimal::from(100));
+    assert_eq!(processor.system().equity_capital, Decimal::from(100));
+    // The recorded ids are part of the replayed state, so a duplicate is still caught after a restart
+    let error = processor.execute_command(external_payment("alice", 60, "txn-2", PaymentMethod::Crypto)).unwrap_err();
+    assert_eq!(error.outcome().unwrap().source.to_string(), "Duplicate external payment ID: txn-2");
+
+    let _ = std::fs::remove_file(&test_file);
+}
+
+#[test]
+fn opening_balance_is_set_at_creation_and_survives_replay() {
+    let test_file = std::env::temp_dir().join("test_opening_balance.json");
+    let _ = std::fs::remove_file(&test_file);
+    let open = |id: &str, balance: Option<i64>| BankCommand::CreateAccount {
+        id: id.into(),
+        name: id.to_uppercase(),
+        opening_balance: balance.map(Decimal::from),
+    };
+
+    {
+        let storage = Box::new(TextFileEventStorage::new(&test_file, BankJsonConverter::new()).unwrap());
+        let mut processor = MemImgProcessor::new_simple(Bank::new(), storage).unwrap().with_validator(DoubleEntryValidator);
+        processor.execute_command(open("alice", Some(500))).unwrap();
+        processor.execute_command(open("bob", None)).unwrap();
+        assert_eq!(processor.execute_query(&GetBalance { account_id: "alice".into() }).unwrap(), Decimal::from(500));
+        assert_eq!(processor.execute_query(&GetBalance { account_id: "bob".into() }).unwrap(), Decimal::ZERO);
+        assert_eq!(processor.system().equity_capital, Decimal::from(500));
+    }
+
+    // Accounts opened without a balance keep the original event format
+    let log = std::fs::read_to_string(&test_file).unwrap();
+    assert!(log.lines().nth(1).unwrap().ends_with(r#"{"CreateAccount":{"id":"bob","name":"BOB"}}"#));
+
+    let storage = Box::new(TextFileEventStorage::new(&test_file, BankJsonConverter::new()).unwrap());
+    let processor = MemImgProcessor::new_simple(Bank::new(), storage).unwrap();
+    processor.assert_query(&GetBalance { account_id: "alice".into() }, |balance| assert_eq!(balance, Decimal::from(500))).unwrap();
+    processor.assert_state(|bank| assert_eq!(bank.equity_capital, Decimal::from(500)));
+
+    let _ = std::fs::remove_file(&test_file);
+}
+
+#[test]
+fn rejects_negative_opening_balance() {
+    let mut processor = MemImgProcessor::new_simple(Bank::new(), Box::new(MemoryEventStorage::new())).unwrap();
+
+    let error = processor
+        .execute_command(BankCommand::CreateAccount {
+            id: "alice".into(),
+            name: "Alice".to_string(),
+            opening_balance: Some(Decimal::from(-1)),
+        })
+        .unwrap_err();
+
+    let error = error.outcome().unwrap().source.downcast_ref::<BankError>().cloned().unwrap();
+    assert_eq!(error, BankError::InvalidAmount("-1".to_string()));
+    assert!(processor.system().accounts.is_empty());
+    assert!(processor.event_storage.events().is_empty());
+}
+
+fn sweep(from: &str, to: &str) -> BankCommand {
+    BankCommand::Sweep { from_account_id: from.into(), to_account_id: to.into(), amount: None }
+}
+
+#[test]
+fn sweep_moves_the_whole_balance_and_logs_the_amount_read() {
+    let mut processor = MemImgProcessor::new_simple(Bank::new(), Box::new(MemoryEventStorage::new())).unwrap().with_validator(DoubleEntryValidator);
+    processor.execute_command(BankCommand::ImportLedger { entries: vec![ledger_entry("alice", 120), ledger_entry("bob", 5)] }).unwrap();
+    processor.execute_command(deposit("alice", 30)).unwrap();
+
+    processor.execute_command(sweep("alice", "bob")).unwrap();
+
+    assert_eq!(processor.execute_query(&GetBalance { account_id: "alice".into() }).unwrap(), Decimal::ZERO);
+    assert_eq!(processor.execute_query(&GetBalance { account_id: "bob".into() }).unwrap(), Decimal::from(155));
+    assert_eq!(
+        processor.event_storage.events().last().unwrap(),
+        &BankCommand::Sweep { from_account_id: "alice".into(), to_account_id: "bob".into(), amount: Some(Decimal::from(150)) }
+    );
+    assert_replay_deterministic(&mut processor);
+}
+
+#[test]
+fn sweeping_an_empty_account_is_logged_and_replays_to_zero() {
+    let test_file = std::env::temp_dir().join("test_sweep_empty.json");
+    let _ = std::fs::remove_file(&test_file);
+
+    {
+        let storage = Box::new(TextFileEventStorage::new(&test_file, BankJsonConverter::new()).unwrap());
+        let mut processor = MemImgProcessor::new_simple(Bank::new(), storage).unwrap();
+        processor.execute_command(BankCommand::ImportLedger { entries: vec![ledger_entry("alice", 0), ledger_entry("bob", 40)] }).unwrap();
+
+        processor.execute_command(sweep("alice", "bob")).unwrap();
+        assert_eq!(processor.event_version(), EventId(2));
+    }
+
+    let log = std::fs::read_to_string(&test_file).unwrap();
+    assert!(log.lines().last().unwrap().contains(r#""amount":"0""#), "{}", log);
+
+    let storage = Box::new(TextFileEventStorage::new(&test_file, BankJsonConverter::new()).unwrap());
+    let processor = MemImgProcessor::new_simple(Bank::new(), storage).unwrap();
+    processor.assert_state(|bank| {
+        assert_eq!(bank.accounts["alice"].balance(), Decimal::ZERO);
+        assert_eq!(bank.accounts["bob"].balance(), Decimal::from(40));
+    });
+
+    let _ = std::fs::remove_file(&test_file);
+}
+
+#[test]
+fn sweep_from_unknown_account_fails_without_logging() {
+    let mut processor = MemImgProcessor::new_simple(Bank::new(), Box::new(MemoryEventStorage::new())).unwrap();
+    processor.execute_command(BankCommand::ImportLedger { entries: vec![ledger_entry("bob", 40)] }).unwrap();
+
+    let error = processor.execute_command(sweep("ghost", "bob")).unwrap_err();
+
+    assert_eq!(error.outcome().unwrap().source.downcast_ref::<BankError>(), Some(&BankError::AccountNotFound("ghost".to_string())));
+    assert_eq!(processor.event_storage.events().len(), 1);
+}
+
+#[test]
+fn faulty_storage_follows_its_script() {
+    let storage = FaultyEventStorage::new(MemoryEventStorage::new())
+        .with_failing_append(2, ErrorKind::WriteZero)
+        .with_failing_replay_at(1);
+    let counters = storage.counters();
+    let mut processor = MemImgProcessor::new_simple(Bank::new(), Box::new(storage)).unwrap();
+
+    processor.execute_command(BankCommand::CreateAccount { id: "acc1".into(), name: "Alice".to_string(), opening_balance: None }).unwrap();
+    let error = processor.execute_command(deposit("acc1", 10)).unwrap_err();
+    let storage_error = error.outcome().unwrap().source.downcast_ref::<StorageError>().unwrap();
+    assert_eq!((storage_error.op, storage_error.source.kind()), (StorageOp::Append, ErrorKind::WriteZero));
+    assert!(processor.is_poisoned());
+
+    let mut replayed = Vec::new();
+    let replay = processor.event_storage.replay(&mut |event| {
+        replayed.push(event);
+        Ok(())
+    });
+    assert!(replay.is_err());
+    assert!(replayed.is_empty());
+
+    assert_eq!(
+        counters.stats(),
+        FaultStats { append_attempts: 2, appends_succeeded: 1, replay_attempts: 2, replays_succeeded: 1 }
+    );
+}
+
+#[test]
+fn faulty_storage_recovers_after_scripted_failures() {
+    let mut storage = FaultyEventStorage::new(MemoryEventStorage::new()).with_failing_appends(2, ErrorKind::TimedOut);
+
+    assert!(storage.append(&deposit("acc1", 1)).is_err());
+    assert!(storage.append(&deposit("acc1", 2)).is_err());
+    storage.append(&deposit("acc1", 3)).unwrap();
+
+    assert_eq!(storage.inner().events(), &[deposit("acc1", 3)][..]);
+    assert_eq!(storage.stats().append_attempts, 3);
+}
+
+#[test]
+fn commands_roll_back_when_a_scripted_append_fails() {
+    let storage = FaultyEventStorage::new(MemoryEventStorage::new()).with_failing_every_append(3, ErrorKind::Other);
+    let mut processor = MemImgProcessor::new_simple(Bank::new(), Box::new(storage)).unwrap();
+    processor.execute_command(BankCommand::CreateAccount { id: "acc1".into(), name: "Alice".to_string(), opening_balance: None }).unwrap();
+    processor.execute_command(deposit("acc1", 10)).unwrap();
+
+    let error = processor.execute_command(deposit("acc1", 5)).unwrap_err();
+    assert!(matches!(error, MemImgError::SystemFailure(_)));
+    assert_eq!(processor.system().accounts["acc1"].balance(), Decimal::from(10));
+    assert_eq!(processor.event_version(), EventId(2));
+    assert_eq!(processor.event_storage.inner().events().len(), 2);
+
+    // A disk of a given size fails the append that would overflow it
+    let mut storage = FaultyEventStorage::new(MemoryEventStorage::new()).with_failing_after_bytes(100);
+    storage.append(&deposit("acc1", 1)).unwrap();
+    let error = storage.append(&BankCommand::CreateAccount { id: "acc2".into(), name: "B".repeat(100), opening_balance: None }).unwrap_err();
+    assert_eq!(error.downcast_ref::<StorageError>().unwrap().source.kind(), ErrorKind::StorageFull);
+    storage.append(&deposit("acc1", 2)).unwrap();
+    assert_eq!(storage.stats().appends_succeeded, 2);
+}
+
+#[derive(Default, serde::Serialize, serde::Deserialize)]
+struct DepositCounter {
+    events: u64,
+    deposits: u64,
+}
+
+impl Projection<BankCommand> for DepositCounter {
+    fn apply(&mut self, event: &BankCommand) {
+        self.events += 1;
+        if matches!(event, BankCommand::Deposit { .. }) {
+            self.deposits += 1;
+        }
+    }
+}
+
+#[test]
+fn persistent_projection_resumes_from_its_watermark() {
+    let state_file = std::env::temp_dir().join("test_persistent_projection.json");
+    let _ = std::fs::remove_file(&state_file);
+    let deposit = || BankCommand::Deposit { account_id: "alice".into(), amount: Decimal::from(10) };
+    let mut storage = MemoryEventStorage::new();
+
+    {
+        let mut projection = PersistentProjection::<DepositCounter, _>::open(&state_file, &mut storage).unwrap();
+        for event in [BankCommand::CreateAccount { id: "alice".into(), name: "Alice".to_string(), opening_balance: None }, deposit(), deposit(), deposit(), deposit()] {
+            storage.append(&event).unwrap();
+            projection.apply(&event).unwrap();
+        }
+        assert_eq!(projection.watermark(), EventId(5));
+    }
+    for _ in 0..3 {
+        storage.append(&deposit()).unwrap();
+    }
+
+    // Only the three events past the saved watermark are replayed
+    let projection = PersistentProjection::<DepositCounter, _>::open(&state_file, &mut storage).unwrap();
+
+    assert_eq!(projection.watermark(), EventId(8));
+    assert_eq!((projection.projection().events, projection.projection().deposits), (8, 7));
+
+    let _ = std::fs::remove_file(&state_file);
+}
+
+#[derive(Debug, Clone)]
+enum NapCommand {
+    Nap { millis: u64 },
+    Noop,
+}
+
+impl Command for NapCommand {
+    type System = ();
+
+    fn apply_to(&self, _system: &mut ()) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        if let NapCommand::Nap { millis } = self {
+            std::thread::sleep(std::time::Duration::from_millis(*millis));
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn commands_over_the_slow_threshold_are_reported() {
+    let slow = std::sync::Arc::new(std::sync::Mutex::new(Vec::<SlowCommand>::new()));
+    let sink = slow.clone();
+    let threshold = std::time::Duration::from_millis(20);
+    let mut processor = MemImgProcessor::new_simple((), Box::new(MemoryEventStorage::new()))
+        .unwrap()
+        .with_slow_command_threshold(threshold, move |command| sink.lock().unwrap().push(command.clone()));
+
+    processor.execute_command(NapCommand::Noop).unwrap();
+    processor.execute_command(NapCommand::Nap { millis: 50 }).unwrap();
+
+    let slow = slow.lock().unwrap();
+    assert_eq!(slow.len(), 1);
+    assert_eq!(slow[0].command_type, "Nap");
+    assert!(slow[0].elapsed >= std::time::Duration::from_millis(50));
+    assert_eq!(slow[0].threshold, threshold);
+}
+
+#[test]
+fn query_audit_hook_fires_once_per_query_and_logs_nothing() {
+    let reads = Arc::new(Mutex::new(Vec::<QueryAudit>::new()));
+    let sink = reads.clone();
+    let mut processor = MemImgProcessor::new_simple(Bank::new(), Box::new(MemoryEventStorage::new()))
+        .unwrap()
+        .with_query_audit(move |read| sink.lock().unwrap().push(read.clone()));
+    processor.execute_command(BankCommand::CreateAccount { id: "alice".into(), name: "Alice".to_string(), opening_balance: None }).unwrap();
+
+    processor.execute_query(&GetBalance { account_id: "alice".into() }).unwrap();
+    processor.execute_query_as("auditor", &GetTotalBalance).unwrap();
+    processor.execute_query_as("auditor", &GetBalance { account_id: "bob".into() }).unwrap_err();
+
+    let reads = reads.lock().unwrap();
+    let seen: Vec<_> = reads.iter().map(|read| (read.query_type.as_str(), read.actor.as_deref(), read.succeeded)).collect();
+    assert_eq!(
+        seen,
+        vec![
+            (std::any::type_name::<GetBalance>(), None, true),
+            (std::any::type_name::<GetTotalBalance>(), Some("auditor"), true),
+            (std::any::type_name::<GetBalance>(), Some("auditor"), false),
+        ]
+    );
+    assert_eq!(processor.event_version(), EventId(1));
+}
+
+#[test]
+fn ephemeral_processor_deletes_its_log_on_drop() {
+    let mut processor = EphemeralMemImgProcessor::in_temp_dir(Bank::new(), BankJsonConverter::new()).unwrap();
+    let path = processor.path().to_path_buf();
+
+    processor
+        .execute_command(BankCommand::CreateAccount { id: "alice".into(), name: "Alice".to_string(), opening_balance: None })
+        .unwrap();
+    processor.execute_command(BankCommand::Deposit { account_id: "alice".into(), amount: Decimal::from(10) }).unwrap();
+    assert_eq!(processor.execute_query(&GetBalance { account_id: "alice".into() }).unwrap(), Decimal::from(10));
+    assert!(path.exists());
+
+    drop(processor);
+
+    assert!(!path.exists());
+}
+
+#[test]
+fn read_replica_tracks_the_leaders_balances() {
+    let mut leader = MemImgProcessor::new_simple(Bank::new(), Box::new(MemoryEventStorage::new())).unwrap();
+    for id in ["alice", "bob"] {
+        leader.execute_command(BankCommand::CreateAccount { id: id.into(), name: id.to_string(), opening_balance: None }).unwrap();
+    }
+    leader.execute_command(BankCommand::Deposit { account_id: "alice".into(), amount: Decimal::from(100) }).unwrap();
+
+    // The first three events come from the leader's log, the rest from its commit channel
+    let mut replica = ReadReplica::follow(&mut leader).unwrap();
+    leader
+        .execute_command(BankCommand::Transfer { from_account_id: "alice".into(), to_account_id: "bob".into(), amount: Decimal::from(30) })
+        .unwrap();
+    leader.execute_command(BankCommand::Withdrawal { account_id: "bob".into(), amount: Decimal::from(500) }).unwrap_err();
+    leader.execute_command(BankCommand::Sweep { from_account_id: "alice".into(), to_account_id: "bob".into(), amount: None }).unwrap();
+
+    let balance = |replica: &mut ReadReplica<Bank, BankCommand>, id: &str| replica.execute_query(&GetBalance { account_id: id.into() }).unwrap();
+    assert_eq!((balance(&mut replica, "alice"), balance(&mut replica, "bob")), (Decimal::ZERO, Decimal::from(100)));
+    assert_eq!(replica.events_applied(), leader.event_version().as_u64());
+    assert_eq!(replica.system(), leader.system());
+}
+
+type FileBankProcessor = MemImgProcessor<Bank, BankCommand, TextFileEventStorage<BankCommand, BankJsonConverter>>;
+
+fn open_standby(log: &std::path::Path) -> FileBankProcessor {
+    MemImgProcessor::new_simple(Bank::new(), Box::new(TextFileEventStorage::new(log, BankJsonConverter::new()).unwrap())).unwrap()
+}
+
+#[test]
+fn standby_restarted_mid_stream_catches_up_to_the_primary() {
+    let log = std::env::temp_dir().join("test_hot_standby.json");
+    let _ = std::fs::remove_file(&log);
+    let mut primary = MemImgProcessor::new_simple(Bank::new(), Box::new(MemoryEventStorage::new())).unwrap();
+    primary.execute_command(BankCommand::CreateAccount { id: "alice".into(), name: "Alice".to_string(), opening_balance: None }).unwrap();
+    let mut source = ReplicationSource::attach(&mut primary);
+
+    // Events committed before the standby connected are read back from the primary's log
+    let mut standby = ReplicaProcessor::new(open_standby(&log), source.connect(0));
+    primary.execute_command(deposit("alice", 100)).unwrap();
+    source.pump(&mut primary).unwrap();
+    assert_eq!(standby.sync().unwrap(), 2);
+    assert!(standby.processor().is_read_only());
+
+    // Killed with events in flight, which are lost with it
+    primary.execute_command(deposit("alice", 20)).unwrap();
+    primary.execute_command(BankCommand::Withdrawal { account_id: "alice".into(), amount: Decimal::from(500) }).unwrap_err();
+    source.pump(&mut primary).unwrap();
+    drop(standby);
+    primary.execute_command(deposit("alice", 3)).unwrap();
+    source.pump(&mut primary).unwrap();
+    assert_eq!(source.replicas(), 0);
+
+    let restarted = open_standby(&log);
+    assert_eq!(restarted.event_version().as_u64(), 2);
+    let endpoint = source.connect(restarted.event_version().as_u64());
+    let mut standby = ReplicaProcessor::new(restarted, endpoint);
+    primary.execute_command(deposit("alice", 4)).unwrap();
+    source.pump(&mut primary).unwrap();
+    assert_eq!(standby.sync().unwrap(), 3);
+    assert_eq!(standby.last_applied_seq(), primary.event_version().as_u64());
+    assert_eq!(fingerprint(standby.processor().system()).unwrap(), fingerprint(primary.system()).unwrap());
+    assert_eq!(standby.execute_query(&GetBalance { account_id: "alice".into() }).unwrap(), Decimal::from(127));
+
+    // Promoted, the standby takes writes on its own log, which it keeps locked
+    drop(source);
+    let mut promoted = standby.promote().unwrap();
+    assert!(!promoted.is_read_only());
+    promoted.execute_command(deposit("alice", 1)).unwrap();
+    assert!(TextFileEventStorage::<BankCommand, _>::new(&log, BankJsonConverter::new()).is_err());
+    drop(promoted);
+    assert_eq!(open_standby(&log).event_version().as_u64(), 6);
+    let _ = std::fs::remove_file(&log);
+}
+
+#[test]
+fn standby_requests_the_range_it_missed() {
+    let (events, received) = std::sync::mpsc::channel();
+    let (requests, requested) = std::sync::mpsc::channel();
+    let processor = MemImgProcessor::new_simple(Bank::new(), Box::new(MemoryEventStorage::new())).unwrap();
+    let mut standby = ReplicaProcessor::new(processor, ReplicaEndpoint { events: received, requests });
+    let create = BankCommand::CreateAccount { id: "alice".into(), name: "Alice".to_string(), opening_balance: None };
+    let stream = [(1, create), (2, deposit("alice", 10)), (3, deposit("alice", 20)), (4, deposit("alice", 30))]
+        .map(|(seq, event)| ReplicatedEvent { seq, event });
+
+    for index in [0, 2, 3] {
+        events.send(stream[index].clone()).unwrap();
+    }
+    assert_eq!(standby.sync().unwrap(), 1);
+    let asked: Vec<_> = requested.try_iter().collect();
+    assert_eq!(
+        asked,
+        vec![ReplicaRequest::CatchUp { from_seq: 2, through_seq: 3 }, ReplicaRequest::CatchUp { from_seq: 4, through_seq: 4 }]
+    );
+
+    // The resent range, with a duplicate, brings the standby back in sequence
+    for index in [0, 1, 2, 3] {
+        events.send(stream[index].clone()).unwrap();
+    }
+    assert_eq!(standby.sync().unwrap(), 3);
+    assert_eq!(standby.last_applied_seq(), 4);
+    assert_eq!(standby.execute_query(&GetBalance { account_id: "alice".into() }).unwrap(), Decimal::from(60));
+    assert!(requested.try_recv().is_err());
+}
+
+#[test]
+fn full_disk_surfaces_as_storage_full_and_leaves_state_uncommitted() {
+    for kind in [ErrorKind::StorageFull, ErrorKind::WriteZero] {
+        let storage = FaultyEventStorage::new(MemoryEventStorage::new()).with_failing_append(2, kind);
+        let mut processor = MemImgProcessor::new_simple(Bank::new(), Box::new(storage)).unwrap();
+        processor.execute_command(BankCommand::CreateAccount { id: "alice".into(), name: "Alice".to_string(), opening_balance: None }).unwrap();
+        let before = processor.system().clone();
+
+        let error = processor.execute_command(deposit("alice", 10)).unwrap_err();
+
+        assert!(matches!(error, MemImgError::StorageFull(_)), "{:?}", kind);
+        assert_eq!(error.code(), "STORAGE_FULL");
+        assert_eq!(processor.system(), &before);
+        assert_eq!(processor.event_version(), EventId(1));
+    }
+}
+
+/// Balance deltas per account, the only thing a balance read model needs from a bank event
+#[derive(Debug)]
+struct BalanceChange(Vec<(String, Decimal)>);
+
+impl Command for BalanceChange {
+    type System = HashMap<String, Decimal>;
+
+    fn apply_to(&self, balances: &mut Self::System) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        for (account_id, delta) in &self.0 {
+            *balances.entry(account_id.clone()).or_default() += delta;
+        }
+        Ok(())
+    }
+}
+
+fn balance_change(command: BankCommand) -> Option<BalanceChange> {
+    let deltas = match command {
+        BankCommand::CreateAccount { id, opening_balance, .. } => vec![(id.to_string(), opening_balance.unwrap_or_default())],
+        BankCommand::Deposit { account_id, amount } => vec![(account_id.to_string(), amount)],
+        BankCommand::Withdrawal { account_id, amount } => vec![(account_id.to_string(), -amount)],
+        BankCommand::Transfer { from_account_id, to_account_id, amount } => {
+            vec![(from_account_id.to_string(), -amount), (to_account_id.to_string(), amount)]
+        }
+        _ => return None,
+    };
+    Some(BalanceChange(deltas))
+}
+
+#[test]
+fn replays_bank_events_into_a_balance_read_model() {
+    let mut processor = MemImgProcessor::new_simple(Bank::new(), Box::new(MemoryEventStorage::new())).unwrap();
+    for command in [
+        BankCommand::CreateAccount { id: "alice".into(), name: "Alice".to_string(), opening_balance: Some(Decimal::from(50)) },
+        BankCommand::CreateAccount { id: "bob".into(), name: "Bob".to_string(), opening_balance: None },
+        deposit("alice", 100),
+        BankCommand::Transfer { from_account_id: "alice".into(), to_account_id: "bob".into(), amount: Decimal::from(30) },
+        BankCommand::Withdrawal { account_id: "bob".into(), amount: Decimal::from(5) },
+        BankCommand::AddOwner { account_id: "bob".into(), owner: "Carol".to_string() },
+    ] {
+        processor.execute_command(command).unwrap();
+    }
+
+    let balances = processor.replay_into_new(HashMap::new(), balance_change).unwrap();
+
+    assert_eq!(balances, HashMap::from([("alice".to_string(), Decimal::from(120)), ("bob".to_string(), Decimal::from(25))]));
+    // Skipped events leave the target alone, and the live state is untouched
+    let deposits_only = processor
+        .replay_into_new(HashMap::new(), |command| matches!(command, BankCommand::Deposit { .. }).then(|| balance_change(command)).flatten())
+        .unwrap();
+    assert_eq!(deposits_only, HashMap::from([("alice".to_string(), Decimal::from(100))]));
+    assert_eq!(processor.system().accounts.len(), 2);
+}
+
+#[test]
+fn visits_every_logged_event_without_changing_state() {
+    let mut processor = MemImgProcessor::new_simple(Bank::new(), Box::new(MemoryEventStorage::new())).unwrap();
+    for command in [
+        BankCommand::CreateAccount { id: "alice".into(), name: "Alice".to_string(), opening_balance: None },
+        BankCommand::CreateAccount { id: "bob".into(), name: "Bob".to_string(), opening_balance: None },
+        deposit("alice", 100),
+        BankCommand::Transfer { from_account_id: "alice".into(), to_account_id: "bob".into(), amount: Decimal::from(30) },
+        deposit("bob", 5),
+        BankCommand::Transfer { from_account_id: "bob".into(), to_account_id: "alice".into(), amount: Decimal::from(10) },
+    ] {
+        processor.execute_command(command).unwrap();
+    }
+    let before = processor.system().clone();
+
+    let mut variants: BTreeMap<&str, u64> = BTreeMap::new();
+    let mut transferred = Decimal::ZERO;
+    let visited = processor
+        .for_each_event(|event| {
+            let variant = match event {
+                BankCommand::CreateAccount { .. } => "CreateAccount",
+                BankCommand::Deposit { .. } => "Deposit",
+                BankCommand::Transfer { amount, .. } => {
+                    transferred += amount;
+                    "Transfer"
+                }
+                _ => "Other",
+            };
+            *variants.entry(variant).or_default() += 1;
+        })
+        .unwrap();
+
+    assert_eq!(visited, 6);
+    assert_eq!(variants, BTreeMap::from([("CreateAccount", 2), ("Deposit", 2), ("Transfer", 2)]));
+    assert_eq!(transferred, Decimal::from(40));
+    assert_eq!(processor.system(), &before);
+
+    let mut deposit_seqs = Vec::new();
+    processor
+        .for_each_event_with_index(|seq, event| {
+            if matches!(event, BankCommand::Deposit { .. }) {
+                deposit_seqs.push(seq);
+            }
+        })
+        .unwrap();
+    assert_eq!(deposit_seqs, vec![3, 5]);
+}
+
+/// Storage backend named by `kind`, as a config file would name it
+fn storage_named(kind: &str, log: &std::path::Path) -> BoxedEventStorage<BankCommand> {
+    match kind {
+        "memory" => Box::new(MemoryEventStorage::new()),
+        "file" => Box::new(TextFileEventStorage::new(log, BankJsonConverter::new()).unwrap()),
+        other => panic!("unknown storage {}", other),
+    }
+}
+
+#[test]
+fn processor_runs_on_a_storage_backend_chosen_at_runtime() {
+    let log = std::env::temp_dir().join("test_runtime_storage.json");
+    let _ = std::fs::remove_file(&log);
+
+    for kind in ["memory", "file"] {
+        let mut processor = MemImgProcessor::new_simple(Bank::new(), Box::new(storage_named(kind, &log))).unwrap();
+        processor
+            .execute_command(BankCommand::CreateAccount { id: "alice".into(), name: "Alice".to_string(), opening_balance: None })
+            .unwrap();
+        processor.execute_command(deposit("alice", 10)).unwrap();
+        assert_eq!(processor.event_storage.version().unwrap(), 2, "{}", kind);
+        assert_eq!(processor.replay_from_scratch().unwrap(), *processor.system(), "{}", kind);
+    }
+
+    let reopened = MemImgProcessor::new_simple(Bank::new(), Box::new(storage_named("file", &log))).unwrap();
+    assert_eq!(reopened.system().accounts["alice"].balance(), Decimal::from(10));
+    let _ = std::fs::remove_file(&log);
+}
+
+#[test]
+fn multi_version_storage_reports_where_the_shadow_log_diverges() {
+    let storage = MultiVersionEventStorage::new(Box::new(MemoryEventStorage::new()), Box::new(MemoryEventStorage::new()));
+    let mut processor = MemImgProcessor::new_simple(Bank::new(), Box::new(storage)).unwrap();
+    processor
+        .execute_command(BankCommand::CreateAccount { id: "alice".into(), name: "Alice".to_string(), opening_balance: None })
+        .unwrap();
+    for amount in [10, 20, 30, 40] {
+        processor.execute_command(deposit("alice", amount)).unwrap();
+    }
+
+    let in_step = processor.event_storage.compare_replay().unwrap();
+    assert!(!in_step.diverged());
+    assert_eq!((in_step.current_events, in_step.shadow_events), (5, 5));
+
+    let mut corrupted = processor.event_storage.shadow().events().to_vec();
+    corrupted[2] = deposit("alice", 21);
+    *processor.event_storage.shadow_mut() = MemoryEventStorage::with_events(corrupted);
+    let report = processor.event_storage.compare_replay().unwrap();
+    assert_eq!(report.first_divergence, Some(3));
+    assert_eq!(report.event_count_difference(), 0);
+    let (current, shadow) = report.divergent_events.unwrap();
+    assert!(current.unwrap().contains("20"));
+    assert!(shadow.unwrap().contains("21"));
+
+    // Replay reads the current log only, and a shorter shadow diverges where it ends
+    *processor.event_storage.shadow_mut() = MemoryEventStorage::new();
+    let report = processor.event_storage.compare_replay().unwrap();
+    assert_eq!((report.first_divergence, report.event_count_difference()), (Some(1), 5));
+    assert_eq!(processor.replay_from_scratch().unwrap().accounts["alice"].balance(), Decimal::from(100));
+}
+
+#[test]
+fn replay_from_scratch_starts_from_the_system_factory() {
+    let factory_calls = Arc::new(AtomicU64::new(0));
+    let calls = factory_calls.clone();
+    let seeded_bank = move || {
+        calls.fetch_add(1, Ordering::SeqCst);
+        let mut bank = Bank::new();
+        BankCommand::CreateAccount { id: "fees".into(), name: "Fees".to_string(), opening_balance: None }.apply_to(&mut bank).unwrap();
+        bank
+    };
+    let mut processor = MemImgProcessor::new_with_system_factory(seeded_bank, Box::new(MemoryEventStorage::new())).unwrap();
+    processor
+        .execute_command(BankCommand::CreateAccount { id: "alice".into(), name: "Alice".to_string(), opening_balance: Some(Decimal::from(100)) })
+        .unwrap();
+    processor
+        .execute_command(BankCommand::Transfer { from_account_id: "alice".into(), to_account_id: "fees".into(), amount: Decimal::from(2) })
+        .unwrap();
+
+    let replayed = processor.replay_from_scratch().unwrap();
+    assert_eq!(&replayed, processor.system());
+    assert_eq!(replayed.accounts["fees"].balance(), Decimal::from(2));
+    assert!(!processor.validate_replay().unwrap().diverged);
+    assert_eq!(factory_calls.load(Ordering::SeqCst), 3);
+}
+
+#[test]
+fn replay_hook_fires_once_with_the_replayed_event_count() {
+    let events = vec![
+        BankCommand::CreateAccount { id: "alice".into(), name: "Alice".to_string(), opening_balance: None },
+        deposit("alice", 100),
+        deposit("alice", 5),
+    ];
+    let mut counts = Vec::new();
+    let mut processor =
+        MemImgProcessor::new_with_replay_hook(Bank::new(), Box::new(MemoryEventStorage::with_events(events)), |count| counts.push(count)).unwrap();
+    processor.execute_command(deposit("alice", 1)).unwrap();
+
+    assert_eq!(counts, vec![3]);
+    assert_eq!(processor.event_version().0, 4);
+}
+
+#[test]
+fn tenants_keep_separate_state_and_logs() {
+    let directory = std::env::temp_dir().join("test_tenants");
+    let _ = std::fs::remove_dir_all(&directory);
+    let tenant_log_dir = directory.clone();
+    let open = move |tenant: &str| {
+        let storage = TextFileEventStorage::new(tenant_log_dir.join(format!("{}.json", tenant)), BankJsonConverter::new()).unwrap();
+        MemImgProcessor::new_simple(Bank::new(), Box::new(storage))
+    };
+    let mut tenants = MultiTenantProcessor::new(open.clone());
+    tenants.open_tenant("acme").unwrap();
+    tenants.open_tenant("globex").unwrap();
+    let alice = |balance| BankCommand::CreateAccount { id: "alice".into(), name: "Alice".to_string(), opening_balance: Some(Decimal::from(balance)) };
+    tenants.execute_command("acme", alice(100)).unwrap();
+    tenants.execute_command("globex", alice(7)).unwrap();
+    tenants.execute_command("globex", BankCommand::CreateAccount { id: "bob".into(), name: "Bob".to_string(), opening_balance: None }).unwrap();
+
+    // Acme has no bob: a transfer to another tenant's account is rejected and changes nothing
+    let cross_tenant = BankCommand::Transfer { from_account_id: "alice".into(), to_account_id: "bob".into(), amount: Decimal::from(10) };
+    let error = tenants.execute_command("acme", cross_tenant).unwrap_err();
+    assert!(matches!(error.outcome().unwrap().source.downcast_ref::<BankError>(), Some(BankError::AccountNotFound(_))));
+
+    let balance = |tenants: &MultiTenantProcessor<_, _, _>, tenant| tenants.execute_query(tenant, &GetBalance { account_id: "alice".into() }).unwrap();
+    assert_eq!(balance(&tenants, "acme"), Decimal::from(100));
+    assert_eq!(balance(&tenants, "globex"), Decimal::from(7));
+    assert_eq!(tenants.tenant_mut("acme").unwrap().event_storage.version().unwrap(), 1);
+    assert_eq!(tenants.tenant_mut("globex").unwrap().event_storage.version().unwrap(), 2);
+    assert_eq!(tenants.tenants().collect::<Vec<_>>(), vec!["acme", "globex"]);
+
+    assert_eq!(tenants.execute_command("initech", alice(1)).unwrap_err().code(), "UNKNOWN_TENANT");
+    assert!(matches!(tenants.open_tenant("../etc"), Err(MemImgError::InvalidTenantId(_))));
+    drop(tenants);
+
+    // Each tenant's log replays into its own bank
+    let mut reopened = MultiTenantProcessor::new(open);
+    reopened.open_tenant("globex").unwrap();
+    assert_eq!(reopened.tenant("globex").unwrap().system().accounts.len(), 2);
+    assert_eq!(balance(&reopened, "globex"), Decimal::from(7));
+    drop(reopened);
+    let _ = std::fs::remove_dir_all(&directory);
+}
+
+fn replay_three_of_five<E: EventStorage<Event = BankCommand>>(mut storage: E) {
+    for amount in 1..=5 {
+        storage.append(&deposit("alice", amount)).unwrap();
+    }
+    storage.flush().unwrap();
+
+    let mut consumed = Vec::new();
+    let replayed = storage
+        .replay_n(3, &mut |event| {
+            consumed.push(event);
+            Ok(())
+        })
+        .unwrap();
+
+    assert_eq!(replayed, 3);
+    assert_eq!(consumed, vec![deposit("alice", 1), deposit("alice", 2), deposit("alice", 3)]);
+    assert_eq!(storage.replay_n(10, &mut |_| Ok(())).unwrap(), 5);
+}
+
+#[test]
+fn replay_n_stops_after_n_events() {
+    replay_three_of_five(MemoryEventStorage::new());
+    // Forwards `replay` only, so it runs the trait's default `replay_n`
+    replay_three_of_five(FaultyEventStorage::new(MemoryEventStorage::new()));
+
+    let test_file = std::env::temp_dir().join("test_replay_n_events.json");
+    let _ = std::fs::remove_file(&test_file);
+    replay_three_of_five(TextFileEventStorage::new(&test_file, BankJsonConverter::new()).unwrap());
+    let _ = std::fs::remove_file(&test_file);
+}
+
+// `BankCommand` split into standalone command types, dispatched through a `CommandRegistry`
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+struct OpenAccount {
+    id: String,
+    name: String,
+}
+
+impl Command for OpenAccount {
+    type System = Bank;
+
+    fn apply_to(&self, bank: &mut Bank) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        BankCommand::CreateAccount { id: self.id.as_str().into(), name: self.name.clone(), opening_balance: None }.apply_to(bank)
+    }
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+struct DepositFunds {
+    account_id: String,
+    amount: Decimal,
+}
+
+impl Command for DepositFunds {
+    type System = Bank;
+
+    fn apply_to(&self, bank: &mut Bank) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        BankCommand::Deposit { account_id: self.account_id.as_str().into(), amount: self.amount }.apply_to(bank)
+    }
+}
+
+fn bank_command_registry() -> CommandRegistry<Bank> {
+    CommandRegistry::new()
+        .register::<OpenAccount>("bank.open_account")
+        .unwrap()
+        .register::<DepositFunds>("bank.deposit")
+        .unwrap()
+}
+
+#[test]
+fn command_registry_rejects_name_collisions() {
+    let error = bank_command_registry().register::<DepositFunds>("bank.open_account").err().unwrap();
+
+    assert_eq!(error, DuplicateCommandName("bank.open_account".to_string()));
+}
+
+#[test]
+fn command_registry_executes_json_and_reports_unknown_or_invalid_commands() {
+    let registry = bank_command_registry();
+    let mut processor = MemImgProcessor::new_simple(Bank::new(), Box::new(MemoryEventStorage::<DynCommand<Bank>>::new())).unwrap();
+
+    registry.execute_json(&mut processor, "bank.open_account", serde_json::json!({"id": "alice", "name": "Alice"})).unwrap();
+    registry.execute_json(&mut processor, "bank.deposit", serde_json::json!({"account_id": "alice", "amount": "25"})).unwrap();
+
+    assert_eq!(processor.system().accounts["alice"].balance(), Decimal::from(25));
+    let logged = processor.event_storage.events();
+    assert_eq!(logged.iter().map(DynCommand::tag).collect::<Vec<_>>(), vec!["bank.open_account", "bank.deposit"]);
+    assert_eq!(logged[1].downcast_ref::<DepositFunds>().unwrap().amount, Decimal::from(25));
+
+    let unknown = registry.execute_json(&mut processor, "bank.withdraw", serde_json::json!({})).unwrap_err();
+    assert_eq!(unknown.code(), "UNKNOWN_COMMAND");
+    let invalid = registry.execute_json(&mut processor, "bank.deposit", serde_json::json!({"account_id": "alice"})).unwrap_err();
+    assert_eq!(invalid.code(), "INVALID_COMMAND_PAYLOAD");
+    let failed = registry.execute_json(&mut processor, "bank.deposit", serde_json::json!({"account_id": "bob", "amount": "1"})).unwrap_err();
+    assert_eq!(failed.code(), "COMMAND_FAILURE");
+    assert_eq!(processor.event_storage.events().len(), 2);
+}
+
+#[test]
+fn registered_commands_persist_with_their_tags_and_replay_through_the_registry() {
+    let test_file = std::env::temp_dir().join("test_command_registry_events.json");
+    let _ = std::fs::remove_file(&test_file);
+    let registry = bank_command_registry();
+
+    {
+        let storage = Box::new(TextFileEventStorage::new(&test_file, registry.clone()).unwrap());
+        let mut processor = MemImgProcessor::new_simple(Bank::new(), storage).unwrap();
+        registry.execute_json(&mut processor, "bank.open_account", serde_json::json!({"id": "alice", "name": "Alice"})).unwrap();
+        registry.execute_json(&mut processor, "bank.deposit", serde_json::json!({"account_id": "alice", "amount": "40"})).unwrap();
+    }
+    let log = std::fs::read_to_string(&test_file).unwrap();
+    assert_eq!(log.lines().next().unwrap(), r#"{"type":"bank.open_account","payload":{"id":"alice","name":"Alice"}}"#);
+
+    let storage = Box::new(TextFileEventStorage::new(&test_file, registry.clone()).unwrap());
+    let mut processor = MemImgProcessor::new_simple(Bank::new(), storage).unwrap();
+    assert_eq!(processor.system().accounts["alice"].balance(), Decimal::from(40));
+    registry.execute_json(&mut processor, "bank.deposit", serde_json::json!({"account_id": "alice", "amount": "2"})).unwrap();
+    drop(processor);
+
+    // A registry that no longer knows deposits fails strict replay and skips them leniently
+    let opening_only = CommandRegistry::new().register::<OpenAccount>("bank.open_account").unwrap();
+    let strict = Box::new(TextFileEventStorage::new(&test_file, opening_only.clone()).unwrap());
+    assert!(MemImgProcessor::new_simple(Bank::new(), strict).is_err());
+    let lenient = Box::new(TextFileEventStorage::new(&test_file, opening_only).unwrap().with_replay_policy(ReplayPolicy::Lenient));
+    let (processor, metrics) = MemImgProcessor::new(Bank::new(), lenient).unwrap();
+    assert_eq!((metrics.events_replayed, metrics.skip_count), (1, 2));
+    assert!(processor.warnings()[0].message.contains("Unknown command bank.deposit"));
+    assert_eq!(processor.system().accounts["alice"].balance(), Decimal::ZERO);
+
+    drop(processor);
+    let _ = std::fs::remove_file(&test_file);
+}
+
+fn counting_processor(log: &std::path::Path) -> SchemaFreeProcessor<BTreeMap<String, i64>> {
+    counting_processor_over(Box::new(TextFileEventStorage::json(log).unwrap()))
+}
+
+fn counting_processor_over(storage: BoxedEventStorage<serde_json::Value>) -> SchemaFreeProcessor<BTreeMap<String, i64>> {
+    let mut processor = SchemaFreeProcessor::new(BTreeMap::new(), storage);
+    processor
+        .register_handler("CustomEvent", |counts: &mut BTreeMap<String, i64>, event: &serde_json::Value| {
+            let key = event["key"].as_str().ok_or("CustomEvent needs a key")?;
+            *counts.entry(key.to_string()).or_default() += event["by"].as_i64().unwrap_or(1);
+            Ok(())
+        })
+        .unwrap();
+    processor
+}
+
+#[test]
+fn schema_free_processor_dispatches_logged_values_to_registered_handlers() {
+    let test_file = std::env::temp_dir().join("test_schema_free_events.json");
+    let _ = std::fs::remove_file(&test_file);
+    std::fs::write(&test_file, "{\"type\":\"CustomEvent\",\"key\":\"clicks\",\"by\":3}\n").unwrap();
+
+    let mut processor = counting_processor(&test_file);
+    assert!(matches!(
+        processor.register_handler("CustomEvent", |_: &mut BTreeMap<String, i64>, _: &serde_json::Value| Ok(())),
+        Err(DuplicateCommandName(name)) if name == "CustomEvent"
+    ));
+    assert_eq!(processor.replay().unwrap(), 1);
+    assert_eq!(processor.system()["clicks"], 3);
+
+    processor.execute(serde_json::json!({"type": "CustomEvent", "key": "clicks"})).unwrap();
+    assert!(matches!(processor.execute(serde_json::json!({"type": "Other"})), Err(MemImgError::UnknownCommand(name)) if name == "Other"));
+    assert!(matches!(processor.execute(serde_json::json!({"key": "clicks"})), Err(MemImgError::InvalidCommandPayload { .. })));
+    assert!(matches!(processor.execute(serde_json::json!({"type": "CustomEvent"})), Err(MemImgError::CommandFailure(_))));
+    assert_eq!((processor.system()["clicks"], processor.event_count()), (4, 2));
+    drop(processor);
+
+    let mut reopened = counting_processor(&test_file);
+    assert_eq!(reopened.replay().unwrap(), 2);
+    assert_eq!(reopened.system()["clicks"], 4);
+    drop(reopened);
+
+    // Without the handler the log cannot be replayed
+    let storage: BoxedEventStorage<serde_json::Value> = Box::new(TextFileEventStorage::json(&test_file).unwrap());
+    let mut unaware = SchemaFreeProcessor::new(BTreeMap::<String, i64>::new(), storage);
+    assert!(!unaware.handles("CustomEvent"));
+    assert!(matches!(unaware.replay(), Err(MemImgError::SystemFailure(_))));
+    assert!(unaware.system().is_empty());
+
+    drop(unaware);
+    let _ = std::fs::remove_file(&test_file);
+}
+
+#[test]
+fn schema_free_processor_commits_its_write_ahead_intents() {
+    let test_file = std::env::temp_dir().join("test_schema_free_intents.json");
+    let _ = std::fs::remove_file(&test_file);
+    let with_intents = || -> BoxedEventStorage<serde_json::Value> { Box::new(TextFileEventStorage::json(&test_file).unwrap().with_write_ahead_intents()) };
+
+    let mut processor = counting_processor_over(with_intents());
+    processor.execute(serde_json::json!({"type": "CustomEvent", "key": "clicks"})).unwrap();
+    processor.execute(serde_json::json!({"type": "CustomEvent", "key": "clicks"})).unwrap();
+    assert_eq!(processor.system()["clicks"], 2);
+    drop(processor);
+
+    let mut reopened = counting_processor_over(with_intents());
+    assert_eq!(reopened.replay().unwrap(), 2);
+    assert_eq!(reopened.system()["clicks"], 2);
+
+    drop(reopened);
+    let _ = std::fs::remove_file(&test_file);
+}
+
+// A state that deliberately does not implement `Clone`, like one built around an arena
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+struct Tally {
+    counts: BTreeMap<String, i64>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+enum TallyCommand {
+    Add { key: String, by: i64 },
+    // Changes the state, then fails, so only a rollback can undo the change
+    AddThenFail { key: String },
+}
+
+impl Command for TallyCommand {
+    type System = Tally;
+
+    fn apply_to(&self, tally: &mut Tally) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        match self {
+            TallyCommand::Add { key, by } => *tally.counts.entry(key.clone()).or_default() += by,
+            TallyCommand::AddThenFail { key } => {
+                *tally.counts.entry(key.clone()).or_default() += 1;
+                return Err("failed halfway".into());
+            }
+        }
+        Ok(())
+    }
+}
+
+impl rmemimg::memimg::JsonEvent for TallyCommand {}
+
+struct NoNegativeCounts;
+
+impl SystemValidator<Tally> for NoNegativeCounts {
+    fn validate(&self, tally: &Tally) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        if tally.counts.values().any(|count| *count < 0) {
+            return Err("negative count".into());
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn serialized_rollback_runs_a_state_that_cannot_be_cloned() {
+    let test_file = std::env::temp_dir().join("test_serialized_rollback.json");
+    let _ = std::fs::remove_file(&test_file);
+    let storage = Box::new(TextFileEventStorage::json(&test_file).unwrap());
+    let mut processor = MemImgProcessor::new_with_serialized_rollback(Tally::default(), storage)
+        .unwrap()
+        .with_validator(NoNegativeCounts);
+
+    processor.execute_command(TallyCommand::Add { key: "a".to_string(), by: 2 }).unwrap();
+    assert!(matches!(processor.execute_command(TallyCommand::AddThenFail { key: "a".to_string() }), Err(MemImgError::CommandFailure(_))));
+    assert!(matches!(processor.execute_command(TallyCommand::AddThenFail { key: "b".to_string() }), Err(MemImgError::CommandFailure(_))));
+    assert!(matches!(processor.execute_command(TallyCommand::Add { key: "a".to_string(), by: -5 }), Err(MemImgError::CommandFailure(_))));
+    assert_eq!(processor.system().counts, BTreeMap::from([("a".to_string(), 2)]));
+    assert_eq!((processor.event_version(), processor.statistics().total_commands_failed), (EventId(1), 3));
+    drop(processor);
+
+    let reopened: MemImgProcessor<Tally, TallyCommand, _, _> =
+        MemImgProcessor::new_with_serialized_rollback(Tally::default(), Box::new(TextFileEventStorage::json(&test_file).unwrap())).unwrap();
+    assert_eq!(reopened.system().counts, BTreeMap::from([("a".to_string(), 2)]));
+    drop(reopened);
+
+    // A command whose event cannot be logged is rolled back before the processor is poisoned
+    let storage = Box::new(FaultyEventStorage::new(MemoryEventStorage::new()).with_failing_appends(u64::MAX, ErrorKind::PermissionDenied));
+    let mut failing = MemImgProcessor::new_with_serialized_rollback(Tally::default(), storage).unwrap();
+    assert!(matches!(failing.execute_command(TallyCommand::Add { key: "a".to_string(), by: 1 }), Err(MemImgError::SystemFailure(_))));
+    assert!(failing.is_poisoned());
+    assert!(failing.system().counts.is_empty());
+
+    let _ = std::fs::remove_file(&test_file);
+}
+
+#[test]
+fn preview_replay_returns_the_state_without_keeping_the_log_open() {
+    let test_file = std::env::temp_dir().join("test_preview_replay.json");
+    let _ = std::fs::remove_file(&test_file);
+    {
+        let storage = Box::new(TextFileEventStorage::new(&test_file, BankJsonConverter::new()).unwrap());
+        let mut processor = MemImgProcessor::new_simple(Bank::new(), storage).unwrap();
+        processor.execute_command(BankCommand::CreateAccount { id: "alice".into(), name: "Alice".to_string(), opening_balance: None }).unwrap();
+        processor.execute_command(deposit("alice", 70)).unwrap();
+    }
+    let log = std::fs::read_to_string(&test_file).unwrap();
+
+    let storage = Box::new(TextFileEventStorage::new(&test_file, BankJsonConverter::new()).unwrap());
+    let bank = MemImgProcessor::preview_replay(Bank::new(), storage).unwrap();
+
+    assert_eq!(bank.accounts["alice"].balance(), Decimal::from(70));
+    assert_eq!(std::fs::read_to_string(&test_file).unwrap(), log);
+    #[cfg(target_os = "linux")]
+    {
+        let open_on_log = std::fs::read_dir("/proc/self/fd")
+            .unwrap()
+            .filter_map(|fd| std::fs::read_link(fd.unwrap().path()).ok())
+            .filter(|target| *target == test_file)
+            .count();
+        assert_eq!(open_on_log, 0);
+    }
+
+    let _ = std::fs::remove_file(&test_file);
+}
+
+#[test]
+fn fallback_storage_takes_over_when_primary_replay_fails() {
+    let mut logged = MemoryEventStorage::new();
+    logged.append(&BankCommand::CreateAccount { id: "alice".into(), name: "Alice".to_string(), opening_balance: None }).unwrap();
+    logged.append(&deposit("alice", 70)).unwrap();
+    let primary = Box::new(FaultyEventStorage::new(logged).with_failing_replay_at(2));
+
+    let mut processor = MemImgProcessor::new_with_fallback(Bank::new(), primary, Box::new(MemoryEventStorage::new())).unwrap();
+
+    assert_eq!(processor.storage_mode(), StorageMode::Fallback);
+    // Replay starts over from the fallback, so nothing the primary delivered before failing remains
+    assert!(processor.system().accounts.is_empty());
+    assert_eq!(processor.warnings().len(), 1);
+    assert_eq!(processor.warnings()[0].kind, WarningKind::StorageFallback);
+
+    processor.execute_command(BankCommand::CreateAccount { id: "bob".into(), name: "Bob".to_string(), opening_balance: None }).unwrap();
+    processor.execute_command(deposit("bob", 25)).unwrap();
+    assert_eq!(processor.system().accounts["bob"].balance(), Decimal::from(25));
+}
+
+#[test]
+fn fallback_storage_is_unused_while_primary_replays() {
+    let primary = Box::new(FaultyEventStorage::new(MemoryEventStorage::<BankCommand>::new()));
+    let processor = MemImgProcessor::new_with_fallback(Bank::new(), primary, Box::new(MemoryEventStorage::new())).unwrap();
+
+    assert_eq!(processor.storage_mode(), StorageMode::Primary);
+    assert!(processor.warnings().is_empty());
+}
+
+#[test]
+fn export_state_streams_json_and_reports_writer_failures() {
+    let mut processor = MemImgProcessor::new_simple(Bank::new(), Box::new(MemoryEventStorage::new())).unwrap();
+    processor.execute_command(BankCommand::CreateAccount { id: "alice".into(), name: "Alice".to_string(), opening_balance: None }).unwrap();
+    processor.execute_command(deposit("alice", 70)).unwrap();
+
+    let mut dump = Vec::new();
+    processor.export_state(&mut dump).unwrap();
+    let exported: Bank = serde_json::from_slice(&dump).unwrap();
+    assert_eq!(&exported, processor.system());
+    assert_eq!(dump.iter().filter(|&&byte| byte == b'\n').count(), 1);
+
+    let mut too_small = [0u8; 16];
+    let result = processor.export_state(&mut too_small[..]);
+    assert!(matches!(result, Err(MemImgError::SystemFailure(_))), "{:?}", result);
+}
+
+#[test]
+fn replay_failure_names_the_event_that_failed_to_apply() {
+    let mut storage = MemoryEventStorage::new();
+    storage.append(&BankCommand::CreateAccount { id: "alice".into(), name: "Alice".to_string(), opening_balance: None }).unwrap();
+    storage.append(&deposit("alice", 10)).unwrap();
+    storage.append(&BankCommand::Withdrawal { account_id: "alice".into(), amount: Decimal::from(25) }).unwrap();
+
+    let error = MemImgProcessor::new_simple(Bank::new(), Box::new(storage)).err().unwrap();
+
+    let failed = error.outcome().unwrap().failed_event.as_ref().unwrap();
+    assert_eq!(failed.index, 3);
+    assert!(failed.event.starts_with("Withdrawal {"), "{}", failed.event);
+    let message = error.to_string();
+    assert!(message.contains("at event 3 (Withdrawal {"), "{}", message);
+    assert!(message.contains("Insufficient funds"), "{}", message);
+}
+
+#[test]
+fn bootstraps_an_image_from_a_state_dump() {
+    let mut source = MemImgProcessor::new_simple(Bank::new(), Box::new(MemoryEventStorage::new())).unwrap();
+    for i in 0..1000 {
+        let id = format!("acc{:04}", i);
+        source.execute_command(BankCommand::CreateAccount { id: id.as_str().into(), name: format!("Holder {}", i), opening_balance: Some(Decimal::from(100 + i)) }).unwrap();
+        if i > 0 {
+            let previous = format!("acc{:04}", i - 1);
+            source.execute_command(BankCommand::Transfer { from_account_id: id.as_str().into(), to_account_id: previous.as_str().into(), amount: Decimal::new(2550, 2) }).unwrap();
+        }
+        if i % 7 == 0 {
+            source.execute_command(BankCommand::AddOwner { account_id: id.as_str().into(), owner: "Trustee".to_string() }).unwrap();
+        }
+    }
+    source.execute_command(BankCommand::CreateAccount { id: "gone".into(), name: "Gone".to_string(), opening_balance: None }).unwrap();
+    source.execute_command(BankCommand::CloseAccount { id: "gone".into() }).unwrap();
+    let mut dump = Vec::new();
+    source.export_state(&mut dump).unwrap();
+    let state: Bank = serde_json::from_slice(&dump).unwrap();
+
+    let log = std::env::temp_dir().join("test_bootstrap_from_state.json");
+    let _ = std::fs::remove_file(&log);
+    let storage = Box::new(TextFileEventStorage::new(&log, BankJsonConverter::new()).unwrap());
+    let processor = MemImgProcessor::bootstrap_from_state(state, storage, Bank::genesis_commands).unwrap();
+    assert_eq!(fingerprint(processor.system()).unwrap(), fingerprint(source.system()).unwrap());
+    drop(processor);
+
+    let restarted = MemImgProcessor::new_simple(Bank::new(), Box::new(TextFileEventStorage::new(&log, BankJsonConverter::new()).unwrap())).unwrap();
+    assert_eq!(fingerprint(restarted.system()).unwrap(), fingerprint(source.system()).unwrap());
+    assert_eq!(restarted.system(), source.system());
+
+    // The log is no longer empty, so a second bootstrap is refused and leaves it as it was
+    let events = restarted.event_version().as_u64();
+    drop(restarted);
+    let storage = Box::new(TextFileEventStorage::new(&log, BankJsonConverter::new()).unwrap());
+    let error = MemImgProcessor::bootstrap_from_state(source.system().clone(), storage, Bank::genesis_commands).err().unwrap();
+    assert!(error.to_string().contains(&format!("already holds {} events", events)), "{}", error);
+    assert_eq!(std::fs::read_to_string(&log).unwrap().lines().count() as u64, events);
+    let _ = std::fs::remove_file(&log);
+}
+
+#[test]
+fn bootstrap_refuses_a_state_its_genesis_events_do_not_reproduce() {
+    let mut state = Bank::new().with_auto_create_on_deposit();
+    state.accounts.insert("alice".into(), rmemimg::memimg::bank::Account::new("alice", "Alice".to_string()));
+
+    let storage = MemoryEventStorage::new();
+    let error = MemImgProcessor::bootstrap_from_state(state, Box::new(storage), Bank::genesis_commands).err().unwrap();
+    assert!(matches!(error, MemImgError::SystemFailure(_)));
+    assert!(error.to_string().contains("do not reproduce the state"), "{}", error);
+}
+
+#[test]
+fn only_one_storage_at_a_time_may_open_a_log() {
+    let log = std::env::temp_dir().join("test_concurrent_writers.json");
+    let _ = std::fs::remove_file(&log);
+    let barrier = std::sync::Arc::new(std::sync::Barrier::new(2));
+
+    let openers: Vec<_> = (0..2)
+        .map(|_| {
+            let (log, barrier) = (log.clone(), barrier.clone());
+            std::thread::spawn(move || {
+                barrier.wait();
+                let storage = TextFileEventStorage::<BankCommand, _>::new(&log, BankJsonConverter::new());
+                // Hold any lock taken until both threads have tried
+                barrier.wait();
+                storage
+            })
+        })
+        .collect();
+    let results: Vec<_> = openers.into_iter().map(|opener| opener.join().unwrap()).collect();
+
+    assert_eq!(results.iter().filter(|result| result.is_ok()).count(), 1);
+    let error = results.iter().find_map(|result| result.as_ref().err()).unwrap();
+    assert_eq!(error.downcast_ref::<StorageError>().unwrap().source.kind(), ErrorKind::WouldBlock);
+    assert!(error.to_string().contains("locked by another writer"), "{}", error);
+
+    // The caller may take charge of locking instead, and the lock goes with its holder
+    assert!(TextFileEventStorage::<BankCommand, _>::new_unlocked(&log, BankJsonConverter::new()).is_ok());
+    drop(results);
+    assert!(TextFileEventStorage::<BankCommand, _>::new(&log, BankJsonConverter::new()).is_ok());
+    let _ = std::fs::remove_file(&log);
+}
+
+#[test]
+fn lock_timeout_waits_for_the_holder_to_release_the_log() {
+    let log = std::env::temp_dir().join("test_lock_timeout.json");
+    let _ = std::fs::remove_file(&log);
+    let holder = TextFileEventStorage::<BankCommand, _>::new(&log, BankJsonConverter::new()).unwrap();
+
+    let error = TextFileEventStorage::<BankCommand, _>::new_with_lock_timeout(&log, BankJsonConverter::new(), std::time::Duration::from_millis(30)).err().unwrap();
+    assert!(error.to_string().contains("after 30ms"), "{}", error);
+
+    let releaser = std::thread::spawn(move || {
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        drop(holder);
+    });
+    assert!(TextFileEventStorage::<BankCommand, _>::new_with_lock_timeout(&log, BankJsonConverter::new(), std::time::Duration::from_secs(10)).is_ok());
+    releaser.join().unwrap();
+    let _ = std::fs::remove_file(&log);
+}
+
+#[test]
+fn builder_opens_a_read_only_lenient_log_beside_its_writer() {
+    let log = std::env::temp_dir().join("test_builder_read_only.json");
+    let _ = std::fs::remove_file(&log);
+    let mut writer = TextFileEventStorage::builder(&log, BankJsonConverter::new()).durability(Durability::EveryEvent).open().unwrap();
+    writer.append(&BankCommand::CreateAccount { id: "alice".into(), name: "Alice".to_string(), opening_balance: None }).unwrap();
+    writer.append(&deposit("alice", 10)).unwrap();
+    let contents = std::fs::read_to_string(&log).unwrap();
+    std::fs::write(&log, format!("{}not an event\n{{\"torn", contents)).unwrap();
+
+    // The writer holds the lock, yet a read-only storage opens and replays past the bad lines
+    let mut reader = TextFileEventStorage::<BankCommand, _>::builder(&log, BankJsonConverter::new())
+        .read_only()
+        .replay_policy(ReplayPolicy::Lenient)
+        .open()
+        .unwrap();
+    let mut events = 0;
+    reader.replay(&mut |_| { events += 1; Ok(()) }).unwrap();
+    assert_eq!(events, 2);
+    let warnings = reader.drain_warnings();
+    assert_eq!(warnings.iter().map(|warning| warning.kind).collect::<Vec<_>>(), vec![WarningKind::SkippedLine, WarningKind::SkippedLine]);
+
+    // It neither writes nor repairs the torn tail
+    let error = reader.append(&deposit("alice", 1)).err().unwrap();
+    assert_eq!(error.downcast_ref::<StorageError>().unwrap().source.kind(), ErrorKind::PermissionDenied);
+    assert!(error.to_string().contains("opened read-only"), "{}", error);
+    assert!(std::fs::read_to_string(&log).unwrap().ends_with("{\"torn"));
+
+    // Nor does it create a missing log
+    let missing = std::env::temp_dir().join("test_builder_read_only_missing.json");
+    let _ = std::fs::remove_file(&missing);
+    assert!(TextFileEventStorage::<BankCommand, _>::builder(&missing, BankJsonConverter::new()).read_only().open().is_err());
+    assert!(!missing.exists());
+    drop(writer);
+    let _ = std::fs::remove_file(&log);
+}
+
+static_assertions::assert_impl_all!(MemImgProcessor<Bank, BankCommand, TextFileEventStorage<BankCommand, BankJsonConverter>>: Send);
+static_assertions::assert_impl_all!(MemImgProcessor<Bank, BankCommand, MemoryEventStorage<BankCommand>>: Send);
+static_assertions::assert_impl_all!(MemImgProcessor<Bank, BankCommand, BoxedEventStorage<BankCommand>>: Send);
+
+// Compiles only if the processor is `Send` for every `Send` state and storage, whatever the command
+#[allow(dead_code)]
+fn processor_is_send_whenever_its_state_and_storage_are<S: Send, C: Command<System = S>, E: EventStorage<Event = C> + Send>() {
+    fn assert_send<T: Send>() {}
+    assert_send::<MemImgProcessor<S, C, E>>();
+}
+
+#[test]
+fn a_processor_keeps_executing_commands_after_moving_to_another_thread() {
+    let log = std::env::temp_dir().join("test_processor_on_thread.json");
+    let _ = std::fs::remove_file(&log);
+    let storage = TextFileEventStorage::new(&log, BankJsonConverter::new()).unwrap();
+    let mut processor = MemImgProcessor::new_simple(Bank::new(), Box::new(storage)).unwrap();
+    processor
+        .execute_command(BankCommand::CreateAccount { id: "alice".into(), name: "Alice".to_string(), opening_balance: None })
+        .unwrap();
+
+    let worker = std::thread::spawn(move || {
+        for _ in 0..3 {
+            processor.execute_command(BankCommand::Deposit { account_id: "alice".into(), amount: Decimal::from(10) }).unwrap();
+        }
+        processor
+    });
+    let processor = worker.join().unwrap();
+
+    assert_eq!(processor.execute_query(&GetBalance { account_id: "alice".into() }).unwrap(), Decimal::from(30));
+    assert_eq!(processor.statistics().system_event_count, 4);
+    drop(processor);
+    let reopened = MemImgProcessor::new_simple(Bank::new(), Box::new(TextFileEventStorage::new(&log, BankJsonConverter::new()).unwrap())).unwrap();
+    assert_eq!(reopened.execute_query(&GetBalance { account_id: "alice".into() }).unwrap(), Decimal::from(30));
+    let _ = std::fs::remove_file(&log);
+}
+
+/// A step on a running figure, at the instant it was made; the order of steps changes the result
+#[derive(Debug, Clone)]
+enum Step {
+    Add(chrono::DateTime<chrono::Utc>, i64),
+    Double(chrono::DateTime<chrono::Utc>),
+    Untimed(i64),
+}
+
+impl Command for Step {
+    type System = i64;
+
+    fn apply_to(&self, figure: &mut i64) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        match self {
+            Step::Add(_, amount) | Step::Untimed(amount) => *figure += amount,
+            Step::Double(_) => *figure *= 2,
+        }
+        Ok(())
+    }
+
+    fn timestamp(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        match self {
+            Step::Add(at, _) | Step::Double(at) => Some(*at),
+            Step::Untimed(_) => None,
+        }
+    }
+}
+
+#[test]
+fn merge_orders_same_instant_events_by_source_then_position() {
+    let noon = chrono::DateTime::parse_from_rfc3339("2024-01-01T12:00:00Z").unwrap().to_utc();
+    let later = noon + chrono::Duration::seconds(1);
+    let merged_state = || {
+        let mut sources = [
+            MemoryEventStorage::with_events(vec![Step::Add(noon, 3), Step::Add(later, 1)]),
+            MemoryEventStorage::with_events(vec![Step::Double(noon), Step::Untimed(10), Step::Add(noon, 5)]),
+        ];
+        let merged = merge_by_timestamp(&mut sources).unwrap();
+        MemImgProcessor::new_simple(0, Box::new(MemoryEventStorage::with_events(merged))).unwrap().system().to_owned()
+    };
+
+    // At noon: +3 (source 0), then x2, +10 (inheriting noon) and +5 in source 1's order; +1 last
+    let expected = ((3 * 2) + 10 + 5) + 1;
+    for _ in 0..20 {
+        assert_eq!(merged_state(), expected);
+    }
+}
+
+/// Wall clock a test sets back, as an NTP adjustment would
+struct MockClock(std::cell::Cell<chrono::DateTime<chrono::Utc>>);
+
+impl MockClock {
+    fn now(&self) -> chrono::DateTime<chrono::Utc> {
+        self.0.get()
+    }
+
+    fn shift(&self, seconds: i64) {
+        self.0.set(self.0.get() + chrono::Duration::seconds(seconds));
+    }
+}
+
+/// A meter reading, stamped with when it was taken
+#[derive(Debug, Clone, PartialEq)]
+struct Reading(chrono::DateTime<chrono::Utc>, i64);
+
+impl Command for Reading {
+    type System = i64;
+
+    fn apply_to(&self, total: &mut i64) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        *total += self.1;
+        Ok(())
+    }
+
+    fn timestamp(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        Some(self.0)
+    }
+
+    fn with_timestamp(&self, at: chrono::DateTime<chrono::Utc>) -> Option<Self> {
+        Some(Reading(at, self.1))
+    }
+}
+
+#[test]
+fn commands_stamped_before_the_latest_event_are_warned_about_or_clamped() {
+    let logged_after_regression = |policy: ClockRegressionPolicy| {
+        let clock = MockClock(std::cell::Cell::new(chrono::DateTime::parse_from_rfc3339("2024-01-01T12:00:00Z").unwrap().to_utc()));
+        let mut processor = MemImgProcessor::new_simple(0, Box::new(MemoryEventStorage::new())).unwrap().with_clock_regression_policy(policy);
+        processor.execute_command(Reading(clock.now(), 1)).unwrap();
+        clock.shift(60);
+        let latest = clock.now();
+        processor.execute_command(Reading(latest, 2)).unwrap();
+        clock.shift(-30);
+        processor.execute_command(Reading(clock.now(), 3)).unwrap();
+        assert_eq!(processor.system(), &6);
+        (latest, processor.event_storage.events()[2].clone(), processor.warnings().to_vec())
+    };
+
+    let (latest, logged, warnings) = logged_after_regression(ClockRegressionPolicy::Warn);
+    assert_eq!(logged.0, latest - chrono::Duration::seconds(30));
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0].kind, WarningKind::ClockRegression);
+    assert_eq!(warnings[0].index, 3);
+
+    let (latest, logged, warnings) = logged_after_regression(ClockRegressionPolicy::Clamp);
+    assert_eq!(logged, Reading(latest, 3));
+    assert!(warnings.is_empty());
+}
+
+thread_local! {
+    static PURSE_CLONES: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+}
+
+/// A balance that counts how often it is cloned
+#[derive(Debug, Default)]
+struct Purse(i64);
+
+impl Clone for Purse {
+    fn clone(&self) -> Self {
+        PURSE_CLONES.with(|clones| clones.set(clones.get() + 1));
+        Purse(self.0)
+    }
+}
+
+#[derive(Debug, Clone)]
+enum PurseCommand {
+    /// Checked cheaply against the balance
+    Spend(i64),
+    /// No cheap check: dry-run on a copy
+    Refill(i64),
+}
+
+impl Command for PurseCommand {
+    type System = Purse;
+
+    fn apply_to(&self, purse: &mut Purse) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        match self {
+            PurseCommand::Spend(amount) if *amount > purse.0 => return Err(format!("cannot spend {} of {}", amount, purse.0).into()),
+            PurseCommand::Spend(amount) => purse.0 -= amount,
+            PurseCommand::Refill(amount) if *amount < 0 => return Err("refills are positive".into()),
+            PurseCommand::Refill(amount) => purse.0 += amount,
+        }
+        Ok(())
+    }
+
+    fn check(&self, purse: &Purse) -> Option<Result<(), Box<dyn std::error::Error + Send + Sync>>> {
+        match self {
+            PurseCommand::Spend(amount) if *amount > purse.0 => Some(Err(format!("cannot spend {} of {}", amount, purse.0).into())),
+            PurseCommand::Spend(_) => Some(Ok(())),
+            PurseCommand::Refill(_) => None,
+        }
+    }
+}
+
+#[test]
+fn can_apply_checks_without_cloning_and_dry_runs_the_rest() {
+    let mut processor = MemImgProcessor::new_simple(Purse::default(), Box::new(MemoryEventStorage::new())).unwrap();
+    processor.execute_command(PurseCommand::Refill(10)).unwrap();
+    let clones = || PURSE_CLONES.with(std::cell::Cell::get);
+
+    let before = clones();
+    assert!(matches!(processor.can_apply(&PurseCommand::Spend(11)), Err(MemImgError::CommandFailure(_))));
+    assert!(processor.can_apply(&PurseCommand::Spend(10)).is_ok());
+    assert_eq!(clones(), before);
+
+    assert!(processor.can_apply(&PurseCommand::Refill(-1)).is_err());
+    assert!(processor.can_apply(&PurseCommand::Refill(1)).is_ok());
+    assert!(clones() > before);
+    assert_eq!(processor.system().0, 10);
+    assert_eq!(processor.event_version(), EventId(1));
+
+    // The bank checks withdrawals cheaply too
+    let mut bank = MemImgProcessor::new_simple(Bank::new(), Box::new(MemoryEventStorage::new())).unwrap();
+    bank.execute_command(BankCommand::CreateAccount { id: "alice".into(), name: "Alice".to_string(), opening_balance: Some(Decimal::from(5)) }).unwrap();
+    let error = bank.can_apply(&BankCommand::Withdrawal { account_id: "alice".into(), amount: Decimal::from(6) }).unwrap_err();
+    let domain = error.outcome().and_then(|outcome| outcome.source.downcast_ref::<BankError>());
+    assert_eq!(domain, Some(&BankError::InsufficientFunds { available: Decimal::from(5), requested: Decimal::from(6) }));
+    assert!(bank.can_apply(&deposit("bob", 1)).is_err());
+    assert!(bank.can_apply(&BankCommand::Transfer { from_account_id: "alice".into(), to_account_id: "alice".into(), amount: Decimal::from(5) }).is_ok());
+}
@@ -0,0 +1,67 @@
+#![cfg(all(feature = "metrics", feature = "http"))]
+
+// Metrics are process-wide, so this file holds the one test that drives them
+
+use axum::body::{to_bytes, Body};
+use axum::http::{Request, StatusCode};
+use rmemimg::memimg::bank::{Bank, BankCommand};
+use rmemimg::memimg::bank_http::router;
+use rmemimg::memimg::bank_storage::BankJsonConverter;
+use rmemimg::memimg::metrics::gather;
+use rmemimg::memimg::{EventStorage, MemImgProcessor, MemoryEventStorage, SnapshotFormat, TextFileEventStorage};
+use rust_decimal::Decimal;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tower::ServiceExt;
+
+/// The sample in `exposition` for `series`, such as `memimg_commands_total{outcome="success"}`
+fn sample(exposition: &str, series: &str) -> String {
+    exposition
+        .lines()
+        .find_map(|line| line.strip_prefix(series)?.strip_prefix(' '))
+        .unwrap_or_else(|| panic!("no {} in\n{}", series, exposition))
+        .to_string()
+}
+
+#[tokio::test]
+async fn exposes_processor_and_storage_metrics() {
+    let log = std::env::temp_dir().join("test_metrics.json");
+    let _ = std::fs::remove_file(&log);
+    let mut processor = MemImgProcessor::new_simple(Bank::new(), Box::new(TextFileEventStorage::new(&log, BankJsonConverter::new()).unwrap())).unwrap();
+    processor.execute_command(BankCommand::CreateAccount { id: "alice".into(), name: "Alice".to_string(), opening_balance: Some(Decimal::from(10)) }).unwrap();
+    processor.execute_command(BankCommand::Deposit { account_id: "alice".into(), amount: Decimal::from(5) }).unwrap();
+    assert!(processor.execute_command(BankCommand::Withdrawal { account_id: "alice".into(), amount: Decimal::from(50) }).is_err());
+    processor.event_storage.flush().unwrap();
+    let log_bytes = std::fs::metadata(&log).unwrap().len();
+    drop(processor);
+
+    // Reopening replays both events, then compaction takes a snapshot
+    let mut processor = MemImgProcessor::new_simple(Bank::new(), Box::new(TextFileEventStorage::new(&log, BankJsonConverter::new()).unwrap())).unwrap();
+    processor.checkpoint_and_compact(&SnapshotFormat::new(1), std::io::sink()).unwrap();
+    drop(processor);
+    let _ = std::fs::remove_file(&log);
+
+    let exposition = gather();
+    assert!(exposition.contains("# TYPE memimg_commands_total counter"));
+    assert!(exposition.contains("# TYPE memimg_command_duration_seconds histogram"));
+    assert_eq!(sample(&exposition, r#"memimg_commands_total{outcome="success"}"#), "2");
+    assert_eq!(sample(&exposition, r#"memimg_commands_total{outcome="failure"}"#), "1");
+    assert_eq!(sample(&exposition, "memimg_command_duration_seconds_count"), "3");
+    assert_eq!(sample(&exposition, r#"memimg_command_duration_seconds_bucket{le="+Inf"}"#), "3");
+    assert_eq!(sample(&exposition, "memimg_replay_events_total"), "2");
+    assert_eq!(sample(&exposition, "memimg_replay_duration_seconds_count"), "2");
+    assert_eq!(sample(&exposition, "memimg_storage_append_bytes_total"), log_bytes.to_string());
+    assert_eq!(sample(&exposition, "memimg_storage_flushes_total"), "1");
+    assert_eq!(sample(&exposition, "memimg_snapshot_duration_seconds_count"), "1");
+    assert_eq!(sample(&exposition, "memimg_event_log_size"), "2");
+    assert_eq!(sample(&exposition, "memimg_poisoned"), "0");
+
+    // The HTTP router serves the same exposition
+    let processor = MemImgProcessor::new_simple(Bank::new(), Box::new(MemoryEventStorage::new())).unwrap();
+    let app = router(Arc::new(Mutex::new(processor)));
+    let response = app.oneshot(Request::get("/metrics").body(Body::empty()).unwrap()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = String::from_utf8(to_bytes(response.into_body(), usize::MAX).await.unwrap().to_vec()).unwrap();
+    assert_eq!(sample(&body, r#"memimg_commands_total{outcome="success"}"#), "2");
+    assert_eq!(sample(&body, "memimg_replay_duration_seconds_count"), "3");
+}
@@ -0,0 +1,187 @@
+#![cfg(feature = "test-util")]
+
+use proptest::collection::vec;
+use proptest::prelude::*;
+use rmemimg::memimg::bank::{AccountId, Amount, Bank, BankCommand, LedgerEntry, PaymentMethod};
+use rmemimg::memimg::bank_storage::BankJsonConverter;
+use rmemimg::memimg::testing::bank_commands_strategy;
+use rmemimg::memimg::{EventStorage, MemImgProcessor, MemoryEventStorage, ReplayPolicy, TextConverter, TextFileEventStorage};
+use rust_decimal::Decimal;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+prop_compose! {
+    /// Any decimal `Decimal` can hold: a 96-bit mantissa of either sign at scale 0 to 28
+    fn decimal()(lo in any::<u32>(), mid in any::<u32>(), hi in any::<u32>(), negative in any::<bool>(), scale in 0u32..=28) -> Amount {
+        Decimal::from_parts(lo, mid, hi, negative, scale)
+    }
+}
+
+prop_compose! {
+    /// Any string, valid as an account id or not: ids are only checked when accounts are created
+    fn account_id()(id in any::<String>()) -> AccountId {
+        AccountId::from(id)
+    }
+}
+
+prop_compose! {
+    fn ledger_entry()(account_id in account_id(), name in any::<String>(), balance in decimal()) -> LedgerEntry {
+        LedgerEntry { account_id, name, balance }
+    }
+}
+
+fn payment_method() -> impl Strategy<Value = PaymentMethod> {
+    prop_oneof![
+        Just(PaymentMethod::CreditCard),
+        Just(PaymentMethod::WireTransfer),
+        Just(PaymentMethod::ACH),
+        Just(PaymentMethod::Crypto),
+    ]
+}
+
+/// Every variant, with arbitrary strings and decimals in every field
+fn any_command() -> impl Strategy<Value = BankCommand> {
+    prop_oneof![
+        (account_id(), any::<String>(), proptest::option::of(decimal()))
+            .prop_map(|(id, name, opening_balance)| BankCommand::CreateAccount { id, name, opening_balance }),
+        (account_id(), decimal()).prop_map(|(account_id, amount)| BankCommand::Deposit { account_id, amount }),
+        (account_id(), decimal()).prop_map(|(account_id, amount)| BankCommand::Withdrawal { account_id, amount }),
+        (account_id(), account_id(), decimal())
+            .prop_map(|(from_account_id, to_account_id, amount)| BankCommand::Transfer { from_account_id, to_account_id, amount }),
+        vec((account_id(), any::<String>()), 0..4).prop_map(|accounts| BankCommand::BulkCreateAccounts { accounts }),
+        account_id().prop_map(|id| BankCommand::CloseAccount { id }),
+        (account_id(), any::<String>()).prop_map(|(account_id, owner)| BankCommand::AddOwner { account_id, owner }),
+        (account_id(), any::<String>()).prop_map(|(account_id, owner)| BankCommand::RemoveOwner { account_id, owner }),
+        (account_id(), account_id(), proptest::option::of(decimal()))
+            .prop_map(|(from_account_id, to_account_id, amount)| BankCommand::Sweep { from_account_id, to_account_id, amount }),
+        vec(ledger_entry(), 0..4).prop_map(|entries| BankCommand::ImportLedger { entries }),
+        (account_id(), decimal(), any::<String>(), any::<String>(), payment_method()).prop_map(
+            |(account_id, amount, gateway, gateway_transaction_id, payment_method)| BankCommand::RecordExternalPayment {
+                account_id,
+                amount,
+                gateway,
+                gateway_transaction_id,
+                payment_method,
+            }
+        ),
+    ]
+}
+
+static LOGS: AtomicU64 = AtomicU64::new(0);
+
+/// Every converter a bank log can be written in
+fn converters() -> Vec<Box<dyn TextConverter<BankCommand>>> {
+    vec![
+        Box::new(BankJsonConverter::new()),
+        Box::new(BankJsonConverter::internally_tagged("type")),
+        Box::new(BankJsonConverter::adjacently_tagged("type", "data")),
+    ]
+}
+
+/// What the parse fuzz targets check: `text` fails to parse or round-trips through `converter`
+fn assert_parses_or_errs(converter: &dyn TextConverter<BankCommand>, text: &str) {
+    if let Ok(command) = converter.parse(text) {
+        let formatted = converter.format(&command).unwrap();
+        assert_eq!(converter.parse(&formatted).unwrap(), command);
+    }
+}
+
+/// Checked-in fuzz regression seeds for `target`, as `(file name, bytes)`
+fn fuzz_seeds(target: &str) -> Vec<(String, Vec<u8>)> {
+    let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("fuzz/corpus").join(target);
+    let mut seeds: Vec<_> = std::fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.file_name().unwrap().to_string_lossy().starts_with("regression-"))
+        .map(|path| (path.file_name().unwrap().to_string_lossy().to_string(), std::fs::read(&path).unwrap()))
+        .collect();
+    seeds.sort();
+    seeds
+}
+
+/// Path of a fresh event log for one test case
+fn fresh_log() -> std::path::PathBuf {
+    let path = std::env::temp_dir().join(format!("proptest_bank_{}_{}.json", std::process::id(), LOGS.fetch_add(1, Ordering::Relaxed)));
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+proptest! {
+    #[test]
+    fn json_converter_round_trips_every_command(command in any_command()) {
+        let text = BankJsonConverter::new().format(&command).unwrap();
+
+        prop_assert!(!text.contains('\n'));
+        prop_assert_eq!(BankJsonConverter::new().parse(&text).unwrap(), command);
+    }
+
+    #[test]
+    fn tagged_converters_round_trip_every_command(command in any_command()) {
+        for converter in [BankJsonConverter::internally_tagged("type"), BankJsonConverter::adjacently_tagged("type", "data")] {
+            let text = converter.format(&command).unwrap();
+            prop_assert_eq!(converter.parse(&text).unwrap(), command.clone());
+        }
+    }
+}
+
+proptest! {
+    #[test]
+    fn converters_reject_arbitrary_text_without_panicking(text in ".*") {
+        for converter in converters() {
+            assert_parses_or_errs(converter.as_ref(), &text);
+        }
+    }
+}
+
+#[test]
+fn fuzz_regression_seeds_parse_or_fail_cleanly() {
+    let seeds: Vec<_> = fuzz_seeds("parse_bank_command").into_iter().chain(fuzz_seeds("parse_tagged_bank_command")).collect();
+    assert!(!seeds.is_empty());
+    for (name, bytes) in seeds {
+        let text = String::from_utf8_lossy(&bytes);
+        for converter in converters() {
+            let outcome = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| assert_parses_or_errs(converter.as_ref(), &text)));
+            assert!(outcome.is_ok(), "seed {} panicked", name);
+        }
+    }
+}
+
+#[test]
+fn fuzz_regression_logs_replay_or_fail_cleanly() {
+    for (name, bytes) in fuzz_seeds("replay_bank_log") {
+        // The first byte picks the file kind and replay policy, as in the replay_bank_log target
+        let (mode, contents) = bytes.split_first().unwrap();
+        let log = fresh_log().with_extension(if mode & 1 == 0 { "json" } else { "json.gz" });
+        std::fs::write(&log, contents).unwrap();
+        let policy = match (mode >> 1) & 3 {
+            0 => ReplayPolicy::Strict,
+            1 => ReplayPolicy::Lenient,
+            _ => ReplayPolicy::Budgeted { error_budget: usize::from(mode >> 3) },
+        };
+        let outcome = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let mut storage = TextFileEventStorage::new_unlocked(&log, BankJsonConverter::new()).unwrap().with_replay_policy(policy);
+            let _ = storage.replay(&mut |_event: BankCommand| Ok(()));
+        }));
+        let _ = std::fs::remove_file(&log);
+        assert!(outcome.is_ok(), "seed {} panicked", name);
+    }
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    #[test]
+    fn file_storage_reloads_the_bank_memory_storage_keeps(commands in bank_commands_strategy(30)) {
+        let log = fresh_log();
+        let mut in_memory = MemImgProcessor::new_simple(Bank::new(), Box::new(MemoryEventStorage::new())).unwrap();
+        let mut on_file = MemImgProcessor::new_simple(Bank::new(), Box::new(TextFileEventStorage::new(&log, BankJsonConverter::new()).unwrap())).unwrap();
+        for command in commands {
+            let _ = in_memory.execute_command(command.clone());
+            let _ = on_file.execute_command(command);
+        }
+        drop(on_file);
+
+        let reloaded = MemImgProcessor::new_simple(Bank::new(), Box::new(TextFileEventStorage::new(&log, BankJsonConverter::new()).unwrap())).unwrap();
+        let _ = std::fs::remove_file(&log);
+        prop_assert_eq!(reloaded.system(), in_memory.system());
+    }
+}
@@ -0,0 +1,109 @@
+import json
+from decimal import Decimal
+
+import pytest
+
+import rmemimg
+
+
+def make_processor():
+    processor = rmemimg.MemImgProcessor(rmemimg.Bank())
+    processor.create_account("alice", "Alice")
+    processor.create_account("bob", "Bob")
+    return processor
+
+
+def test_commands_update_balances():
+    processor = make_processor()
+    processor.deposit("alice", "$1,000")
+    processor.withdraw("alice", 100)
+    version = processor.transfer("alice", "bob", Decimal("250.50"))
+
+    assert version == 5
+    assert processor.event_version == 5
+    assert processor.get_balance("alice") == Decimal("649.50")
+    assert processor.get_balance("bob") == Decimal("250.50")
+    assert processor.bank().account_ids() == ["alice", "bob"]
+
+
+def test_domain_errors_raise_bank_error_with_code():
+    processor = make_processor()
+
+    with pytest.raises(rmemimg.BankError) as error:
+        processor.withdraw("alice", 5)
+    assert error.value.args[0] == "INSUFFICIENT_FUNDS"
+
+    with pytest.raises(rmemimg.BankError) as error:
+        processor.get_balance("carol")
+    assert error.value.args[0] == "ACCOUNT_NOT_FOUND"
+
+    with pytest.raises(rmemimg.BankError) as error:
+        processor.deposit("alice", "lots")
+    assert error.value.args[0] == "INVALID_AMOUNT"
+
+
+def test_failed_commands_leave_state_unchanged():
+    processor = make_processor()
+    processor.deposit("alice", 10)
+
+    with pytest.raises(rmemimg.BankError):
+        processor.transfer("alice", "bob", 50)
+
+    assert processor.get_balance("alice") == Decimal(10)
+    assert processor.get_balance("bob") == Decimal(0)
+    assert processor.event_version == 3
+
+
+def open_bank_log(path):
+    processor = rmemimg.BankProcessor(str(path))
+    processor.execute({"CreateAccount": {"id": "alice", "name": "Alice", "opening_balance": Decimal("100.25")}})
+    processor.execute({"CreateAccount": {"id": "bob", "name": "Bob"}})
+    processor.execute({"Transfer": {"from_account_id": "alice", "to_account_id": "bob", "amount": Decimal("40")}})
+    return processor
+
+
+def test_bank_processor_replays_its_log(tmp_path):
+    log = tmp_path / "bank_events.json"
+    assert open_bank_log(log).event_version == 3
+
+    processor = rmemimg.BankProcessor(str(log))
+
+    assert processor.event_version == 3
+    assert processor.balance("alice") == Decimal("60.25")
+    assert processor.accounts() == [
+        {"id": "alice", "name": "Alice", "owners": ["Alice"], "balance": Decimal("60.25")},
+        {"id": "bob", "name": "Bob", "owners": ["Bob"], "balance": Decimal("40")},
+    ]
+
+
+def test_bank_processor_history_lists_commands_touching_an_account(tmp_path):
+    processor = open_bank_log(tmp_path / "bank_events.json")
+
+    history = processor.history("bob")
+
+    assert [seq for seq, _ in history] == [2, 3]
+    assert history[1][1] == {"Transfer": {"from_account_id": "alice", "to_account_id": "bob", "amount": Decimal("40")}}
+
+
+def test_bank_processor_writes_snapshots(tmp_path):
+    processor = open_bank_log(tmp_path / "bank_events.json")
+    snapshot = tmp_path / "snapshot.json"
+
+    processor.snapshot(str(snapshot))
+
+    document = json.loads(snapshot.read_text())
+    assert (document["version"], document["event_count"]) == (1, 3)
+    assert sorted(document["state"]["accounts"]) == ["alice", "bob"]
+
+
+def test_bank_processor_errors_carry_codes(tmp_path):
+    processor = open_bank_log(tmp_path / "bank_events.json")
+
+    with pytest.raises(rmemimg.BankError) as error:
+        processor.execute({"Withdrawal": {"account_id": "bob", "amount": Decimal("41")}})
+    assert error.value.args[0] == "INSUFFICIENT_FUNDS"
+
+    with pytest.raises(rmemimg.ProcessorError) as error:
+        processor.execute({"Teleport": {"account_id": "bob"}})
+    assert error.value.args[0] == "MALFORMED_INPUT"
+    assert processor.event_version == 3
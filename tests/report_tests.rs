@@ -0,0 +1,91 @@
+#![cfg(feature = "bank-example")]
+
+use rmemimg::memimg::bank::{Bank, BankCommand};
+use rmemimg::memimg::bank_storage::BankJsonConverter;
+use rmemimg::memimg::{FailureReport, MemImgProcessor, TextFileEventStorage};
+use rust_decimal::Decimal;
+use std::path::Path;
+
+fn assert_matches_golden(report: &FailureReport, golden: &str) {
+    let golden_path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/reports").join(golden);
+    let expected = std::fs::read_to_string(&golden_path).unwrap();
+    let expected = expected.replace("<version>", env!("CARGO_PKG_VERSION"));
+    assert_eq!(report.to_json().trim_end(), expected.trim_end());
+}
+
+#[test]
+fn replay_failure_report_matches_golden() {
+    let test_file = std::env::temp_dir().join("test_report_replay_failure.json");
+    std::fs::write(
+        &test_file,
+        concat!(
+            r#"{"CreateAccount":{"id":"acc1","name":"Alice"}}"#,
+            "\n",
+            r#"{"Deposit":{"account_id":"acc1","amount":"100"}}"#,
+            "\n",
+            r#"{"Deposit":{"account_id":"ghost","amount":"5"}}"#,
+            "\n",
+        ),
+    )
+    .unwrap();
+
+    let storage = Box::new(TextFileEventStorage::new(&test_file, BankJsonConverter::new()).unwrap());
+    let error = MemImgProcessor::new_simple(Bank::new(), storage).err().unwrap();
+
+    let report = error.to_report();
+    assert_eq!(report.replay.as_ref().unwrap().events_replayed, 2);
+    assert_matches_golden(&report, "replay_failure.json");
+
+    let _ = std::fs::remove_file(&test_file);
+}
+
+#[test]
+fn command_failure_report_matches_golden() {
+    let test_file = std::env::temp_dir().join("test_report_command_failure.json");
+    let _ = std::fs::remove_file(&test_file);
+
+    let storage = Box::new(TextFileEventStorage::new(&test_file, BankJsonConverter::new()).unwrap());
+    let mut processor = MemImgProcessor::new_simple(Bank::new(), storage).unwrap();
+    processor
+        .execute_command(BankCommand::CreateAccount {
+            id: "acc1".into(),
+            name: "Alice".to_string(),
+            opening_balance: None,
+        })
+        .unwrap();
+    let error = processor
+        .execute_command(BankCommand::Withdrawal {
+            account_id: "acc1".into(),
+            amount: Decimal::new(50, 0),
+        })
+        .err()
+        .unwrap();
+
+    let report = error.to_report();
+    assert!(report.replay.is_none());
+    assert_matches_golden(&report, "command_failure.json");
+
+    let report_file = std::env::temp_dir().join("test_report_command_failure.report.json");
+    error.write_report(&report_file).unwrap();
+    let written: FailureReport = serde_json::from_str(&std::fs::read_to_string(&report_file).unwrap()).unwrap();
+    assert_eq!(written, report);
+
+    let _ = std::fs::remove_file(&test_file);
+    let _ = std::fs::remove_file(&report_file);
+}
+
+#[test]
+fn storage_failure_report_names_storage_path() {
+    let test_dir = std::env::temp_dir().join("test_report_storage_dir.json");
+    let _ = std::fs::remove_file(&test_dir);
+    std::fs::create_dir_all(&test_dir).unwrap();
+
+    let storage = Box::new(TextFileEventStorage::new(&test_dir, BankJsonConverter::new()).unwrap());
+    let error = MemImgProcessor::new_simple(Bank::new(), storage).err().unwrap();
+
+    let report = error.to_report();
+    assert_eq!(report.storage_path.as_deref(), Some(test_dir.to_string_lossy().as_ref()));
+    assert!(report.frames.iter().any(|frame| frame.code == "STORAGE_IO"));
+
+    let _ = std::fs::remove_dir_all(&test_dir);
+}
@@ -0,0 +1,82 @@
+#![cfg(feature = "schemars")]
+
+use rmemimg::memimg::bank_schema::{command_schema, schemas, write_schemas, SCHEMA_ID_BASE};
+use serde_json::{json, Value};
+
+fn schema(file_name: &str) -> Value {
+    let (_, schema) = schemas().into_iter().find(|(name, _)| *name == file_name).unwrap();
+    serde_json::to_value(schema).unwrap()
+}
+
+#[test]
+fn bank_command_schema_accepts_logged_commands_and_rejects_malformed_ones() {
+    let validator = jsonschema::validator_for(&schema("BankCommand.schema.json")).unwrap();
+
+    assert!(validator.is_valid(&json!({"Deposit": {"account_id": "alice", "amount": "10.50"}})));
+    assert!(validator.is_valid(&json!({"CreateAccount": {"id": "bob", "name": "Bob"}})));
+    assert!(validator.is_valid(&json!({"Sweep": {"from_account_id": "alice", "to_account_id": "bob"}})));
+
+    assert!(!validator.is_valid(&json!({"Deposit": {"account_id": "alice", "amount": "ten"}})));
+    assert!(!validator.is_valid(&json!({"Deposit": {"amount": "10"}})));
+    assert!(!validator.is_valid(&json!({"Deposit": {"account_id": "", "amount": "10"}})));
+    assert!(!validator.is_valid(&json!({"Refund": {"account_id": "alice", "amount": "10"}})));
+    assert!(!validator.is_valid(&json!({"account_id": "alice", "amount": "10"})));
+}
+
+#[test]
+fn command_schema_names_every_variant_and_takes_amounts_as_strings() {
+    let schema = command_schema();
+    assert_eq!(schema, self::schema("BankCommand.schema.json"));
+
+    let mut variants: Vec<_> = schema["oneOf"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .flat_map(|variant| variant["required"].as_array().unwrap().iter().map(|name| name.as_str().unwrap()))
+        .collect();
+    variants.sort_unstable();
+    let mut expected = [
+        "AccrueInterest",
+        "AddOwner",
+        "ApplyOverdraftFee",
+        "BulkCreateAccounts",
+        "CloseAccount",
+        "CreateAccount",
+        "Deposit",
+        "ImportLedger",
+        "RecordExternalPayment",
+        "RemoveOwner",
+        "RestoreModificationSeqs",
+        "ScheduleInterest",
+        "Sweep",
+        "Transfer",
+        "Withdrawal",
+    ];
+    expected.sort_unstable();
+    assert_eq!(variants, expected);
+
+    // Amounts are decimal strings, as logged; the number form the deserializer also reads is allowed
+    let withdrawal = schema["oneOf"].as_array().unwrap().iter().find(|variant| variant["required"][0] == "Withdrawal").unwrap();
+    let amount = &withdrawal["properties"]["Withdrawal"]["properties"]["amount"];
+    assert!(amount["type"].as_array().unwrap().contains(&json!("string")), "{}", amount);
+    let validator = jsonschema::validator_for(&schema).unwrap();
+    assert!(validator.is_valid(&json!({"Withdrawal": {"account_id": "alice", "amount": "0.25"}})));
+    assert!(!validator.is_valid(&json!({"Withdrawal": {"account_id": "alice", "amount": "1/4"}})));
+}
+
+#[test]
+fn schemas_are_written_with_stable_ids() {
+    let dir = std::env::temp_dir().join("test_bank_schemas");
+    let _ = std::fs::remove_dir_all(&dir);
+
+    let written = write_schemas(&dir).unwrap();
+
+    assert_eq!(written.len(), schemas().len());
+    for path in written {
+        let schema: Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        let file_name = path.file_name().unwrap().to_str().unwrap();
+        assert_eq!(schema["$id"], format!("{}{}", SCHEMA_ID_BASE, file_name));
+    }
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,65 @@
+#![cfg(all(feature = "signals", feature = "bank-example"))]
+
+use rmemimg::memimg::bank::{Bank, BankCommand};
+use rmemimg::memimg::bank_pipe::run_pipe_until;
+use rmemimg::memimg::bank_storage::BankJsonConverter;
+use rmemimg::memimg::{MemImgProcessor, ShutdownGuard, TextFileEventStorage};
+use rust_decimal::Decimal;
+use std::cell::Cell;
+use std::io::Cursor;
+use std::sync::{Arc, Mutex};
+
+#[test]
+fn stop_request_wakes_waiters() {
+    let guard = ShutdownGuard::install().unwrap();
+    let stop = guard.stop_signal();
+    let waiter = std::thread::spawn(move || stop.wait());
+
+    assert!(!guard.should_stop());
+    guard.handle_signal();
+    assert!(guard.should_stop());
+    waiter.join().unwrap();
+}
+
+#[test]
+fn close_flushes_the_log_and_reports_appended_events() {
+    let log = std::env::temp_dir().join("test_shutdown_close.json");
+    let _ = std::fs::remove_file(&log);
+    let lines = Arc::new(Mutex::new(Vec::new()));
+    let logged = lines.clone();
+    let guard = ShutdownGuard::install().unwrap().with_log(move |line| logged.lock().unwrap().push(line.to_string()));
+
+    let mut processor = MemImgProcessor::new_simple(Bank::new(), Box::new(TextFileEventStorage::new(&log, BankJsonConverter::new()).unwrap())).unwrap();
+    processor.execute_command(BankCommand::CreateAccount { id: "alice".into(), name: "Alice".to_string(), opening_balance: Some(Decimal::from(10)) }).unwrap();
+    processor.execute_command(BankCommand::Deposit { account_id: "alice".into(), amount: Decimal::from(5) }).unwrap();
+    assert_eq!(guard.close(processor).unwrap(), 2);
+    assert_eq!(*lines.lock().unwrap(), vec!["shutdown: flushed and closed the event log; the 2 events appended since it opened are on disk".to_string()]);
+
+    // The lock is released and both events are on disk
+    let processor = MemImgProcessor::new_simple(Bank::new(), Box::new(TextFileEventStorage::new(&log, BankJsonConverter::new()).unwrap())).unwrap();
+    assert_eq!(processor.system().accounts["alice"].balance(), Decimal::from(15));
+    drop(processor);
+    let _ = std::fs::remove_file(&log);
+}
+
+#[test]
+fn pipe_stops_before_reading_another_line() {
+    let log = std::env::temp_dir().join("test_shutdown_pipe.json");
+    let _ = std::fs::remove_file(&log);
+    let mut processor = MemImgProcessor::new_simple(Bank::new(), Box::new(TextFileEventStorage::new(&log, BankJsonConverter::new()).unwrap())).unwrap();
+    let input = r#"{"CreateAccount":{"id":"alice","name":"Alice"}}
+{"Deposit":{"account_id":"alice","amount":"100"}}
+{"Deposit":{"account_id":"alice","amount":"50"}}
+"#;
+    let checks = Cell::new(0);
+    let mut output = Vec::new();
+    run_pipe_until(&mut processor, Cursor::new(input), &mut output, || {
+        checks.set(checks.get() + 1);
+        checks.get() > 2
+    })
+    .unwrap();
+
+    assert_eq!(String::from_utf8(output).unwrap().lines().count(), 2);
+    assert_eq!(processor.close().unwrap(), 2);
+    let _ = std::fs::remove_file(&log);
+}
@@ -0,0 +1,148 @@
+#![cfg(feature = "bank-example")]
+
+use rmemimg::memimg::bank::{Bank, BankCommand};
+use rmemimg::memimg::{MemImgError, MemImgProcessor, MemoryEventStorage, Snapshot, SnapshotError, SnapshotFormat, SnapshotMismatchPolicy, WarningKind};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+// Version 2 of an account shape: `currency` did not exist in version 1
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct AccountV2 {
+    id: String,
+    name: String,
+    balance: Decimal,
+    currency: String,
+}
+
+fn v2_format() -> SnapshotFormat {
+    SnapshotFormat::new(2).with_migration(1, |mut state| {
+        for account in state.as_array_mut().into_iter().flatten() {
+            account["currency"] = serde_json::Value::from("USD");
+        }
+        Ok(state)
+    })
+}
+
+#[test]
+fn upgrades_v1_snapshot_on_load() {
+    let v1 = r#"{"version":1,"event_count":3,"state":[{"id":"acc1","name":"Alice","balance":"100"}]}"#;
+
+    let snapshot = v2_format().read::<Vec<AccountV2>, _>(v1.as_bytes()).unwrap();
+
+    assert_eq!(snapshot.version, 2);
+    assert_eq!(snapshot.event_count, 3);
+    assert_eq!(
+        snapshot.state,
+        vec![AccountV2 {
+            id: "acc1".into(),
+            name: "Alice".to_string(),
+            balance: Decimal::new(100, 0),
+            currency: "USD".to_string(),
+        }]
+    );
+}
+
+#[test]
+fn round_trips_current_version() {
+    let state = vec![AccountV2 {
+        id: "acc1".into(),
+        name: "Alice".to_string(),
+        balance: Decimal::new(2550, 2),
+        currency: "EUR".to_string(),
+    }];
+
+    let mut buffer = Vec::new();
+    v2_format().write(&mut buffer, &state, 7).unwrap();
+    let snapshot = v2_format().read::<Vec<AccountV2>, _>(buffer.as_slice()).unwrap();
+
+    assert_eq!(snapshot.version, 2);
+    assert_eq!(snapshot.event_count, 7);
+    assert_eq!(snapshot.state, state);
+}
+
+#[test]
+fn rejects_future_snapshot_version() {
+    let v3 = r#"{"version":3,"event_count":0,"state":[]}"#;
+
+    let error = v2_format().read::<Vec<AccountV2>, _>(v3.as_bytes()).err().unwrap();
+
+    assert!(matches!(
+        error.downcast_ref::<SnapshotError>(),
+        Some(SnapshotError::UnsupportedVersion { found: 3, supported: 2 })
+    ));
+}
+
+#[test]
+fn rejects_snapshot_without_migration_path() {
+    let v1 = r#"{"version":1,"event_count":0,"state":[]}"#;
+
+    let error = SnapshotFormat::new(2).read::<Vec<AccountV2>, _>(v1.as_bytes()).err().unwrap();
+
+    assert!(matches!(
+        error.downcast_ref::<SnapshotError>(),
+        Some(SnapshotError::MissingMigration { from_version: 1 })
+    ));
+}
+
+#[test]
+fn tampered_snapshot_fails_verification_against_the_log() {
+    let mut processor = MemImgProcessor::new_simple(Bank::new(), Box::new(MemoryEventStorage::new())).unwrap();
+    processor.execute_command(BankCommand::CreateAccount { id: "acc1".into(), name: "Alice".to_string(), opening_balance: None }).unwrap();
+    processor.execute_command(BankCommand::Deposit { account_id: "acc1".into(), amount: Decimal::new(50, 0) }).unwrap();
+    let mut written = Vec::new();
+    let format = SnapshotFormat::new(1);
+    format.write(&mut written, processor.system(), processor.event_version().as_u64()).unwrap();
+    processor.execute_command(BankCommand::Deposit { account_id: "acc1".into(), amount: Decimal::new(1, 0) }).unwrap();
+
+    let snapshot = format.read::<Bank, _>(written.as_slice()).unwrap();
+    snapshot.verify_against(Bank::new(), &mut *processor.event_storage).unwrap();
+
+    let tampered = String::from_utf8(written).unwrap().replace(r#""50""#, r#""500""#);
+    let tampered = format.read::<Bank, _>(tampered.as_bytes()).unwrap();
+    let error = tampered.verify_against(Bank::new(), &mut *processor.event_storage).unwrap_err();
+    assert!(matches!(error.downcast_ref::<SnapshotError>(), Some(SnapshotError::StateMismatch { event_count: 2 })));
+
+    let ahead = Snapshot { event_count: 5, ..snapshot };
+    let error = ahead.verify_against(Bank::new(), &mut *processor.event_storage).unwrap_err();
+    assert!(matches!(error.downcast_ref::<SnapshotError>(), Some(SnapshotError::LogTooShort { event_count: 5, available: 3 })));
+}
+
+#[test]
+fn snapshot_ahead_of_a_short_log_is_reconciled_per_policy() {
+    let events = vec![
+        BankCommand::CreateAccount { id: "acc1".into(), name: "Alice".to_string(), opening_balance: None },
+        BankCommand::Deposit { account_id: "acc1".into(), amount: Decimal::new(50, 0) },
+    ];
+    let replayed = MemImgProcessor::preview_replay(Bank::new(), Box::new(MemoryEventStorage::with_events(events.clone()))).unwrap();
+    let after_create = MemImgProcessor::preview_replay(Bank::new(), Box::new(MemoryEventStorage::with_events(events[..1].to_vec()))).unwrap();
+
+    let covered = Snapshot { version: 1, event_count: 1, state: after_create.clone() };
+    let processor = MemImgProcessor::new_with_snapshot(Bank::new(), covered, Box::new(MemoryEventStorage::with_events(events.clone())), SnapshotMismatchPolicy::default()).unwrap();
+    assert_eq!(processor.system(), &replayed);
+    assert_eq!(processor.event_version().as_u64(), 2);
+    assert!(processor.warnings().is_empty());
+
+    // A snapshot of three more deposits than the log still holds
+    let mut lost = events.clone();
+    lost.extend((0..3).map(|_| BankCommand::Deposit { account_id: "acc1".into(), amount: Decimal::new(25, 0) }));
+    let lost_state = MemImgProcessor::preview_replay(Bank::new(), Box::new(MemoryEventStorage::with_events(lost))).unwrap();
+    let ahead = Snapshot { version: 1, event_count: 5, state: lost_state.clone() };
+    let open = |policy| MemImgProcessor::new_with_snapshot(Bank::new(), ahead.clone(), Box::new(MemoryEventStorage::with_events(events.clone())), policy);
+
+    let processor = open(SnapshotMismatchPolicy::default()).unwrap();
+    assert_eq!(processor.system(), &replayed);
+    assert_eq!(processor.event_version().as_u64(), 2);
+    assert_eq!(processor.warnings()[0].kind, WarningKind::SnapshotMismatch);
+
+    let mut processor = open(SnapshotMismatchPolicy::TrustSnapshot).unwrap();
+    assert_eq!(processor.system(), &lost_state);
+    assert_eq!(processor.event_version().as_u64(), 2);
+    assert_eq!(processor.warnings()[0].kind, WarningKind::SnapshotMismatch);
+    let receipt = processor.execute_command(BankCommand::Deposit { account_id: "acc1".into(), amount: Decimal::new(1, 0) }).unwrap();
+    assert_eq!(receipt.seq, 3);
+
+    let Err(MemImgError::SystemFailure(outcome)) = open(SnapshotMismatchPolicy::Fail) else {
+        panic!("a snapshot ahead of its log should fail to open under SnapshotMismatchPolicy::Fail");
+    };
+    assert!(matches!(outcome.source.downcast_ref::<SnapshotError>(), Some(SnapshotError::LogTooShort { event_count: 5, available: 2 })));
+}
@@ -0,0 +1,21 @@
+use rmemimg::memimg::Command;
+
+#[derive(Debug, Clone, Default)]
+struct Counter(u64);
+
+impl Counter {
+    fn apply_increment(&mut self) -> Result<(), std::io::Error> {
+        self.0 += 1;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Command)]
+#[command(system = "Counter")]
+enum CounterCommand {
+    #[command(handler = "apply_increment")]
+    Increment,
+    Reset,
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use rmemimg::memimg::Command;
+
+#[derive(Debug, Command)]
+enum CounterCommand {
+    #[command(handler = "apply_increment")]
+    Increment,
+}
+
+fn main() {}
@@ -0,0 +1,20 @@
+use rmemimg::memimg::Command;
+
+#[derive(Debug, Clone, Default)]
+struct Counter(u64);
+
+impl Counter {
+    fn apply_add(&mut self, amount: u64) -> Result<(), std::io::Error> {
+        self.0 += amount;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Command)]
+#[command(system = "Counter")]
+enum CounterCommand {
+    #[command(handler = "apply_add")]
+    Add { amount: u64, note: String },
+}
+
+fn main() {}
@@ -0,0 +1,55 @@
+use rmemimg::memimg::Command;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Default)]
+struct Inventory {
+    stock: HashMap<String, u32>,
+    audits: u32,
+}
+
+#[derive(Debug)]
+struct InventoryError(String);
+
+impl std::fmt::Display for InventoryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for InventoryError {}
+
+impl Inventory {
+    fn apply_add_item(&mut self, sku: &str, qty: &u32) -> Result<(), InventoryError> {
+        *self.stock.entry(sku.to_string()).or_default() += qty;
+        Ok(())
+    }
+
+    fn apply_remove_item(&mut self, sku: &str) -> Result<(), InventoryError> {
+        self.stock.remove(sku).map(|_| ()).ok_or_else(|| InventoryError(format!("unknown sku {}", sku)))
+    }
+
+    fn apply_audit(&mut self) -> Result<(), InventoryError> {
+        self.audits += 1;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Command)]
+#[command(system = "Inventory")]
+enum InventoryCommand {
+    #[command(handler = "apply_add_item")]
+    AddItem { sku: String, qty: u32 },
+    #[command(handler = "apply_remove_item")]
+    RemoveItem(String),
+    #[command(handler = "apply_audit")]
+    Audit,
+}
+
+fn main() {
+    let mut inventory = Inventory::default();
+    InventoryCommand::AddItem { sku: "widget".to_string(), qty: 3 }.apply_to(&mut inventory).unwrap();
+    InventoryCommand::Audit.apply_to(&mut inventory).unwrap();
+    assert_eq!(inventory.stock["widget"], 3);
+    assert_eq!(inventory.audits, 1);
+    assert!(InventoryCommand::RemoveItem("gadget".to_string()).apply_to(&mut inventory).is_err());
+}
@@ -0,0 +1,162 @@
+#![cfg(feature = "inventory-example")]
+
+use rmemimg::memimg::warehouse::{GetLowStockItems, GetStockLevel, StockLevel, Warehouse, WarehouseCommand, WarehouseError};
+use rmemimg::memimg::testing::assert_replay_deterministic_by;
+use rmemimg::memimg::warehouse_storage::WarehouseJsonConverter;
+use rmemimg::memimg::{Command, MemImgError, MemImgProcessor, MemoryEventStorage, Query, TextConverter, TextFileEventStorage};
+
+fn add_item(sku: &str) -> WarehouseCommand {
+    WarehouseCommand::AddItem { sku: sku.to_string(), name: sku.to_uppercase() }
+}
+
+fn receive(sku: &str, quantity: u32) -> WarehouseCommand {
+    WarehouseCommand::ReceiveStock { sku: sku.to_string(), quantity }
+}
+
+fn reserve(sku: &str, quantity: u32) -> WarehouseCommand {
+    WarehouseCommand::Reserve { sku: sku.to_string(), quantity }
+}
+
+fn stocked_warehouse() -> Warehouse {
+    let mut warehouse = Warehouse::new();
+    for command in [add_item("bolt"), add_item("nut"), receive("bolt", 10), receive("nut", 4), reserve("bolt", 6), reserve("nut", 2)] {
+        command.apply_to(&mut warehouse).unwrap();
+    }
+    warehouse
+}
+
+fn domain_error(error: MemImgError) -> WarehouseError {
+    error.outcome().unwrap().source.downcast_ref::<WarehouseError>().cloned().unwrap()
+}
+
+#[test]
+fn reserving_and_shipping_moves_stock() {
+    let mut warehouse = stocked_warehouse();
+
+    WarehouseCommand::Ship { lines: vec![("bolt".to_string(), 4)] }.apply_to(&mut warehouse).unwrap();
+
+    let level = GetStockLevel { sku: "bolt".to_string() };
+    assert_eq!(
+        level.extract_from(&warehouse).unwrap(),
+        StockLevel { on_hand: 6, reserved: 2, available: 4 }
+    );
+}
+
+#[test]
+fn rejects_reserving_more_than_available() {
+    let mut warehouse = stocked_warehouse();
+
+    let error = reserve("bolt", 5).apply_to(&mut warehouse).unwrap_err();
+
+    assert_eq!(
+        error.downcast_ref::<WarehouseError>(),
+        Some(&WarehouseError::InsufficientStock { sku: "bolt".to_string(), available: 4, requested: 5 })
+    );
+}
+
+#[test]
+fn rejects_releasing_more_than_reserved() {
+    let mut warehouse = stocked_warehouse();
+    WarehouseCommand::ReleaseReservation { sku: "nut".to_string(), quantity: 1 }.apply_to(&mut warehouse).unwrap();
+
+    let error = WarehouseCommand::ReleaseReservation { sku: "nut".to_string(), quantity: 2 }.apply_to(&mut warehouse).unwrap_err();
+
+    assert_eq!(
+        error.downcast_ref::<WarehouseError>(),
+        Some(&WarehouseError::NotReserved { sku: "nut".to_string(), reserved: 1, requested: 2 })
+    );
+}
+
+#[test]
+fn rejects_duplicate_unknown_and_zero_quantity() {
+    let mut warehouse = stocked_warehouse();
+
+    let error = |command: WarehouseCommand, warehouse: &mut Warehouse| {
+        command.apply_to(warehouse).unwrap_err().downcast_ref::<WarehouseError>().cloned().unwrap()
+    };
+    assert_eq!(error(add_item("bolt"), &mut warehouse), WarehouseError::DuplicateSku("bolt".to_string()));
+    assert_eq!(error(receive("washer", 1), &mut warehouse), WarehouseError::UnknownSku("washer".to_string()));
+    assert_eq!(error(receive("bolt", 0), &mut warehouse), WarehouseError::ZeroQuantity("bolt".to_string()));
+}
+
+#[test]
+fn failed_multi_line_shipment_rolls_back_earlier_lines() {
+    let mut processor = MemImgProcessor::new_simple(Warehouse::new(), Box::new(MemoryEventStorage::new())).unwrap();
+    for command in [add_item("bolt"), add_item("nut"), receive("bolt", 10), receive("nut", 4), reserve("bolt", 6), reserve("nut", 2)] {
+        processor.execute_command(command).unwrap();
+    }
+    let before = processor.system().clone();
+
+    // The bolt line ships before the nut line fails
+    let error = processor
+        .execute_command(WarehouseCommand::Ship { lines: vec![("bolt".to_string(), 6), ("nut".to_string(), 3)] })
+        .unwrap_err();
+
+    assert_eq!(domain_error(error), WarehouseError::NotReserved { sku: "nut".to_string(), reserved: 2, requested: 3 });
+    assert_eq!(processor.system(), &before);
+    assert_eq!(processor.event_storage.events().len(), 6);
+    assert_replay_deterministic_by(&mut processor, |warehouse| serde_json::to_string(warehouse).unwrap());
+}
+
+#[test]
+fn lists_low_stock_items_sorted_by_sku() {
+    let processor = MemImgProcessor::new_simple(stocked_warehouse(), Box::new(MemoryEventStorage::<WarehouseCommand>::new())).unwrap();
+
+    let low = processor.execute_query(&GetLowStockItems { threshold: 3 }).unwrap();
+
+    assert_eq!(low.iter().map(|item| item.sku.as_str()).collect::<Vec<_>>(), vec!["nut"]);
+    let all = processor.execute_query(&GetLowStockItems { threshold: 100 }).unwrap();
+    assert_eq!(all.iter().map(|item| item.sku.as_str()).collect::<Vec<_>>(), vec!["bolt", "nut"]);
+}
+
+#[test]
+fn unknown_sku_query_fails() {
+    let processor = MemImgProcessor::new_simple(stocked_warehouse(), Box::new(MemoryEventStorage::<WarehouseCommand>::new())).unwrap();
+
+    let error = processor.execute_query(&GetStockLevel { sku: "washer".to_string() }).unwrap_err();
+
+    assert_eq!(domain_error(error), WarehouseError::UnknownSku("washer".to_string()));
+}
+
+#[test]
+fn converter_round_trips_commands_on_one_line() {
+    let command = WarehouseCommand::Ship { lines: vec![("bolt".to_string(), 2), ("nut".to_string(), 1)] };
+
+    let text = WarehouseJsonConverter.format(&command).unwrap();
+
+    assert!(!text.contains('\n'));
+    assert_eq!(WarehouseJsonConverter.parse(&text).unwrap(), command);
+}
+
+#[test]
+fn warehouse_round_trips_through_json() {
+    let warehouse = stocked_warehouse();
+
+    let json = serde_json::to_string(&warehouse).unwrap();
+
+    assert_eq!(serde_json::from_str::<Warehouse>(&json).unwrap(), warehouse);
+}
+
+#[test]
+fn restores_warehouse_from_text_file_events() {
+    let test_file = std::env::temp_dir().join("test_warehouse_events.json");
+    let _ = std::fs::remove_file(&test_file);
+
+    {
+        let storage = Box::new(TextFileEventStorage::new(&test_file, WarehouseJsonConverter).unwrap());
+        let mut processor = MemImgProcessor::new_simple(Warehouse::new(), storage).unwrap();
+        for command in [add_item("bolt"), receive("bolt", 10), reserve("bolt", 3)] {
+            processor.execute_command(command).unwrap();
+        }
+        processor.execute_command(WarehouseCommand::Ship { lines: vec![("bolt".to_string(), 3)] }).unwrap();
+    }
+
+    let storage = Box::new(TextFileEventStorage::new(&test_file, WarehouseJsonConverter).unwrap());
+    let processor = MemImgProcessor::new_simple(Warehouse::new(), storage).unwrap();
+    assert_eq!(
+        processor.execute_query(&GetStockLevel { sku: "bolt".to_string() }).unwrap(),
+        StockLevel { on_hand: 7, reserved: 0, available: 7 }
+    );
+
+    let _ = std::fs::remove_file(&test_file);
+}